@@ -0,0 +1,22 @@
+// =====================================================================
+// File: build.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Generates the gRPC service from proto/kvstore.proto when the `grpc`
+//   feature is on. The proto is parsed with protox, so no `protoc` needs
+//   to be installed. Without the feature there is nothing to build.
+// =====================================================================
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/kvstore.proto");
+        let descriptors = protox::compile(["proto/kvstore.proto"], ["proto"]).expect("proto/kvstore.proto does not compile");
+        tonic_build::configure().compile_fds(descriptors).expect("cannot generate the gRPC service");
+    }
+}
@@ -0,0 +1,30 @@
+// =====================================================================
+// File: fuzz_targets/execute_line.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   cargo-fuzz target that feeds arbitrary bytes through the same path
+//   the REPL uses: each input is split into lines, and every line is
+//   parsed and handled by `execute_line` against one fresh session,
+//   with the per-command background tick in between.
+//
+//   Any panic is a bug. Run from the repository root with:
+//     KVSTORE_DATA_FILE=/tmp/kvstore_fuzz.db cargo +nightly fuzz run execute_line
+// =====================================================================
+#![no_main]
+
+use kvstore::{execute_line, CommandResult, Session};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut session = Session::new();
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        if let CommandResult::Exit = execute_line(line, &mut session) {
+            break;
+        }
+        let _ = session.tick();
+    }
+});
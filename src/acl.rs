@@ -0,0 +1,286 @@
+// =====================================================================
+// File: acl.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Access control lists: named users, the command categories each may
+//   run, and the keys each may touch.
+//
+//   Users come from a file named by `KVSTORE_ACL`, one per line:
+//
+//     user <name> <password> <categories> [<key pattern> ...]
+//
+//   `<categories>` is a comma-separated list of `read`, `write` and
+//   `admin` (or `all`). Key patterns use `*` (any run of characters) and
+//   `?` (any one character); with no patterns every key is allowed.
+//   Blank lines and `#` comments are ignored.
+//
+//   Once an ACL is loaded, a client runs as the `default` user if one is
+//   defined (and must `AUTH` otherwise) until `AUTH <name> <password>`
+//   switches users. Without an ACL every command is allowed, as before.
+// =====================================================================
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::Fs;
+
+/// Kinds of command a user may be granted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Commands that only read: `GET`, `MGET`, `EXISTS`, `TTL`, `RANGE`,
+    /// `SCANPREFIX`, `COUNT`, `DBSIZE`, `INFO`.
+    Read,
+
+    /// Commands that change keys: `SET`, `MSET`, `DEL`, `EXPIRE`,
+    /// `EXPIREAT`, `PEXPIREAT`, `PERSIST` and the transaction commands.
+    Write,
+
+    /// Maintenance commands: `COMPACT`, `DEBUGKEYS`, `REPLICATE` /
+    /// `REPLICAOF` (streaming the log to a replica, following a primary),
+    /// and `EXPORT` / `IMPORT` (moving the keyspace to and from files).
+    Admin,
+}
+
+
+impl Category {
+    /// Category of a (uppercase) command, or `None` for commands anyone
+    /// may run (`AUTH`, `EXIT`, empty and unknown input).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Category;
+    /// assert_eq!(Category::of("MGET"), Some(Category::Read));
+    /// assert_eq!(Category::of("COMMIT"), Some(Category::Write));
+    /// assert_eq!(Category::of("EXIT"), None);
+    /// ```
+    pub fn of(cmd: &str) -> Option<Category> {
+        match cmd {
+            "GET" | "MGET" | "EXISTS" | "TTL" | "RANGE" | "SCANPREFIX" | "COUNT" | "DBSIZE" | "INFO" | "STATS" => Some(Category::Read),
+            "SET" | "MSET" | "DEL" | "EXPIRE" | "EXPIREAT" | "PEXPIREAT" | "PERSIST" | "BEGIN" | "COMMIT" | "ABORT" => Some(Category::Write),
+            "COMPACT" | "SNAPSHOT" | "DEBUGKEYS" | "REPLICATE" | "REPLICAOF" | "EXPORT" | "IMPORT" | "BACKUP" => Some(Category::Admin),
+            _ => None,
+        }
+    }
+
+
+    /// Parse a category name as written in the ACL file.
+    pub fn from_name(name: &str) -> Option<Category> {
+        match name.to_ascii_lowercase().as_str() {
+            "read" => Some(Category::Read),
+            "write" => Some(Category::Write),
+            "admin" => Some(Category::Admin),
+            _ => None,
+        }
+    }
+
+
+    /// Lowercase name used in the ACL file and in errors.
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Read => "read",
+            Category::Write => "write",
+            Category::Admin => "admin",
+        }
+    }
+}
+
+
+/// One user from the ACL file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclUser {
+    /// Login name.
+    pub name: String,
+
+    /// Password checked by `AUTH` (stored as written in the file).
+    pub password: String,
+
+    /// Command categories the user may run.
+    pub categories: Vec<Category>,
+
+    /// Key patterns the user may touch; empty allows every key.
+    pub key_patterns: Vec<String>,
+}
+
+
+impl AclUser {
+    /// Returns `true` if the user may run commands of `category`.
+    pub fn allows(&self, category: Category) -> bool {
+        self.categories.contains(&category)
+    }
+
+
+    /// Returns `true` if the user may touch `key`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{AclUser, Category};
+    /// let user = AclUser {
+    ///     name: "analyst".into(),
+    ///     password: "pw".into(),
+    ///     categories: vec![Category::Read],
+    ///     key_patterns: vec!["report:*".into()],
+    /// };
+    /// assert!(user.allows_key("report:2026"));
+    /// assert!(!user.allows_key("salary:bob"));
+    /// ```
+    pub fn allows_key(&self, key: &str) -> bool {
+        self.key_patterns.is_empty() || self.key_patterns.iter().any(|p| glob_match(p, key))
+    }
+}
+
+
+/// Users and their permissions, keyed by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Acl {
+    /// Every user defined in the ACL file.
+    pub users: HashMap<String, AclUser>,
+}
+
+
+impl Acl {
+    /// Parse the contents of an ACL file.
+    ///
+    /// # Returns
+    /// * `Ok(Acl)` with every user defined.
+    /// * `Err(message)` naming the first bad line; nothing is loaded then,
+    ///   so a typo can't silently widen anyone's access.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Acl, Category};
+    /// let acl = Acl::parse("user admin s3cret all\nuser analyst pw read report:*\n").unwrap();
+    /// assert!(acl.users["admin"].allows(Category::Admin));
+    /// assert!(!acl.users["analyst"].allows(Category::Write));
+    /// assert!(Acl::parse("user broken").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Acl, String> {
+        let mut acl = Acl::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let user = parse_user(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            acl.users.insert(user.name.clone(), user);
+        }
+        Ok(acl)
+    }
+
+
+    /// Read and parse the ACL file at `path` through `fs`.
+    ///
+    /// # Returns
+    /// `Err` of kind `InvalidData` if the file doesn't parse.
+    pub fn load(fs: &dyn Fs, path: &str) -> io::Result<Acl> {
+        let bytes = fs.read(path)?;
+        Acl::parse(&String::from_utf8_lossy(&bytes)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+
+    /// The user `name` if `password` matches.
+    pub fn authenticate(&self, name: &str, password: &str) -> Option<&AclUser> {
+        self.users.get(name).filter(|u| u.password == password)
+    }
+}
+
+
+/// Parse one `user <name> <password> <categories> [patterns...]` line.
+fn parse_user(line: &str) -> Result<AclUser, String> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let ["user", name, password, categories, patterns @ ..] = parts.as_slice() else {
+        return Err("expected user <name> <password> <categories> [<key pattern> ...]".to_string());
+    };
+
+    let mut granted = Vec::new();
+    for cat in categories.split(',') {
+        if cat.eq_ignore_ascii_case("all") {
+            granted = vec![Category::Read, Category::Write, Category::Admin];
+            continue;
+        }
+        let cat = Category::from_name(cat).ok_or_else(|| format!("unknown category '{}'", cat))?;
+        if !granted.contains(&cat) {
+            granted.push(cat);
+        }
+    }
+
+    Ok(AclUser {
+        name: name.to_string(),
+        password: password.to_string(),
+        categories: granted,
+        key_patterns: patterns.iter().map(|p| p.to_string()).collect(),
+    })
+}
+
+
+/// Match `text` against a pattern where `*` is any run of characters and
+/// `?` is any single character.
+///
+/// # Example
+/// ```
+/// use kvstore::glob_match;
+/// assert!(glob_match("user:*", "user:42"));
+/// assert!(glob_match("k?y", "key"));
+/// assert!(!glob_match("user:*", "order:1"));
+/// ```
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    // Last `*` seen, and the text position it currently absorbs up to
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            // Let the star swallow one more character and retry
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+
+// =================================================================
+// acl.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod acl_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_users_and_categories() {
+        let text = "# team\nuser admin pw all\n\nuser ops pw read,admin\nuser analyst pw READ report:* stats:??\n";
+        let acl = Acl::parse(text).unwrap();
+        assert_eq!(acl.users.len(), 3);
+        assert_eq!(acl.users["ops"].categories, vec![Category::Read, Category::Admin]);
+        assert_eq!(acl.users["analyst"].key_patterns, vec!["report:*", "stats:??"]);
+
+        assert_eq!(Acl::parse("user a pw read\nuser b pw root\n").unwrap_err(), "line 2: unknown category 'root'");
+        assert!(acl.authenticate("ops", "pw").is_some());
+        assert!(acl.authenticate("ops", "nope").is_none());
+        assert!(acl.authenticate("ghost", "pw").is_none());
+    }
+
+    #[test]
+    fn test_glob_match_edge_cases() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(glob_match("a*", "a"));
+        assert!(!glob_match("a*c", "abcd"));
+        assert!(!glob_match("?", ""));
+        assert!(glob_match("日*", "日本"));
+    }
+}
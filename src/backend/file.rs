@@ -0,0 +1,190 @@
+// =====================================================================
+// File: backend/file.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! [`FileBackend`] keeps logs in real files.
+//!
+//! There is one per process ([`FileBackend::process`]): it holds the
+//! open [`LogWriter`] of every log appended to, so each file is opened
+//! once and grown in preallocated chunks however many sessions write to
+//! it. A handle whose append fails is dropped and reopened on the next
+//! append.
+// =====================================================================
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use super::StorageBackend;
+use crate::storage::{self, Layout, LogWriter};
+use crate::RealFs;
+
+/// The process's log files and their open append handles.
+#[derive(Debug)]
+pub struct FileBackend {
+    /// Open handles, keyed by path.
+    writers: Mutex<HashMap<String, LogWriter>>,
+}
+
+
+impl FileBackend {
+    /// The backend every append to a real file goes through.
+    pub fn process() -> &'static FileBackend {
+        static FILES: OnceLock<FileBackend> = OnceLock::new();
+        FILES.get_or_init(|| FileBackend { writers: Mutex::new(HashMap::new()) })
+    }
+
+
+    fn writers(&self) -> MutexGuard<'_, HashMap<String, LogWriter>> {
+        self.writers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+
+    /// Run `write` on the log's handle, opening it first if needed.
+    fn with_writer<T>(&self, path: &str, write: impl FnOnce(&mut LogWriter) -> io::Result<T>) -> io::Result<T> {
+        let mut writers = self.writers();
+        let writer = match writers.entry(path.to_string()) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(LogWriter::open(path)?),
+        };
+        let result = write(writer);
+
+        // Drop a handle that failed so the next append starts fresh
+        if result.is_err() {
+            writers.remove(path);
+        }
+        result
+    }
+
+
+    /// Append one line (which may hold several records).
+    ///
+    /// # Returns
+    /// The offset at which it starts.
+    pub fn append_line(&self, path: &str, text: &str) -> io::Result<u64> {
+        self.with_writer(path, |writer| writer.append(text))
+    }
+
+
+    /// Append `records` as one write and at most one sync.
+    ///
+    /// # Returns
+    /// The offset at which each record starts.
+    pub fn append_lines<S: AsRef<str>>(&self, path: &str, records: &[S]) -> io::Result<Vec<u64>> {
+        self.with_writer(path, |writer| writer.append_many(records))
+    }
+
+
+    /// Append `bytes` as they are.
+    ///
+    /// # Returns
+    /// The offset at which they start.
+    pub fn append_bytes(&self, path: &str, bytes: &[u8]) -> io::Result<u64> {
+        self.with_writer(path, |writer| writer.append_raw(bytes))
+    }
+
+
+    /// How the log's records are written, from its open handle or,
+    /// without one, the file's header. Text for a missing or blank file.
+    pub fn layout(&self, path: &str) -> io::Result<Layout> {
+        if let Some(writer) = self.writers().get(path) {
+            return Ok(writer.layout().unwrap_or_default());
+        }
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Layout::Text),
+            Err(e) => return Err(e),
+        };
+        let len = file.metadata()?.len();
+        let end = storage::data_end(&mut file, len)?;
+        Ok(storage::file_layout(&mut file, end)?.unwrap_or_default())
+    }
+
+
+    /// Offset just past the log's last record, from its open handle or,
+    /// without one, the file with its zero padding skipped. Zero for a
+    /// missing file.
+    pub fn end(&self, path: &str) -> io::Result<u64> {
+        if let Some(writer) = self.writers().get(path) {
+            return Ok(writer.offset());
+        }
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let len = file.metadata()?.len();
+        storage::data_end(&mut file, len)
+    }
+
+
+    /// Close the log's handle, trimming its preallocation.
+    pub fn close(&self, path: &str) -> io::Result<()> {
+        let writer = self.writers().remove(path);
+        match writer {
+            Some(mut w) => w.finish(),
+            None => Ok(()),
+        }
+    }
+
+
+    /// Close every handle.
+    ///
+    /// # Returns
+    /// The last error; every handle is closed regardless.
+    pub fn close_all(&self) -> io::Result<()> {
+        let drained: Vec<LogWriter> = self.writers().drain().map(|(_, w)| w).collect();
+        let mut result = Ok(());
+        for mut w in drained {
+            if let Err(e) = w.finish() {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+
+    /// Sync every handle whose interval durability policy is due.
+    ///
+    /// # Returns
+    /// The first error; the other logs are still synced.
+    pub fn sync_due(&self) -> io::Result<()> {
+        let mut result = Ok(());
+        for writer in self.writers().values_mut() {
+            if let Err(e) = writer.sync_if_due() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+
+impl StorageBackend for FileBackend {
+    fn append(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        self.append_lines(path, records)
+    }
+
+
+    fn replay(&self, path: &str, start: u64) -> io::Result<Vec<(u64, String)>> {
+        storage::replay_records(&RealFs, path, start)
+    }
+
+
+    fn compact(&self, path: &str) -> io::Result<usize> {
+        storage::compact_log_with(&RealFs, path)
+    }
+
+
+    fn flush(&self, path: &str) -> io::Result<()> {
+        match self.writers().get_mut(path) {
+            Some(writer) => writer.sync_pending(),
+            None => Ok(()),
+        }
+    }
+}
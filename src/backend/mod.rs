@@ -0,0 +1,62 @@
+// =====================================================================
+// File: backend/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `backend` module defines [`StorageBackend`], the log-level
+//! persistence operations (append, replay, compact, flush), and the
+//! backends that implement it.
+//!
+//! Structure:
+//! - `file.rs`  : [`FileBackend`], the process's real log files. It owns
+//!   the cached, preallocating [`LogWriter`](crate::LogWriter) of every
+//!   open log; [`append_write`](crate::append_write) and the other
+//!   `storage` append functions go through it.
+//! - `null.rs`  : [`NullBackend`], which accepts every append and keeps
+//!   nothing, for tests and embedders that need no persistence.
+//! - `tests.rs` : Unit tests for both backends.
+//!
+//! [`Fs`](crate::Fs) sits one level down: the file operations a backend
+//! (and compaction, checkpoints and recovery) are built from. A new
+//! backend (sled, S3, mmap) implements this trait over whatever it
+//! stores records in.
+// =====================================================================
+
+pub mod file;
+pub mod null;
+
+pub use self::file::FileBackend;
+pub use self::null::NullBackend;
+
+use std::fmt::Debug;
+use std::io;
+
+/// Where a log's records are kept, addressed by the log's path.
+pub trait StorageBackend: Debug + Send + Sync {
+    /// Append `records`, one line each, as a single write.
+    ///
+    /// # Returns
+    /// The offset at which each record starts.
+    fn append(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>>;
+
+
+    /// Every record from offset `start` on, with its offset, in log
+    /// order and with checksums and stamps stripped.
+    fn replay(&self, path: &str, start: u64) -> io::Result<Vec<(u64, String)>>;
+
+
+    /// Rewrite the log to the records that still matter.
+    ///
+    /// # Returns
+    /// The number of records kept.
+    fn compact(&self, path: &str) -> io::Result<usize>;
+
+
+    /// Make every record appended so far durable.
+    fn flush(&self, path: &str) -> io::Result<()>;
+}
+
+#[cfg(test)]
+pub mod tests;
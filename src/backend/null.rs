@@ -0,0 +1,49 @@
+// =====================================================================
+// File: backend/null.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! [`NullBackend`] persists nothing: every append succeeds and is
+//! dropped, replay finds no records, and compaction and flushing have
+//! nothing to do. The backend counterpart of [`NullFs`](crate::NullFs).
+// =====================================================================
+
+use std::io;
+
+use super::StorageBackend;
+
+/// A backend that keeps nothing.
+///
+/// # Example
+/// ```
+/// use kvstore::{NullBackend, StorageBackend};
+/// let backend = NullBackend;
+/// assert_eq!(backend.append("log", &["SET a 1".to_string()]).unwrap(), vec![0]);
+/// assert!(backend.replay("log", 0).unwrap().is_empty());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullBackend;
+
+
+impl StorageBackend for NullBackend {
+    fn append(&self, _path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        Ok(vec![0; records.len()])
+    }
+
+
+    fn replay(&self, _path: &str, _start: u64) -> io::Result<Vec<(u64, String)>> {
+        Ok(Vec::new())
+    }
+
+
+    fn compact(&self, _path: &str) -> io::Result<usize> {
+        Ok(0)
+    }
+
+
+    fn flush(&self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+// =====================================================================
+// File: backend/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for FileBackend and NullBackend.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// FileBackend Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod file_backend_tests {
+    use crate::{append_write_at, log_bytes, log_end, log_text, seal_record, FileBackend, StorageBackend};
+
+    fn temp_log(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kvstore_backend_{}_{}.db", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn appends_replay_and_share_the_storage_handles() {
+        let path = temp_log("replay");
+        let backend = FileBackend::process();
+        let records = vec![seal_record("SET a 1"), seal_record("SET b 2")];
+        let offsets = backend.append(&path, &records).unwrap();
+        assert_eq!(offsets[0], 0);
+
+        // append_write uses the same handle, so offsets carry on
+        let next = append_write_at(&path, &seal_record("DEL a")).unwrap();
+        assert_eq!(next, offsets[1] + records[1].len() as u64 + 1);
+        assert_eq!(log_end(&path).unwrap(), next + seal_record("DEL a").len() as u64 + 1);
+        backend.flush(&path).unwrap();
+
+        let replayed: Vec<String> = backend.replay(&path, 0).unwrap().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(replayed, ["SET a 1", "SET b 2", "DEL a"]);
+        backend.close(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_live_records() {
+        let path = temp_log("compact");
+        let backend = FileBackend::process();
+        std::fs::write(&path, log_bytes(&log_text(&["SET a 1", "SET a 2", "SET b 1", "DEL b"])).unwrap()).unwrap();
+
+        assert_eq!(backend.compact(&path).unwrap(), 1);
+        let replayed: Vec<String> = backend.replay(&path, 0).unwrap().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(replayed, ["SET a 2"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn closing_trims_preallocation() {
+        let path = temp_log("close");
+        let backend = FileBackend::process();
+        backend.append_line(&path, "SET a 1").unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 8);
+
+        backend.close(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 8);
+        assert_eq!(backend.end(&path).unwrap(), 8);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+
+// =====================================================================
+// NullBackend Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod null_backend_tests {
+    use crate::{NullBackend, StorageBackend};
+    use std::sync::Arc;
+
+    #[test]
+    fn keeps_nothing() {
+        let backend: Arc<dyn StorageBackend> = Arc::new(NullBackend);
+        let records = vec!["SET a 1".to_string(), "SET b 2".to_string()];
+        assert_eq!(backend.append("log", &records).unwrap(), vec![0, 0]);
+        backend.flush("log").unwrap();
+        assert_eq!(backend.compact("log").unwrap(), 0);
+        assert!(backend.replay("log", 0).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+// =====================================================================
+// File: backup/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `backup` module copies the dataset to a directory or to
+//! S3-compatible object storage (`BACKUP s3://bucket/prefix`), and
+//! restores it from there (`kvstore restore`).
+//!
+//! Structure:
+//! - `snapshot.rs` : [`create_backup`] and [`restore_backup`], and the
+//!   [`BackupManifest`] that lets a restore verify what it downloaded.
+//! - `target.rs`   : [`BackupTarget`], a directory or `s3://` location.
+//! - `s3.rs`       : [`S3Config`], a `PUT`/`GET` client signing requests
+//!   with AWS Signature Version 4, uploading large objects in parts, and
+//!   reaching `https://` endpoints with the `s3-tls` feature. Digests
+//!   come from the `sha2` and `hmac` crates.
+//! - `tests.rs`    : Unit tests for signing, manifests and round trips
+//!   through a directory and a fake S3 server, in parts and over HTTPS.
+//!
+//! Each backup is a full snapshot: the compacted equivalent of the log
+//! and any cold segments behind it, one `SET` per live key.
+// =====================================================================
+
+pub mod s3;
+pub mod snapshot;
+pub mod target;
+
+pub use self::s3::{hex, signing_key, S3Config};
+pub use self::snapshot::{create_backup, manifest_name, restore_backup, snapshot_name, BackupManifest};
+pub use self::target::BackupTarget;
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,289 @@
+// =====================================================================
+// File: backup/s3.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   A small client for S3-compatible object storage: `PUT` and `GET` of
+//   objects, signed with AWS Signature Version 4. Objects larger than
+//   the part size are uploaded as a multipart upload, so snapshots can
+//   grow past the single-PUT limit (5 GiB on AWS).
+//
+//   Requests are path-style (`<endpoint>/<bucket>/<key>`), which MinIO,
+//   Ceph, Garage and AWS all accept. `https://` endpoints need the
+//   `s3-tls` feature; without it only plain `http://` is available.
+// =====================================================================
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::http::Response;
+
+/// Longest one object request may take.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default [`S3Config::part_size`].
+pub const DEFAULT_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most parts S3 accepts in one multipart upload.
+const MAX_PARTS: usize = 10_000;
+
+/// Where the object store is and how to sign for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Config {
+    /// Base URL, e.g. `http://127.0.0.1:9000`.
+    pub endpoint: String,
+
+    /// Signing region (`us-east-1` unless the store says otherwise).
+    pub region: String,
+
+    /// Access key ID.
+    pub access_key: String,
+
+    /// Secret access key.
+    pub secret_key: String,
+
+    /// Session token of temporary credentials, sent as
+    /// `x-amz-security-token`.
+    pub session_token: Option<String>,
+
+    /// PEM file of the CA certificates an `https://` endpoint is checked
+    /// against, instead of the built-in roots (for a private CA).
+    pub ca_file: Option<String>,
+
+    /// Objects larger than this many bytes are uploaded in parts of this
+    /// size. S3 wants parts of at least 5 MiB (except the last).
+    pub part_size: usize,
+}
+
+
+impl S3Config {
+    /// A config for `endpoint` in `us-east-1` with the given keys.
+    pub fn new(endpoint: &str, access_key: &str, secret_key: &str) -> Self {
+        S3Config {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            region: "us-east-1".to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            session_token: None,
+            ca_file: None,
+            part_size: DEFAULT_PART_SIZE,
+        }
+    }
+
+
+    /// Upload `body` as `bucket/key`, in parts if it is larger than
+    /// [`part_size`](Self::part_size).
+    pub fn put_object(&self, bucket: &str, key: &str, body: &[u8]) -> io::Result<()> {
+        if body.len() > self.part_size {
+            return self.put_multipart(bucket, key, body);
+        }
+        let (status, _, response) = self.send("PUT", bucket, key, "", body)?;
+        check(status, &response, || format!("PUT {}/{}", bucket, key))
+    }
+
+
+    /// Upload `body` with a multipart upload, aborting it if a part or
+    /// the completion fails so no parts are left behind.
+    fn put_multipart(&self, bucket: &str, key: &str, body: &[u8]) -> io::Result<()> {
+        let (status, _, response) = self.send("POST", bucket, key, "uploads=", &[])?;
+        check(status, &response, || format!("starting the upload of {}/{}", bucket, key))?;
+        let upload_id = xml_field(&response, "UploadId")
+            .ok_or_else(|| io::Error::other(format!("no UploadId for {}/{}", bucket, key)))?;
+        let upload = format!("uploadId={}", uri_encode(&upload_id));
+
+        let result = (|| {
+            // A huge object gets bigger parts rather than too many of them
+            let part_size = self.part_size.max(body.len().div_ceil(MAX_PARTS)).max(1);
+            let mut completion = String::from("<CompleteMultipartUpload>");
+            for (i, part) in body.chunks(part_size).enumerate() {
+                let (status, headers, response) = self.send("PUT", bucket, key, &format!("partNumber={}&{}", i + 1, upload), part)?;
+                check(status, &response, || format!("part {} of {}/{}", i + 1, bucket, key))?;
+                let etag = headers.iter().find(|(name, _)| name == "etag").map(|(_, value)| value.as_str())
+                    .ok_or_else(|| io::Error::other(format!("part {} of {}/{} has no ETag", i + 1, bucket, key)))?;
+                completion.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag));
+            }
+            completion.push_str("</CompleteMultipartUpload>");
+
+            // Completion can fail after a 200, with the error in the body
+            let (status, _, response) = self.send("POST", bucket, key, &upload, completion.as_bytes())?;
+            let status = if xml_field(&response, "Code").is_some() { 500 } else { status };
+            check(status, &response, || format!("completing {}/{}", bucket, key))
+        })();
+        if result.is_err() {
+            let _ = self.send("DELETE", bucket, key, &upload, &[]);
+        }
+        result
+    }
+
+
+    /// Download `bucket/key`.
+    ///
+    /// # Returns
+    /// `Err` of kind `NotFound` if there is no such object.
+    pub fn get_object(&self, bucket: &str, key: &str) -> io::Result<Vec<u8>> {
+        let (status, _, response) = self.send("GET", bucket, key, "", &[])?;
+        match status {
+            200..=299 => Ok(response),
+            404 => Err(io::Error::new(io::ErrorKind::NotFound, format!("{}/{} not found", bucket, key))),
+            _ => Err(io::Error::other(format!("GET {}/{} answered {}{}", bucket, key, status, error_code(&response)))),
+        }
+    }
+
+
+    /// Send one signed request.
+    ///
+    /// # Arguments
+    /// * `query` - Canonical query string: parameters sorted by name,
+    ///   values URI-encoded (empty for none).
+    fn send(&self, method: &str, bucket: &str, key: &str, query: &str, body: &[u8]) -> io::Result<Response> {
+        let path = format!("/{}/{}", uri_encode(bucket), key.split('/').map(uri_encode).collect::<Vec<_>>().join("/"));
+        let url = match query {
+            "" => format!("{}{}", self.endpoint, path),
+            _ => format!("{}{}?{}", self.endpoint, path, query),
+        };
+        let (host, _, tls) = crate::http::split_endpoint(&url).map_err(io::Error::other)?;
+        // The Host header carries no default port, so neither does the signature
+        let host = host.strip_suffix(if tls { ":443" } else { ":80" }).unwrap_or(&host).to_string();
+
+        let headers = self.sign(method, &host, &path, query, &Sha256::digest(body), SystemTime::now());
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        crate::http::request(method, &url, &headers, body, TIMEOUT, self.ca_file.as_deref())
+    }
+
+
+    /// The signed headers of one request: `x-amz-date`,
+    /// `x-amz-content-sha256`, the session token if any, and
+    /// `Authorization`.
+    pub(crate) fn sign(&self, method: &str, host: &str, path: &str, query: &str, body_hash: &[u8], now: SystemTime)
+        -> Vec<(&'static str, String)> {
+        let (date, time) = amz_date(now);
+        let amz_date = format!("{}T{}Z", date, time);
+        let body_hash = hex(body_hash);
+
+        let mut headers = vec![("host", host.to_string()), ("x-amz-content-sha256", body_hash.clone()), ("x-amz-date", amz_date.clone())];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(n, v)| format!("{}:{}\n", n, v)).collect();
+        let signed_headers = headers.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(";");
+        let canonical_request = format!("{}\n{}\n{}\n{}\n{}\n{}", method, path, query, canonical_headers, signed_headers, body_hash);
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical_request.as_bytes())));
+        let signature = hex(&hmac_sha256(&signing_key(&self.secret_key, &date, &self.region, "s3"), string_to_sign.as_bytes()));
+
+        // Host is sent by the HTTP client itself
+        headers.remove(0);
+        headers.push((
+            "Authorization",
+            format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}", self.access_key, scope, signed_headers, signature),
+        ));
+        headers
+    }
+}
+
+
+/// The SigV4 key for one day, region and service.
+///
+/// # Example
+/// ```
+/// use kvstore::backup::{hex, signing_key};
+/// let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+/// assert_eq!(hex(&key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+/// ```
+pub fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+
+/// `(YYYYMMDD, HHMMSS)` of a time, in UTC.
+pub(crate) fn amz_date(time: SystemTime) -> (String, String) {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rest) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{:04}{:02}{:02}", year, month, day),
+        format!("{:02}{:02}{:02}", rest / 3600, rest / 60 % 60, rest % 60),
+    )
+}
+
+
+/// Percent-encode one path segment the way SigV4 expects.
+fn uri_encode(segment: &str) -> String {
+    let mut out = String::new();
+    for b in segment.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+
+/// `Ok` for a 2xx status, otherwise an error naming `what` was refused.
+fn check(status: u16, response: &[u8], what: impl FnOnce() -> String) -> io::Result<()> {
+    if (200..300).contains(&status) {
+        return Ok(());
+    }
+    Err(io::Error::other(format!("{} answered {}{}", what(), status, error_code(response))))
+}
+
+
+/// The text of the first `<name>` element of an S3 XML body.
+fn xml_field(body: &[u8], name: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(body);
+    let (_, rest) = text.split_once(&format!("<{}>", name))?;
+    let (value, _) = rest.split_once(&format!("</{}>", name))?;
+    Some(value.to_string())
+}
+
+
+/// `" (<Code>)"` from an S3 XML error body, if there is one.
+fn error_code(body: &[u8]) -> String {
+    match xml_field(body, "Code") {
+        Some(code) => format!(" ({})", code),
+        None => String::new(),
+    }
+}
+
+
+/// HMAC-SHA256 of `message` under `key`.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+
+/// Lowercase hex of `bytes`.
+///
+/// # Example
+/// ```
+/// use kvstore::backup::hex;
+/// assert_eq!(hex(&[0x00, 0xab, 0x7f]), "00ab7f");
+/// ```
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
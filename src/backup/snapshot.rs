@@ -0,0 +1,210 @@
+// =====================================================================
+// File: backup/snapshot.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Taking a backup and restoring one.
+//
+//   A backup is a snapshot of every live key, written as a data file
+//   (header record, then one `SET` per key, as a full sync sends), and a
+//   manifest recording its size and SHA-256. The snapshot goes first,
+//   then the manifest, then `LATEST` is pointed at the new backup, so a
+//   backup interrupted part way is never the one restored by default.
+//   Restoring checks the size and digest before the data file is
+//   replaced, in one rename.
+// =====================================================================
+
+use std::io;
+use std::ops::Bound;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use super::s3::{amz_date, hex, S3Config};
+use super::target::BackupTarget;
+use crate::compress;
+use crate::storage::{self, FORMAT_VERSION};
+use crate::{Fs, Session};
+
+/// First line of a manifest.
+const MANIFEST_HEADER: &str = "KVSTORE-BACKUP 1";
+
+/// What a backup holds and how to verify it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifest {
+    /// Backup ID: its UTC start time, e.g. `20261014T153000.250Z`.
+    pub id: String,
+
+    /// Start time, Unix milliseconds.
+    pub created_ms: u64,
+
+    /// Data file format version of the snapshot.
+    pub format: u32,
+
+    /// Keys in the snapshot.
+    pub keys: usize,
+
+    /// Snapshot size in bytes.
+    pub bytes: usize,
+
+    /// Lowercase hex SHA-256 of the snapshot.
+    pub sha256: String,
+}
+
+
+impl BackupManifest {
+    /// The manifest as stored, one `name:value` per line.
+    pub fn encode(&self) -> String {
+        format!(
+            "{}\nid:{}\ncreated_ms:{}\nformat:{}\nkeys:{}\nbytes:{}\nsha256:{}\n",
+            MANIFEST_HEADER, self.id, self.created_ms, self.format, self.keys, self.bytes, self.sha256
+        )
+    }
+
+
+    /// Parse a stored manifest.
+    ///
+    /// # Returns
+    /// `Err(message)` if the header or a field is missing or malformed.
+    pub fn parse(text: &str) -> Result<BackupManifest, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err("not a kvstore backup manifest".to_string());
+        }
+        let fields: Vec<(&str, &str)> = lines.filter_map(|line| line.split_once(':')).collect();
+        let field = |name: &str| {
+            fields.iter().find(|(n, _)| *n == name).map(|(_, v)| *v).ok_or_else(|| format!("manifest has no {}", name))
+        };
+        let number = |name: &str| field(name)?.parse::<u64>().map_err(|_| format!("manifest {} is not a number", name));
+        Ok(BackupManifest {
+            id: field("id")?.to_string(),
+            created_ms: number("created_ms")?,
+            format: number("format")? as u32,
+            keys: number("keys")? as usize,
+            bytes: number("bytes")? as usize,
+            sha256: field("sha256")?.to_string(),
+        })
+    }
+}
+
+
+/// Object name of a backup's snapshot.
+pub fn snapshot_name(id: &str) -> String {
+    format!("backup-{}.db", id)
+}
+
+
+/// Object name of a backup's manifest.
+pub fn manifest_name(id: &str) -> String {
+    format!("backup-{}.manifest", id)
+}
+
+
+/// Back up every live key to `target`.
+///
+/// Keys are written as the data file stores them: a `SET` each, plus an
+/// `EXPIREAT` with the deadline of a running TTL. They are walked on a
+/// [`snapshot`](crate::BTreeIndex::snapshot) of the index, so no list of
+/// keys is collected first.
+///
+/// # Returns
+/// The new backup's manifest, or `Err` if the dataset is still loading
+/// or a store failed (`LATEST` then still names the previous backup).
+pub fn create_backup(session: &mut Session, target: &BackupTarget) -> io::Result<BackupManifest> {
+    let mut span = crate::telemetry::span("kvstore.backup");
+    if session.is_loading() {
+        return Err(io::Error::other("dataset is still being replayed"));
+    }
+    let now = SystemTime::now();
+    let created_ms = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+    let (date, time) = amz_date(now);
+    let id = format!("{}T{}.{:03}Z", date, time, created_ms % 1000);
+
+    let index = session.index.snapshot();
+    let (mut records, mut count) = (Vec::new(), 0);
+    for (key, _) in index.range(Bound::Unbounded, Bound::Unbounded) {
+        if !session.key_visible(key) || session.ttl_status(key) == -2 {
+            continue;
+        }
+        // Reads cold and separated values too, so the session is needed
+        if let Some(value) = session.get_bytes(key) {
+            records.push(storage::set_record_bytes(key, &value));
+            count += 1;
+            if let Some(at) = session.ttl.expires_at(key) {
+                records.push(storage::expire_at_record(key, at));
+            }
+        }
+    }
+    // Encrypted here: the snapshot may go straight to S3, past the file system
+    let text = match session.fs.log_key() {
+        Some(key) => key.encrypt_lines(&storage::log_text(&records)),
+        None => storage::log_text(&records),
+    };
+    let bytes = storage::log_bytes(&text)?;
+
+    let manifest = BackupManifest {
+        id: id.clone(),
+        created_ms,
+        format: FORMAT_VERSION,
+        keys: count,
+        bytes: bytes.len(),
+        sha256: hex(&Sha256::digest(&bytes)),
+    };
+    span.attr("kvstore.backup.id", id.as_str()).attr("kvstore.backup.keys", count);
+    let (fs, s3) = (session.fs.clone(), session.s3.clone());
+    let stored = target.put(fs.as_ref(), s3.as_ref(), &snapshot_name(&id), &bytes)
+        .and_then(|()| target.put(fs.as_ref(), s3.as_ref(), &manifest_name(&id), manifest.encode().as_bytes()))
+        .and_then(|()| target.put(fs.as_ref(), s3.as_ref(), "LATEST", format!("{}\n", id).as_bytes()));
+    if let Err(e) = &stored {
+        span.error(&e.to_string());
+    }
+    stored.map(|()| manifest)
+}
+
+
+/// Download backup `id` (the latest if `None`) from `target`, verify it,
+/// and make it the data file at `data_file`.
+///
+/// Meant to run before the data file is opened, e.g. from
+/// `kvstore restore`.
+///
+/// # Returns
+/// The restored backup's manifest, or `Err` of kind `InvalidData` if the
+/// snapshot does not match it (the data file is then left alone).
+pub fn restore_backup(fs: &dyn Fs, s3: Option<&S3Config>, target: &BackupTarget, id: Option<&str>, data_file: &str) -> io::Result<BackupManifest> {
+    let id = match id {
+        Some(id) => id.to_string(),
+        None => String::from_utf8_lossy(&target.get(fs, s3, "LATEST")?).trim().to_string(),
+    };
+    let manifest = target.get(fs, s3, &manifest_name(&id))?;
+    let manifest = BackupManifest::parse(&String::from_utf8_lossy(&manifest)).map_err(invalid)?;
+    let snapshot = target.get(fs, s3, &snapshot_name(&id))?;
+
+    if snapshot.len() != manifest.bytes {
+        return Err(invalid(format!("snapshot is {} bytes, manifest says {}", snapshot.len(), manifest.bytes)));
+    }
+    if hex(&Sha256::digest(&snapshot)) != manifest.sha256 {
+        return Err(invalid("snapshot does not match its SHA-256".to_string()));
+    }
+    let header = snapshot.split(|&b| b == b'\n').next().and_then(|line| std::str::from_utf8(line).ok());
+    if header.and_then(storage::parse_header) != Some(manifest.format) {
+        return Err(invalid("snapshot has no data file header".to_string()));
+    }
+
+    // The restored log holds every key, so segments the old one continued
+    // go; a log too damaged to tell continues none worth keeping
+    let segment = compress::first_segment(fs, data_file).ok().flatten();
+    storage::replace_file_bytes(fs, data_file, &snapshot)?;
+    if let Some(newest) = segment {
+        compress::remove_segments(fs, data_file, newest)?;
+    }
+    Ok(manifest)
+}
+
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,106 @@
+// =====================================================================
+// File: backup/target.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Where backups go: a local directory, or an S3-compatible bucket
+//   under a key prefix (`s3://bucket/prefix`). Both store the same flat
+//   set of named objects (`backup-<id>.db`, `backup-<id>.manifest` and
+//   `LATEST`).
+// =====================================================================
+
+use std::io;
+
+use super::s3::S3Config;
+use crate::{storage, Fs};
+
+/// A parsed backup destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupTarget {
+    /// A directory, written through the session's file system.
+    Dir(String),
+
+    /// `s3://<bucket>/<prefix>`.
+    S3 {
+        /// Bucket name.
+        bucket: String,
+
+        /// Key prefix, without leading or trailing `/` (may be empty).
+        prefix: String,
+    },
+}
+
+
+impl BackupTarget {
+    /// Parse `s3://bucket[/prefix]` or a directory path.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::backup::BackupTarget;
+    /// assert_eq!(
+    ///     BackupTarget::parse("s3://backups/kv/prod/").unwrap(),
+    ///     BackupTarget::S3 { bucket: "backups".into(), prefix: "kv/prod".into() }
+    /// );
+    /// assert_eq!(BackupTarget::parse("/var/backups/kv").unwrap(), BackupTarget::Dir("/var/backups/kv".into()));
+    /// assert!(BackupTarget::parse("s3://").is_err());
+    /// ```
+    pub fn parse(spec: &str) -> Result<BackupTarget, String> {
+        let Some(rest) = spec.strip_prefix("s3://") else {
+            return Ok(BackupTarget::Dir(spec.trim_end_matches('/').to_string()));
+        };
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(format!("{} has no bucket", spec));
+        }
+        Ok(BackupTarget::S3 { bucket: bucket.to_string(), prefix: prefix.trim_matches('/').to_string() })
+    }
+
+
+    /// Where `name` is stored, for messages.
+    pub fn location(&self, name: &str) -> String {
+        match self {
+            BackupTarget::Dir(dir) => format!("{}/{}", dir, name),
+            BackupTarget::S3 { bucket, .. } => format!("s3://{}/{}", bucket, self.object_key(name)),
+        }
+    }
+
+
+    fn object_key(&self, name: &str) -> String {
+        match self {
+            BackupTarget::S3 { prefix, .. } if !prefix.is_empty() => format!("{}/{}", prefix, name),
+            _ => name.to_string(),
+        }
+    }
+
+
+    /// Store `bytes` as `name`.
+    ///
+    /// A directory target (which must exist) gets a temporary file that
+    /// is synced and renamed into place.
+    pub fn put(&self, fs: &dyn Fs, s3: Option<&S3Config>, name: &str, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            BackupTarget::Dir(dir) => storage::replace_file_bytes(fs, &format!("{}/{}", dir, name), bytes),
+            BackupTarget::S3 { bucket, .. } => require(s3)?.put_object(bucket, &self.object_key(name), bytes),
+        }
+    }
+
+
+    /// Read `name` back.
+    ///
+    /// # Returns
+    /// `Err` of kind `NotFound` if it was never stored.
+    pub fn get(&self, fs: &dyn Fs, s3: Option<&S3Config>, name: &str) -> io::Result<Vec<u8>> {
+        match self {
+            BackupTarget::Dir(dir) => fs.read(&format!("{}/{}", dir, name)),
+            BackupTarget::S3 { bucket, .. } => require(s3)?.get_object(bucket, &self.object_key(name)),
+        }
+    }
+}
+
+
+fn require(s3: Option<&S3Config>) -> io::Result<&S3Config> {
+    s3.ok_or_else(|| io::Error::other("S3 is not configured (set KVSTORE_S3_ENDPOINT and the AWS_* keys)"))
+}
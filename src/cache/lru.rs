@@ -0,0 +1,148 @@
+// =====================================================================
+// File: cache/lru.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`LruCache`] keeps up to `capacity` recently read key–value pairs.
+//! Recency is tracked with a monotonic tick per access; the entry with
+//! the oldest tick is evicted when the cache is full.
+// =====================================================================
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Fixed-capacity least recently used cache of key to value.
+#[derive(Debug, Clone)]
+pub struct LruCache {
+    /// Maximum number of cached entries.
+    capacity: usize,
+
+    /// Cached value and last-access tick per key.
+    entries: HashMap<String, (String, u64)>,
+
+    /// Keys ordered by last-access tick (oldest first).
+    order: BTreeMap<u64, String>,
+
+    /// Monotonic counter used to order accesses.
+    tick: u64,
+
+    /// Lookups answered from the cache.
+    hits: u64,
+
+    /// Lookups that had to go to the index.
+    misses: u64,
+}
+
+
+impl LruCache {
+    /// Create a cache holding at most `capacity` entries.
+    ///
+    /// A capacity of zero creates a cache that never stores anything.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+
+    /// Look up a cached value, marking it as most recently used.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::LruCache;
+    /// let mut cache = LruCache::new(2);
+    /// cache.put("dog", "bark");
+    /// assert_eq!(cache.get("dog"), Some("bark".to_string()));
+    /// assert_eq!(cache.get("cat"), None);
+    /// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    /// ```
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        match self.entries.get_mut(key) {
+            Some((value, last)) => {
+                self.order.remove(last);
+                *last = tick;
+                self.order.insert(tick, key.to_string());
+                self.hits += 1;
+                Some(value.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+
+    /// Cache a value read from the index, evicting the oldest entry if full.
+    pub fn put(&mut self, key: &str, value: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+
+        if let Some((_, last)) = self.entries.insert(key.to_string(), (value.to_string(), self.tick)) {
+            self.order.remove(&last);
+        }
+        self.order.insert(self.tick, key.to_string());
+
+        while self.entries.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+
+    /// Drop a key from the cache (called on write, delete and expiry).
+    pub fn invalidate(&mut self, key: &str) {
+        if let Some((_, last)) = self.entries.remove(key) {
+            self.order.remove(&last);
+        }
+    }
+
+
+    /// Remove every cached entry. Hit and miss counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+
+    /// Number of cached entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+
+    /// Maximum number of cached entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+
+    /// Lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+
+    /// Lookups that missed the cache.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
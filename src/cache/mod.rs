@@ -0,0 +1,25 @@
+// =====================================================================
+// File: cache/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `cache` module provides a small read cache that sits in front of
+//! the B-tree for skewed workloads where a few keys dominate reads.
+//!
+//! Structure:
+//! - `lru.rs`   : Defines [`LruCache`], a fixed-capacity least recently
+//!   used map of key to value with hit/miss counters.
+//! - `tests.rs` : Unit tests for eviction and invalidation.
+//!
+//! The session owns the cache and invalidates entries on every write,
+//! delete and expiration, so a cached value is never stale.
+// =====================================================================
+
+pub mod lru;
+
+pub use self::lru::LruCache;
+
+#[cfg(test)]
+pub mod tests;
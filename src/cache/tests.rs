@@ -0,0 +1,120 @@
+// =====================================================================
+// File: cache/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for the LRU read cache and its session integration.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// LRU Cache Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod lru_cache_tests {
+    use crate::LruCache;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", "1");
+        cache.put("b", "2");
+        cache.get("a");
+        cache.put("c", "3");
+
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some("1".to_string()));
+        assert_eq!(cache.get("c"), Some("3".to_string()));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn put_overwrites_existing_entry() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", "old");
+        cache.put("a", "new");
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("a"), Some("new".to_string()));
+    }
+
+    #[test]
+    fn invalidate_removes_entry() {
+        let mut cache = LruCache::new(4);
+        cache.put("a", "1");
+        cache.invalidate("a");
+        assert!(cache.is_empty());
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn zero_capacity_never_caches() {
+        let mut cache = LruCache::new(0);
+        cache.put("a", "1");
+        assert!(cache.is_empty());
+    }
+}
+
+
+// =====================================================================
+// Session Integration Tests
+// =====================================================================
+#[cfg(test)]
+mod cache_session_tests {
+    use crate::{LruCache, Session};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn cached_session() -> Session {
+        let mut session = Session::ephemeral();
+        session.cache = Some(LruCache::new(8));
+        session
+    }
+
+    #[test]
+    fn repeated_reads_hit_cache() {
+        let mut session = cached_session();
+        session.set("cache_hot".into(), "v".into());
+
+        assert_eq!(session.get("cache_hot"), Some("v".to_string()));
+        assert_eq!(session.get("cache_hot"), Some("v".to_string()));
+        let cache = session.cache.as_ref().unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    }
+
+    #[test]
+    fn write_invalidates_cached_value() {
+        let mut session = cached_session();
+        session.set("cache_w".into(), "old".into());
+        session.get("cache_w");
+        session.set("cache_w".into(), "new".into());
+        assert_eq!(session.get("cache_w"), Some("new".to_string()));
+    }
+
+    #[test]
+    fn delete_invalidates_cached_value() {
+        let mut session = cached_session();
+        session.set("cache_d".into(), "v".into());
+        session.get("cache_d");
+        assert!(session.delete("cache_d"));
+        assert_eq!(session.get("cache_d"), None);
+    }
+
+    #[test]
+    fn expiry_invalidates_cached_value() {
+        let mut session = cached_session();
+        session.set("cache_e".into(), "v".into());
+        session.get("cache_e");
+        session.ttl.set_expiration("cache_e", 30);
+        sleep(Duration::from_millis(50));
+
+        assert_eq!(session.get("cache_e"), None);
+        assert!(session.cache.as_ref().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+// =====================================================================
+// File: cdc.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Change data capture: every committed mutation is written to a sink
+//   as one JSON line, so downstream systems can mirror the keyspace.
+//
+//     {"seq":7,"ts":1760450000123,"op":"set","key":"a","value":"1"}
+//     {"seq":8,"ts":1760450000456,"op":"del","key":"a"}
+//     {"seq":9,"ts":1760450000789,"op":"expireat","key":"b","at":1760450060789}
+//
+//   `seq` counts mutations and `ts` is the commit time in Unix
+//   milliseconds. A mutation is published once its record is in the log,
+//   so a write that fails to persist never shows up. An `MSET` or a
+//   committed transaction gives one line per key, all with the same `ts`.
+//   `EXPIRE` shows up as `expireat` with the Unix-ms deadline in `at`,
+//   and `PERSIST` as `persist`.
+//   When a replica is re-seeded from its primary's snapshot, a
+//   `{"op":"reset"}` line (clear your copy) comes before the new keys.
+//
+//   The sink (`KVSTORE_CDC`) is a file (appended to; `seq` continues from
+//   its last line), `tcp://host:port`, or `stdout`. A sink that fails is
+//   dropped and retried at most once a second; the lines lost meanwhile
+//   are counted, and show up as a gap in `seq`.
+// =====================================================================
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::storage::{decode_record, ReplayOp};
+
+/// How long a failed sink rests before it is reopened.
+const RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Where change events go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CdcSink {
+    /// Appended to the file at this path.
+    File(String),
+
+    /// Streamed to this `host:port`.
+    Tcp(String),
+
+    /// Written to standard output, for piping into another process.
+    Stdout,
+}
+
+
+impl CdcSink {
+    /// Parse a sink: `stdout` (or `-`), `tcp://host:port`, or a file path
+    /// (optionally written `file:<path>`).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::CdcSink;
+    /// assert_eq!(CdcSink::parse("tcp://10.0.0.9:7000"), CdcSink::Tcp("10.0.0.9:7000".into()));
+    /// assert_eq!(CdcSink::parse("changes.jsonl"), CdcSink::File("changes.jsonl".into()));
+    /// assert_eq!(CdcSink::parse("-"), CdcSink::Stdout);
+    /// ```
+    pub fn parse(spec: &str) -> CdcSink {
+        let spec = spec.trim();
+        if spec == "-" || spec.eq_ignore_ascii_case("stdout") {
+            CdcSink::Stdout
+        } else if let Some(addr) = spec.strip_prefix("tcp://") {
+            CdcSink::Tcp(addr.to_string())
+        } else {
+            CdcSink::File(spec.strip_prefix("file:").unwrap_or(spec).to_string())
+        }
+    }
+
+
+    fn open(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(match self {
+            CdcSink::File(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+            CdcSink::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                Box::new(stream)
+            }
+            CdcSink::Stdout => Box::new(io::stdout()),
+        })
+    }
+}
+
+
+/// A change stream: numbers committed mutations and writes them to a sink.
+pub struct Cdc {
+    /// Where the lines go.
+    pub sink: CdcSink,
+
+    /// Sequence number of the last event.
+    pub seq: u64,
+
+    /// Events lost because the sink was failing.
+    pub dropped: u64,
+
+    /// The open sink, `None` while it rests after a failure.
+    out: Option<Box<dyn Write + Send>>,
+
+    /// When a failed sink may be reopened.
+    retry_at: Instant,
+}
+
+
+impl Cdc {
+    /// Open `sink`; a file sink's sequence continues from its last line.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` if the sink can't be opened, so a misconfiguration
+    /// shows at startup rather than as lost events.
+    pub fn open(sink: CdcSink) -> io::Result<Cdc> {
+        let seq = match &sink {
+            CdcSink::File(path) => last_seq(path)?,
+            _ => 0,
+        };
+        let out = sink.open()?;
+        let mut cdc = Cdc::with_writer(sink, out);
+        cdc.seq = seq;
+        Ok(cdc)
+    }
+
+
+    /// A stream writing to `out`; if `out` fails, `sink` is reopened.
+    pub fn with_writer(sink: CdcSink, out: Box<dyn Write + Send>) -> Cdc {
+        Cdc { sink, seq: 0, dropped: 0, out: Some(out), retry_at: Instant::now() }
+    }
+
+
+    /// Publish the mutations of one log record that was just appended.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Cdc, CdcSink};
+    /// let mut cdc = Cdc::with_writer(CdcSink::Stdout, Box::new(std::io::sink()));
+    /// cdc.publish("MSET a 1 b 2");
+    /// cdc.publish("DEL a");
+    /// assert_eq!(cdc.seq, 3);
+    /// ```
+    pub fn publish(&mut self, record: &str) {
+        let ts = now_ms();
+        for op in decode_record(0, record) {
+            let seq = self.seq + 1;
+            let line = match op {
+                ReplayOp::Set(key, value, _) => format!(
+                    r#"{{"seq":{},"ts":{},"op":"set","key":{},"value":{}}}"#,
+                    seq, ts, json_string(&key), json_string(&String::from_utf8_lossy(&value))
+                ),
+                ReplayOp::Del(key) => format!(r#"{{"seq":{},"ts":{},"op":"del","key":{}}}"#, seq, ts, json_string(&key)),
+                ReplayOp::ExpireAt(key, at) => format!(
+                    r#"{{"seq":{},"ts":{},"op":"expireat","key":{},"at":{}}}"#,
+                    seq, ts, json_string(&key), at
+                ),
+                ReplayOp::Persist(key) => format!(r#"{{"seq":{},"ts":{},"op":"persist","key":{}}}"#, seq, ts, json_string(&key)),
+                // Sessions publish the value itself, never a value log pointer
+                ReplayOp::SetRef(..) => continue,
+            };
+            self.seq = seq;
+            self.emit(&line);
+        }
+    }
+
+
+    /// Tell the sink the keyspace is being replaced; the new keys follow
+    /// as regular `set` events.
+    pub fn reset(&mut self) {
+        self.seq += 1;
+        let line = format!(r#"{{"seq":{},"ts":{},"op":"reset"}}"#, self.seq, now_ms());
+        self.emit(&line);
+    }
+
+
+    fn emit(&mut self, line: &str) {
+        if self.out.is_none() && Instant::now() >= self.retry_at {
+            match self.sink.open() {
+                Ok(out) => {
+                    eprintln!("cdc: sink reopened after {} lost events", self.dropped);
+                    self.out = Some(out);
+                }
+                Err(_) => self.retry_at = Instant::now() + RETRY_AFTER,
+            }
+        }
+        let Some(out) = &mut self.out else {
+            self.dropped += 1;
+            return;
+        };
+        let written = out.write_all(line.as_bytes())
+            .and_then(|()| out.write_all(b"\n"))
+            .and_then(|()| out.flush());
+        if let Err(e) = written {
+            eprintln!("cdc: sink failed: {}", e);
+            self.out = None;
+            self.retry_at = Instant::now() + RETRY_AFTER;
+            self.dropped += 1;
+        }
+    }
+}
+
+
+/// `seq` of the last complete line of an existing change file (0 if none).
+fn last_seq(path: &str) -> io::Result<u64> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata()?.len();
+    let mut start = len.saturating_sub(64 * 1024);
+    let mut tail = Vec::new();
+    loop {
+        file.seek(SeekFrom::Start(start))?;
+        tail.clear();
+        file.read_to_end(&mut tail)?;
+
+        // The window must hold the whole last line
+        let body = tail.strip_suffix(b"\n").unwrap_or(&tail);
+        if start == 0 || body.contains(&b'\n') {
+            break;
+        }
+        start = start.saturating_sub(start.max(64 * 1024));
+    }
+    let tail = String::from_utf8_lossy(&tail);
+    let seq = tail.lines().rev()
+        .filter_map(|line| line.strip_prefix(r#"{"seq":"#))
+        .find_map(|rest| rest.split(',').next()?.parse().ok());
+    Ok(seq.unwrap_or(0))
+}
+
+
+/// `text` as a quoted JSON string.
+///
+/// # Example
+/// ```
+/// use kvstore::json_string;
+/// assert_eq!(json_string("a \"b\"\n\u{1}"), r#""a \"b\"\n\u0001""#);
+/// ```
+pub fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis())
+}
+
+
+// =================================================================
+// cdc.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod cdc_tests {
+    use super::*;
+    use crate::{execute_line, MemFs, Session};
+    use std::sync::{Arc, Mutex};
+
+    /// A writer tests can read back, or make fail.
+    #[derive(Clone, Default)]
+    struct Shared {
+        bytes: Arc<Mutex<Vec<u8>>>,
+        broken: Arc<Mutex<bool>>,
+    }
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if *self.broken.lock().unwrap() {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+            }
+            self.bytes.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        /// The lines written so far, with `ts` blanked out.
+        fn lines(&self) -> Vec<String> {
+            let text = String::from_utf8(self.bytes.lock().unwrap().clone()).unwrap();
+            text.lines()
+                .map(|line| {
+                    let start = line.find(r#""ts":"#).unwrap() + 5;
+                    let end = start + line[start..].find(',').unwrap();
+                    format!("{}T{}", &line[..start], &line[end..])
+                })
+                .collect()
+        }
+    }
+
+    fn session_with_cdc() -> (Session, Shared) {
+        let out = Shared::default();
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session.cdc = Some(Cdc::with_writer(CdcSink::Stdout, Box::new(out.clone())));
+        (session, out)
+    }
+
+    #[test]
+    fn committed_mutations_become_json_lines() {
+        let (mut session, out) = session_with_cdc();
+        execute_line(b"SET greeting \"hi there\"", &mut session);
+        execute_line(b"MSET a 1 b 2", &mut session);
+        execute_line(b"DEL a", &mut session);
+        execute_line(b"DEL missing", &mut session);
+        execute_line(b"BEGIN", &mut session);
+        execute_line(b"SET c 3", &mut session);
+        execute_line(b"COMMIT", &mut session);
+        execute_line(b"BEGIN", &mut session);
+        execute_line(b"SET d 4", &mut session);
+        execute_line(b"ABORT", &mut session);
+        execute_line(b"GET b", &mut session);
+
+        assert_eq!(out.lines(), vec![
+            r#"{"seq":1,"ts":T,"op":"set","key":"greeting","value":"hi there"}"#,
+            r#"{"seq":2,"ts":T,"op":"set","key":"a","value":"1"}"#,
+            r#"{"seq":3,"ts":T,"op":"set","key":"b","value":"2"}"#,
+            r#"{"seq":4,"ts":T,"op":"del","key":"a"}"#,
+            r#"{"seq":5,"ts":T,"op":"set","key":"c","value":"3"}"#,
+        ]);
+    }
+
+    #[test]
+    fn failing_sinks_drop_events_and_leave_a_gap() {
+        let (mut session, out) = session_with_cdc();
+        execute_line(b"SET a 1", &mut session);
+        *out.broken.lock().unwrap() = true;
+        execute_line(b"SET b 2", &mut session);
+        execute_line(b"SET c 3", &mut session);
+
+        // The write itself still succeeds
+        assert_eq!(session.get("c"), Some("3".to_string()));
+        let cdc = session.cdc.as_ref().unwrap();
+        assert_eq!((cdc.seq, cdc.dropped), (3, 2));
+        assert_eq!(out.lines().len(), 1);
+    }
+
+    #[test]
+    fn file_sinks_continue_their_sequence() {
+        let path = std::env::temp_dir().join(format!("kvstore_cdc_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+
+        let mut cdc = Cdc::open(CdcSink::parse(&path)).unwrap();
+        cdc.publish("MSET a 1 b 2");
+        let mut cdc = Cdc::open(CdcSink::parse(&format!("file:{}", path))).unwrap();
+        assert_eq!(cdc.seq, 2);
+        cdc.reset();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.lines().last().unwrap().starts_with(r#"{"seq":3,"ts":"#));
+        assert!(text.ends_with("\"op\":\"reset\"}\n"));
+    }
+}
@@ -0,0 +1,269 @@
+// =====================================================================
+// File: checkpoint/checkpoint.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! Checkpoints: the keyspace saved beside the log, and the log cut back.
+//!
+//! A checkpoint is taken in two steps, each a file replaced atomically
+//! (see [`storage::replace_file`]):
+//! 1. `<log>.snap` gets a `KVSNAP <id> <keys> gzip` line and, as one
+//!    line of base64, the gzip-compressed text of one sealed `SET` record
+//!    per live key (see [`crate::compress`]).
+//! 2. The log is replaced by its header, a `CHECKPOINT <id>` record and
+//!    an `EXPIREAT` record per running TTL; later writes are appended
+//!    after them as usual.
+//!
+//! A log that starts with `CHECKPOINT <id>` needs a snapshot with that
+//! id or a newer one. Newer is what a crash between the two renames
+//! leaves: the snapshot already holds everything the old log does, so
+//! replaying that log over it changes nothing. A log without the record
+//! is complete on its own and any snapshot beside it is stale (a
+//! compaction or restore wrote the log after it).
+//!
+//! Snapshots written before they were compressed (a `KVSNAP <id> <keys>`
+//! line, then the records one per line) are read as before.
+// =====================================================================
+
+use std::io;
+
+use crate::compress::{self, gunzip, gzip};
+use crate::crypt::{base64_decode, base64_encode};
+use crate::storage::{self, LogRecord};
+use crate::Fs;
+
+/// The keyspace saved by one checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Number of the checkpoint, counting up from 1 per data file.
+    pub id: u64,
+
+    /// Every live key and its value, in key order.
+    pub pairs: Vec<(String, Vec<u8>)>,
+}
+
+
+/// Path of the snapshot kept beside the data file, `<path>.snap`.
+///
+/// # Example
+/// ```
+/// use kvstore::checkpoint_path;
+/// assert_eq!(checkpoint_path("data.db"), "data.db.snap");
+/// ```
+pub fn checkpoint_path(data_file: &str) -> String {
+    storage::sidecar_path(data_file, "snap")
+}
+
+
+/// The record a log truncated by checkpoint `id` starts with.
+///
+/// # Example
+/// ```
+/// use kvstore::{checkpoint_record, parse_checkpoint_record};
+/// assert_eq!(checkpoint_record(3), "CHECKPOINT 3");
+/// assert_eq!(parse_checkpoint_record("CHECKPOINT 3"), Some(3));
+/// assert_eq!(parse_checkpoint_record("SET CHECKPOINT 3"), None);
+/// ```
+pub fn checkpoint_record(id: u64) -> String {
+    LogRecord::Checkpoint { id }.encode()
+}
+
+
+/// The checkpoint id in a `CHECKPOINT <id>` record.
+pub fn parse_checkpoint_record(record: &str) -> Option<u64> {
+    match LogRecord::decode(record)? {
+        LogRecord::Checkpoint { id } => Some(id),
+        _ => None,
+    }
+}
+
+
+/// Read the snapshot beside `data_file`.
+///
+/// # Returns
+/// * `Ok(Some(checkpoint))` once every record passed its checksum.
+/// * `Ok(None)` if there is no snapshot.
+/// * `Err(io::Error)` of kind `InvalidData` if it is damaged or cut
+///   short, or any error from reading it.
+pub fn read_checkpoint(fs: &dyn Fs, data_file: &str) -> io::Result<Option<Checkpoint>> {
+    let path = checkpoint_path(data_file);
+    let bytes = match fs.read(&path) {
+        Ok(b) => b,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let damaged = |what: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, what));
+    let text = std::str::from_utf8(&bytes).map_err(|e| damaged(e.to_string()))?;
+
+    let mut lines = text.lines();
+    let (id, keys, compressed) =
+        lines.next().and_then(parse_snapshot_header).ok_or_else(|| damaged("no KVSNAP header".to_string()))?;
+    let body;
+    let lines = if compressed {
+        let packed = lines.next().and_then(base64_decode).ok_or_else(|| damaged("body is not base64".to_string()))?;
+        let unpacked = gunzip(&packed).map_err(|e| damaged(e.to_string()))?;
+        body = String::from_utf8(unpacked).map_err(|e| damaged(e.to_string()))?;
+        body.lines()
+    } else {
+        lines
+    };
+    let key = fs.log_key();
+    let mut pairs = Vec::with_capacity(keys);
+    for line in lines.filter(|l| !l.is_empty()) {
+        let pair = storage::open_record(line, key.as_deref())?.and_then(|record| storage::parse_set_record(&record));
+        pairs.push(pair.ok_or_else(|| damaged(format!("record {} fails its checksum", pairs.len() + 1)))?);
+    }
+    if pairs.len() != keys {
+        return Err(damaged(format!("holds {} of its {} keys", pairs.len(), keys)));
+    }
+    Ok(Some(Checkpoint { id, pairs }))
+}
+
+
+/// Id, key count and whether the body is compressed, from a
+/// `KVSNAP <id> <keys> [gzip]` line.
+fn parse_snapshot_header(line: &str) -> Option<(u64, usize, bool)> {
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["KVSNAP", id, keys] => Some((id.parse().ok()?, keys.parse().ok()?, false)),
+        ["KVSNAP", id, keys, "gzip"] => Some((id.parse().ok()?, keys.parse().ok()?, true)),
+        _ => None,
+    }
+}
+
+
+/// Save `pairs` as the next checkpoint of `data_file`, then truncate the
+/// log to its header, the `CHECKPOINT` record and an `EXPIREAT` record
+/// per entry of `expirations` (key, Unix ms deadline).
+///
+/// The caller must not append to the log meanwhile; the session takes
+/// checkpoints between commands.
+///
+/// # Returns
+/// * `Ok(id)` of the new checkpoint.
+/// * `Err(io::Error)` from writing either file. If the snapshot was
+///   replaced but the log was not, the data file still loads the same.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_text, read_checkpoint, replay_records, write_checkpoint, Fs, MemFs};
+/// let fs = MemFs::new();
+/// fs.append("log", &log_text(&["SET a 1"])).unwrap();
+/// let pairs = vec![("a".to_string(), b"1".to_vec())];
+/// assert_eq!(write_checkpoint(&fs, "log", &pairs, &[("a".to_string(), 4_000_000_000_000)]).unwrap(), 1);
+///
+/// assert_eq!(read_checkpoint(&fs, "log").unwrap().unwrap().pairs, pairs);
+/// let records = replay_records(&fs, "log", 0).unwrap();
+/// assert_eq!((records[0].1.as_str(), records[1].1.as_str()), ("CHECKPOINT 1", "EXPIREAT a 4000000000000"));
+/// ```
+pub fn write_checkpoint(
+    fs: &dyn Fs,
+    data_file: &str,
+    pairs: &[(String, Vec<u8>)],
+    expirations: &[(String, u64)],
+) -> io::Result<u64> {
+    // A newer snapshot always holds at least what the log points at
+    let id = match fs.read(&checkpoint_path(data_file)) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).lines().next().and_then(parse_snapshot_header).map_or(0, |(id, ..)| id),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e),
+    } + 1;
+
+    let path = checkpoint_path(data_file);
+    let mut body = String::new();
+    for (key, value) in pairs {
+        body.push_str(&storage::seal_record(&storage::set_record_bytes(key, value)));
+        body.push('\n');
+    }
+    // The body is one base64 line, so the file system can't see its records
+    if let Some(key) = fs.log_key() {
+        body = key.encrypt_lines(&body);
+    }
+    let text = format!("KVSNAP {} {} gzip\n{}", id, pairs.len(), base64_encode(&gzip(body.as_bytes())));
+    storage::replace_file(fs, &path, &text)?;
+
+    // Only now that the snapshot is durable can the log lose its records
+    let segment = compress::first_segment(fs, data_file)?;
+    let mut records = vec![checkpoint_record(id)];
+    records.extend(expirations.iter().map(|(key, at)| storage::expire_at_record(key, *at)));
+    let last_seq = storage::last_seq(fs, data_file)?;
+    storage::replace_file(fs, data_file, &storage::log_text_after(&records, last_seq))?;
+    if let Some(newest) = segment {
+        compress::remove_segments(fs, data_file, newest)?;
+    }
+    Ok(id)
+}
+
+
+/// The checkpoint `records` (a whole log, as replayed) continue from, if
+/// any; its `CHECKPOINT` record is taken off the front of `records`.
+/// A log rotated into cold segments first gets their records back in
+/// front of its own (see [`crate::compress::segment`]).
+///
+/// # Returns
+/// * `Ok(None)` if the log is complete on its own.
+/// * `Err(io::Error)` of kind `InvalidData` if the snapshot or a segment
+///   it needs is missing, older or damaged.
+pub fn load_base(fs: &dyn Fs, data_file: &str, records: &mut Vec<(u64, String)>) -> io::Result<Option<Checkpoint>> {
+    if let Some(id) = records.first().and_then(|(_, record)| compress::parse_segment_record(record)) {
+        let mut cold = compress::read_segment(fs, data_file, id)?;
+        cold.extend(records.drain(1..));
+        *records = cold;
+    }
+    let Some(want) = records.first().and_then(|(_, record)| parse_checkpoint_record(record)) else {
+        return Ok(None);
+    };
+    records.remove(0);
+    read_base(fs, data_file, want).map(Some)
+}
+
+
+/// The snapshot a log that starts with `CHECKPOINT <want>` continues, for
+/// callers that read the log's records one at a time.
+///
+/// # Returns
+/// `Err(io::Error)` as from [`load_base`].
+pub fn read_base(fs: &dyn Fs, data_file: &str, want: u64) -> io::Result<Checkpoint> {
+    let missing = |what: String| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{} continues checkpoint {}, but {}", data_file, want, what))
+    };
+    match read_checkpoint(fs, data_file)? {
+        Some(checkpoint) if checkpoint.id >= want => Ok(checkpoint),
+        Some(checkpoint) => Err(missing(format!("{} holds checkpoint {}", checkpoint_path(data_file), checkpoint.id))),
+        None => Err(missing(format!("{} is missing", checkpoint_path(data_file)))),
+    }
+}
+
+
+/// Turn a log that continues a checkpoint or cold segments back into a
+/// complete one: the snapshot's keys as `SET` records, then the records
+/// logged since.
+///
+/// Needed wherever records must be found in the log itself, such as the
+/// value pointers of memory-limited sessions and offline compaction.
+/// The new log replaces the old one in a single rename; the snapshot is
+/// left in place (and is stale from then on), and the segments are
+/// removed.
+///
+/// # Returns
+/// * `Ok(true)` if the log was rewritten, `Ok(false)` if it was complete.
+/// * `Err(io::Error)` as from [`load_base`], or from the rewrite.
+pub fn fold_checkpoint(fs: &dyn Fs, data_file: &str) -> io::Result<bool> {
+    let mut records = storage::replay_records(fs, data_file, 0)?;
+    let segment = records.first().and_then(|(_, record)| compress::parse_segment_record(record));
+    let checkpoint = load_base(fs, data_file, &mut records)?;
+    if checkpoint.is_none() && segment.is_none() {
+        return Ok(false);
+    }
+
+    let mut lines: Vec<String> =
+        checkpoint.iter().flat_map(|base| &base.pairs).map(|(key, value)| storage::set_record_bytes(key, value)).collect();
+    lines.extend(records.into_iter().map(|(_, record)| record));
+    let last_seq = storage::last_seq(fs, data_file)?;
+    storage::replace_file(fs, data_file, &storage::log_text_after(&lines, last_seq))?;
+    if let Some(newest) = segment {
+        compress::remove_segments(fs, data_file, newest)?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,30 @@
+// =====================================================================
+// File: checkpoint/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `checkpoint` module bounds recovery time by saving the whole
+//! keyspace to `<log>.snap` and truncating the log behind it.
+//!
+//! Structure:
+//! - `checkpoint.rs` : Writing and reading the snapshot file, the
+//!   `CHECKPOINT <id>` record a truncated log starts with, and folding a
+//!   snapshot back into its log.
+//! - `tests.rs`      : Unit tests for round trips, crashes between the
+//!   two renames, and loading sessions from a checkpoint.
+//!
+//! `SNAPSHOT` (or [`Session::checkpoint_tick`](crate::Session::checkpoint_tick)
+//! every `KVSTORE_CHECKPOINT_EVERY` appends) takes a checkpoint; startup
+//! loads the snapshot and replays only the records logged after it.
+// =====================================================================
+
+#[allow(clippy::module_inception)]
+pub mod checkpoint;
+
+pub use self::checkpoint::{checkpoint_path, checkpoint_record, fold_checkpoint, load_base, parse_checkpoint_record,
+    read_base, read_checkpoint, write_checkpoint, Checkpoint};
+
+#[cfg(test)]
+pub mod tests;
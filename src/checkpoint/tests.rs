@@ -0,0 +1,215 @@
+// =====================================================================
+// File: checkpoint/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for checkpoints: the snapshot file, crashes between its
+//   two renames, and sessions loading (or folding) a checkpoint.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Checkpoint Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod checkpoint_tests {
+    use std::sync::Arc;
+
+    use crate::storage::log_text;
+    use crate::{checkpoint_path, compact_log_with, fold_checkpoint, load_data, read_checkpoint, repair_log_with,
+        replay_records, write_checkpoint, Fs, Limits, MemFs, Session};
+
+    const LOG: &str = "checkpoint.db";
+
+    fn session_on(fs: &Arc<MemFs>) -> Session {
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        session.data_file = LOG.to_string();
+        session
+    }
+
+    fn records(fs: &MemFs) -> Vec<String> {
+        replay_records(fs, LOG, 0).unwrap().into_iter().map(|(_, record)| record).collect()
+    }
+
+    #[test]
+    fn checkpoint_truncates_the_log_and_reloads() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.set("b".into(), "two words".into());
+        session.set("gone".into(), "x".into());
+        assert!(session.delete("gone"));
+
+        assert_eq!(session.checkpoint(), Ok(1));
+        assert_eq!(records(&fs), ["CHECKPOINT 1"]);
+        assert_eq!(read_checkpoint(&*fs, LOG).unwrap().unwrap().pairs.len(), 2);
+        assert_eq!(session.records_since_checkpoint, 0);
+
+        session.set("c".into(), "3".into());
+        assert!(session.delete("a"));
+        assert_eq!(session.checkpoint(), Ok(2));
+        session.set("d".into(), "4".into());
+
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, LOG);
+        let report = restarted.load_report.clone().unwrap();
+        assert_eq!((report.checkpoint_keys, report.records), (2, 1));
+        assert_eq!(restarted.checkpoint_id, 2);
+        assert_eq!(restarted.get("a"), None);
+        assert_eq!(restarted.get("b"), Some("two words".to_string()));
+        assert_eq!(restarted.get("d"), Some("4".to_string()));
+        assert!(!restarted.read_only);
+    }
+
+    #[test]
+    fn checkpoints_and_compaction_keep_ttls() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.set("b".into(), "2".into());
+        assert_eq!(session.expire("a", 60_000), Ok(true));
+
+        session.checkpoint().unwrap();
+        assert!(records(&fs)[1].starts_with("EXPIREAT a "));
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, LOG);
+        assert!(restarted.ttl.ttl_remaining("a") > 0);
+        assert_eq!(restarted.ttl.ttl_remaining("b"), -1);
+
+        restarted.start_compaction().unwrap();
+        while !restarted.compaction_tick().unwrap() {}
+        let mut compacted = session_on(&fs);
+        load_data(&mut compacted, LOG);
+        assert!(compacted.ttl.ttl_remaining("a") > 0);
+        assert_eq!(compacted.get("b"), Some("2".to_string()));
+    }
+
+    #[test]
+    fn crash_between_the_renames_loads_the_same() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        assert_eq!(session.checkpoint(), Ok(1));
+        session.set("a".into(), "2".into());
+        session.set("b".into(), "3".into());
+        assert!(session.delete("b"));
+
+        // Snapshot 2 is in place, but the log still continues checkpoint 1
+        let log = fs.contents(LOG).unwrap();
+        assert_eq!(session.checkpoint(), Ok(2));
+        fs.write_file(LOG, &log);
+
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, LOG);
+        assert_eq!(restarted.get("a"), Some("2".to_string()));
+        assert!(!restarted.exists("b"));
+        assert!(!restarted.read_only);
+    }
+
+    #[test]
+    fn missing_or_damaged_snapshot_turns_read_only() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.checkpoint().unwrap();
+
+        let snapshot = fs.contents(&checkpoint_path(LOG)).unwrap();
+        fs.write_file(&checkpoint_path(LOG), &snapshot[..snapshot.len() - 3]);
+        assert!(read_checkpoint(&*fs, LOG).is_err());
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, LOG);
+        assert!(restarted.read_only);
+        assert!(restarted.try_set("b".into(), "2".into()).is_err());
+
+        fs.remove(&checkpoint_path(LOG)).unwrap();
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, LOG);
+        assert!(restarted.read_only);
+    }
+
+    #[test]
+    fn complete_logs_ignore_a_stale_snapshot() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.checkpoint().unwrap();
+        assert!(session.delete("a"));
+        session.set("b".into(), "2".into());
+
+        // The compacted log holds every key and no CHECKPOINT record
+        session.start_compaction().unwrap();
+        while !session.compaction_tick().unwrap() {}
+        assert_eq!(records(&fs), ["SET b 2"]);
+        assert_eq!(session.checkpoint_id, 0);
+
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, LOG);
+        assert!(!restarted.exists("a"));
+        assert_eq!(restarted.get("b"), Some("2".to_string()));
+
+        // The next checkpoint still gets a new id
+        assert_eq!(restarted.checkpoint(), Ok(2));
+    }
+
+    #[test]
+    fn fold_rebuilds_a_complete_log() {
+        let fs = MemFs::new();
+        fs.append(LOG, &log_text(&["SET a 1", "SET b 2"])).unwrap();
+        write_checkpoint(&fs, LOG, &[("a".into(), "1".into()), ("b".into(), "2".into())], &[]).unwrap();
+        fs.append(LOG, &crate::seal_record("DEL a")).unwrap();
+
+        assert!(fold_checkpoint(&fs, LOG).unwrap());
+        assert_eq!(records(&fs), ["SET a 1", "SET b 2", "DEL a"]);
+        assert!(!fold_checkpoint(&fs, LOG).unwrap());
+
+        // Offline tools fold first, so deletes after the checkpoint hold
+        write_checkpoint(&fs, LOG, &[("b".into(), "2".into()), ("c".into(), "3".into())], &[]).unwrap();
+        fs.append(LOG, &crate::seal_record("DEL c")).unwrap();
+        let report = repair_log_with(&fs, LOG, &Limits::default()).unwrap();
+        assert_eq!((report.keys_kept, report.dropped.len()), (1, 0));
+        assert_eq!(compact_log_with(&fs, LOG).unwrap(), 1);
+        assert_eq!(records(&fs), ["SET b 2"]);
+    }
+
+    #[test]
+    fn memory_limited_sessions_fold_on_load() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.set("b".into(), "2".into());
+        session.checkpoint().unwrap();
+        session.set("c".into(), "3".into());
+
+        let mut limited = Session::with_memory_limit(1);
+        limited.fs = fs.clone();
+        limited.data_file = LOG.to_string();
+        assert!(limited.checkpoint().is_err());
+        load_data(&mut limited, LOG);
+        assert_eq!(records(&fs), ["SET a 1", "SET b 2", "SET c 3"]);
+        assert_eq!(limited.get("a"), Some("1".to_string()));
+        assert_eq!(limited.get("b"), Some("2".to_string()));
+        assert_eq!(limited.get("c"), Some("3".to_string()));
+    }
+
+    #[test]
+    fn checkpoint_tick_waits_for_enough_records() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.checkpoint_every = 3;
+        session.set("a".into(), "1".into());
+        session.set("b".into(), "2".into());
+        assert!(!session.checkpoint_tick().unwrap());
+        session.set("a".into(), "3".into());
+        assert!(session.checkpoint_tick().unwrap());
+        assert_eq!(session.checkpoint_id, 1);
+        assert!(!session.checkpoint_tick().unwrap());
+    }
+}
@@ -0,0 +1,431 @@
+// =====================================================================
+// File: cluster.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Cluster mode: keys are hashed to N shards, each its own server
+//   process with its own data file.
+//
+//   Every process gets the same shard list (`KVSTORE_CLUSTER`). A shard
+//   (`KVSTORE_CLUSTER_SHARD=<i>`) serves only the keys that hash to it
+//   and answers any other key with
+//
+//     ERR MOVED <shard> <host:port>
+//
+//   A process given the list but no shard number is a router: it reads
+//   commands on stdin like the REPL, sends each key to its shard, and
+//   splits `MGET`/`MSET` by shard and merges the replies. `RANGE`,
+//   `SCANPREFIX`, `COUNT`, `DBSIZE`, `INFO`, `AUTH` and `COMPACT` go to
+//   every shard.
+//   Transactions are refused, since no shard can commit another's keys;
+//   an `MSET` that spans shards is applied shard by shard, not atomically.
+//
+//   A key's shard is the CRC-32 of its bytes modulo the shard count, so
+//   changing the number of shards moves most keys.
+// =====================================================================
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+use crate::{crc32, parse_command};
+
+/// The shards of a cluster and which one (if any) we are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterMap {
+    /// Shard addresses (`host:port`), by shard number.
+    pub shards: Vec<String>,
+
+    /// Our shard number; `None` for a router.
+    pub me: Option<usize>,
+}
+
+
+impl ClusterMap {
+    /// Build a map from a comma-separated address list.
+    ///
+    /// # Returns
+    /// `Err(message)` if the list is empty or `me` is not a shard number.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ClusterMap;
+    /// let map = ClusterMap::parse("10.0.0.1:6380, 10.0.0.2:6380", Some(1)).unwrap();
+    /// assert_eq!(map.shards, vec!["10.0.0.1:6380", "10.0.0.2:6380"]);
+    /// assert!(ClusterMap::parse("a:1", Some(3)).is_err());
+    /// ```
+    pub fn parse(list: &str, me: Option<usize>) -> Result<ClusterMap, String> {
+        let shards: Vec<String> = list.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect();
+        if shards.is_empty() {
+            return Err("cluster needs at least one shard address".to_string());
+        }
+        if let Some(me) = me
+            && me >= shards.len()
+        {
+            return Err(format!("shard {} is not in a cluster of {}", me, shards.len()));
+        }
+        Ok(ClusterMap { shards, me })
+    }
+
+
+    /// Shard number owning `key`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ClusterMap;
+    /// let map = ClusterMap::parse("a:1,b:2,c:3", None).unwrap();
+    /// assert_eq!(map.shard_of("user:42"), map.shard_of("user:42"));
+    /// assert!(map.shard_of("user:42") < 3);
+    /// ```
+    pub fn shard_of(&self, key: &str) -> usize {
+        crc32(key.as_bytes()) as usize % self.shards.len()
+    }
+
+
+    /// The `MOVED` error for the first of `keys` another shard owns.
+    ///
+    /// # Returns
+    /// `None` for a router, or when we own every key.
+    pub fn misrouted(&self, keys: &[&str]) -> Option<String> {
+        let me = self.me?;
+        let shard = keys.iter().map(|k| self.shard_of(k)).find(|&s| s != me)?;
+        Some(format!("MOVED {} {}", shard, self.shards[shard]))
+    }
+}
+
+
+/// Open connection to one shard.
+struct Link {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+
+/// How many reply lines a command sent to a shard produces.
+#[derive(Clone, Copy)]
+enum Reply {
+    /// Exactly this many lines, or one `ERR` line.
+    Lines(usize),
+
+    /// Lines up to and including `END`, or one `ERR` line.
+    UntilEnd,
+}
+
+
+/// Sends commands to the shards that own their keys.
+pub struct Router {
+    /// The cluster being routed to.
+    pub map: ClusterMap,
+
+    /// Connection per shard, opened on first use and after a failure.
+    links: Vec<Option<Link>>,
+}
+
+
+impl Router {
+    /// Creates a router for `map`; nothing connects until a command is run.
+    pub fn new(map: ClusterMap) -> Self {
+        let links = map.shards.iter().map(|_| None).collect();
+        Router { map, links }
+    }
+
+
+    /// Runs one command line across the cluster.
+    ///
+    /// # Returns
+    /// The reply lines, as a single server would print them.
+    pub fn execute(&mut self, line: &str) -> Vec<String> {
+        let (cmd, args) = match parse_command(line) {
+            Ok(parsed) => parsed,
+            Err(e) => return vec![format!("ERR {}", e)],
+        };
+
+        match cmd.as_str() {
+            "GET" | "SET" | "DEL" | "EXISTS" | "EXPIRE" | "EXPIREAT" | "PEXPIREAT" | "TTL" | "PERSIST" => {
+                let Some(key) = args.first() else {
+                    return vec![format!("ERR {} requires a key", cmd)];
+                };
+                let shard = self.map.shard_of(key);
+                self.ask(shard, line.trim(), Reply::Lines(1))
+            }
+            "MGET" if !args.is_empty() => self.mget(&args),
+            "MSET" if !args.is_empty() && args.len().is_multiple_of(2) => self.mset(&args),
+            "RANGE" | "SCANPREFIX" => {
+                let mut keys: Vec<String> = Vec::new();
+                for shard in 0..self.map.shards.len() {
+                    let reply = self.ask(shard, line.trim(), Reply::UntilEnd);
+                    if is_error(&reply) {
+                        return reply;
+                    }
+                    keys.extend(reply.into_iter().filter(|l| l != "END"));
+                }
+                keys.sort();
+                if args.get(2).is_some_and(|order| order.eq_ignore_ascii_case("DESC")) {
+                    keys.reverse();
+                }
+                keys.push("END".to_string());
+                keys
+            }
+            "COUNT" | "DBSIZE" => {
+                let mut total = 0u64;
+                for shard in 0..self.map.shards.len() {
+                    let reply = self.ask(shard, line.trim(), Reply::Lines(1));
+                    match reply.first().map(|r| r.parse::<u64>()) {
+                        Some(Ok(n)) => total += n,
+                        _ => return reply,
+                    }
+                }
+                vec![total.to_string()]
+            }
+            "INFO" | "STATS" => {
+                let mut out = Vec::new();
+                for shard in 0..self.map.shards.len() {
+                    out.push(format!("shard:{} {}", shard, self.map.shards[shard]));
+                    out.extend(self.ask(shard, line.trim(), Reply::UntilEnd).into_iter().filter(|l| l != "END"));
+                }
+                out.push("END".to_string());
+                out
+            }
+            "AUTH" | "COMPACT" | "SNAPSHOT" => {
+                let replies: Vec<Vec<String>> = (0..self.map.shards.len()).map(|s| self.ask(s, line.trim(), Reply::Lines(1))).collect();
+                if let Some(error) = replies.iter().find(|r| is_error(r)) {
+                    return error.clone();
+                }
+                // `COMPACT VALUES` answers with the bytes each shard reclaimed
+                if cmd == "COMPACT" && !args.is_empty() {
+                    let total: u64 = replies.iter().flatten().filter_map(|r| r.parse::<u64>().ok()).sum();
+                    return vec![total.to_string()];
+                }
+                vec!["OK".to_string()]
+            }
+            "BEGIN" | "COMMIT" | "ABORT" => vec!["ERR transactions are not supported in cluster mode".to_string()],
+            "MGET" | "MSET" => vec![format!("ERR wrong number of arguments for {}", cmd)],
+            _ => vec![format!("ERR unknown command '{}' in cluster mode", cmd)],
+        }
+    }
+
+
+    /// Splits an `MGET` by shard and puts the values back in key order.
+    fn mget(&mut self, keys: &[String]) -> Vec<String> {
+        let mut out = vec![String::new(); keys.len()];
+        for (shard, positions) in self.group(keys.len(), |i| &keys[i]) {
+            let line = format!("MGET {}", positions.iter().map(|&i| quote_arg(&keys[i])).collect::<Vec<_>>().join(" "));
+            let reply = self.ask(shard, &line, Reply::Lines(positions.len()));
+            if is_error(&reply) {
+                return reply;
+            }
+            for (pos, value) in positions.into_iter().zip(reply) {
+                out[pos] = value;
+            }
+        }
+        out
+    }
+
+
+    /// Splits an `MSET` by shard; each shard's part is applied on its own.
+    fn mset(&mut self, args: &[String]) -> Vec<String> {
+        for (shard, pairs) in self.group(args.len() / 2, |i| &args[2 * i]) {
+            let fields: Vec<String> = pairs.iter().flat_map(|&i| [quote_arg(&args[2 * i]), quote_arg(&args[2 * i + 1])]).collect();
+            let reply = self.ask(shard, &format!("MSET {}", fields.join(" ")), Reply::Lines(1));
+            if is_error(&reply) {
+                return reply;
+            }
+        }
+        vec!["OK".to_string()]
+    }
+
+
+    /// Positions `0..n`, grouped by the shard owning `key(i)`, in shard order.
+    fn group<'a>(&self, n: usize, key: impl Fn(usize) -> &'a String) -> Vec<(usize, Vec<usize>)> {
+        let mut groups: Vec<Vec<usize>> = vec![Vec::new(); self.map.shards.len()];
+        for i in 0..n {
+            groups[self.map.shard_of(key(i))].push(i);
+        }
+        groups.into_iter().enumerate().filter(|(_, g)| !g.is_empty()).collect()
+    }
+
+
+    /// Sends one line to a shard and reads its reply.
+    ///
+    /// A shard that can't be reached answers with an `ERR` line, and its
+    /// connection is reopened next time.
+    fn ask(&mut self, shard: usize, line: &str, reply: Reply) -> Vec<String> {
+        match self.try_ask(shard, line, reply) {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.links[shard] = None;
+                vec![format!("ERR shard {} ({}) unavailable: {}", shard, self.map.shards[shard], e)]
+            }
+        }
+    }
+
+
+    fn try_ask(&mut self, shard: usize, line: &str, reply: Reply) -> io::Result<Vec<String>> {
+        if self.links[shard].is_none() {
+            let writer = TcpStream::connect(&self.map.shards[shard])?;
+            let reader = BufReader::new(writer.try_clone()?);
+            self.links[shard] = Some(Link { writer, reader });
+        }
+        let Some(link) = &mut self.links[shard] else {
+            unreachable!("link was just opened");
+        };
+        link.writer.write_all(format!("{}\n", line).as_bytes())?;
+
+        let mut lines = Vec::new();
+        loop {
+            let mut text = String::new();
+            if link.reader.read_line(&mut text)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+            }
+            let text = text.trim_end_matches(['\r', '\n']).to_string();
+            let first_error = lines.is_empty() && text.starts_with("ERR");
+            let done = match reply {
+                Reply::Lines(n) => first_error || lines.len() + 1 == n,
+                Reply::UntilEnd => first_error || text == "END",
+            };
+            lines.push(text);
+            if done {
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+
+/// `true` if a shard answered with an error instead of a reply.
+fn is_error(reply: &[String]) -> bool {
+    reply.len() == 1 && reply[0].starts_with("ERR")
+}
+
+
+/// Quote an argument so the shard parses it back unchanged.
+///
+/// # Example
+/// ```
+/// use kvstore::quote_arg;
+/// assert_eq!(quote_arg("plain"), "plain");
+/// assert_eq!(quote_arg("two words"), "\"two words\"");
+/// assert_eq!(quote_arg(""), "\"\"");
+/// ```
+pub fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || c == '"') {
+        return arg.to_string();
+    }
+    let mut out = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+    out
+}
+
+
+/// Router REPL: read commands on stdin and print the cluster's replies.
+pub fn route_loop(router: &mut Router) {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            println!("ERR failed to read input");
+            break;
+        };
+        if matches!(line.trim().to_uppercase().as_str(), "EXIT" | "QUIT") {
+            println!("Exiting...");
+            break;
+        }
+        for reply in router.execute(&line) {
+            println!("{}", reply);
+        }
+    }
+}
+
+
+// =================================================================
+// cluster.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod cluster_tests {
+    use super::*;
+    use crate::{capture_replies, execute_line, serve, MemFs, ServerConfig, Session};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Start `n` shard servers that know the whole cluster.
+    fn start_cluster(n: usize) -> ClusterMap {
+        let listeners: Vec<TcpListener> = (0..n).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect();
+        let list: Vec<String> = listeners.iter().map(|l| l.local_addr().unwrap().to_string()).collect();
+        let map = ClusterMap::parse(&list.join(","), None).unwrap();
+        for (i, listener) in listeners.into_iter().enumerate() {
+            let mut session = Session::new();
+            session.fs = Arc::new(MemFs::new());
+            session.cluster = Some(ClusterMap { me: Some(i), ..map.clone() });
+            thread::spawn(move || serve(listener, session, ServerConfig::new()));
+        }
+        map
+    }
+
+    /// A key owned by `shard`.
+    fn key_on(map: &ClusterMap, shard: usize, prefix: &str) -> String {
+        (0..).map(|i| format!("{}{}", prefix, i)).find(|k| map.shard_of(k) == shard).unwrap()
+    }
+
+    #[test]
+    fn test_shards_refuse_keys_they_do_not_own() {
+        let map = ClusterMap::parse("127.0.0.1:7000,127.0.0.1:7001", Some(0)).unwrap();
+        let (ours, theirs) = (key_on(&map, 0, "k"), key_on(&map, 1, "k"));
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session.cluster = Some(map);
+
+        let (_, captured) = capture_replies(1024, || {
+            execute_line(format!("SET {} 1", ours).as_bytes(), &mut session);
+            execute_line(format!("GET {}", theirs).as_bytes(), &mut session);
+            execute_line(format!("MGET {} {}", ours, theirs).as_bytes(), &mut session);
+        });
+        assert_eq!(
+            String::from_utf8(captured.bytes).unwrap(),
+            "OK\nERR MOVED 1 127.0.0.1:7001\nERR MOVED 1 127.0.0.1:7001\n"
+        );
+    }
+
+    #[test]
+    fn test_router_fans_out_mget_mset_and_range() {
+        let map = start_cluster(3);
+        let keys: Vec<String> = (0..3).map(|s| key_on(&map, s, "key")).collect();
+        let mut router = Router::new(map);
+
+        let mset = format!("MSET {} a {} \"b c\" {} d", keys[0], keys[1], keys[2]);
+        assert_eq!(router.execute(&mset), vec!["OK"]);
+        assert_eq!(router.execute(&format!("MGET {} {} nope {}", keys[2], keys[1], keys[0])), vec!["d", "b c", "nil", "a"]);
+        assert_eq!(router.execute(&format!("DEL {}", keys[1])), vec!["1"]);
+
+        let mut expected = vec![keys[0].clone(), keys[2].clone()];
+        expected.sort();
+        expected.push("END".to_string());
+        assert_eq!(router.execute("RANGE - +"), expected);
+        assert_eq!(router.execute("DBSIZE"), vec!["2"]);
+        assert_eq!(router.execute("BEGIN"), vec!["ERR transactions are not supported in cluster mode"]);
+    }
+
+    #[test]
+    fn test_unreachable_shards_answer_errors() {
+        let mut router = Router::new(ClusterMap::parse("127.0.0.1:1", None).unwrap());
+        assert!(router.execute("GET a")[0].starts_with("ERR shard 0 (127.0.0.1:1) unavailable: "));
+    }
+
+    #[test]
+    fn test_quote_arg_round_trips_through_the_parser() {
+        for arg in ["plain", "two words", "", "q\"uote", "back\\slash", "tab\there"] {
+            let (_, args) = parse_command(&format!("MGET {}", quote_arg(arg))).unwrap();
+            assert_eq!(args, vec![arg]);
+        }
+    }
+}
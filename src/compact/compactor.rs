@@ -0,0 +1,346 @@
+// =====================================================================
+// File: compact/compactor.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`Compactor`] rewrites the log a few records at a time.
+//!
+//! A pass works like this:
+//! 1. `start` snapshots the sorted live keys and remembers where the log
+//!    currently ends.
+//! 2. Each `step` copies up to `budget` keys into `<log>.compact`,
+//!    reading their current value (cold values come from the old log)
+//!    and their TTL. Keys whose value is in the value log keep their
+//!    `VSET`, so large values are never copied. Keys deleted or expired
+//!    since the snapshot are skipped.
+//! 3. Once every key is copied, the records appended to the old log
+//!    during the pass are copied over verbatim, the new file replaces the
+//!    old one, and spilled value pointers are moved to the new offsets.
+//!
+//! Because the REPL is single-threaded, the final swap happens between
+//! two commands and no write can land in the middle of it.
+// =====================================================================
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use crate::{compress, storage};
+use crate::{BTreeIndex, Fs, Layout, LogRecord, RealFs, ReplayOp, SpillManager, TTLManager, ValueLog, ValuePointer};
+
+/// Records copied per tick unless configured otherwise.
+pub const DEFAULT_BUDGET: usize = 128;
+
+/// State of an in-progress compaction pass.
+#[derive(Debug)]
+struct Pass {
+    /// Log being compacted.
+    path: String,
+
+    /// Temporary file receiving the compacted log.
+    tmp_path: String,
+
+    /// File system holding both logs.
+    fs: Arc<dyn Fs>,
+
+    /// How the new log's records are written.
+    layout: Layout,
+
+    /// Live keys at the start of the pass, in sorted order.
+    keys: Vec<String>,
+
+    /// Index of the next key in `keys` to copy.
+    next: usize,
+
+    /// End of the old log when the pass started.
+    log_end: u64,
+
+    /// New value positions of the keys copied so far.
+    moved: Vec<(String, ValuePointer)>,
+}
+
+
+/// Incremental log compactor driven one bounded step per command.
+#[derive(Debug)]
+pub struct Compactor {
+    /// Maximum keys copied per `step`.
+    budget: usize,
+
+    /// Current pass, if one is running.
+    pass: Option<Pass>,
+
+    /// Number of passes finished since startup.
+    completed: u64,
+
+    /// When the last pass finished (Unix ms).
+    last_completed: Option<u64>,
+}
+
+
+impl Default for Compactor {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET)
+    }
+}
+
+
+impl Compactor {
+    /// Create an idle compactor that copies at most `budget` keys per step.
+    ///
+    /// A budget of zero is bumped to one so a pass always makes progress.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget: budget.max(1),
+            pass: None,
+            completed: 0,
+            last_completed: None,
+        }
+    }
+
+
+    /// Returns `true` while a pass is in progress.
+    pub fn is_running(&self) -> bool {
+        self.pass.is_some()
+    }
+
+
+    /// Keys copied and keys in total for the running pass.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        self.pass.as_ref().map(|p| (p.next, p.keys.len()))
+    }
+
+
+    /// Number of passes finished since startup.
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+
+    /// When the last pass finished (Unix ms), if one has since startup.
+    pub fn last_completed(&self) -> Option<u64> {
+        self.last_completed
+    }
+
+
+    /// Maximum keys copied per step.
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+
+    /// Change the per-step budget; a running pass uses it from its next step.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget.max(1);
+    }
+
+
+    /// Begin a new pass over the log at `path` on the real file system.
+    ///
+    /// # Returns
+    /// * `Ok(())` once the pass is set up.
+    /// * `Err(io::Error)` of kind `AlreadyExists` if a pass is already
+    ///   running, or any error from creating the temporary file.
+    pub fn start(&mut self, path: &str, index: &BTreeIndex) -> io::Result<()> {
+        self.start_with(Arc::new(RealFs), path, index)
+    }
+
+
+    /// Begin a new pass over the log at `path` on `fs`.
+    pub fn start_with(&mut self, fs: Arc<dyn Fs>, path: &str, index: &BTreeIndex) -> io::Result<()> {
+        if self.pass.is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "compaction already running"));
+        }
+
+        // The compacted log is a new data file, so it opens with the header,
+        // which carries the sequence numbers on past the records it drops
+        let tmp_path = storage::sidecar_path(path, "compact");
+        let header = storage::header_record_after(storage::last_seq(&*fs, path)?);
+        fs.create(&tmp_path)?;
+        fs.append(&tmp_path, &header)?;
+        let layout = fs.layout(&tmp_path)?;
+        let mut keys = Vec::new();
+        index.collect_keys(&mut keys);
+
+        self.pass = Some(Pass {
+            path: path.to_string(),
+            tmp_path,
+            log_end: fs.end(path)?,
+            fs,
+            layout,
+            keys,
+            next: 0,
+            moved: Vec::new(),
+        });
+        Ok(())
+    }
+
+
+    /// Abandon the running pass, if any, leaving the log untouched.
+    ///
+    /// # Returns
+    /// `true` if a pass was running.
+    pub fn cancel(&mut self) -> bool {
+        match self.pass.take() {
+            Some(pass) => {
+                let _ = pass.fs.remove(&pass.tmp_path);
+                true
+            }
+            None => false,
+        }
+    }
+
+
+    /// Copy up to `budget` keys, finishing the pass when all are copied.
+    ///
+    /// Does nothing when no pass is running. On error the pass is
+    /// abandoned and the old log is left untouched.
+    ///
+    /// # Returns
+    /// `Ok(true)` if this step finished the pass, otherwise `Ok(false)`.
+    pub fn step(
+        &mut self,
+        index: &BTreeIndex,
+        ttl: &TTLManager,
+        values: &ValueLog,
+        spill: Option<&mut SpillManager>,
+    ) -> io::Result<bool> {
+        let Some(pass) = &mut self.pass else {
+            return Ok(false);
+        };
+
+        let result = match pass.copy_keys(self.budget, index, ttl, values, spill.as_deref()) {
+            Ok(()) if pass.next == pass.keys.len() => pass.swap(spill).map(|_| true),
+            Ok(()) => Ok(false),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(false) => {}
+            Ok(true) => {
+                self.pass = None;
+                self.completed += 1;
+                self.last_completed = Some(crate::ttl::unix_now_ms());
+            }
+            Err(_) => {
+                if let Some(pass) = self.pass.take() {
+                    let _ = pass.fs.remove(&pass.tmp_path);
+                }
+            }
+        }
+        result
+    }
+}
+
+
+impl Pass {
+    /// Copy the next `budget` still-live keys into the new log.
+    fn copy_keys(
+        &mut self,
+        budget: usize,
+        index: &BTreeIndex,
+        ttl: &TTLManager,
+        values: &ValueLog,
+        spill: Option<&SpillManager>,
+    ) -> io::Result<()> {
+        let end = (self.next + budget).min(self.keys.len());
+        let mut batch: Vec<String> = Vec::new();
+        // Copied values, by their record's place in the batch
+        let mut copied = Vec::new();
+
+        for key in &self.keys[self.next..end] {
+            let Some(value) = index.get_bytes(key) else {
+                continue; // deleted since the snapshot
+            };
+            if ttl.get_expiration(key) == -2 {
+                continue;
+            }
+
+            // A separated value stays in the value log
+            let line = if let Some(value) = values.location(key) {
+                LogRecord::SetRef { key: key.clone(), value }.encode()
+            } else {
+                // Cold values still live in the old log
+                let value = match spill.filter(|s| s.is_cold(key)).and_then(|s| s.location(key)) {
+                    Some(ptr) => storage::read_value_with(&*self.fs, &self.path, ptr)?,
+                    None => value.to_vec(),
+                };
+                let line = storage::set_record_bytes(key, &value);
+                copied.push((batch.len(), key.clone(), line.clone()));
+                line
+            };
+            batch.push(storage::seal_record(&line));
+
+            // The TTL goes along, still as an absolute deadline
+            if let Some(at) = ttl.expires_at(key) {
+                batch.push(storage::seal_record(&storage::expire_at_record(key, at)));
+            }
+        }
+
+        // One append per step keeps syncs to one per command
+        let offsets = self.fs.append_many(&self.tmp_path, &batch)?;
+        for (at, key, line) in copied {
+            if let Some(ptr) = value_at(self.layout, offsets[at], &line) {
+                self.moved.push((key, ptr));
+            }
+        }
+        self.next = end;
+        Ok(())
+    }
+
+
+    /// Append writes made during the pass and replace the old log.
+    fn swap(&mut self, spill: Option<&mut SpillManager>) -> io::Result<()> {
+        // Tail records keep their stamps, so sequence numbers and times
+        // stay as they were appended
+        let mut tail = storage::ReplayIter::open(&*self.fs, &self.path, self.log_end)?;
+        let mut tail_moved: HashMap<String, ValuePointer> = HashMap::new();
+        let mut records = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(next) = tail.next() {
+            let (_, line) = next?;
+            let sealed = tail.sealed_line();
+            lines.push(match storage::unseal_stamped(&sealed) {
+                Some(_) => sealed.into_owned(),
+                None => storage::seal_record(&line), // from a log without checksums
+            });
+            records.push(line);
+        }
+        let offsets = self.fs.append_many(&self.tmp_path, &lines)?;
+        for (offset, line) in offsets.into_iter().zip(&records) {
+            if let (Some((key, _)), Some(ptr)) = (storage::parse_set_record(line), value_at(self.layout, offset, line)) {
+                tail_moved.insert(key, ptr);
+            }
+        }
+
+        // The new log must be durable before it replaces the old one. It
+        // holds every key, so cold segments the old one continued go
+        let segment = compress::first_segment(&*self.fs, &self.path)?;
+        storage::install_file(&*self.fs, &self.tmp_path, &self.path)?;
+        if let Some(newest) = segment {
+            compress::remove_segments(&*self.fs, &self.path, newest)?;
+        }
+
+        // Later writes win over the copied snapshot
+        if let Some(spill) = spill {
+            for (key, ptr) in self.moved.drain(..) {
+                spill.relocate(&key, ptr);
+            }
+            for (key, ptr) in tail_moved {
+                spill.relocate(&key, ptr);
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Where the value of the `SET` record `line` lands once it is appended
+/// at `offset` to a log laid out as `layout`.
+fn value_at(layout: Layout, offset: u64, line: &str) -> Option<ValuePointer> {
+    layout.decode(offset, line).into_iter().find_map(|op| match op {
+        ReplayOp::Set(_, _, ptr) => Some(ptr),
+        _ => None,
+    })
+}
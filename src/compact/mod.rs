@@ -0,0 +1,36 @@
+// =====================================================================
+// File: compact/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `compact` module shrinks the append-only log by rewriting it with
+//! only the latest value of each live key.
+//!
+//! Structure:
+//! - `compactor.rs` : Defines the [`Compactor`], which runs a pass in
+//!   bounded steps so compaction never stalls command handling.
+//! - `policy.rs`    : Defines [`CompactionPolicy`], when the session
+//!   starts a pass on its own.
+//! - `stats.rs`     : Defines [`StorageStats`], the log's size and dead
+//!   records, for deciding when to compact.
+//! - `tests.rs`     : Unit tests for stepping, concurrent writes and
+//!   the final swap.
+//!
+//! The session drives one step after every command (see
+//! [`Session::compaction_tick`](crate::Session::compaction_tick)); `COMPACT`
+//! or the session's [`CompactionPolicy`] starts a pass, `INFO` reports its
+//! progress and `STATS` whether one is worth running.
+// =====================================================================
+
+pub mod compactor;
+pub mod policy;
+pub mod stats;
+
+pub use self::compactor::{Compactor, DEFAULT_BUDGET};
+pub use self::policy::CompactionPolicy;
+pub use self::stats::StorageStats;
+
+#[cfg(test)]
+pub mod tests;
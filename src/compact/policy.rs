@@ -0,0 +1,86 @@
+// =====================================================================
+// File: compact/policy.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! A [`CompactionPolicy`] decides when the session starts a compaction
+//! pass on its own. It is checked after every command from the counts
+//! the session already keeps (records appended, live keys, the log's
+//! length), so deciding never scans the log; the pass it starts then
+//! runs in the same bounded steps as one `COMPACT` starts, between
+//! commands, so the REPL is never blocked on it.
+// =====================================================================
+
+use crate::loader::{COMPACT_DEAD_RATIO, COMPACT_MIN_WRITES};
+
+
+/// When to compact the log without being asked.
+///
+/// The default never does; set [`dead_ratio`](Self::dead_ratio),
+/// [`max_log_bytes`](Self::max_log_bytes) or both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactionPolicy {
+    /// Compact once at least this share (0.0 to 1.0) of the log's
+    /// records are dead: overwritten, deleted or expired.
+    pub dead_ratio: Option<f64>,
+
+    /// Compact once the log is at least this many bytes.
+    pub max_log_bytes: Option<u64>,
+
+    /// Fewest dead records before either trigger fires, so a log that is
+    /// big because its keys are live isn't rewritten over and over.
+    pub min_dead_records: u64,
+}
+
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self { dead_ratio: None, max_log_bytes: None, min_dead_records: COMPACT_MIN_WRITES / 2 }
+    }
+}
+
+
+impl CompactionPolicy {
+    /// Compact once `ratio` of the log's records are dead.
+    pub fn with_dead_ratio(ratio: f64) -> Self {
+        Self { dead_ratio: Some(ratio), ..Self::default() }
+    }
+
+
+    /// Compact once [`COMPACT_DEAD_RATIO`] of the log is dead, the same
+    /// threshold the startup report recommends a `COMPACT` at.
+    pub fn recommended() -> Self {
+        Self::with_dead_ratio(COMPACT_DEAD_RATIO)
+    }
+
+
+    /// `true` if either trigger is set.
+    pub fn is_enabled(&self) -> bool {
+        self.dead_ratio.is_some() || self.max_log_bytes.is_some()
+    }
+
+
+    /// Whether a log of `records` records, `live_keys` of them still
+    /// live, and `log_bytes` long is due a compaction.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::CompactionPolicy;
+    /// let policy = CompactionPolicy { max_log_bytes: Some(1 << 20), ..CompactionPolicy::recommended() };
+    /// assert!(policy.is_due(2000, 900, 4096));      // 55% dead
+    /// assert!(!policy.is_due(2000, 1100, 4096));    // 45% dead
+    /// assert!(policy.is_due(2000, 1100, 2 << 20));  // too big
+    /// assert!(!policy.is_due(1100, 1000, 2 << 20)); // too few dead records either way
+    /// ```
+    pub fn is_due(&self, records: u64, live_keys: u64, log_bytes: u64) -> bool {
+        let dead = records.saturating_sub(live_keys);
+        if dead == 0 || dead < self.min_dead_records {
+            return false;
+        }
+        let too_dead = self.dead_ratio.is_some_and(|ratio| dead as f64 / records as f64 >= ratio);
+        let too_big = self.max_log_bytes.is_some_and(|max| log_bytes >= max);
+        too_dead || too_big
+    }
+}
@@ -0,0 +1,144 @@
+// =====================================================================
+// File: compact/stats.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! [`StorageStats`] describe what the log holds right now: how big it
+//! is, how many of its records still back a live key, and when it was
+//! last compacted. Unlike the [`LoadReport`](crate::LoadReport), which
+//! is taken once at startup, they are gathered by scanning the log on
+//! request (`STATS`), so they count every write since.
+// =====================================================================
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+
+use crate::loader::{COMPACT_DEAD_RATIO, COMPACT_MIN_WRITES};
+use crate::storage::{self, ReplayIter, ReplayOp};
+use crate::{checkpoint, compress, Fs};
+
+
+/// Size and liveness of the log, for deciding when to compact.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageStats {
+    /// Bytes in the log, without preallocated padding.
+    pub log_bytes: u64,
+
+    /// Records in the log; the header and blank lines don't count.
+    pub records: u64,
+
+    /// Keys live in the session.
+    pub live_keys: usize,
+
+    /// Records that no longer back a live key: overwritten, deleted,
+    /// expired or unreadable. A compaction would drop them.
+    pub dead_records: u64,
+
+    /// When the session last finished a compaction (Unix ms); `None` if
+    /// it has not since it started.
+    pub last_compaction: Option<u64>,
+}
+
+
+impl StorageStats {
+    /// Scan the log at `path` for its size and dead records.
+    ///
+    /// A record is live if it holds the latest value of a key for which
+    /// `is_live` is `true`, or that key's latest TTL, or it is the
+    /// `CHECKPOINT` or `SEGMENT` the log continues from. Only the live
+    /// log is scanned, not the cold segments behind it. `live_keys` and
+    /// `last_compaction` are left for the caller.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` if the log cannot be read.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{log_text, Fs, MemFs, StorageStats};
+    /// let fs = MemFs::new();
+    /// fs.append("log", &log_text(&["SET a 1", "SET b 2", "SET a 3", "DEL b"])).unwrap();
+    /// let stats = StorageStats::scan(&fs, "log", |key| key == "a").unwrap();
+    /// assert_eq!((stats.records, stats.dead_records), (4, 3));
+    /// ```
+    pub fn scan(fs: &dyn Fs, path: &str, is_live: impl Fn(&str) -> bool) -> io::Result<Self> {
+        let mut records = 0;
+        let mut kept = HashSet::new();
+        // Record number of each key's latest value and TTL
+        let mut values: HashMap<String, u64> = HashMap::new();
+        let mut ttls: HashMap<String, u64> = HashMap::new();
+        for next in ReplayIter::open(fs, path, 0)? {
+            let (offset, line) = next?;
+            let n = records;
+            records += 1;
+            if checkpoint::parse_checkpoint_record(&line).is_some() || compress::parse_segment_record(&line).is_some() {
+                kept.insert(n);
+                continue;
+            }
+            for op in storage::decode_record(offset, &line) {
+                match op {
+                    ReplayOp::Set(key, _, _) | ReplayOp::SetRef(key, _) => {
+                        values.insert(key, n);
+                    }
+                    ReplayOp::ExpireAt(key, _) => {
+                        ttls.insert(key, n);
+                    }
+                    ReplayOp::Del(key) => {
+                        values.remove(&key);
+                        ttls.remove(&key);
+                    }
+                    ReplayOp::Persist(key) => {
+                        ttls.remove(&key);
+                    }
+                }
+            }
+        }
+        for (key, n) in values.iter().chain(&ttls) {
+            if is_live(key) {
+                kept.insert(*n);
+            }
+        }
+
+        Ok(Self {
+            log_bytes: fs.end(path)?,
+            records,
+            dead_records: records - kept.len() as u64,
+            ..Self::default()
+        })
+    }
+
+
+    /// Share of records a compaction would drop, from 0.0 to 1.0.
+    pub fn dead_ratio(&self) -> f64 {
+        if self.records == 0 {
+            return 0.0;
+        }
+        self.dead_records as f64 / self.records as f64
+    }
+
+
+    /// `true` once the log is big enough and mostly dead records, by the
+    /// same thresholds as the startup report.
+    pub fn compaction_recommended(&self) -> bool {
+        self.records >= COMPACT_MIN_WRITES && self.dead_ratio() >= COMPACT_DEAD_RATIO
+    }
+}
+
+
+impl fmt::Display for StorageStats {
+    /// One `field:value` line per stat, as `STATS` prints them.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "log_bytes:{}", self.log_bytes)?;
+        writeln!(f, "log_records:{}", self.records)?;
+        writeln!(f, "live_keys:{}", self.live_keys)?;
+        writeln!(f, "dead_records:{}", self.dead_records)?;
+        writeln!(f, "dead_ratio:{:.2}", self.dead_ratio())?;
+        match self.last_compaction {
+            Some(at) => writeln!(f, "last_compaction:{}", at)?,
+            None => writeln!(f, "last_compaction:never")?,
+        }
+        write!(f, "compaction:{}", if self.compaction_recommended() { "recommended" } else { "not_needed" })
+    }
+}
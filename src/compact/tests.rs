@@ -0,0 +1,262 @@
+// =====================================================================
+// File: compact/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for incremental log compaction, the policy that starts
+//   one on its own, and the storage stats that say when one is worth
+//   running.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Compactor Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod compactor_tests {
+    use crate::{append_write_at, read_value, replay_log, BTreeIndex, Compactor, SpillManager,
+        TTLManager, ValueLog, ValuePointer};
+    use std::fs;
+
+    fn log_file(name: &str) -> String {
+        let p = std::env::temp_dir().join(format!("kvstore_compact_{}.db", name));
+        let path = p.to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Write `SET` records to `path` and mirror them into `index`.
+    fn write(path: &str, index: &mut BTreeIndex, key: &str, value: &str) -> ValuePointer {
+        let line = format!("SET {} {}", key, value);
+        let offset = append_write_at(path, &line).unwrap();
+        index.insert(key.into(), value.into());
+        ValuePointer::for_record(offset, &line, value)
+    }
+
+    #[test]
+    fn pass_runs_in_bounded_steps() {
+        let path = log_file("steps");
+        let mut index = BTreeIndex::new(2);
+        let ttl = TTLManager::new();
+        for i in 0..5 {
+            write(&path, &mut index, &format!("k{i}"), "old");
+            write(&path, &mut index, &format!("k{i}"), "new");
+        }
+
+        let mut compactor = Compactor::new(2);
+        compactor.start(&path, &index).unwrap();
+        assert!(!compactor.step(&index, &ttl, &ValueLog::default(), None).unwrap());
+        assert_eq!(compactor.progress(), Some((2, 5)));
+        assert!(!compactor.step(&index, &ttl, &ValueLog::default(), None).unwrap());
+        assert!(compactor.step(&index, &ttl, &ValueLog::default(), None).unwrap());
+
+        assert!(!compactor.is_running());
+        assert_eq!(compactor.completed(), 1);
+        assert!(compactor.last_completed().is_some());
+        let records = replay_log(&path).unwrap();
+        assert_eq!(records.len(), 5);
+        assert!(records.iter().all(|r| r.ends_with(" new")));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn writes_during_pass_are_kept() {
+        let path = log_file("concurrent");
+        let mut index = BTreeIndex::new(2);
+        let mut ttl = TTLManager::new();
+        write(&path, &mut index, "a", "1");
+        write(&path, &mut index, "b", "2");
+        write(&path, &mut index, "gone", "x");
+
+        let mut compactor = Compactor::new(1);
+        compactor.start(&path, &index).unwrap();
+        compactor.step(&index, &ttl, &ValueLog::default(), None).unwrap();
+
+        // Mutations between ticks
+        write(&path, &mut index, "a", "10");
+        write(&path, &mut index, "c", "3");
+        index.delete("gone");
+        ttl.set_expiration("b", 1);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        while !compactor.step(&index, &ttl, &ValueLog::default(), None).unwrap() {}
+
+        let records = replay_log(&path).unwrap();
+        assert_eq!(records, vec!["SET a 1", "SET a 10", "SET c 3"]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn writes_during_pass_keep_their_stamps() {
+        let path = log_file("stamps");
+        let mut index = BTreeIndex::new(2);
+        let ttl = TTLManager::new();
+        write(&path, &mut index, "a", "1");
+
+        let mut compactor = Compactor::new(1);
+        compactor.start(&path, &index).unwrap();
+        let at = |seq, unix_ms| crate::Stamp { seq, unix_ms };
+        let tail = [crate::seal_record_at("SET a 2", at(7, 1000)), crate::seal_record_at("SET b 3", at(8, 2000))];
+        for line in &tail {
+            append_write_at(&path, line).unwrap();
+        }
+        index.insert("a".into(), "2".into());
+        index.insert("b".into(), "3".into());
+
+        while !compactor.step(&index, &ttl, &ValueLog::default(), None).unwrap() {}
+
+        let header = format!("{}\n", crate::header_record());
+        let framed = crate::log_bytes(&format!("{}{}", header, tail.join("\n"))).unwrap();
+        assert!(fs::read(&path).unwrap().ends_with(&framed[header.len()..]));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn spilled_pointers_follow_the_new_log() {
+        let path = log_file("spill");
+        let mut index = BTreeIndex::new(2);
+        let ttl = TTLManager::new();
+        let mut spill = SpillManager::key_only();
+        for (k, v) in [("x", "old"), ("x", "xval"), ("y", "yval")] {
+            let ptr = write(&path, &mut index, k, v);
+            spill.record_write(k, ptr);
+            index.get_bytes_mut(k).unwrap().clear();
+        }
+
+        let mut compactor = Compactor::new(8);
+        compactor.start(&path, &index).unwrap();
+        assert!(compactor.step(&index, &ttl, &ValueLog::default(), Some(&mut spill)).unwrap());
+
+        assert_eq!(read_value(&path, spill.location("x").unwrap()).unwrap(), b"xval");
+        assert_eq!(read_value(&path, spill.location("y").unwrap()).unwrap(), b"yval");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn second_start_is_rejected() {
+        let path = log_file("twice");
+        let index = BTreeIndex::new(2);
+        let mut compactor = Compactor::new(1);
+        compactor.start(&path, &index).unwrap();
+        assert!(compactor.start(&path, &index).is_err());
+        let _ = fs::remove_file(format!("{}.compact", path));
+    }
+}
+
+
+// =====================================================================
+// Storage Stats Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod stats_tests {
+    use crate::{log_text, Fs, MemFs, StorageStats};
+
+    #[test]
+    fn only_records_backing_live_keys_count_as_live() {
+        let fs = MemFs::new();
+        let records = [
+            "CHECKPOINT 3",
+            "SET a 1",
+            "MSET b 2 c 3",
+            "EXPIREAT b 99999999999999",
+            "EXPIREAT c 99999999999999",
+            "PERSIST c",
+            "SET a 4",
+            "NOPE",
+        ];
+        fs.append("log", &log_text(&records)).unwrap();
+
+        // Live: the checkpoint, the MSET (b and c), b's TTL and a's latest value
+        let stats = StorageStats::scan(&fs, "log", |key| ["a", "b", "c"].contains(&key)).unwrap();
+        assert_eq!(stats.records, 8);
+        assert_eq!(stats.dead_records, 4);
+        assert_eq!(stats.log_bytes, fs.end("log").unwrap());
+        assert_eq!(stats.dead_ratio(), 0.5);
+        assert!(!stats.compaction_recommended(), "too few records to bother");
+
+        // Keys that expired or are otherwise gone take their records with them
+        let stats = StorageStats::scan(&fs, "log", |key| key == "a").unwrap();
+        assert_eq!(stats.dead_records, 6);
+
+        let missing = StorageStats::scan(&fs, "missing", |_| true).unwrap();
+        assert_eq!(missing, StorageStats::default());
+    }
+
+    #[test]
+    fn stats_print_one_field_per_line() {
+        let stats = StorageStats { log_bytes: 10, records: 2000, live_keys: 1, dead_records: 1999, last_compaction: Some(5) };
+        assert_eq!(
+            stats.to_string(),
+            "log_bytes:10\nlog_records:2000\nlive_keys:1\ndead_records:1999\ndead_ratio:1.00\nlast_compaction:5\ncompaction:recommended"
+        );
+        assert!(StorageStats::default().to_string().contains("last_compaction:never\n"));
+    }
+}
+
+
+// =====================================================================
+// Compaction Policy Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod policy_tests {
+    use crate::{apply_setting, CompactionPolicy, MemFs, Session};
+    use std::sync::Arc;
+
+    #[test]
+    fn the_default_policy_never_compacts() {
+        let policy = CompactionPolicy::default();
+        assert!(!policy.is_enabled());
+        assert!(!policy.is_due(1_000_000, 0, u64::MAX));
+
+        let size_only = CompactionPolicy { max_log_bytes: Some(100), min_dead_records: 0, ..CompactionPolicy::default() };
+        assert!(size_only.is_due(10, 9, 100));
+        assert!(!size_only.is_due(10, 9, 99));
+        assert!(!size_only.is_due(10, 10, 1000), "a log with nothing dead is left alone");
+    }
+
+    #[test]
+    fn sessions_compact_on_their_own_between_commands() {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session.compactor.set_budget(2);
+        apply_setting(&mut session, "auto_compact_ratio", "0.5").unwrap();
+        session.compaction_policy.min_dead_records = 10;
+
+        // Ten dead records of fourteen: the tenth overwrite trips it
+        for i in 0..4 {
+            session.set(format!("k{}", i), "first".into());
+        }
+        for i in 0..9 {
+            session.set(format!("k{}", i % 4), format!("v{}", i));
+            session.tick().unwrap();
+            assert!(!session.compactor.is_running(), "not due after {} overwrites", i + 1);
+        }
+        session.set("k0".into(), "last".into());
+        session.tick().unwrap();
+        assert!(session.compactor.is_running());
+
+        // The pass runs a bounded step per command, and isn't started again
+        while session.compactor.is_running() {
+            session.tick().unwrap();
+        }
+        assert_eq!(session.compactor.completed(), 1);
+        let stats = session.storage_stats().unwrap();
+        assert_eq!((stats.records, stats.dead_records), (4, 0));
+        assert_eq!(session.get("k0"), Some("last".to_string()));
+        session.tick().unwrap();
+        assert!(!session.compactor.is_running());
+
+        assert!(apply_setting(&mut session, "auto_compact_ratio", "1.5").is_err());
+        apply_setting(&mut session, "auto_compact_ratio", "off").unwrap();
+        apply_setting(&mut session, "auto_compact_bytes", "4096").unwrap();
+        assert_eq!(session.compaction_policy.max_log_bytes, Some(4096));
+        assert_eq!(session.compaction_policy.dead_ratio, None);
+    }
+}
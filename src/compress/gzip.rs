@@ -0,0 +1,48 @@
+// =====================================================================
+// File: compress/gzip.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   The gzip container (RFC 1952), from the `flate2` crate. `gunzip`
+//   checks the CRC-32 and length in the trailer, so a damaged file is
+//   never taken as whole.
+// =====================================================================
+
+use std::io::{self, Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+
+/// Compress `data` into a gzip file's bytes.
+///
+/// # Example
+/// ```
+/// use kvstore::compress::{gunzip, gzip};
+/// let packed = gzip(b"hello hello hello");
+/// assert_eq!(packed[..2], [0x1f, 0x8b]);
+/// assert_eq!(gunzip(&packed).unwrap(), b"hello hello hello");
+/// ```
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec cannot fail
+    encoder.write_all(data).and_then(|()| encoder.finish()).expect("gzip into memory")
+}
+
+
+/// Decompress a gzip file's bytes (the first member).
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidData` if it is not gzip, is cut
+/// short, or fails its CRC or length check.
+pub fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut out)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("gzip: {}", e)))?;
+    Ok(out)
+}
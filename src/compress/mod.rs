@@ -0,0 +1,33 @@
+// =====================================================================
+// File: compress/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `compress` module shrinks checkpoint snapshots and rotated log
+//! segments with gzip.
+//!
+//! Structure:
+//! - `gzip.rs`    : [`gzip`] and [`gunzip`], from the `flate2` crate.
+//! - `segment.rs` : [`rotate_log`], which moves the log into a compressed
+//!   cold segment, and [`read_segment`], which replay reads it back with.
+//! - `tests.rs`   : Unit tests for round trips, streams other tools
+//!   wrote, damaged input, compressed snapshots and rotated logs.
+//!
+//! Snapshots are written compressed and read either way (see
+//! [`crate::checkpoint`]). The live log stays uncompressed, since it is
+//! appended to; once a session rotates it (every `KVSTORE_SEGMENT_BYTES`
+//! bytes), the old records only ever need to be read back in full, so
+//! they are kept compressed.
+// =====================================================================
+
+pub mod gzip;
+pub mod segment;
+
+pub use self::gzip::{gunzip, gzip};
+pub use self::segment::{first_segment, parse_segment_record, read_segment, remove_segments, rotate_log, segment_path,
+    segment_record};
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,163 @@
+// =====================================================================
+// File: compress/segment.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! Cold segments: the log rotated out of the way, gzip-compressed.
+//!
+//! Rotating a log takes two steps, each a file replaced atomically (see
+//! [`storage::replace_file`]):
+//! 1. `<log>.<id>.gz` gets the log's bytes as they are on disk (header,
+//!    checksums, stamps and encrypted records included), compressed.
+//! 2. The log is replaced by its header and a `SEGMENT <id>` record;
+//!    later writes are appended after them as usual.
+//!
+//! A log that starts with `SEGMENT <id>` continues that segment, which
+//! may in turn start with the `SEGMENT` record of the one rotated before
+//! it, and so on back to a log that is complete or continues a
+//! checkpoint. Replay reads the chain oldest first, decompressing each
+//! segment in memory (see [`crate::load_base`]), so a rotated log loads
+//! exactly as it did before. A crash between the two renames leaves an
+//! unreferenced segment that the next rotation overwrites.
+//!
+//! Checkpoints, compaction and folding write a log that needs no
+//! segment, and remove the chain the old log pointed at.
+// =====================================================================
+
+use std::io;
+
+use super::gzip::{gunzip, gzip};
+use crate::storage::{self, LogRecord};
+use crate::Fs;
+
+
+/// Path of cold segment `id` of `data_file`, `<path>.<id>.gz`.
+///
+/// # Example
+/// ```
+/// use kvstore::segment_path;
+/// assert_eq!(segment_path("data.db", 3), "data.db.3.gz");
+/// ```
+pub fn segment_path(data_file: &str, id: u64) -> String {
+    storage::sidecar_path(data_file, &format!("{}.gz", id))
+}
+
+
+/// The record a log rotated into segment `id` starts with.
+///
+/// # Example
+/// ```
+/// use kvstore::{parse_segment_record, segment_record};
+/// assert_eq!(segment_record(2), "SEGMENT 2");
+/// assert_eq!(parse_segment_record("SEGMENT 2"), Some(2));
+/// assert_eq!(parse_segment_record("CHECKPOINT 2"), None);
+/// ```
+pub fn segment_record(id: u64) -> String {
+    LogRecord::Segment { id }.encode()
+}
+
+
+/// The segment id in a `SEGMENT <id>` record.
+pub fn parse_segment_record(record: &str) -> Option<u64> {
+    match LogRecord::decode(record)? {
+        LogRecord::Segment { id } => Some(id),
+        _ => None,
+    }
+}
+
+
+/// Compress the log at `data_file` into its next cold segment and start
+/// the log over with a `SEGMENT` record pointing at it.
+///
+/// The caller must not append to the log meanwhile; the session rotates
+/// between commands.
+///
+/// # Returns
+/// * `Ok(id)` of the new segment.
+/// * `Err(io::Error)` of kind `InvalidData` if the log has a torn tail
+///   (cut it off first, see [`crate::recover_log_with`]), or any error
+///   from reading or writing either file. If the segment was written but
+///   the log was not replaced, the log still loads the same.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_text, replay_records, rotate_log, segment_path, Fs, MemFs};
+/// let fs = MemFs::new();
+/// fs.append("log", &log_text(&["SET a 1"])).unwrap();
+/// assert_eq!(rotate_log(&fs, "log").unwrap(), 1);
+/// assert!(fs.contents(&segment_path("log", 1)).unwrap().starts_with(&[0x1f, 0x8b]));
+/// assert_eq!(replay_records(&fs, "log", 0).unwrap()[0].1, "SEGMENT 1");
+/// ```
+pub fn rotate_log(fs: &dyn Fs, data_file: &str) -> io::Result<u64> {
+    let bytes = fs.read(data_file)?;
+    let (records, torn_at) = storage::parse_records(&bytes, 0, storage::log_version(&bytes)?, fs.log_key())?;
+    if let Some(at) = torn_at {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is torn at byte {}", data_file, at)));
+    }
+    let id = records.first().and_then(|(_, record)| parse_segment_record(record)).map_or(1, |newest| newest + 1);
+    storage::replace_file_bytes(fs, &segment_path(data_file, id), &gzip(&bytes))?;
+
+    // Only now that the segment is durable can the log lose its records
+    let last_seq = storage::last_seq(fs, data_file)?;
+    storage::replace_file(fs, data_file, &storage::log_text_after(&[segment_record(id)], last_seq))?;
+    Ok(id)
+}
+
+
+/// The records of cold segment `id` of `data_file` and of every segment
+/// before it, oldest first, as [`crate::replay_records`] returns them.
+/// Offsets are those within each segment.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidData` if a segment of the chain is
+/// missing, is not gzip, or holds a record that fails its checksum.
+pub fn read_segment(fs: &dyn Fs, data_file: &str, id: u64) -> io::Result<Vec<(u64, String)>> {
+    let mut chain = Vec::new();
+    let mut next = Some(id);
+    while let Some(id) = next {
+        let path = segment_path(data_file, id);
+        let damaged = |what: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, what));
+        let packed = match fs.read(&path) {
+            Ok(packed) => packed,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(damaged(format!("is missing, but {} continues it", data_file)));
+            }
+            Err(e) => return Err(e),
+        };
+        let bytes = gunzip(&packed).map_err(|e| damaged(e.to_string()))?;
+        let (mut records, torn_at) = storage::parse_records(&bytes, 0, storage::log_version(&bytes)?, fs.log_key())?;
+        if let Some(at) = torn_at {
+            return Err(damaged(format!("record at byte {} fails its checksum", at)));
+        }
+        next = records.first().and_then(|(_, record)| parse_segment_record(record));
+        if next.is_some() {
+            records.remove(0);
+        }
+        chain.push(records);
+    }
+    Ok(chain.into_iter().rev().flatten().collect())
+}
+
+
+/// The newest cold segment the log at `data_file` continues, if any.
+pub fn first_segment(fs: &dyn Fs, data_file: &str) -> io::Result<Option<u64>> {
+    match storage::ReplayIter::open(fs, data_file, 0)?.next() {
+        Some(first) => Ok(parse_segment_record(&first?.1)),
+        None => Ok(None),
+    }
+}
+
+
+/// Remove segment `newest` and every segment before it, once the log no
+/// longer continues them. Segments already gone are skipped.
+pub fn remove_segments(fs: &dyn Fs, data_file: &str, newest: u64) -> io::Result<()> {
+    for id in (1..=newest).rev() {
+        match fs.remove(&segment_path(data_file, id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
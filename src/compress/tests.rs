@@ -0,0 +1,251 @@
+// =====================================================================
+// File: compress/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for gzip round trips, for reading streams another gzip
+//   wrote, for damaged input, for compressed snapshots, and for logs
+//   rotated into cold segments.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// gzip Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod gzip_tests {
+    use crate::compress::{gunzip, gzip};
+    use crate::crypt::base64_decode;
+
+    /// Bytes that barely repeat, from a small linear congruential generator.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 12345u32;
+        (0..len).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn round_trips_any_input() {
+        let long_run = vec![b'x'; 1000];
+        let far_repeat = [noise(40_000), noise(40_000)].concat();
+        let inputs: [&[u8]; 6] = [b"", b"a", b"abcabcabcabcabc", &long_run, &noise(5000), &far_repeat];
+        for input in inputs {
+            assert_eq!(gunzip(&gzip(input)).unwrap(), input, "{} bytes", input.len());
+        }
+        assert!(gzip(&long_run).len() < 40, "runs compress");
+    }
+
+    #[test]
+    fn reads_what_other_gzip_tools_write() {
+        // Python's gzip.compress of 40 SET records: dynamic Huffman codes
+        let packed = base64_decode(
+            "H4sIAAAAAAACA23TO05DQRBE0dyr8BKmqudLzgrs8CUEJrOReGL/iABNSdVp3+yo6/Z+v/6cj++3cn19PB/H+fV5nH+H4yyX23+ExbEjLaLuGlaJXavXuWuzGm3XbrVy1+F17Tqttr7rstpDMJxqqFWCJVpwrilccK8lXnCwJWBwMRQhg5sBgoaRdGGDu4ECB5dDCB1L8itiRyRdXy35tSZ6dD104WNNuvgx8Rvix8Rvih8Tvyl+TPyW+NH9WMQvSrIm8QskXfzC/Ugda7LWEL9I9hriF+7HKn7hfmziFyPp4hfuxy5+kfiNuPwCi4n9C+UEAAA=",
+        ).unwrap();
+        let expected: String = (0..40).map(|i| format!("SET user:{} name\\sof\\suser\\s{}\n", i, i * 7)).collect();
+        assert_eq!(gunzip(&packed).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn damaged_input_is_an_error() {
+        let packed = gzip(b"some text that compresses some text");
+        let mut flipped = packed.clone();
+        let last = flipped.len() - 5;
+        flipped[last] ^= 0xff;
+        assert!(gunzip(&flipped).is_err(), "length check");
+        let mut flipped = packed.clone();
+        flipped[12] ^= 0x10;
+        assert!(gunzip(&flipped).is_err(), "stream or CRC check");
+        assert!(gunzip(&packed[..packed.len() - 1]).is_err());
+        assert!(gunzip(b"not gzip at all, not at all").is_err());
+    }
+}
+
+
+// =====================================================================
+// Compressed Snapshot Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod snapshot_tests {
+    use crate::{checkpoint_path, log_text, read_checkpoint, seal_record, set_record, set_record_bytes, write_checkpoint, Fs, MemFs};
+
+    #[test]
+    fn snapshots_are_written_compressed() {
+        let fs = MemFs::new();
+        fs.append("log", &log_text(&["SET a 1"])).unwrap();
+        let pairs: Vec<(String, Vec<u8>)> = (0..500).map(|i| (format!("user:{:04}", i), format!("profile of user {}", i).into_bytes())).collect();
+        write_checkpoint(&fs, "log", &pairs, &[]).unwrap();
+
+        let snapshot = String::from_utf8(fs.contents(&checkpoint_path("log")).unwrap()).unwrap();
+        assert!(snapshot.starts_with("KVSNAP 1 500 gzip\n"));
+        let plain: usize = pairs.iter().map(|(k, v)| seal_record(&set_record_bytes(k, v)).len() + 1).sum();
+        assert!(snapshot.len() < plain / 2, "{} of {} bytes", snapshot.len(), plain);
+        assert_eq!(read_checkpoint(&fs, "log").unwrap().unwrap().pairs, pairs);
+
+        // The next id still comes from the header
+        assert_eq!(write_checkpoint(&fs, "log", &pairs[..1], &[]).unwrap(), 2);
+    }
+
+    #[test]
+    fn uncompressed_snapshots_still_load() {
+        let fs = MemFs::new();
+        let text = format!("KVSNAP 4 2\n{}\n{}", seal_record(&set_record("a", "1")), seal_record(&set_record("b", "two words")));
+        fs.append(&checkpoint_path("log"), &text).unwrap();
+        let checkpoint = read_checkpoint(&fs, "log").unwrap().unwrap();
+        assert_eq!(checkpoint.id, 4);
+        assert_eq!(checkpoint.pairs, [("a".to_string(), b"1".to_vec()), ("b".to_string(), b"two words".to_vec())]);
+
+        fs.write_file(&checkpoint_path("log"), b"KVSNAP 5 1 gzip\nH4sI\n");
+        assert!(read_checkpoint(&fs, "log").is_err());
+    }
+}
+
+
+// =====================================================================
+// Cold Segment Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod segment_tests {
+    use std::sync::Arc;
+
+    use crate::compress::gunzip;
+    use crate::{compact_log_with, log_text, read_segment, repair_log_with, replay_records, rotate_log, segment_path,
+        try_load_data, Fs, Limits, MemFs, Session};
+
+    const LOG: &str = "segmented.db";
+
+    fn session_on(fs: &Arc<MemFs>) -> Session {
+        let mut session = Session::ephemeral();
+        session.fs = fs.clone();
+        session.data_file = LOG.to_string();
+        session
+    }
+
+    fn reload(fs: &Arc<MemFs>) -> Session {
+        let mut session = session_on(fs);
+        try_load_data(&mut session, LOG).unwrap();
+        session
+    }
+
+    fn records(fs: &MemFs) -> Vec<String> {
+        replay_records(fs, LOG, 0).unwrap().into_iter().map(|(_, record)| record).collect()
+    }
+
+    #[test]
+    fn old_segments_are_compressed_on_rotation_and_replayed() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.segment_bytes = 4096;
+        for i in 0..600 {
+            session.set(format!("user:{:04}", i % 250), format!("profile {} of user {}", i / 250, i % 250));
+            if i % 7 == 0 {
+                session.delete(&format!("user:{:04}", (i + 3) % 250));
+            }
+            session.tick().unwrap();
+        }
+        session.expire_at("user:0001", 4_000_000_000_000).unwrap();
+
+        // Every segment is gzip, far smaller than the log it was, and
+        // starts with the one before it
+        let newest = records(&fs)[0].strip_prefix("SEGMENT ").unwrap().parse::<u64>().unwrap();
+        assert!(newest >= 3, "rotated {} times", newest);
+        for id in 1..=newest {
+            let packed = fs.contents(&segment_path(LOG, id)).unwrap();
+            let plain = gunzip(&packed).unwrap();
+            assert!(plain.len() >= 4096 && packed.len() < plain.len() / 3, "{} of {} bytes", packed.len(), plain.len());
+        }
+        assert!(fs.contents(&segment_path(LOG, newest + 1)).is_none());
+        let cold = read_segment(&*fs, LOG, newest).unwrap();
+        assert!(cold.iter().all(|(_, record)| !record.starts_with("SEGMENT")));
+
+        let reopened = reload(&fs);
+        let (mut keys, mut reloaded) = (Vec::new(), Vec::new());
+        session.index.collect_keys(&mut keys);
+        reopened.index.collect_keys(&mut reloaded);
+        assert_eq!(reloaded, keys);
+        for key in &keys {
+            assert_eq!(reopened.index.get_bytes(key), session.index.get_bytes(key), "{}", key);
+        }
+        assert!(reopened.ttl_status("user:0001") > 0);
+        assert_eq!(reopened.load_report.unwrap().records as usize, cold.len() + records(&fs).len() - 1);
+    }
+
+    #[test]
+    fn rotation_keeps_the_checkpoint_behind_it() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.checkpoint().unwrap();
+        session.set("b".into(), "2".into());
+        assert_eq!(session.rotate_log(), Ok(1));
+        session.set("c".into(), "3".into());
+        assert_eq!(session.rotate_log(), Ok(2));
+        assert_eq!(records(&fs), ["SEGMENT 2"]);
+
+        let reopened = reload(&fs);
+        assert_eq!(reopened.checkpoint_id, 1);
+        for (key, value) in [("a", "1"), ("b", "2"), ("c", "3")] {
+            assert_eq!(reopened.index.search(key), Some(value));
+        }
+
+        // Offline repair reads through the segments too
+        let report = repair_log_with(&*fs, LOG, &Limits::default()).unwrap();
+        assert_eq!((report.keys_kept, report.dropped.len()), (3, 0));
+    }
+
+    #[test]
+    fn rewrites_remove_the_segments() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.rotate_log().unwrap();
+        session.set("b".into(), "2".into());
+        session.rotate_log().unwrap();
+
+        assert_eq!(session.checkpoint(), Ok(1));
+        assert!(fs.contents(&segment_path(LOG, 1)).is_none() && fs.contents(&segment_path(LOG, 2)).is_none());
+        assert_eq!(reload(&fs).index.search("b"), Some("2"));
+
+        // Compaction folds them back into the log
+        session.set("c".into(), "3".into());
+        assert_eq!(session.rotate_log(), Ok(1));
+        assert_eq!(compact_log_with(&*fs, LOG).unwrap(), 3);
+        assert!(fs.contents(&segment_path(LOG, 1)).is_none());
+        assert_eq!(records(&fs), ["SET a 1", "SET b 2", "SET c 3"]);
+    }
+
+    #[test]
+    fn a_missing_segment_leaves_the_session_read_only() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.rotate_log().unwrap();
+        session.set("b".into(), "2".into());
+
+        fs.remove(&segment_path(LOG, 1)).unwrap();
+        let mut reopened = session_on(&fs);
+        assert!(try_load_data(&mut reopened, LOG).is_err());
+        assert!(reopened.read_only);
+
+        fs.write_file(&segment_path(LOG, 1), b"not gzip");
+        assert!(read_segment(&*fs, LOG, 1).is_err());
+    }
+
+    #[test]
+    fn a_torn_log_is_not_rotated() {
+        let fs = MemFs::new();
+        fs.append(LOG, &log_text(&["SET a 1"])).unwrap();
+        fs.append_raw(LOG, &[1, 9, 0]).unwrap();
+        assert!(rotate_log(&fs, LOG).is_err());
+        assert!(fs.contents(&segment_path(LOG, 1)).is_none());
+        assert_eq!(records(&fs), ["SET a 1"]);
+    }
+}
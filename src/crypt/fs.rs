@@ -0,0 +1,130 @@
+// =====================================================================
+// File: crypt/fs.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   `EncryptedFs` wraps a session's file system with its log key. Every
+//   sealed record appended through it is encrypted on the way to disk,
+//   and readers that replay through it decrypt with the key it carries
+//   (see `Fs::log_key`). Two sessions with different keys, or one with
+//   a key and one without, can run in the same process.
+// =====================================================================
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+
+use super::key::LogKey;
+use crate::storage::Layout;
+use crate::Fs;
+
+/// `inner`, with every record appended encrypted with `key`.
+#[derive(Debug)]
+pub struct EncryptedFs {
+    inner: Arc<dyn Fs>,
+    key: Arc<LogKey>,
+}
+
+
+impl EncryptedFs {
+    /// Encrypt what is appended to `inner` with `key`.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "encryption")] {
+    /// use std::sync::Arc;
+    /// use kvstore::crypt::{EncryptedFs, LogKey};
+    /// use kvstore::{log_text, replay_records, Fs, MemFs};
+    /// let mem = Arc::new(MemFs::new());
+    /// let fs = EncryptedFs::new(mem.clone(), LogKey::new(&[5; 32]));
+    /// fs.append("log", &log_text(&["SET card 4111"])).unwrap();
+    ///
+    /// assert!(!mem.read("log").unwrap().windows(4).any(|bytes| bytes == b"4111"));
+    /// assert_eq!(replay_records(&fs, "log", 0).unwrap()[0].1, "SET card 4111");
+    /// assert!(replay_records(&*mem, "log", 0).is_err());
+    /// # }
+    /// ```
+    pub fn new(inner: Arc<dyn Fs>, key: LogKey) -> EncryptedFs {
+        EncryptedFs { inner, key: Arc::new(key) }
+    }
+}
+
+
+impl Fs for EncryptedFs {
+    fn open(&self, path: &str) -> io::Result<()> {
+        self.inner.open(path)
+    }
+
+
+    fn create(&self, path: &str) -> io::Result<()> {
+        self.inner.create(path)
+    }
+
+
+    fn append(&self, path: &str, text: &str) -> io::Result<u64> {
+        self.inner.append(path, &self.key.encrypt_lines(text))
+    }
+
+
+    fn append_many(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        let encrypted: Vec<String> = records.iter().map(|line| self.key.encrypt_line(line).into_owned()).collect();
+        self.inner.append_many(path, &encrypted)
+    }
+
+
+    fn append_raw(&self, path: &str, bytes: &[u8]) -> io::Result<u64> {
+        self.inner.append_raw(path, bytes)
+    }
+
+
+    fn layout(&self, path: &str) -> io::Result<Layout> {
+        self.inner.layout(path)
+    }
+
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+
+    fn reader(&self, path: &str) -> io::Result<Box<dyn BufRead + Send>> {
+        self.inner.reader(path)
+    }
+
+
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.inner.read_at(path, offset, len)
+    }
+
+
+    fn end(&self, path: &str) -> io::Result<u64> {
+        self.inner.end(path)
+    }
+
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.inner.rename(from, to)
+    }
+
+
+    fn sync(&self, path: &str) -> io::Result<()> {
+        self.inner.sync(path)
+    }
+
+
+    fn sync_dir(&self, path: &str) -> io::Result<()> {
+        self.inner.sync_dir(path)
+    }
+
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        self.inner.remove(path)
+    }
+
+
+    fn log_key(&self) -> Option<Arc<LogKey>> {
+        Some(self.key.clone())
+    }
+}
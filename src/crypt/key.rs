@@ -0,0 +1,305 @@
+// =====================================================================
+// File: crypt/key.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   The log encryption key: where it comes from (`KVSTORE_ENCRYPTION_KEY`
+//   or `KVSTORE_ENCRYPTION_KEY_FILE`), and how one record is encrypted
+//   and decrypted with it.
+//
+//   An encrypted record is `ENC <base64>`, the base64 holding the nonce,
+//   the ciphertext and the tag. It is sealed with a checksum like any
+//   other record, so torn writes are still found without the key. A
+//   stamped record's stamp (`<seq>@<unix ms>`) is its associated data:
+//   moving the ciphertext to another sequence number fails to decrypt.
+//
+//   The cipher is the `aes-gcm` crate's, built with the `encryption`
+//   feature. Without it a key can't be made, and a log holding encrypted
+//   records can't be read.
+// =====================================================================
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "encryption")]
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload, rand_core::RngCore};
+#[cfg(feature = "encryption")]
+use aes_gcm::{Aes256Gcm, Nonce};
+
+use super::base64::{base64_decode, base64_encode};
+use crate::storage::{self, Stamp};
+
+/// Environment variable holding the key as 64 hex digits.
+pub const KEY_ENV: &str = "KVSTORE_ENCRYPTION_KEY";
+
+/// Environment variable naming a file that holds the key, as 64 hex
+/// digits or 32 raw bytes.
+pub const KEY_FILE_ENV: &str = "KVSTORE_ENCRYPTION_KEY_FILE";
+
+/// How every encrypted record starts.
+const PREFIX: &str = "ENC ";
+
+/// Bytes of the nonce each record starts with.
+const NONCE_LEN: usize = 12;
+
+/// Bytes of the tag each record ends with.
+const TAG_LEN: usize = 16;
+
+/// What a build without the cipher says when asked for one.
+const NOT_BUILT: &str = "log encryption needs kvstore built with --features encryption";
+
+
+/// A 256-bit AES-GCM key for log records.
+///
+/// Nonces are a random per-key base with a counter mixed into its low
+/// bytes, so no two records encrypted with one key share one.
+pub struct LogKey {
+    #[cfg(feature = "encryption")]
+    cipher: Aes256Gcm,
+
+    /// Never made: without the cipher there are no keys.
+    #[cfg(not(feature = "encryption"))]
+    cipher: std::convert::Infallible,
+
+    nonce_base: [u8; NONCE_LEN],
+    counter: AtomicU64,
+}
+
+
+impl fmt::Debug for LogKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LogKey(..)")
+    }
+}
+
+
+impl LogKey {
+    /// Use the 32 bytes of `key`.
+    #[cfg(feature = "encryption")]
+    pub fn new(key: &[u8; 32]) -> Self {
+        let mut nonce_base = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce_base);
+        Self { cipher: Aes256Gcm::new(key.into()), nonce_base, counter: AtomicU64::new(0) }
+    }
+
+
+    /// [`LogKey::new`], or an error in a build without the cipher.
+    #[cfg(feature = "encryption")]
+    fn from_bytes(key: &[u8; 32]) -> Result<Self, String> {
+        Ok(Self::new(key))
+    }
+
+
+    #[cfg(not(feature = "encryption"))]
+    fn from_bytes(_key: &[u8; 32]) -> Result<Self, String> {
+        Err(NOT_BUILT.to_string())
+    }
+
+
+    /// Parse a key written as 64 hex digits.
+    ///
+    /// # Returns
+    /// `Err(message)` if it is malformed, or the crate was built
+    /// without the `encryption` feature.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::crypt::LogKey;
+    /// assert!(LogKey::from_hex("abcd").is_err());
+    /// # #[cfg(feature = "encryption")]
+    /// assert!(LogKey::from_hex(&"ab".repeat(32)).is_ok());
+    /// ```
+    pub fn from_hex(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.len() != 64 || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err("encryption key must be 64 hex digits (256 bits)".to_string());
+        }
+        let mut key = [0u8; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[2 * i..2 * i + 2], 16).map_err(|e| e.to_string())?;
+        }
+        Self::from_bytes(&key)
+    }
+
+
+    /// Read a key file: 32 raw bytes, or 64 hex digits with optional
+    /// surrounding whitespace.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` if the file can't be read or holds neither, or
+    /// of kind `Unsupported` without the `encryption` feature.
+    pub fn from_file(path: &str) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+        if let Ok(raw) = <[u8; 32]>::try_from(bytes.as_slice()) {
+            return Self::from_bytes(&raw).map_err(|e| io::Error::new(io::ErrorKind::Unsupported, e));
+        }
+        let text = std::str::from_utf8(&bytes).map_err(|_| invalid("encryption key file must hold 32 bytes or 64 hex digits"))?;
+        Self::from_hex(text).map_err(|e| invalid(&e))
+    }
+
+
+    /// The key named by [`KEY_ENV`] or [`KEY_FILE_ENV`].
+    ///
+    /// # Returns
+    /// * `Ok(None)` if neither is set.
+    /// * `Err(message)` if both are, or the key is malformed.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match (std::env::var(KEY_ENV), std::env::var(KEY_FILE_ENV)) {
+            (Ok(_), Ok(_)) => Err(format!("set only one of {} and {}", KEY_ENV, KEY_FILE_ENV)),
+            (Ok(hex), Err(_)) => Self::from_hex(&hex).map(Some),
+            (Err(_), Ok(path)) => Self::from_file(&path).map(Some).map_err(|e| format!("cannot read key file {}: {}", path, e)),
+            (Err(_), Err(_)) => Ok(None),
+        }
+    }
+
+
+    /// Encrypt one record (without its checksum) as `ENC <base64>`,
+    /// bound to `aad`: it decrypts only with the same associated data.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "encryption")] {
+    /// use kvstore::crypt::LogKey;
+    /// let key = LogKey::new(&[9; 32]);
+    /// let sealed = key.encrypt("SET password hunter2", b"7@1");
+    /// assert!(sealed.starts_with("ENC ") && !sealed.contains("hunter2"));
+    /// assert_eq!(key.decrypt(&sealed, b"7@1").as_deref(), Some("SET password hunter2"));
+    /// assert_eq!(key.decrypt(&sealed, b"8@1"), None);
+    /// assert_eq!(LogKey::new(&[8; 32]).decrypt(&sealed, b"7@1"), None);
+    /// # }
+    /// ```
+    pub fn encrypt(&self, record: &str, aad: &[u8]) -> String {
+        let mut nonce = self.nonce_base;
+        let count = self.counter.fetch_add(1, Ordering::Relaxed).to_be_bytes();
+        for (b, c) in nonce[NONCE_LEN - 8..].iter_mut().zip(count) {
+            *b ^= c;
+        }
+        let mut bytes = nonce.to_vec();
+        bytes.extend(self.seal(&nonce, record.as_bytes(), aad));
+        format!("{}{}", PREFIX, base64_encode(&bytes))
+    }
+
+
+    /// The record inside what [`LogKey::encrypt`] produced.
+    ///
+    /// # Returns
+    /// `None` if it is not an encrypted record, or it was encrypted with
+    /// another key or other associated data, or changed since.
+    pub fn decrypt(&self, record: &str, aad: &[u8]) -> Option<String> {
+        let bytes = base64_decode(record.strip_prefix(PREFIX)?)?;
+        if bytes.len() < NONCE_LEN + TAG_LEN {
+            return None;
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+        String::from_utf8(self.open(nonce, sealed, aad)?).ok()
+    }
+
+
+    /// A sealed log line (see [`crate::seal_record`]) with its record
+    /// encrypted and the checksum redone; the stamp, if any, stays in
+    /// the clear and is the associated data.
+    ///
+    /// Anything else, such as the header or an already encrypted record,
+    /// is returned as it is.
+    pub fn encrypt_line<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        match storage::unseal_stamped(line) {
+            Some((record, None)) if !is_encrypted(record) => Cow::Owned(storage::seal_record(&self.encrypt(record, b""))),
+            Some((record, Some(stamp))) if !is_encrypted(record) => {
+                Cow::Owned(storage::seal_record_at(&self.encrypt(record, stamp_aad(Some(stamp)).as_bytes()), stamp))
+            }
+            _ => Cow::Borrowed(line),
+        }
+    }
+
+
+    /// [`LogKey::encrypt_line`] for each line of `text`.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "encryption")] {
+    /// use kvstore::crypt::LogKey;
+    /// use kvstore::{header_record, log_text, open_record};
+    /// let key = LogKey::new(&[3; 32]);
+    /// let text = key.encrypt_lines(&log_text(&["SET a 1"]));
+    /// let (header, line) = text.split_once('\n').unwrap();
+    /// assert_eq!(header, header_record());
+    /// assert_eq!(open_record(line, Some(&key)).unwrap().as_deref(), Some("SET a 1"));
+    /// assert!(open_record(line, None).is_err());
+    /// # }
+    /// ```
+    pub fn encrypt_lines(&self, text: &str) -> String {
+        text.split('\n').map(|line| self.encrypt_line(line)).collect::<Vec<_>>().join("\n")
+    }
+
+
+    #[cfg(feature = "encryption")]
+    fn seal(&self, nonce: &[u8; NONCE_LEN], plain: &[u8], aad: &[u8]) -> Vec<u8> {
+        self.cipher
+            .encrypt(Nonce::from_slice(nonce), Payload { msg: plain, aad })
+            .expect("a log record is far below the GCM message limit")
+    }
+
+
+    #[cfg(feature = "encryption")]
+    fn open(&self, nonce: &[u8], sealed: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        self.cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad }).ok()
+    }
+
+
+    #[cfg(not(feature = "encryption"))]
+    fn seal(&self, _nonce: &[u8; NONCE_LEN], _plain: &[u8], _aad: &[u8]) -> Vec<u8> {
+        match self.cipher {}
+    }
+
+
+    #[cfg(not(feature = "encryption"))]
+    fn open(&self, _nonce: &[u8], _sealed: &[u8], _aad: &[u8]) -> Option<Vec<u8>> {
+        match self.cipher {}
+    }
+}
+
+
+/// Whether `record` was written encrypted.
+pub fn is_encrypted(record: &str) -> bool {
+    record.starts_with(PREFIX)
+}
+
+
+/// The associated data of a record with `stamp`: the stamp as written.
+fn stamp_aad(stamp: Option<Stamp>) -> String {
+    stamp.map_or_else(String::new, |stamp| stamp.to_string())
+}
+
+
+/// `record` as read from disk, decrypted with `key` if it was written
+/// encrypted. `stamp` is the one sealed with it.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidData` if it is encrypted and there
+/// is no key, or it does not decrypt with `key`.
+pub fn decrypt_record<'a>(key: Option<&LogKey>, record: &'a str, stamp: Option<Stamp>) -> io::Result<Cow<'a, str>> {
+    if !is_encrypted(record) {
+        return Ok(Cow::Borrowed(record));
+    }
+    let Some(key) = key else {
+        return Err(invalid(&if cfg!(feature = "encryption") {
+            format!("log is encrypted; set {} or {}", KEY_ENV, KEY_FILE_ENV)
+        } else {
+            format!("log is encrypted; {}", NOT_BUILT)
+        }));
+    };
+    key.decrypt(record, stamp_aad(stamp).as_bytes())
+        .map(Cow::Owned)
+        .ok_or_else(|| invalid("log record does not decrypt with the encryption key"))
+}
+
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
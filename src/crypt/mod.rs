@@ -0,0 +1,42 @@
+// =====================================================================
+// File: crypt/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `crypt` module encrypts log records at rest with AES-256-GCM, so
+//! the data file, its checkpoint snapshots and backups never hold keys or
+//! values in the clear.
+//!
+//! Structure:
+//! - `key.rs`    : [`LogKey`], read from `KVSTORE_ENCRYPTION_KEY` or
+//!   `KVSTORE_ENCRYPTION_KEY_FILE`, which encrypts and decrypts one
+//!   record with the `aes-gcm` crate.
+//! - `fs.rs`     : [`EncryptedFs`], a session's file system carrying its
+//!   key: appends are encrypted, and replay decrypts with
+//!   [`crate::Fs::log_key`].
+//! - `base64.rs` : Base64, which encrypted records are written in.
+//! - `tests.rs`  : Unit tests for record encryption and key parsing.
+//!
+//! Encryption is per record, so the log stays append-only text: an
+//! encrypted record reads `ENC <base64>` and carries its checksum like
+//! any other. Values are read back through [`crate::ValuePointer`]s in
+//! key-only and memory-limited sessions and from the value log, so those
+//! modes are not available with a key.
+//!
+//! The cipher is only built with the `encryption` feature. Without it
+//! no [`LogKey`] can be made, and a log holding encrypted records fails
+//! to load rather than loading as if it were empty.
+// =====================================================================
+
+pub mod base64;
+pub mod fs;
+pub mod key;
+
+pub use self::base64::{base64_decode, base64_encode};
+pub use self::fs::EncryptedFs;
+pub use self::key::{decrypt_record, is_encrypted, LogKey, KEY_ENV, KEY_FILE_ENV};
+
+#[cfg(test)]
+pub mod tests;
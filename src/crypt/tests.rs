@@ -0,0 +1,162 @@
+// =====================================================================
+// File: crypt/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for base64, for encrypting log records with the
+//   `aes-gcm` cipher and binding them to their stamps, for the
+//   encrypting file system, and for reading keys.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+//   * Tests that need a key only run in `encryption` builds.
+// =====================================================================
+
+
+// =====================================================================
+// Record Encryption Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod record_tests {
+    use std::io;
+
+    use crate::crypt::{base64_decode, base64_encode, decrypt_record, LogKey};
+    use crate::{check_log_key, crc32, header_record, log_text, Fs, MemFs, FORMAT_VERSION};
+
+    #[test]
+    fn base64_round_trips_every_padding() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+        assert_eq!(base64_encode(b"abc"), "YWJj");
+        for len in 0..8 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 200) as u8).collect();
+            assert_eq!(base64_decode(&base64_encode(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn encrypted_records_need_a_key() {
+        // Any well-formed base64 will do: without a key it is never opened
+        let record = "ENC AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        let sealed = format!("{}\t{:08x}", record, crc32(record.as_bytes()));
+        let text = format!("{}\n{}", header_record(), sealed);
+        let log = crate::log_bytes(&text).unwrap();
+
+        let err = crate::storage::parse_records(&log, 0, FORMAT_VERSION, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(decrypt_record(None, record, None).is_err());
+        assert_eq!(decrypt_record(None, "SET a 1", None).unwrap(), "SET a 1");
+        let fs = MemFs::new();
+        fs.append("plain", &log_text(&["SET a 1"])).unwrap();
+        fs.append("encrypted", &text).unwrap();
+        assert!(check_log_key(&fs, "plain").is_ok() && check_log_key(&fs, "missing").is_ok());
+        assert_eq!(check_log_key(&fs, "encrypted").unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // A torn one is still found as torn, key or no key
+        let torn = &log[..log.len() - 6];
+        let (records, torn_at) = crate::storage::parse_records(torn, 0, FORMAT_VERSION, None).unwrap();
+        assert!(records.is_empty());
+        assert_eq!(torn_at, Some(header_record().len() as u64 + 1));
+    }
+
+    #[test]
+    fn keys_must_be_256_bits_of_hex() {
+        let hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+        assert!(LogKey::from_hex(&hex[2..]).is_err());
+        assert!(LogKey::from_hex(&hex.replace('0', "g")).is_err());
+        assert_eq!(LogKey::from_hex(hex).is_ok(), cfg!(feature = "encryption"));
+        assert!(MemFs::new().log_key().is_none(), "plain file systems carry no key");
+    }
+}
+
+
+// =====================================================================
+// Cipher Unit Tests (`encryption` builds)
+// =====================================================================
+#[cfg(all(test, feature = "encryption"))]
+mod cipher_tests {
+    use std::io;
+    use std::sync::Arc;
+
+    use crate::crypt::{is_encrypted, EncryptedFs, LogKey};
+    use crate::{check_log_key, log_text, open_record, replay_records, seal_record_at, set_record, unseal_stamped, Fs, MemFs, Stamp};
+
+    #[test]
+    fn records_encrypt_under_fresh_nonces() {
+        let key = LogKey::new(&[6; 32]);
+        let record = set_record("user:1", "secret value");
+        let (a, b) = (key.encrypt(&record, b""), key.encrypt(&record, b""));
+        assert_ne!(a, b, "every record gets its own nonce");
+        assert!(is_encrypted(&a) && !a.contains("secret") && !a.contains('\t') && !a.contains('\n'));
+        assert_eq!(key.decrypt(&a, b"").unwrap(), record);
+        assert_eq!(key.decrypt(&b, b"").unwrap(), record);
+
+        assert_eq!(key.decrypt(&record, b""), None, "plain records are not encrypted ones");
+        assert_eq!(key.decrypt("ENC not base64", b""), None);
+        assert_eq!(key.decrypt("ENC AAAA", b""), None);
+    }
+
+    #[test]
+    fn stamped_records_are_bound_to_their_stamp() {
+        let key = LogKey::new(&[7; 32]);
+        let stamp = Stamp { seq: 7, unix_ms: 1_700_000_000_000 };
+        let line = key.encrypt_line(&seal_record_at("SET a 1", stamp)).into_owned();
+        let (record, read_stamp) = unseal_stamped(&line).unwrap();
+        assert!(is_encrypted(record));
+        assert_eq!(read_stamp, Some(stamp));
+        assert_eq!(open_record(&line, Some(&key)).unwrap().as_deref(), Some("SET a 1"));
+
+        // Moved to another sequence number, with a fresh checksum
+        let moved = seal_record_at(record, Stamp { seq: 8, ..stamp });
+        assert_eq!(open_record(&moved, Some(&key)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let unstamped = crate::seal_record(record);
+        assert!(open_record(&unstamped, Some(&key)).is_err());
+
+        // Encrypting twice is a no-op
+        assert_eq!(key.encrypt_line(&line), line);
+    }
+
+    #[test]
+    fn keys_come_from_hex_or_key_files() {
+        let hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+        let sealed = LogKey::from_hex(hex).unwrap().encrypt("DEL a", b"");
+        assert_eq!(LogKey::from_hex(&format!(" {}\n", hex.to_uppercase())).unwrap().decrypt(&sealed, b"").as_deref(), Some("DEL a"));
+
+        let path = std::env::temp_dir().join(format!("kvstore_key_{}", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        std::fs::write(&path, format!("{}\n", hex)).unwrap();
+        assert_eq!(LogKey::from_file(&path).unwrap().decrypt(&sealed, b"").as_deref(), Some("DEL a"));
+        let raw: Vec<u8> = (0..32).map(|i| (i % 16 * 0x11) as u8).collect();
+        std::fs::write(&path, &raw).unwrap();
+        assert_eq!(LogKey::from_file(&path).unwrap().decrypt(&sealed, b"").as_deref(), Some("DEL a"));
+        std::fs::write(&path, "short").unwrap();
+        assert_eq!(LogKey::from_file(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+        assert!(LogKey::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn each_file_system_keeps_its_own_key() {
+        let mem = Arc::new(MemFs::new());
+        let fs = EncryptedFs::new(mem.clone(), LogKey::new(&[1; 32]));
+        fs.append("log", &log_text(&["SET a 1"])).unwrap();
+        fs.append_many("log", &[seal_record_at("SET b 2", Stamp { seq: 1, unix_ms: 5 })]).unwrap();
+        let on_disk = mem.read("log").unwrap();
+        assert!(on_disk.starts_with(b"KVSTORE 5\n") && !on_disk.windows(3).any(|bytes| bytes == b"SET"));
+
+        let records: Vec<String> = replay_records(&fs, "log", 0).unwrap().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(records, ["SET a 1", "SET b 2"]);
+        assert!(check_log_key(&fs, "log").is_ok());
+
+        // Neither the plain file system nor one with another key reads it
+        assert!(check_log_key(&*mem, "log").is_err());
+        let other = EncryptedFs::new(mem.clone(), LogKey::new(&[2; 32]));
+        assert!(replay_records(&other, "log", 0).is_err());
+        mem.append("plain", &log_text(&["SET c 3"])).unwrap();
+        assert_eq!(replay_records(&*mem, "plain", 0).unwrap()[0].1, "SET c 3");
+    }
+}
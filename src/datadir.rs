@@ -0,0 +1,288 @@
+// =====================================================================
+// File: datadir.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   A data directory holds one store: its log segment, the files kept
+//   beside it (the checkpoint snapshot `<segment>.snap`, value logs
+//   `<segment>.vlog.<n>`, cold segments `<segment>.<n>.gz` it was
+//   rotated into, the `.lock`), and a `MANIFEST` naming the
+//   segment and the log format it was last opened with:
+//
+//       KVSTORE-MANIFEST
+//       format 5
+//       segment 000001.log
+//
+//   The manifest is replaced in a single rename, so it always names a
+//   whole file set. Opening a directory creates it (and a first, empty
+//   segment) if needed, adopts a `data.db` left by the single-file layout
+//   as its segment, and refuses a manifest from a newer format or one
+//   whose segment is missing.
+//
+//   Not to be confused with the readers' `<log>.manifest` (see
+//   `readers`), which only counts replacements of the log.
+// =====================================================================
+
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::storage::{self, FORMAT_VERSION};
+use crate::RealFs;
+
+/// Name of the manifest inside a data directory.
+pub const MANIFEST: &str = "MANIFEST";
+
+/// First line of every manifest.
+const MANIFEST_HEADER: &str = "KVSTORE-MANIFEST";
+
+/// Segment a new data directory starts with.
+pub const FIRST_SEGMENT: &str = "000001.log";
+
+/// Log of the single-file layout, adopted as the segment of a directory
+/// that holds one.
+pub const LEGACY_LOG: &str = "data.db";
+
+
+/// An open data directory and what its manifest records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDir {
+    dir: PathBuf,
+
+    /// Log format the segment was last opened with.
+    format: u32,
+
+    /// File name of the live log segment.
+    segment: String,
+}
+
+
+impl DataDir {
+    /// Open the data directory at `dir`, creating it, its manifest and a
+    /// first segment if it has none.
+    ///
+    /// # Returns
+    /// * `Err(io::Error)` of kind `InvalidData` if the manifest is
+    ///   malformed, was written by a newer format, or names a segment
+    ///   that is missing.
+    /// * `Err(io::Error)` if the directory or manifest cannot be created.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::DataDir;
+    /// let dir = std::env::temp_dir().join("kvstore_datadir_doc");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let data = DataDir::open(&dir).unwrap();
+    /// assert_eq!(data.segment(), "000001.log");
+    /// assert!(dir.join("MANIFEST").exists() && dir.join("000001.log").exists());
+    /// assert_eq!(DataDir::open(&dir).unwrap(), data);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        match fs::read_to_string(dir.join(MANIFEST)) {
+            Ok(text) => {
+                let data = Self::parse(&dir, &text)?;
+                if !Path::new(&data.log_path()).exists() {
+                    return Err(invalid(format!("{} names segment {}, which is missing", data.manifest_path(), data.segment)));
+                }
+                Ok(data)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::create(dir),
+            Err(e) => Err(e),
+        }
+    }
+
+
+    /// Write the manifest of a directory that has none, adopting the log
+    /// already there.
+    fn create(dir: PathBuf) -> io::Result<Self> {
+        // A segment left by a crash before its manifest, or a single-file log
+        let segment = [FIRST_SEGMENT, LEGACY_LOG].into_iter().find(|name| dir.join(name).exists());
+        let data = match segment {
+            Some(name) => {
+                let format = file_format(&dir.join(name))?;
+                Self { dir, format, segment: name.to_string() }
+            }
+            None => {
+                // The segment goes first, so the manifest never names a missing one
+                let data = Self { dir, format: FORMAT_VERSION, segment: FIRST_SEGMENT.to_string() };
+                storage::migrate_log(&data.log_path())?;
+                data
+            }
+        };
+        data.write_manifest()?;
+        Ok(data)
+    }
+
+
+    /// Parse the manifest `text` of the directory `dir`.
+    fn parse(dir: &Path, text: &str) -> io::Result<Self> {
+        let path = dir.join(MANIFEST).to_string_lossy().into_owned();
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(invalid(format!("{} is not a kvstore manifest", path)));
+        }
+
+        let (mut format, mut segments) = (None, Vec::new());
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("format", n)) => {
+                    format = Some(n.parse::<u32>().map_err(|_| invalid(format!("{}: bad format {:?}", path, n)))?);
+                }
+                // A plain file name, so the manifest can't point outside the directory
+                Some(("segment", name)) if !name.is_empty() && !name.contains(['/', '\\']) && name != ".." => {
+                    segments.push(name.to_string());
+                }
+                _ => return Err(invalid(format!("{}: unrecognized line {:?}", path, line))),
+            }
+        }
+
+        let format = format.ok_or_else(|| invalid(format!("{} has no format line", path)))?;
+        if format > FORMAT_VERSION {
+            return Err(invalid(format!(
+                "{} is format {}; this build reads up to format {}",
+                path, format, FORMAT_VERSION
+            )));
+        }
+        let [segment] = <[String; 1]>::try_from(segments)
+            .map_err(|segments| invalid(format!("{} lists {} segments; this build reads one", path, segments.len())))?;
+        Ok(Self { dir: dir.to_path_buf(), format, segment })
+    }
+
+
+    /// Replace the manifest with what this records.
+    fn write_manifest(&self) -> io::Result<()> {
+        let text = format!("{}\nformat {}\nsegment {}", MANIFEST_HEADER, self.format, self.segment);
+        storage::replace_file(&RealFs, &self.manifest_path(), &text)
+    }
+
+
+    /// Record that the segment is now in log format `format`, as it is
+    /// once [`migrate_log`](crate::migrate_log) has run on it.
+    pub fn record_format(&mut self, format: u32) -> io::Result<()> {
+        if self.format == format {
+            return Ok(());
+        }
+        self.format = format;
+        self.write_manifest()
+    }
+
+
+    /// The directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+
+    /// Log format the manifest records.
+    pub fn format(&self) -> u32 {
+        self.format
+    }
+
+
+    /// File name of the live log segment.
+    pub fn segment(&self) -> &str {
+        &self.segment
+    }
+
+
+    /// Path of the live log segment, the session's data file.
+    pub fn log_path(&self) -> String {
+        self.dir.join(&self.segment).to_string_lossy().into_owned()
+    }
+
+
+    /// Path of the manifest.
+    pub fn manifest_path(&self) -> String {
+        self.dir.join(MANIFEST).to_string_lossy().into_owned()
+    }
+}
+
+
+/// Log format of the file at `path`, from its header record (0 if it
+/// has none).
+fn file_format(path: &Path) -> io::Result<u32> {
+    let mut first = String::new();
+    BufReader::new(fs::File::open(path)?).read_line(&mut first)?;
+    Ok(storage::parse_header(first.trim_end()).unwrap_or(0))
+}
+
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+// =================================================================
+// datadir.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod datadir_tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kvstore_datadir_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_new_directories_get_a_manifest_and_segment() {
+        let dir = test_dir("new").join("nested");
+        let mut data = DataDir::open(&dir).unwrap();
+        assert_eq!((data.format(), data.segment()), (FORMAT_VERSION, FIRST_SEGMENT));
+        assert_eq!(
+            fs::read_to_string(dir.join(MANIFEST)).unwrap(),
+            format!("KVSTORE-MANIFEST\nformat {}\nsegment 000001.log\n", FORMAT_VERSION)
+        );
+        assert_eq!(fs::read_to_string(data.log_path()).unwrap(), format!("{}\n", storage::header_record()));
+
+        // Recording the same format leaves the manifest alone
+        data.record_format(FORMAT_VERSION).unwrap();
+        assert_eq!(DataDir::open(&dir).unwrap(), data);
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn test_a_single_file_log_is_adopted() {
+        let dir = test_dir("legacy");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(LEGACY_LOG), "SET a 1\n").unwrap();
+
+        let mut data = DataDir::open(&dir).unwrap();
+        assert_eq!((data.format(), data.segment()), (0, LEGACY_LOG));
+        assert_eq!(data.log_path(), dir.join("data.db").to_string_lossy());
+        assert!(!dir.join(FIRST_SEGMENT).exists());
+
+        storage::migrate_log(&data.log_path()).unwrap();
+        data.record_format(FORMAT_VERSION).unwrap();
+        assert_eq!(DataDir::open(&dir).unwrap().format(), FORMAT_VERSION);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_bad_manifests_are_refused() {
+        let dir = test_dir("bad");
+        DataDir::open(&dir).unwrap();
+        let refused = |manifest: &str| {
+            fs::write(dir.join(MANIFEST), manifest).unwrap();
+            let err = DataDir::open(&dir).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", manifest);
+            err.to_string()
+        };
+
+        assert!(refused("format 4\nsegment 000001.log\n").contains("not a kvstore manifest"));
+        assert!(refused("KVSTORE-MANIFEST\nformat 99\nsegment 000001.log\n").contains("format 99"));
+        assert!(refused("KVSTORE-MANIFEST\nsegment 000001.log\n").contains("no format line"));
+        assert!(refused("KVSTORE-MANIFEST\nformat 4\n").contains("0 segments"));
+        assert!(refused("KVSTORE-MANIFEST\nformat 4\nsegment a.log\nsegment b.log\n").contains("2 segments"));
+        assert!(refused("KVSTORE-MANIFEST\nformat 4\nsegment ../data.db\n").contains("unrecognized line"));
+        assert!(refused("KVSTORE-MANIFEST\nformat 4\nsegment 000002.log\n").contains("which is missing"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
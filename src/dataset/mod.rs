@@ -0,0 +1,34 @@
+// =====================================================================
+// File: dataset/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `dataset` module moves the keyspace out as plain text, for
+//! analysis with everyday tools or migration to another store, and bulk
+//! loads it back in.
+//!
+//! Structure:
+//! - `writer.rs` : [`write_json`] and [`write_csv`], which write rows as
+//!   JSON lines or CSV, and [`export_dataset`] behind `EXPORT JSON` and
+//!   `EXPORT CSV`.
+//! - `reader.rs` : [`JsonRows`] and [`CsvRows`], which stream rows back
+//!   out of either format, and [`import_dataset`] behind `IMPORT JSON`
+//!   and `IMPORT CSV`.
+//! - `tests.rs`  : Unit tests for both formats and both commands.
+//!
+//! Both formats carry the same three fields as `EXPORT SQLITE`: the key,
+//! its value and `expires_at`, the absolute expiration in Unix
+//! milliseconds. Keys without a TTL leave `expires_at` out (JSON) or
+//! empty (CSV).
+// =====================================================================
+
+pub mod reader;
+pub mod writer;
+
+pub use self::reader::{import_dataset, import_rows, CsvRows, DatasetImportReport, JsonRows, IMPORT_BATCH};
+pub use self::writer::{export_dataset, write_csv, write_json, DatasetFormat};
+
+#[cfg(test)]
+pub mod tests;
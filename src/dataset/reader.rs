@@ -0,0 +1,444 @@
+// =====================================================================
+// File: dataset/reader.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Reads JSON lines or CSV back into the keyspace (`IMPORT JSON`,
+//   `IMPORT CSV`), in the layout the export writes.
+//
+//   Both readers stream: [`JsonRows`] and [`CsvRows`] hold one line (or
+//   one quoted CSV record) at a time, and [`import_dataset`] writes the
+//   rows through the session in batches of [`IMPORT_BATCH`], each logged
+//   with a single append like `MSET`. JSON objects may hold other fields,
+//   and CSV files other columns, in any order; they are ignored. A row
+//   that can't be parsed, or that the session refuses, is counted and
+//   skipped rather than ending the import.
+// =====================================================================
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use super::writer::DatasetFormat;
+use crate::{KvRow, Session};
+
+/// Rows written per log append.
+pub const IMPORT_BATCH: usize = 1000;
+
+/// Outcome of an import.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatasetImportReport {
+    /// Keys that did not exist before.
+    pub inserted: usize,
+
+    /// Keys that existed and got the imported value.
+    pub overwritten: usize,
+
+    /// Rows already expired at import time.
+    pub expired_skipped: usize,
+
+    /// Rows that could not be parsed, or that the session refused (size
+    /// limits, another shard's key, ...).
+    pub rejected: usize,
+}
+
+
+impl DatasetImportReport {
+    /// Keys written, new or not.
+    pub fn imported(&self) -> usize {
+        self.inserted + self.overwritten
+    }
+}
+
+
+/// Import the JSON lines or CSV file at `path` into `session`.
+///
+/// Each row is written as a regular `SET`; a future `expires_at` sets
+/// the key's TTL. The session must not be in a transaction or loading.
+///
+/// # Returns
+/// * `Ok(DatasetImportReport)` once every row is read.
+/// * `Err(io::Error)` if the file can't be read, a CSV file has no `key`
+///   and `value` columns, or a batch can't be logged (the batches before
+///   it stay imported).
+pub fn import_dataset(session: &mut Session, format: DatasetFormat, path: &str) -> io::Result<DatasetImportReport> {
+    let input = BufReader::new(File::open(path)?);
+    match format {
+        DatasetFormat::Json => import_rows(session, JsonRows::new(input)),
+        DatasetFormat::Csv => import_rows(session, CsvRows::new(input)?),
+    }
+}
+
+
+/// Write `rows` through `session` in batches, as [`import_dataset`] does.
+///
+/// Rows that fail with `InvalidData` are counted as rejected; any other
+/// error ends the import.
+pub fn import_rows(session: &mut Session, rows: impl Iterator<Item = io::Result<KvRow>>) -> io::Result<DatasetImportReport> {
+    let now_ms = crate::ttl::unix_now_ms() as i64;
+    let mut report = DatasetImportReport::default();
+    let mut pairs = Vec::new();
+    let mut expirations = Vec::new();
+    let mut batched = HashSet::new();
+
+    for row in rows {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                report.rejected += 1;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if row.expires_at.is_some_and(|at| at <= now_ms) {
+            report.expired_skipped += 1;
+            continue;
+        }
+        let misrouted = session.cluster.as_ref().is_some_and(|c| c.misrouted(&[&row.key]).is_some());
+        if misrouted || session.limits.check_write(&row.key, row.value.as_bytes()).is_err() {
+            report.rejected += 1;
+            continue;
+        }
+
+        // A key repeated within the batch is an overwrite too
+        if batched.contains(&row.key) || session.exists(&row.key) {
+            report.overwritten += 1;
+        } else {
+            report.inserted += 1;
+        }
+        if !batched.insert(row.key.clone()) {
+            // The last row for a key decides its TTL
+            expirations.retain(|(key, _)| *key != row.key);
+        }
+        if let Some(at) = row.expires_at {
+            expirations.push((row.key.clone(), at as u64));
+        }
+        pairs.push((row.key, row.value));
+
+        if pairs.len() == IMPORT_BATCH {
+            session.import_batch(std::mem::take(&mut pairs), std::mem::take(&mut expirations)).map_err(io::Error::other)?;
+            batched.clear();
+        }
+    }
+    if !pairs.is_empty() {
+        session.import_batch(pairs, expirations).map_err(io::Error::other)?;
+    }
+    Ok(report)
+}
+
+
+/// Error for a row that can't be read.
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+
+/// Rows of a JSON lines file, one object per non-blank line.
+///
+/// # Example
+/// ```
+/// use kvstore::{JsonRows, KvRow};
+/// let text = "{\"key\":\"a\",\"value\":\"1\",\"expires_at\":null}\n\n{\"value\":\"2\",\"key\":\"b\\u00e9\"}\n";
+/// let rows: Vec<KvRow> = JsonRows::new(text.as_bytes()).map(Result::unwrap).collect();
+/// assert_eq!(rows[1], KvRow { key: "bé".into(), value: "2".into(), expires_at: None });
+/// ```
+pub struct JsonRows<R> {
+    input: R,
+    line: String,
+}
+
+
+impl<R: BufRead> JsonRows<R> {
+    /// Read rows from `input`.
+    pub fn new(input: R) -> Self {
+        JsonRows { input, line: String::new() }
+    }
+}
+
+
+impl<R: BufRead> Iterator for JsonRows<R> {
+    type Item = io::Result<KvRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.input.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) if self.line.trim().is_empty() => continue,
+                Ok(_) => return Some(parse_json_row(self.line.trim()).map_err(invalid)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+
+/// A scalar JSON value; rows hold nothing else.
+enum Scalar {
+    Text(String),
+    Number(String),
+    Null,
+    Bool,
+}
+
+
+/// Decode one `{"key":..,"value":..,"expires_at":..}` object.
+fn parse_json_row(text: &str) -> Result<KvRow, String> {
+    let mut json = Json { text, at: 0 };
+    let (mut key, mut value, mut expires_at) = (None, None, None);
+    json.expect('{')?;
+    if !json.eat('}') {
+        loop {
+            let field = json.string()?;
+            json.expect(':')?;
+            let scalar = json.scalar()?;
+            match (field.as_str(), scalar) {
+                // Numbers stand for their text, as in the SQLite import
+                ("key", Scalar::Text(s) | Scalar::Number(s)) => key = Some(s),
+                ("value", Scalar::Text(s) | Scalar::Number(s)) => value = Some(s),
+                ("expires_at", Scalar::Number(n)) => {
+                    expires_at = Some(n.parse::<i64>().map_err(|_| format!("expires_at {} is not a whole number", n))?)
+                }
+                ("expires_at", Scalar::Null) => expires_at = None,
+                ("key" | "value" | "expires_at", _) => return Err(format!("{} has the wrong type", field)),
+                _ => {}
+            }
+            if json.eat('}') {
+                break;
+            }
+            json.expect(',')?;
+        }
+    }
+    json.skip_whitespace();
+    if json.at != text.len() {
+        return Err("text after the object".to_string());
+    }
+    match (key, value) {
+        (Some(key), Some(value)) => Ok(KvRow { key, value, expires_at }),
+        _ => Err("object has no key or no value".to_string()),
+    }
+}
+
+
+/// Cursor over the text of one JSON object.
+struct Json<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+
+impl Json<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.at..];
+        self.at += rest.len() - rest.trim_start().len();
+    }
+
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.at..].chars().next()
+    }
+
+
+    /// Consume `c` (after whitespace) if it is next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.peek() == Some(c);
+        if found {
+            self.at += c.len_utf8();
+        }
+        found
+    }
+
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) { Ok(()) } else { Err(format!("expected '{}' at byte {}", c, self.at)) }
+    }
+
+
+    fn scalar(&mut self) -> Result<Scalar, String> {
+        self.skip_whitespace();
+        let rest = &self.text[self.at..];
+        for (word, scalar) in [("null", Scalar::Null), ("true", Scalar::Bool), ("false", Scalar::Bool)] {
+            if rest.starts_with(word) {
+                self.at += word.len();
+                return Ok(scalar);
+            }
+        }
+        match self.peek() {
+            Some('"') => self.string().map(Scalar::Text),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let len = rest.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c))).unwrap_or(rest.len());
+                self.at += len;
+                Ok(Scalar::Number(rest[..len].to_string()))
+            }
+            _ => Err(format!("unsupported value at byte {}", self.at)),
+        }
+    }
+
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        let mut chars = self.text[self.at..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.at += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, e)| e) {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let high = utf16_unit(&mut chars).ok_or("bad \\u escape")?;
+                        let code = if (0xD800..0xDC00).contains(&high) {
+                            // A surrogate pair spells one character beyond the BMP
+                            let low = match (chars.next(), chars.next()) {
+                                (Some((_, '\\')), Some((_, 'u'))) => utf16_unit(&mut chars),
+                                _ => None,
+                            };
+                            let low = low.filter(|low| (0xDC00..0xE000).contains(low)).ok_or("unpaired surrogate")?;
+                            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                        } else {
+                            high
+                        };
+                        out.push(char::from_u32(code).ok_or("unpaired surrogate")?);
+                    }
+                    _ => return Err("bad escape in string".to_string()),
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+
+/// The four hex digits after a `\\u`, as a UTF-16 code unit.
+fn utf16_unit(chars: &mut impl Iterator<Item = (usize, char)>) -> Option<u32> {
+    let hex: String = chars.take(4).map(|(_, h)| h).collect();
+    u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4)
+}
+
+
+/// Rows of a CSV file whose header names a `key` and a `value` column
+/// (and optionally `expires_at`).
+///
+/// Quoted fields may hold commas, doubled quotes and line breaks, kept
+/// as written; records may end in `\n` or `\r\n`.
+///
+/// # Example
+/// ```
+/// use kvstore::{CsvRows, KvRow};
+/// let text = "value,key\n\"two\nlines\",a\n";
+/// let rows: Vec<KvRow> = CsvRows::new(text.as_bytes()).unwrap().map(Result::unwrap).collect();
+/// assert_eq!(rows, [KvRow { key: "a".into(), value: "two\nlines".into(), expires_at: None }]);
+/// ```
+pub struct CsvRows<R> {
+    input: R,
+    key_at: usize,
+    value_at: usize,
+    expires_at: Option<usize>,
+}
+
+
+impl<R: BufRead> CsvRows<R> {
+    /// Read the header of `input`.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` of kind `InvalidData` if the header has no `key`
+    /// and `value` columns.
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let header = read_csv_record(&mut input)?.unwrap_or_default();
+        let position = |name: &str| header.iter().position(|c| c.trim() == name);
+        let (Some(key_at), Some(value_at)) = (position("key"), position("value")) else {
+            return Err(invalid("CSV header has no key and value columns"));
+        };
+        Ok(CsvRows { input, key_at, value_at, expires_at: position("expires_at") })
+    }
+
+
+    fn row(&self, fields: Vec<String>) -> io::Result<KvRow> {
+        let (Some(key), Some(value)) = (fields.get(self.key_at), fields.get(self.value_at)) else {
+            return Err(invalid(format!("row has {} fields", fields.len())));
+        };
+        let expires_at = match self.expires_at.and_then(|i| fields.get(i)).filter(|at| !at.is_empty()) {
+            Some(at) => Some(at.parse().map_err(|_| invalid(format!("expires_at {} is not a whole number", at)))?),
+            None => None,
+        };
+        Ok(KvRow { key: key.clone(), value: value.clone(), expires_at })
+    }
+}
+
+
+impl<R: BufRead> Iterator for CsvRows<R> {
+    type Item = io::Result<KvRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match read_csv_record(&mut self.input) {
+                Ok(None) => return None,
+                Ok(Some(fields)) if fields.len() == 1 && fields[0].is_empty() => continue,
+                Ok(Some(fields)) => return Some(self.row(fields)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+
+/// The fields of the next CSV record, which may span several lines.
+///
+/// # Returns
+/// * `Ok(None)` at the end of the input.
+/// * `Err(io::Error)` of kind `InvalidData` for a quote left open at the
+///   end of the input, or one that is not UTF-8.
+fn read_csv_record(input: &mut impl BufRead) -> io::Result<Option<Vec<String>>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            if quoted {
+                return Err(invalid("quoted field is never closed"));
+            }
+            if fields.is_empty() && field.is_empty() {
+                return Ok(None);
+            }
+            fields.push(field);
+            return Ok(Some(fields));
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' if quoted => quoted = false,
+                '"' if field.is_empty() => quoted = true,
+                c if quoted => field.push(c),
+                ',' => fields.push(std::mem::take(&mut field)),
+                '\r' if chars.peek() == Some(&'\n') => {}
+                '\n' => {}
+                c => field.push(c),
+            }
+        }
+        if !quoted {
+            fields.push(field);
+            return Ok(Some(fields));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// =====================================================================
+// File: dataset/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for the JSON lines and CSV encodings, and for exporting
+//   and importing the keyspace through the session and the EXPORT and
+//   IMPORT commands.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Dataset Export Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod dataset_writer_tests {
+    use crate::{execute_line, export_dataset, write_csv, write_json, DatasetFormat, KvRow, Session};
+
+    fn rows() -> Vec<KvRow> {
+        vec![
+            KvRow { key: "plain".into(), value: "two words".into(), expires_at: None },
+            KvRow { key: "quote\"d".into(), value: "line\nbreak, comma".into(), expires_at: Some(1_760_000_000_000) },
+        ]
+    }
+
+    #[test]
+    fn json_lines_escape_and_carry_ttls() {
+        let mut out = Vec::new();
+        write_json(&rows(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"key\":\"plain\",\"value\":\"two words\"}\n\
+             {\"key\":\"quote\\\"d\",\"value\":\"line\\nbreak, comma\",\"expires_at\":1760000000000}\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_only_what_needs_it() {
+        let mut out = Vec::new();
+        write_csv(&rows(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "key,value,expires_at\nplain,two words,\n\"quote\"\"d\",\"line\nbreak, comma\",1760000000000\n"
+        );
+        let mut out = Vec::new();
+        write_csv(&[], &mut out).unwrap();
+        assert_eq!(out, b"key,value,expires_at\n");
+    }
+
+    #[test]
+    fn export_writes_live_keys_with_their_deadlines() {
+        let mut session = Session::ephemeral();
+        session.set("b".into(), "2".into());
+        session.set("a".into(), "1".into());
+        session.set("gone".into(), "x".into());
+        assert!(session.delete("gone"));
+        assert_eq!(session.expire("b", 60_000), Ok(true));
+
+        let path = std::env::temp_dir().join(format!("kvstore_export_{}.csv", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        assert_eq!(export_dataset(&mut session, DatasetFormat::Csv, &path).unwrap(), 2);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[..2], ["key,value,expires_at", "a,1,"]);
+        let deadline: u64 = lines[2].strip_prefix("b,2,").unwrap().parse().unwrap();
+        assert!(deadline > crate::ttl::unix_now_ms());
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+
+        execute_line(format!("EXPORT json {}", path).as_bytes(), &mut session);
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("{\"key\":\"a\",\"value\":\"1\"}\n{\"key\":\"b\""));
+        std::fs::remove_file(&path).unwrap();
+        assert!(export_dataset(&mut session, DatasetFormat::Json, "/nonexistent/out.json").is_err());
+    }
+}
+
+
+// =====================================================================
+// Dataset Import Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod dataset_reader_tests {
+    use crate::{execute_line, import_dataset, import_rows, CsvRows, DatasetFormat, JsonRows, KvRow, Session};
+    use crate::dataset::IMPORT_BATCH;
+    use std::io;
+
+    fn row(key: &str, value: &str, expires_at: Option<i64>) -> KvRow {
+        KvRow { key: key.into(), value: value.into(), expires_at }
+    }
+
+    #[test]
+    fn json_rows_decode_escapes_and_reject_bad_lines() {
+        let text = "{\"key\":\"a\",\"value\":\"tab\\there \\ud83d\\ude00\",\"extra\":[1]}\n\
+                    {\"key\" : 7 , \"value\":\"x\", \"expires_at\":1760000000000, \"note\":true}\n\
+                    {\"key\":\"no value\"}\n\
+                    not json\n\
+                    {\"key\":\"b\",\"value\":\"\\\"q\\\"\"}";
+        let rows: Vec<io::Result<KvRow>> = JsonRows::new(text.as_bytes()).collect();
+        assert!(rows[0].is_err(), "nested values are not rows");
+        assert_eq!(rows[1].as_ref().unwrap(), &row("7", "x", Some(1_760_000_000_000)));
+        assert!(rows[2].is_err() && rows[3].is_err());
+        assert_eq!(rows[3].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(rows[4].as_ref().unwrap(), &row("b", "\"q\"", None));
+
+        let emoji: Vec<KvRow> = JsonRows::new("{\"key\":\"\\ud83d\\ude00\",\"value\":\"\\t\"}".as_bytes()).map(Result::unwrap).collect();
+        assert_eq!(emoji, [row("\u{1F600}", "\t", None)]);
+    }
+
+    #[test]
+    fn csv_rows_follow_the_header_and_quoting() {
+        let text = "expires_at,value,key,other\r\n1760000000000,\"a,\"\"b\"\"\r\nc\",k1,z\r\n\r\n,plain,k2\n5\n";
+        let rows: Vec<io::Result<KvRow>> = CsvRows::new(text.as_bytes()).unwrap().collect();
+        assert_eq!(rows[0].as_ref().unwrap(), &row("k1", "a,\"b\"\r\nc", Some(1_760_000_000_000)));
+        assert_eq!(rows[1].as_ref().unwrap(), &row("k2", "plain", None));
+        assert!(rows[2].is_err(), "a short row is rejected");
+        assert_eq!(rows.len(), 3);
+
+        assert!(CsvRows::new("key,val\n".as_bytes()).is_err());
+        assert!(CsvRows::new("".as_bytes()).is_err());
+        let open: Vec<io::Result<KvRow>> = CsvRows::new("key,value\nk,\"never closed\n".as_bytes()).unwrap().collect();
+        assert_eq!(open[0].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn import_counts_inserts_overwrites_and_skips() {
+        let mut session = Session::ephemeral();
+        session.set("old".into(), "0".into());
+        let future = crate::ttl::unix_now_ms() as i64 + 60_000;
+        let rows = vec![
+            Ok(row("old", "1", None)),
+            Ok(row("new", "2", Some(future))),
+            Ok(row("new", "3", Some(future))),
+            Ok(row("stale", "4", Some(1))),
+            Err(io::Error::new(io::ErrorKind::InvalidData, "bad")),
+        ];
+        let report = import_rows(&mut session, rows.into_iter()).unwrap();
+        assert_eq!((report.inserted, report.overwritten, report.expired_skipped, report.rejected), (1, 2, 1, 1));
+        assert_eq!(session.get("old"), Some("1".to_string()));
+        assert_eq!(session.get("new"), Some("3".to_string()));
+        assert!(session.ttl_status("new") > 0);
+        assert!(!session.exists("stale"));
+
+        let many = (0..IMPORT_BATCH + 5).map(|i| Ok(row(&format!("k{}", i), "v", None)));
+        assert_eq!(import_rows(&mut session, many).unwrap().inserted, IMPORT_BATCH + 5);
+        assert!(session.exists(&format!("k{}", IMPORT_BATCH + 4)));
+
+        let failed = std::iter::once(Err(io::Error::other("disk gone")));
+        assert!(import_rows(&mut session, failed).is_err());
+    }
+
+    #[test]
+    fn exports_import_back_through_the_command() {
+        let mut source = Session::ephemeral();
+        source.set("a".into(), "line\nbreak, \"quoted\"".into());
+        source.set("b".into(), "2".into());
+        assert_eq!(source.expire("b", 60_000), Ok(true));
+
+        for format in ["json", "csv"] {
+            let path = std::env::temp_dir().join(format!("kvstore_import_{}.{}", std::process::id(), format));
+            let path = path.to_string_lossy().into_owned();
+            execute_line(format!("EXPORT {} {}", format, path).as_bytes(), &mut source);
+
+            let mut target = Session::ephemeral();
+            target.set("b".into(), "old".into());
+            let report = import_dataset(&mut target, DatasetFormat::parse(format).unwrap(), &path).unwrap();
+            assert_eq!((report.inserted, report.overwritten), (1, 1));
+            let strip = |rows: Vec<KvRow>| rows.into_iter().map(|r| (r.key, r.value, r.expires_at.is_some())).collect::<Vec<_>>();
+            assert_eq!(strip(target.export_rows()), strip(source.export_rows()));
+            execute_line(format!("IMPORT {} {}", format, path).as_bytes(), &mut target);
+            std::fs::remove_file(&path).unwrap();
+        }
+        let mut target = Session::ephemeral();
+        assert!(import_dataset(&mut target, DatasetFormat::Csv, "/nonexistent/in.csv").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+// =====================================================================
+// File: dataset/writer.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Writes the keyspace as JSON lines or CSV (`EXPORT JSON`, `EXPORT CSV`).
+//
+//   JSON lines hold one object per key, `{"key":..,"value":..}` plus
+//   `"expires_at"` for keys with a TTL. CSV starts with the header
+//   `key,value,expires_at`; fields holding a comma, quote or line break
+//   are quoted, with quotes doubled. Rows are in key order, and like
+//   `EXPORT SQLITE` the file is written next to the target and renamed
+//   over it, so readers never see half an export.
+// =====================================================================
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::{json_string, KvRow, Session};
+
+/// A text format the keyspace can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// One JSON object per line.
+    Json,
+
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+
+impl DatasetFormat {
+    /// The format named `name` (`JSON` or `CSV`, any case).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::DatasetFormat;
+    /// assert_eq!(DatasetFormat::parse("csv"), Some(DatasetFormat::Csv));
+    /// assert_eq!(DatasetFormat::parse("SQLITE"), None);
+    /// ```
+    pub fn parse(name: &str) -> Option<DatasetFormat> {
+        match name.to_ascii_uppercase().as_str() {
+            "JSON" => Some(DatasetFormat::Json),
+            "CSV" => Some(DatasetFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+
+/// Write `rows` as JSON lines.
+///
+/// # Example
+/// ```
+/// use kvstore::{write_json, KvRow};
+/// let rows = [KvRow { key: "a".into(), value: "1".into(), expires_at: Some(1_700_000_000_000) }];
+/// let mut out = Vec::new();
+/// write_json(&rows, &mut out).unwrap();
+/// assert_eq!(out, b"{\"key\":\"a\",\"value\":\"1\",\"expires_at\":1700000000000}\n");
+/// ```
+pub fn write_json(rows: &[KvRow], mut out: impl Write) -> io::Result<()> {
+    for row in rows {
+        write!(out, "{{\"key\":{},\"value\":{}", json_string(&row.key), json_string(&row.value))?;
+        if let Some(at) = row.expires_at {
+            write!(out, ",\"expires_at\":{}", at)?;
+        }
+        writeln!(out, "}}")?;
+    }
+    out.flush()
+}
+
+
+/// Write `rows` as CSV, header first.
+///
+/// # Example
+/// ```
+/// use kvstore::{write_csv, KvRow};
+/// let rows = [KvRow { key: "a,b".into(), value: "say \"hi\"".into(), expires_at: None }];
+/// let mut out = Vec::new();
+/// write_csv(&rows, &mut out).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), "key,value,expires_at\n\"a,b\",\"say \"\"hi\"\"\",\n");
+/// ```
+pub fn write_csv(rows: &[KvRow], mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "key,value,expires_at")?;
+    for row in rows {
+        let at = row.expires_at.map_or(String::new(), |at| at.to_string());
+        writeln!(out, "{},{},{}", csv_field(&row.key), csv_field(&row.value), at)?;
+    }
+    out.flush()
+}
+
+
+/// `text` as one CSV field, quoted only if it needs to be.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+
+/// Export every live key `session` lets the current user see to `path`
+/// in `format`.
+///
+/// # Returns
+/// * `Ok(count)` of exported keys.
+/// * `Err(io::Error)` if the file can't be written.
+pub fn export_dataset(session: &mut Session, format: DatasetFormat, path: &str) -> io::Result<usize> {
+    let tmp = format!("{}.tmp", path);
+    let written = File::create(&tmp).and_then(|file| {
+        let out = BufWriter::new(file);
+        match format {
+            DatasetFormat::Json => session.export_json(out),
+            DatasetFormat::Csv => session.export_csv(out),
+        }
+    });
+    if written.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    let count = written?;
+    std::fs::rename(&tmp, path)?;
+    Ok(count)
+}
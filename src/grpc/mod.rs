@@ -0,0 +1,43 @@
+// =====================================================================
+// File: grpc/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `grpc` module serves `proto/kvstore.proto` over HTTP/2 with tonic,
+//! beside (or instead of) the line protocol, the REST API and the
+//! memcached protocol. It is only built with the `grpc` feature.
+//!
+//! Structure:
+//! - [`pb`]         : Messages, client and server generated from the proto
+//!   by `build.rs`.
+//! - `service.rs`   : [`GrpcService`], which runs each RPC as the
+//!   protocol command it maps to, and [`serve_grpc`].
+//! - `tests.rs`     : Unit tests for the RPCs, direct and over a socket.
+//!
+//! As with the REST API, every RPC runs as its line-protocol command, so
+//! ACLs, size limits, replicas and cluster shards behave as they do over
+//! TCP, and an `ERR` reply becomes a status ([`status_for`]). Basic
+//! credentials in the `authorization` metadata are checked with `AUTH`,
+//! and trace context metadata (`traceparent`, or whatever the global
+//! propagator reads) makes the call's span part of that trace.
+//! `Get`, `Mget` and `Range` without credentials are answered from the
+//! store's latest snapshot when it can serve them; `Range` then streams
+//! keys straight out of the index as the client reads them.
+// =====================================================================
+
+/// Code generated from `proto/kvstore.proto` (package `kvstore.v1`).
+#[allow(clippy::all, clippy::pedantic)]
+pub mod pb {
+    tonic::include_proto!("kvstore.v1");
+}
+
+// tonic's `Status` is large, but it is the error type the service must return
+#[allow(clippy::result_large_err)]
+pub mod service;
+
+pub use self::service::{serve_grpc, status_for, GrpcService};
+
+#[cfg(test)]
+pub mod tests;
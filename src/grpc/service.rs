@@ -0,0 +1,435 @@
+// =====================================================================
+// File: grpc/service.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   `GrpcService` implements the `KvStore` service over a shared store,
+//   and `serve_grpc` runs it on a listener.
+//
+//   The session is synchronous, so each call runs on tokio's blocking
+//   pool: it takes the session lock (or reads the latest snapshot) just
+//   as an HTTP request does, and the runtime's own threads never wait on
+//   it. A `Range` answered from the session collects its keys under the
+//   lock and streams them after releasing it, so a slow reader never
+//   holds up writers.
+//
+//   Quotas match the other front ends:
+//     * `max_connections`    - calls past this many in flight fail with
+//                              RESOURCE_EXHAUSTED
+//     * `idle_timeout`       - bounds how long a call may take to answer
+//     * `max_pending_output` - a reply that grows past this many bytes
+//                              fails with RESOURCE_EXHAUSTED
+// =====================================================================
+
+use std::io;
+use std::net::TcpListener;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::metadata::MetadataMap;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status};
+
+use super::pb::kv_store_server::{KvStore, KvStoreServer};
+use super::pb::{
+    DelRequest, DelResponse, ExpireRequest, ExpireResponse, GetRequest, GetResponse, MgetRequest, MgetResponse, RangeRequest,
+    RangeResponse, SetRequest, SetResponse, TxnRequest, TxnResponse,
+};
+use crate::http::{basic_credentials, range_bound};
+use crate::server::{apply_reload_request, spawn_replication_poller, Connection};
+use crate::{capture_replies, execute_line, quote_arg, telemetry, ServerConfig, Session, SharedStore, Snapshot};
+
+/// Keys a `Range` stream may run ahead of its client.
+const RANGE_BUFFER: usize = 64;
+
+/// The `KvStore` service, answering from one shared store.
+pub struct GrpcService {
+    store: Arc<SharedStore>,
+    config: ServerConfig,
+
+    /// One permit per call in flight, `max_connections` in all.
+    active: Arc<Semaphore>,
+}
+
+
+/// What a call carries besides its message.
+struct Caller {
+    /// User and password from `authorization: Basic ...` metadata.
+    credentials: Option<(String, String)>,
+
+    /// Trace context metadata (`traceparent` and whatever else the global
+    /// propagator reads).
+    trace_headers: Vec<(String, String)>,
+}
+
+
+/// Where a call is answered from.
+enum Target<'a> {
+    /// The store's latest snapshot, without the session lock.
+    Snapshot(&'a Snapshot),
+
+    /// The session, locked, as the caller's user.
+    Session(&'a mut Session),
+}
+
+
+impl GrpcService {
+    /// Serve `store` within `config`'s limits.
+    pub fn new(store: Arc<SharedStore>, config: ServerConfig) -> Self {
+        let active = Arc::new(Semaphore::new(config.max_connections.min(Semaphore::MAX_PERMITS)));
+        GrpcService { store, config, active }
+    }
+
+
+    /// A slot for one more call, if `max_connections` allows it.
+    fn admit(&self) -> Result<OwnedSemaphorePermit, Status> {
+        self.active.clone().try_acquire_owned().map_err(|_| Status::resource_exhausted("ERR max number of clients reached"))
+    }
+
+
+    /// Run `answer` on the blocking pool; see [`answer`].
+    async fn call<T: Send + 'static>(
+        &self,
+        method: &'static str,
+        metadata: &MetadataMap,
+        reads: bool,
+        answer_with: impl FnOnce(&mut Target<'_>, &ServerConfig) -> Result<T, Status> + Send + 'static,
+    ) -> Result<T, Status> {
+        let permit = self.admit()?;
+        let caller = caller(metadata)?;
+        let (store, config) = (self.store.clone(), self.config);
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            answer(&store, &config, method, &caller, reads, answer_with)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("ERR {}", e)))?
+    }
+}
+
+
+#[tonic::async_trait]
+impl KvStore for GrpcService {
+    type RangeStream = Pin<Box<dyn Stream<Item = Result<RangeResponse, Status>> + Send>>;
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let key = request.get_ref().key.clone();
+        let value = self.call("Get", request.metadata(), true, move |target, config| get(target, &key, config)).await?;
+        Ok(Response::new(GetResponse { value }))
+    }
+
+
+    async fn set(&self, request: Request<SetRequest>) -> Result<Response<SetResponse>, Status> {
+        let line = set_line(request.get_ref())?;
+        self.call("Set", request.metadata(), false, move |target, config| run(target, &line, config)).await?;
+        Ok(Response::new(SetResponse {}))
+    }
+
+
+    async fn del(&self, request: Request<DelRequest>) -> Result<Response<DelResponse>, Status> {
+        let line = format!("DEL {}", key_arg(&request.get_ref().key)?);
+        let replies = self.call("Del", request.metadata(), false, move |target, config| run(target, &line, config)).await?;
+        Ok(Response::new(DelResponse { deleted: replies.first().is_some_and(|reply| reply == "1") }))
+    }
+
+
+    async fn mget(&self, request: Request<MgetRequest>) -> Result<Response<MgetResponse>, Status> {
+        let keys = request.get_ref().keys.clone();
+        if keys.is_empty() {
+            return Err(Status::invalid_argument("ERR MGET requires at least one key"));
+        }
+        // One GET per key, all under one lock (or from one snapshot), so
+        // values that span lines come back whole
+        let values = self
+            .call("Mget", request.metadata(), true, move |target, config| {
+                keys.iter().map(|key| get(target, key, config)).collect::<Result<Vec<_>, _>>()
+            })
+            .await?;
+        Ok(Response::new(MgetResponse { values: values.into_iter().map(|value| GetResponse { value }).collect() }))
+    }
+
+
+    async fn range(&self, request: Request<RangeRequest>) -> Result<Response<Self::RangeStream>, Status> {
+        let permit = self.admit()?;
+        let caller = caller(request.metadata())?;
+        let RangeRequest { start, end } = request.into_inner();
+        let (store, config) = (self.store.clone(), self.config);
+        let (sender, receiver) = mpsc::channel(RANGE_BUFFER);
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let send = |key: String| sender.blocking_send(Ok(RangeResponse { key })).is_ok();
+            let collected = answer(&store, &config, "Range", &caller, true, |target, config| match target {
+                Target::Snapshot(snapshot) => {
+                    snapshot.scan(&start, &end, |key| send(key.to_string()));
+                    Ok(Vec::new())
+                }
+                Target::Session(_) => {
+                    let line = format!("RANGE {} {}", range_bound(&start, "-"), range_bound(&end, "+"));
+                    let mut keys = run(target, &line, config)?;
+                    keys.pop(); // END
+                    Ok(keys)
+                }
+            });
+            match collected {
+                Ok(keys) => {
+                    for key in keys {
+                        if !send(key) {
+                            break;
+                        }
+                    }
+                }
+                Err(status) => {
+                    let _ = sender.blocking_send(Err(status));
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+
+
+    async fn expire(&self, request: Request<ExpireRequest>) -> Result<Response<ExpireResponse>, Status> {
+        let line = format!("EXPIRE {} {}", key_arg(&request.get_ref().key)?, request.get_ref().milliseconds);
+        let replies = self.call("Expire", request.metadata(), false, move |target, config| run(target, &line, config)).await?;
+        Ok(Response::new(ExpireResponse { applied: replies.first().is_some_and(|reply| reply == "1") }))
+    }
+
+
+    async fn txn(&self, request: Request<TxnRequest>) -> Result<Response<TxnResponse>, Status> {
+        let lines = request.get_ref().writes.iter().map(set_line).collect::<Result<Vec<_>, _>>()?;
+        if lines.is_empty() {
+            return Err(Status::invalid_argument("ERR transaction has no writes"));
+        }
+        self.call("Txn", request.metadata(), false, move |target, config| {
+            run(target, "BEGIN", config)?;
+            for line in &lines {
+                if let Err(status) = run(target, line, config) {
+                    let _ = run(target, "ABORT", config);
+                    return Err(status);
+                }
+            }
+            run(target, "COMMIT", config)
+        })
+        .await?;
+        Ok(Response::new(TxnResponse {}))
+    }
+}
+
+
+/// Serve the `KvStore` gRPC service on `listener` until it fails.
+///
+/// Runs a tokio runtime on the calling thread; the RPCs themselves run
+/// on its blocking pool.
+///
+/// # Example
+/// ```no_run
+/// use kvstore::grpc::serve_grpc;
+/// use kvstore::{ServerConfig, Session, SharedStore};
+/// use std::net::TcpListener;
+/// use std::sync::Arc;
+///
+/// let listener = TcpListener::bind("127.0.0.1:50051").unwrap();
+/// serve_grpc(listener, Arc::new(SharedStore::new(Session::open("data").unwrap())), ServerConfig::new()).unwrap();
+/// ```
+pub fn serve_grpc(listener: TcpListener, store: Arc<SharedStore>, config: ServerConfig) -> io::Result<()> {
+    spawn_replication_poller(&store);
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+
+    runtime.block_on(async move {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        let incoming = TcpIncoming::from_listener(listener, true, None).map_err(io::Error::other)?;
+        let mut server = Server::builder();
+        if let Some(timeout) = config.idle_timeout {
+            server = server.timeout(timeout);
+        }
+        server
+            .add_service(KvStoreServer::new(GrpcService::new(store, config)))
+            .serve_with_incoming(incoming)
+            .await
+            .map_err(io::Error::other)
+    })
+}
+
+
+/// The status for an `ERR <message>` reply; see the table in the proto.
+///
+/// # Example
+/// ```
+/// use kvstore::grpc::status_for;
+/// use tonic::Code;
+/// assert_eq!(status_for("ERR NOAUTH authentication required").code(), Code::Unauthenticated);
+/// assert_eq!(status_for("ERR MOVED 3 10.0.0.2:6380").code(), Code::FailedPrecondition);
+/// assert_eq!(status_for("ERR value too long").code(), Code::InvalidArgument);
+/// ```
+pub fn status_for(reply: &str) -> Status {
+    let message = reply.strip_prefix("ERR").unwrap_or(reply).trim_start_matches([':', ' ']);
+    let code = match message.split(' ').next().unwrap_or("") {
+        "NOAUTH" => Code::Unauthenticated,
+        "NOPERM" | "READONLY" => Code::PermissionDenied,
+        "MOVED" => Code::FailedPrecondition,
+        "LOADING" => Code::Unavailable,
+        _ if message.starts_with("persistence failure") || message.starts_with("read-only mode") => Code::Unavailable,
+        _ => Code::InvalidArgument,
+    };
+    Status::new(code, reply)
+}
+
+
+/// Answer one call: from the snapshot if it `reads` and may, otherwise
+/// against the session as the caller's user, inside a `kvstore.grpc`
+/// span.
+fn answer<T>(
+    store: &SharedStore,
+    config: &ServerConfig,
+    method: &'static str,
+    caller: &Caller,
+    reads: bool,
+    answer_with: impl FnOnce(&mut Target<'_>, &ServerConfig) -> Result<T, Status>,
+) -> Result<T, Status> {
+    traced(method, &caller.trace_headers, || {
+        let snapshot = store.snapshot();
+        if reads && caller.credentials.is_none() && snapshot.serves_reads {
+            return answer_with(&mut Target::Snapshot(&snapshot), config);
+        }
+        store.write(|session| {
+            apply_reload_request(session);
+            let mut conn = Connection::default();
+            conn.swap(session);
+            let mut target = Target::Session(&mut *session);
+            let result = authenticate(&mut target, caller, config).and_then(|()| answer_with(&mut target, config));
+            if let Err(e) = session.tick() {
+                eprintln!("background work failed: {}", e);
+            }
+            conn.swap(session);
+            result
+        })
+    })
+}
+
+
+/// Run `answer` for `method`, joining the trace `trace_headers` carry.
+fn traced<T>(method: &'static str, trace_headers: &[(String, String)], answer: impl FnOnce() -> Result<T, Status>) -> Result<T, Status> {
+    telemetry::with_remote_parent(trace_headers, || traced_call(method, answer))
+}
+
+
+/// `answer` inside a `kvstore.grpc` span.
+fn traced_call<T>(method: &'static str, answer: impl FnOnce() -> Result<T, Status>) -> Result<T, Status> {
+    let mut span = telemetry::span("kvstore.grpc");
+    span.attr("rpc.system", "grpc").attr("rpc.service", "kvstore.v1.KvStore").attr("rpc.method", method);
+    let result = answer();
+    let code = result.as_ref().err().map_or(Code::Ok, Status::code);
+    span.attr("rpc.grpc.status_code", i64::from(code as i32));
+    if let Err(status) = &result
+        && matches!(code, Code::Unavailable | Code::ResourceExhausted | Code::Internal)
+    {
+        span.error(status.message());
+    }
+    result
+}
+
+
+/// Credentials and trace parent from a call's metadata.
+fn caller(metadata: &MetadataMap) -> Result<Caller, Status> {
+    let text = |name: &str| metadata.get(name).map(|value| value.to_str().map_err(|_| Status::invalid_argument(format!("ERR {} is not text", name))));
+    let credentials = match text("authorization").transpose()? {
+        Some(header) => Some(basic_credentials(header).ok_or_else(|| Status::unauthenticated("ERR malformed Basic credentials"))?),
+        None => None,
+    };
+    // Unreadable trace context is ignored, as a malformed `traceparent` is
+    let trace_headers = telemetry::propagation_fields()
+        .into_iter()
+        .filter_map(|field| Some((field.clone(), metadata.get(field.as_str())?.to_str().ok()?.to_string())))
+        .collect();
+    Ok(Caller { credentials, trace_headers })
+}
+
+
+/// `AUTH` as the caller's user, if they sent credentials.
+fn authenticate(target: &mut Target<'_>, caller: &Caller, config: &ServerConfig) -> Result<(), Status> {
+    if let Some((user, password)) = &caller.credentials {
+        run(target, &format!("AUTH {} {}", quote_arg(user), quote_arg(password)), config)
+            .map_err(|refused| Status::unauthenticated(refused.message()))?;
+    }
+    Ok(())
+}
+
+
+/// A key's value, or `None` if it is missing or expired.
+fn get(target: &mut Target<'_>, key: &str, config: &ServerConfig) -> Result<Option<String>, Status> {
+    let line = format!("GET {}", key_arg(key)?);
+    match target {
+        Target::Snapshot(snapshot) => match snapshot.get(key) {
+            Some(value) if value.len() < config.max_pending_output => Ok(Some(value)),
+            Some(_) => Err(overflow()),
+            None => Ok(None),
+        },
+        Target::Session(session) => {
+            // A value may span lines, or even read `nil` or `ERR ...`
+            let text = capture(session, &line, config)?;
+            let value = text.strip_suffix('\n').unwrap_or(&text);
+            if session.get(key).is_some_and(|stored| stored == value) {
+                Ok(Some(value.to_string()))
+            } else if value == "nil" {
+                Ok(None)
+            } else {
+                Err(status_for(value.lines().next().unwrap_or("")))
+            }
+        }
+    }
+}
+
+
+/// Run one protocol line against the session and collect its reply lines.
+///
+/// # Returns
+/// `Err(status)` for an `ERR` reply, or a reply past the output cap.
+fn run(target: &mut Target<'_>, line: &str, config: &ServerConfig) -> Result<Vec<String>, Status> {
+    let Target::Session(session) = target else {
+        return Err(Status::internal("ERR writes need the session"));
+    };
+    let text = capture(session, line, config)?;
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    match lines.iter().find(|line| line.starts_with("ERR")) {
+        Some(error) => Err(status_for(error)),
+        None => Ok(lines),
+    }
+}
+
+
+/// Run one protocol line and return its replies as written.
+fn capture(session: &mut Session, line: &str, config: &ServerConfig) -> Result<String, Status> {
+    let (_, captured) = capture_replies(config.max_pending_output, || execute_line(line.as_bytes(), session));
+    if captured.overflowed {
+        return Err(overflow());
+    }
+    Ok(String::from_utf8_lossy(&captured.bytes).into_owned())
+}
+
+
+/// The `SET` line for one write.
+fn set_line(write: &SetRequest) -> Result<String, Status> {
+    Ok(format!("SET {} {}", key_arg(&write.key)?, quote_arg(&write.value)))
+}
+
+
+/// `key` quoted as a protocol argument; empty keys are refused.
+fn key_arg(key: &str) -> Result<String, Status> {
+    if key.is_empty() {
+        return Err(Status::invalid_argument("ERR empty key"));
+    }
+    Ok(quote_arg(key))
+}
+
+
+fn overflow() -> Status {
+    Status::resource_exhausted("ERR reply exceeds max pending output")
+}
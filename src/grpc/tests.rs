@@ -0,0 +1,192 @@
+// =====================================================================
+// File: grpc/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for the gRPC service, called directly and over a socket.
+//
+// Notes:
+//   * Only compiled when running `cargo test --features grpc`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// GrpcService Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod grpc_service_tests {
+    use std::future::Future;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    use tokio_stream::StreamExt;
+    use tonic::{Code, Request};
+
+    use crate::grpc::pb::kv_store_client::KvStoreClient;
+    use crate::grpc::pb::kv_store_server::KvStore;
+    use crate::grpc::pb::{DelRequest, ExpireRequest, GetRequest, MgetRequest, RangeRequest, SetRequest, TxnRequest};
+    use crate::grpc::{serve_grpc, GrpcService};
+    use crate::{Acl, MemFs, ServerConfig, Session, SharedStore};
+
+    fn memory_store() -> Arc<SharedStore> {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        Arc::new(SharedStore::new(session))
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(future)
+    }
+
+    fn set(key: &str, value: &str) -> SetRequest {
+        SetRequest { key: key.into(), value: value.into() }
+    }
+
+    /// `request` with `reader:pw` Basic credentials.
+    fn as_reader<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", "Basic cmVhZGVyOnB3".parse().unwrap());
+        request
+    }
+
+    async fn get(service: &GrpcService, key: &str) -> Option<String> {
+        service.get(Request::new(GetRequest { key: key.into() })).await.unwrap().into_inner().value
+    }
+
+    async fn range(service: &GrpcService, request: Request<RangeRequest>) -> Result<Vec<String>, Code> {
+        let mut stream = service.range(request).await.map_err(|status| status.code())?.into_inner();
+        let mut keys = Vec::new();
+        while let Some(message) = stream.next().await {
+            keys.push(message.map_err(|status| status.code())?.key);
+        }
+        Ok(keys)
+    }
+
+    #[test]
+    fn keys_can_be_stored_read_and_deleted() {
+        let service = GrpcService::new(memory_store(), ServerConfig::new());
+        block_on(async {
+            for (key, value) in [("a key", "two\nlines"), ("n", "nil"), ("e", "ERR not really")] {
+                service.set(Request::new(set(key, value))).await.unwrap();
+                assert_eq!(get(&service, key).await.as_deref(), Some(value));
+            }
+
+            let mget = service.mget(Request::new(MgetRequest { keys: vec!["n".into(), "gone".into()] })).await.unwrap();
+            let values: Vec<_> = mget.into_inner().values.into_iter().map(|v| v.value).collect();
+            assert_eq!(values, [Some("nil".to_string()), None]);
+
+            let del = |key: &str| service.del(Request::new(DelRequest { key: key.into() }));
+            assert!(del("a key").await.unwrap().into_inner().deleted);
+            assert!(!del("a key").await.unwrap().into_inner().deleted);
+            assert_eq!(get(&service, "a key").await, None);
+            assert_eq!(del("").await.unwrap_err().code(), Code::InvalidArgument);
+        });
+    }
+
+    #[test]
+    fn range_streams_keys_between_the_bounds() {
+        let service = GrpcService::new(memory_store(), ServerConfig::new());
+        block_on(async {
+            for key in ["-", "a", "b", "c"] {
+                service.set(Request::new(set(key, "1"))).await.unwrap();
+            }
+            let between = |start: &str, end: &str| Request::new(RangeRequest { start: start.into(), end: end.into() });
+            assert_eq!(range(&service, between("b", "")).await.unwrap(), ["b", "c"]);
+            assert_eq!(range(&service, between("", "a")).await.unwrap(), ["-", "a"]);
+            assert_eq!(range(&service, between("-", "-")).await.unwrap(), ["-"]);
+
+            let expire = service.expire(Request::new(ExpireRequest { key: "c".into(), milliseconds: 1 })).await.unwrap();
+            assert!(expire.into_inner().applied);
+            thread::sleep(std::time::Duration::from_millis(5));
+            assert_eq!(range(&service, between("b", "")).await.unwrap(), ["b"]);
+            let missing = service.expire(Request::new(ExpireRequest { key: "zz".into(), milliseconds: 10 })).await.unwrap();
+            assert!(!missing.into_inner().applied);
+        });
+    }
+
+    #[test]
+    fn transactions_apply_all_or_nothing() {
+        let store = memory_store();
+        let service = GrpcService::new(store.clone(), ServerConfig::new());
+        block_on(async {
+            let txn = |writes: Vec<SetRequest>| service.txn(Request::new(TxnRequest { writes }));
+            txn(vec![set("a", "1"), set("b", "2")]).await.unwrap();
+            assert_eq!(get(&service, "b").await.as_deref(), Some("2"));
+
+            // A bad write anywhere leaves everything as it was
+            store.write(|session| session.limits.max_value_len = 4);
+            let refused = txn(vec![set("a", "10"), set("b", "too-long")]).await.unwrap_err();
+            assert_eq!(refused.code(), Code::InvalidArgument);
+            assert_eq!(get(&service, "a").await.as_deref(), Some("1"));
+            assert!(!store.write(|session| session.in_transaction()));
+
+            assert_eq!(txn(Vec::new()).await.unwrap_err().code(), Code::InvalidArgument);
+        });
+    }
+
+    #[test]
+    fn acl_errors_map_to_auth_statuses() {
+        let store = memory_store();
+        store.write(|session| {
+            session.set("app:x".into(), "1".into());
+            session.set("other".into(), "2".into());
+            session.acl = Some(Acl::parse("user reader pw read app:*\n").unwrap());
+        });
+        let service = GrpcService::new(store, ServerConfig::new());
+        block_on(async {
+            let refused = service.get(Request::new(GetRequest { key: "app:x".into() })).await.unwrap_err();
+            assert_eq!(refused.code(), Code::Unauthenticated);
+
+            let read = service.get(as_reader(GetRequest { key: "app:x".into() })).await.unwrap();
+            assert_eq!(read.into_inner().value.as_deref(), Some("1"));
+            let other = service.get(as_reader(GetRequest { key: "other".into() })).await.unwrap_err();
+            assert_eq!(other.code(), Code::PermissionDenied);
+            let write = service.set(as_reader(set("app:y", "1"))).await.unwrap_err();
+            assert_eq!(write.code(), Code::PermissionDenied);
+
+            // Ranges through the session list what the user may read
+            let keys = range(&service, as_reader(RangeRequest { start: "app:".into(), end: "app:~".into() })).await;
+            assert_eq!(keys.unwrap(), ["app:x"]);
+
+            let mut wrong = Request::new(GetRequest { key: "app:x".into() });
+            wrong.metadata_mut().insert("authorization", "Basic cmVhZGVyOm5v".parse().unwrap());
+            assert_eq!(service.get(wrong).await.unwrap_err().code(), Code::Unauthenticated);
+        });
+    }
+
+    #[test]
+    fn calls_past_the_quota_are_refused() {
+        let config = ServerConfig { max_connections: 0, ..ServerConfig::new() };
+        let service = GrpcService::new(memory_store(), config);
+        block_on(async {
+            let refused = service.get(Request::new(GetRequest { key: "a".into() })).await.unwrap_err();
+            assert_eq!(refused.code(), Code::ResourceExhausted);
+            let refused = range(&service, Request::new(RangeRequest::default())).await.unwrap_err();
+            assert_eq!(refused, Code::ResourceExhausted);
+        });
+    }
+
+    #[test]
+    fn calls_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let store = memory_store();
+        thread::spawn(move || serve_grpc(listener, store, ServerConfig::new()));
+
+        block_on(async {
+            let mut client = KvStoreClient::connect(format!("http://{}", addr)).await.unwrap();
+            client.set(set("k", "value")).await.unwrap();
+            let value = client.get(GetRequest { key: "k".into() }).await.unwrap().into_inner().value;
+            assert_eq!(value.as_deref(), Some("value"));
+
+            let mut stream = client.range(RangeRequest::default()).await.unwrap().into_inner();
+            assert_eq!(stream.message().await.unwrap().map(|m| m.key).as_deref(), Some("k"));
+            assert!(stream.message().await.unwrap().is_none());
+        });
+    }
+}
@@ -0,0 +1,892 @@
+// =====================================================================
+// File: http.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   HTTP front end: a small REST API over the same session as the line
+//   protocol, for clients that would rather not speak it.
+//
+//     GET    /keys/{key}              -> 200 and the value, or 404
+//     PUT    /keys/{key}              -> store the request body: 200 OK
+//     DELETE /keys/{key}              -> 200 OK, or 404
+//     GET    /range?start=..&end=..   -> 200 and one key per line
+//     POST   /tx                      -> apply the body's SET / MSET lines
+//                                        in one transaction: 200 OK
+//
+//   Every request runs as the line-protocol command it maps to, so ACLs,
+//   size limits, replicas and cluster shards behave exactly as they do
+//   over TCP; an `ERR` reply becomes an error status with the message as
+//   its body. HTTP Basic credentials are checked with `AUTH`, and trace
+//   context headers (`traceparent`, or whatever the global propagator
+//   reads) make the request's span part of that trace.
+//
+//   One request per connection (`Connection: close`), under the same
+//   connection limit, idle timeout and output cap as the TCP server.
+//   Like the TCP server, a `GET` of a key or a range without credentials
+//   is answered from the store's latest snapshot, without the session
+//   lock, when the snapshot can serve it.
+// =====================================================================
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::crypt::base64_decode;
+use crate::server::{apply_reload_request, spawn_replication_poller, Connection, Slot};
+use crate::{capture_replies, execute_line, parse_command, quote_arg, telemetry, ServerConfig, Session, SharedStore, Snapshot};
+
+/// Largest request line plus headers, in bytes.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// A parsed HTTP request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// Request method, as sent (`GET`, `PUT`, ...).
+    pub method: String,
+
+    /// Path, still percent-encoded, without the query string.
+    pub path: String,
+
+    /// Query string, still percent-encoded (empty if none).
+    pub query: String,
+
+    /// Value of the `Authorization` header, if any.
+    pub authorization: Option<String>,
+
+    /// Trace context headers (`traceparent` and whatever else the global
+    /// propagator reads); the request's span joins the trace they carry.
+    pub trace_headers: Vec<(String, String)>,
+
+    /// Request body.
+    pub body: Vec<u8>,
+}
+
+
+impl HttpRequest {
+    /// Builds a request with no query, credentials or body.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::HttpRequest;
+    /// let request = HttpRequest::new("GET", "/keys/a");
+    /// assert_eq!(request.path, "/keys/a");
+    /// assert!(request.body.is_empty());
+    /// ```
+    pub fn new(method: &str, path: &str) -> Self {
+        HttpRequest { method: method.to_string(), path: path.to_string(), ..HttpRequest::default() }
+    }
+}
+
+
+/// What a request is answered with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// Status code.
+    pub status: u16,
+
+    /// Response body (`text/plain`).
+    pub body: String,
+}
+
+
+impl HttpResponse {
+    /// A response with `status` and `body`.
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        HttpResponse { status, body: body.into() }
+    }
+
+
+    /// The error response for an `ERR <message>` reply.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::HttpResponse;
+    /// assert_eq!(HttpResponse::from_error("ERR NOAUTH authentication required").status, 401);
+    /// assert_eq!(HttpResponse::from_error("ERR value too long").status, 400);
+    /// ```
+    pub fn from_error(reply: &str) -> Self {
+        let message = reply.strip_prefix("ERR").unwrap_or(reply).trim_start_matches([':', ' ']);
+        let status = match message.split(' ').next().unwrap_or("") {
+            "NOAUTH" => 401,
+            "NOPERM" | "READONLY" => 403,
+            "MOVED" => 421,
+            "LOADING" => 503,
+            _ if message.starts_with("persistence failure") || message.starts_with("read-only mode") => 503,
+            _ => 400,
+        };
+        HttpResponse::new(status, format!("{}\n", reply))
+    }
+
+
+    /// Reason phrase for the status code.
+    pub fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            421 => "Misdirected Request",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        }
+    }
+
+
+    /// Write the full response, headers and body.
+    fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status, self.reason(), self.body.len()
+        );
+        if self.status == 401 {
+            head.push_str("WWW-Authenticate: Basic realm=\"kvstore\"\r\n");
+        }
+        head.push_str("\r\n");
+        out.write_all(head.as_bytes())?;
+        out.write_all(self.body.as_bytes())?;
+        out.flush()
+    }
+}
+
+
+/// Serve the REST API from `listener` on a shared store.
+///
+/// # Example
+/// ```no_run
+/// use kvstore::{serve_http, ServerConfig, Session, SharedStore};
+/// use std::net::TcpListener;
+/// use std::sync::Arc;
+///
+/// let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+/// serve_http(listener, Arc::new(SharedStore::new(Session::open("data").unwrap())), ServerConfig::new()).unwrap();
+/// ```
+pub fn serve_http(listener: TcpListener, store: Arc<SharedStore>, config: ServerConfig) -> io::Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+    spawn_replication_poller(&store);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if active.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let _ = HttpResponse::new(503, "ERR max number of clients reached\n").write_to(&mut stream);
+            continue;
+        }
+        let slot = Slot(active.clone());
+        let store = store.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = handle_http_client(stream, &store, config) {
+                eprintln!("http client error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+
+/// Read one request, answer it and close the connection.
+fn handle_http_client(stream: TcpStream, store: &SharedStore, config: ServerConfig) -> io::Result<()> {
+    stream.set_read_timeout(config.idle_timeout)?;
+    stream.set_write_timeout(config.idle_timeout)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let request = match read_request(&mut reader, config.max_pending_output) {
+        Ok(request) => request,
+        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+            return HttpResponse::new(408, "ERR idle timeout, closing connection\n").write_to(&mut writer);
+        }
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+        Err(e) => return error_for(&e).write_to(&mut writer),
+    };
+
+    let snapshot = store.snapshot();
+    let response = if reads_from_snapshot(&snapshot, &request) {
+        traced(&request, || answer_read(&snapshot, &request, &config))
+    } else {
+        store.write(|session| {
+            apply_reload_request(session);
+            let mut conn = Connection::default();
+            conn.swap(session);
+            let response = handle_request(session, &request, &config);
+            if let Err(e) = session.tick() {
+                eprintln!("background work failed: {}", e);
+            }
+            conn.swap(session);
+            response
+        })
+    };
+    response.write_to(&mut writer)
+}
+
+
+/// Parse a request line, headers and `Content-Length` body.
+fn read_request(reader: &mut impl BufRead, max_body: usize) -> io::Result<HttpRequest> {
+    let mut head_bytes = 0;
+    let mut next_line = |reader: &mut dyn BufRead| -> io::Result<String> {
+        let mut line = Vec::new();
+        let read = reader.take((MAX_HEAD_BYTES - head_bytes) as u64).read_until(b'\n', &mut line)?;
+        head_bytes += read;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "client closed the connection"));
+        }
+        if !line.ends_with(b"\n") {
+            return Err(io::Error::new(io::ErrorKind::OutOfMemory, "request head too large"));
+        }
+        let text = String::from_utf8(line).map_err(|_| invalid("request head is not UTF-8"))?;
+        Ok(text.trim_end_matches(['\r', '\n']).to_string())
+    };
+
+    let request_line = next_line(reader)?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(invalid("only HTTP/1.x is supported"));
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = HttpRequest { query: query.to_string(), ..HttpRequest::new(method, path) };
+
+    let trace_fields = telemetry::propagation_fields();
+    let mut length = 0;
+    loop {
+        let line = next_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(invalid("malformed header"));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.parse().map_err(|_| invalid("bad Content-Length"))?,
+            "transfer-encoding" => return Err(io::Error::new(io::ErrorKind::Unsupported, "chunked bodies are not supported")),
+            "authorization" => request.authorization = Some(value.to_string()),
+            name if trace_fields.iter().any(|field| field == name) => {
+                request.trace_headers.push((name.to_string(), value.to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    if length > max_body {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, "request body too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+
+/// Answer one request against `session`, as the connection's own user.
+///
+/// # Example
+/// ```
+/// use kvstore::{handle_request, HttpRequest, MemFs, ServerConfig, Session};
+/// let mut session = Session::ephemeral();
+/// session.fs = std::sync::Arc::new(MemFs::new());
+/// let config = ServerConfig::new();
+///
+/// let put = HttpRequest { body: b"hello".to_vec(), ..HttpRequest::new("PUT", "/keys/greeting") };
+/// assert_eq!(handle_request(&mut session, &put, &config).status, 200);
+/// let get = handle_request(&mut session, &HttpRequest::new("GET", "/keys/greeting"), &config);
+/// assert_eq!((get.status, get.body.as_str()), (200, "hello"));
+/// ```
+pub fn handle_request(session: &mut Session, request: &HttpRequest, config: &ServerConfig) -> HttpResponse {
+    traced(request, || answer(session, request, config))
+}
+
+
+/// Run `answer` for `request`, joining the trace its headers carry.
+fn traced(request: &HttpRequest, answer: impl FnOnce() -> HttpResponse) -> HttpResponse {
+    telemetry::with_remote_parent(&request.trace_headers, || traced_request(request, answer))
+}
+
+
+/// `answer` inside a `kvstore.http` span.
+fn traced_request(request: &HttpRequest, answer: impl FnOnce() -> HttpResponse) -> HttpResponse {
+    let mut span = telemetry::span("kvstore.http");
+    span.attr("http.request.method", request.method.as_str()).attr("url.path", request.path.as_str());
+    let response = answer();
+    span.attr("http.response.status_code", i64::from(response.status));
+    if response.status >= 500 {
+        span.error(response.body.trim_end());
+    }
+    response
+}
+
+
+/// Authenticate, then route.
+fn answer(session: &mut Session, request: &HttpRequest, config: &ServerConfig) -> HttpResponse {
+    let result = (|| {
+        if let Some(credentials) = &request.authorization {
+            let (user, password) = basic_credentials(credentials).ok_or_else(|| HttpResponse::new(401, "ERR malformed Basic credentials\n"))?;
+            run(session, &format!("AUTH {} {}", quote_arg(&user), quote_arg(&password)), config)
+                .map_err(|refused| HttpResponse::new(401, refused.body))?;
+        }
+        route(session, request, config)
+    })();
+    result.unwrap_or_else(|response| response)
+}
+
+
+/// Dispatch on method and path.
+fn route(session: &mut Session, request: &HttpRequest, config: &ServerConfig) -> Result<HttpResponse, HttpResponse> {
+    if let Some(key) = request.path.strip_prefix("/keys/") {
+        let key = percent_decode(key, false).ok_or_else(|| HttpResponse::new(400, "ERR malformed key in path\n"))?;
+        if key.is_empty() {
+            return Err(HttpResponse::new(400, "ERR empty key\n"));
+        }
+        let quoted = quote_arg(&key);
+        return match request.method.as_str() {
+            "GET" => {
+                // A value may span lines, or even read `nil` or `ERR ...`
+                let text = capture(session, &format!("GET {}", quoted), config)?;
+                let value = text.strip_suffix('\n').unwrap_or(&text);
+                if session.get(&key).is_some_and(|stored| stored == value) {
+                    Ok(HttpResponse::new(200, value))
+                } else if value == "nil" {
+                    Err(HttpResponse::new(404, "nil\n"))
+                } else {
+                    Err(HttpResponse::from_error(value.lines().next().unwrap_or("")))
+                }
+            }
+            "PUT" => {
+                let value = std::str::from_utf8(&request.body).map_err(|_| HttpResponse::new(400, "ERR value is not UTF-8\n"))?;
+                run(session, &format!("SET {} {}", quoted, quote_arg(value)), config)?;
+                Ok(HttpResponse::new(200, "OK\n"))
+            }
+            "DELETE" => match run(session, &format!("DEL {}", quoted), config)?.first().map(String::as_str) {
+                Some("1") => Ok(HttpResponse::new(200, "OK\n")),
+                _ => Err(HttpResponse::new(404, "nil\n")),
+            },
+            _ => Err(HttpResponse::new(405, "ERR /keys/{key} allows GET, PUT and DELETE\n")),
+        };
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/range") => {
+            let mut lines = run(session, &range_line(&request.query)?, config)?;
+            lines.pop(); // END
+            Ok(HttpResponse::new(200, lines.iter().map(|key| format!("{}\n", key)).collect::<String>()))
+        }
+        ("POST", "/tx") => transaction(session, &request.body, config),
+        (_, "/range") => Err(HttpResponse::new(405, "ERR /range allows GET\n")),
+        (_, "/tx") => Err(HttpResponse::new(405, "ERR /tx allows POST\n")),
+        _ => Err(HttpResponse::new(404, "ERR no such resource\n")),
+    }
+}
+
+
+/// `true` if `request` is a `GET` of a key or a range, without
+/// credentials, that `snapshot` can answer as the session would.
+fn reads_from_snapshot(snapshot: &Snapshot, request: &HttpRequest) -> bool {
+    snapshot.serves_reads
+        && request.method == "GET"
+        && request.authorization.is_none()
+        && (request.path.starts_with("/keys/") || request.path == "/range")
+}
+
+
+/// [`route`] for a request [`reads_from_snapshot`] accepted.
+fn answer_read(snapshot: &Snapshot, request: &HttpRequest, config: &ServerConfig) -> HttpResponse {
+    let result = (|| {
+        let overflow = || HttpResponse::new(500, "ERR reply exceeds max pending output\n");
+        if let Some(key) = request.path.strip_prefix("/keys/") {
+            let key = percent_decode(key, false).ok_or_else(|| HttpResponse::new(400, "ERR malformed key in path\n"))?;
+            if key.is_empty() {
+                return Err(HttpResponse::new(400, "ERR empty key\n"));
+            }
+            return match snapshot.get(&key) {
+                Some(value) if value.len() < config.max_pending_output => Ok(HttpResponse::new(200, value)),
+                Some(_) => Err(overflow()),
+                None => Err(HttpResponse::new(404, "nil\n")),
+            };
+        }
+
+        let line = range_line(&request.query)?;
+        let (_, captured) = capture_replies(config.max_pending_output, || snapshot.execute_read(line.as_bytes()));
+        if captured.overflowed {
+            return Err(overflow());
+        }
+        let text = String::from_utf8_lossy(&captured.bytes);
+        Ok(HttpResponse::new(200, text.strip_suffix("END\n").unwrap_or(&text)))
+    })();
+    result.unwrap_or_else(|response| response)
+}
+
+
+/// The protocol `RANGE` line a `/range` query string asks for.
+fn range_line(query: &str) -> Result<String, HttpResponse> {
+    let (mut start, mut end) = (String::new(), String::new());
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value, true).ok_or_else(|| HttpResponse::new(400, "ERR malformed query string\n"))?;
+        match name {
+            "start" => start = value,
+            "end" => end = value,
+            _ => return Err(HttpResponse::new(400, format!("ERR unknown range parameter '{}'\n", name))),
+        }
+    }
+    Ok(format!("RANGE {} {}", range_bound(&start, "-"), range_bound(&end, "+")))
+}
+
+
+/// Apply a body of `SET` / `MSET` lines in one transaction.
+fn transaction(session: &mut Session, body: &[u8], config: &ServerConfig) -> Result<HttpResponse, HttpResponse> {
+    let body = std::str::from_utf8(body).map_err(|_| HttpResponse::new(400, "ERR transaction body is not UTF-8\n"))?;
+    let lines: Vec<&str> = body.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return Err(HttpResponse::new(400, "ERR transaction body has no commands\n"));
+    }
+
+    // Only buffered writes belong in the batch; check before starting it
+    for line in &lines {
+        let (cmd, _) = parse_command(line).map_err(|e| HttpResponse::new(400, format!("ERR {}\n", e)))?;
+        if !matches!(cmd.as_str(), "SET" | "MSET") {
+            return Err(HttpResponse::new(400, format!("ERR {} cannot be batched; /tx takes SET and MSET lines\n", cmd)));
+        }
+    }
+
+    run(session, "BEGIN", config)?;
+    for line in &lines {
+        if let Err(response) = run(session, line, config) {
+            let _ = run(session, "ABORT", config);
+            return Err(response);
+        }
+    }
+    run(session, "COMMIT", config)?;
+    Ok(HttpResponse::new(200, "OK\n"))
+}
+
+
+/// Run one protocol line and collect its reply lines.
+///
+/// # Returns
+/// `Err(response)` for an `ERR` reply, or a reply past the output cap.
+fn run(session: &mut Session, line: &str, config: &ServerConfig) -> Result<Vec<String>, HttpResponse> {
+    let text = capture(session, line, config)?;
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    match lines.iter().find(|line| line.starts_with("ERR")) {
+        Some(error) => Err(HttpResponse::from_error(error)),
+        None => Ok(lines),
+    }
+}
+
+
+/// Run one protocol line and return its replies as written.
+fn capture(session: &mut Session, line: &str, config: &ServerConfig) -> Result<String, HttpResponse> {
+    let (_, captured) = capture_replies(config.max_pending_output, || execute_line(line.as_bytes(), session));
+    if captured.overflowed {
+        return Err(HttpResponse::new(500, "ERR reply exceeds max pending output\n"));
+    }
+    Ok(String::from_utf8_lossy(&captured.bytes).into_owned())
+}
+
+
+/// A `RANGE` bound: `open` if empty, quoted if it looks like a sentinel.
+pub(crate) fn range_bound(bound: &str, open: &str) -> String {
+    match bound {
+        "" => open.to_string(),
+        "-" | "+" => format!("\"{}\"", bound),
+        _ => quote_arg(bound),
+    }
+}
+
+
+/// Decode `%XX` escapes (and `+` as a space, in query strings).
+///
+/// # Example
+/// ```
+/// use kvstore::percent_decode;
+/// assert_eq!(percent_decode("a%2Fb%20c", false).as_deref(), Some("a/b c"));
+/// assert_eq!(percent_decode("a+b", true).as_deref(), Some("a b"));
+/// assert_eq!(percent_decode("%zz", false), None);
+/// ```
+pub fn percent_decode(text: &str, plus_as_space: bool) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+                continue;
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).ok()
+}
+
+
+/// User and password from a `Basic <base64>` header value.
+pub(crate) fn basic_credentials(header: &str) -> Option<(String, String)> {
+    let (scheme, encoded) = header.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(base64_decode(encoded.trim())?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+
+/// The response for a request that could not be read.
+fn error_for(e: &io::Error) -> HttpResponse {
+    let status = match e.kind() {
+        io::ErrorKind::OutOfMemory => 431,
+        io::ErrorKind::FileTooLarge => 413,
+        io::ErrorKind::Unsupported => 501,
+        _ => 400,
+    };
+    HttpResponse::new(status, format!("ERR {}\n", e))
+}
+
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+
+/// Split an `http://host[:port][/path]` URL into `host:port` and path.
+///
+/// # Returns
+/// `Err(message)` for another scheme or a missing host.
+pub(crate) fn split_url(url: &str) -> Result<(String, String), String> {
+    if !url.starts_with("http://") {
+        return Err(format!("{} is not an http:// URL", url));
+    }
+    split_endpoint(url).map(|(host, path, _)| (host, path))
+}
+
+
+/// [`split_url`] for `http://` or `https://` URLs; the flag is `true`
+/// for `https://`, whose default port is 443.
+pub(crate) fn split_endpoint(url: &str) -> Result<(String, String, bool), String> {
+    let (rest, tls) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+        (Some(rest), _) => (rest, false),
+        (_, Some(rest)) => (rest, true),
+        _ => return Err(format!("{} is not an http:// or https:// URL", url)),
+    };
+    let (host, path) = match rest.find('/') {
+        Some(at) => rest.split_at(at),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(format!("{} has no host", url));
+    }
+    let host = match (host.contains(':'), tls) {
+        (true, _) => host.to_string(),
+        (false, false) => format!("{}:80", host),
+        (false, true) => format!("{}:443", host),
+    };
+    Ok((host, path.to_string(), tls))
+}
+
+
+/// POST `body` to an `http://` URL and return the response status.
+///
+/// The client side of the few outgoing calls (webhooks, trace export,
+/// backups): one request per connection, no redirects.
+pub(crate) fn post(url: &str, headers: &[(&str, &str)], body: &[u8], timeout: Duration) -> io::Result<u16> {
+    // Callers only want the status
+    send("POST", url, headers, body, timeout, None).map(|(code, _)| code)
+}
+
+
+/// A client connection, in TLS for `https://` URLs.
+enum Conn {
+    Plain(TcpStream),
+    #[cfg(feature = "s3-tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+
+impl Read for Conn {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Conn::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "s3-tls")]
+            Conn::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+
+impl Write for Conn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Conn::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "s3-tls")]
+            Conn::Tls(stream) => stream.write(buf),
+        }
+    }
+
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Conn::Plain(stream) => stream.flush(),
+            #[cfg(feature = "s3-tls")]
+            Conn::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+
+/// Wrap `stream` in TLS to `host`, trusting the Mozilla root store, or
+/// only the PEM certificates in `ca_file` if one is given.
+#[cfg(feature = "s3-tls")]
+fn tls_connect(host: &str, stream: TcpStream, ca_file: Option<&str>) -> io::Result<Conn> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, ServerName};
+
+    let mut roots = rustls::RootCertStore::empty();
+    match ca_file {
+        Some(path) => {
+            for cert in CertificateDer::pem_file_iter(path).map_err(|e| invalid(&format!("{}: {}", path, e)))? {
+                roots.add(cert.map_err(|e| invalid(&format!("{}: {}", path, e)))?).map_err(io::Error::other)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = host.rsplit_once(':').map_or(host, |(name, _)| name).trim_matches(['[', ']']);
+    let name = ServerName::try_from(name.to_string()).map_err(|e| invalid(&e.to_string()))?;
+    let conn = rustls::ClientConnection::new(Arc::new(config), name).map_err(io::Error::other)?;
+    Ok(Conn::Tls(Box::new(rustls::StreamOwned::new(conn, stream))))
+}
+
+
+#[cfg(not(feature = "s3-tls"))]
+fn tls_connect(_host: &str, _stream: TcpStream, _ca_file: Option<&str>) -> io::Result<Conn> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "https:// needs kvstore built with --features s3-tls"))
+}
+
+
+/// Send one request and read its status line.
+fn send(method: &str, url: &str, headers: &[(&str, &str)], body: &[u8], timeout: Duration, ca_file: Option<&str>)
+    -> io::Result<(u16, BufReader<Conn>)> {
+    let (host, path, tls) = split_endpoint(url).map_err(|e| invalid(&e))?;
+    let addr = host.to_socket_addrs()?.next().ok_or_else(|| invalid("host did not resolve"))?;
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut conn = if tls { tls_connect(&host, stream, ca_file)? } else { Conn::Plain(stream) };
+
+    let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n", method, path, host, body.len());
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    conn.write_all(head.as_bytes())?;
+    conn.write_all(body)?;
+    conn.flush()?;
+
+    let mut reader = BufReader::new(conn);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let code = status.split_whitespace().nth(1).and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid(&format!("bad status line {:?}", status.trim_end())))?;
+    Ok((code, reader))
+}
+
+
+/// A response's status, headers and body.
+pub(crate) type Response = (u16, Vec<(String, String)>, Vec<u8>);
+
+
+/// Send one `method` request to an `http://` or `https://` URL.
+///
+/// # Arguments
+/// * `ca_file` - PEM certificates to trust instead of the built-in
+///   roots, for `https://` servers with a private CA.
+///
+/// # Returns
+/// The response status, headers (names lowercased) and body (read by
+/// `Content-Length`, chunked, or to the end of the connection).
+pub(crate) fn request(method: &str, url: &str, headers: &[(&str, &str)], body: &[u8], timeout: Duration, ca_file: Option<&str>)
+    -> io::Result<Response> {
+    let (code, mut reader) = send(method, url, headers, body, timeout, ca_file)?;
+
+    let (mut length, mut chunked, mut response_headers) = (None, false, Vec::new());
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim().to_ascii_lowercase(), value.trim());
+            if name == "content-length" {
+                length = Some(value.parse::<usize>().map_err(|_| invalid("bad Content-Length"))?);
+            } else if name == "transfer-encoding" && value.eq_ignore_ascii_case("chunked") {
+                chunked = true;
+            }
+            response_headers.push((name, value.to_string()));
+        }
+    }
+
+    let mut content = Vec::new();
+    if chunked {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size)?;
+            let size = size.split(';').next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| invalid("bad chunk size"))?;
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk)?;
+            if size == 0 {
+                break;
+            }
+            content.extend_from_slice(&chunk[..size]);
+        }
+    } else if let Some(length) = length {
+        content.resize(length, 0);
+        reader.read_exact(&mut content)?;
+    } else {
+        reader.read_to_end(&mut content)?;
+    }
+    Ok((code, response_headers, content))
+}
+
+
+// =================================================================
+// http.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod http_tests {
+    use super::*;
+    use crate::{Acl, MemFs};
+    use std::net::SocketAddr;
+
+    fn memory_session() -> Session {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session
+    }
+
+    fn call(session: &mut Session, method: &str, path: &str, body: &str) -> (u16, String) {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let request = HttpRequest { query: query.to_string(), body: body.as_bytes().to_vec(), ..HttpRequest::new(method, path) };
+        let response = handle_request(session, &request, &ServerConfig::new());
+        (response.status, response.body)
+    }
+
+    #[test]
+    fn test_keys_can_be_stored_read_and_deleted() {
+        let mut session = memory_session();
+        assert_eq!(call(&mut session, "PUT", "/keys/a%20key", "two\nlines"), (200, "OK\n".into()));
+        assert_eq!(call(&mut session, "GET", "/keys/a%20key", ""), (200, "two\nlines".into()));
+        assert_eq!(call(&mut session, "PUT", "/keys/n", "nil"), (200, "OK\n".into()));
+        assert_eq!(call(&mut session, "GET", "/keys/n", ""), (200, "nil".into()));
+        assert_eq!(call(&mut session, "PUT", "/keys/e", "ERR not really"), (200, "OK\n".into()));
+        assert_eq!(call(&mut session, "GET", "/keys/e", ""), (200, "ERR not really".into()));
+
+        assert_eq!(call(&mut session, "DELETE", "/keys/a%20key", ""), (200, "OK\n".into()));
+        assert_eq!(call(&mut session, "GET", "/keys/a%20key", "").0, 404);
+        assert_eq!(call(&mut session, "DELETE", "/keys/a%20key", "").0, 404);
+        assert_eq!(call(&mut session, "POST", "/keys/n", "").0, 405);
+        assert_eq!(call(&mut session, "GET", "/nowhere", "").0, 404);
+    }
+
+    #[test]
+    fn test_range_lists_keys_between_the_bounds() {
+        let mut session = memory_session();
+        for key in ["-", "a", "b", "c"] {
+            call(&mut session, "PUT", &format!("/keys/{}", key), "1");
+        }
+        assert_eq!(call(&mut session, "GET", "/range?start=b", ""), (200, "b\nc\n".into()));
+        assert_eq!(call(&mut session, "GET", "/range?end=a", ""), (200, "-\na\n".into()));
+        assert_eq!(call(&mut session, "GET", "/range?start=-&end=-", ""), (200, "-\n".into()));
+        assert_eq!(call(&mut session, "GET", "/range?from=a", "").0, 400);
+    }
+
+    #[test]
+    fn test_transactions_apply_all_or_nothing() {
+        let mut session = memory_session();
+        assert_eq!(call(&mut session, "POST", "/tx", "SET a 1\nMSET b 2 c 3\n"), (200, "OK\n".into()));
+        assert_eq!(call(&mut session, "GET", "/keys/c", ""), (200, "3".into()));
+
+        // A bad line anywhere leaves everything as it was
+        session.limits.max_value_len = 4;
+        let (status, _) = call(&mut session, "POST", "/tx", "SET a 10\nSET b too-long\n");
+        assert_eq!(status, 400);
+        assert_eq!(call(&mut session, "GET", "/keys/a", ""), (200, "1".into()));
+        assert!(!session.in_transaction());
+
+        assert_eq!(call(&mut session, "POST", "/tx", "SET a 1\nDEL b\n").0, 400);
+        assert_eq!(call(&mut session, "POST", "/tx", "").0, 400);
+    }
+
+    #[test]
+    fn test_acl_errors_map_to_auth_statuses() {
+        let mut session = memory_session();
+        session.acl = Some(Acl::parse("user reader pw read app:*\n").unwrap());
+        assert_eq!(call(&mut session, "GET", "/keys/app:x", "").0, 401);
+
+        // reader:pw
+        let basic = |path: &str| HttpRequest { authorization: Some("Basic cmVhZGVyOnB3".into()), ..HttpRequest::new("GET", path) };
+        assert_eq!(handle_request(&mut session, &basic("/keys/app:x"), &ServerConfig::new()).status, 404);
+        assert_eq!(handle_request(&mut session, &basic("/keys/other"), &ServerConfig::new()).status, 403);
+        let wrong = HttpRequest { authorization: Some("Basic cmVhZGVyOm5v".into()), ..HttpRequest::new("GET", "/keys/app:x") };
+        assert_eq!(handle_request(&mut session, &wrong, &ServerConfig::new()).status, 401);
+    }
+
+    #[test]
+    fn test_requests_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let store = Arc::new(SharedStore::new(memory_session()));
+        thread::spawn(move || serve_http(listener, store, ServerConfig::new()));
+
+        let send = |raw: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(raw.as_bytes()).unwrap();
+            let mut text = String::new();
+            stream.read_to_string(&mut text).unwrap();
+            text
+        };
+        let put = send("PUT /keys/k HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nvalue");
+        assert!(put.starts_with("HTTP/1.1 200 OK\r\n"), "{}", put);
+        let get = send("GET /keys/k HTTP/1.1\r\n\r\n");
+        assert!(get.ends_with("Content-Length: 5\r\nConnection: close\r\n\r\nvalue"), "{}", get);
+        assert!(send("GARBAGE\r\n\r\n").starts_with("HTTP/1.1 400 "));
+    }
+
+    #[test]
+    fn test_base64_decoding() {
+        assert_eq!(base64_decode("cmVhZGVyOnB3").unwrap(), b"reader:pw");
+        assert_eq!(base64_decode("YQ==").unwrap(), b"a");
+        assert_eq!(base64_decode("YWI=").unwrap(), b"ab");
+        assert!(base64_decode("Y").is_none());
+        assert!(base64_decode("Y$==").is_none());
+    }
+}
@@ -0,0 +1,152 @@
+// =====================================================================
+// File: index/collation.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`Collation`] decides how the index orders its keys.
+//!
+//! - `Binary` compares raw bytes (the original behavior).
+//! - `CaseInsensitive` folds ASCII letters, so `Apple` sorts with `apple`.
+//! - `Unicode` lowercases every character and folds common Latin accents
+//!   and combining marks, so `Éclair` sorts with `eclair`.
+//!
+//! Keys that fold to the same text are still distinct: ties are broken
+//! by their bytes, so the order is total and no two keys ever collide.
+// =====================================================================
+
+use std::cmp::Ordering;
+
+/// Key ordering used by a [`crate::BTreeIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collation {
+    /// Raw byte order.
+    #[default]
+    Binary,
+
+    /// ASCII case-insensitive order.
+    CaseInsensitive,
+
+    /// Lowercased, accent-folded character order.
+    Unicode,
+}
+
+
+impl Collation {
+    /// Parse a collation name as used by `KVSTORE_COLLATION`
+    /// (`binary`, `nocase`, `unicode`).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Collation;
+    /// assert_eq!(Collation::from_name("NoCase"), Some(Collation::CaseInsensitive));
+    /// assert_eq!(Collation::from_name("random"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "binary" => Some(Collation::Binary),
+            "nocase" => Some(Collation::CaseInsensitive),
+            "unicode" => Some(Collation::Unicode),
+            _ => None,
+        }
+    }
+
+
+    /// Total order of two keys: folded text first, then bytes.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Collation;
+    /// use std::cmp::Ordering;
+    /// let c = Collation::CaseInsensitive;
+    /// assert_eq!(c.compare("Banana", "apple"), Ordering::Greater);
+    /// assert_eq!(c.compare("Apple", "apple"), Ordering::Less);
+    /// assert_eq!(Collation::Binary.compare("Banana", "apple"), Ordering::Less);
+    /// ```
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.compare_folded(a, b).then_with(|| a.cmp(b))
+    }
+
+
+    /// Order of two keys by folded text only; `Equal` for keys that
+    /// differ just in case or accents.
+    ///
+    /// Range bounds use this, so `RANGE a b` under a case-insensitive
+    /// collation includes both `B` and `b`.
+    pub fn compare_folded(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.cmp(b),
+            Collation::CaseInsensitive => a
+                .bytes()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+            Collation::Unicode => fold_chars(a).cmp(fold_chars(b)),
+        }
+    }
+
+
+    /// `true` if `key` starts with `prefix` once both are folded, so
+    /// `SCANPREFIX user:` under `nocase` also finds `User:1`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Collation;
+    /// assert!(Collation::Binary.has_prefix("user:1", "user:"));
+    /// assert!(!Collation::Binary.has_prefix("User:1", "user:"));
+    /// assert!(Collation::CaseInsensitive.has_prefix("User:1", "user:"));
+    /// assert!(Collation::Unicode.has_prefix("Éclair", "ec"));
+    /// ```
+    pub fn has_prefix(&self, key: &str, prefix: &str) -> bool {
+        match self {
+            Collation::Binary => key.starts_with(prefix),
+            Collation::CaseInsensitive => key
+                .as_bytes()
+                .get(..prefix.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(prefix.as_bytes())),
+            Collation::Unicode => {
+                let mut folded = fold_chars(key);
+                fold_chars(prefix).all(|c| folded.next() == Some(c))
+            }
+        }
+    }
+}
+
+
+/// Characters of `text` lowercased, with combining marks dropped and
+/// common Latin accented letters replaced by their base letters.
+fn fold_chars(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .flat_map(char::to_lowercase)
+        .flat_map(|c| {
+            let (chars, n) = base_letters(c);
+            chars.into_iter().take(n)
+        })
+}
+
+
+/// Base letter(s) for a lowercase character and how many are used.
+fn base_letters(c: char) -> ([char; 2], usize) {
+    let base = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+        'ł' => 'l',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ř' => 'r',
+        'ś' | 'š' => 's',
+        'ť' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        'ß' => return (['s', 's'], 2),
+        'æ' => return (['a', 'e'], 2),
+        'œ' => return (['o', 'e'], 2),
+        other => other,
+    };
+    ([base, base], 1)
+}
@@ -5,24 +5,46 @@
 // Midterm/Final Project
 // Date: Sept 22, 2025
 //
-//! The `index` module contains the B-tree implementation used
+//! The `index` module contains the B+tree implementation used
 //! for in-memory indexing of the key-value store.
 //!
 //! Structure:
 //! - `node.rs`  : Defines the [`BTreeNode`] structure and its helpers.
+//! - `collation.rs` : The [`Collation`] that orders keys in the tree.
 //! - `tree.rs`  : Defines the [`BTreeIndex`] and its algorithms
-//!                (insert, search, delete).
-//! - `tests.rs` : Unit tests for the B-tree (compiled only in test mode).
+//!   (insert, search, delete).
+//! - `range.rs` : The [`Range`] and [`RangeRev`] iterators over keys
+//!   between two bounds.
+//! - `table.rs` : The node table that maps a tree's [`NodeId`]s to its
+//!   nodes, copied on write.
+//! - `tests.rs` : Unit tests for the B+tree (compiled only in test mode).
 //!
 //! This organization separates the small `BTreeNode` definition from
 //! the larger `BTreeIndex` implementation for readability, while tests
 //! are isolated to avoid cluttering the main code paths.
+//!
+//! Values live only in the leaves, which are linked both ways in key
+//! order; internal nodes hold just the separator keys that steer a
+//! descent. Every ordered walk (`range`, `range_rev`, `scan_prefix`,
+//! `collect_keys`) descends once to its first leaf and then follows the
+//! links, leaf to leaf, without a stack or cloning.
+//!
+//! Nodes name their children and neighbouring leaves by [`NodeId`], an
+//! id in the tree's node table, not by pointer. Snapshots share the
+//! table and copy entries on write, so changing a leaf copies that leaf
+//! alone: the leaves linked to it and its parent still reach it by the
+//! same id, and keep pointing at the old copy in the snapshot.
 // =====================================================================
 
+pub mod collation;
 pub mod node;
+pub mod range;
+mod table;
 pub mod tree;
 
-pub use self::node::BTreeNode;
+pub use self::collation::Collation;
+pub use self::node::{BTreeNode, NodeId};
+pub use self::range::{Range, RangeRev};
 pub use self::tree::BTreeIndex;
 
 #[cfg(test)]
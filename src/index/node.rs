@@ -6,46 +6,96 @@
 // Date: Sept 21, 2025 - Refactored Sept 22, 2025
 //
 // Description:
-//   Defines the core B-tree node structure (`BTreeNode`) used by the
+//   Defines the core B+tree node structure (`BTreeNode`) used by the
 //   in-memory index of the key-value store. Each node maintains:
 //
-//   - `kv_pairs`: Ordered key–value pairs stored within the node.
-//   - `children`: References to child nodes (empty if this node is a leaf).
-//   - `is_leaf` : Boolean flag indicating whether the node is a leaf.
+//   - `kv_pairs`  : Ordered key–value pairs (leaves only). Values are
+//                   bytes (`Vec<u8>`), so they need not be text.
+//   - `separators`: Keys that divide the children (internal nodes only).
+//   - `children`  : Ids of the child nodes in the tree's node table
+//                   (empty if this node is a leaf).
+//   - `prev`/`next`: Ids of the neighbouring leaves, in key order.
+//   - `is_leaf`   : Boolean flag indicating whether the node is a leaf.
+//   - `size`      : Pairs in the node and every node below it, kept by the
+//                   tree so it can rank and select keys in O(log n).
+//
+//   Pairs, separators and children are `SmallVec`s, so small nodes live
+//   entirely inside their table entry instead of owning separate heap Vecs.
 //
 // Notes:
-//   * A B-tree node can contain multiple key–value pairs, with children
-//     linking to subtrees that maintain the B-tree ordering invariants.
+//   * Every value lives in a leaf. Child `i` of an internal node holds
+//     the keys from separator `i - 1` (included) to separator `i`
+//     (excluded), so a separator need not be a key still in the tree.
 //   * This file contains only the node representation and helpers.
 //     Higher-level operations (insert, search, delete) are implemented
 //     in `tree.rs`.
 // =====================================================================
+use smallvec::SmallVec;
+
+use super::Collation;
+
+/// Inline capacity for node pairs: a full node at the default degree (2t - 1, t = 2).
+pub const INLINE_PAIRS: usize = 3;
+
+/// Inline capacity for node children: a full node at the default degree (2t, t = 2).
+pub const INLINE_CHILDREN: usize = 4;
+
+/// Id of a node in its tree's node table (see [`BTreeIndex::node`](crate::BTreeIndex::node)).
+pub type NodeId = u64;
+
+/// Ordered key–value pairs of a leaf; values are raw bytes.
+pub type KvPairs = SmallVec<[(String, Vec<u8>); INLINE_PAIRS]>;
+
+/// Separator keys of an internal node.
+pub type Separators = SmallVec<[String; INLINE_PAIRS]>;
+
+/// Child links of an internal node, by id. Nodes may be shared with
+/// other trees (see [`BTreeIndex::snapshot`](crate::BTreeIndex::snapshot)).
+pub type Children = SmallVec<[NodeId; INLINE_CHILDREN]>;
 
 
 // BTree Referencing:
 // https://build-your-own.org/database/
 // https://www.geeksforgeeks.org/dsa/introduction-of-b-tree-2/
-/// Basic Foundational BTree Node
-#[derive(Debug)]
+/// Basic Foundational B+tree Node
+#[derive(Debug, Clone)]
 pub struct BTreeNode {
-    pub kv_pairs: Vec<(String, String)>,
-    /// Box allows Rust to recursivley move through values and nodes - Heap
-    pub children: Vec<Box<BTreeNode>>,
+    /// Leaves: the pairs, in key order. Empty in internal nodes.
+    pub kv_pairs: KvPairs,
+
+    /// Internal nodes: child `i` holds keys below `separators[i]`, and
+    /// child `i + 1` keys at or above it. Empty in leaves.
+    pub separators: Separators,
+
+    /// Children are ids in the tree's node table, so a child can be
+    /// copied on write without touching its parent.
+    pub children: Children,
     pub is_leaf: bool,
+
+    /// Pairs in this node and all of its subtrees. Kept up to date by
+    /// [`BTreeIndex`](crate::BTreeIndex).
+    pub size: usize,
+
+    /// Leaves: the leaf holding the keys just before this one's.
+    pub prev: Option<NodeId>,
+
+    /// Leaves: the leaf holding the keys just after this one's.
+    pub next: Option<NodeId>,
 }
 
 
 impl BTreeNode {
-    // Creates a new empty B-tree node.
+    // Creates a new empty B+tree node.
     ///
     /// # Arguments
     ///
     /// * `is_leaf` - A boolean flag indicating whether this node
-    ///   is a leaf (has no children) or an internal node (may have children).
+    ///   is a leaf (holds pairs) or an internal node (holds separators
+    ///   and children).
     ///
     /// # Returns
     ///
-    /// A `BTreeNode` instance with empty keys-values, and children vectors.
+    /// A `BTreeNode` instance with no pairs, separators, children or links.
     ///
     /// # Example
     /// ```
@@ -53,12 +103,17 @@ impl BTreeNode {
     /// let leaf = BTreeNode::new(true);
     /// assert!(leaf.kv_pairs.is_empty());
     /// assert!(leaf.is_leaf);
+    /// assert_eq!(leaf.next, None);
     /// ```
     pub fn new(is_leaf: bool) -> Self {
         Self {
-            kv_pairs: Vec::new(),
-            children: Vec::new(),
+            kv_pairs: KvPairs::new(),
+            separators: Separators::new(),
+            children: Children::new(),
             is_leaf,
+            size: 0,
+            prev: None,
+            next: None,
         }
     }
 
@@ -68,7 +123,7 @@ impl BTreeNode {
     ///
     /// # Arguments
     ///
-    /// * `key` - The key to compare against the node’s stored keys.
+    /// * `key` - The key to compare against the leaf's stored keys.
     ///
     /// # Returns
     ///
@@ -81,72 +136,64 @@ impl BTreeNode {
     /// use kvstore::BTreeNode;
     ///
     /// let mut node = BTreeNode::new(true);
-    /// node.kv_pairs.push(("cat".to_string(), "meow".to_string()));
-    /// node.kv_pairs.push(("dog".to_string(), "bark".to_string()));
+    /// node.kv_pairs.push(("cat".to_string(), "meow".into()));
+    /// node.kv_pairs.push(("dog".to_string(), "bark".into()));
     ///
     /// assert_eq!(node.lower_bound("ant"), 0);
     /// assert_eq!(node.lower_bound("dog"), 1);
     /// assert_eq!(node.lower_bound("elephant"), 2);
     /// ```
     pub fn lower_bound(&self, key: &str) -> usize {
+        self.lower_bound_by(key, Collation::Binary)
+    }
+
+
+    /// [`lower_bound`](Self::lower_bound) under the given key ordering.
+    pub fn lower_bound_by(&self, key: &str, collation: Collation) -> usize {
         self.kv_pairs
-            .binary_search_by(|(k, _)| k.as_str().cmp(key))
+            .binary_search_by(|(k, _)| collation.compare(k, key))
             .unwrap_or_else(|pos| pos)
     }
 
 
-    /// Collects all keys stored in this subtree and appends them to the
-    /// provided output vector in sorted (in-order) order.
+    /// Index of the child of an internal node whose keys would include
+    /// `key`: the number of separators at or below it.
     ///
-    /// This method performs an in-order traversal of the B-tree:
-    /// - If the node is a leaf, it simply pushes all keys in their
-    ///   stored order.
-    /// - If the node is internal, it recursively visits each child,
-    ///   inserting the key that separates the children between those visits.
-    ///
-    /// # Arguments
-    ///
-    /// * `out` - A mutable vector that will be appended with the keys
-    ///   discovered during traversal.
-    ///
-    /// # Behavior
+    /// # Example
+    /// ```
+    /// use kvstore::{BTreeNode, Collation};
+    /// let mut node = BTreeNode::new(false);
+    /// node.separators.push("m".to_string());
+    /// assert_eq!(node.child_index_by("a", Collation::Binary), 0);
+    /// assert_eq!(node.child_index_by("m", Collation::Binary), 1);
+    /// ```
+    pub fn child_index_by(&self, key: &str, collation: Collation) -> usize {
+        self.separators.partition_point(|s| collation.compare(s, key).is_le())
+    }
+
+
+    /// Entries stored directly in this node: pairs in a leaf, separators
+    /// in an internal node. Fill limits count these.
+    pub fn key_count(&self) -> usize {
+        if self.is_leaf { self.kv_pairs.len() } else { self.separators.len() }
+    }
+
+
+    /// Bytes of key and value data stored directly in this node (of
+    /// separators, in an internal node).
     ///
-    /// Keys are cloned and appended to `out`. The traversal guarantees that
-    /// the resulting vector is globally sorted across the entire subtree.
+    /// Used by byte-budgeted trees to decide when a node is full.
     ///
     /// # Example
     /// ```
-    /// use kvstore::BTreeNode;
-    ///
-    /// // Build a simple leaf node
+    /// use kvstore::index::BTreeNode;
     /// let mut node = BTreeNode::new(true);
-    /// node.kv_pairs.push(("a".to_string(), "1".to_string()));
-    /// node.kv_pairs.push(("b".to_string(), "2".to_string()));
-    ///
-    /// let mut out = Vec::new();
-    /// node.collect_keys(&mut out);
-    ///
-    /// assert_eq!(out, vec!["a".to_string(), "b".to_string()]);
+    /// node.kv_pairs.push(("ab".to_string(), "xyz".into()));
+    /// assert_eq!(node.byte_size(), 5);
     /// ```
-    pub fn collect_keys(&self, out: &mut Vec<String>) {
-        if self.is_leaf {
-            // Push ONLY keys
-            for (k, _) in &self.kv_pairs {
-                out.push(k.clone());
-            }
-        } else {
-            // Internal node: in-order traversal
-            for i in 0..self.kv_pairs.len() {
-                // Left subtree
-                self.children[i].collect_keys(out);
-
-                // Key at index i
-                out.push(self.kv_pairs[i].0.clone());
-            }
-
-            // Last child (rightmost subtree)
-            self.children[self.kv_pairs.len()].collect_keys(out);
-        }
+    pub fn byte_size(&self) -> usize {
+        self.kv_pairs.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>()
+            + self.separators.iter().map(String::len).sum::<usize>()
     }
 
 }
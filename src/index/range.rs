@@ -0,0 +1,158 @@
+// =====================================================================
+// File: index/range.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! [`Range`] walks the pairs of a [`BTreeIndex`] between two bounds
+//! without collecting them first, and [`RangeRev`] walks the same pairs
+//! from the other end.
+//!
+//! Starting one descends once from the root to the leaf holding the
+//! first key it yields; subtrees outside the bounds are never visited.
+//! From there it is a leaf walk: each leaf's pairs in turn, then the
+//! leaf its `next` (or `prev`) link names, so it holds a leaf and a
+//! position however many keys are in range.
+// =====================================================================
+
+use std::ops::Bound;
+
+use super::{BTreeIndex, BTreeNode, Collation};
+
+
+/// In-order iterator over the pairs of a B+tree within two bounds.
+///
+/// Created by [`BTreeIndex::range`](crate::BTreeIndex::range).
+#[derive(Debug, Clone)]
+pub struct Range<'a> {
+    tree: &'a BTreeIndex,
+
+    /// Leaf holding the next pair, or `None` once the range is done.
+    leaf: Option<&'a BTreeNode>,
+
+    /// Index of the next pair in `leaf`.
+    at: usize,
+
+    end: Bound<&'a str>,
+
+    collation: Collation,
+}
+
+
+impl<'a> Range<'a> {
+    /// Start at the first pair of `tree` that is not below `start`.
+    pub(crate) fn new(tree: &'a BTreeIndex, start: Bound<&str>, end: Bound<&'a str>, collation: Collation) -> Self {
+        let mut node = tree.node(tree.root);
+        while !node.is_leaf {
+            // Children before `i` hold only keys below their separator, so below start
+            let i = node.separators.partition_point(|s| below(s, start, collation));
+            node = tree.node(node.children[i]);
+        }
+        let at = node.kv_pairs.partition_point(|(key, _)| below(key, start, collation));
+        Self { tree, leaf: Some(node), at, end, collation }
+    }
+}
+
+
+impl<'a> Iterator for Range<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<(&'a str, &'a [u8])> {
+        loop {
+            let leaf = self.leaf?;
+            if let Some((key, value)) = leaf.kv_pairs.get(self.at) {
+                self.at += 1;
+                if above(key, self.end, self.collation) {
+                    // Every later key is past it too
+                    self.leaf = None;
+                    return None;
+                }
+                return Some((key, value));
+            }
+
+            // On along the leaf chain
+            self.leaf = leaf.next.map(|id| self.tree.node(id));
+            self.at = 0;
+        }
+    }
+}
+
+
+/// Descending iterator over the pairs of a B+tree within two bounds.
+///
+/// Created by [`BTreeIndex::range_rev`](crate::BTreeIndex::range_rev).
+#[derive(Debug, Clone)]
+pub struct RangeRev<'a> {
+    tree: &'a BTreeIndex,
+
+    /// Leaf holding the next pair, or `None` once the range is done.
+    leaf: Option<&'a BTreeNode>,
+
+    /// Pairs of `leaf` left to yield; the next is the one before this.
+    left: usize,
+
+    start: Bound<&'a str>,
+
+    collation: Collation,
+}
+
+
+impl<'a> RangeRev<'a> {
+    /// Start at the last pair of `tree` that is not above `end`.
+    pub(crate) fn new(tree: &'a BTreeIndex, start: Bound<&'a str>, end: Bound<&str>, collation: Collation) -> Self {
+        let mut node = tree.node(tree.root);
+        while !node.is_leaf {
+            // Children after `i` hold only keys at or above their separator, so above end
+            let i = node.separators.partition_point(|s| !above(s, end, collation));
+            node = tree.node(node.children[i]);
+        }
+        let left = node.kv_pairs.partition_point(|(key, _)| !above(key, end, collation));
+        Self { tree, leaf: Some(node), left, start, collation }
+    }
+}
+
+
+impl<'a> Iterator for RangeRev<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<(&'a str, &'a [u8])> {
+        loop {
+            let leaf = self.leaf?;
+            if self.left > 0 {
+                self.left -= 1;
+                let (key, value) = &leaf.kv_pairs[self.left];
+                if below(key, self.start, self.collation) {
+                    // Every earlier key is below it too
+                    self.leaf = None;
+                    return None;
+                }
+                return Some((key, value));
+            }
+
+            // Back along the leaf chain
+            self.leaf = leaf.prev.map(|id| self.tree.node(id));
+            self.left = self.leaf.map_or(0, |leaf| leaf.kv_pairs.len());
+        }
+    }
+}
+
+
+/// `true` if `key` falls before the lower bound `start`.
+pub(super) fn below(key: &str, start: Bound<&str>, collation: Collation) -> bool {
+    match start {
+        Bound::Included(s) => collation.compare_folded(key, s).is_lt(),
+        Bound::Excluded(s) => collation.compare_folded(key, s).is_le(),
+        Bound::Unbounded => false,
+    }
+}
+
+
+/// `true` if `key` falls after the upper bound `end`.
+pub(super) fn above(key: &str, end: Bound<&str>, collation: Collation) -> bool {
+    match end {
+        Bound::Included(e) => collation.compare_folded(key, e).is_gt(),
+        Bound::Excluded(e) => collation.compare_folded(key, e).is_ge(),
+        Bound::Unbounded => false,
+    }
+}
@@ -0,0 +1,209 @@
+// =====================================================================
+// File: index/table.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   `NodeTable`, the store behind a B+tree's node ids: a radix trie over
+//   the id bits, 32 slots per chunk, with `Arc` chunks and nodes copied
+//   on write. Cloning it is O(1), and changing one node copies only that
+//   node and the chunks above it.
+//
+//   Ids are array positions rather than hashes, so a lookup is a few
+//   indexing steps with no hashing.
+//
+// Notes:
+//   * A new node takes the lowest free id, so ids stay dense and the
+//     trie stays shallow however many splits and merges come and go.
+//   * Chunks emptied by removals are dropped; the trie never shrinks
+//     its height.
+// =====================================================================
+
+use std::sync::Arc;
+
+use super::node::{BTreeNode, NodeId};
+
+/// Id bits consumed per level.
+const BITS: u32 = 5;
+
+/// Slots per chunk.
+const WIDTH: usize = 1 << BITS;
+
+
+#[derive(Debug, Clone)]
+enum Slots {
+    /// Chunks one level down (chunks above level 0).
+    Chunks([Option<Arc<Chunk>>; WIDTH]),
+
+    /// The nodes themselves (chunks at level 0).
+    Nodes([Option<Arc<BTreeNode>>; WIDTH]),
+}
+
+
+#[derive(Debug, Clone)]
+struct Chunk {
+    slots: Slots,
+
+    /// Nodes anywhere below this chunk.
+    used: usize,
+}
+
+
+impl Chunk {
+    fn new(level: u32) -> Self {
+        let slots = if level == 0 { Slots::Nodes(Default::default()) } else { Slots::Chunks(Default::default()) };
+        Chunk { slots, used: 0 }
+    }
+}
+
+
+/// Nodes a chunk at `level` holds when full (level 0 holds nodes).
+fn capacity(level: u32) -> usize {
+    1 << (BITS * (level + 1))
+}
+
+
+/// Slot of `id` in its chunk at `level`.
+fn slot_at(id: NodeId, level: u32) -> usize {
+    (id >> (BITS * level)) as usize & (WIDTH - 1)
+}
+
+
+/// The nodes of a tree by id, O(1) to clone; see the module notes.
+#[derive(Debug, Clone)]
+pub(crate) struct NodeTable {
+    root: Arc<Chunk>,
+
+    /// Level of the root chunk; chunks at level 0 hold the nodes.
+    height: u32,
+}
+
+
+impl Default for NodeTable {
+    fn default() -> Self {
+        NodeTable { root: Arc::new(Chunk::new(0)), height: 0 }
+    }
+}
+
+
+impl NodeTable {
+    /// Number of nodes.
+    pub(crate) fn len(&self) -> usize {
+        self.root.used
+    }
+
+
+    /// The node stored under `id`.
+    pub(crate) fn get(&self, id: NodeId) -> Option<&BTreeNode> {
+        if id as usize >= capacity(self.height) {
+            return None;
+        }
+        let mut chunk = &*self.root;
+        let mut level = self.height;
+        loop {
+            match &chunk.slots {
+                Slots::Chunks(chunks) => chunk = chunks[slot_at(id, level)].as_deref()?,
+                Slots::Nodes(nodes) => return nodes[slot_at(id, 0)].as_deref(),
+            }
+            level -= 1;
+        }
+    }
+
+
+    /// Mutable access to the node stored under `id`, copying it and the
+    /// chunks above it first if a clone still shares them.
+    pub(crate) fn get_mut(&mut self, id: NodeId) -> Option<&mut BTreeNode> {
+        if id as usize >= capacity(self.height) {
+            return None;
+        }
+        let mut chunk = Arc::make_mut(&mut self.root);
+        let mut level = self.height;
+        loop {
+            match &mut chunk.slots {
+                Slots::Chunks(chunks) => chunk = Arc::make_mut(chunks[slot_at(id, level)].as_mut()?),
+                Slots::Nodes(nodes) => return nodes[slot_at(id, 0)].as_mut().map(Arc::make_mut),
+            }
+            level -= 1;
+        }
+    }
+
+
+    /// Store `node` under the lowest free id.
+    ///
+    /// # Returns
+    /// The id.
+    pub(crate) fn insert(&mut self, node: BTreeNode) -> NodeId {
+        if self.len() == capacity(self.height) {
+            // Full: the old root becomes the first chunk of a taller trie
+            self.height += 1;
+            let mut root = Chunk::new(self.height);
+            root.used = self.root.used;
+            if let Slots::Chunks(chunks) = &mut root.slots {
+                chunks[0] = Some(std::mem::replace(&mut self.root, Arc::new(Chunk::new(0))));
+            }
+            self.root = Arc::new(root);
+        }
+        insert_at(Arc::make_mut(&mut self.root), self.height, node)
+    }
+
+
+    /// Drop the node stored under `id`; its id is free for reuse.
+    ///
+    /// # Returns
+    /// The node, if there was one.
+    pub(crate) fn remove(&mut self, id: NodeId) -> Option<BTreeNode> {
+        self.get(id)?;
+        let node = remove_at(Arc::make_mut(&mut self.root), self.height, id);
+        Some(Arc::unwrap_or_clone(node))
+    }
+
+
+    /// Drop every node. Clones taken before keep theirs.
+    pub(crate) fn clear(&mut self) {
+        *self = NodeTable::default();
+    }
+}
+
+
+/// Put `node` in the first free slot under `chunk`, at `level`, and
+/// return the id of that slot within the chunk.
+fn insert_at(chunk: &mut Chunk, level: u32, node: BTreeNode) -> NodeId {
+    chunk.used += 1;
+    match &mut chunk.slots {
+        Slots::Nodes(nodes) => {
+            let at = nodes.iter().position(Option::is_none).expect("a chunk with room has an empty slot");
+            nodes[at] = Some(Arc::new(node));
+            at as NodeId
+        }
+        Slots::Chunks(chunks) => {
+            let at = chunks
+                .iter()
+                .position(|child| child.as_ref().is_none_or(|child| child.used < capacity(level - 1)))
+                .expect("a chunk with room has a child with room");
+            let child = chunks[at].get_or_insert_with(|| Arc::new(Chunk::new(level - 1)));
+            let below = insert_at(Arc::make_mut(child), level - 1, node);
+            ((at as NodeId) << (BITS * level)) | below
+        }
+    }
+}
+
+
+/// Take the node under `id` out of `chunk`, at `level`; the caller has
+/// checked it is there.
+fn remove_at(chunk: &mut Chunk, level: u32, id: NodeId) -> Arc<BTreeNode> {
+    chunk.used -= 1;
+    let at = slot_at(id, level);
+    match &mut chunk.slots {
+        Slots::Nodes(nodes) => nodes[at].take().expect("the caller checked the node is there"),
+        Slots::Chunks(chunks) => {
+            let child = Arc::make_mut(chunks[at].as_mut().expect("the caller checked the node is there"));
+            let node = remove_at(child, level - 1, id);
+            if child.used == 0 {
+                chunks[at] = None;
+            }
+            node
+        }
+    }
+}
@@ -6,8 +6,9 @@
 // Date: Sept. 21, 2025 - Refactored Sept. 22, 2025
 //
 // Description:
-//   Unit tests for the B-tree implementation (`BTreeNode` and
-//   `BTreeIndex`). Covers insert, search, delete, and structural tests
+//   Unit tests for the B+tree implementation (`BTreeNode` and
+//   `BTreeIndex`) and its node table. Covers insert, search, delete, the
+//   leaf chain, and structural tests
 //
 // Notes:
 //   * Only compiled when running `cargo test`.
@@ -29,32 +30,36 @@ mod index_tests {
         assert!(node.kv_pairs.is_empty());
         assert!(node.children.is_empty());
         assert!(node.is_leaf);
+        assert_eq!((node.prev, node.next), (None, None));
     }
 
     #[test]
     fn test_new_internal_node() {
         let node = BTreeNode::new(false);
         assert!(!node.is_leaf);
+        assert!(node.separators.is_empty());
     }
 
     #[test]
     fn test_new_internal_index() {
         let index = BTreeIndex::new(2);
         assert!(index.t >= 2);
-        assert!(index.root.kv_pairs.is_empty());
-        assert!(index.root.children.is_empty());
-        assert!(index.root.is_leaf);
+        let root = index.node(index.root);
+        assert!(root.kv_pairs.is_empty());
+        assert!(root.children.is_empty());
+        assert!(root.is_leaf);
     }
 
     #[test]
     // Initial search testing without using inserts
     fn search_in_single_leaf_node() {
         // Create a leaf with two kv_pairs
-        let mut root = BTreeNode::new(true);
-        root.kv_pairs.push(("cat".into(), "meow".into()));
-        root.kv_pairs.push(("dog".into(), "bark".into()));
-        // println!("{:?}", root.kv_pairs);
-        let tree = BTreeIndex { t: 2, root: Box::new(root) };
+        let mut tree = BTreeIndex::new(2);
+        let root = tree.root;
+        let leaf = tree.node_mut(root);
+        leaf.kv_pairs.push(("cat".into(), "meow".into()));
+        leaf.kv_pairs.push(("dog".into(), "bark".into()));
+        leaf.size = 2;
 
         // Should find exact matches
         assert_eq!(tree.search("dog"), Some("bark"));
@@ -62,38 +67,49 @@ mod index_tests {
 
         // This will miss - key not in tree
         assert_eq!(tree.search("fish"), None);
+        assert_eq!(tree.check_invariants(), Ok(()));
     }
 
     #[test]
-    // Tests how search performs recursively - not using insert to build
+    // Tests how search descends - not using insert to build
     fn search_in_internal_node() {
-        // Root is internal (is_leaf = false)
-        let mut root = BTreeNode::new(false);
-        // Make a split
-        root.kv_pairs.push(("m".into(), "middle".into()));
+        let mut tree = BTreeIndex::new(2);
 
-        // Left child: [a -> "A", f -> "F"]
-        let mut left = BTreeNode::new(true);
-        left.kv_pairs.push(("a".into(), "A".into()));
-        left.kv_pairs.push(("f".into(), "F".into()));
+        // Left leaf: [a -> "A", f -> "F"]
+        let left = tree.root;
+        tree.node_mut(left).kv_pairs.push(("a".into(), "A".into()));
+        tree.node_mut(left).kv_pairs.push(("f".into(), "F".into()));
+        tree.node_mut(left).size = 2;
 
-        // Right child: [z -> "Z"]
+        // Right leaf: [z -> "Z"], linked after the left one
         let mut right = BTreeNode::new(true);
         right.kv_pairs.push(("z".into(), "Z".into()));
+        right.size = 1;
+        right.prev = Some(left);
+        let right = tree.add_node(right);
+        tree.node_mut(left).next = Some(right);
 
-        // Attach children
-        root.children.push(Box::new(left));
-        root.children.push(Box::new(right));
-
-        let tree = BTreeIndex { t: 2, root: Box::new(root) };
+        // Root is internal (is_leaf = false); "m" only separates, it holds no value
+        let mut root = BTreeNode::new(false);
+        root.separators.push("m".into());
+        root.children.extend([left, right]);
+        root.size = 3;
+        tree.root = tree.add_node(root);
+        assert_eq!(tree.check_invariants(), Ok(()));
 
         // These require descending into children
         assert_eq!(tree.search("a"), Some("A"));
         assert_eq!(tree.search("f"), Some("F"));
         assert_eq!(tree.search("z"), Some("Z"));
 
-        // Key not present
+        // Key not present, separators included
         assert_eq!(tree.search("x"), None);
+        assert_eq!(tree.search("m"), None);
+
+        // Walking the leaf chain both ways
+        let keys: Vec<&str> = tree.range(std::ops::Bound::Unbounded, std::ops::Bound::Unbounded).map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!["a", "f", "z"]);
+        assert_eq!(tree.last(), Some(("z", &b"Z"[..])));
     }
 }
 
@@ -152,7 +168,7 @@ mod index_insertion_tests {
         t.insert("cat".into(), "meow".into());
         t.insert("apple".into(), "fruit".into());
 
-        let root = &t.root;
+        let root = t.node(t.root);
         assert!(root.kv_pairs.windows(2).all(|w| w[0].0 < w[1].0));
     }
 }
@@ -220,6 +236,180 @@ mod index_expanded_search_tests {
         // Null case
         assert_eq!(tree.search("k99"), None);
     }
+
+    #[test]
+    fn sorted_batch_matches_single_searches() {
+        let mut tree = BTreeIndex::new(2);
+        for i in (0..60).step_by(2) {
+            tree.insert(format!("k{:02}", i), format!("v{:02}", i));
+        }
+
+        // Hits, misses, duplicates and keys past both ends
+        let mut keys: Vec<String> = (0..61).map(|i| format!("k{:02}", i)).collect();
+        keys.extend(["a".to_string(), "k10".to_string(), "zz".to_string()]);
+        keys.sort();
+        let refs: Vec<&str> = keys.iter().map(|k| k.as_str()).collect();
+
+        let expected: Vec<Option<&str>> = refs.iter().map(|k| tree.search(k)).collect();
+        assert_eq!(tree.search_sorted(&refs), expected);
+    }
+
+    #[test]
+    fn sorted_batch_on_empty_tree() {
+        let tree = BTreeIndex::new(2);
+        assert_eq!(tree.search_sorted(&["a", "b"]), vec![None, None]);
+        assert!(tree.search_sorted(&[]).is_empty());
+    }
+}
+
+
+// =================================================================
+// Unit tests for byte-budgeted node sizing
+// =================================================================
+#[cfg(test)]
+mod index_node_budget_tests {
+    use crate::index::NodeId;
+    use crate::BTreeIndex;
+
+    fn max_node_bytes(tree: &BTreeIndex, id: NodeId) -> usize {
+        let node = tree.node(id);
+        node.children.iter().map(|&c| max_node_bytes(tree, c)).fold(node.byte_size(), usize::max)
+    }
+
+    fn height(tree: &BTreeIndex, id: NodeId) -> usize {
+        let node = tree.node(id);
+        if node.is_leaf { 1 } else { 1 + height(tree, node.children[0]) }
+    }
+
+    #[test]
+    fn huge_values_keep_nodes_small() {
+        let mut count = BTreeIndex::new(8);
+        let mut budget = BTreeIndex::with_node_budget(8, 4096);
+        for i in 0..200 {
+            let value = "x".repeat(1000);
+            count.insert(format!("k{:03}", i), value.clone());
+            budget.insert(format!("k{:03}", i), value);
+        }
+
+        // Count-sized nodes hold 7 to 15 of these values (7 KB+)
+        assert!(max_node_bytes(&count, count.root) > 7_000);
+        // Budget is soft by at most the pair inserted after it was reached
+        assert!(max_node_bytes(&budget, budget.root) < 4096 + 1100);
+        assert_eq!(budget.search("k123").map(|v| v.len()), Some(1000));
+    }
+
+    #[test]
+    fn tiny_keys_make_a_shorter_tree() {
+        let mut count = BTreeIndex::new(2);
+        let mut budget = BTreeIndex::with_node_budget(2, 256);
+        for i in 0..500 {
+            count.insert(format!("{:03}", i), "v".into());
+            budget.insert(format!("{:03}", i), "v".into());
+        }
+        assert!(height(&budget, budget.root) < height(&count, count.root));
+    }
+
+    #[test]
+    fn budget_tree_survives_interleaved_deletes() {
+        let mut tree = BTreeIndex::with_node_budget(2, 48);
+        for i in 0..300 {
+            tree.insert(format!("k{:03}", (i * 37) % 300), i.to_string());
+        }
+        for i in (0..300).step_by(3) {
+            tree.delete(&format!("k{:03}", i));
+        }
+
+        let mut keys = Vec::new();
+        tree.collect_keys(&mut keys);
+        assert_eq!(keys.len(), 200);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(tree.search("k000"), None);
+        assert!(tree.search("k001").is_some());
+    }
+}
+
+
+// =================================================================
+// Unit tests for building a tree from sorted pairs
+// =================================================================
+#[cfg(test)]
+mod index_bulk_load_tests {
+    use crate::index::NodeId;
+    use crate::{BTreeIndex, Collation};
+
+    fn pairs(n: usize) -> Vec<(String, Vec<u8>)> {
+        (0..n).map(|i| (format!("k{:04}", i), i.to_string().into_bytes())).collect()
+    }
+
+    /// Depth of every leaf, and the fewest and most pairs (separators) in
+    /// a non-root node.
+    fn shape(tree: &BTreeIndex, id: NodeId, depth: usize, out: &mut (Vec<usize>, usize, usize)) {
+        let node = tree.node(id);
+        if depth > 0 {
+            out.1 = out.1.min(node.key_count());
+            out.2 = out.2.max(node.key_count());
+        }
+        if node.is_leaf {
+            out.0.push(depth);
+            return;
+        }
+        assert_eq!(node.children.len(), node.separators.len() + 1);
+        for &child in &node.children {
+            shape(tree, child, depth + 1, out);
+        }
+    }
+
+    #[test]
+    fn builds_a_balanced_tree_of_every_size() {
+        for t in [2, 3, 8] {
+            for n in 0..200 {
+                let tree = BTreeIndex::bulk_load(t, pairs(n));
+                let mut out = (Vec::new(), usize::MAX, 0);
+                shape(&tree, tree.root, 0, &mut out);
+                assert!(out.0.windows(2).all(|w| w[0] == w[1]), "t={} n={}: uneven leaves", t, n);
+                assert_eq!(tree.check_invariants(), Ok(()), "t={} n={}", t, n);
+                if n >= 2 * t {
+                    assert!(out.1 >= t - 1 && out.2 < 2 * t, "t={} n={}: {}..{} pairs", t, n, out.1, out.2);
+                }
+
+                let mut keys = Vec::new();
+                tree.collect_keys(&mut keys);
+                assert_eq!(keys, pairs(n).into_iter().map(|(k, _)| k).collect::<Vec<_>>());
+                assert_eq!(tree.len(), n);
+            }
+        }
+    }
+
+    #[test]
+    fn bulk_loaded_trees_take_inserts_and_deletes() {
+        let mut tree = BTreeIndex::bulk_load(2, pairs(500));
+        assert_eq!(tree.search("k0321"), Some("321"));
+        for i in (0..500).step_by(2) {
+            tree.delete(&format!("k{:04}", i));
+        }
+        tree.insert("k9999".into(), "last".into());
+        assert_eq!(tree.len(), 251);
+        assert_eq!(tree.search("k0320"), None);
+        assert_eq!(tree.last(), Some(("k9999", &b"last"[..])));
+    }
+
+    #[test]
+    fn load_sorted_keeps_the_budget_and_collation() {
+        let mut budget = BTreeIndex::with_node_budget(2, 64);
+        budget.load_sorted(pairs(300));
+        let mut out = (Vec::new(), usize::MAX, 0);
+        shape(&budget, budget.root, 0, &mut out);
+        assert_eq!(budget.check_invariants(), Ok(()));
+        assert!(out.0.windows(2).all(|w| w[0] == w[1]));
+        assert_eq!(budget.node_bytes, Some(64));
+        assert_eq!(budget.search("k0299"), Some("299"));
+
+        let mut folded = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        folded.load_sorted(vec![("apple".into(), b"1".to_vec()), ("Banana".into(), b"2".to_vec())]);
+        folded.insert("cherry".into(), "3".into());
+        assert_eq!(folded.search("Banana"), Some("2"));
+        assert_eq!(folded.first(), Some(("apple", &b"1"[..])));
+    }
 }
 
 
@@ -261,10 +451,10 @@ mod index_delete_tests {
     }
 
     #[test]
-    fn delete_internal_key_with_predecessor() {
+    fn delete_key_before_a_separator() {
         let mut t = sample_tree();
         assert_eq!(t.search("cat"), Some("meow"));
-        // "cat" will be replaced with predecessor
+        // "cat" ends a leaf; the leaf borrows or merges to stay full enough
         t.delete("cat");
         assert_eq!(t.search("cat"), None);
         // Other entries still intact
@@ -272,10 +462,10 @@ mod index_delete_tests {
     }
 
     #[test]
-    fn delete_internal_key_with_successor() {
+    fn delete_key_that_is_a_separator() {
         let mut t = sample_tree();
         assert_eq!(t.search("dinosaur"), Some("raaawr"));
-        // "dinosaur" replaced with successor
+        // "dinosaur" starts a leaf, so it also separates it from the one before
         t.delete("dinosaur");
         assert_eq!(t.search("dinosaur"), None);
         // Tree still contains other values
@@ -295,8 +485,37 @@ mod index_delete_tests {
             assert_eq!(t.search(k), None, "still present after delete: {}", k);
         }
         // Root should now be empty leaf
-        assert!(t.root.is_leaf);
-        assert!(t.root.kv_pairs.is_empty());
+        let root = t.node(t.root);
+        assert!(root.is_leaf);
+        assert!(root.kv_pairs.is_empty());
+        assert!(t.is_empty());
+        assert_eq!(t.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn len_tracks_inserts_overwrites_and_deletes() {
+        let mut t = BTreeIndex::with_node_budget(2, 48);
+        let mut count = 0;
+        for i in 0..400 {
+            t.insert(format!("k{}", i * 13 % 400), "v".into());
+            count += 1;
+            if i % 3 == 0 {
+                // Overwrites and misses leave the count alone
+                t.insert(format!("k{}", i * 13 % 400), "w".into());
+                t.delete("missing");
+            }
+            if i % 5 == 4 {
+                t.delete(&format!("k{}", (i - 2) * 13 % 400));
+                count -= 1;
+            }
+            assert_eq!(t.len(), count, "after {}", i);
+        }
+        let mut keys = Vec::new();
+        t.collect_keys(&mut keys);
+        assert_eq!(keys.len(), t.len());
+
+        t.clear();
+        assert!(t.is_empty());
     }
 
     #[test]
@@ -311,5 +530,539 @@ mod index_delete_tests {
         assert_eq!(t.search("a"), Some("vala"));
         assert_eq!(t.search("g"), Some("valg"));
     }
+
+    #[test]
+    fn delete_and_insert_hand_back_the_old_value() {
+        let mut t = BTreeIndex::new(2);
+        for i in 0..300 {
+            assert_eq!(t.insert(format!("k{:03}", i * 7 % 300), format!("v{}", i * 7 % 300)), None);
+        }
+        assert_eq!(t.insert("k100".into(), "new".into()), Some("v100".to_string()));
+        assert_eq!(t.insert_bytes("k101".into(), vec![0xff]), Some(b"v101".to_vec()));
+        assert_eq!(t.insert("k101".into(), "text".into()), Some("\u{fffd}".to_string()));
+
+        // Keys that are separators too and keys that are not, alike
+        for i in (0..300).rev().step_by(3) {
+            let want = if i == 101 { "text".to_string() } else { format!("v{}", i) };
+            assert_eq!(t.delete(&format!("k{:03}", i)), Some(want.into_bytes()), "k{:03}", i);
+            assert_eq!(t.delete(&format!("k{:03}", i)), None);
+        }
+        assert_eq!(t.len(), 200);
+    }
+}
+
+
+
+// =================================================================
+// Invariants under random inserts and deletes
+// =================================================================
+#[cfg(test)]
+mod index_invariant_tests {
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+
+    use crate::{BTreeIndex, Collation};
+
+    /// Small deterministic generator (xorshift64*).
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) % n as u64) as usize
+        }
+    }
+
+    /// Runs seeded inserts and deletes against `tree` and a map, checking
+    /// the invariants after every step so a failure names the one that broke them.
+    fn run(mut tree: BTreeIndex, seed: u64) {
+        let mut rng = Rng(seed);
+        let mut model = BTreeMap::new();
+        for step in 0..1500 {
+            let key = format!("k{:03}", rng.below(200));
+            let op = if rng.below(5) < 3 {
+                let value = "v".repeat(rng.below(40));
+                assert_eq!(tree.insert(key.clone(), value.clone()), model.insert(key.clone(), value));
+                "insert"
+            } else {
+                assert_eq!(tree.delete(&key), model.remove(&key).map(String::into_bytes));
+                "delete"
+            };
+            if let Err(e) = tree.check_invariants() {
+                panic!("seed {} step {}: {} {}: {}", seed, step, op, key, e);
+            }
+        }
+        let keys: Vec<&str> = tree.range(std::ops::Bound::Unbounded, std::ops::Bound::Unbounded).map(|(k, _)| k).collect();
+        assert_eq!(keys, model.keys().map(String::as_str).collect::<Vec<_>>(), "seed {}", seed);
+    }
+
+    #[test]
+    fn random_inserts_and_deletes_keep_the_invariants() {
+        for seed in 1..=6 {
+            for t in [2, 3, 5] {
+                run(BTreeIndex::new(t), seed * 7919 + t as u64);
+            }
+            run(BTreeIndex::with_node_budget(2, 96), seed);
+            run(BTreeIndex::with_collation(3, Collation::CaseInsensitive), seed);
+        }
+    }
+
+    #[test]
+    fn rank_and_select_follow_random_edits() {
+        let mut rng = Rng(42);
+        for mut tree in [BTreeIndex::new(2), BTreeIndex::new(4), BTreeIndex::with_node_budget(2, 64)] {
+            let mut model = BTreeMap::new();
+            for step in 0..1200 {
+                let key = format!("k{:03}", rng.below(150));
+                if rng.below(3) < 2 {
+                    tree.insert(key.clone(), step.to_string());
+                    model.insert(key, step.to_string());
+                } else {
+                    tree.delete(&key);
+                    model.remove(&key);
+                }
+                if step % 50 != 0 {
+                    continue;
+                }
+                for (n, (k, v)) in model.iter().enumerate() {
+                    assert_eq!(tree.select(n), Some((k.as_str(), v.as_bytes())), "step {} select {}", step, n);
+                    assert_eq!(tree.rank(k), n, "step {} rank {}", step, k);
+                }
+                assert_eq!(tree.select(model.len()), None);
+                let probe = format!("k{:03}5", rng.below(150));
+                assert_eq!(tree.rank(&probe), model.range(..probe.clone()).count(), "step {} rank {}", step, probe);
+            }
+        }
+    }
+
+    #[test]
+    fn snapshots_keep_their_contents_through_later_writes() {
+        let mut rng = Rng(7);
+        let mut tree = BTreeIndex::new(2);
+        let mut model = BTreeMap::new();
+        let mut snapshots = Vec::new();
+        for step in 0..2000 {
+            let key = format!("k{:03}", rng.below(300));
+            if rng.below(3) < 2 {
+                tree.insert(key.clone(), step.to_string());
+                model.insert(key, step.to_string());
+            } else {
+                tree.delete(&key);
+                model.remove(&key);
+            }
+            if step % 400 == 0 {
+                snapshots.push((tree.snapshot(), model.clone()));
+            }
+        }
+
+        for (snapshot, expected) in snapshots.iter().chain([(tree, model)].iter()) {
+            assert_eq!(snapshot.check_invariants(), Ok(()));
+            let pairs: Vec<(&str, &[u8])> = snapshot.range(Bound::Unbounded, Bound::Unbounded).collect();
+            let expected: Vec<(&str, &[u8])> = expected.iter().map(|(k, v)| (k.as_str(), v.as_bytes())).collect();
+            assert_eq!(pairs, expected);
+        }
+    }
+
+    #[test]
+    fn writes_copy_only_the_nodes_they_change() {
+        let mut tree = BTreeIndex::bulk_load(2, (0..64).map(|i| (format!("k{:02}", i), Vec::new())).collect());
+        let snapshot = tree.snapshot();
+        let shared = |tree: &BTreeIndex, id| std::ptr::eq(tree.node(id), snapshot.node(id));
+
+        // A miss copies nothing
+        assert!(tree.get_bytes_mut("missing").is_none());
+        let first = tree.node(tree.root).children[0];
+        assert!(shared(&tree, tree.root) && shared(&tree, first));
+
+        // A hit copies its leaf alone: the parents and the next leaf still name it by id
+        let mut leaf = first;
+        while !tree.node(leaf).is_leaf {
+            leaf = tree.node(leaf).children[0];
+        }
+        *tree.get_bytes_mut("k00").unwrap() = b"new".to_vec();
+        assert!(!shared(&tree, leaf));
+        assert!(shared(&tree, tree.root) && shared(&tree, first));
+        assert!(shared(&tree, tree.node(leaf).next.unwrap()));
+        assert_eq!(snapshot.get_bytes("k00"), Some(&b""[..]));
+
+        // Inserts and their splits leave the far side of the tree shared
+        let last = *snapshot.node(snapshot.root).children.last().unwrap();
+        for key in ["k00a", "k00b", "k00c"] {
+            tree.insert_bytes(key.into(), Vec::new());
+        }
+        assert!(shared(&tree, last));
+        assert_eq!(tree.check_invariants(), Ok(()));
+        assert_eq!(snapshot.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn violations_name_the_node() {
+        let mut tree = BTreeIndex::bulk_load(2, (0..20).map(|i| (format!("k{:02}", i), Vec::new())).collect());
+        assert_eq!(tree.check_invariants(), Ok(()));
+        let root = tree.node(tree.root);
+        let (left, right) = (root.children[0], root.children[1]);
+        let first_leaf = tree.node(left).children[0];
+
+        let mut unordered = tree.clone();
+        unordered.node_mut(right).separators[0] = "a".into();
+        assert!(unordered.check_invariants().unwrap_err().starts_with("root.1: separator "));
+
+        let mut short = tree.clone();
+        short.node_mut(left).children.pop();
+        assert!(short.check_invariants().unwrap_err().contains("children"));
+
+        let mut unlinked = tree.clone();
+        unlinked.node_mut(first_leaf).next = None;
+        assert!(unlinked.check_invariants().unwrap_err().starts_with("root.0.0: next links to None"));
+
+        // The clones copied the nodes they changed; `tree` itself is untouched
+        assert_eq!(tree.check_invariants(), Ok(()));
+        tree.node_mut(left).size += 1;
+        assert_eq!(tree.check_invariants().unwrap_err(), "root.0: size is 13 but 12 pairs are below it");
+    }
+}
+
+
+// =================================================================
+// Unit tests for inline node storage
+// =================================================================
+#[cfg(test)]
+mod inline_storage_tests {
+    use crate::BTreeIndex;
+
+    #[test]
+    fn default_degree_nodes_stay_inline() {
+        let mut t = BTreeIndex::new(2);
+        for i in 0..100 {
+            t.insert(format!("k{:03}", i), format!("v{}", i));
+        }
+        fn all_inline(t: &BTreeIndex, id: crate::index::NodeId) -> bool {
+            let node = t.node(id);
+            !node.kv_pairs.spilled()
+                && !node.separators.spilled()
+                && !node.children.spilled()
+                && node.children.iter().all(|&c| all_inline(t, c))
+        }
+        assert!(all_inline(&t, t.root));
+    }
+
+    #[test]
+    fn larger_degree_tree_spills_and_still_works() {
+        let mut t = BTreeIndex::new(4);
+        for i in 0..200 {
+            t.insert(format!("k{:03}", i), format!("v{}", i));
+        }
+        for i in (0..200).step_by(3) {
+            t.delete(&format!("k{:03}", i));
+        }
+        assert_eq!(t.search("k001"), Some("v1"));
+        assert_eq!(t.search("k003"), None);
+
+        let mut keys = Vec::new();
+        t.collect_keys(&mut keys);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(keys.len(), 200 - 67);
+    }
+}
+
+
+// =================================================================
+// Collation: key ordering under each comparator
+// =================================================================
+#[cfg(test)]
+mod index_collation_tests {
+    use crate::{BTreeIndex, Collation};
+    use std::cmp::Ordering;
+
+    fn keys_of(t: &BTreeIndex) -> Vec<String> {
+        let mut keys = Vec::new();
+        t.collect_keys(&mut keys);
+        keys
+    }
+
+    #[test]
+    fn binary_orders_by_bytes() {
+        let mut t = BTreeIndex::new(2);
+        for k in ["b", "B", "a", "A", "é"] {
+            t.insert(k.into(), "v".into());
+        }
+        assert_eq!(keys_of(&t), vec!["A", "B", "a", "b", "é"]);
+    }
+
+    #[test]
+    fn case_insensitive_interleaves_cases_but_keeps_keys_distinct() {
+        let mut t = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        for k in ["b", "B", "a", "A", "c"] {
+            t.insert(k.into(), k.to_lowercase());
+        }
+        t.insert("a".into(), "overwritten".into());
+        assert_eq!(keys_of(&t), vec!["A", "a", "B", "b", "c"]);
+        assert_eq!(t.search("A"), Some("a"));
+        assert_eq!(t.search("a"), Some("overwritten"));
+    }
+
+    #[test]
+    fn unicode_folds_accents() {
+        let c = Collation::Unicode;
+        assert_eq!(c.compare_folded("Éclair", "eclair"), Ordering::Equal);
+        assert_eq!(c.compare_folded("e\u{301}clair", "eclair"), Ordering::Equal);
+        assert_eq!(c.compare_folded("straße", "strasse"), Ordering::Equal);
+        assert_ne!(c.compare("Éclair", "eclair"), Ordering::Equal);
+
+        let mut t = BTreeIndex::with_collation(2, c);
+        for k in ["zebra", "Éclair", "apple", "eclairs", "Dog"] {
+            t.insert(k.into(), "v".into());
+        }
+        assert_eq!(keys_of(&t), vec!["apple", "Dog", "Éclair", "eclairs", "zebra"]);
+    }
+
+    #[test]
+    fn collated_tree_survives_many_inserts_and_deletes() {
+        let mut t = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        for i in 0..300 {
+            let key = if i % 2 == 0 { format!("K{:03}", i) } else { format!("k{:03}", i) };
+            t.insert(key, i.to_string());
+        }
+        for i in (0..300).step_by(4) {
+            t.delete(&format!("K{:03}", i));
+        }
+        assert_eq!(t.search("k001"), Some("1"));
+        assert_eq!(t.search("K000"), None);
+        assert_eq!(t.search("K002"), Some("2"));
+
+        let keys = keys_of(&t);
+        assert_eq!(keys.len(), 300 - 75);
+        assert!(keys.windows(2).all(|w| Collation::CaseInsensitive.compare(&w[0], &w[1]).is_lt()));
+
+        let batch: Vec<&str> = keys.iter().map(String::as_str).collect();
+        assert!(t.search_sorted(&batch).iter().all(Option::is_some));
+    }
 }
 
+
+// =================================================================
+// Bounded range scans
+// =================================================================
+#[cfg(test)]
+mod index_range_tests {
+    use std::ops::{Bound, RangeBounds};
+
+    use crate::{BTreeIndex, Collation};
+
+    fn tree_with(keys: &[&str]) -> BTreeIndex {
+        let mut t = BTreeIndex::new(2);
+        for k in keys {
+            t.insert(k.to_string(), "v".into());
+        }
+        t
+    }
+
+    #[test]
+    fn includes_keys_with_any_characters() {
+        let t = tree_with(&["user:1", "user:2", "user-3", "a1", "Z_9", "b", "user:10"]);
+        assert_eq!(t.range_keys(Some("user:"), Some("user:~")), vec!["user:1", "user:10", "user:2"]);
+        assert_eq!(t.range_keys(None, None), vec!["Z_9", "a1", "b", "user-3", "user:1", "user:10", "user:2"]);
+    }
+
+    #[test]
+    fn bounds_are_inclusive_and_may_be_open() {
+        let t = tree_with(&["a", "b", "c", "d"]);
+        assert_eq!(t.range_keys(Some("b"), Some("c")), vec!["b", "c"]);
+        assert_eq!(t.range_keys(Some("bb"), None), vec!["c", "d"]);
+        assert_eq!(t.range_keys(None, Some("a")), vec!["a"]);
+        assert!(t.range_keys(Some("d"), Some("a")).is_empty());
+    }
+
+    #[test]
+    fn matches_a_full_scan_on_a_deep_tree() {
+        let mut t = BTreeIndex::new(2);
+        for i in 0..500 {
+            t.insert(format!("k:{}", i * 7 % 500), i.to_string());
+        }
+        let mut all = Vec::new();
+        t.collect_keys(&mut all);
+
+        for (start, end) in [("k:1", "k:2"), ("k:250", "k:9"), ("k:", "k:0"), ("k:499", "k:499")] {
+            let expected: Vec<String> = all
+                .iter()
+                .filter(|k| k.as_str() >= start && k.as_str() <= end)
+                .cloned()
+                .collect();
+            assert_eq!(t.range_keys(Some(start), Some(end)), expected, "{}..{}", start, end);
+        }
+    }
+
+    #[test]
+    fn uses_folded_bounds_under_collation() {
+        let mut t = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        for k in ["A", "a", "B", "b", "C"] {
+            t.insert(k.into(), "v".into());
+        }
+        assert_eq!(t.range_keys(Some("a"), Some("b")), vec!["A", "a", "B", "b"]);
+        assert_eq!(t.count_range(Bound::Included("a"), Bound::Included("b")), 4);
+        assert_eq!(t.count_range(Bound::Excluded("A"), Bound::Unbounded), 3);
+    }
+
+    #[test]
+    fn scan_prefix_stops_at_the_end_of_the_namespace() {
+        let mut t = BTreeIndex::new(2);
+        for i in 0..200 {
+            t.insert(format!("user:{}", i), i.to_string());
+            t.insert(format!("order:{}", i), i.to_string());
+        }
+        t.insert("user".into(), "v".into());
+        t.insert("users".into(), "v".into());
+
+        let users: Vec<(&str, &[u8])> = t.scan_prefix("user:").collect();
+        assert_eq!(users.len(), 200);
+        assert!(users.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(users.iter().all(|(k, v)| &k.as_bytes()["user:".len()..] == *v));
+        assert_eq!(t.scan_prefix("user:19").count(), 11);
+        assert_eq!(t.scan_prefix("nobody").count(), 0);
+        assert_eq!(t.scan_prefix("").count(), 402);
+    }
+
+    #[test]
+    fn scan_prefix_folds_like_the_collation() {
+        let mut t = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        for k in ["User:1", "user:2", "USER:3", "usex", "Users"] {
+            t.insert(k.into(), "v".into());
+        }
+        let keys: Vec<&str> = t.scan_prefix("user:").map(|(k, _)| k).collect();
+        assert_eq!(keys, vec!["User:1", "user:2", "USER:3"]);
+    }
+
+    #[test]
+    fn floor_and_ceiling_match_a_full_scan() {
+        let mut t = BTreeIndex::new(2);
+        for i in (0..400).step_by(4) {
+            t.insert(format!("k{:03}", i), i.to_string());
+        }
+        let all: Vec<(&str, &[u8])> = t.range(Bound::Unbounded, Bound::Unbounded).collect();
+        for i in 0..405 {
+            let probe = format!("k{:03}", i);
+            let floor = all.iter().rev().find(|(k, _)| *k <= probe.as_str()).copied();
+            let ceiling = all.iter().find(|(k, _)| *k >= probe.as_str()).copied();
+            assert_eq!(t.get_le(&probe), floor, "{}", probe);
+            assert_eq!(t.get_ge(&probe), ceiling, "{}", probe);
+        }
+        assert_eq!(t.first(), Some(("k000", &b"0"[..])));
+        assert_eq!(t.last(), Some(("k396", &b"396"[..])));
+        assert_eq!(t.get_le(""), None);
+        assert_eq!(t.get_ge(""), t.first());
+
+        let empty = BTreeIndex::new(2);
+        assert_eq!((empty.first(), empty.last(), empty.get_le("k"), empty.get_ge("k")), (None, None, None, None));
+    }
+
+    #[test]
+    fn floor_and_ceiling_compare_folded() {
+        let mut t = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        for key in ["Apple", "banana", "Cherry"] {
+            t.insert(key.into(), "v".into());
+        }
+        assert_eq!(t.get_ge("BANANA").map(|(k, _)| k), Some("banana"));
+        assert_eq!(t.get_le("bz").map(|(k, _)| k), Some("banana"));
+        assert_eq!(t.get_ge("c").map(|(k, _)| k), Some("Cherry"));
+    }
+
+    #[test]
+    fn streams_pairs_both_ways_for_every_kind_of_bound() {
+        let mut t = BTreeIndex::new(2);
+        for i in 0..300 {
+            t.insert(format!("k{:03}", i * 7 % 300), i.to_string());
+        }
+        for i in (0..300).step_by(3) {
+            t.delete(&format!("k{:03}", i));
+        }
+        let mut all = Vec::new();
+        t.collect_keys(&mut all);
+
+        let bounds = [
+            Bound::Unbounded,
+            Bound::Included("k100"),
+            Bound::Excluded("k100"),
+            Bound::Included("k101"),
+            Bound::Excluded("k101"),
+            Bound::Included("k2"),
+            Bound::Excluded("k299"),
+        ];
+        for start in bounds {
+            for end in bounds {
+                let expected: Vec<&str> = all
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|k| RangeBounds::<&str>::contains(&(start, end), k))
+                    .collect();
+                let streamed: Vec<&str> = t.range(start, end).map(|(k, _)| k).collect();
+                assert_eq!(streamed, expected, "{:?}..{:?}", start, end);
+                assert_eq!(t.count_range(start, end), expected.len(), "{:?}..{:?} count", start, end);
+            }
+        }
+
+        // Descending walks visit the same pairs from the other end
+        for start in bounds {
+            for end in bounds {
+                let mut ascending: Vec<(&str, &[u8])> = t.range(start, end).collect();
+                ascending.reverse();
+                assert_eq!(t.range_rev(start, end).collect::<Vec<_>>(), ascending, "{:?}..{:?} rev", start, end);
+            }
+        }
+
+        // Values come with their keys
+        assert_eq!(t.range(Bound::Included("k007"), Bound::Included("k007")).next(), Some(("k007", &b"1"[..])));
+        assert_eq!(BTreeIndex::new(2).range(Bound::Unbounded, Bound::Unbounded).next(), None);
+        assert_eq!(BTreeIndex::new(2).range_rev(Bound::Unbounded, Bound::Unbounded).next(), None);
+    }
+}
+
+
+// =================================================================
+// Node table: ids, reuse and copy-on-write
+// =================================================================
+#[cfg(test)]
+mod node_table_tests {
+    use crate::index::table::NodeTable;
+    use crate::BTreeNode;
+
+    fn leaf(size: usize) -> BTreeNode {
+        BTreeNode { size, ..BTreeNode::new(true) }
+    }
+
+    #[test]
+    fn ids_are_dense_and_reused_lowest_first() {
+        let mut table = NodeTable::default();
+        // Enough nodes for a trie three chunks tall
+        for i in 0..2000 {
+            assert_eq!(table.insert(leaf(i)), i as u64);
+        }
+        assert_eq!(table.remove(1500).map(|n| n.size), Some(1500));
+        assert_eq!(table.remove(40).map(|n| n.size), Some(40));
+        assert_eq!(table.remove(40).map(|n| n.size), None);
+        assert_eq!((table.get(40).is_none(), table.len()), (true, 1998));
+
+        assert_eq!(table.insert(leaf(7)), 40);
+        assert_eq!(table.insert(leaf(7)), 1500);
+        assert_eq!(table.insert(leaf(7)), 2000);
+        assert_eq!(table.get(1999).map(|n| n.size), Some(1999));
+        assert!(table.get(1 << 40).is_none());
+    }
+
+    #[test]
+    fn clones_keep_their_own_nodes() {
+        let mut live = NodeTable::default();
+        for i in 0..100 {
+            live.insert(leaf(i));
+        }
+        let snapshot = live.clone();
+        live.get_mut(5).unwrap().size = 50;
+        live.remove(6);
+        live.insert(leaf(1000));
+
+        assert_eq!((snapshot.get(5).unwrap().size, snapshot.get(6).unwrap().size, snapshot.len()), (5, 6, 100));
+        assert_eq!((live.get(5).unwrap().size, live.get(6).unwrap().size, live.len()), (50, 1000, 100));
+        assert!(std::ptr::eq(live.get(7).unwrap(), snapshot.get(7).unwrap()));
+    }
+}
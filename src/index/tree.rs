@@ -6,32 +6,79 @@
 // Date: Sept 21, 2025 - Refactored Sept. 22, 2025
 //
 // Description:
-//   Implements the B-tree index (`BTreeIndex`) that manages insertion,
+//   Implements the B+tree index (`BTreeIndex`) that manages insertion,
 //   search, and deletion operations over `BTreeNode` structures. This
 //   index serves as the in-memory data structure backing the key-value
 //   store, ensuring efficient lookups and ordered key management.
 //
+//   Every value lives in a leaf, and each leaf links to its neighbours,
+//   so ordered walks go from leaf to leaf. Nodes are kept in a node
+//   table (`table.rs`, indexed by id) and refer to each other by id:
+//   changing a node, leaf links included, rewrites only its own entry.
+//
 // Features:
-//   - `insert`: Adds or overwrites key–value pairs (last write wins).
-//   - `search`: Standard B-tree search; returns the value for a key.
-//   - `delete`: Removes keys while preserving B-tree invariants.
+//   - `bulk_load` / `load_sorted`: Builds a tree bottom-up from sorted
+//     pairs, in O(n).
+//   - `insert_bytes` / `insert`: Adds or overwrites key–value pairs (last
+//     write wins) and returns the value replaced. Values are stored as
+//     bytes; `insert` takes a string.
+//   - `get_bytes` / `search`: Descends by separators to the key's leaf;
+//     returns the value for a key, as bytes or (if it is UTF-8) as text.
+//   - `range` / `range_rev`: Stream the pairs between two bounds, in
+//     either order, walking the leaf chain (see `range.rs`).
+//   - `count_range`: Counts the pairs between two bounds from subtree
+//     sizes, without visiting them.
+//   - `scan_prefix`: Streams the pairs whose keys share a prefix.
+//   - `get_le` / `get_ge` / `first` / `last`: The nearest pair at or
+//     below / at or above a key, and the pairs at either end.
+//   - `rank` / `select`: A key's position in key order, and the pair at
+//     a position, from the subtree sizes kept in every node.
+//   - `delete`: Removes keys from their leaves while preserving B+tree
+//     invariants, and returns the value removed.
 //   - Split/merge helpers: Maintain balance during inserts and deletes.
+//   - Optional byte budget: nodes split by data size instead of key count.
+//   - Pluggable collation: keys are ordered by a `Collation` (bytes by default).
+//   - `check_invariants`: Validates ordering, node fill, leaf depth and
+//     the leaf chain.
+//   - `snapshot`: An O(1) point-in-time copy; the node table is shared
+//     and copied on write, one node at a time.
 //
 // Notes:
 //   * Relies on `node.rs` for the `BTreeNode` definition.
 //   * The minimum degree `t` determines the branching factor and the
 //     number of keys per node.
 //   * Internal helpers (`insert_internal`, `delete_internal`, etc.)
-//     implement the recursive B-tree algorithms.
+//     implement the recursive B+tree algorithms.
 // =====================================================================
-use super::BTreeNode;
+use std::ops::Bound;
+
+use super::node::NodeId;
+use super::range::{above, below};
+use super::table::NodeTable;
+use super::{BTreeNode, Collation, Range, RangeRev};
 
 /// BTree Index, interfaces with lib to index the db with the nodes and leafs.
-/// Contains the branching factor (t) and root node.
-#[derive(Debug)]
+/// Contains the branching factor (t), root node, node table, optional node
+/// byte budget and the key collation.
+///
+/// The node table is shared between clones and copied on write, so cloning a tree is O(1) (see [`snapshot`](Self::snapshot)).
+#[derive(Debug, Clone)]
 pub struct BTreeIndex {
     pub t: usize,
-    pub root: Box<BTreeNode>,
+
+    /// Id of the root node.
+    pub root: NodeId,
+
+    /// Every node of the tree, by id.
+    nodes: NodeTable,
+
+    /// When set, a node is full once its keys and values reach this many
+    /// bytes (and it holds at least three pairs), instead of at `2t - 1`
+    /// pairs. `t` still sets the minimum fill used by deletion.
+    pub node_bytes: Option<usize>,
+
+    /// Order of keys in the tree. Only change it on an empty tree.
+    pub collation: Collation,
 }
 
 
@@ -42,22 +89,270 @@ impl BTreeIndex {
     /// Create a new empty B-tree with minimum degree t greather than 2.
     pub fn new(t: usize) -> Self {
         assert!(t >= 2, "B-tree minimum degree t must be >= 2");
-        Self {
+        let mut tree = Self {
             t,
-            root: Box::new(BTreeNode::new(true)),
+            root: 0,
+            nodes: NodeTable::default(),
+            node_bytes: None,
+            collation: Collation::Binary,
+        };
+        tree.root = tree.add_node(BTreeNode::new(true));
+        tree
+    }
+
+    /// Create an empty B-tree whose nodes are sized by a byte budget.
+    ///
+    /// Small keys pack many to a node (a shorter tree), while huge values
+    /// split off after a few pairs so no single node gets enormous.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut t = BTreeIndex::with_node_budget(2, 64);
+    /// for i in 0..20 {
+    ///     t.insert(format!("k{:02}", i), "v".into());
+    /// }
+    /// // 4-byte pairs pack 16 to a 64-byte node, so one split is enough
+    /// assert_eq!(t.node(t.root).children.len(), 2);
+    /// assert_eq!(t.search("k07"), Some("v"));
+    /// ```
+    pub fn with_node_budget(t: usize, node_bytes: usize) -> Self {
+        Self {
+            node_bytes: Some(node_bytes),
+            ..Self::new(t)
+        }
+    }
+
+    /// Create an empty B-tree that orders keys by `collation`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{BTreeIndex, Collation};
+    /// let mut t = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+    /// for key in ["banana", "Apple", "cherry", "apple"] {
+    ///     t.insert(key.into(), "v".into());
+    /// }
+    /// let mut keys = Vec::new();
+    /// t.collect_keys(&mut keys);
+    /// assert_eq!(keys, vec!["Apple", "apple", "banana", "cherry"]);
+    /// ```
+    pub fn with_collation(t: usize, collation: Collation) -> Self {
+        Self {
+            collation,
+            ..Self::new(t)
         }
     }
 
-    /// Search for a key in the B-tree.
+    /// A point-in-time view of the tree that later writes leave alone.
     ///
-    /// Traverses the tree from the root, descending into child nodes as needed,
-    /// to locate the target key.
+    /// The snapshot shares the node table with the tree, so taking one is
+    /// O(1) whatever the size. A write to either side afterwards copies
+    /// only the nodes it changes (and the few table chunks leading to
+    /// them) before changing them; the rest stay shared. Nodes name each
+    /// other by id, so a copied leaf is still the one its neighbours and
+    /// parent link to. Scans, [`range`](Self::range) and backups can run
+    /// on a snapshot while the tree keeps taking writes.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for i in 0..100 {
+    ///     tree.insert(format!("k{:02}", i), "old".into());
+    /// }
+    /// let snapshot = tree.snapshot();
+    /// tree.insert("k07".into(), "new".into());
+    /// tree.delete("k08");
+    ///
+    /// assert_eq!((snapshot.search("k07"), snapshot.len()), (Some("old"), 100));
+    /// assert_eq!((tree.search("k07"), tree.len()), (Some("new"), 99));
+    /// ```
+    pub fn snapshot(&self) -> BTreeIndex {
+        self.clone()
+    }
+
+    /// The node with id `id`.
+    ///
+    /// # Panics
+    /// If the tree has no such node; ids taken from the tree's own nodes
+    /// (`root`, `children`, `prev`, `next`) are always there.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["a", "b", "c", "d"] {
+    ///     tree.insert(key.into(), "v".into());
+    /// }
+    /// let root = tree.node(tree.root);
+    /// let first = tree.node(root.children[0]);
+    /// assert_eq!(first.next, Some(root.children[1]));
+    /// ```
+    pub fn node(&self, id: NodeId) -> &BTreeNode {
+        self.nodes.get(id).expect("node ids in a tree name its nodes")
+    }
+
+    /// Mutable access to the node with id `id`, copying it first if a
+    /// snapshot still shares it (copy-on-write).
+    ///
+    /// For tests and debugging: the tree keeps sizes, separators and
+    /// links consistent itself, and nothing here checks them.
+    ///
+    /// # Panics
+    /// If the tree has no such node.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut BTreeNode {
+        self.nodes.get_mut(id).expect("node ids in a tree name its nodes")
+    }
+
+    /// Add `node` to the table under a free id, for a caller to link in.
+    pub(crate) fn add_node(&mut self, node: BTreeNode) -> NodeId {
+        self.nodes.insert(node)
+    }
+
+    /// Build a B+tree from pairs already sorted by key, bottom-up.
+    ///
+    /// Each node is filled once and never split, so this takes O(n)
+    /// rather than the O(n log n) of inserting the pairs one at a time.
+    /// The keys must be sorted and distinct.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let pairs = (0..100).map(|i| (format!("k{:03}", i), b"v".to_vec())).collect();
+    /// let tree = BTreeIndex::bulk_load(2, pairs);
+    /// assert_eq!(tree.len(), 100);
+    /// assert_eq!(tree.search("k042"), Some("v"));
+    /// ```
+    pub fn bulk_load(t: usize, sorted_pairs: Vec<(String, Vec<u8>)>) -> Self {
+        let mut tree = Self::new(t);
+        tree.load_sorted(sorted_pairs);
+        tree
+    }
+
+    /// Replace the contents of the tree with pairs sorted in its
+    /// collation, built bottom-up as [`bulk_load`](Self::bulk_load) does.
+    ///
+    /// The degree, node budget and collation are kept. The pairs are
+    /// spread over leaves linked in order, and the first key of each leaf
+    /// but the first separates it from the one before. Those separators
+    /// are built into the level above with one held back between each two
+    /// nodes to separate them, and so on until one node is left.
+    pub fn load_sorted(&mut self, sorted_pairs: Vec<(String, Vec<u8>)>) {
+        debug_assert!(
+            sorted_pairs.windows(2).all(|w| self.collation.compare(&w[0].0, &w[1].0).is_lt()),
+            "load_sorted needs distinct keys in collation order"
+        );
+        self.nodes.clear();
+
+        // The leaves, each linked to the one before
+        let sizes = self.leaf_sizes(&sorted_pairs);
+        let mut pairs = sorted_pairs.into_iter();
+        let mut level: Vec<NodeId> = Vec::with_capacity(sizes.len());
+        let mut separators = Vec::with_capacity(sizes.len() - 1);
+        for size in sizes {
+            let mut leaf = BTreeNode::new(true);
+            leaf.kv_pairs.extend(pairs.by_ref().take(size));
+            leaf.size = leaf.kv_pairs.len();
+            leaf.prev = level.last().copied();
+            if leaf.prev.is_some() {
+                separators.push(leaf.kv_pairs[0].0.clone());
+            }
+            let id = self.add_node(leaf);
+            if let Some(&prev) = level.last() {
+                self.node_mut(prev).next = Some(id);
+            }
+            level.push(id);
+        }
+
+        while level.len() > 1 {
+            let sizes = self.level_sizes(&separators);
+            let mut keys = std::mem::take(&mut separators).into_iter();
+            let mut children = std::mem::take(&mut level).into_iter();
+            for (n, size) in sizes.iter().enumerate() {
+                let mut node = BTreeNode::new(false);
+                node.separators.extend(keys.by_ref().take(*size));
+                node.children.extend(children.by_ref().take(size + 1));
+                node.size = node.children.iter().map(|&child| self.node(child).size).sum();
+                level.push(self.add_node(node));
+                if n + 1 < sizes.len() {
+                    // The separators held back become the keys of the level above
+                    separators.extend(keys.next());
+                }
+            }
+        }
+        self.root = level[0];
+    }
+
+    /// How many of `pairs` go in each bulk-loaded leaf.
+    ///
+    /// Count-sized trees spread the pairs evenly over as few leaves as
+    /// hold them, so every leaf has between `t - 1` and `2t - 1`.
+    /// Byte-budgeted trees fill each leaf until it is full.
+    fn leaf_sizes(&self, pairs: &[(String, Vec<u8>)]) -> Vec<usize> {
+        let n = pairs.len();
+        let Some(budget) = self.node_bytes else {
+            let leaves = n.div_ceil(2 * self.t - 1).max(1);
+            return (0..leaves).map(|i| n / leaves + usize::from(i < n % leaves)).collect();
+        };
+
+        let mut sizes = Vec::new();
+        let (mut len, mut bytes) = (0, 0);
+        for (i, (key, value)) in pairs.iter().enumerate() {
+            len += 1;
+            bytes += key.len() + value.len();
+            // Close the leaf only if a pair is left for the next
+            if len >= 3 && bytes >= budget && i + 1 < n {
+                sizes.push(len);
+                (len, bytes) = (0, 0);
+            }
+        }
+        sizes.push(len);
+        sizes
+    }
+
+    /// How many of `separators` go in each internal node of one
+    /// bulk-loaded level, with one between each two nodes left over to
+    /// separate them.
+    ///
+    /// Count-sized trees spread them evenly over as few nodes as hold
+    /// them, so every node has between `t - 1` and `2t - 1`. Byte-budgeted
+    /// trees fill each node until it is full.
+    fn level_sizes(&self, separators: &[String]) -> Vec<usize> {
+        let n = separators.len();
+        let Some(budget) = self.node_bytes else {
+            let nodes = (n + 1).div_ceil(2 * self.t).max(1);
+            let (each, extra) = ((n + 1 - nodes) / nodes, (n + 1 - nodes) % nodes);
+            return (0..nodes).map(|i| each + usize::from(i < extra)).collect();
+        };
+
+        let mut sizes = Vec::new();
+        let (mut len, mut bytes) = (0, 0);
+        let mut i = 0;
+        while i < n {
+            len += 1;
+            bytes += separators[i].len();
+            i += 1;
+            // Close the node only if a separator and a key are left for the next
+            if len >= 3 && bytes >= budget && n - i >= 2 {
+                sizes.push(len);
+                (len, bytes) = (0, 0);
+                i += 1;
+            }
+        }
+        sizes.push(len);
+        sizes
+    }
+
+    /// Search for a key in the B+tree.
+    ///
+    /// Descends from the root by the separators to the one leaf that can
+    /// hold the key, then searches that leaf.
     ///
     /// # Arguments
     /// * `key` - The key to search for.
     ///
     /// # Returns
-    /// * `Some(&str)` containing a reference to the associated value if the key exists.
+    /// * `Some(&[u8])` with the bytes of the associated value if the key exists.
     /// * `None` if the key is not found in the tree.
     ///
     /// # Notes
@@ -68,33 +363,133 @@ impl BTreeIndex {
     /// ```
     /// use kvstore::BTreeIndex;
     /// let mut t = BTreeIndex::new(2);
+    /// t.insert_bytes("png".into(), vec![0x89, b'P', b'N', b'G']);
+    /// assert_eq!(t.get_bytes("png"), Some(&[0x89, b'P', b'N', b'G'][..]));
+    /// assert_eq!(t.get_bytes("gif"), None);
+    /// ```
+    pub fn get_bytes(&self, key: &str) -> Option<&[u8]> {
+        let leaf = self.node(self.find_leaf(key));
+        match leaf.kv_pairs.get(leaf.lower_bound_by(key, self.collation)) {
+            Some((k, value)) if k == key => Some(value),
+            _ => None,
+        }
+    }
+
+
+    /// Id of the leaf whose keys would include `key`.
+    fn find_leaf(&self, key: &str) -> NodeId {
+        let mut id = self.root;
+        loop {
+            let node = self.node(id);
+            if node.is_leaf {
+                return id;
+            }
+            id = node.children[node.child_index_by(key, self.collation)];
+        }
+    }
+
+
+    /// Search for a key whose value is text.
+    ///
+    /// The string form of [`get_bytes`](Self::get_bytes), for the command
+    /// layer and anything else that only stores text.
+    ///
+    /// # Returns
+    /// * `Some(&str)` with the value if the key exists and its value is UTF-8.
+    /// * `None` if the key is not found, or its value is not UTF-8 (use
+    ///   [`contains_key`](Self::contains_key) to tell the two apart).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut t = BTreeIndex::new(2);
     /// t.insert("dog".into(), "bark".into());
     /// assert_eq!(t.search("dog"), Some("bark"));
     /// assert_eq!(t.search("cat"), None);
     /// ```
     pub fn search(&self, key: &str) -> Option<&str> {
+        self.get_bytes(key).and_then(|value| std::str::from_utf8(value).ok())
+    }
 
-        // Recursive function declaration for node search
-        fn search_node<'a>(node: &'a BTreeNode, key: &str) -> Option<&'a str> {
-            // Find the position in this node where the key would belong
-            let idx = node.lower_bound(key);
 
-            // Base Case - Successfully found the key in the current node
-            if idx < node.kv_pairs.len() && node.kv_pairs[idx].0 == key {
-                return Some(node.kv_pairs[idx].1.as_str());
-            }
+    /// `true` if the key is in the tree, whatever its value holds.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get_bytes(key).is_some()
+    }
+
+    /// Look up a sorted batch of keys in one coordinated traversal.
+    ///
+    /// Each node is visited at most once per batch: the keys are split by
+    /// the separators into runs, and each run descends into its child
+    /// together instead of restarting from the root per key.
+    ///
+    /// # Arguments
+    /// * `keys` - Keys in ascending order under the tree's collation
+    ///   (duplicates allowed).
+    ///
+    /// # Returns
+    /// One result per input key, in the same order as `keys`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut t = BTreeIndex::new(2);
+    /// t.insert("ant".into(), "1".into());
+    /// t.insert("cat".into(), "3".into());
+    /// assert_eq!(t.get_bytes_sorted(&["ant", "bee", "cat"]), vec![Some(&b"1"[..]), None, Some(&b"3"[..])]);
+    /// ```
+    pub fn get_bytes_sorted<'a>(&'a self, keys: &[&str]) -> Vec<Option<&'a [u8]>> {
+        let c = self.collation;
+        debug_assert!(keys.windows(2).all(|w| c.compare(w[0], w[1]).is_le()), "get_bytes_sorted needs sorted keys");
 
-            // No key here, base case fails - search ends
+        // Resolve keys[..] into out[..]; both slices line up
+        fn search_run<'a>(tree: &'a BTreeIndex, node: &'a BTreeNode, keys: &[&str], out: &mut [Option<&'a [u8]>], c: Collation) {
             if node.is_leaf {
-                None
+                for (key, out) in keys.iter().zip(out) {
+                    *out = match node.kv_pairs.get(node.lower_bound_by(key, c)) {
+                        Some((k, value)) if k == key => Some(value),
+                        _ => None,
+                    };
+                }
+                return;
+            }
 
-            // No key here, there are children, so recursive search
-            } else {
-                search_node(&node.children[idx], key)
+            let mut i = 0;
+            while i < keys.len() {
+                let idx = node.child_index_by(keys[i], c);
+
+                // Every following key below the next separator shares this child
+                let end = match node.separators.get(idx) {
+                    Some(sep) => i + keys[i..].iter().take_while(|k| c.compare(k, sep).is_lt()).count(),
+                    None => keys.len(),
+                };
+                search_run(tree, tree.node(node.children[idx]), &keys[i..end], &mut out[i..end], c);
+                i = end;
             }
         }
-        // Call search
-        search_node(&self.root, key)
+
+        let mut out = vec![None; keys.len()];
+        search_run(self, self.node(self.root), keys, &mut out, c);
+        out
+    }
+
+
+    /// [`get_bytes_sorted`](Self::get_bytes_sorted) for text values; a
+    /// value that is not UTF-8 comes back as `None`, as from [`search`](Self::search).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut t = BTreeIndex::new(2);
+    /// t.insert("ant".into(), "1".into());
+    /// t.insert("cat".into(), "3".into());
+    /// assert_eq!(t.search_sorted(&["ant", "bee", "cat"]), vec![Some("1"), None, Some("3")]);
+    /// ```
+    pub fn search_sorted<'a>(&'a self, keys: &[&str]) -> Vec<Option<&'a str>> {
+        self.get_bytes_sorted(keys)
+            .into_iter()
+            .map(|value| value.and_then(|v| std::str::from_utf8(v).ok()))
+            .collect()
     }
 
     /// Insert a key-value pair into the B-tree.
@@ -128,83 +523,138 @@ impl BTreeIndex {
     /// assert_eq!(index.search("dog"), Some("bark"));
     /// assert_eq!(index.search("cat"), Some("meow"));
     ///
-    /// // Overwrite existing key
-    /// index.insert("dog".into(), "woof".into());
+    /// // Overwrite existing key, getting the old value back
+    /// assert_eq!(index.insert("dog".into(), "woof".into()), Some("bark".to_string()));
     /// assert_eq!(index.search("dog"), Some("woof"));
     /// ```
-    pub fn insert(&mut self, key: String, value: String) {
+    ///
+    /// # Returns
+    /// The value the key had before, if it was in the tree. Bytes of it
+    /// that are not UTF-8 read as `U+FFFD`.
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        self.insert_bytes(key, value.into_bytes()).map(|old| match String::from_utf8(old) {
+            Ok(text) => text,
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        })
+    }
+
+
+    /// Insert a key with a value of any bytes.
+    ///
+    /// The same as [`insert`](Self::insert), which stores a string's
+    /// bytes through this.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut index = BTreeIndex::new(2);
+    /// index.insert_bytes("raw".into(), vec![0, 159, 146, 150]);
+    /// assert_eq!(index.get_bytes("raw"), Some(&[0, 159, 146, 150][..]));
+    /// assert_eq!(index.search("raw"), None); // not UTF-8
+    /// assert!(index.contains_key("raw"));
+    /// ```
+    ///
+    /// # Returns
+    /// The value the key had before, if it was in the tree.
+    pub fn insert_bytes(&mut self, key: String, value: Vec<u8>) -> Option<Vec<u8>> {
         // Before we mutate anything, try to find and overwrite an existing key directly.
-        if let Some(existing) = self.search_mut(&key) {
-            *existing = value;
+        if let Some(existing) = self.get_bytes_mut(&key) {
             // Short-circuit to updated the value
-            return;
+            return Some(std::mem::replace(existing, value));
         }
 
-        let t = self.t;
-
-        if self.root.kv_pairs.len() == 2 * t - 1 {
+        if self.is_full(self.root) {
             // Create a new root and hang the old root under it
-            let mut new_root = Box::new(BTreeNode::new(false));
-            new_root.children.push(std::mem::replace(
-                &mut self.root,
-                Box::new(BTreeNode::new(true)),
-            ));
-
-            // Split old root (now child 0 of new_root)
-            Self::split_child(&mut new_root, t, 0);
+            let mut new_root = BTreeNode::new(false);
+            new_root.children.push(self.root);
+            new_root.size = self.len();
+            self.root = self.add_node(new_root);
 
-            // Choose which child to descend into
-            let idx = if key > new_root.kv_pairs[0].0 { 1 } else { 0 };
-            Self::insert_internal(&mut new_root.children[idx], t, key, value);
-
-            // Replace the tree's root
-            self.root = new_root;
-        } else {
-            // Root not full — normal descent - Assiociative func call
-            Self::insert_internal(&mut self.root, t, key, value);
+            // Split old root (now child 0 of the new root)
+            self.split_child(self.root, 0);
         }
+        self.insert_internal(self.root, key, value);
+        None
     }
 
 
-    /// Deletes a key and value) from the B-tree if present.
+    /// Deletes a key and value) from the B+tree if present.
+    ///
+    /// Values live only in leaves, so the pair is removed from its leaf.
+    /// On the way down, each child about to be entered is topped up to at
+    /// least `t` entries first:
+    /// - borrowing from a sibling that can spare one, or
+    /// - merging with a sibling (and dropping the separator between them).
     ///
-    /// This follows the standard B-tree deletion algorithm:
-    /// - If the key is in a leaf node, it is removed directly.
-    /// - If the key is in an internal node:
-    ///   - Replace it with its predecessor or successor key, then delete recursively.
-    ///   - If necessary, borrow from a sibling or merge children to maintain B-tree properties.
+    /// A separator equal to the deleted key may stay behind: it still
+    /// divides the keys on either side.
     ///
     /// # Arguments
     /// * `key` - The key to be deleted, as a string slice.
     ///
     /// # Behavior
-    /// - Maintains the B-tree invariants after deletion.
+    /// - Maintains the B+tree invariants after deletion.
     /// - If the key does not exist, the tree is unchanged.
     ///
+    /// # Returns
+    /// The removed value, or `None` if the key was not in the tree.
+    ///
     /// # Example
     /// ```
     /// use kvstore::index::BTreeIndex;
     /// let mut index = BTreeIndex::new(2);
     /// index.insert("dog".into(), "bark".into());
-    /// index.delete("dog");
+    /// assert_eq!(index.delete("dog"), Some(b"bark".to_vec()));
     /// assert_eq!(index.search("dog"), None);
+    /// assert_eq!(index.delete("dog"), None);
     /// ```
-    pub fn delete(&mut self, key: &str) {
-        let t = self.t;
-
-        // Call inside delete - recurse - Use associative call - less borrow headaches
-        Self::delete_internal(&mut self.root, t, key);
+    pub fn delete(&mut self, key: &str) -> Option<Vec<u8>> {
+        // A miss restructures nothing and copies no shared node
+        if !self.contains_key(key) {
+            return None;
+        }
+        let removed = self.delete_internal(self.root, key);
 
         // If the root became empty and is internal - shrink height
-        if !self.root.is_leaf && self.root.kv_pairs.is_empty() {
-            self.root = self.root.children.remove(0);
+        let root = self.node(self.root);
+        if !root.is_leaf && root.separators.is_empty() {
+            let only_child = root.children[0];
+            let old = std::mem::replace(&mut self.root, only_child);
+            self.nodes.remove(old);
         }
+        Some(removed)
     }
 
 
-    /// Collect all keys stored in the B-tree in lexicographic order.
+    /// Number of keys in the tree.
+    ///
+    /// Every node counts the pairs below it as keys are inserted and
+    /// deleted, so this is the root's count, O(1).
     ///
-    /// Performs an **in-order traversal** of the tree to gather keys
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// tree.insert("dog".into(), "bark".into());
+    /// tree.insert("cat".into(), "meow".into());
+    /// tree.insert("dog".into(), "woof".into());
+    /// tree.delete("fish");
+    /// assert_eq!(tree.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.node(self.root).size
+    }
+
+
+    /// `true` if the tree holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+
+    /// Collect all keys stored in the B+tree in lexicographic order.
+    ///
+    /// Walks the leaf chain from the first leaf to the last to gather keys
     /// into the provided vector. This method is primarily used by
     /// range-based operations (e.g., `RANGE <start> <end>`), ensuring
     /// keys are returned in sorted order regardless of insertion order.
@@ -220,8 +670,7 @@ impl BTreeIndex {
     /// # Notes
     /// - Duplicates are not expected, as each key in the B-tree is unique.
     /// - The traversal runs in **O(n)** time, where *n* is the number of keys.
-    /// - Leaf and internal nodes are visited recursively, maintaining
-    ///   lexicographic ordering at every level.
+    /// - Only leaves are visited; they are linked in key order.
     ///
     /// # Example
     /// ```
@@ -250,99 +699,534 @@ impl BTreeIndex {
     /// assert!(keys.windows(2).all(|w| w[0] <= w[1]));
     /// ```
     pub fn collect_keys(&self, out: &mut Vec<String>) {
-        self.root.collect_keys(out);
+        // Unbounded, so the collation never compares anything
+        out.extend(self.range(Bound::Unbounded, Bound::Unbounded).map(|(key, _)| key.to_string()));
+    }
+
+
+    /// Iterate over the pairs between two bounds, in tree order.
+    ///
+    /// Bounds compare with the collation's folded order, so any key
+    /// (digits, dashes, colons, ...) is included when it falls in range.
+    /// One descent finds the leaf holding the first key in range; from
+    /// there the walk follows the leaf chain, and nothing is collected:
+    /// each pair is found as it is asked for.
+    ///
+    /// # Arguments
+    /// * `start` - Lowest key to yield, or `Unbounded`.
+    /// * `end`   - Highest key to yield, or `Unbounded`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::Bound;
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["a", "b", "c", "d", "e"] {
+    ///     tree.insert(key.into(), key.to_uppercase());
+    /// }
+    /// let pairs: Vec<_> = tree.range(Bound::Excluded("b"), Bound::Included("d")).collect();
+    /// assert_eq!(pairs, vec![("c", &b"C"[..]), ("d", &b"D"[..])]);
+    /// assert_eq!(tree.range(Bound::Unbounded, Bound::Excluded("c")).count(), 2);
+    /// ```
+    pub fn range<'a>(&'a self, start: Bound<&str>, end: Bound<&'a str>) -> Range<'a> {
+        Range::new(self, start, end, self.collation)
+    }
+
+
+    /// Iterate over the pairs between two bounds, largest key first.
+    ///
+    /// The same pairs as [`range`](Self::range) in the opposite order,
+    /// found from the end bound down the leaf chain's `prev` links rather
+    /// than collected and reversed.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::Bound;
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["a", "b", "c", "d", "e"] {
+    ///     tree.insert(key.into(), key.to_uppercase());
+    /// }
+    /// let keys: Vec<&str> = tree.range_rev(Bound::Included("b"), Bound::Excluded("e")).map(|(k, _)| k).collect();
+    /// assert_eq!(keys, vec!["d", "c", "b"]);
+    /// assert_eq!(tree.range_rev(Bound::Unbounded, Bound::Unbounded).next(), Some(("e", &b"E"[..])));
+    /// ```
+    pub fn range_rev<'a>(&'a self, start: Bound<&'a str>, end: Bound<&str>) -> RangeRev<'a> {
+        RangeRev::new(self, start, end, self.collation)
+    }
+
+
+    /// Number of pairs between two bounds: what [`range`](Self::range)
+    /// would yield, counted without visiting them.
+    ///
+    /// The keys below `start` and the keys not above `end` each form a
+    /// run from the first key, counted in one descent from the subtree
+    /// sizes, so this is O(t log n) however many keys are in range.
+    /// Bounds compare folded, as in `range`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::Bound;
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["a", "b", "c", "d", "e"] {
+    ///     tree.insert(key.into(), "v".into());
+    /// }
+    /// assert_eq!(tree.count_range(Bound::Excluded("b"), Bound::Included("d")), 2);
+    /// assert_eq!(tree.count_range(Bound::Included("bb"), Bound::Unbounded), 3);
+    /// assert_eq!(tree.count_range(Bound::Included("d"), Bound::Included("b")), 0);
+    /// ```
+    pub fn count_range(&self, start: Bound<&str>, end: Bound<&str>) -> usize {
+        let not_after_end = self.count_while(|key| !above(key, end, self.collation));
+        let before_start = self.count_while(|key| below(key, start, self.collation));
+        not_after_end.saturating_sub(before_start)
+    }
+
+
+    /// `true` if `key` falls between two bounds, compared folded as
+    /// [`range`](Self::range) compares them. The key need not be in the
+    /// tree.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::Bound;
+    /// use kvstore::{BTreeIndex, Collation};
+    /// let tree = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+    /// assert!(tree.in_range("B", Bound::Included("a"), Bound::Included("b")));
+    /// assert!(!tree.in_range("b", Bound::Excluded("B"), Bound::Unbounded));
+    /// ```
+    pub fn in_range(&self, key: &str, start: Bound<&str>, end: Bound<&str>) -> bool {
+        !below(key, start, self.collation) && !above(key, end, self.collation)
+    }
+
+
+    /// Number of keys, from the first, for which `pred` holds; `pred`
+    /// must hold for some first run of keys and for none after it.
+    ///
+    /// In each internal node, the children left of the first separator
+    /// failing `pred` hold only keys below it, wholly in the run, and are
+    /// added by size; the child just after them can straddle the end of
+    /// the run, so it is the one descended.
+    fn count_while(&self, pred: impl Fn(&str) -> bool) -> usize {
+        let mut node = self.node(self.root);
+        let mut count = 0;
+        while !node.is_leaf {
+            let i = node.separators.partition_point(|s| pred(s));
+            count += node.children[..i].iter().map(|&child| self.node(child).size).sum::<usize>();
+            node = self.node(node.children[i]);
+        }
+        count + node.kv_pairs.partition_point(|(key, _)| pred(key))
+    }
+
+
+    /// Iterate over the pairs whose keys start with `prefix`, in tree
+    /// order.
+    ///
+    /// Matching keys sit next to each other in the tree, so this is a
+    /// [`range`](Self::range) from `prefix` that stops at the first key
+    /// without it. The prefix is compared folded, like range bounds.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["user:2", "order:1", "user:1", "user", "users"] {
+    ///     tree.insert(key.into(), "v".into());
+    /// }
+    /// let keys: Vec<&str> = tree.scan_prefix("user:").map(|(k, _)| k).collect();
+    /// assert_eq!(keys, vec!["user:1", "user:2"]);
+    /// ```
+    pub fn scan_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, &'a [u8])> {
+        let collation = self.collation;
+        self.range(Bound::Included(prefix), Bound::Unbounded)
+            .take_while(move |(key, _)| collation.has_prefix(key, prefix))
+    }
+
+
+    /// The pair with the largest key at or below `key` (the floor).
+    ///
+    /// One descent from the root, like starting a
+    /// [`range_rev`](Self::range_rev) that ends at `key`; keys are
+    /// compared folded, like range bounds.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for (key, value) in [("t:0100", "a"), ("t:0200", "b"), ("t:0300", "c")] {
+    ///     tree.insert(key.into(), value.into());
+    /// }
+    /// assert_eq!(tree.get_le("t:0250"), Some(("t:0200", &b"b"[..])));
+    /// assert_eq!(tree.get_le("t:0200"), Some(("t:0200", &b"b"[..])));
+    /// assert_eq!(tree.get_le("t:0050"), None);
+    /// ```
+    pub fn get_le(&self, key: &str) -> Option<(&str, &[u8])> {
+        self.range_rev(Bound::Unbounded, Bound::Included(key)).next()
+    }
+
+
+    /// The pair with the smallest key at or above `key` (the ceiling).
+    ///
+    /// The mirror of [`get_le`](Self::get_le).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for (key, value) in [("t:0100", "a"), ("t:0200", "b"), ("t:0300", "c")] {
+    ///     tree.insert(key.into(), value.into());
+    /// }
+    /// assert_eq!(tree.get_ge("t:0250"), Some(("t:0300", &b"c"[..])));
+    /// assert_eq!(tree.get_ge("t:0300"), Some(("t:0300", &b"c"[..])));
+    /// assert_eq!(tree.get_ge("t:0350"), None);
+    /// ```
+    pub fn get_ge(&self, key: &str) -> Option<(&str, &[u8])> {
+        self.range(Bound::Included(key), Bound::Unbounded).next()
+    }
+
+
+    /// The pair with the smallest key, or `None` if the tree is empty.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// assert_eq!(tree.first(), None);
+    /// tree.insert("b".into(), "2".into());
+    /// tree.insert("a".into(), "1".into());
+    /// assert_eq!(tree.first(), Some(("a", &b"1"[..])));
+    /// ```
+    pub fn first(&self) -> Option<(&str, &[u8])> {
+        self.range(Bound::Unbounded, Bound::Unbounded).next()
+    }
+
+
+    /// The pair with the largest key, or `None` if the tree is empty.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// tree.insert("b".into(), "2".into());
+    /// tree.insert("a".into(), "1".into());
+    /// assert_eq!(tree.last(), Some(("b", &b"2"[..])));
+    /// ```
+    pub fn last(&self) -> Option<(&str, &[u8])> {
+        self.range_rev(Bound::Unbounded, Bound::Unbounded).next()
+    }
+
+
+    /// Number of keys in the tree that sort before `key`, whether or not
+    /// `key` itself is there: its position if it is.
+    ///
+    /// Each internal node on the path down adds the
+    /// [`size`](BTreeNode::size) of the subtrees left of the branch taken,
+    /// and the leaf the pairs before the key, so this is one descent,
+    /// O(t log n).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["b", "d", "f", "h"] {
+    ///     tree.insert(key.into(), "v".into());
+    /// }
+    /// assert_eq!(tree.rank("d"), 1);
+    /// assert_eq!(tree.rank("e"), 2);
+    /// assert_eq!(tree.rank("a"), 0);
+    /// assert_eq!(tree.rank("z"), 4);
+    /// ```
+    pub fn rank(&self, key: &str) -> usize {
+        let mut node = self.node(self.root);
+        let mut rank = 0;
+        while !node.is_leaf {
+            let idx = node.child_index_by(key, self.collation);
+            rank += node.children[..idx].iter().map(|&child| self.node(child).size).sum::<usize>();
+            node = self.node(node.children[idx]);
+        }
+        rank + node.lower_bound_by(key, self.collation)
+    }
+
+
+    /// The pair at position `n` (from 0) in key order, or `None` if the
+    /// tree holds `n` keys or fewer.
+    ///
+    /// The inverse of [`rank`](Self::rank): subtree sizes say which
+    /// branch holds it, so this is one descent, O(t log n).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for (i, key) in ["b", "d", "f", "h"].into_iter().enumerate() {
+    ///     tree.insert(key.into(), i.to_string());
+    /// }
+    /// assert_eq!(tree.select(2), Some(("f", &b"2"[..])));
+    /// assert_eq!(tree.select(4), None);
+    /// ```
+    pub fn select(&self, mut n: usize) -> Option<(&str, &[u8])> {
+        if n >= self.len() {
+            return None;
+        }
+        let mut node = self.node(self.root);
+        'descend: while !node.is_leaf {
+            for &child in &node.children {
+                let child = self.node(child);
+                if n < child.size {
+                    node = child;
+                    continue 'descend;
+                }
+                n -= child.size;
+            }
+            return None;
+        }
+        node.kv_pairs.get(n).map(|(key, value)| (key.as_str(), value.as_slice()))
+    }
+
+
+    /// Collect the keys between two inclusive bounds, in tree order.
+    ///
+    /// The same walk as [`range`](Self::range), for callers that want
+    /// owned keys.
+    ///
+    /// # Arguments
+    /// * `start` - Lowest key to include, or `None` for no lower bound.
+    /// * `end`   - Highest key to include, or `None` for no upper bound.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// for key in ["user:1", "user:2", "user:10", "order-7", "a"] {
+    ///     tree.insert(key.into(), "v".into());
+    /// }
+    /// assert_eq!(tree.range_keys(Some("user:"), Some("user:2")), vec!["user:1", "user:10", "user:2"]);
+    /// assert_eq!(tree.range_keys(None, Some("b")), vec!["a"]);
+    /// ```
+    pub fn range_keys(&self, start: Option<&str>, end: Option<&str>) -> Vec<String> {
+        let start = start.map_or(Bound::Unbounded, Bound::Included);
+        let end = end.map_or(Bound::Unbounded, Bound::Included);
+        self.range(start, end).map(|(key, _)| key.to_string()).collect()
     }
 
 
     /// Dumps tree state information for degugging in tests.
     pub fn debug_dump(&self) {
-        fn dump(node: &BTreeNode, depth: usize) {
+        fn dump(tree: &BTreeIndex, id: NodeId, depth: usize) {
             let indent = "  ".repeat(depth);
+            let node = tree.node(id);
             for (k, v) in &node.kv_pairs {
-                println!("{}KEY={} VAL={}", indent, k, v);
+                println!("{}KEY={} VAL={}", indent, k, String::from_utf8_lossy(v));
             }
-            for child in &node.children {
-                dump(child, depth + 1);
+            for (i, &child) in node.children.iter().enumerate() {
+                if i > 0 {
+                    println!("{}SEP={}", indent, node.separators[i - 1]);
+                }
+                dump(tree, child, depth + 1);
             }
         }
-        dump(&self.root, 0);
+        dump(self, self.root, 0);
     }
 
 
-    /// Mutable search for a key in the B-tree.
+    /// Check the invariants of the tree, for tests and debugging.
     ///
-    /// Traverses the tree recursively to locate the target key and returns
-    /// a mutable reference to its associated value, allowing in-place updates.
-    ///
-    /// # Arguments
-    /// * `key` - The key to locate in the index.
+    /// Walks every node and checks that:
+    /// - keys are in strict collation order, within each node, and every
+    ///   key below a separator sorts before it while every key after it
+    ///   sorts at or after it;
+    /// - every node but the root holds `t - 1` to `2t - 1` pairs or
+    ///   separators (at least one, with no upper count, in byte-budgeted
+    ///   trees), and the root no more than that;
+    /// - internal nodes have one more child than separators, and every
+    ///   leaf is at the same depth;
+    /// - every node's [`size`](BTreeNode::size) counts the pairs below it;
+    /// - the leaves are linked in key order both ways, from a first leaf
+    ///   with no `prev` to a last with no `next`, and the node table holds
+    ///   no node outside the tree.
     ///
     /// # Returns
-    /// * `Some(&mut String)` if the key exists, providing mutable access
-    ///   to the value for modification.
-    /// * `None` if the key is not found anywhere in the tree.
-    ///
-    /// # Notes
-    /// - This function is primarily used by [`insert()`] to overwrite
-    ///   existing keys before performing a new insertion.
-    /// - Runtime complexity is **O(log n)** in a balanced B-tree.
+    /// `Err(message)` describing the first violation found, with the path
+    /// of child indexes from the root to the node (`root.1.0`).
     ///
     /// # Example
     /// ```
     /// use kvstore::BTreeIndex;
     /// let mut tree = BTreeIndex::new(2);
-    /// tree.insert("dog".into(), "bark".into());
-    ///
-    /// if let Some(val) = tree.search_mut("dog") {
-    ///     *val = "woof".into();
+    /// for i in 0..50 {
+    ///     tree.insert(format!("k{:02}", i), "v".into());
     /// }
+    /// assert_eq!(tree.check_invariants(), Ok(()));
     ///
-    /// assert_eq!(tree.search("dog"), Some("woof"));
+    /// let first = tree.node(tree.root).children[0];
+    /// tree.node_mut(first).separators.push("zzz".into());
+    /// assert!(tree.check_invariants().unwrap_err().starts_with("root.0: separator \"zzz\""));
     /// ```
-    pub fn search_mut(&mut self, key: &str) -> Option<&mut String> {
-        fn search_node<'a>(node: &'a mut BTreeNode, key: &str) -> Option<&'a mut String> {
-            let idx = node.lower_bound(key);
+    pub fn check_invariants(&self) -> Result<(), String> {
+        struct Walk<'a> {
+            tree: &'a BTreeIndex,
+            t: usize,
+            budget: bool,
+            collation: Collation,
+            leaf_depth: Option<usize>,
+
+            /// Pairs seen so far, in walk order.
+            pairs: usize,
+
+            /// Nodes seen so far.
+            nodes: usize,
+
+            /// Leaves in walk order, with their paths.
+            leaves: Vec<(NodeId, String)>,
+        }
 
-            if idx < node.kv_pairs.len() && node.kv_pairs[idx].0 == key {
-                return Some(&mut node.kv_pairs[idx].1);
+        fn check(w: &mut Walk, id: NodeId, path: &str, depth: usize, lower: Option<&str>, upper: Option<&str>) -> Result<(), String> {
+            let Some(node) = w.tree.nodes.get(id) else {
+                return Err(format!("{}: node {} is not in the node table", path, id));
+            };
+            w.nodes += 1;
+            let n = node.key_count();
+            let root = depth == 0;
+            let (min, max) = match (root, w.budget) {
+                (true, true) => (0, usize::MAX),
+                (true, false) => (0, 2 * w.t - 1),
+                (false, true) => (1, usize::MAX),
+                (false, false) => (w.t - 1, 2 * w.t - 1),
+            };
+            let what = if node.is_leaf { "pairs" } else { "separators" };
+            if n < min || n > max {
+                return Err(format!("{}: holds {} {}, outside {}..={}", path, n, what, min, max));
+            }
+            if root && !node.is_leaf && n == 0 {
+                return Err(format!("{}: internal root holds no separators", path));
+            }
+            if node.is_leaf && !node.separators.is_empty() || !node.is_leaf && !node.kv_pairs.is_empty() {
+                return Err(format!("{}: {} holds {}", path, if node.is_leaf { "leaf" } else { "internal node" }, if node.is_leaf { "separators" } else { "pairs" }));
             }
 
+            // Keys (separators) are in order, at or after (after) the bound on the left
+            let keys: Vec<&str> = match node.is_leaf {
+                true => node.kv_pairs.iter().map(|(key, _)| key.as_str()).collect(),
+                false => node.separators.iter().map(String::as_str).collect(),
+            };
+            let name = if node.is_leaf { "key" } else { "separator" };
+            for (i, key) in keys.iter().enumerate() {
+                let (prev, may_equal) = match i {
+                    0 => (lower, node.is_leaf),
+                    _ => (Some(keys[i - 1]), false),
+                };
+                if let Some(prev) = prev {
+                    let order = w.collation.compare(prev, key);
+                    if order.is_gt() || order.is_eq() && !may_equal {
+                        return Err(format!("{}: {} {:?} is not after {:?}", path, name, key, prev));
+                    }
+                }
+            }
+            if let (Some(last), Some(upper)) = (keys.last(), upper)
+                && !w.collation.compare(last, upper).is_lt()
+            {
+                return Err(format!("{}: {} {:?} is not before {:?}", path, name, last, upper));
+            }
+            let before = w.pairs;
+
             if node.is_leaf {
-                None
+                w.pairs += n;
+                if !node.children.is_empty() {
+                    return Err(format!("{}: leaf has {} children", path, node.children.len()));
+                }
+                match w.leaf_depth {
+                    Some(d) if d != depth => return Err(format!("{}: leaf at depth {}, others at {}", path, depth, d)),
+                    _ => w.leaf_depth = Some(depth),
+                }
+                w.leaves.push((id, path.to_string()));
             } else {
-                search_node(&mut node.children[idx], key)
+                if node.children.len() != n + 1 {
+                    return Err(format!("{}: {} separators but {} children", path, n, node.children.len()));
+                }
+                for (i, &child) in node.children.iter().enumerate() {
+                    let lower = if i == 0 { lower } else { Some(keys[i - 1]) };
+                    let upper = if i == n { upper } else { Some(keys[i]) };
+                    check(w, child, &format!("{}.{}", path, i), depth + 1, lower, upper)?;
+                }
             }
-        }
-
-        search_node(&mut self.root, key)
-    }
-
 
-    /// Needed to eliminate tree insert duplications in recurcive function.
-    pub fn deduplicate(&mut self) {
-        use std::collections::BTreeMap;
-        let mut unique = BTreeMap::new();
+            if node.size != w.pairs - before {
+                return Err(format!("{}: size is {} but {} pairs are below it", path, node.size, w.pairs - before));
+            }
+            Ok(())
+        }
 
-        // Collect all (key, value) pairs from the tree (depth-first)
-        fn collect(node: &BTreeNode, map: &mut BTreeMap<String, String>) {
-            for (k, v) in &node.kv_pairs {
-                // Last write wins
-                map.insert(k.clone(), v.clone());
+        let mut walk = Walk {
+            tree: self,
+            t: self.t,
+            budget: self.node_bytes.is_some(),
+            collation: self.collation,
+            leaf_depth: None,
+            pairs: 0,
+            nodes: 0,
+            leaves: Vec::new(),
+        };
+        check(&mut walk, self.root, "root", 0, None, None)?;
+
+        // The chain links each leaf to the ones beside it in the walk
+        for (i, (id, path)) in walk.leaves.iter().enumerate() {
+            let leaf = self.node(*id);
+            let prev = i.checked_sub(1).map(|i| walk.leaves[i].0);
+            let next = walk.leaves.get(i + 1).map(|(id, _)| *id);
+            if leaf.prev != prev {
+                return Err(format!("{}: prev links to {:?}, but the leaf before is {:?}", path, leaf.prev, prev));
             }
-            for child in &node.children {
-                collect(child, map);
+            if leaf.next != next {
+                return Err(format!("{}: next links to {:?}, but the leaf after is {:?}", path, leaf.next, next));
             }
         }
+        if walk.nodes != self.nodes.len() {
+            return Err(format!("root: the node table holds {} nodes but {} are in the tree", self.nodes.len(), walk.nodes));
+        }
+        Ok(())
+    }
 
-        collect(&self.root, &mut unique);
-
-        // Clear the entire tree structure
-        self.root = Box::new(BTreeNode::new(true));
 
-        // Reinsert sorted unique pairs to rebuild clean structure
-        for (k, v) in unique {
-            self.insert(k, v);
+    /// Mutable search for a key in the B+tree.
+    ///
+    /// Finds the key's leaf and returns a mutable reference to its
+    /// associated value, allowing in-place updates.
+    ///
+    /// # Arguments
+    /// * `key` - The key to locate in the index.
+    ///
+    /// # Returns
+    /// * `Some(&mut Vec<u8>)` if the key exists, providing mutable access
+    ///   to the value bytes for modification.
+    /// * `None` if the key is not found anywhere in the tree.
+    ///
+    /// # Notes
+    /// - This function is primarily used by [`insert_bytes()`](Self::insert_bytes) to overwrite
+    ///   existing keys before performing a new insertion.
+    /// - Runtime complexity is **O(log n)** in a balanced B-tree.
+    /// - Only the leaf is copied if a snapshot shares it; its parents name
+    ///   it by id, so they stay as they are.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::BTreeIndex;
+    /// let mut tree = BTreeIndex::new(2);
+    /// tree.insert("dog".into(), "bark".into());
+    ///
+    /// if let Some(val) = tree.get_bytes_mut("dog") {
+    ///     *val = b"woof".to_vec();
+    /// }
+    ///
+    /// assert_eq!(tree.search("dog"), Some("woof"));
+    /// ```
+    pub fn get_bytes_mut(&mut self, key: &str) -> Option<&mut Vec<u8>> {
+        // Find the key read-only first, so a miss copies no shared node
+        let id = self.find_leaf(key);
+        let at = self.node(id).lower_bound_by(key, self.collation);
+        if self.node(id).kv_pairs.get(at).is_none_or(|(k, _)| k != key) {
+            return None;
         }
+        Some(&mut self.node_mut(id).kv_pairs[at].1)
     }
 
 
@@ -350,125 +1234,140 @@ impl BTreeIndex {
     // Insertion helpers
     // =========================
 
-    /// Inserts a key-value pair into the subtree rooted at `node`.
+    /// Inserts a key-value pair into the subtree rooted at node `id`.
     ///
     /// This function handles both the base case (insertion into a leaf node)
-    /// and the recursive case (descent into an internal node). If the key
-    /// already exists at the current level, its value is updated to satisfy
-    /// the "last write wins" requirement.
+    /// and the recursive case (descent into an internal node). The key
+    /// must not be in the tree yet: `insert_bytes` overwrites existing
+    /// keys in place before it gets here.
     ///
     /// # Arguments
-    /// * `node`  - Mutable reference to the current subtree root.
+    /// * `id`    - Id of the current subtree root.
     /// * `key`   - The key to insert (String).
-    /// * `value` - The value to associate with the key (String).
+    /// * `value` - The value bytes to associate with the key.
     ///
     /// # Behavior
-    /// - **Leaf node**:
-    ///   - If the key exists, overwrite its value.
-    ///   - Otherwise, insert `(key, value)` at the correct sorted position.
+    /// - **Leaf node**: insert `(key, value)` at the correct sorted position.
     /// - **Internal node**:
-    ///   - If the key exists, overwrite its value.
-    ///   - Otherwise, split a full child before descending to valid space,
-    ///     then recurse into the correct child.
+    ///   - Split a full child before descending to valid space, then
+    ///     recurse into the child whose keys would include `key`.
     ///
     /// # Notes
     /// - Uses `lower_bound` to maintain sorted order of keys.
     /// - Checks that no child is full before recursion.
-    /// - Does not return a value; modifies the tree in place.
-    ///
-    /// # Call outs
-    /// Will call out if there is a violation like attempting to split a
-    /// non-full child. Should not happend if properly working.
-    fn insert_internal(node: &mut BTreeNode, t: usize, key: String, value: String) {
-        // Find first position where key could go based on ordering
-        let mut idx = node.lower_bound(&key);
+    /// - Counts the new pair in the `size` of every node on the way down,
+    ///   so each node is written once.
+    fn insert_internal(&mut self, id: NodeId, key: String, value: Vec<u8>) {
+        let c = self.collation;
+        let node = self.node_mut(id);
+        node.size += 1;
 
         // Case 1: Leaf node
         if node.is_leaf {
-            // If exact key found at idx, overwrite
-            if idx < node.kv_pairs.len() && node.kv_pairs[idx].0 == key {
-                node.kv_pairs[idx].1 = value;
-                return;
-            }
-
-            // Defensive: if lower_bound implementation returned the slot
-            // *after* an equal key, also treat that as overwrite
-            if idx > 0 && node.kv_pairs[idx - 1].0 == key {
-                node.kv_pairs[idx - 1].1 = value;
-                return;
-            }
-
-            // Otherwise, insert new key at computed position
+            let idx = node.lower_bound_by(&key, c);
             node.kv_pairs.insert(idx, (key, value));
             return;
         }
 
-        // Case 2: Internal node - check for existing key in this node
-        if idx < node.kv_pairs.len() && node.kv_pairs[idx].0 == key {
-            node.kv_pairs[idx].1 = value;
-            return;
-        }
+        // Case 2: Descend into child; split if needed
+        let mut idx = node.child_index_by(&key, c);
+        let mut child = node.children[idx];
+        if self.is_full(child) {
+            self.split_child(id, idx);
 
-        if idx > 0 && node.kv_pairs[idx - 1].0 == key {
-            node.kv_pairs[idx - 1].1 = value;
-            return;
-        }
-
-        // Case 3: Descend into child; split if needed
-        if node.children[idx].kv_pairs.len() == 2 * t - 1 {
-            // Child full: split it
-            Self::split_child(node, t, idx);
-
-            // After split, decide which side to follow or overwrite pivot
-            if key > node.kv_pairs[idx].0 {
+            // After split, keys at or above the new separator go right
+            let node = self.node(id);
+            if c.compare(&key, &node.separators[idx]).is_ge() {
                 idx += 1;
-            } else if key == node.kv_pairs[idx].0 {
-                node.kv_pairs[idx].1 = value;
-                return;
             }
+            child = node.children[idx];
         }
 
         // Recurse into selected child
-        Self::insert_internal(&mut node.children[idx], t, key, value);
+        self.insert_internal(child, key, value);
+    }
+
+
+    /// Returns `true` if node `id` must be split before inserting below it.
+    ///
+    /// Count-sized trees: `2t - 1` pairs (separators, in an internal
+    /// node). Byte-budgeted trees: the node's data reached the budget and
+    /// it has enough entries to split in two.
+    fn is_full(&self, id: NodeId) -> bool {
+        let node = self.node(id);
+        match self.node_bytes {
+            None => node.key_count() == 2 * self.t - 1,
+            Some(bytes) => node.key_count() >= 3 && node.byte_size() >= bytes,
+        }
     }
 
 
     /// Split a full child node during insertion.
     ///
-    /// When a child at `node.children[i]` contains the maximum number of keys
-    /// (`2t - 1`), this function splits it into two nodes and bumps the middle
-    /// key into the parent. Check that there is no node overflows and maintains
-    /// B-tree balance.
+    /// When the child at `children[i]` of node `parent` is full (`2t - 1`
+    /// entries, or over the byte budget), this function splits it into two
+    /// nodes and adds a separator between them to the parent. Check that
+    /// there is no node overflows and maintains B+tree balance.
     ///
     /// # Arguments
-    /// * `node` - The parent node containing the full child.
-    /// * `i`    - The index of the full child to split.
+    /// * `parent` - Id of the node containing the full child.
+    /// * `i`      - The index of the full child to split.
     ///
     /// # Behavior
-    /// - The left child keeps the first `t - 1` keys.
-    /// - The right child receives the last `t - 1` keys.
-    /// - The median key is moved up into the parent at position `i`.
-    /// - If the full child is an internal node, its children are split as well.
+    /// - **Leaf**: the right half of the pairs moves to a new leaf linked
+    ///   in after it, and a copy of the new leaf's first key becomes the
+    ///   separator. Every value stays in a leaf.
+    /// - **Internal node**: the separators after the median move right
+    ///   with their children, and the median moves up into the parent.
     ///
     /// # Call outs
-    /// Will call out when called on a child that is not actually full.
-    fn split_child(node: &mut BTreeNode, t: usize, i: usize) {
-        // We are here because child node is full
-        let full_child = &mut node.children[i];
-        let mut right = Box::new(BTreeNode::new(full_child.is_leaf));
-
-        // Right node gets t-1 largest kv_pairs
-        right.kv_pairs = full_child.kv_pairs.split_off(t);
-        // Grab  the middle node
-        let middle = full_child.kv_pairs.pop().expect("full child must have middle");
-
-        // If internal, split children too: left keeps [0..t), right takes [t..]
-        if !full_child.is_leaf {
-            right.children = full_child.children.split_off(t);
+    /// A child with fewer than two entries can't be split in two, so it is
+    /// left alone.
+    fn split_child(&mut self, parent: NodeId, i: usize) {
+        let child_id = self.node(parent).children[i];
+        let child = self.node_mut(child_id);
+        if child.key_count() < 2 {
+            return;
+        }
+        let mid = child.key_count() / 2;
+        let mut right = BTreeNode::new(child.is_leaf);
+
+        let separator = if child.is_leaf {
+            // Right leaf gets the pairs from the median on, and its first key goes up
+            right.kv_pairs = child.kv_pairs.drain(mid..).collect();
+            right.size = right.kv_pairs.len();
+            right.prev = Some(child_id);
+            right.next = child.next;
+            right.kv_pairs[0].0.clone()
+        } else {
+            // Right node gets the separators after the median, and their children
+            right.separators = child.separators.drain(mid + 1..).collect();
+            right.children = child.children.drain(mid + 1..).collect();
+            let Some(middle) = child.separators.pop() else {
+                return;
+            };
+            middle
+        };
+        if !right.is_leaf {
+            right.size = right.children.iter().map(|&c| self.node(c).size).sum();
         }
-        // Insert middle into parent and link new right child
-        node.kv_pairs.insert(i, middle);
-        node.children.insert(i + 1, right);
+        let (right_size, right_next) = (right.size, right.next);
+        let right_id = self.add_node(right);
+
+        let child = self.node_mut(child_id);
+        child.size -= right_size;
+        if child.is_leaf {
+            // Link the new leaf in between the child and the leaf after it
+            child.next = Some(right_id);
+            if let Some(next) = right_next {
+                self.node_mut(next).prev = Some(right_id);
+            }
+        }
+
+        // Insert the separator into the parent and link the new right child
+        let parent = self.node_mut(parent);
+        parent.separators.insert(i, separator);
+        parent.children.insert(i + 1, right_id);
     }
 
 
@@ -476,242 +1375,222 @@ impl BTreeIndex {
     // Deletion helpers
     // =========================
 
-    /// Recursive helper for deleting a key from a B-tree node.
+    /// Recursive helper for deleting a key from a B+tree node.
     ///
     /// # Arguments
-    /// * `node` - A mutable reference to the current B-tree node being examined.
-    /// * `t` - The minimum degree of the B-tree (controls branching factor).
+    /// * `id` - Id of the current B+tree node being examined.
     /// * `key` - The key to delete.
     ///
     /// # Behavior
-    /// This function implements the standard B-tree deletion algorithm:
-    ///
-    /// 1. **Key found in this node**
-    ///    - If the node is a leaf: remove the key directly.
-    ///    - If the node is internal:
-    ///       * Replace with predecessor if left child has ≥ `t` keys.
-    ///       * Replace with successor if right child has ≥ `t` keys.
-    ///       * Otherwise merge the two children and recurse into the merged node.
-    ///
-    /// 2. **Key not found in this node**
-    ///    - If the node is a leaf: the key is not present, nothing is done.
-    ///    - If the node is internal:
-    ///       * Checks if the child about to be descended into has ≥ `t` keys
-    ///         (borrowing/merging if needed).
-    ///       * Recurse into the correct child to continue searching.
+    /// This function implements B+tree deletion:
+    ///
+    /// 1. **Leaf node**: remove the pair.
+    ///
+    /// 2. **Internal node**
+    ///    - Checks if the child about to be descended into has ≥ `t`
+    ///      entries (borrowing/merging if needed).
+    ///    - Recurse into the correct child to continue searching.
     ///
     /// # Notes
-    /// * The `t` parameter helps check that all nodes (except root)
+    /// * The key must be in the tree: `delete` checks before it calls this.
+    /// * The `t` degree helps check that all nodes (except root)
     ///   maintain the minimum space property of a B-tree.
-    /// * This function assumes helper functions (`max_kvs`, `min_kvs`,
-    ///   `merge_children`, `check_min_kvs) handle the details of
-    ///   maintaining balance and invariants.
+    /// * This function assumes helper functions (`merge_children`,
+    ///   `check_min_kvs`, `borrow_from_prev`, `borrow_from_next`) handle
+    ///   the details of maintaining balance and invariants.
     /// * Used internally by `delete` to perform the actual recursive traversal.
-    fn delete_internal(node: &mut BTreeNode, t: usize, key: &str) {
-        let idx = node.lower_bound(key);
-
-        // First case - key is in this node
-        if idx < node.kv_pairs.len() && node.kv_pairs[idx].0 == key {
-            if node.is_leaf {
-                // Leaf node - just remove
-                node.kv_pairs.remove(idx);
-
-            } else {
-                // Internal node
-                if node.children[idx].kv_pairs.len() >= t {
-                    // Replace with predecessor
-                    let (pred_k, pred_v) = Self::max_kvs(&mut node.children[idx]);
-                    node.kv_pairs[idx] = (pred_k.clone(), pred_v.clone());
-                    Self::delete_internal(&mut node.children[idx], t, &pred_k);
-
-                } else if node.children[idx + 1].kv_pairs.len() >= t {
-                    // Replace with successor
-                    let (succ_k, succ_v) = Self::min_kvs(&mut node.children[idx + 1]);
-                    node.kv_pairs[idx] = (succ_k.clone(), succ_v.clone());
-                    Self::delete_internal(&mut node.children[idx + 1], t, &succ_k);
-
-                } else {
-                    // Merge children[idx] + key + children[idx+1], then recurse
-                    Self::merge_children(node, idx);
-                    Self::delete_internal(&mut node.children[idx], t, key);
-                }
-            }
-            return;
-        }
-
-        // Next case - key is not in this node - no op
+    /// * Returns the value removed, after uncounting it from the `size`
+    ///   of every node on the way down.
+    fn delete_internal(&mut self, id: NodeId, key: &str) -> Vec<u8> {
+        let c = self.collation;
+        let node = self.node_mut(id);
+        node.size -= 1;
         if node.is_leaf {
-            return;
+            let idx = node.lower_bound_by(key, c);
+            debug_assert_eq!(node.kv_pairs[idx].0, key);
+            return node.kv_pairs.remove(idx).1;
         }
 
-        // Check child[idx] has at least t kv_pairs before descending
-        Self::check_min_kvs(node, t, idx);
-
-        // Descend (idx might shift after borrow/merge - watch for it)
-        let next_idx = idx.min(node.kv_pairs.len());
-        Self::delete_internal(&mut node.children[next_idx], t, key);
+        // Check the child has at least t entries before descending
+        let idx = node.child_index_by(key, c);
+        let idx = self.check_min_kvs(id, idx);
+        let child = self.node(id).children[idx];
+        self.delete_internal(child, key)
     }
 
 
-    /// Checks that the child at index `idx` has at least `t` kv_pairs before descending.
+    /// Checks that child `idx` of node `parent` has at least `t` entries
+    /// before descending.
     ///
     /// # Arguments
-    /// * `node` - The parent node containing the child at index `idx`.
+    /// * `parent` - Id of the node holding the child at index `idx`.
     /// * `idx` - The index of the child to check.
     ///
     /// # Behavior
-    /// - If the child already has ≥ `t` kv_pairs, nothing is done.
+    /// - If the child already has ≥ `t` entries, nothing is done.
     /// - Otherwise:
-    ///   * Try borrowing a key from the left sibling (if it exists and has ≥ `t` kv_pairs).
+    ///   * Try borrowing one from the left sibling (if it exists and has ≥ `t`).
     ///   * Else try borrowing from the right sibling.
     ///   * If neither sibling can donate, merge the child with one of its siblings.
-    fn check_min_kvs(node: &mut BTreeNode, t: usize, idx: usize) {
+    ///
+    /// # Returns
+    /// The index the child's keys are at now: `idx - 1` if it was merged
+    /// into its left sibling, `idx` otherwise.
+    fn check_min_kvs(&mut self, parent: NodeId, idx: usize) -> usize {
+        let t = self.t;
+        let children = self.node(parent).children.clone();
+        let count = |i: usize| self.node(children[i]).key_count();
 
-        // If child already has enough kv_pairs, nothing to do
-        if node.children[idx].kv_pairs.len() >= t {
-            return;
+        // If child already has enough entries, nothing to do
+        if count(idx) >= t {
+            return idx;
         }
 
         // Try to borrow from left sibling
-        if idx > 0 && node.children[idx - 1].kv_pairs.len() >= t {
-            Self::borrow_from_prev(node, idx);
+        if idx > 0 && count(idx - 1) >= t {
+            self.borrow_from_prev(parent, idx);
+            idx
         }
         // Else try to borrow from right sibling
-        else if idx + 1 < node.children.len() && node.children[idx + 1].kv_pairs.len() >= t {
-            Self::borrow_from_next(node, idx);
+        else if idx + 1 < children.len() && count(idx + 1) >= t {
+            self.borrow_from_next(parent, idx);
+            idx
         }
         // Else merge with a sibling
-        else {
-            if idx + 1 < node.children.len() {
-                Self::merge_children(node, idx);
-            } else {
-                Self::merge_children(node, idx - 1);
-            }
+        else if idx + 1 < children.len() {
+            self.merge_children(parent, idx);
+            idx
+        } else {
+            self.merge_children(parent, idx - 1);
+            idx - 1
         }
     }
 
 
-    /// Borrows a kv_pair from the left sibling of `node.children[idx]`.
+    /// Borrows an entry from the left sibling of child `idx` of `parent`.
     ///
     /// # Arguments
-    /// * `node` - The parent node that holds the key separating the two siblings.
-    /// * `idx` - The index of the child that is underflowing (has < t kv_pairs).
+    /// * `parent` - Id of the node holding the separator between the two siblings.
+    /// * `idx` - The index of the child that is underflowing (has < t entries).
     ///
     /// # Behavior
-    /// - Takes the separator key from the parent (`node.kv_pairs[idx - 1]`)
-    ///   and inserts it as the first key of the underflowing child.
-    /// - Moves the last key from the left sibling up into the parent
-    ///   (replacing the separator).
-    /// - If the nodes are internal:
-    ///   * Moves the last child pointer of the left sibling into the beginning
-    ///     of the underflowing child’s children.
+    /// - **Leaves**: the left sibling's last pair moves to the front of
+    ///   the child, and its key becomes the separator between them.
+    /// - **Internal nodes**: the separator comes down to the front of the
+    ///   child with the left sibling's last child, and the left sibling's
+    ///   last separator goes up to replace it.
     ///
-    /// This maintains the B-tree invariants during deletion by redistributing
-    /// kv_pairs so that the underflowing child regains at least `t` keys.
-    fn borrow_from_prev(node: &mut BTreeNode, idx: usize) {
-        // Child idx borrows one kv_pair from child idx-1 via parent
-        let (left_slice, right_slice) = node.children.split_at_mut(idx);
-        let left = &mut left_slice[idx - 1];
-        let child = &mut right_slice[0];
-
-        // Move parent kv_pair down to child (as first)
-        let parent_kvs = node.kv_pairs[idx - 1].clone();
-        child.kv_pairs.insert(0, parent_kvs);
-
-        // Move left's last kv_pair up to parent
-        let left_last = left.kv_pairs.pop().expect("left sibling has kv_pairs");
-        node.kv_pairs[idx - 1] = left_last;
-
-        // If internal, move a child pointer
-        if !left.is_leaf {
-            let moved = left.children.pop().expect("left child has a child to move");
-            child.children.insert(0, moved);
+    /// This maintains the B+tree invariants during deletion by
+    /// redistributing entries so that the underflowing child regains at
+    /// least `t`.
+    fn borrow_from_prev(&mut self, parent: NodeId, idx: usize) {
+        let (left_id, child_id) = (self.node(parent).children[idx - 1], self.node(parent).children[idx]);
+        if self.node(child_id).is_leaf {
+            let left = self.node_mut(left_id);
+            // An empty sibling has nothing to lend
+            let Some(pair) = left.kv_pairs.pop() else {
+                return;
+            };
+            left.size -= 1;
+            self.node_mut(parent).separators[idx - 1] = pair.0.clone();
+            let child = self.node_mut(child_id);
+            child.kv_pairs.insert(0, pair);
+            child.size += 1;
+            return;
         }
+
+        let left = self.node_mut(left_id);
+        let (Some(up), Some(moved)) = (left.separators.pop(), left.children.pop()) else {
+            return;
+        };
+        let moved_size = self.node(moved).size;
+        self.node_mut(left_id).size -= moved_size;
+        let down = std::mem::replace(&mut self.node_mut(parent).separators[idx - 1], up);
+        let child = self.node_mut(child_id);
+        child.separators.insert(0, down);
+        child.children.insert(0, moved);
+        child.size += moved_size;
     }
 
 
-    /// Borrows a kv_pair from the right sibling of `node.children[idx]`.
+    /// Borrows an entry from the right sibling of child `idx` of `parent`.
     ///
     /// # Arguments
-    /// * `node` - The parent node that holds the key separating the two siblings.
-    /// * `idx` - The index of the child that is underflowing (has < t kv_pairs).
+    /// * `parent` - Id of the node holding the separator between the two siblings.
+    /// * `idx` - The index of the child that is underflowing (has < t entries).
     ///
     /// # Behavior
-    /// - Takes the separator key from the parent (`node.kv_pairs[idx + 1]`)
-    ///   and inserts it as the first key of the underflowing child.
-    /// - Moves the last key from the right sibling up into the parent
-    ///   (replacing the separator).
-    /// - If the nodes are internal:
-    ///   * Moves the last child pointer of the right sibling into the beginning
-    ///     of the underflowing child’s children.
-    ///
-    /// This maintains the B-tree invariants during deletion by redistributing
-    /// kv_pairs so that the underflowing child regains at least `t` keys.
-    fn borrow_from_next(node: &mut BTreeNode, idx: usize) {
-        // Child idx borrows one kv_pair from child idx+1 via parent
-        let (left_slice, right_slice) = node.children.split_at_mut(idx + 1);
-        let right = &mut right_slice[0];
-        let child = &mut left_slice[idx];
-
-        // Move parent kv_pair down to child (as last)
-        let parent_kvs = node.kv_pairs[idx].clone();
-        child.kv_pairs.push(parent_kvs);
-
-        // Move right's first kv_pair up to parent
-        let right_first = right.kv_pairs.remove(0);
-        node.kv_pairs[idx] = right_first;
-
-        // If internal, move a child pointer
-        if !right.is_leaf {
-            let moved = right.children.remove(0);
-            child.children.push(moved);
+    /// - **Leaves**: the right sibling's first pair moves to the end of
+    ///   the child, and the right sibling's new first key becomes the
+    ///   separator between them.
+    /// - **Internal nodes**: the separator comes down to the end of the
+    ///   child with the right sibling's first child, and the right
+    ///   sibling's first separator goes up to replace it.
+    ///
+    /// This maintains the B+tree invariants during deletion by
+    /// redistributing entries so that the underflowing child regains at
+    /// least `t`.
+    fn borrow_from_next(&mut self, parent: NodeId, idx: usize) {
+        let (child_id, right_id) = (self.node(parent).children[idx], self.node(parent).children[idx + 1]);
+        if self.node(child_id).is_leaf {
+            let right = self.node_mut(right_id);
+            let pair = right.kv_pairs.remove(0);
+            right.size -= 1;
+            let separator = right.kv_pairs[0].0.clone();
+            self.node_mut(parent).separators[idx] = separator;
+            let child = self.node_mut(child_id);
+            child.kv_pairs.push(pair);
+            child.size += 1;
+            return;
         }
-    }
-
 
-    /// Merge `node.children[idx]`, the separating parent key,
-    /// and `node.children[idx+1]` into a single child at `idx`.
-    fn merge_children(node: &mut BTreeNode, idx: usize) {
-        // Merge child idx, parent kv_pairs idx, and child idx+1 into child idx
-        let mut right = node.children.remove(idx + 1);
-        let parent_kvs = node.kv_pairs.remove(idx);
-        let left = &mut node.children[idx];
-
-        // Bring parent key down and append right child’s kv_pairs
-        left.kv_pairs.push(parent_kvs);
-        left.kv_pairs.append(&mut right.kv_pairs);
-
-        // If internal, also merge child pointers
-        if !left.is_leaf {
-            left.children.append(&mut right.children);
-        }
+        let right = self.node_mut(right_id);
+        let up = right.separators.remove(0);
+        let moved = right.children.remove(0);
+        let moved_size = self.node(moved).size;
+        self.node_mut(right_id).size -= moved_size;
+        let down = std::mem::replace(&mut self.node_mut(parent).separators[idx], up);
+        let child = self.node_mut(child_id);
+        child.separators.push(down);
+        child.children.push(moved);
+        child.size += moved_size;
     }
 
 
-    /// Return the minimum key–value pair in the given subtree.
-    /// Descends left until reaching a leaf.
-    fn min_kvs(node: &mut BTreeNode) -> (String, String) {
-        let mut current_node = node;
-        while !current_node.is_leaf {
-            current_node = &mut current_node.children[0];
+    /// Merge child `idx + 1` of `parent` into child `idx`, dropping the
+    /// separator between them (internal nodes keep it, between their
+    /// children) and the right node itself.
+    fn merge_children(&mut self, parent: NodeId, idx: usize) {
+        // Take child idx+1 and the separator out of the parent
+        let parent = self.node_mut(parent);
+        let right_id = parent.children.remove(idx + 1);
+        let separator = parent.separators.remove(idx);
+        let left_id = parent.children[idx];
+        let Some(right) = self.nodes.remove(right_id) else {
+            return;
+        };
+
+        let left = self.node_mut(left_id);
+        if left.is_leaf {
+            // Append the right leaf's pairs and unlink it from the chain
+            left.kv_pairs.extend(right.kv_pairs);
+            left.next = right.next;
+            if let Some(next) = right.next {
+                self.node_mut(next).prev = Some(left_id);
+            }
+        } else {
+            // Bring the separator down between the two sets of children
+            left.separators.push(separator);
+            left.separators.extend(right.separators);
+            left.children.extend(right.children);
         }
-        current_node.kv_pairs.first().expect("non-empty").clone()
+        self.node_mut(left_id).size += right.size;
     }
 
 
-    /// Return the maximum key–value pair in the given subtree.
-    /// Descends right until reaching a leaf.
-    fn max_kvs(node: &mut BTreeNode) -> (String, String) {
-        let mut current_node = node;
-        while !current_node.is_leaf {
-            let last = current_node.children.len() - 1;
-            current_node = &mut current_node.children[last];
-        }
-        current_node.kv_pairs.last().expect("non-empty").clone()
-    }
-
     /// Added helper to clear tree for repeated sessions.
     pub fn clear(&mut self) {
-        self.root = Box::new(BTreeNode::new(true));
+        self.nodes.clear();
+        self.root = self.add_node(BTreeNode::new(true));
     }
 }
@@ -0,0 +1,120 @@
+// =====================================================================
+// File: limits.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Validation for keys and values. Every write (SET, MSET, COMMIT of
+//   staged writes) and every replayed log record is checked against the
+//   session's `Limits`, so one pathological input cannot bloat or break
+//   the line-based log or the in-memory index.
+//
+//   - Keys must be non-empty and free of control characters (newlines,
+//     tabs, NUL, ...), which a line-based log cannot carry safely.
+//   - Lengths are measured in bytes of the raw key or value, before the
+//     log escaping is applied.
+// =====================================================================
+
+/// Longest key accepted unless configured otherwise (bytes).
+pub const DEFAULT_MAX_KEY_LEN: usize = 1024;
+
+/// Longest value accepted unless configured otherwise (bytes, 1 MiB).
+pub const DEFAULT_MAX_VALUE_LEN: usize = 1024 * 1024;
+
+/// Maximum key and value sizes for one session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Longest accepted key, in bytes.
+    pub max_key_len: usize,
+
+    /// Longest accepted value, in bytes.
+    pub max_value_len: usize,
+}
+
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_key_len: DEFAULT_MAX_KEY_LEN,
+            max_value_len: DEFAULT_MAX_VALUE_LEN,
+        }
+    }
+}
+
+
+impl Limits {
+    /// Check a key with [`validate_key`] and against the key limit.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the key is well-formed and fits.
+    /// * `Err(message)` describing the violation, without the `ERR` prefix.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Limits;
+    /// let limits = Limits { max_key_len: 3, max_value_len: 10 };
+    /// assert!(limits.check_key("abc").is_ok());
+    /// assert_eq!(limits.check_key("abcd").unwrap_err(), "key is too long (4 bytes, max 3)");
+    /// ```
+    pub fn check_key(&self, key: &str) -> Result<(), String> {
+        validate_key(key)?;
+        if key.len() > self.max_key_len {
+            return Err(format!("key is too long ({} bytes, max {})", key.len(), self.max_key_len));
+        }
+        Ok(())
+    }
+
+
+    /// Check a value against the value limit.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the value fits.
+    /// * `Err(message)` describing the violation, without the `ERR` prefix.
+    pub fn check_value(&self, value: &[u8]) -> Result<(), String> {
+        if value.len() > self.max_value_len {
+            return Err(format!("value is too long ({} bytes, max {})", value.len(), self.max_value_len));
+        }
+        Ok(())
+    }
+
+
+    /// Check both halves of a write; the key is checked first.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Limits;
+    /// let limits = Limits { max_key_len: 8, max_value_len: 4 };
+    /// assert!(limits.check_write("dog", b"bark").is_ok());
+    /// assert_eq!(limits.check_write("dog", b"barking").unwrap_err(), "value is too long (7 bytes, max 4)");
+    /// ```
+    pub fn check_write(&self, key: &str, value: &[u8]) -> Result<(), String> {
+        self.check_key(key)?;
+        self.check_value(value)
+    }
+}
+
+
+/// Reject keys the log and the REPL cannot round-trip.
+///
+/// # Returns
+/// * `Ok(())` for a non-empty key without control characters.
+/// * `Err(message)` naming the problem, without the `ERR` prefix.
+///
+/// # Example
+/// ```
+/// use kvstore::validate_key;
+/// assert!(validate_key("user:1 profile").is_ok());
+/// assert_eq!(validate_key("bad\nkey").unwrap_err(), "key contains control character U+000A");
+/// assert_eq!(validate_key("").unwrap_err(), "key must not be empty");
+/// ```
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("key must not be empty".to_string());
+    }
+    if let Some(ch) = key.chars().find(|c| c.is_control()) {
+        return Err(format!("key contains control character U+{:04X}", ch as u32));
+    }
+    Ok(())
+}
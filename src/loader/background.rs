@@ -0,0 +1,251 @@
+// =====================================================================
+// File: loader/background.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`BackgroundLoad`] replays the log on a worker thread.
+//!
+//! - The worker reads every record, decodes it (`SET`, `MSET`, `DEL`,
+//!   `EXPIREAT`, `PERSIST`), and sends the writes in batches of
+//!   [`LOAD_BATCH`], newest first, each with the key's final TTL.
+//!   A key whose newest record is a `DEL` (or a deadline that has
+//!   passed) is never sent. A log that continues a checkpoint is first
+//!   folded back into one whole log.
+//! - The session pulls a bounded number of records per command tick and
+//!   inserts a key only the first time it is seen.
+//! - Writes made while loading are queued here and applied, in order,
+//!   once the last record has been inserted.
+// =====================================================================
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use crate::checkpoint;
+use crate::storage::{self, ReplayOp};
+use crate::{Fs, LoadReport, RealFs};
+
+/// Records per batch sent by the worker, and applied per command tick.
+pub const LOAD_BATCH: usize = 4096;
+
+/// One replayed `SET` (a [`ReplayOp::Set`] or [`ReplayOp::SetRef`]) and
+/// the key's deadline in Unix ms if it has a TTL.
+pub type LoadedRecord = (ReplayOp, Option<u64>);
+
+/// One write made while loading: key and value (`None` is a delete).
+pub type QueuedWrite = (String, Option<String>);
+
+/// A log replay running on a worker thread, plus writes waiting for it.
+#[derive(Debug)]
+pub struct BackgroundLoad {
+    /// Batches of records from the worker, newest first.
+    receiver: Receiver<Vec<LoadedRecord>>,
+
+    /// Worker thread; joined once its channel is closed. Returns the
+    /// record counts of the replay.
+    worker: Option<JoinHandle<io::Result<LoadReport>>>,
+
+    /// Records received but not yet handed to the session.
+    pending: VecDeque<LoadedRecord>,
+
+    /// `true` once the worker has sent its last batch.
+    finished: bool,
+
+    /// Writes made while loading, in order (`None` is a delete).
+    queued: Vec<QueuedWrite>,
+
+    /// Records handed to the session so far.
+    replayed: u64,
+}
+
+
+impl BackgroundLoad {
+    /// Start replaying the log at `path` on a new thread.
+    pub fn spawn(path: &str) -> Self {
+        Self::spawn_with(Arc::new(RealFs), path)
+    }
+
+
+    /// Start replaying the log at `path` on `fs` on a new thread.
+    pub fn spawn_with(fs: Arc<dyn Fs>, path: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_string();
+
+        let worker = thread::spawn(move || -> io::Result<LoadReport> {
+            // Writes stay queued until the load is done, so none can land behind a torn tail
+            let torn_bytes = storage::recover_log_with(&*fs, &path)?;
+            // Replay runs newest first and ends at the log's oldest record,
+            // so a checkpoint goes back into the log ahead of it
+            checkpoint::fold_checkpoint(&*fs, &path)?;
+            let records = storage::replay_records(&*fs, &path, 0)?;
+            let layout = fs.layout(&path)?;
+            let mut report = LoadReport { torn_bytes, ..LoadReport::default() };
+
+            // A SET keeps the key's TTL, so TTLs are folded oldest first
+            let now = crate::ttl::unix_now_ms();
+            let mut expirations: HashMap<String, u64> = HashMap::new();
+            for (offset, line) in &records {
+                for op in layout.decode(*offset, line) {
+                    match op {
+                        ReplayOp::ExpireAt(key, at) if at > now => expirations.insert(key, at),
+                        ReplayOp::Del(key) | ReplayOp::ExpireAt(key, _) | ReplayOp::Persist(key) => expirations.remove(&key),
+                        ReplayOp::Set(..) | ReplayOp::SetRef(..) => None,
+                    };
+                }
+            }
+
+            // Newest first, so the first value seen per key is final
+            let mut batch = Vec::with_capacity(LOAD_BATCH);
+            let mut seen: HashSet<String> = HashSet::new();
+            let mut deleted: HashSet<String> = HashSet::new();
+            for (offset, line) in records.iter().rev() {
+                let ops = layout.decode(*offset, line);
+                report.count(ops.len());
+                for op in ops.into_iter().rev() {
+                    match op {
+                        // Older writes must not resurrect a deleted (or expired) key
+                        ReplayOp::Del(key) => {
+                            if !seen.contains(&key) {
+                                deleted.insert(key.clone());
+                            }
+                            seen.insert(key);
+                        }
+                        ReplayOp::ExpireAt(key, at) if at <= now => {
+                            if !seen.contains(&key) {
+                                deleted.insert(key.clone());
+                            }
+                            seen.insert(key);
+                        }
+                        ReplayOp::Set(ref key, ..) | ReplayOp::SetRef(ref key, _) => {
+                            if deleted.contains(key) {
+                                continue;
+                            }
+                            seen.insert(key.clone());
+                            let expires_at = expirations.get(key).copied();
+                            batch.push((op, expires_at));
+                        }
+                        ReplayOp::ExpireAt(..) | ReplayOp::Persist(_) => {}
+                    }
+                }
+
+                if batch.len() >= LOAD_BATCH && sender.send(std::mem::take(&mut batch)).is_err() {
+                    return Ok(report); // session went away
+                }
+            }
+            if !batch.is_empty() {
+                let _ = sender.send(batch);
+            }
+            Ok(report)
+        });
+
+        Self {
+            receiver,
+            worker: Some(worker),
+            pending: VecDeque::new(),
+            finished: false,
+            queued: Vec::new(),
+            replayed: 0,
+        }
+    }
+
+
+    /// Up to `max` records that are ready now, without blocking.
+    pub fn next_records(&mut self, max: usize) -> Vec<LoadedRecord> {
+        while self.pending.len() < max && !self.finished {
+            match self.receiver.try_recv() {
+                Ok(batch) => self.pending.extend(batch),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.finished = true,
+            }
+        }
+        self.take_pending(max)
+    }
+
+
+    /// Up to `max` records, waiting for the worker if none are ready.
+    pub fn wait_records(&mut self, max: usize) -> Vec<LoadedRecord> {
+        if self.pending.is_empty() && !self.finished {
+            match self.receiver.recv() {
+                Ok(batch) => self.pending.extend(batch),
+                Err(_) => self.finished = true,
+            }
+        }
+        self.take_pending(max)
+    }
+
+
+    /// Returns `true` once every record has been handed out.
+    pub fn is_drained(&self) -> bool {
+        self.finished && self.pending.is_empty()
+    }
+
+
+    /// Join the worker and hand back the writes queued while loading.
+    ///
+    /// # Returns
+    /// * `Ok(writes)` in the order they were made (`None` = delete).
+    /// * `Err(io::Error)` if the worker could not read the log.
+    pub fn finish(self) -> io::Result<Vec<QueuedWrite>> {
+        self.finish_with_report().map(|(writes, _)| writes)
+    }
+
+
+    /// Like [`BackgroundLoad::finish`], also returning the worker's counts.
+    ///
+    /// Only the record counts are filled in; the session adds the live
+    /// keys and rejected writes.
+    pub fn finish_with_report(mut self) -> io::Result<(Vec<QueuedWrite>, LoadReport)> {
+        let report = match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .map_err(|_| io::Error::other("log replay thread panicked"))??,
+            None => LoadReport::default(),
+        };
+        Ok((std::mem::take(&mut self.queued), report))
+    }
+
+
+    /// Queue a `SET` to apply after loading.
+    pub fn queue_set(&mut self, key: String, value: String) {
+        self.queued.push((key, Some(value)));
+    }
+
+
+    /// Queue a delete to apply after loading.
+    pub fn queue_delete(&mut self, key: &str) {
+        self.queued.push((key.to_string(), None));
+    }
+
+
+    /// Latest queued write for `key`.
+    ///
+    /// # Returns
+    /// * `Some(Some(value))` if the key was set while loading.
+    /// * `Some(None)` if it was deleted while loading.
+    /// * `None` if nothing is queued for it.
+    pub fn queued_value(&self, key: &str) -> Option<Option<&str>> {
+        self.queued
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_deref())
+    }
+
+
+    /// Records handed to the session so far.
+    pub fn replayed(&self) -> u64 {
+        self.replayed
+    }
+
+
+    /// Remove up to `max` records from the front of `pending`.
+    fn take_pending(&mut self, max: usize) -> Vec<LoadedRecord> {
+        let n = max.min(self.pending.len());
+        self.replayed += n as u64;
+        self.pending.drain(..n).collect()
+    }
+}
@@ -0,0 +1,35 @@
+// =====================================================================
+// File: loader/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `loader` module lets the REPL start before the log is replayed.
+//!
+//! Structure:
+//! - `background.rs` : Defines [`BackgroundLoad`], which reads the log on
+//!   a worker thread and hands records to the session in batches, plus
+//!   the queue of writes made while loading.
+//! - `parallel.rs`   : Defines [`ParallelReplay`], which parses and
+//!   decodes records on one thread while startup applies them on another.
+//! - `report.rs`     : Defines [`LoadReport`], the startup integrity
+//!   summary (records, malformed lines, dead writes).
+//! - `tests.rs`      : Unit tests for ordering, LOADING misses and the
+//!   write queue.
+//!
+//! Records are delivered newest first, so the first value seen for a key
+//! is already its final (last-write-wins) value. Any key present in the
+//! partial index can therefore be served; other keys answer `LOADING`.
+// =====================================================================
+
+pub mod background;
+pub mod parallel;
+pub mod report;
+
+pub use self::background::{BackgroundLoad, LoadedRecord, LOAD_BATCH};
+pub use self::parallel::{ParallelReplay, PARSE_AHEAD, PARSE_BATCH};
+pub use self::report::{LoadReport, COMPACT_DEAD_RATIO, COMPACT_MIN_WRITES};
+
+#[cfg(test)]
+pub mod tests;
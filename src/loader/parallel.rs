@@ -0,0 +1,108 @@
+// =====================================================================
+// File: loader/parallel.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! [`ParallelReplay`] splits a startup replay across two threads.
+//!
+//! - A parser thread reads the log's records (checksums, stamps,
+//!   decryption) and decodes each into its [`ReplayOp`]s, sending them
+//!   on in batches of [`PARSE_BATCH`], in log order.
+//! - The loading thread only applies them to the index. The channel holds
+//!   at most [`PARSE_AHEAD`] batches, so the parser stays a bounded
+//!   distance ahead and a large log is never held in memory at once.
+// =====================================================================
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::vec;
+
+use crate::storage::{Layout, ReplayOp};
+
+/// Records decoded per batch sent to the loading thread.
+pub const PARSE_BATCH: usize = 1024;
+
+/// Batches the parser may have decoded ahead of the loading thread.
+pub const PARSE_AHEAD: usize = 16;
+
+
+/// The decoded records of a log, parsed on their own thread.
+///
+/// Yields each record's ops in log order, like decoding the records
+/// with [`Layout::decode`] one at a time.
+///
+/// # Example
+/// ```
+/// use kvstore::{Layout, ParallelReplay, ReplayOp};
+/// let records = vec![(0, "SET a 1".to_string()), (8, "DEL a".to_string())];
+/// let ops: Vec<Vec<ReplayOp>> = ParallelReplay::spawn(Layout::Text, records).collect();
+/// assert_eq!(ops.len(), 2);
+/// assert_eq!(ops[1], vec![ReplayOp::Del("a".to_string())]);
+/// ```
+#[derive(Debug)]
+pub struct ParallelReplay {
+    receiver: Receiver<Vec<Vec<ReplayOp>>>,
+
+    /// Parser thread; joined once its channel is closed.
+    worker: Option<JoinHandle<()>>,
+
+    /// The batch being handed out.
+    batch: vec::IntoIter<Vec<ReplayOp>>,
+}
+
+
+impl ParallelReplay {
+    /// Start decoding `records` (offsets and lines, as
+    /// [`ReplayIter`](crate::ReplayIter) yields them, of a log laid out
+    /// as `layout`) on a new thread.
+    pub fn spawn<I>(layout: Layout, records: I) -> Self
+    where
+        I: IntoIterator<Item = (u64, String)>,
+        I::IntoIter: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(PARSE_AHEAD);
+        let records = records.into_iter();
+
+        let worker = thread::spawn(move || {
+            let mut batch = Vec::with_capacity(PARSE_BATCH);
+            for (offset, line) in records {
+                batch.push(layout.decode(offset, &line));
+                if batch.len() >= PARSE_BATCH && sender.send(std::mem::replace(&mut batch, Vec::with_capacity(PARSE_BATCH))).is_err() {
+                    return; // replay went away
+                }
+            }
+            if !batch.is_empty() {
+                let _ = sender.send(batch);
+            }
+        });
+
+        Self { receiver, worker: Some(worker), batch: Vec::new().into_iter() }
+    }
+}
+
+
+impl Iterator for ParallelReplay {
+    type Item = Vec<ReplayOp>;
+
+    fn next(&mut self) -> Option<Vec<ReplayOp>> {
+        loop {
+            if let Some(ops) = self.batch.next() {
+                return Some(ops);
+            }
+            match self.receiver.recv() {
+                Ok(batch) => self.batch = batch.into_iter(),
+                Err(_) => {
+                    // A parser that died would otherwise look like the end of the log
+                    if let Some(worker) = self.worker.take()
+                        && let Err(panic) = worker.join()
+                    {
+                        std::panic::resume_unwind(panic);
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// =====================================================================
+// File: loader/report.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`LoadReport`] summarizes the health of the log after a replay.
+//!
+//! Every replayed record is counted, along with the writes it held and
+//! whether it could be decoded at all, plus the keys loaded from a
+//! checkpoint and any torn tail cut off the log before the replay. Once
+//! the replay finishes the session adds the live-key count and the
+//! records it rejected, which
+//! gives the share of writes that are dead (overwritten or deleted) and
+//! whether a `COMPACT` is worth running.
+// =====================================================================
+
+use std::fmt;
+
+/// Share of dead writes at which compaction is recommended.
+pub const COMPACT_DEAD_RATIO: f64 = 0.5;
+
+/// Fewest writes in the log before compaction is ever recommended.
+pub const COMPACT_MIN_WRITES: u64 = 1000;
+
+/// Counts gathered while replaying the log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadReport {
+    /// Records replayed; blank lines and the format header don't count.
+    pub records: u64,
+
+    /// Writes in those records (an `MSET` counts once per pair).
+    pub writes: u64,
+
+    /// Records that decoded to nothing and were skipped.
+    pub malformed: u64,
+
+    /// Writes skipped for breaking the key/value limits.
+    pub rejected: u64,
+
+    /// Bytes of damaged records cut off the end of the log (see
+    /// [`recover_log`](crate::recover_log)).
+    pub torn_bytes: u64,
+
+    /// Keys loaded from a checkpoint before the log was replayed.
+    pub checkpoint_keys: usize,
+
+    /// Keys live once the replay finished.
+    pub live_keys: usize,
+}
+
+
+impl LoadReport {
+    /// Count one replayed record that decoded to `ops` writes.
+    pub fn count(&mut self, ops: usize) {
+        self.records += 1;
+        if ops == 0 {
+            self.malformed += 1;
+        }
+        self.writes += ops as u64;
+    }
+
+
+    /// Share of writes no longer backing a live key, from 0.0 to 1.0.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::LoadReport;
+    /// let report = LoadReport { records: 4, writes: 4, live_keys: 1, ..LoadReport::default() };
+    /// assert_eq!(report.dead_ratio(), 0.75);
+    /// assert_eq!(LoadReport::default().dead_ratio(), 0.0);
+    /// ```
+    pub fn dead_ratio(&self) -> f64 {
+        if self.writes == 0 {
+            return 0.0;
+        }
+        self.writes.saturating_sub(self.live_keys as u64) as f64 / self.writes as f64
+    }
+
+
+    /// `true` once the log is big enough and mostly dead writes.
+    pub fn compaction_recommended(&self) -> bool {
+        self.writes >= COMPACT_MIN_WRITES && self.dead_ratio() >= COMPACT_DEAD_RATIO
+    }
+}
+
+
+impl fmt::Display for LoadReport {
+    /// One `field:value` line, as printed to stderr after startup.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "checkpoint_keys:{} records_replayed:{} live_keys:{} malformed_skipped:{} rejected:{} torn_bytes_cut:{} dead_ratio:{:.2} compaction:{}",
+            self.checkpoint_keys,
+            self.records,
+            self.live_keys,
+            self.malformed,
+            self.rejected,
+            self.torn_bytes,
+            self.dead_ratio(),
+            if self.compaction_recommended() { "recommended" } else { "not_needed" },
+        )
+    }
+}
@@ -0,0 +1,219 @@
+// =====================================================================
+// File: loader/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for background log replay, the loading-mode session,
+//   parallel replay and the startup load report.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Background Load Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod background_load_tests {
+    use crate::{load_data_background, BackgroundLoad, MemFs, ReplayOp, Session};
+    use std::sync::Arc;
+
+    /// A session whose log, `log` on its own [`MemFs`], holds `contents`.
+    fn session_with(contents: &str) -> Session {
+        let fs = Arc::new(MemFs::new());
+        fs.write_file("log", contents.as_bytes());
+        let mut session = Session::ephemeral();
+        session.fs = fs;
+        session.data_file = "log".to_string();
+        session
+    }
+
+    #[test]
+    fn records_arrive_newest_first() {
+        let fs = Arc::new(MemFs::new());
+        fs.write_file("log", b"SET a 1\nSET b 2\nSET a 3\n");
+        let mut load = BackgroundLoad::spawn_with(fs, "log");
+
+        let mut keys = Vec::new();
+        while !load.is_drained() {
+            keys.extend(load.wait_records(10).into_iter().filter_map(|(op, _)| match op {
+                ReplayOp::Set(k, v, _) => Some(format!("{k}={}", String::from_utf8_lossy(&v))),
+                _ => None,
+            }));
+        }
+        assert_eq!(keys, vec!["a=3", "b=2", "a=1"]);
+        assert!(load.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn mset_and_del_records_replay_like_load_data() {
+        let log = "SET a 1\nMSET b 2 c 3 b 4\nDEL a\nSET d 5\nDEL d\nSET d 6\n";
+        let mut background = session_with(log);
+        load_data_background(&mut background, "log");
+        background.finish_loading().unwrap();
+
+        let mut foreground = session_with(log);
+        crate::load_data(&mut foreground, "log");
+
+        for session in [&mut background, &mut foreground] {
+            assert_eq!(session.get("a"), None);
+            assert_eq!(session.get("b"), Some("4".to_string()));
+            assert_eq!(session.get("c"), Some("3".to_string()));
+            assert_eq!(session.get("d"), Some("6".to_string()));
+            assert_eq!(session.live_keys.len(), 3);
+        }
+    }
+
+    #[test]
+    fn unreplayed_keys_are_loading_misses() {
+        let mut session = session_with("SET dog bark\n");
+        load_data_background(&mut session, "log");
+
+        // Nothing has been applied yet
+        assert!(session.is_loading());
+        assert!(session.loading_miss("dog"));
+
+        session.finish_loading().unwrap();
+        assert!(!session.loading_miss("dog"));
+        assert_eq!(session.get("dog"), Some("bark".to_string()));
+    }
+
+    #[test]
+    fn writes_queue_until_replay_finishes() {
+        let mut session = session_with("SET keep old\nSET drop x\n");
+        load_data_background(&mut session, "log");
+
+        session.set("bgload_new".into(), "v".into());
+        session.set("keep".into(), "new".into());
+        assert!(session.delete("keep"));
+        session.set("keep".into(), "newest".into());
+
+        // Queued writes are visible, but not applied yet
+        assert_eq!(session.get("bgload_new"), Some("v".to_string()));
+        assert!(session.index.search("bgload_new").is_none());
+
+        session.finish_loading().unwrap();
+        assert_eq!(session.get("keep"), Some("newest".to_string()));
+        assert_eq!(session.get("drop"), Some("x".to_string()));
+        assert_eq!(session.get("bgload_new"), Some("v".to_string()));
+    }
+
+    #[test]
+    fn ticks_eventually_finish_loading() {
+        let mut contents = String::new();
+        for i in 0..10_000 {
+            contents.push_str(&format!("SET k{i} v{i}\n"));
+        }
+        let mut session = session_with(&contents);
+        load_data_background(&mut session, "log");
+
+        while session.is_loading() {
+            session.tick().unwrap();
+        }
+        assert_eq!(session.live_keys.len(), 10_000);
+        assert_eq!(session.get("k9999"), Some("v9999".to_string()));
+    }
+}
+
+
+// =====================================================================
+// Load Report Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod load_report_tests {
+    use crate::{load_data, load_data_background, LoadReport, MemFs, Session};
+    use std::sync::Arc;
+
+    #[test]
+    fn both_loaders_report_the_same_counts() {
+        let fs = Arc::new(MemFs::new());
+        fs.write_file("log", b"KVSTORE 1\nSET a 1\nGARBAGE here\nMSET a 2 b 3\n\nDEL b\nSET big 123456\n");
+        let session = || {
+            let mut session = Session::ephemeral();
+            session.fs = fs.clone();
+            session.data_file = "log".to_string();
+            session.limits.max_value_len = 4;
+            session
+        };
+
+        let mut foreground = session();
+        load_data(&mut foreground, "log");
+
+        let mut background = session();
+        load_data_background(&mut background, "log");
+        assert!(background.load_report.is_none());
+        background.finish_loading().unwrap();
+
+        let want = LoadReport { records: 5, writes: 5, malformed: 1, rejected: 1, live_keys: 1, torn_bytes: 0, checkpoint_keys: 0 };
+        assert_eq!(foreground.load_report, Some(want.clone()));
+        assert_eq!(background.load_report, Some(want));
+    }
+
+    #[test]
+    fn compaction_is_recommended_for_large_mostly_dead_logs() {
+        let report = LoadReport { records: 2000, writes: 2000, live_keys: 10, ..LoadReport::default() };
+        assert!(report.compaction_recommended());
+        assert!(report.to_string().ends_with("dead_ratio:0.99 compaction:recommended"));
+
+        // Small logs are never worth it, and neither are mostly live ones
+        assert!(!LoadReport { records: 10, writes: 10, ..LoadReport::default() }.compaction_recommended());
+        assert!(!LoadReport { records: 2000, writes: 2000, live_keys: 1500, ..LoadReport::default() }.compaction_recommended());
+    }
+}
+
+
+// =====================================================================
+// Parallel Replay Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod parallel_replay_tests {
+    use crate::loader::PARSE_BATCH;
+    use crate::{load_data, log_text, set_record, Fs, Layout, MemFs, ParallelReplay, Session};
+    use std::sync::Arc;
+
+    #[test]
+    fn records_come_out_in_log_order_across_batches() {
+        let records: Vec<(u64, String)> = (0..PARSE_BATCH as u64 * 5 + 7).map(|i| (i * 100, set_record(&format!("k{}", i % 97), &i.to_string()))).collect();
+        let want: Vec<_> = records.iter().map(|(offset, line)| Layout::Binary.decode(*offset, line)).collect();
+        assert_eq!(ParallelReplay::spawn(Layout::Binary, records).collect::<Vec<_>>(), want);
+        assert_eq!(ParallelReplay::spawn(Layout::Text, Vec::new()).count(), 0);
+
+        // Stopping early leaves the parser to notice and quit
+        let many = (0..PARSE_BATCH as u64 * 40).map(|i| (i, set_record("k", "v")));
+        assert_eq!(ParallelReplay::spawn(Layout::Text, many).take(3).count(), 3);
+    }
+
+    #[test]
+    fn a_parallel_load_matches_a_sequential_replay() {
+        let mut lines = Vec::new();
+        for i in 0..20_000 {
+            lines.push(set_record(&format!("key{}", i % 1500), &format!("value {}", i)));
+            if i % 7 == 0 {
+                lines.push(format!("DEL key{}", (i * 13) % 1500));
+            }
+        }
+        lines.push("garbage".to_string());
+        let fs = Arc::new(MemFs::new());
+        fs.append("log", &log_text(&lines)).unwrap();
+
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        load_data(&mut session, "log");
+
+        let mut sequential = Session::ephemeral();
+        let records = crate::replay_records(&*fs, "log", 0).unwrap();
+        crate::load_records(&mut sequential, None, fs.layout("log").unwrap(), records, crate::LoadReport::default());
+        assert_eq!(session.load_report, sequential.load_report);
+        assert_eq!(session.load_report.as_ref().unwrap().malformed, 1);
+        assert_eq!(session.live_keys, sequential.live_keys);
+        for i in 0..1500 {
+            let key = format!("key{}", i);
+            assert_eq!(session.index.search(&key), sequential.index.search(&key), "{}", key);
+        }
+    }
+}
@@ -20,27 +20,555 @@
 //   MSET, MGET, EXPIRE, TTL, RANGE, and transaction controls—are
 //   processed via the session context for modular, testable behavior.
 // =====================================================================
-use std::fs::OpenOptions;
-use kvstore::{load_data, repl_loop, Session};
-mod storage;
+use std::sync::Arc;
+
+use kvstore::{check_log_key, compact_log_with, EncryptedFs, DataDir, FORMAT_VERSION, LogTail, ManifestFs, restore_backup, BackupTarget, S3Config, close_all_logs, import_redis, load_webhooks, telemetry, RetryPolicy, Webhooks, Cdc, CdcSink, OtlpConfig, route_loop, ClusterMap, Router, serve_http, serve_memcached, serve_shared, Replica, ReplicationLog, ServerConfig, DEFAULT_BACKLOG, Acl, RealFs, load_data, load_data_background, repl_loop, BTreeIndex, Collation, Compactor, install_reload_signal, LogKey, LogLock, LruCache, migrate_log_with, recover_to_with, reload, repair_log_with, Session, SharedStore};
 
 /// Entry point for the key-value store assignment.
 fn main() {
 
-    // Initialize a new in-memory session (includes BTree index and TTL manager)
-    let mut session = Session::new();
-    let db_file = storage::get_data_file();
+    // Initialize a new in-memory session (includes BTree index and TTL manager).
+    // KVSTORE_KEY_ONLY=1 keeps only keys in memory; otherwise
+    // KVSTORE_MAX_HOT_KEYS limits how many values stay in memory.
+    // `kvstore --no-persist` keeps everything in memory and never touches
+    // the data file; evicted or separated values would have nowhere to be
+    // read back from.
+    let key_only = std::env::var("KVSTORE_KEY_ONLY").is_ok_and(|v| v == "1");
+    let no_persist = std::env::args().skip(1).any(|a| a == "--no-persist");
+    let separated = std::env::var("KVSTORE_VALUE_LOG").is_ok();
+    if no_persist && (key_only || separated || std::env::var("KVSTORE_MAX_HOT_KEYS").is_ok()) {
+        println!("ERR --no-persist cannot be combined with KVSTORE_MAX_HOT_KEYS, KVSTORE_KEY_ONLY or KVSTORE_VALUE_LOG");
+        std::process::exit(1);
+    }
+    if no_persist && std::env::args().len() > 2 {
+        println!("ERR --no-persist takes no other arguments");
+        std::process::exit(1);
+    }
+    let max_hot_keys = match std::env::var("KVSTORE_MAX_HOT_KEYS") {
+        Ok(n) => match n.parse() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                println!("ERR KVSTORE_MAX_HOT_KEYS: must be a number, got '{}'", n);
+                std::process::exit(1);
+            }
+        },
+        Err(_) => None,
+    };
+    let mut session = match max_hot_keys {
+        _ if no_persist => Session::ephemeral(),
+        _ if key_only => Session::key_only(),
+        Some(max_hot_keys) => Session::with_memory_limit(max_hot_keys),
+        None => Session::new(),
+    };
+    // KVSTORE_READ_CACHE enables an LRU cache of that many hot reads.
+    if let Some(capacity) = std::env::var("KVSTORE_READ_CACHE").ok().and_then(|n| n.parse().ok()) {
+        session.cache = Some(LruCache::new(capacity));
+    }
+    // KVSTORE_VALUE_LOG=N keeps values of N bytes or more in the value log.
+    if let Some(threshold) = std::env::var("KVSTORE_VALUE_LOG").ok().and_then(|n| n.parse().ok()) {
+        session.value_log.set_threshold(Some(threshold));
+    }
+    // KVSTORE_NODE_BYTES sizes B-tree nodes by bytes instead of key count.
+    if let Some(bytes) = std::env::var("KVSTORE_NODE_BYTES").ok().and_then(|n| n.parse().ok()) {
+        session.index = BTreeIndex::with_node_budget(session.index.t, bytes);
+    }
+    // KVSTORE_COLLATION orders keys: binary (default), nocase or unicode.
+    if let Some(collation) = std::env::var("KVSTORE_COLLATION").ok().and_then(|c| Collation::from_name(&c)) {
+        session.index.collation = collation;
+    }
+    // KVSTORE_LOWERCASE_KEYS=1 lowercases every key, so keys are case-insensitive.
+    session.lowercase_keys = std::env::var("KVSTORE_LOWERCASE_KEYS").is_ok_and(|v| v == "1");
+    // KVSTORE_COMPACT_BUDGET sets how many keys compaction copies per command.
+    if let Some(budget) = std::env::var("KVSTORE_COMPACT_BUDGET").ok().and_then(|n| n.parse().ok()) {
+        session.compactor = Compactor::new(budget);
+    }
+    // KVSTORE_AUTO_COMPACT_RATIO / KVSTORE_AUTO_COMPACT_BYTES start a compaction on their own
+    // once that share of the log is dead or it grows that big.
+    for (name, setting) in [("KVSTORE_AUTO_COMPACT_RATIO", "auto_compact_ratio"), ("KVSTORE_AUTO_COMPACT_BYTES", "auto_compact_bytes")] {
+        if let Ok(value) = std::env::var(name)
+            && let Err(e) = kvstore::apply_setting(&mut session, setting, &value)
+        {
+            println!("ERR {}: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+    // KVSTORE_CHECKPOINT_EVERY=N checkpoints the keyspace once N records follow the last checkpoint.
+    if let Some(n) = std::env::var("KVSTORE_CHECKPOINT_EVERY").ok().and_then(|n| n.parse().ok()) {
+        if session.spill.is_some() {
+            println!("ERR KVSTORE_CHECKPOINT_EVERY cannot be combined with KVSTORE_MAX_HOT_KEYS or KVSTORE_KEY_ONLY");
+            std::process::exit(1);
+        }
+        session.checkpoint_every = n;
+    }
+    // KVSTORE_SEGMENT_BYTES=N rotates the log into a compressed cold segment once it is N bytes long.
+    if let Some(n) = std::env::var("KVSTORE_SEGMENT_BYTES").ok().and_then(|n| n.parse().ok()) {
+        if session.spill.is_some() {
+            println!("ERR KVSTORE_SEGMENT_BYTES cannot be combined with KVSTORE_MAX_HOT_KEYS or KVSTORE_KEY_ONLY");
+            std::process::exit(1);
+        }
+        session.segment_bytes = n;
+    }
+    // KVSTORE_EXPIRE_BUDGET caps expired keys reclaimed per command.
+    if let Some(budget) = std::env::var("KVSTORE_EXPIRE_BUDGET").ok().and_then(|n| n.parse().ok()) {
+        session.expire_budget = budget;
+    }
+    // KVSTORE_MAX_KEY_BYTES / KVSTORE_MAX_VALUE_BYTES cap write sizes.
+    if let Some(bytes) = std::env::var("KVSTORE_MAX_KEY_BYTES").ok().and_then(|n| n.parse().ok()) {
+        session.limits.max_key_len = bytes;
+    }
+    if let Some(bytes) = std::env::var("KVSTORE_MAX_VALUE_BYTES").ok().and_then(|n| n.parse().ok()) {
+        session.limits.max_value_len = bytes;
+    }
+    // KVSTORE_READ_ONLY_AFTER=N refuses writes after N failed log appends in a row.
+    if let Some(n) = std::env::var("KVSTORE_READ_ONLY_AFTER").ok().and_then(|n| n.parse().ok()) {
+        session.read_only_after = n;
+    }
+    // KVSTORE_LOAD_REPORT=0 silences the startup integrity report on stderr.
+    session.report_load = std::env::var("KVSTORE_LOAD_REPORT").map_or(true, |v| v != "0");
+    // KVSTORE_ACK=1 acknowledges every command with OK/ERR (BEGIN/ABORT too).
+    session.ack_mode = std::env::var("KVSTORE_ACK").is_ok_and(|v| v == "1");
+    // KVSTORE_SYNC picks how appends are flushed: all (default), data or dsync.
+    // KVSTORE_DURABILITY picks when appends are flushed: always (default), <n>ms or exit.
+    for (name, setting) in [("KVSTORE_SYNC", "sync"), ("KVSTORE_DURABILITY", "durability")] {
+        if let Ok(value) = std::env::var(name)
+            && let Err(e) = kvstore::apply_setting(&mut session, setting, &value)
+        {
+            println!("ERR {}: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+    // KVSTORE_ENCRYPTION_KEY (64 hex digits) or KVSTORE_ENCRYPTION_KEY_FILE encrypts
+    // every record written (`encryption` builds). The modes that read values back
+    // from the log by position would read ciphertext, so they are refused.
+    match LogKey::from_env() {
+        Ok(Some(_)) if key_only || separated || session.spill.is_some() => {
+            println!("ERR an encryption key cannot be combined with KVSTORE_MAX_HOT_KEYS, KVSTORE_KEY_ONLY or KVSTORE_VALUE_LOG");
+            std::process::exit(1);
+        }
+        Ok(Some(key)) if !no_persist => session.fs = Arc::new(EncryptedFs::new(session.fs.clone(), key)),
+        Ok(_) => {}
+        Err(e) => {
+            println!("ERR {}", e);
+            std::process::exit(1);
+        }
+    }
+    // KVSTORE_CONFIG names a `name = value` settings file, re-read on SIGHUP.
+    if let Ok(path) = std::env::var("KVSTORE_CONFIG") {
+        session.config_path = Some(path);
+        for problem in reload(&mut session) {
+            eprintln!("config: {}", problem);
+        }
+    }
+    // KVSTORE_ACL names a file of users and their permissions (see `AUTH`).
+    if let Ok(path) = std::env::var("KVSTORE_ACL") {
+        match Acl::load(&RealFs, &path) {
+            Ok(acl) => session.acl = Some(acl),
+            Err(e) => {
+                println!("ERR cannot load ACL {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        session.acl_path = Some(path);
+    }
+    // KVSTORE_WEBHOOKS names a file of `hook <url> <key pattern> [<events>]` lines.
+    if let Ok(path) = std::env::var("KVSTORE_WEBHOOKS") {
+        match load_webhooks(&RealFs, &path) {
+            Ok(hooks) => session.webhooks = Some(Webhooks::start(hooks, RetryPolicy::default())),
+            Err(e) => {
+                println!("ERR cannot load webhooks {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        session.webhooks_path = Some(path);
+    }
+    install_reload_signal();
+    // KVSTORE_S3_ENDPOINT=http://host:9000 is where `s3://` backups go, signed with
+    // AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY (and AWS_SESSION_TOKEN) in KVSTORE_S3_REGION.
+    // https:// endpoints (`s3-tls` builds) are checked against KVSTORE_S3_CA_FILE if set;
+    // KVSTORE_S3_PART_SIZE sets the multipart upload part size in bytes.
+    if let Ok(endpoint) = std::env::var("KVSTORE_S3_ENDPOINT") {
+        if endpoint.starts_with("https://") && !cfg!(feature = "s3-tls") {
+            println!("ERR KVSTORE_S3_ENDPOINT={} needs kvstore built with --features s3-tls", endpoint);
+            std::process::exit(1);
+        }
+        let key = |name: &str| std::env::var(name).unwrap_or_default();
+        let mut s3 = S3Config::new(&endpoint, &key("AWS_ACCESS_KEY_ID"), &key("AWS_SECRET_ACCESS_KEY"));
+        if let Ok(region) = std::env::var("KVSTORE_S3_REGION") {
+            s3.region = region;
+        }
+        s3.session_token = std::env::var("AWS_SESSION_TOKEN").ok();
+        s3.ca_file = std::env::var("KVSTORE_S3_CA_FILE").ok();
+        if let Ok(size) = std::env::var("KVSTORE_S3_PART_SIZE") {
+            match size.parse() {
+                Ok(size) if size >= 5 * 1024 * 1024 => s3.part_size = size,
+                _ => {
+                    println!("ERR KVSTORE_S3_PART_SIZE must be a byte count of at least 5 MiB, not '{}'", size);
+                    std::process::exit(1);
+                }
+            }
+        }
+        session.s3 = Some(s3);
+    }
+    // KVSTORE_OTLP_ENDPOINT=http://host:4318/v1/traces exports spans (`otel` builds);
+    // KVSTORE_OTEL_SERVICE_NAME names the service in them.
+    if let Ok(endpoint) = std::env::var("KVSTORE_OTLP_ENDPOINT") {
+        let mut config = OtlpConfig::new(&endpoint);
+        if let Ok(name) = std::env::var("KVSTORE_OTEL_SERVICE_NAME") {
+            config.service_name = name;
+        }
+        if let Err(e) = telemetry::init(config) {
+            eprintln!("otel: tracing disabled: {}", e);
+        }
+    }
+
+    // KVSTORE_CLUSTER lists shard addresses; KVSTORE_CLUSTER_SHARD says
+    // which one we are. Without a shard number we route for the cluster.
+    if let Ok(list) = std::env::var("KVSTORE_CLUSTER") {
+        let me = std::env::var("KVSTORE_CLUSTER_SHARD").ok().and_then(|n| n.parse().ok());
+        let map = match ClusterMap::parse(&list, me) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("ERR {}", e);
+                std::process::exit(1);
+            }
+        };
+        if me.is_none() {
+            route_loop(&mut Router::new(map));
+            return;
+        }
+        session.cluster = Some(map);
+    }
+    // KVSTORE_DATA_DIR (without KVSTORE_DATA_FILE) is a data directory:
+    // its MANIFEST names the log segment, created on first use
+    let mut data_dir = None;
+    if !no_persist && std::env::var_os("KVSTORE_DATA_FILE").is_none() && let Some(dir) = std::env::var_os("KVSTORE_DATA_DIR") {
+        match DataDir::open(&dir) {
+            Ok(dir) => {
+                session.data_file = dir.log_path();
+                data_dir = Some(dir);
+            }
+            Err(e) => {
+                println!("ERR cannot open {}: {}", dir.to_string_lossy(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let db_file = session.data_file.clone();
+
+    // KVSTORE_READER=1 serves reads from a data file another kvstore process
+    // writes, following its appends; every value stays in memory.
+    let reader = std::env::var("KVSTORE_READER").is_ok_and(|v| v == "1");
+    if reader && no_persist {
+        println!("ERR KVSTORE_READER cannot be combined with --no-persist");
+        std::process::exit(1);
+    }
+    if reader && (session.spill.is_some() || key_only) {
+        println!("ERR KVSTORE_READER cannot be combined with KVSTORE_MAX_HOT_KEYS or KVSTORE_KEY_ONLY");
+        std::process::exit(1);
+    }
+    if reader && std::env::args().len() > 1 {
+        println!("ERR KVSTORE_READER only serves reads; run other commands on the writer");
+        std::process::exit(1);
+    }
+
+    // Only one process may append to the log; held until exit
+    let _lock = if reader || no_persist {
+        None
+    } else {
+        match LogLock::acquire(&db_file) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                println!("ERR {}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // A missing or wrong key would replay an encrypted log as if it were empty
+    if !no_persist && let Err(e) = check_log_key(&*session.fs, &db_file) {
+        println!("ERR cannot open {}: {}", db_file, e);
+        std::process::exit(1);
+    }
+
+    // `kvstore --repair` rebuilds a clean copy of a damaged log and exits
+    if std::env::args().skip(1).any(|a| a == "--repair") {
+        match repair_log_with(&*session.fs, &db_file, &session.limits) {
+            Ok(report) => {
+                println!("records_read:{}", report.records_read);
+                println!("keys_kept:{}", report.keys_kept);
+                println!("records_dropped:{}", report.dropped.len());
+                println!("repaired:{}", report.repaired_path);
+                println!("report:{}", report.report_path);
+            }
+            Err(e) => {
+                println!("ERR repair failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `kvstore restore <dir | s3://bucket/prefix> [<id>]` replaces the data
+    // file with a verified backup (the latest by default) and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "restore") {
+        let Some(target) = args.get(1) else {
+            println!("ERR usage: kvstore restore <dir | s3://bucket/prefix> [<id>]");
+            std::process::exit(1);
+        };
+        let restored = BackupTarget::parse(target)
+            .map_err(std::io::Error::other)
+            .and_then(|target| {
+                // Through the manifest, so reader processes reload
+                let fs = ManifestFs::open(session.fs.clone(), &db_file)?;
+                restore_backup(&fs, session.s3.as_ref(), &target, args.get(2).map(String::as_str), &db_file)
+            });
+        match restored {
+            Ok(manifest) => {
+                println!("restored:{}", manifest.id);
+                println!("keys:{}", manifest.keys);
+                println!("bytes:{}", manifest.bytes);
+            }
+            Err(e) => {
+                println!("ERR restore failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `kvstore compact` rewrites the data file with only its live keys and
+    // exits; `COMPACT` does the same while serving
+    if args.first().is_some_and(|a| a == "compact") {
+        let size = |path: &str| std::fs::metadata(path).map_or(0, |m| m.len());
+        let before = size(&db_file);
+        match ManifestFs::open(session.fs.clone(), &db_file).and_then(|fs| compact_log_with(&fs, &db_file)) {
+            Ok(keys) => {
+                println!("keys:{}", keys);
+                println!("bytes_before:{}", before);
+                println!("bytes_after:{}", size(&db_file));
+            }
+            Err(e) => {
+                println!("ERR compact failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // Create the log, or bring an older one up to the current format
+    if !reader && !no_persist && let Err(e) = migrate_log_with(&*session.fs, &db_file) {
+        println!("ERR cannot open {}: {}", db_file, e);
+        std::process::exit(1);
+    }
+    if !reader && let Some(Err(e)) = data_dir.as_mut().map(|dir| dir.record_format(FORMAT_VERSION)) {
+        println!("ERR cannot update the MANIFEST beside {}: {}", db_file, e);
+        std::process::exit(1);
+    }
 
-    // Check if file exists without truncating or modifying it
-    let _ = OpenOptions::new()
-        .create(true)
-        .read(true)
-        .write(true)
-        .open(&db_file);
+    // The writer announces every replacement of the data file in its
+    // manifest; readers follow the log and reload when it says so.
+    if reader {
+        match LogTail::open(&db_file) {
+            Ok(tail) => session.tail = Some(tail),
+            Err(e) => {
+                println!("ERR cannot follow {}: {}", db_file, e);
+                std::process::exit(1);
+            }
+        }
+    } else if !no_persist {
+        match ManifestFs::open(session.fs.clone(), &db_file) {
+            Ok(fs) => session.fs = Arc::new(fs),
+            Err(e) => {
+                println!("ERR cannot open the manifest of {}: {}", db_file, e);
+                std::process::exit(1);
+            }
+        }
+    }
 
-    // Replay existing records into the in-memory index
-    load_data(&mut session, &db_file);
+    // `kvstore --recover-to <unix ms>` rolls the log back to that moment
+    // (the later records are moved to data.db.after.<ms>) before replaying it
+    if let Some(at) = args.iter().position(|a| a == "--recover-to") {
+        let Some(unix_ms) = args.get(at + 1).and_then(|ms| ms.parse().ok()) else {
+            println!("ERR usage: kvstore --recover-to <unix ms>");
+            std::process::exit(1);
+        };
+        match recover_to_with(&*session.fs, &db_file, unix_ms) {
+            Ok(undone) => eprintln!("recovery: rolled back {} records written after {}", undone, unix_ms),
+            Err(e) => {
+                println!("ERR cannot recover {}: {}", db_file, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Replay existing records into the in-memory index.
+    // KVSTORE_BACKGROUND_LOAD=1 starts the REPL first and replays meanwhile.
+    if reader {
+        session.poll_tail();
+    } else if no_persist {
+        // Nothing was ever written
+    } else if std::env::var("KVSTORE_BACKGROUND_LOAD").is_ok_and(|v| v == "1") {
+        load_data_background(&mut session, &db_file);
+    } else {
+        load_data(&mut session, &db_file);
+    }
+
+    // KVSTORE_CDC=<file | tcp://host:port | stdout> streams committed mutations
+    // as JSON lines; stdout only in server mode, where it carries no replies.
+    if let Ok(spec) = std::env::var("KVSTORE_CDC") {
+        let sink = CdcSink::parse(&spec);
+        let serving = ["KVSTORE_LISTEN", "KVSTORE_HTTP", "KVSTORE_MEMCACHED", "KVSTORE_GRPC"].iter().any(|v| std::env::var(v).is_ok());
+        if sink == CdcSink::Stdout && !serving {
+            println!("ERR KVSTORE_CDC=stdout needs KVSTORE_LISTEN, KVSTORE_HTTP, KVSTORE_MEMCACHED or KVSTORE_GRPC");
+            std::process::exit(1);
+        }
+        match Cdc::open(sink) {
+            Ok(cdc) => session.cdc = Some(cdc),
+            Err(e) => {
+                println!("ERR cannot open CDC sink {}: {}", spec, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // `kvstore import-redis <file>` loads a Redis RDB or AOF into the log and exits
+    if args.first().is_some_and(|a| a == "import-redis") {
+        let Some(path) = args.get(1) else {
+            println!("ERR usage: kvstore import-redis <file>");
+            std::process::exit(1);
+        };
+        match import_redis(&mut session, path) {
+            Ok(report) => {
+                println!("format:{}", report.format.name());
+                println!("keys_imported:{}", report.keys_imported);
+                println!("expired_skipped:{}", report.expired_skipped);
+                println!("ttls_not_kept:{}", report.ttls_not_kept);
+                for skipped in &report.skipped {
+                    println!("skipped:{}", skipped);
+                }
+            }
+            Err(e) => {
+                println!("ERR import failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        let _ = close_all_logs();
+        telemetry::shutdown();
+        return;
+    }
+
+    // KVSTORE_REPLICA_OF=<host:port> follows that primary's log stream.
+    if let Ok(primary) = std::env::var("KVSTORE_REPLICA_OF") {
+        session.replica = Some(Replica::follow(&primary));
+    }
+
+    // KVSTORE_LISTEN=<host:port> serves clients over TCP instead of stdin;
+    // KVSTORE_HTTP=<host:port> serves the REST API,
+    // KVSTORE_MEMCACHED=<host:port> the memcached protocol and
+    // KVSTORE_GRPC=<host:port> the gRPC service, alone or alongside.
+    let listen = std::env::var("KVSTORE_LISTEN").ok();
+    let http = std::env::var("KVSTORE_HTTP").ok();
+    let memcached = std::env::var("KVSTORE_MEMCACHED").ok();
+    let grpc = std::env::var("KVSTORE_GRPC").ok();
+    #[cfg(not(feature = "grpc"))]
+    if grpc.is_some() {
+        println!("ERR KVSTORE_GRPC needs kvstore built with --features grpc");
+        std::process::exit(1);
+    }
+    if listen.is_some() || http.is_some() || memcached.is_some() || grpc.is_some() {
+        if listen.is_some() {
+            // KVSTORE_REPL_BACKLOG sets how many records are kept for replicas.
+            let backlog = std::env::var("KVSTORE_REPL_BACKLOG").ok().and_then(|n| n.parse().ok());
+            session.replication = Some(ReplicationLog::new(backlog.unwrap_or(DEFAULT_BACKLOG)));
+        }
+        let mut config = ServerConfig::new();
+        // KVSTORE_MAX_CLIENTS caps connected clients.
+        if let Some(n) = std::env::var("KVSTORE_MAX_CLIENTS").ok().and_then(|n| n.parse().ok()) {
+            config.max_connections = n;
+        }
+        // KVSTORE_IDLE_TIMEOUT_MS closes silent clients; 0 waits forever.
+        if let Some(ms) = std::env::var("KVSTORE_IDLE_TIMEOUT_MS").ok().and_then(|n| n.parse().ok()) {
+            config.idle_timeout = (ms > 0).then(|| std::time::Duration::from_millis(ms));
+        }
+        // KVSTORE_MAX_OUTPUT_BYTES caps the reply buffered for one command.
+        if let Some(bytes) = std::env::var("KVSTORE_MAX_OUTPUT_BYTES").ok().and_then(|n| n.parse().ok()) {
+            config.max_pending_output = bytes;
+        }
+        let bind = |addr: &str| match std::net::TcpListener::bind(addr) {
+            Ok(listener) => {
+                eprintln!("listening on {}", addr);
+                listener
+            }
+            Err(e) => {
+                println!("ERR cannot listen on {}: {}", addr, e);
+                std::process::exit(1);
+            }
+        };
+        let listener = listen.as_deref().map(bind);
+        let mut http_listener = http.as_deref().map(bind);
+        let mut memcached_listener = memcached.as_deref().map(bind);
+        let store = Arc::new(SharedStore::new(session));
+
+        // The first configured front end runs here, the others beside it
+        #[cfg(feature = "grpc")]
+        if let Some(side) = grpc.as_deref().map(bind) {
+            let alone = listener.is_none() && http_listener.is_none() && memcached_listener.is_none();
+            let shared = store.clone();
+            let grpc_server = std::thread::spawn(move || {
+                if let Err(e) = kvstore::grpc::serve_grpc(side, shared, config) {
+                    eprintln!("grpc server stopped: {}", e);
+                }
+            });
+            if alone {
+                let _ = grpc_server.join();
+            }
+        }
+        if (listener.is_some() || http_listener.is_some())
+            && let Some(side) = memcached_listener.take()
+        {
+            let shared = store.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = serve_memcached(side, shared, config) {
+                    eprintln!("memcached server stopped: {}", e);
+                }
+            });
+        }
+        if listener.is_some()
+            && let Some(side) = http_listener.take()
+        {
+            let shared = store.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = serve_http(side, shared, config) {
+                    eprintln!("http server stopped: {}", e);
+                }
+            });
+        }
+        let result = match (listener, http_listener, memcached_listener) {
+            (Some(listener), _, _) => serve_shared(listener, store, config),
+            (None, Some(listener), _) => serve_http(listener, store, config),
+            (None, None, Some(listener)) => serve_memcached(listener, store, config),
+            (None, None, None) => Ok(()),
+        };
+        if let Err(e) = result {
+            println!("ERR server stopped: {}", e);
+        }
+        let _ = close_all_logs();
+        telemetry::shutdown();
+        return;
+    }
 
     // Hand off to the main REPL loop, which handles commands
     repl_loop(&mut session);
+
+    // Give queued webhook notifications a moment to go out
+    if let Some(webhooks) = session.webhooks.take()
+        && !webhooks.finish(std::time::Duration::from_secs(5))
+    {
+        eprintln!("webhook: exiting with notifications still queued");
+    }
+
+    // Trim preallocated log space before exiting
+    let _ = close_all_logs();
+    telemetry::shutdown();
 }
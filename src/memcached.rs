@@ -0,0 +1,475 @@
+// =====================================================================
+// File: memcached.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   memcached front end: the ASCII protocol's storage and retrieval
+//   commands over the same session as the line protocol, so existing
+//   memcached clients can use kvstore unchanged.
+//
+//     get <key>*                                  -> VALUE lines, then END
+//     set <key> <flags> <exptime> <bytes> [noreply] + data block
+//                                                 -> STORED
+//     delete <key> [noreply]                      -> DELETED / NOT_FOUND
+//     incr|decr <key> <delta> [noreply]           -> the new value / NOT_FOUND
+//     version, quit
+//
+//   Like the HTTP API, every command runs as the line-protocol commands
+//   it maps to, so ACLs, size limits, replicas and cluster shards apply;
+//   an `ERR` reply becomes `SERVER_ERROR <message>`. `exptime` follows
+//   memcached: 0 never expires, up to 30 days is seconds from now, more
+//   is a Unix time, and a negative or past time deletes the key. It is
+//   applied with `EXPIRE` (or `PERSIST`), so it lives in the TTLManager
+//   like any other TTL. A `get` is answered from the store's latest
+//   snapshot, without the session lock, when the snapshot can serve it.
+//
+// Notes:
+//   * Flags are not stored; `get` always reports 0, which is what clients
+//     use for plain strings.
+//   * Values must be UTF-8, like every value in the store.
+//   * There is no CAS (`gets`, `cas`) and no SASL, so with an ACL every
+//     command runs as the unauthenticated user.
+// =====================================================================
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::server::{apply_reload_request, spawn_replication_poller, Connection, Slot};
+use crate::{capture_replies, execute_line, quote_arg, ServerConfig, Session, SharedStore, Snapshot};
+
+/// Longest command line memcached itself accepts, plus its `\r\n`.
+const MAX_LINE_BYTES: usize = 2048 + 2;
+
+/// Longest key memcached allows.
+const MAX_KEY_BYTES: usize = 250;
+
+/// Largest `exptime` read as seconds from now; larger ones are Unix times.
+const MAX_RELATIVE_EXPTIME: i64 = 60 * 60 * 24 * 30;
+
+/// Serve the memcached protocol from `listener` on a shared session.
+///
+/// # Example
+/// ```no_run
+/// use kvstore::{serve_memcached, ServerConfig, Session, SharedStore};
+/// use std::net::TcpListener;
+/// use std::sync::Arc;
+///
+/// let listener = TcpListener::bind("127.0.0.1:11211").unwrap();
+/// serve_memcached(listener, Arc::new(SharedStore::new(Session::open("data").unwrap())), ServerConfig::new()).unwrap();
+/// ```
+pub fn serve_memcached(listener: TcpListener, store: Arc<SharedStore>, config: ServerConfig) -> io::Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+    spawn_replication_poller(&store);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if active.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let _ = stream.write_all(b"SERVER_ERROR max number of clients reached\r\n");
+            continue;
+        }
+        let slot = Slot(active.clone());
+        let store = store.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = handle_memcached_client(stream, &store, config) {
+                eprintln!("memcached client error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+
+/// Answer one client's commands until it quits, disconnects or breaks a quota.
+fn handle_memcached_client(stream: TcpStream, store: &SharedStore, config: ServerConfig) -> io::Result<()> {
+    stream.set_read_timeout(config.idle_timeout)?;
+    stream.set_write_timeout(config.idle_timeout)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut conn = Connection::default();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        match reader.by_ref().take(MAX_LINE_BYTES as u64).read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) if !line.ends_with(b"\n") => {
+                return writer.write_all(b"CLIENT_ERROR line too long, closing connection\r\n");
+            }
+            Ok(_) => {}
+            // memcached closes idle clients without a word
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+        let Ok(text) = std::str::from_utf8(&line) else {
+            writer.write_all(b"CLIENT_ERROR command line is not UTF-8\r\n")?;
+            continue;
+        };
+        let command = text.trim_end_matches(['\r', '\n']).to_string();
+        if command == "quit" {
+            return Ok(());
+        }
+
+        // A storage command's data block follows its line
+        let mut data = None;
+        if let Some(length) = data_length(&command) {
+            if length > config.max_pending_output {
+                io::copy(&mut reader.by_ref().take(length as u64 + 2), &mut io::sink())?;
+                writer.write_all(b"SERVER_ERROR object too large for cache\r\n")?;
+                continue;
+            }
+            let mut block = vec![0; length + 2];
+            reader.read_exact(&mut block)?;
+            if !block.ends_with(b"\r\n") {
+                return writer.write_all(b"CLIENT_ERROR bad data chunk\r\n");
+            }
+            block.truncate(length);
+            data = Some(block);
+        }
+
+        let reply = match get_from_snapshot(&store.snapshot(), &command, &config) {
+            Some(reply) => reply,
+            None => store.write(|session| {
+                apply_reload_request(session);
+                conn.swap(session);
+                let reply = handle_memcached(session, &command, data.as_deref(), &config);
+                if let Err(e) = session.tick() {
+                    eprintln!("background work failed: {}", e);
+                }
+                conn.swap(session);
+                reply
+            }),
+        };
+
+        if reply.len() > config.max_pending_output {
+            return writer.write_all(b"SERVER_ERROR reply exceeds max pending output, closing connection\r\n");
+        }
+        writer.write_all(reply.as_bytes())?;
+    }
+}
+
+
+/// The data block length a `set` line announces, if `command` is one.
+fn data_length(command: &str) -> Option<usize> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    match parts.as_slice() {
+        ["set", _, _, _, bytes, ..] => bytes.parse().ok(),
+        _ => None,
+    }
+}
+
+
+/// Answer one memcached command against `session`.
+///
+/// # Arguments
+/// * `command` - The command line, without its `\r\n`.
+/// * `data` - The data block of a storage command, without its `\r\n`.
+///
+/// # Returns
+/// The reply, `\r\n`-terminated; empty for `noreply` commands.
+///
+/// # Example
+/// ```
+/// use kvstore::{handle_memcached, MemFs, ServerConfig, Session};
+/// let mut session = Session::ephemeral();
+/// session.fs = std::sync::Arc::new(MemFs::new());
+/// let config = ServerConfig::new();
+///
+/// assert_eq!(handle_memcached(&mut session, "set greeting 0 0 5", Some(b"hello"), &config), "STORED\r\n");
+/// assert_eq!(handle_memcached(&mut session, "get greeting", None, &config), "VALUE greeting 0 5\r\nhello\r\nEND\r\n");
+/// ```
+pub fn handle_memcached(session: &mut Session, command: &str, data: Option<&[u8]>, config: &ServerConfig) -> String {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    let Some((&name, args)) = parts.split_first() else {
+        return "ERROR\r\n".to_string();
+    };
+    let noreply = args.last() == Some(&"noreply");
+    let args = if noreply { &args[..args.len() - 1] } else { args };
+    if let Some(key) = args.first()
+        && name != "version"
+        && (key.len() > MAX_KEY_BYTES || key.chars().any(char::is_control))
+    {
+        return "CLIENT_ERROR bad command line format\r\n".to_string();
+    }
+
+    let reply = match (name, args) {
+        ("get", keys) if !keys.is_empty() => get(session, keys, config),
+        ("set", [key, flags, exptime, _bytes]) => set(session, key, flags, exptime, data.unwrap_or_default(), config),
+        ("delete", [key]) => run(session, &format!("DEL {}", quote_arg(key)), config).map(|lines| {
+            if lines.first().map(String::as_str) == Some("1") { "DELETED\r\n" } else { "NOT_FOUND\r\n" }.to_string()
+        }),
+        ("incr", [key, delta]) => add(session, key, delta, true, config),
+        ("decr", [key, delta]) => add(session, key, delta, false, config),
+        ("version", []) => Ok(format!("VERSION kvstore-{}\r\n", env!("CARGO_PKG_VERSION"))),
+        ("get" | "set" | "delete" | "incr" | "decr" | "version", _) => Err("CLIENT_ERROR bad command line format\r\n".to_string()),
+        _ => Err("ERROR\r\n".to_string()),
+    };
+    match reply {
+        Ok(_) if noreply => String::new(),
+        Ok(reply) | Err(reply) => reply,
+    }
+}
+
+
+/// `get`: a `VALUE` line and data block per key found, then `END`.
+fn get(session: &mut Session, keys: &[&str], config: &ServerConfig) -> Result<String, String> {
+    let mut reply = String::new();
+    for key in keys {
+        if let Some(value) = fetch(session, key, config)? {
+            reply.push_str(&format!("VALUE {} 0 {}\r\n{}\r\n", key, value.len(), value));
+        }
+    }
+    reply.push_str("END\r\n");
+    Ok(reply)
+}
+
+
+/// [`get`] answered from `snapshot`, if `command` is a plain `get` it can
+/// answer as the session would; `None` sends it to the session.
+fn get_from_snapshot(snapshot: &Snapshot, command: &str, config: &ServerConfig) -> Option<String> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    let ["get", keys @ ..] = parts.as_slice() else {
+        return None;
+    };
+    let valid = |key: &&str| key.len() <= MAX_KEY_BYTES && !key.chars().any(char::is_control);
+    if !snapshot.serves_reads || keys.is_empty() || keys.last() == Some(&"noreply") || !valid(&keys[0]) {
+        return None;
+    }
+
+    let mut reply = String::new();
+    for key in keys {
+        if let Some(value) = snapshot.get(key) {
+            // Too large for one GET reply: let the session report it
+            if value.len() >= config.max_pending_output {
+                return None;
+            }
+            reply.push_str(&format!("VALUE {} 0 {}\r\n{}\r\n", key, value.len(), value));
+        }
+    }
+    reply.push_str("END\r\n");
+    Some(reply)
+}
+
+
+/// `set`: `SET`, then the TTL `exptime` asks for.
+fn set(session: &mut Session, key: &str, flags: &str, exptime: &str, data: &[u8], config: &ServerConfig) -> Result<String, String> {
+    let bad_format = || "CLIENT_ERROR bad command line format\r\n".to_string();
+    flags.parse::<u32>().map_err(|_| bad_format())?;
+    let exptime: i64 = exptime.parse().map_err(|_| bad_format())?;
+    let value = std::str::from_utf8(data).map_err(|_| "CLIENT_ERROR value is not UTF-8\r\n".to_string())?;
+    let key = quote_arg(key);
+
+    match expiry_ms(exptime, SystemTime::now()) {
+        // Stored and expired at once: nothing is left to read
+        Some(ms) if ms <= 0 => {
+            run(session, &format!("DEL {}", key), config)?;
+        }
+        Some(ms) => {
+            run(session, &format!("SET {} {}", key, quote_arg(value)), config)?;
+            run(session, &format!("EXPIRE {} {}", key, ms), config)?;
+        }
+        // A replaced item loses its old TTL
+        None => {
+            run(session, &format!("SET {} {}", key, quote_arg(value)), config)?;
+            run(session, &format!("PERSIST {}", key), config)?;
+        }
+    }
+    Ok("STORED\r\n".to_string())
+}
+
+
+/// `incr` / `decr`: add or subtract `delta`, keeping the key's TTL.
+///
+/// As in memcached, `incr` wraps at 2^64 and `decr` stops at 0.
+fn add(session: &mut Session, key: &str, delta: &str, up: bool, config: &ServerConfig) -> Result<String, String> {
+    let delta: u64 = delta.parse().map_err(|_| "CLIENT_ERROR invalid numeric delta argument\r\n".to_string())?;
+    let Some(value) = fetch(session, key, config)? else {
+        return Ok("NOT_FOUND\r\n".to_string());
+    };
+    let current: u64 = value
+        .parse()
+        .map_err(|_| "CLIENT_ERROR cannot increment or decrement non-numeric value\r\n".to_string())?;
+    let next = if up { current.wrapping_add(delta) } else { current.saturating_sub(delta) };
+    // SET leaves a TTL in place
+    run(session, &format!("SET {} {}", quote_arg(key), next), config)?;
+    Ok(format!("{}\r\n", next))
+}
+
+
+/// Milliseconds until an `exptime` is reached, or `None` for never.
+///
+/// Zero or less means the item is already expired.
+fn expiry_ms(exptime: i64, now: SystemTime) -> Option<i64> {
+    match exptime {
+        0 => None,
+        ..0 => Some(0),
+        1..=MAX_RELATIVE_EXPTIME => Some(exptime * 1000),
+        _ => {
+            let now_ms = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+            Some(exptime.saturating_mul(1000) - now_ms)
+        }
+    }
+}
+
+
+/// `GET` one key.
+///
+/// # Returns
+/// `Ok(None)` if it is missing, or `Err(reply)` for an `ERR` reply.
+fn fetch(session: &mut Session, key: &str, config: &ServerConfig) -> Result<Option<String>, String> {
+    // A value may span lines, or even read `nil` or `ERR ...`
+    let text = capture(session, &format!("GET {}", quote_arg(key)), config)?;
+    let value = text.strip_suffix('\n').unwrap_or(&text);
+    if session.get(key).is_some_and(|stored| stored == value) {
+        Ok(Some(value.to_string()))
+    } else if value == "nil" {
+        Ok(None)
+    } else {
+        Err(server_error(value.lines().next().unwrap_or("")))
+    }
+}
+
+
+/// Run one protocol line and collect its reply lines.
+///
+/// # Returns
+/// `Err(reply)` for an `ERR` reply, or a reply past the output cap.
+fn run(session: &mut Session, line: &str, config: &ServerConfig) -> Result<Vec<String>, String> {
+    let text = capture(session, line, config)?;
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    match lines.iter().find(|line| line.starts_with("ERR")) {
+        Some(error) => Err(server_error(error)),
+        None => Ok(lines),
+    }
+}
+
+
+/// Run one protocol line and return its replies as written.
+fn capture(session: &mut Session, line: &str, config: &ServerConfig) -> Result<String, String> {
+    let (_, captured) = capture_replies(config.max_pending_output, || execute_line(line.as_bytes(), session));
+    if captured.overflowed {
+        return Err("SERVER_ERROR reply exceeds max pending output\r\n".to_string());
+    }
+    Ok(String::from_utf8_lossy(&captured.bytes).into_owned())
+}
+
+
+/// `SERVER_ERROR` with the message of an `ERR` reply.
+fn server_error(reply: &str) -> String {
+    let message = reply.strip_prefix("ERR").unwrap_or(reply).trim_start_matches([':', ' ']);
+    format!("SERVER_ERROR {}\r\n", message)
+}
+
+
+// =================================================================
+// memcached.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod memcached_tests {
+    use super::*;
+    use crate::MemFs;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    fn memory_session() -> Session {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session
+    }
+
+    fn call(session: &mut Session, command: &str, data: Option<&str>) -> String {
+        handle_memcached(session, command, data.map(str::as_bytes), &ServerConfig::new())
+    }
+
+    #[test]
+    fn test_keys_can_be_stored_read_and_deleted() {
+        let mut session = memory_session();
+        assert_eq!(call(&mut session, "set a 0 0 9", Some("two words")), "STORED\r\n");
+        assert_eq!(call(&mut session, "set b 5 0 3", Some("nil")), "STORED\r\n");
+        assert_eq!(call(&mut session, "get a missing b", None), "VALUE a 0 9\r\ntwo words\r\nVALUE b 0 3\r\nnil\r\nEND\r\n");
+        assert_eq!(session.get("a").as_deref(), Some("two words"));
+
+        assert_eq!(call(&mut session, "delete a", None), "DELETED\r\n");
+        assert_eq!(call(&mut session, "delete a", None), "NOT_FOUND\r\n");
+        assert_eq!(call(&mut session, "delete b noreply", None), "");
+        assert_eq!(call(&mut session, "get a b", None), "END\r\n");
+
+        assert_eq!(call(&mut session, "set a x 0 1", Some("1")), "CLIENT_ERROR bad command line format\r\n");
+        assert_eq!(call(&mut session, "get", None), "CLIENT_ERROR bad command line format\r\n");
+        assert_eq!(call(&mut session, "touch a 0", None), "ERROR\r\n");
+    }
+
+    #[test]
+    fn test_exptime_maps_onto_ttls() {
+        let mut session = memory_session();
+        call(&mut session, "set a 0 100 1", Some("1"));
+        let ttl = session.ttl_status("a");
+        assert!(ttl > 99_000 && ttl <= 100_000, "{}", ttl);
+
+        // Past 30 days it is a Unix time; setting again without one clears it
+        let in_an_hour = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 3600;
+        call(&mut session, &format!("set b 0 {} 1", in_an_hour), Some("1"));
+        assert!(session.ttl_status("b") > 3_500_000);
+        call(&mut session, "set b 0 0 1", Some("2"));
+        assert_eq!(session.ttl_status("b"), -1);
+
+        // A time already gone deletes the key
+        call(&mut session, "set b 0 -1 1", Some("3"));
+        call(&mut session, "set c 0 1000000000 1", Some("3"));
+        assert_eq!(call(&mut session, "get b c", None), "END\r\n");
+        assert_eq!(expiry_ms(MAX_RELATIVE_EXPTIME, UNIX_EPOCH), Some(MAX_RELATIVE_EXPTIME * 1000));
+    }
+
+    #[test]
+    fn test_incr_and_decr() {
+        let mut session = memory_session();
+        assert_eq!(call(&mut session, "incr n 1", None), "NOT_FOUND\r\n");
+        call(&mut session, "set n 0 100 2", Some("10"));
+        assert_eq!(call(&mut session, "incr n 5", None), "15\r\n");
+        assert_eq!(call(&mut session, "decr n 20", None), "0\r\n");
+        assert!(session.ttl_status("n") > 0, "the TTL survives");
+        call(&mut session, "set n 0 0 20", Some("18446744073709551615"));
+        assert_eq!(call(&mut session, "incr n 2", None), "1\r\n");
+
+        call(&mut session, "set s 0 0 3", Some("abc"));
+        assert_eq!(call(&mut session, "incr s 1", None), "CLIENT_ERROR cannot increment or decrement non-numeric value\r\n");
+        assert_eq!(call(&mut session, "incr n -1", None), "CLIENT_ERROR invalid numeric delta argument\r\n");
+    }
+
+    #[test]
+    fn test_store_errors_become_server_errors() {
+        let mut session = memory_session();
+        session.limits.max_value_len = 4;
+        let reply = call(&mut session, "set a 0 0 5", Some("12345"));
+        assert!(reply.starts_with("SERVER_ERROR ") && !reply.contains("ERR "), "{}", reply);
+        assert_eq!(call(&mut session, "get a", None), "END\r\n");
+    }
+
+    #[test]
+    fn test_commands_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let store = Arc::new(SharedStore::new(memory_session()));
+        thread::spawn(move || serve_memcached(listener, store, ServerConfig::new()));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(b"set k 0 0 6\r\nab\r\ncd\r\nset q 0 0 1 noreply\r\n1\r\nget k q\r\nquit\r\n").unwrap();
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "STORED\r\nVALUE k 0 6\r\nab\r\ncd\r\nVALUE q 0 1\r\n1\r\nEND\r\n");
+    }
+}
@@ -0,0 +1,284 @@
+// =====================================================================
+// File: pager/cache.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`PageCache`] keeps up to `capacity` pages of a file in memory.
+//!
+//! - Every page read from disk has its checksum verified; a mismatch is
+//!   reported as `io::ErrorKind::InvalidData` instead of handing back
+//!   corrupted bytes.
+//! - Writes only touch the cached copy and mark it dirty. Dirty pages are
+//!   written back together, in page order, with a single `sync_data`,
+//!   either on `flush`, once `batch` dirty pages pile up, or when a dirty
+//!   page would be evicted.
+//! - Pages past the end of the file (or never written) read as zeros.
+// =====================================================================
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use super::checksum::crc32;
+
+/// Size of one page on disk, checksum header included.
+pub const PAGE_SIZE: usize = 4096;
+
+/// Usable bytes per page after the 4-byte checksum header.
+pub const PAGE_PAYLOAD: usize = PAGE_SIZE - 4;
+
+/// Dirty pages written back per batch unless configured otherwise.
+const DEFAULT_BATCH: usize = 32;
+
+/// A cached page payload and its last-access tick.
+#[derive(Debug)]
+struct CachedPage {
+    data: Box<[u8]>,
+    tick: u64,
+}
+
+/// Bounded LRU cache of checksummed pages backed by a file.
+#[derive(Debug)]
+pub struct PageCache {
+    /// Backing file holding the pages.
+    file: File,
+
+    /// Maximum number of pages held in memory.
+    capacity: usize,
+
+    /// Number of dirty pages that triggers a write-back.
+    batch: usize,
+
+    /// Cached payloads by page number.
+    pages: HashMap<u64, CachedPage>,
+
+    /// Page numbers ordered by last-access tick (oldest first).
+    order: BTreeMap<u64, u64>,
+
+    /// Pages modified since the last write-back.
+    dirty: BTreeSet<u64>,
+
+    /// Monotonic counter used to order accesses.
+    tick: u64,
+
+    /// Reads answered from memory.
+    hits: u64,
+
+    /// Reads that had to load the page from disk.
+    misses: u64,
+}
+
+
+impl PageCache {
+    /// Open (or create) a page file with room for `capacity` cached pages.
+    ///
+    /// A capacity of zero is bumped to one so the page being worked on
+    /// always fits.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::PageCache;
+    /// let path = std::env::temp_dir().join("doctest_pages.db");
+    /// let _ = std::fs::remove_file(&path);
+    ///
+    /// let mut pages = PageCache::open(path.to_str().unwrap(), 8).unwrap();
+    /// pages.write(3, b"hello").unwrap();
+    /// pages.flush().unwrap();
+    /// assert_eq!(&pages.read(3).unwrap()[..5], b"hello");
+    /// # drop(pages);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn open(path: &str, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        Ok(Self {
+            file,
+            capacity: capacity.max(1),
+            batch: DEFAULT_BATCH,
+            pages: HashMap::new(),
+            order: BTreeMap::new(),
+            dirty: BTreeSet::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+
+    /// Set how many dirty pages accumulate before they are written back.
+    pub fn set_batch(&mut self, batch: usize) {
+        self.batch = batch.max(1);
+    }
+
+
+    /// Payload of page `page_no`, loading and verifying it on a miss.
+    ///
+    /// # Returns
+    /// * `Ok(bytes)` with exactly [`PAGE_PAYLOAD`] bytes.
+    /// * `Err(io::Error)` of kind `InvalidData` if the checksum is wrong,
+    ///   or any error from reading the file.
+    pub fn read(&mut self, page_no: u64) -> io::Result<&[u8]> {
+        if self.pages.contains_key(&page_no) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            let data = self.load(page_no)?;
+            self.install(page_no, data)?;
+        }
+        self.touch(page_no);
+        Ok(&self.pages[&page_no].data)
+    }
+
+
+    /// Replace the payload of page `page_no`; `data` is zero-padded.
+    ///
+    /// The page is only marked dirty. It reaches the disk on the next
+    /// write-back batch.
+    pub fn write(&mut self, page_no: u64, data: &[u8]) -> io::Result<()> {
+        if data.len() > PAGE_PAYLOAD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("page payload of {} bytes exceeds {}", data.len(), PAGE_PAYLOAD),
+            ));
+        }
+
+        let mut payload = vec![0u8; PAGE_PAYLOAD].into_boxed_slice();
+        payload[..data.len()].copy_from_slice(data);
+
+        match self.pages.get_mut(&page_no) {
+            Some(page) => page.data = payload,
+            None => self.install(page_no, payload)?,
+        }
+        self.touch(page_no);
+        self.dirty.insert(page_no);
+
+        if self.dirty.len() >= self.batch {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+
+    /// Write every dirty page back in page order, then sync once.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+
+        for &page_no in &self.dirty {
+            let page = &self.pages[&page_no];
+            let mut buf = Vec::with_capacity(PAGE_SIZE);
+            buf.extend_from_slice(&crc32(&page.data).to_le_bytes());
+            buf.extend_from_slice(&page.data);
+
+            self.file.seek(SeekFrom::Start(page_no * PAGE_SIZE as u64))?;
+            self.file.write_all(&buf)?;
+        }
+        self.file.sync_data()?;
+        self.dirty.clear();
+        Ok(())
+    }
+
+
+    /// Number of pages held in memory.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+
+    /// Returns `true` if no pages are cached.
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+
+    /// Number of pages waiting to be written back.
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+
+    /// Reads answered from memory.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+
+    /// Reads that loaded the page from disk.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+
+    /// Read and verify a page from disk.
+    fn load(&mut self, page_no: u64) -> io::Result<Box<[u8]>> {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        self.file.seek(SeekFrom::Start(page_no * PAGE_SIZE as u64))?;
+
+        // A short read means the page lies (partly) past the end of file
+        let mut filled = 0;
+        while filled < PAGE_SIZE {
+            match self.file.read(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+
+        // Never-written pages are all zeros, header included
+        if buf.iter().all(|b| *b == 0) {
+            return Ok(buf.split_off(4).into_boxed_slice());
+        }
+
+        let stored = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        let payload = buf.split_off(4);
+        if crc32(&payload) != stored {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("checksum mismatch on page {}", page_no),
+            ));
+        }
+        Ok(payload.into_boxed_slice())
+    }
+
+
+    /// Cache a page, evicting the least recently used one if full.
+    fn install(&mut self, page_no: u64, data: Box<[u8]>) -> io::Result<()> {
+        while self.pages.len() >= self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+
+            // Write back the whole dirty batch rather than a single page
+            if self.dirty.contains(&oldest) {
+                self.flush()?;
+            }
+            self.pages.remove(&oldest);
+        }
+
+        self.pages.insert(page_no, CachedPage { data, tick: 0 });
+        Ok(())
+    }
+
+
+    /// Mark a cached page as most recently used.
+    fn touch(&mut self, page_no: u64) {
+        self.tick += 1;
+        if let Some(page) = self.pages.get_mut(&page_no) {
+            self.order.remove(&page.tick);
+            page.tick = self.tick;
+            self.order.insert(self.tick, page_no);
+        }
+    }
+}
+
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
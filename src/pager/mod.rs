@@ -0,0 +1,28 @@
+// =====================================================================
+// File: pager/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `pager` module is the page layer for an on-disk index mode. It
+//! reads and writes fixed-size pages of a file through a bounded cache.
+//!
+//! Structure:
+//! - `checksum.rs` : CRC-32 used to detect torn or corrupted pages.
+//! - `cache.rs`    : Defines [`PageCache`], an LRU cache of pages with
+//!   checksum validation on load and batched dirty-page write-back.
+//! - `tests.rs`    : Unit tests for checksums, eviction and write-back.
+//!
+//! Page layout on disk is a 4-byte little-endian CRC-32 of the payload
+//! followed by [`PAGE_PAYLOAD`] bytes of payload.
+// =====================================================================
+
+pub mod cache;
+pub mod checksum;
+
+pub use self::cache::{PageCache, PAGE_PAYLOAD, PAGE_SIZE};
+pub use self::checksum::crc32;
+
+#[cfg(test)]
+pub mod tests;
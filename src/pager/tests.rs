@@ -0,0 +1,122 @@
+// =====================================================================
+// File: pager/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for the CRC-32 helper and the checksummed page cache.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Page Cache Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod page_cache_tests {
+    use crate::{crc32, PageCache, PAGE_PAYLOAD, PAGE_SIZE};
+    use std::fs;
+    use std::io::{Seek, SeekFrom, Write};
+
+    fn page_file(name: &str) -> String {
+        let p = std::env::temp_dir().join(format!("kvstore_pages_{}.db", name));
+        let _ = fs::remove_file(&p);
+        p.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn crc32_known_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn unwritten_pages_read_as_zeros() {
+        let path = page_file("zeros");
+        let mut pages = PageCache::open(&path, 4).unwrap();
+        let page = pages.read(7).unwrap();
+        assert_eq!(page.len(), PAGE_PAYLOAD);
+        assert!(page.iter().all(|b| *b == 0));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn pages_survive_reopen() {
+        let path = page_file("reopen");
+        {
+            let mut pages = PageCache::open(&path, 4).unwrap();
+            pages.write(0, b"root").unwrap();
+            pages.write(2, b"leaf").unwrap();
+        } // dropped -> flushed
+
+        let mut pages = PageCache::open(&path, 4).unwrap();
+        assert_eq!(&pages.read(2).unwrap()[..4], b"leaf");
+        assert_eq!(&pages.read(0).unwrap()[..4], b"root");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn corrupted_page_is_rejected() {
+        let path = page_file("corrupt");
+        {
+            let mut pages = PageCache::open(&path, 4).unwrap();
+            pages.write(1, b"payload").unwrap();
+        }
+
+        // Flip a payload byte behind the cache's back
+        let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(PAGE_SIZE as u64 + 4)).unwrap();
+        file.write_all(b"X").unwrap();
+        drop(file);
+
+        let mut pages = PageCache::open(&path, 4).unwrap();
+        let err = pages.read(1).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn dirty_pages_written_back_in_batches() {
+        let path = page_file("batch");
+        let mut pages = PageCache::open(&path, 16).unwrap();
+        pages.set_batch(3);
+
+        pages.write(0, b"a").unwrap();
+        pages.write(1, b"b").unwrap();
+        assert_eq!(pages.dirty_count(), 2);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        pages.write(2, b"c").unwrap();
+        assert_eq!(pages.dirty_count(), 0);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 3 * PAGE_SIZE as u64);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn evicting_dirty_page_writes_it_back() {
+        let path = page_file("evict");
+        let mut pages = PageCache::open(&path, 2).unwrap();
+        pages.write(0, b"zero").unwrap();
+        pages.write(1, b"one").unwrap();
+        pages.read(2).unwrap(); // evicts page 0
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages.dirty_count(), 0);
+        assert_eq!(&pages.read(0).unwrap()[..4], b"zero");
+        assert_eq!(pages.misses(), 2);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn oversized_payload_rejected() {
+        let path = page_file("oversize");
+        let mut pages = PageCache::open(&path, 2).unwrap();
+        assert!(pages.write(0, &vec![1u8; PAGE_PAYLOAD + 1]).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
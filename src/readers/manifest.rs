@@ -0,0 +1,182 @@
+// =====================================================================
+// File: readers/manifest.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   The writer's side of the protocol: the manifest next to the data
+//   file (`<data file>.manifest`) holds one line, `generation:<n>`.
+//
+//   `ManifestFs` wraps the writer's file system. Whenever the data file
+//   is replaced (a compaction or full sync renaming a new file over it)
+//   it takes the manifest's exclusive lock, does the rename and bumps
+//   the generation before unlocking, and readers, which read under the
+//   shared lock, never see a new file with an old generation. The
+//   generation is also bumped when the writer starts, since the file
+//   may have been replaced while no writer ran (a restore or migration).
+// =====================================================================
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::crypt::LogKey;
+use crate::storage::{self, Layout};
+use crate::Fs;
+
+/// Open (creating it if needed) the manifest of `data_file`.
+pub(crate) fn open_manifest(data_file: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(storage::sidecar_path(data_file, "manifest"))
+}
+
+
+/// The generation a manifest records; `0` for a new, empty one.
+pub(crate) fn read_generation(file: &mut File) -> io::Result<u64> {
+    let mut text = String::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_string(&mut text)?;
+    if text.trim().is_empty() {
+        return Ok(0);
+    }
+    text.trim()
+        .strip_prefix("generation:")
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("bad manifest {:?}", text.trim())))
+}
+
+
+fn write_generation(file: &mut File, generation: u64) -> io::Result<()> {
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(format!("generation:{}\n", generation).as_bytes())?;
+    file.sync_data()
+}
+
+
+/// The writer's file system: `inner`, plus a manifest bump whenever the
+/// data file is replaced.
+#[derive(Debug)]
+pub struct ManifestFs {
+    inner: Arc<dyn Fs>,
+
+    /// Data file whose replacement is announced.
+    data_file: String,
+
+    /// Open manifest and the generation it holds.
+    manifest: Mutex<(File, u64)>,
+}
+
+
+impl ManifestFs {
+    /// Wrap `inner` for the writer of `data_file`, bumping the manifest's
+    /// generation once to announce a (possibly) new file.
+    pub fn open(inner: Arc<dyn Fs>, data_file: &str) -> io::Result<ManifestFs> {
+        let mut file = open_manifest(data_file)?;
+        file.lock()?;
+        let bumped = read_generation(&mut file).and_then(|g| write_generation(&mut file, g + 1).map(|()| g + 1));
+        file.unlock()?;
+        let generation = bumped?;
+        Ok(ManifestFs { inner, data_file: data_file.to_string(), manifest: Mutex::new((file, generation)) })
+    }
+
+
+    /// The generation last written.
+    pub fn generation(&self) -> u64 {
+        self.manifest.lock().unwrap_or_else(PoisonError::into_inner).1
+    }
+}
+
+
+impl Fs for ManifestFs {
+    fn open(&self, path: &str) -> io::Result<()> {
+        self.inner.open(path)
+    }
+
+
+    fn create(&self, path: &str) -> io::Result<()> {
+        self.inner.create(path)
+    }
+
+
+    fn append(&self, path: &str, text: &str) -> io::Result<u64> {
+        self.inner.append(path, text)
+    }
+
+
+    fn append_many(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        self.inner.append_many(path, records)
+    }
+
+
+    fn append_raw(&self, path: &str, bytes: &[u8]) -> io::Result<u64> {
+        self.inner.append_raw(path, bytes)
+    }
+
+
+    fn layout(&self, path: &str) -> io::Result<Layout> {
+        self.inner.layout(path)
+    }
+
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+
+    fn reader(&self, path: &str) -> io::Result<Box<dyn BufRead + Send>> {
+        self.inner.reader(path)
+    }
+
+
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.inner.read_at(path, offset, len)
+    }
+
+
+    fn end(&self, path: &str) -> io::Result<u64> {
+        self.inner.end(path)
+    }
+
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        if to != self.data_file {
+            return self.inner.rename(from, to);
+        }
+        let mut manifest = self.manifest.lock().unwrap_or_else(PoisonError::into_inner);
+        let (file, generation) = &mut *manifest;
+        file.lock()?;
+        let replaced = self.inner.rename(from, to).and_then(|()| {
+            *generation += 1;
+            write_generation(file, *generation)
+        });
+        file.unlock()?;
+        replaced
+    }
+
+
+    fn sync(&self, path: &str) -> io::Result<()> {
+        self.inner.sync(path)
+    }
+
+
+    fn sync_dir(&self, path: &str) -> io::Result<()> {
+        self.inner.sync_dir(path)
+    }
+
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        self.inner.remove(path)
+    }
+
+
+    fn log_key(&self) -> Option<Arc<LogKey>> {
+        self.inner.log_key()
+    }
+}
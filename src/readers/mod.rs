@@ -0,0 +1,32 @@
+// =====================================================================
+// File: readers/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `readers` module lets several processes serve reads from one data
+//! file while a single process writes it.
+//!
+//! Structure:
+//! - `manifest.rs` : [`ManifestFs`], the writer's file system, which
+//!   announces each replacement of the data file by bumping the
+//!   generation in `<data file>.manifest`.
+//! - `tail.rs`     : [`LogTail`], which applies what the writer appends
+//!   and reloads the file when the generation changes.
+//! - `tests.rs`    : Unit tests for tailing and reloads.
+//!
+//! The writer holds the existing `.lock` file exclusively, so there is
+//! only ever one. Readers (`KVSTORE_READER=1`) don't take it. They
+//! coordinate with the writer through the manifest's own lock: shared
+//! while a reader polls, exclusive while the writer swaps files.
+// =====================================================================
+
+pub mod manifest;
+pub mod tail;
+
+pub use self::manifest::ManifestFs;
+pub use self::tail::LogTail;
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,112 @@
+// =====================================================================
+// File: readers/tail.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   The reader's side of the protocol: `LogTail` keeps a session's
+//   index in step with a data file another process writes.
+//
+//   Each poll takes the manifest's shared lock. If the generation is the
+//   one last seen, the records appended since the last poll are applied;
+//   otherwise the file was replaced and is loaded from the start (on top
+//   of the writer's snapshot, if the log continues a checkpoint). Only
+//   whole records (newline-terminated lines, or in a binary log whole
+//   frames) are applied, so an append still
+//   being written is picked up by a later poll. A record that fails its
+//   checksum holds the reader there until the writer, on restart, cuts
+//   it off and bumps the generation.
+// =====================================================================
+
+use std::fs::File;
+use std::io;
+
+use super::manifest::{open_manifest, read_generation};
+use crate::{checkpoint, storage};
+use crate::{Layout, LoadReport, Session};
+
+/// Follows a data file written by another process.
+pub struct LogTail {
+    /// Data file being followed.
+    pub path: String,
+
+    /// Manifest generation the session's data is from (`None` before the
+    /// first poll).
+    pub generation: Option<u64>,
+
+    /// Offset just past the last record applied.
+    pub offset: u64,
+
+    /// Format version of the file, from its header.
+    version: u32,
+
+    manifest: File,
+}
+
+
+impl LogTail {
+    /// Start following `data_file`. Nothing is read until [`LogTail::poll`].
+    pub fn open(data_file: &str) -> io::Result<LogTail> {
+        Ok(LogTail {
+            path: data_file.to_string(),
+            generation: None,
+            offset: 0,
+            version: 0,
+            manifest: open_manifest(data_file)?,
+        })
+    }
+
+
+    /// Bring `session` up to date with the file.
+    ///
+    /// # Returns
+    /// The number of records applied; after a reload, every record in
+    /// the file.
+    pub fn poll(&mut self, session: &mut Session) -> io::Result<usize> {
+        self.manifest.lock_shared()?;
+        let polled = self.poll_locked(session);
+        self.manifest.unlock()?;
+        polled
+    }
+
+
+    fn poll_locked(&mut self, session: &mut Session) -> io::Result<usize> {
+        let generation = read_generation(&mut self.manifest)?;
+        let end = session.fs.end(&self.path)?;
+        // A shorter file without a new generation was replaced behind our back
+        if self.generation != Some(generation) || end < self.offset {
+            let bytes = match session.fs.read(&self.path) {
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            self.version = storage::log_version(&bytes)?;
+            let layout = Layout::of_version(self.version);
+            let whole = storage::whole_records(&bytes, 0, layout);
+            // A damaged record stops the reader until the writer cuts it off
+            let (mut records, torn_at) = storage::parse_records(&bytes[..whole], 0, self.version, session.fs.log_key())?;
+            let base = checkpoint::load_base(&*session.fs, &self.path, &mut records)?;
+            let count = records.len();
+            crate::load_records(session, base, layout, records, LoadReport::default());
+            (self.generation, self.offset) = (Some(generation), torn_at.unwrap_or(whole as u64));
+            return Ok(count);
+        }
+        if end == self.offset {
+            return Ok(0);
+        }
+
+        let bytes = session.fs.read_at(&self.path, self.offset, (end - self.offset) as usize)?;
+        let layout = Layout::of_version(self.version);
+        let whole = storage::whole_records(&bytes, self.offset, layout);
+        let (records, torn_at) = storage::parse_records(&bytes[..whole], self.offset, self.version, session.fs.log_key())?;
+        for (offset, line) in &records {
+            for op in layout.decode(*offset, line) {
+                session.replay_op(op);
+            }
+        }
+        self.offset = torn_at.unwrap_or(self.offset + whole as u64);
+        Ok(records.len())
+    }
+}
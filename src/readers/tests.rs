@@ -0,0 +1,116 @@
+// =====================================================================
+// File: readers/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for readers following a data file: appends, partial
+//   records, replacements announced in the manifest, and refused writes.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Tail Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod tail_tests {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use crate::readers::*;
+    use crate::storage::set_record;
+    use crate::{capture_replies, execute_line, Fs, RealFs, Session};
+
+    /// A fresh data file (and manifest) in the temp directory.
+    fn data_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kvstore_readers_{}_{}.db", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(format!("{}.manifest", path));
+        path
+    }
+
+    /// A reader of the real file at `path`, which it never writes.
+    fn reader(path: &str) -> Session {
+        let mut session = Session::new();
+        session.data_file = path.to_string();
+        session.report_load = false;
+        session.tail = Some(LogTail::open(path).unwrap());
+        session
+    }
+
+    #[test]
+    fn readers_apply_whole_appended_records() {
+        // Written as another process would, bypassing this one's log handles
+        let path = data_file("append");
+        std::fs::write(&path, "KVSTORE 1\nSET a 1\n").unwrap();
+
+        let mut session = reader(&path);
+        session.poll_tail();
+        assert_eq!(session.get("a").as_deref(), Some("1"));
+
+        // Half a record waits for its newline
+        let record = set_record("b", "two words");
+        let (head, rest) = record.split_at(8);
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "DEL a\n{}", head).unwrap();
+        session.poll_tail();
+        assert_eq!(session.get("a"), None);
+        assert_eq!(session.get("b"), None);
+        writeln!(file, "{}", rest).unwrap();
+        session.poll_tail();
+        assert_eq!(session.get("b").as_deref(), Some("two words"));
+        assert_eq!(session.tail.as_ref().unwrap().offset, std::fs::metadata(&path).unwrap().len());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replaced_files_are_reloaded() {
+        let path = data_file("replace");
+        let writer = ManifestFs::open(Arc::new(RealFs), &path).unwrap();
+        writer.append(&path, "KVSTORE 1\nSET a 1\nSET a 2\nSET gone 1\nDEL gone").unwrap();
+        let mut session = reader(&path);
+        session.poll_tail();
+        let first = session.tail.as_ref().unwrap().generation.unwrap();
+        assert_eq!(first, writer.generation());
+
+        // A compaction renames a shorter file over the log
+        let tmp = format!("{}.compact", path);
+        writer.create(&tmp).unwrap();
+        writer.append(&tmp, "KVSTORE 1\nSET a 2\nSET b 3").unwrap();
+        writer.rename(&tmp, &path).unwrap();
+        assert_eq!(writer.generation(), first + 1);
+
+        session.poll_tail();
+        assert_eq!(session.tail.as_ref().unwrap().generation, Some(first + 1));
+        assert_eq!(session.get("a").as_deref(), Some("2"));
+        assert_eq!(session.get("b").as_deref(), Some("3"));
+        assert_eq!(session.live_keys.len(), 2);
+
+        // A new writer announces itself too
+        let restarted = ManifestFs::open(Arc::new(RealFs), &path).unwrap();
+        assert_eq!(restarted.generation(), first + 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn readers_refuse_writes() {
+        let path = data_file("refuse");
+        std::fs::write(&path, "KVSTORE 1\nSET a 1\n").unwrap();
+        let mut session = reader(&path);
+        session.poll_tail();
+
+        let (_, out) = capture_replies(1024, || execute_line(b"SET a 2", &mut session));
+        assert_eq!(String::from_utf8(out.bytes).unwrap(), format!("ERR READONLY this instance is a reader of {}\n", path));
+        let (_, out) = capture_replies(1024, || execute_line(b"INFO", &mut session));
+        let info = String::from_utf8(out.bytes).unwrap();
+        assert!(info.starts_with("role:reader\nreader_generation:0\nreader_offset:18\n"), "{}", info);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
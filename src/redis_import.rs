@@ -0,0 +1,680 @@
+// =====================================================================
+// File: redis_import.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Migration from Redis (`kvstore import-redis <file>`).
+//
+//   Reads an RDB snapshot or an AOF (including an AOF with an RDB
+//   preamble) and folds it into the string keys Redis would hold at the
+//   end of the file, then writes each one through the session as a
+//   regular `SET`, so the imported keys land in data.db and replay like
+//   any other write.
+//
+//   Only string values of database 0 are imported; other databases,
+//   other value types and commands on them are counted in the report.
+//   An RDB with a non-string value stops the import, since its encoding
+//   can't be skipped without parsing it. Keys already expired at import
+//   time are dropped; the others keep their TTL, logged like `EXPIRE`.
+//   Relative AOF expirations (`EX`, `EXPIRE`, ...) are counted from the
+//   moment of the import. The RDB checksum is not verified.
+// =====================================================================
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Session;
+
+/// Which Redis persistence file was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisFormat {
+    /// An RDB snapshot.
+    Rdb,
+
+    /// An append-only file (possibly with an RDB preamble).
+    Aof,
+}
+
+
+impl RedisFormat {
+    /// Lowercase name, as shown in the report.
+    pub fn name(self) -> &'static str {
+        match self {
+            RedisFormat::Rdb => "rdb",
+            RedisFormat::Aof => "aof",
+        }
+    }
+}
+
+
+/// One string key as Redis holds it after the whole file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisKey {
+    /// Key bytes.
+    pub key: Vec<u8>,
+
+    /// Value bytes.
+    pub value: Vec<u8>,
+
+    /// Absolute expiration in Unix milliseconds, if the key has a TTL.
+    pub expires_at_ms: Option<i64>,
+}
+
+
+/// Keys parsed from a Redis file, before they are imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisDump {
+    /// The file's format.
+    pub format: RedisFormat,
+
+    /// String keys of database 0, in key order.
+    pub keys: Vec<RedisKey>,
+
+    /// What was left out, e.g. `"LPUSH x2"` or `"db 1 x3"`.
+    pub skipped: Vec<String>,
+}
+
+
+/// Outcome of an import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisImportReport {
+    /// The file's format.
+    pub format: RedisFormat,
+
+    /// Keys written to the log.
+    pub keys_imported: usize,
+
+    /// Keys already expired at import time.
+    pub expired_skipped: usize,
+
+    /// Imported keys whose TTL could not be logged, so they were kept
+    /// without one.
+    pub ttls_not_kept: usize,
+
+    /// What was left out, from the file and from the import itself.
+    pub skipped: Vec<String>,
+}
+
+
+/// Import the Redis file at `path` into `session`.
+///
+/// # Returns
+/// * `Ok(RedisImportReport)` once every importable key is written.
+/// * `Err(io::Error)` if the file can't be read or doesn't parse.
+pub fn import_redis(session: &mut Session, path: &str) -> io::Result<RedisImportReport> {
+    let bytes = std::fs::read(path)?;
+    let dump = parse_redis_dump(&bytes, now_ms()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(import_dump(session, dump, now_ms()))
+}
+
+
+/// Write the keys of `dump` that are still live at `now_ms` through `session`.
+///
+/// A key or value that is not UTF-8 or breaks the session's size
+/// limits is reported and left out.
+pub fn import_dump(session: &mut Session, dump: RedisDump, now_ms: i64) -> RedisImportReport {
+    let mut report = RedisImportReport {
+        format: dump.format,
+        keys_imported: 0,
+        expired_skipped: 0,
+        ttls_not_kept: 0,
+        skipped: dump.skipped,
+    };
+    for entry in dump.keys {
+        if entry.expires_at_ms.is_some_and(|at| at <= now_ms) {
+            report.expired_skipped += 1;
+            continue;
+        }
+        let (Ok(key), Ok(value)) = (String::from_utf8(entry.key.clone()), String::from_utf8(entry.value)) else {
+            report.skipped.push(format!("key '{}': not UTF-8", String::from_utf8_lossy(&entry.key)));
+            continue;
+        };
+        if let Err(e) = session.try_set(key.clone(), value) {
+            report.skipped.push(format!("key '{}': {}", key, e));
+            continue;
+        }
+        report.keys_imported += 1;
+        if let Some(at) = entry.expires_at_ms
+            && session.expire(&key, at - now_ms) != Ok(true)
+        {
+            report.ttls_not_kept += 1;
+        }
+    }
+    report
+}
+
+
+/// Parse an RDB or AOF file into the string keys it leaves behind.
+///
+/// # Arguments
+/// * `now_ms` - Unix milliseconds that relative AOF expirations count from.
+///
+/// # Example
+/// ```
+/// use kvstore::{parse_redis_dump, RedisFormat};
+/// let aof = b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n*2\r\n$4\r\nINCR\r\n$1\r\na\r\n";
+/// let dump = parse_redis_dump(aof, 0).unwrap();
+/// assert_eq!(dump.format, RedisFormat::Aof);
+/// assert_eq!(dump.keys[0].value, b"2");
+/// ```
+pub fn parse_redis_dump(bytes: &[u8], now_ms: i64) -> Result<RedisDump, String> {
+    let mut fold = Fold::default();
+    let format = if bytes.starts_with(b"REDIS") {
+        let mut rdb = Rdb { bytes, pos: 0 };
+        rdb.read_into(&mut fold)?;
+        if rdb.pos == bytes.len() {
+            RedisFormat::Rdb
+        } else {
+            // An AOF that starts with an RDB preamble
+            Aof { bytes, pos: rdb.pos, db: 0 }.read_into(&mut fold, now_ms)?;
+            RedisFormat::Aof
+        }
+    } else {
+        Aof { bytes, pos: 0, db: 0 }.read_into(&mut fold, now_ms)?;
+        RedisFormat::Aof
+    };
+
+    let keys = fold
+        .keys
+        .into_iter()
+        .map(|(key, (value, expires_at_ms))| RedisKey { key, value, expires_at_ms })
+        .collect();
+    let skipped = fold.skipped.into_iter().map(|(what, n)| format!("{} x{}", what, n)).collect();
+    Ok(RedisDump { format, keys, skipped })
+}
+
+
+/// The keyspace so far, plus counts of what was left out.
+#[derive(Default)]
+struct Fold {
+    keys: BTreeMap<Vec<u8>, (Vec<u8>, Option<i64>)>,
+    skipped: BTreeMap<String, usize>,
+}
+
+
+impl Fold {
+    fn skip(&mut self, what: String) {
+        *self.skipped.entry(what).or_default() += 1;
+    }
+}
+
+
+/// Reader over an RDB snapshot.
+struct Rdb<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+
+impl Rdb<'_> {
+    /// Read every key up to and including the EOF opcode and checksum.
+    fn read_into(&mut self, fold: &mut Fold) -> Result<(), String> {
+        let magic = self.take(9)?;
+        let version: u32 = std::str::from_utf8(&magic[5..]).ok().and_then(|v| v.parse().ok()).ok_or("bad RDB header")?;
+        let mut db = 0;
+        let mut expires_at_ms = None;
+
+        loop {
+            match self.byte()? {
+                // AUX field
+                0xFA => {
+                    self.string()?;
+                    self.string()?;
+                }
+                // RESIZEDB
+                0xFB => {
+                    self.length()?;
+                    self.length()?;
+                }
+                // SELECTDB
+                0xFE => db = self.length()?,
+                // EXPIRETIME (seconds) / EXPIRETIME_MS
+                0xFD => expires_at_ms = Some(i64::from(u32::from_le_bytes(self.array()?)) * 1000),
+                0xFC => expires_at_ms = Some(i64::from_le_bytes(self.array()?)),
+                // IDLE / FREQ, for eviction; not kept
+                0xF8 => {
+                    self.byte()?;
+                }
+                0xF6 => {
+                    self.length()?;
+                }
+                0xFF => {
+                    if version >= 5 {
+                        self.take(8)?;
+                    }
+                    return Ok(());
+                }
+                0 => {
+                    let key = self.string()?;
+                    let value = self.string()?;
+                    match db {
+                        0 => {
+                            fold.keys.insert(key, (value, expires_at_ms));
+                        }
+                        n => fold.skip(format!("db {}", n)),
+                    }
+                    expires_at_ms = None;
+                }
+                kind => {
+                    let key = self.string()?;
+                    return Err(format!("unsupported RDB value type {} for key '{}'", kind, String::from_utf8_lossy(&key)));
+                }
+            }
+        }
+    }
+
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len()).ok_or("RDB file is truncated")?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+
+    /// A length, or `Err` for a special string encoding.
+    fn length(&mut self) -> Result<usize, String> {
+        match self.length_or_encoding()? {
+            Ok(len) => Ok(len),
+            Err(_) => Err("unexpected string encoding where a length belongs".to_string()),
+        }
+    }
+
+
+    /// `Ok(length)`, or `Err(encoding)` for the `11xxxxxx` special forms.
+    fn length_or_encoding(&mut self) -> Result<Result<usize, u8>, String> {
+        let first = self.byte()?;
+        let len = match first >> 6 {
+            0 => usize::from(first & 0x3F),
+            1 => usize::from(first & 0x3F) << 8 | usize::from(self.byte()?),
+            2 if first == 0x80 => u32::from_be_bytes(self.array()?) as usize,
+            2 if first == 0x81 => usize::try_from(u64::from_be_bytes(self.array()?)).map_err(|_| "length too large")?,
+            2 => return Err(format!("unknown RDB length encoding {:#x}", first)),
+            _ => return Ok(Err(first & 0x3F)),
+        };
+        Ok(Ok(len))
+    }
+
+
+    fn string(&mut self) -> Result<Vec<u8>, String> {
+        match self.length_or_encoding()? {
+            Ok(len) => Ok(self.take(len)?.to_vec()),
+            Err(0) => Ok((self.byte()? as i8).to_string().into_bytes()),
+            Err(1) => Ok(i16::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Err(2) => Ok(i32::from_le_bytes(self.array()?).to_string().into_bytes()),
+            Err(3) => {
+                let compressed_len = self.length()?;
+                let len = self.length()?;
+                lzf_decompress(self.take(compressed_len)?, len)
+            }
+            Err(other) => Err(format!("unknown RDB string encoding {}", other)),
+        }
+    }
+}
+
+
+/// Decompress an LZF block that expands to `len` bytes.
+fn lzf_decompress(input: &[u8], len: usize) -> Result<Vec<u8>, String> {
+    let corrupt = || "corrupt LZF string in RDB file".to_string();
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = usize::from(input[i]);
+        i += 1;
+        if ctrl < 32 {
+            // Literal run of ctrl + 1 bytes
+            let run = input.get(i..i + ctrl + 1).ok_or_else(corrupt)?;
+            out.extend_from_slice(run);
+            i += ctrl + 1;
+        } else {
+            // Back reference: length, then distance into the output
+            let mut run = ctrl >> 5;
+            if run == 7 {
+                run += usize::from(*input.get(i).ok_or_else(corrupt)?);
+                i += 1;
+            }
+            let low = usize::from(*input.get(i).ok_or_else(corrupt)?);
+            i += 1;
+            let back = ((ctrl & 0x1F) << 8) + low + 1;
+            let start = out.len().checked_sub(back).ok_or_else(corrupt)?;
+            for k in 0..run + 2 {
+                out.push(out[start + k]);
+            }
+        }
+    }
+    if out.len() != len {
+        return Err(corrupt());
+    }
+    Ok(out)
+}
+
+
+/// Reader over an AOF: RESP arrays of bulk strings, one per command.
+struct Aof<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    db: usize,
+}
+
+
+impl Aof<'_> {
+    fn read_into(&mut self, fold: &mut Fold, now_ms: i64) -> Result<(), String> {
+        while self.pos < self.bytes.len() {
+            let command = self.command()?;
+            if !command.is_empty() {
+                self.apply(&command, fold, now_ms);
+            }
+        }
+        Ok(())
+    }
+
+
+    /// One `*<n>` array of `$<len>` bulk strings.
+    fn command(&mut self) -> Result<Vec<Vec<u8>>, String> {
+        let header = self.line()?;
+        if header.is_empty() {
+            return Ok(Vec::new());
+        }
+        let count = parse_prefixed(header, b'*').ok_or_else(|| format!("bad AOF record at byte {}", self.pos))?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = parse_prefixed(self.line()?, b'$').ok_or_else(|| format!("bad AOF bulk string at byte {}", self.pos))?;
+            let end = self.pos + len;
+            if self.bytes.get(end..end + 2) != Some(b"\r\n") {
+                return Err("AOF file is truncated".to_string());
+            }
+            args.push(self.bytes[self.pos..end].to_vec());
+            self.pos = end + 2;
+        }
+        Ok(args)
+    }
+
+
+    /// The next `\r\n`-terminated line, without the terminator.
+    fn line(&mut self) -> Result<&[u8], String> {
+        let rest = &self.bytes[self.pos..];
+        let end = rest.windows(2).position(|w| w == b"\r\n").ok_or("AOF file is truncated")?;
+        self.pos += end + 2;
+        Ok(&rest[..end])
+    }
+
+
+    /// Fold one command into the keyspace.
+    fn apply(&mut self, command: &[Vec<u8>], fold: &mut Fold, now_ms: i64) {
+        let name = String::from_utf8_lossy(&command[0]).to_uppercase();
+        let args = &command[1..];
+        let number = |i: usize| args.get(i).and_then(|a| std::str::from_utf8(a).ok()?.parse::<i64>().ok());
+
+        if name == "SELECT" {
+            self.db = number(0).and_then(|db| usize::try_from(db).ok()).unwrap_or(0);
+            return;
+        }
+        if matches!(name.as_str(), "MULTI" | "EXEC") {
+            return;
+        }
+        if self.db != 0 {
+            fold.skip(format!("db {}", self.db));
+            return;
+        }
+
+        let keys = &mut fold.keys;
+        let applied = match (name.as_str(), args) {
+            ("SET", [key, value, options @ ..]) => set_with_options(keys, key, value, options, now_ms),
+            ("SETEX", [key, _, value]) => number(1).map(|s| {
+                keys.insert(key.clone(), (value.clone(), Some(now_ms + s * 1000)));
+            }),
+            ("PSETEX", [key, _, value]) => number(1).map(|ms| {
+                keys.insert(key.clone(), (value.clone(), Some(now_ms + ms)));
+            }),
+            ("MSET", pairs) if !pairs.is_empty() && pairs.len().is_multiple_of(2) => {
+                for pair in pairs.chunks(2) {
+                    keys.insert(pair[0].clone(), (pair[1].clone(), None));
+                }
+                Some(())
+            }
+            ("DEL" | "UNLINK", names) => {
+                for key in names {
+                    keys.remove(key);
+                }
+                Some(())
+            }
+            ("EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT", [key, _, ..]) => number(1).map(|n| {
+                let at = match name.as_str() {
+                    "EXPIRE" => now_ms + n * 1000,
+                    "PEXPIRE" => now_ms + n,
+                    "EXPIREAT" => n * 1000,
+                    _ => n,
+                };
+                if let Some(entry) = keys.get_mut(key) {
+                    entry.1 = Some(at);
+                }
+            }),
+            ("PERSIST", [key]) => {
+                if let Some(entry) = keys.get_mut(key) {
+                    entry.1 = None;
+                }
+                Some(())
+            }
+            ("APPEND", [key, tail]) => {
+                keys.entry(key.clone()).or_insert((Vec::new(), None)).0.extend_from_slice(tail);
+                Some(())
+            }
+            ("INCR" | "DECR" | "INCRBY" | "DECRBY", [key, ..]) => {
+                let by = match name.as_str() {
+                    "INCR" => Some(1),
+                    "DECR" => Some(-1),
+                    "INCRBY" => number(1),
+                    _ => number(1).map(|n| -n),
+                };
+                by.and_then(|by| {
+                    let entry = keys.entry(key.clone()).or_insert((b"0".to_vec(), None));
+                    let current: i64 = std::str::from_utf8(&entry.0).ok()?.parse().ok()?;
+                    entry.0 = current.checked_add(by)?.to_string().into_bytes();
+                    Some(())
+                })
+            }
+            ("FLUSHDB" | "FLUSHALL", _) => {
+                keys.clear();
+                Some(())
+            }
+            _ => None,
+        };
+        if applied.is_none() {
+            fold.skip(name);
+        }
+    }
+}
+
+
+/// `SET key value [NX|XX] [GET] [EX s|PX ms|EXAT s|PXAT ms|KEEPTTL]`.
+fn set_with_options(keys: &mut BTreeMap<Vec<u8>, (Vec<u8>, Option<i64>)>, key: &[u8], value: &[u8], options: &[Vec<u8>], now_ms: i64) -> Option<()> {
+    let mut expires_at = None;
+    let mut keep_ttl = false;
+    let (mut nx, mut xx) = (false, false);
+    let mut i = 0;
+    while i < options.len() {
+        let option = String::from_utf8_lossy(&options[i]).to_uppercase();
+        let amount = || options.get(i + 1).and_then(|a| std::str::from_utf8(a).ok()?.parse::<i64>().ok());
+        match option.as_str() {
+            "NX" => nx = true,
+            "XX" => xx = true,
+            "GET" => {}
+            "KEEPTTL" => keep_ttl = true,
+            "EX" | "PX" | "EXAT" | "PXAT" => {
+                let n = amount()?;
+                expires_at = Some(match option.as_str() {
+                    "EX" => now_ms + n * 1000,
+                    "PX" => now_ms + n,
+                    "EXAT" => n * 1000,
+                    _ => n,
+                });
+                i += 1;
+            }
+            _ => return None,
+        }
+        i += 1;
+    }
+
+    let existing = keys.get(key);
+    if (nx && existing.is_some()) || (xx && existing.is_none()) {
+        return Some(());
+    }
+    if keep_ttl {
+        expires_at = existing.and_then(|(_, at)| *at);
+    }
+    keys.insert(key.to_vec(), (value.to_vec(), expires_at));
+    Some(())
+}
+
+
+/// The number after `prefix` in a RESP header line.
+fn parse_prefixed(line: &[u8], prefix: u8) -> Option<usize> {
+    let digits = line.strip_prefix(&[prefix])?;
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+
+fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64)
+}
+
+
+// =================================================================
+// redis_import.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod redis_import_tests {
+    use super::*;
+    use crate::MemFs;
+    use std::sync::Arc;
+
+    /// RESP encoding of one command.
+    fn resp(args: &[&str]) -> Vec<u8> {
+        let mut out = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            out.extend_from_slice(format!("${}\r\n{}\r\n", arg.len(), arg).as_bytes());
+        }
+        out
+    }
+
+    /// A small RDB: header, an aux field, db 0 with three keys, db 1 with one.
+    fn sample_rdb() -> Vec<u8> {
+        let mut rdb = b"REDIS0009".to_vec();
+        rdb.extend_from_slice(b"\xFA\x09redis-ver\x057.2.0");
+        rdb.extend_from_slice(b"\xFE\x00\xFB\x03\x01");
+        rdb.extend_from_slice(b"\x00\x05plain\x05value");
+        // An integer-encoded value, expiring far in the future (ms)
+        rdb.extend_from_slice(b"\xFC");
+        rdb.extend_from_slice(&4_000_000_000_000i64.to_le_bytes());
+        rdb.extend_from_slice(b"\x00\x03num\xC1\x39\x30");
+        // Expired in the past (seconds)
+        rdb.extend_from_slice(b"\xFD");
+        rdb.extend_from_slice(&1_000u32.to_le_bytes());
+        rdb.extend_from_slice(b"\x00\x04gone\x01x");
+        rdb.extend_from_slice(b"\xFE\x01\x00\x05other\x01y");
+        rdb.extend_from_slice(b"\xFF12345678");
+        rdb
+    }
+
+    #[test]
+    fn test_rdb_string_keys_and_ttls() {
+        let dump = parse_redis_dump(&sample_rdb(), 0).unwrap();
+        assert_eq!(dump.format, RedisFormat::Rdb);
+        let keys: Vec<(&[u8], &[u8], Option<i64>)> =
+            dump.keys.iter().map(|k| (k.key.as_slice(), k.value.as_slice(), k.expires_at_ms)).collect();
+        assert_eq!(keys, vec![
+            (&b"gone"[..], &b"x"[..], Some(1_000_000)),
+            (&b"num"[..], &b"12345"[..], Some(4_000_000_000_000)),
+            (&b"plain"[..], &b"value"[..], None),
+        ]);
+        assert_eq!(dump.skipped, vec!["db 1 x1"]);
+    }
+
+    #[test]
+    fn test_rdb_lzf_strings_and_unsupported_types() {
+        // "abcabcabc": literal "abc", then a 6 byte back reference 3 back
+        let mut rdb = b"REDIS0009\x00\x01k\xC3\x06\x09\x02abc\x80\x02".to_vec();
+        rdb.extend_from_slice(b"\xFF00000000");
+        assert_eq!(parse_redis_dump(&rdb, 0).unwrap().keys[0].value, b"abcabcabc");
+
+        let list = b"REDIS0009\x01\x04list\x01\x01a\xFF00000000";
+        assert_eq!(parse_redis_dump(list, 0).unwrap_err(), "unsupported RDB value type 1 for key 'list'");
+        assert_eq!(parse_redis_dump(b"REDIS0009\x00\x05plain", 0).unwrap_err(), "RDB file is truncated");
+    }
+
+    #[test]
+    fn test_aof_replays_string_commands() {
+        let mut aof = Vec::new();
+        for command in [
+            &["SET", "a", "1"][..],
+            &["SET", "ttl", "v", "PX", "500"],
+            &["MULTI"],
+            &["MSET", "b", "2", "c", "3"],
+            &["EXEC"],
+            &["INCRBY", "a", "41"],
+            &["APPEND", "b", "0"],
+            &["DEL", "c"],
+            &["SET", "a", "x", "NX"],
+            &["LPUSH", "list", "1"],
+            &["SELECT", "2"],
+            &["SET", "elsewhere", "1"],
+            &["SELECT", "0"],
+            &["PEXPIREAT", "b", "100"],
+        ] {
+            aof.extend(resp(command));
+        }
+        let dump = parse_redis_dump(&aof, 1_000).unwrap();
+        assert_eq!(dump.format, RedisFormat::Aof);
+        let keys: Vec<(&[u8], &[u8], Option<i64>)> =
+            dump.keys.iter().map(|k| (k.key.as_slice(), k.value.as_slice(), k.expires_at_ms)).collect();
+        assert_eq!(keys, vec![(&b"a"[..], &b"42"[..], None), (b"b", b"20", Some(100)), (b"ttl", b"v", Some(1_500))]);
+        assert_eq!(dump.skipped, vec!["LPUSH x1", "db 2 x1"]);
+
+        let mut truncated = resp(&["SET", "a", "1"]);
+        truncated.truncate(truncated.len() - 3);
+        assert_eq!(parse_redis_dump(&truncated, 0).unwrap_err(), "AOF file is truncated");
+    }
+
+    #[test]
+    fn test_aof_with_rdb_preamble() {
+        let mut file = sample_rdb();
+        file.extend(resp(&["SET", "plain", "changed"]));
+        let dump = parse_redis_dump(&file, 0).unwrap();
+        assert_eq!(dump.format, RedisFormat::Aof);
+        assert!(dump.keys.iter().any(|k| k.key == b"plain" && k.value == b"changed"));
+    }
+
+    #[test]
+    fn test_import_writes_live_keys_through_the_session() {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session.limits.max_value_len = 5;
+        let mut dump = parse_redis_dump(&sample_rdb(), 0).unwrap();
+        dump.keys.push(RedisKey { key: b"big".to_vec(), value: b"too long".to_vec(), expires_at_ms: None });
+        dump.keys.push(RedisKey { key: vec![0xFF], value: b"1".to_vec(), expires_at_ms: None });
+
+        let report = import_dump(&mut session, dump, 2_000_000);
+        assert_eq!(report.keys_imported, 2);
+        assert_eq!(report.expired_skipped, 1);
+        assert_eq!(report.ttls_not_kept, 0);
+        assert_eq!(report.skipped.len(), 3);
+        assert_eq!(session.get("num"), Some("12345".to_string()));
+        assert!(session.ttl_status("num") > 0);
+        assert_eq!(session.get("plain"), Some("value".to_string()));
+        assert!(!session.exists("gone"));
+    }
+}
@@ -0,0 +1,300 @@
+// =====================================================================
+// File: reload.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Runtime-tunable settings and their reload on SIGHUP.
+//
+//   Settings come from a `name = value` file named by `KVSTORE_CONFIG`
+//   (blank lines and `#` comments are ignored). The file is applied at
+//   startup, after the environment variables, and again whenever the
+//   process receives SIGHUP. A reload also closes the cached log handle,
+//   so the next append reopens `data.db` by name; a log replaced or
+//   rotated underneath the process is picked up without a restart.
+//
+//   The signal handler only raises a flag. The REPL checks it before each
+//   command, so a reload never runs in the middle of one.
+//
+//   Settings:
+//     sync            = all | data | dsync
+//     durability      = always | <n>ms | exit
+//     max_hot_keys    = <n>   (memory-limited sessions only)
+//     expire_budget   = <n>
+//     compact_budget  = <n>
+//     auto_compact_ratio = <0.0-1.0> | off
+//     auto_compact_bytes = <n> | off
+//     read_only_after = <n>
+//     max_key_bytes   = <n>
+//     max_value_bytes = <n>
+// =====================================================================
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::storage::{self, Durability, SyncMode};
+use crate::{load_webhooks, Acl, Fs, RetryPolicy, Session, Webhooks};
+
+/// Set by the SIGHUP handler, cleared by [`take_reload_request`].
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+
+/// Ask for a reload, exactly as SIGHUP does.
+pub fn request_reload() {
+    RELOAD_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+
+/// Returns `true` once per reload request (SIGHUP or [`request_reload`]).
+pub fn take_reload_request() -> bool {
+    RELOAD_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+
+/// Install the SIGHUP handler that requests a reload.
+///
+/// # Returns
+/// `true` if the handler is installed; always `false` off Unix.
+pub fn install_reload_signal() -> bool {
+    sys::install()
+}
+
+
+#[cfg(unix)]
+mod sys {
+    /// `SIGHUP` on every Unix this builds for.
+    const SIGHUP: i32 = 1;
+
+    /// `SIG_ERR`, the `(void *)-1` returned when `signal` fails.
+    const SIG_ERR: usize = usize::MAX;
+
+    unsafe extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    /// Only async-signal-safe work here: raise the flag and return.
+    extern "C" fn on_sighup(_: i32) {
+        super::request_reload();
+    }
+
+    pub fn install() -> bool {
+        unsafe { signal(SIGHUP, on_sighup) != SIG_ERR }
+    }
+}
+
+
+#[cfg(not(unix))]
+mod sys {
+    pub fn install() -> bool {
+        false
+    }
+}
+
+
+/// Apply one runtime setting to the session.
+///
+/// # Arguments
+/// * `name` - Setting name, as listed at the top of this file.
+/// * `value` - Its new value.
+///
+/// # Returns
+/// * `Ok(())` once the setting is in effect.
+/// * `Err(message)` for an unknown name or a value that doesn't parse.
+///
+/// # Example
+/// ```
+/// use kvstore::{apply_setting, Session};
+/// let mut session = Session::ephemeral();
+/// apply_setting(&mut session, "expire_budget", "8").unwrap();
+/// assert_eq!(session.expire_budget, 8);
+/// assert!(apply_setting(&mut session, "expire_budget", "many").is_err());
+/// assert!(apply_setting(&mut session, "colour", "blue").is_err());
+/// ```
+pub fn apply_setting(session: &mut Session, name: &str, value: &str) -> Result<(), String> {
+    let number = || value.parse::<usize>().map_err(|_| format!("{} must be a number, got '{}'", name, value));
+    match name {
+        "sync" => {
+            let mode = SyncMode::from_name(value).ok_or_else(|| format!("unknown sync mode '{}'", value))?;
+            storage::set_sync_mode(mode);
+        }
+        "durability" => {
+            let policy = Durability::from_name(value).ok_or_else(|| format!("unknown durability '{}'", value))?;
+            storage::set_durability(policy);
+        }
+        "max_hot_keys" => session.set_max_hot_keys(number()?)?,
+        "expire_budget" => session.expire_budget = number()?,
+        "compact_budget" => session.compactor.set_budget(number()?),
+        "auto_compact_ratio" => {
+            session.compaction_policy.dead_ratio = match value {
+                "off" => None,
+                _ => Some(value.parse::<f64>().ok().filter(|r| (0.0..=1.0).contains(r))
+                    .ok_or_else(|| format!("{} must be a ratio from 0 to 1 or off, got '{}'", name, value))?),
+            };
+        }
+        "auto_compact_bytes" => {
+            session.compaction_policy.max_log_bytes = match value {
+                "off" => None,
+                _ => Some(number()? as u64),
+            };
+        }
+        "read_only_after" => session.read_only_after = number()? as u32,
+        "max_key_bytes" => session.limits.max_key_len = number()?,
+        "max_value_bytes" => session.limits.max_value_len = number()?,
+        _ => return Err(format!("unknown setting '{}'", name)),
+    }
+    Ok(())
+}
+
+
+/// Apply every setting in the config file at `path`, read through `fs`.
+///
+/// Bad lines are skipped and reported; the good ones still apply.
+///
+/// # Returns
+/// * `Ok(problems)`, one `line N: ...` message per skipped line.
+/// * `Err(io::Error)` if the file can't be read.
+///
+/// # Example
+/// ```
+/// use kvstore::{load_config, Fs, MemFs, Session};
+/// let fs = MemFs::new();
+/// fs.write_file("kvstore.conf", b"# tuning\ncompact_budget = 16\nnope = 1\n");
+///
+/// let mut session = Session::ephemeral();
+/// let problems = load_config(&mut session, &fs, "kvstore.conf").unwrap();
+/// assert_eq!(session.compactor.budget(), 16);
+/// assert_eq!(problems, vec!["line 3: unknown setting 'nope'"]);
+/// ```
+pub fn load_config(session: &mut Session, fs: &dyn Fs, path: &str) -> io::Result<Vec<String>> {
+    let bytes = fs.read(path)?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut problems = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = match line.split_once('=') {
+            Some((name, value)) => apply_setting(session, name.trim(), value.trim()),
+            None => Err("expected <name> = <value>".to_string()),
+        };
+        if let Err(e) = result {
+            problems.push(format!("line {}: {}", i + 1, e));
+        }
+    }
+    Ok(problems)
+}
+
+
+/// Reopen the log and re-read the session's config and ACL files.
+///
+/// The cached log handle is closed (trimming its preallocated space), so
+/// the next append opens the data file by name again. The config file,
+/// if the session has one, is then applied with [`load_config`], and the
+/// ACL and webhook files replace the ACL and hooks; a file that fails to
+/// load keeps the old ones.
+///
+/// # Returns
+/// Problems to report: a failed close or read, and skipped config lines.
+pub fn reload(session: &mut Session) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Err(e) = storage::close_log(&session.data_file) {
+        problems.push(format!("reopening the log: {}", e));
+    }
+    if let Some(path) = session.config_path.clone() {
+        match load_config(session, &*session.fs.clone(), &path) {
+            Ok(skipped) => problems.extend(skipped.into_iter().map(|p| format!("{}: {}", path, p))),
+            Err(e) => problems.push(format!("reading {}: {}", path, e)),
+        }
+    }
+    if let Some(path) = session.acl_path.clone() {
+        match Acl::load(&*session.fs, &path) {
+            Ok(acl) => session.acl = Some(acl),
+            Err(e) => problems.push(format!("reading {}: {}", path, e)),
+        }
+    }
+    if let Some(path) = session.webhooks_path.clone() {
+        match load_webhooks(&*session.fs, &path) {
+            Ok(hooks) => {
+                let restarted = match &session.webhooks {
+                    Some(webhooks) => webhooks.restart(hooks),
+                    None => Webhooks::start(hooks, RetryPolicy::default()),
+                };
+                session.webhooks = Some(restarted);
+            }
+            Err(e) => problems.push(format!("reading {}: {}", path, e)),
+        }
+    }
+    problems
+}
+
+
+// =================================================================
+// reload.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod reload_tests {
+    use super::*;
+    use crate::MemFs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_settings_apply_and_bad_values_are_rejected() {
+        let mut session = Session::with_memory_limit(4);
+        for (name, value) in [("max_hot_keys", "2"), ("read_only_after", "3"), ("max_key_bytes", "8"), ("max_value_bytes", "9")] {
+            apply_setting(&mut session, name, value).unwrap();
+        }
+        assert_eq!(session.spill.as_ref().unwrap().capacity(), 2);
+        assert_eq!(session.read_only_after, 3);
+        assert_eq!(session.limits.max_key_len, 8);
+        assert_eq!(session.limits.max_value_len, 9);
+
+        assert_eq!(apply_setting(&mut session, "sync", "sometimes").unwrap_err(), "unknown sync mode 'sometimes'");
+        assert!(apply_setting(&mut Session::ephemeral(), "max_hot_keys", "2").is_err());
+        assert!(apply_setting(&mut Session::key_only(), "max_hot_keys", "2").is_err());
+    }
+
+    #[test]
+    fn test_lowering_max_hot_keys_evicts_values() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = Session::with_memory_limit(3);
+        session.fs = fs.clone();
+        for key in ["a", "b", "c"] {
+            session.set(key.into(), format!("{}-value", key));
+        }
+
+        session.set_max_hot_keys(1).unwrap();
+        let spill = session.spill.as_ref().unwrap();
+        assert!(spill.is_cold("a") && spill.is_cold("b") && !spill.is_cold("c"));
+        assert_eq!(session.get("a"), Some("a-value".to_string()));
+    }
+
+    #[test]
+    fn test_reload_rereads_the_config_file() {
+        let fs = Arc::new(MemFs::new());
+        fs.write_file("kv.conf", b"expire_budget = 5\n");
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        session.config_path = Some("kv.conf".to_string());
+
+        assert!(reload(&mut session).is_empty());
+        assert_eq!(session.expire_budget, 5);
+
+        fs.write_file("kv.conf", b"expire_budget = 7\nbroken line\n");
+        assert_eq!(reload(&mut session), vec!["kv.conf: line 2: expected <name> = <value>"]);
+        assert_eq!(session.expire_budget, 7);
+
+        fs.remove("kv.conf").unwrap();
+        assert!(reload(&mut session)[0].starts_with("reading kv.conf: "));
+    }
+
+    #[test]
+    fn test_reload_requests_are_taken_once() {
+        request_reload();
+        assert!(take_reload_request());
+        assert!(!take_reload_request());
+    }
+}
@@ -0,0 +1,370 @@
+// =====================================================================
+// File: repair.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Offline repair of a damaged log (`kvstore --repair`).
+//
+//   The log is read as raw bytes rather than as UTF-8 lines, so invalid
+//   UTF-8, unknown commands, truncated trailing writes, records that
+//   fail their checksum (format 2 on) and records that break the
+//   key/value limits are skipped instead of stopping the replay. In a
+//   binary log (format 5 on) a frame that fails its checksum is skipped
+//   up to the next byte where a whole frame starts. The surviving
+//   writes are folded "last write wins" and saved as a compacted
+//   `<log>.repaired` file, a format header followed by one `SET` per
+//   live key (and an `EXPIREAT` per TTL still running), next to a
+//   `<log>.repair.txt` report listing every dropped record. A header
+//   already at the top of the damaged log is neither kept as a record
+//   nor reported; a `CHECKPOINT` record after it starts the fold from
+//   the snapshot it names, and a `SEGMENT` record from the cold
+//   segments it names.
+//
+//   The original log is never modified; the operator swaps the repaired
+//   file in once the report looks right.
+// =====================================================================
+
+use std::io;
+
+use crate::storage::{self, Framed, Keyspace, ReplayOp};
+use crate::{checkpoint, compress, crypt, Fs, Limits, RealFs};
+
+/// Longest excerpt of a dropped record kept in the report.
+const EXCERPT_LEN: usize = 80;
+
+/// One record skipped during repair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedRecord {
+    /// 1-based line number in the damaged log; in a binary log, the
+    /// record's number, counting the header line as 1.
+    pub line: usize,
+
+    /// Byte offset of the record in the damaged log.
+    pub offset: u64,
+
+    /// Why the record was skipped.
+    pub reason: String,
+
+    /// Start of the record (lossy UTF-8, at most 80 bytes).
+    pub excerpt: String,
+}
+
+
+/// Outcome of a repair pass.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RepairReport {
+    /// Records read from the damaged log, including dropped ones.
+    pub records_read: usize,
+
+    /// Live keys written to the repaired file.
+    pub keys_kept: usize,
+
+    /// Records skipped, in log order.
+    pub dropped: Vec<DroppedRecord>,
+
+    /// Path of the compacted clean log.
+    pub repaired_path: String,
+
+    /// Path of the dropped-record report.
+    pub report_path: String,
+}
+
+
+/// Rebuild a clean, compacted copy of the log at `path`.
+///
+/// # Arguments
+/// * `path` - The damaged log; it is only read.
+/// * `limits` - Size limits a kept record must satisfy.
+///
+/// # Returns
+/// * `Ok(RepairReport)` once `<path>.repaired` and `<path>.repair.txt`
+///   are written.
+/// * `Err(io::Error)` if the log cannot be read or an output cannot be
+///   written.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_bytes, log_text, repair_log, Limits};
+/// let path = std::env::temp_dir().join("kvstore_repair_doc.db");
+/// let path = path.to_string_lossy().into_owned();
+/// std::fs::write(&path, "SET a 1\nGARBAGE\nSET b 2\nDEL a\nSET c").unwrap();
+///
+/// let report = repair_log(&path, &Limits::default()).unwrap();
+/// assert_eq!(report.keys_kept, 1);
+/// assert_eq!(report.dropped.len(), 2);
+/// assert_eq!(std::fs::read(&report.repaired_path).unwrap(), log_bytes(&log_text(&["SET b 2"])).unwrap());
+/// # for p in [&path, &report.repaired_path, &report.report_path] { std::fs::remove_file(p).unwrap(); }
+/// ```
+pub fn repair_log(path: &str, limits: &Limits) -> io::Result<RepairReport> {
+    repair_log_with(&RealFs, path, limits)
+}
+
+
+/// Like [`repair_log`], with every file read and written through `fs`.
+pub fn repair_log_with(fs: &dyn Fs, path: &str, limits: &Limits) -> io::Result<RepairReport> {
+    let bytes = fs.read(path)?;
+    let mut live = Keyspace::default();
+    let mut report = RepairReport {
+        repaired_path: storage::sidecar_path(path, "repaired"),
+        report_path: storage::sidecar_path(path, "repair.txt"),
+        ..RepairReport::default()
+    };
+
+    let key = fs.log_key();
+    let mut offset = 0usize;
+    let mut checked = false;
+    for (i, raw) in bytes.split_inclusive(|&b| b == b'\n').enumerate() {
+        let record = raw.strip_suffix(b"\n").unwrap_or(raw);
+        let record = record.strip_suffix(b"\r").unwrap_or(record);
+        let header = match report.records_read {
+            0 => std::str::from_utf8(record).ok().and_then(|l| storage::parse_header(l.trim())),
+            _ => None,
+        };
+        if let Some(version) = header {
+            checked = version >= storage::CHECKSUMS_SINCE;
+        }
+        if header.is_some() || record.iter().all(u8::is_ascii_whitespace) {
+            // Blank lines and the header carry nothing; they are neither kept nor reported
+            offset += raw.len();
+            if header.is_some_and(|version| version >= storage::BINARY_SINCE) {
+                repair_frames(fs, path, &bytes, offset, limits, &mut live, &mut report);
+                break;
+            }
+            continue;
+        }
+        report.records_read += 1;
+
+        let reason = match std::str::from_utf8(record) {
+            _ if !raw.ends_with(b"\n") => Some("truncated record".to_string()),
+            Err(_) => Some("invalid UTF-8".to_string()),
+            Ok(line) => match storage::open_record(line, key.as_deref()) {
+                Err(e) => Some(e.to_string()),
+                Ok(None) if checked => Some("checksum mismatch".to_string()),
+                Ok(unsealed) => fold_record(fs, path, unsealed.as_deref().unwrap_or(line), report.records_read == 1, limits, &mut live).err(),
+            },
+        };
+        if let Some(reason) = reason {
+            report.push_dropped(i + 1, offset, reason, record);
+        }
+        offset += raw.len();
+    }
+
+    fs.create(&report.repaired_path)?;
+    fs.append(&report.repaired_path, &storage::log_text_after(&live.records(), storage::last_seq(fs, path)?))?;
+    fs.sync(&report.repaired_path)?;
+    report.keys_kept = live.values.len();
+
+    let mut summary = vec![
+        format!("repair of {}", path),
+        format!("records_read: {}", report.records_read),
+        format!("keys_kept: {}", report.keys_kept),
+        format!("records_dropped: {}", report.dropped.len()),
+    ];
+    for d in &report.dropped {
+        summary.push(format!("line {} (offset {}): {}: {}", d.line, d.offset, d.reason, d.excerpt));
+    }
+    fs.create(&report.report_path)?;
+    fs.append(&report.report_path, &summary.join("\n"))?;
+    fs.sync(&report.report_path)?;
+
+    Ok(report)
+}
+
+
+/// The frames of a binary log, from byte `start` (just past its header)
+/// of `bytes` on: whole frames are folded into `live`, and the rest is
+/// dropped into `report`.
+fn repair_frames(fs: &dyn Fs, path: &str, bytes: &[u8], start: usize, limits: &Limits, live: &mut Keyspace, report: &mut RepairReport) {
+    let key = fs.log_key();
+    let mut at = start;
+    while at < bytes.len() {
+        if bytes[at] == 0 {
+            at += 1; // zero padding
+            continue;
+        }
+        report.records_read += 1;
+        let number = report.records_read + 1;
+        let (len, reason) = match storage::frame_at(&bytes[at..]) {
+            Framed::Whole(len, record, stamp) => {
+                let folded = crypt::decrypt_record(key.as_deref(), &record, stamp)
+                    .map_err(|e| e.to_string())
+                    .and_then(|record| fold_record(fs, path, &record, report.records_read == 1, limits, live));
+                (len, folded.err())
+            }
+            Framed::Cut => (bytes.len() - at, Some("truncated record".to_string())),
+            Framed::Bad => {
+                // Skip to the next place a whole frame starts
+                let next = (at + 1..bytes.len())
+                    .find(|&next| matches!(storage::frame_at(&bytes[next..]), Framed::Whole(..)))
+                    .unwrap_or(bytes.len());
+                (next - at, Some("checksum mismatch".to_string()))
+            }
+        };
+        if let Some(reason) = reason {
+            report.push_dropped(number, at, reason, &bytes[at..at + len]);
+        }
+        at += len;
+    }
+}
+
+
+/// Fold the record `line` (without its checksum) into `live`; as the
+/// `first` record, a `CHECKPOINT` or `SEGMENT` starts it from what that
+/// names.
+fn fold_record(fs: &dyn Fs, path: &str, line: &str, first: bool, limits: &Limits, live: &mut Keyspace) -> Result<(), String> {
+    let base = checkpoint::parse_checkpoint_record(line).is_some() || compress::parse_segment_record(line).is_some();
+    if first && base {
+        return apply_base(fs, path, line, limits, live);
+    }
+    apply_record(line, limits, live)
+}
+
+
+impl RepairReport {
+    /// Report `record`, which starts at `offset` on `line`, as dropped.
+    fn push_dropped(&mut self, line: usize, offset: usize, reason: String, record: &[u8]) {
+        let cut = record.len().min(EXCERPT_LEN);
+        self.dropped.push(DroppedRecord {
+            line,
+            offset: offset as u64,
+            reason,
+            excerpt: String::from_utf8_lossy(&record[..cut]).into_owned(),
+        });
+    }
+}
+
+
+/// Start `live` from the snapshot a `CHECKPOINT` record points at, or
+/// the cold segments a `SEGMENT` record does.
+fn apply_base(fs: &dyn Fs, path: &str, record: &str, limits: &Limits, live: &mut Keyspace) -> Result<(), String> {
+    let mut records = vec![(0, record.to_string())];
+    let base = checkpoint::load_base(fs, path, &mut records).map_err(|e| e.to_string())?;
+    live.values.extend(base.into_iter().flat_map(|base| base.pairs));
+    // Segment records have no line of the log to report, so one that
+    // breaks the limits is left out without a note
+    for (_, line) in records {
+        let _ = apply_record(&line, limits, live);
+    }
+    Ok(())
+}
+
+
+/// Fold one well-formed record into `live`.
+///
+/// Every write in the record is checked before any is applied, so a bad
+/// pair in an `MSET` drops the whole record, matching the live command.
+fn apply_record(line: &str, limits: &Limits, live: &mut Keyspace) -> Result<(), String> {
+    let ops = storage::decode_record(0, line);
+    if ops.is_empty() {
+        return Err("unparseable record".to_string());
+    }
+
+    for op in &ops {
+        let checked = match op {
+            ReplayOp::Set(key, value, _) => limits.check_write(key, value),
+            ReplayOp::SetRef(key, _) | ReplayOp::Del(key) | ReplayOp::ExpireAt(key, _) | ReplayOp::Persist(key) => {
+                limits.check_key(key)
+            }
+        };
+        checked.map_err(|e| format!("rejected record ({})", e))?;
+    }
+
+    for op in ops {
+        live.apply(op);
+    }
+    Ok(())
+}
+
+
+// =================================================================
+// repair.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod repair_tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn test_file(name: &str) -> String {
+        let mut p: PathBuf = std::env::temp_dir();
+        p.push(format!("kvstore_repair_{}.db", name));
+        p.to_string_lossy().into_owned()
+    }
+
+    fn clean(report: &RepairReport, path: &str) {
+        for p in [path, &report.repaired_path, &report.report_path] {
+            let _ = fs::remove_file(p);
+        }
+    }
+
+    #[test]
+    fn test_repair_skips_corrupt_records_and_compacts() {
+        let path = test_file("corrupt");
+        let mut log = b"SET a 1\nMSET b 2 c 3\n".to_vec();
+        log.extend_from_slice(b"SET \xff\xfe 9\n");
+        log.extend_from_slice(b"MSET d\n");
+        log.extend_from_slice(b"\n");
+        log.extend_from_slice(b"SET a 4\nDEL c\nSET long xxxxxxxx\nSET e");
+        fs::write(&path, &log).unwrap();
+
+        let limits = Limits { max_key_len: 16, max_value_len: 4 };
+        let report = repair_log(&path, &limits).unwrap();
+
+        assert_eq!(report.records_read, 8);
+        assert_eq!(report.keys_kept, 2);
+        let reasons: Vec<(usize, &str)> = report.dropped.iter().map(|d| (d.line, d.reason.as_str())).collect();
+        assert_eq!(reasons, vec![
+            (3, "invalid UTF-8"),
+            (4, "unparseable record"),
+            (8, "rejected record (value is too long (8 bytes, max 4))"),
+            (9, "truncated record"),
+        ]);
+        assert_eq!(report.dropped[1].offset, 30);
+        assert_eq!(fs::read(&report.repaired_path).unwrap(), storage::log_bytes(&storage::log_text(&["SET a 4", "SET b 2"])).unwrap());
+
+        let summary = fs::read_to_string(&report.report_path).unwrap();
+        assert!(summary.contains("records_dropped: 4"));
+        assert!(summary.contains("line 9 (offset "));
+
+        clean(&report, &path);
+    }
+
+    #[test]
+    fn test_repair_resyncs_after_a_damaged_frame() {
+        let path = test_file("frames");
+        let frames = |records: &[&str]| storage::log_bytes(&storage::log_text(records)).unwrap();
+        let good = frames(&["SET a 1"]);
+        let mut log = frames(&["SET a 1", "SET b 2", "SET c 3"]);
+        let bad_at = good.len() + 6;
+        log[bad_at] ^= 1;
+        let torn = frames(&["SET d 4"]).split_off(storage::header_record().len() + 1);
+        log.extend_from_slice(&torn[..torn.len() - 9]);
+        fs::write(&path, &log).unwrap();
+
+        let report = repair_log(&path, &Limits::default()).unwrap();
+        assert_eq!(report.records_read, 4);
+        let reasons: Vec<(usize, &str)> = report.dropped.iter().map(|d| (d.line, d.reason.as_str())).collect();
+        assert_eq!(reasons, vec![(3, "checksum mismatch"), (5, "truncated record")]);
+        assert_eq!(report.dropped[0].offset, good.len() as u64);
+        assert_eq!(fs::read(&report.repaired_path).unwrap(), storage::log_bytes(&storage::log_text(&["SET a 1", "SET c 3"])).unwrap());
+
+        clean(&report, &path);
+    }
+
+    #[test]
+    fn test_repaired_log_keeps_escaped_fields() {
+        let path = test_file("escaped");
+        let record = storage::set_record("a key", "two\nlines");
+        fs::write(&path, format!("KVSTORE 1\n{}\n", record)).unwrap();
+
+        let report = repair_log(&path, &Limits::default()).unwrap();
+        assert!(report.dropped.is_empty());
+        assert_eq!(report.records_read, 1);
+        assert_eq!(fs::read(&report.repaired_path).unwrap(), storage::log_bytes(&storage::log_text(&[record])).unwrap());
+
+        clean(&report, &path);
+    }
+}
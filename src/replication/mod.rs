@@ -0,0 +1,43 @@
+// =====================================================================
+// File: replication/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `replication` module ships the log from a primary to replicas.
+//!
+//! Structure:
+//! - `primary.rs` : Defines the [`ReplicationLog`], which takes every
+//!   record the primary appends under the sequence number stamped on it
+//!   in the log, keeps a bounded backlog of them and
+//!   hands them to each connected replica, and [`start_sync`], which
+//!   decides between resuming a replica and a full sync.
+//! - `replica.rs` : Defines the [`Replica`], which follows a primary on
+//!   a worker thread and queues the records it receives for the session.
+//! - `tests.rs`   : Unit tests for sequencing, the backlog and following
+//!   a primary over TCP.
+//!
+//! A replica connects to the primary's server port and sends
+//! `REPLICATE <replid> <seq>`: the replication id it follows (`?` for
+//! none) and the first sequence number it still needs. The primary can
+//! resume from there, or first send a full sync:
+//!
+//!   FULLSYNC <replid> <seq> <count>   then <count> SET records
+//!
+//! Either way it then answers `STREAM <replid> <seq>` and one
+//! `<seq> <record>` line per appended record, from the backlog first and
+//! then as they are written. A snapshot replaces the replica's data file
+//! and index; each streamed record is appended to the data file and
+//! applied to the index, exactly as replaying the log would, so the
+//! replica serves the same reads and holds a warm copy of the data.
+// =====================================================================
+
+pub mod primary;
+pub mod replica;
+
+pub use self::primary::{start_sync, ReplicationLog, SyncStart, DEFAULT_BACKLOG};
+pub use self::replica::{parse_stream_line, Replica, ReplicaEvent};
+
+#[cfg(test)]
+pub mod tests;
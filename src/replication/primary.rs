@@ -0,0 +1,245 @@
+// =====================================================================
+// File: replication/primary.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Primary side of replication: sequence numbers, the backlog of
+//   recent records, and the channels feeding connected replicas.
+//
+//   A record's sequence number is the one stamped on it in the log (see
+//   `storage::Stamp`), so offsets carry on across restarts and
+//   compactions, and a replica's position names a record in the log. A
+//   replica that falls further behind than the
+//   backlog holds, or stops reading until its channel fills up, is
+//   disconnected rather than buffered without bound.
+//
+//   Every log gets a replication id, so a replica can tell our sequence
+//   numbers from another run's. A replica with no state, another id or a
+//   sequence number the backlog no longer covers gets a full sync: every
+//   live key, then the stream from the snapshot's sequence number on.
+// =====================================================================
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::storage;
+use crate::Session;
+
+/// Records kept for replicas that reconnect, unless configured otherwise.
+pub const DEFAULT_BACKLOG: usize = 10_000;
+
+/// Keeps appended records by sequence number and fans them out to replicas.
+#[derive(Debug)]
+pub struct ReplicationLog {
+    /// Names this run's backlog.
+    replid: String,
+
+    /// Sequence number of the last record published, or of the last one
+    /// in the log when a full sync was taken (`0` before either).
+    last_seq: u64,
+
+    /// The most recent records, oldest first.
+    backlog: VecDeque<(u64, String)>,
+
+    /// Most records the backlog holds; a replica may lag twice as far.
+    capacity: usize,
+
+    /// One channel per connected replica.
+    followers: Vec<SyncSender<(u64, String)>>,
+}
+
+
+impl ReplicationLog {
+    /// Creates a log keeping the last `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        ReplicationLog {
+            replid: new_replid(),
+            last_seq: 0,
+            backlog: VecDeque::new(),
+            capacity: capacity.max(1),
+            followers: Vec::new(),
+        }
+    }
+
+
+    /// Replication id of this log.
+    pub fn replid(&self) -> &str {
+        &self.replid
+    }
+
+
+    /// Most records the backlog holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+
+    /// Sequence number of the last record published (`0` before any).
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+
+    /// Number of replicas currently streaming.
+    pub fn followers(&self) -> usize {
+        self.followers.len()
+    }
+
+
+    /// Keeps one appended record, stamped `seq` in the log, and sends it
+    /// to every replica.
+    ///
+    /// Sequence numbers only grow, but may skip numbers a failed append
+    /// took. A replica whose channel is full or closed is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ReplicationLog;
+    /// let mut log = ReplicationLog::new(8);
+    /// log.publish(41, "SET a 1");
+    /// let stream = log.subscribe(41).unwrap();
+    /// log.publish(42, "DEL a");
+    /// assert_eq!(log.last_seq(), 42);
+    /// assert_eq!(stream.try_iter().collect::<Vec<_>>(),
+    ///            vec![(41, "SET a 1".to_string()), (42, "DEL a".to_string())]);
+    /// ```
+    pub fn publish(&mut self, seq: u64, record: &str) {
+        self.last_seq = self.last_seq.max(seq);
+        let mut span = crate::telemetry::span("kvstore.replication.publish");
+        span.attr("kvstore.repl.seq", seq).attr("kvstore.repl.followers", self.followers.len());
+
+        if self.backlog.len() == self.capacity {
+            self.backlog.pop_front();
+        }
+        self.backlog.push_back((seq, record.to_string()));
+
+        self.followers.retain(|tx| tx.try_send((seq, record.to_string())).is_ok());
+    }
+
+
+    /// Starts a stream for a replica that needs records from `from_seq` on.
+    ///
+    /// The channel is filled with the backlog from `from_seq`, then gets
+    /// every record published later.
+    ///
+    /// # Returns
+    /// * `Ok(receiver)` for the stream.
+    /// * `Err(message)` if records the replica needs already left the
+    ///   backlog, or `from_seq` is past the next record.
+    pub fn subscribe(&mut self, from_seq: u64) -> Result<Receiver<(u64, String)>, String> {
+        let next = self.last_seq + 1;
+        let oldest = self.backlog.front().map_or(next, |(seq, _)| *seq);
+        if from_seq < oldest || from_seq > next {
+            return Err(format!("sequence {} is not in the backlog (oldest {}, next {})", from_seq, oldest, next));
+        }
+
+        let (tx, rx) = mpsc::sync_channel(self.capacity * 2);
+        for (seq, record) in self.backlog.iter().filter(|(seq, _)| *seq >= from_seq) {
+            // Fits: the channel holds twice the backlog
+            let _ = tx.try_send((*seq, record.clone()));
+        }
+        self.followers.push(tx);
+        Ok(rx)
+    }
+}
+
+
+/// How a replica's stream starts, decided by [`start_sync`].
+#[derive(Debug)]
+pub struct SyncStart {
+    /// The primary's replication id.
+    pub replid: String,
+
+    /// For a full sync, the sequence number the snapshot is current to
+    /// and a `SET` record per live key.
+    pub snapshot: Option<(u64, Vec<String>)>,
+
+    /// First sequence number on `records`.
+    pub from_seq: u64,
+
+    /// Records from `from_seq` on, as they are published.
+    pub records: Receiver<(u64, String)>,
+}
+
+
+/// Starts streaming to a replica that last applied `from_seq - 1` of `replid`.
+///
+/// The replica continues where it left off when `replid` is ours and the
+/// backlog still covers `from_seq`; otherwise it gets a snapshot of every
+/// live key, taken together with its subscription so no write falls
+/// between the two.
+///
+/// # Returns
+/// `Err(message)` if the session doesn't replicate, is still loading or
+/// can't read the last sequence number back from its log.
+///
+/// # Example
+/// ```
+/// use kvstore::{start_sync, MemFs, ReplicationLog, Session};
+/// let mut session = Session::ephemeral();
+/// session.fs = std::sync::Arc::new(MemFs::new());
+/// session.replication = Some(ReplicationLog::new(8));
+/// session.index.insert("a".into(), "1".into());
+/// session.live_keys.insert("a".into());
+///
+/// let sync = start_sync(&mut session, "?", 1).unwrap();
+/// assert_eq!(sync.snapshot, Some((0, vec!["SET a 1".to_string()])));
+/// assert_eq!(sync.from_seq, 1);
+/// ```
+pub fn start_sync(session: &mut Session, replid: &str, from_seq: u64) -> Result<SyncStart, String> {
+    let mut span = crate::telemetry::span("kvstore.replication.sync");
+    span.attr("kvstore.repl.from_seq", from_seq);
+    if session.is_loading() {
+        return Err("LOADING dataset is still being replayed".to_string());
+    }
+    if session.replication.is_none() {
+        return Err("replication is not enabled on this server".to_string());
+    }
+    // The snapshot is current to the last record in the log, which this
+    // run may not have published
+    let log_seq = session.log_seq()?;
+    let replication = session.replication.as_mut().ok_or("replication is not enabled on this server")?;
+    let ours = replication.replid.clone();
+
+    if replid == ours
+        && let Ok(records) = replication.subscribe(from_seq)
+    {
+        span.attr("kvstore.repl.full_sync", false);
+        return Ok(SyncStart { replid: ours, snapshot: None, from_seq, records });
+    }
+    span.attr("kvstore.repl.full_sync", true);
+
+    replication.last_seq = replication.last_seq.max(log_seq);
+    let seq = replication.last_seq;
+    let records = replication.subscribe(seq + 1)?;
+    let mut keys = Vec::new();
+    session.index.collect_keys(&mut keys);
+    let mut snapshot = Vec::with_capacity(keys.len());
+    for key in keys {
+        if let Some(value) = session.get_bytes(&key) {
+            snapshot.push(storage::set_record_bytes(&key, &value));
+            if let Some(at) = session.ttl.expires_at(&key) {
+                snapshot.push(storage::expire_at_record(&key, at));
+            }
+        }
+    }
+    Ok(SyncStart { replid: ours, snapshot: Some((seq, snapshot)), from_seq: seq + 1, records })
+}
+
+
+/// A replication id unlikely to repeat across runs.
+fn new_replid() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    format!("{:016x}", nanos ^ (u64::from(std::process::id()) << 40))
+}
+
+
+impl Default for ReplicationLog {
+    fn default() -> Self {
+        ReplicationLog::new(DEFAULT_BACKLOG)
+    }
+}
@@ -0,0 +1,244 @@
+// =====================================================================
+// File: replication/replica.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Replica side of replication: a worker thread that connects to the
+//   primary, sends `REPLICATE <replid> <seq>` and queues the snapshot and
+//   every streamed record.
+//
+//   The session drains the queue between commands (see
+//   `Session::poll_replication`), so records are applied on the
+//   session's own thread like any other write. When the link drops the
+//   worker reports it and reconnects, asking for the record after the
+//   last one it received; the primary sends a new snapshot if it can't
+//   resume from there.
+// =====================================================================
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Pause between reconnection attempts.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How long to wait for the primary to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a blocked read wakes up to check for a stop request.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the worker thread reports to the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicaEvent {
+    /// A full copy of the primary's data, current to sequence number
+    /// `seq`: one `SET` record per live key.
+    Snapshot { seq: u64, records: Vec<String> },
+
+    /// The primary accepted the stream.
+    Connected,
+
+    /// One record, with its sequence number on the primary.
+    Record(u64, String),
+
+    /// The link failed; the worker retries after a short pause.
+    Lost(String),
+}
+
+
+/// A stream of log records from a primary.
+#[derive(Debug)]
+pub struct Replica {
+    /// Primary address, as `host:port`.
+    pub primary: String,
+
+    /// Sequence number of the last record applied (`0` before any).
+    pub applied_seq: u64,
+
+    /// `true` while the primary is streaming to us.
+    pub link_up: bool,
+
+    /// Events from the worker thread.
+    events: Receiver<ReplicaEvent>,
+
+    /// Tells the worker thread to stop once this replica is dropped.
+    stop: Arc<AtomicBool>,
+}
+
+
+impl Replica {
+    /// Starts following `primary`, beginning with a full sync.
+    ///
+    /// Returns at once; the worker connects (and reconnects) in the
+    /// background until the replica is dropped. A reconnect resumes after
+    /// the last record received if the primary's backlog still has it.
+    pub fn follow(primary: &str) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = stop.clone();
+        let addr = primary.to_string();
+        thread::spawn(move || follow_worker(&addr, &tx, &worker_stop));
+
+        Replica {
+            primary: primary.to_string(),
+            applied_seq: 0,
+            link_up: false,
+            events: rx,
+            stop,
+        }
+    }
+
+
+    /// Takes the events reported since the last call.
+    pub fn next_events(&mut self) -> Vec<ReplicaEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+
+impl Drop for Replica {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+
+/// Splits one streamed `<seq> <record>` line.
+///
+/// # Example
+/// ```
+/// use kvstore::parse_stream_line;
+/// assert_eq!(parse_stream_line("7 SET a 1"), Some((7, "SET a 1")));
+/// assert_eq!(parse_stream_line("SET a 1"), None);
+/// ```
+pub fn parse_stream_line(line: &str) -> Option<(u64, &str)> {
+    let (seq, record) = line.split_once(' ')?;
+    Some((seq.parse().ok()?, record))
+}
+
+
+/// Where the worker is in the primary's log.
+struct Position {
+    /// Replication id followed so far (`None` before the first sync).
+    replid: Option<String>,
+
+    /// First sequence number still needed.
+    next_seq: u64,
+}
+
+
+/// Worker loop: stream, and on failure report it and reconnect.
+fn follow_worker(addr: &str, tx: &Sender<ReplicaEvent>, stop: &AtomicBool) {
+    let mut position = Position { replid: None, next_seq: 1 };
+    while !stop.load(Ordering::SeqCst) {
+        let Err(e) = stream_from(addr, &mut position, tx, stop) else {
+            return;
+        };
+        if tx.send(ReplicaEvent::Lost(e.to_string())).is_err() {
+            return;
+        }
+        thread::sleep(RETRY_DELAY);
+    }
+}
+
+
+/// One connection to the primary, forwarding records until it fails.
+///
+/// # Returns
+/// `Ok(())` once asked to stop (or nobody is listening); `Err` when the
+/// link fails.
+fn stream_from(addr: &str, position: &mut Position, tx: &Sender<ReplicaEvent>, stop: &AtomicBool) -> io::Result<()> {
+    let target = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", addr)))?;
+    let mut stream = TcpStream::connect_timeout(&target, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let replid = position.replid.as_deref().unwrap_or("?");
+    stream.write_all(format!("REPLICATE {} {}\n", replid, position.next_seq).as_bytes())?;
+    let mut reader = BufReader::new(stream);
+
+    let Some(mut header) = read_line(&mut reader, stop)? else {
+        return Ok(());
+    };
+
+    // FULLSYNC <replid> <seq> <count>, then the snapshot's records
+    if let Some(rest) = header.strip_prefix("FULLSYNC ") {
+        let fields: Vec<&str> = rest.split(' ').collect();
+        let [replid, seq, count] = fields.as_slice() else {
+            return Err(bad_line(&header));
+        };
+        let (Ok(seq), Ok(count)) = (seq.parse::<u64>(), count.parse::<usize>()) else {
+            return Err(bad_line(&header));
+        };
+        let replid = replid.to_string();
+        let mut records = Vec::with_capacity(count);
+        for _ in 0..count {
+            let Some(record) = read_line(&mut reader, stop)? else {
+                return Ok(());
+            };
+            records.push(record);
+        }
+        if tx.send(ReplicaEvent::Snapshot { seq, records }).is_err() {
+            return Ok(());
+        }
+        *position = Position { replid: Some(replid), next_seq: seq + 1 };
+        header = match read_line(&mut reader, stop)? {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+    }
+
+    // STREAM <replid> <seq>, then one record per line
+    let Some((replid, _)) = header.strip_prefix("STREAM ").and_then(|rest| rest.split_once(' ')) else {
+        return Err(io::Error::other(format!("primary refused: {}", header)));
+    };
+    position.replid = Some(replid.to_string());
+    if tx.send(ReplicaEvent::Connected).is_err() {
+        return Ok(());
+    }
+    while let Some(line) = read_line(&mut reader, stop)? {
+        let (seq, record) = parse_stream_line(&line).ok_or_else(|| bad_line(&line))?;
+        position.next_seq = seq + 1;
+        if tx.send(ReplicaEvent::Record(seq, record.to_string())).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+
+/// Reads one line, without its line ending.
+///
+/// # Returns
+/// `Ok(None)` once asked to stop; `Err` at end of stream.
+fn read_line(reader: &mut impl BufRead, stop: &AtomicBool) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        // A timed-out read keeps what it got; the rest of the line follows
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "primary closed the stream")),
+            Ok(_) if line.ends_with(b"\n") => break,
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let text = String::from_utf8_lossy(&line);
+    Ok(Some(text.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+
+fn bad_line(line: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad stream line: {}", line))
+}
@@ -0,0 +1,212 @@
+// =====================================================================
+// File: replication/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for the primary's sequence numbers and backlog, and for a
+//   replica following a primary over TCP.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// ReplicationLog Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod replication_log_tests {
+    use crate::{start_sync, Fs, ReplicationLog, Session};
+
+    #[test]
+    fn records_are_numbered_and_trimmed_to_the_backlog() {
+        let mut log = ReplicationLog::new(2);
+        assert_eq!(log.last_seq(), 0);
+        for (seq, record) in (1..).zip(["SET a 1", "SET b 2", "DEL a"]) {
+            log.publish(seq, record);
+        }
+        assert_eq!(log.last_seq(), 3);
+
+        // Record 1 was trimmed; 2 onward (or just the future) still streams
+        assert_eq!(log.subscribe(1).unwrap_err(), "sequence 1 is not in the backlog (oldest 2, next 4)");
+        let from_two = log.subscribe(2).unwrap();
+        assert_eq!(from_two.try_iter().map(|(seq, _)| seq).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(log.subscribe(4).unwrap().try_recv().is_err());
+        assert!(log.subscribe(5).is_err());
+    }
+
+    #[test]
+    fn gone_or_stalled_replicas_are_dropped() {
+        let mut log = ReplicationLog::new(1);
+        let gone = log.subscribe(1).unwrap();
+        let stalled = log.subscribe(1).unwrap();
+        drop(gone);
+        log.publish(1, "SET a 1");
+        assert_eq!(log.followers(), 1);
+
+        // Twice the backlog unread, and the stalled replica is cut off
+        log.publish(2, "SET a 2");
+        log.publish(3, "SET a 3");
+        assert_eq!(log.followers(), 0);
+        assert_eq!(stalled.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn replicas_resume_or_fall_back_to_a_full_sync() {
+        let mut session = Session::new();
+        session.fs = std::sync::Arc::new(crate::MemFs::new());
+        session.replication = Some(ReplicationLog::new(2));
+        for (key, value) in [("a", "1"), ("b", "2"), ("a", "3")] {
+            session.set(key.into(), value.into());
+        }
+        let replid = session.replication.as_ref().unwrap().replid().to_string();
+
+        // Same log, still in the backlog: resume
+        let resumed = start_sync(&mut session, &replid, 3).unwrap();
+        assert!(resumed.snapshot.is_none());
+        assert_eq!(resumed.records.try_iter().map(|(seq, _)| seq).collect::<Vec<_>>(), vec![3]);
+
+        // Trimmed from the backlog, or another log's numbers: snapshot
+        for (id, seq) in [(replid.as_str(), 1), ("0123456789abcdef", 3), ("?", 1)] {
+            let full = start_sync(&mut session, id, seq).unwrap();
+            assert_eq!(full.snapshot, Some((3, vec!["SET a 3".to_string(), "SET b 2".to_string()])));
+            assert_eq!(full.from_seq, 4);
+        }
+        assert!(start_sync(&mut Session::ephemeral(), "?", 1).is_err());
+    }
+
+    #[test]
+    fn offsets_are_the_sequence_numbers_stamped_in_the_log() {
+        let fs = std::sync::Arc::new(crate::MemFs::new());
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        for (key, value) in [("a", "1"), ("b", "2")] {
+            session.set(key.into(), value.into());
+        }
+
+        // A restart: the log's numbers carry on, not a fresh count
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        session.replication = Some(ReplicationLog::new(8));
+        let full = start_sync(&mut session, "?", 1).unwrap();
+        assert_eq!(full.snapshot.map(|(seq, _)| seq), Some(2));
+        session.set("c".into(), "3".into());
+        assert_eq!(full.records.try_iter().collect::<Vec<_>>(), vec![(3, "SET c 3".to_string())]);
+
+        let log = fs.read(&session.data_file).unwrap();
+        let last = String::from_utf8(log).unwrap().lines().last().unwrap().to_string();
+        assert_eq!(crate::unseal_stamped(&last).and_then(|(_, stamp)| stamp).map(|s| s.seq), Some(3));
+    }
+}
+
+
+// =====================================================================
+// Replica Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod replica_tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::storage::{get_data_file, log_bytes, log_text_after};
+    use crate::{load_data, serve, MemFs, Replica, ReplicationLog, ServerConfig, Session};
+
+    /// Start a primary server with its log in memory.
+    fn start_primary(backlog: usize) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session.replication = Some(ReplicationLog::new(backlog));
+        thread::spawn(move || serve(listener, session, ServerConfig::new()));
+        addr
+    }
+
+    /// Run commands on the primary, waiting for one reply per line.
+    fn run(addr: SocketAddr, commands: &str) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(commands.as_bytes()).unwrap();
+        let mut reader = BufReader::new(stream);
+        for _ in commands.lines() {
+            reader.read_line(&mut String::new()).unwrap();
+        }
+    }
+
+    /// Poll the replica until `done` holds, for up to five seconds.
+    fn wait_for(replica: &mut Session, done: impl Fn(&mut Session) -> bool) -> bool {
+        (0..500).any(|_| {
+            replica.poll_replication();
+            done(replica) || {
+                thread::sleep(Duration::from_millis(10));
+                false
+            }
+        })
+    }
+
+    #[test]
+    fn replica_applies_the_stream_to_its_index_and_log() {
+        let primary = start_primary(100);
+        run(primary, "SET a 1\nMSET b 2 c 3\n");
+
+        let fs = Arc::new(MemFs::new());
+        let mut replica = Session::new();
+        replica.fs = fs.clone();
+        replica.replica = Some(Replica::follow(&primary.to_string()));
+        assert!(wait_for(&mut replica, |r| r.get("c").is_some()));
+
+        // Later writes keep streaming
+        run(primary, "DEL a\nSET b 20\n");
+        assert!(wait_for(&mut replica, |r| r.get("b") == Some("20".to_string())));
+        assert_eq!(replica.get("a"), None);
+        let link = replica.replica.as_ref().unwrap();
+        assert!(link.link_up);
+        assert_eq!(link.applied_seq, 5); // MSET logs one record per pair
+
+        // The replica's own log replays to the same data
+        let mut restarted = Session::new();
+        restarted.fs = fs.clone();
+        load_data(&mut restarted, &get_data_file());
+        assert_eq!(restarted.get("b"), Some("20".to_string()));
+        assert_eq!(restarted.get("c"), Some("3".to_string()));
+        assert_eq!(restarted.get("a"), None);
+    }
+
+    #[test]
+    fn full_sync_replaces_stale_replica_data() {
+        // Writes the backlog no longer holds still reach the replica
+        let primary = start_primary(1);
+        run(primary, "SET a 1\nSET b 2\nSET a 3\n");
+
+        let fs = Arc::new(MemFs::new());
+        let mut replica = Session::new();
+        replica.fs = fs.clone();
+        replica.set("stale".into(), "x".into());
+        replica.replica = Some(Replica::follow(&primary.to_string()));
+        assert!(wait_for(&mut replica, |r| r.get("a") == Some("3".to_string())));
+        assert_eq!(replica.get("b"), Some("2".to_string()));
+        assert!(!replica.exists("stale"));
+        assert_eq!(replica.replica.as_ref().unwrap().applied_seq, 3);
+        assert_eq!(fs.contents(&get_data_file()).unwrap(), log_bytes(&log_text_after(&["SET a 3", "SET b 2"], 1)).unwrap());
+
+        run(primary, "SET c 4\n");
+        assert!(wait_for(&mut replica, |r| r.exists("c")));
+        assert_eq!(replica.replica.as_ref().unwrap().applied_seq, 4);
+    }
+
+    #[test]
+    fn primary_refuses_malformed_requests() {
+        let primary = start_primary(100);
+        let mut stream = TcpStream::connect(primary).unwrap();
+        stream.write_all(b"REPLICATE 9\n").unwrap();
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply).unwrap();
+        assert_eq!(reply, "ERR REPLICATE requires <replid> <seq>\n");
+    }
+}
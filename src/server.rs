@@ -0,0 +1,459 @@
+// =====================================================================
+// File: server.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   TCP server mode: the same line protocol as the REPL, one client per
+//   connection, all sharing one session.
+//
+//   Each connection runs on its own thread. GET, MGET and RANGE outside
+//   a transaction are answered from the store's latest snapshot without
+//   the session lock (see `Snapshot::execute_read`). Other commands take
+//   the lock one at a time, so they never interleave; a connection's open
+//   transaction and `AUTH` user are its own, swapped into the session
+//   only while its command runs. An open transaction is dropped when its
+//   client disconnects. A client that sends `REPLICATE <replid> <seq>` is a
+//   replica: its connection turns into the log stream (see the
+//   `replication` module).
+//
+//   Quotas keep stuck or abandoned clients from exhausting the process:
+//     * `max_connections`    - clients past the limit get an ERR and are closed
+//     * `idle_timeout`       - a client silent for this long is closed; the
+//                              same timeout bounds a write to a client that
+//                              has stopped reading
+//     * `max_pending_output` - a reply that grows past this many bytes is
+//                              discarded and the client closed
+// =====================================================================
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::mem;
+use std::thread;
+use std::time::Duration;
+
+use crate::{execute_line, reload, start_sync, CommandResult, Session, SharedStore, Transaction};
+
+/// How often an idle server applies records streamed by its primary.
+const REPLICATION_POLL: Duration = Duration::from_millis(50);
+
+/// Limits applied to every client connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// Most clients connected at once.
+    pub max_connections: usize,
+
+    /// How long a client may stay silent (or refuse to read) before it is
+    /// closed; `None` waits forever.
+    pub idle_timeout: Option<Duration>,
+
+    /// Largest reply, in bytes, buffered for one command.
+    pub max_pending_output: usize,
+}
+
+
+impl ServerConfig {
+    /// Defaults: 128 connections, a 5 minute idle timeout and 16 MiB of
+    /// output per reply.
+    pub fn new() -> Self {
+        ServerConfig {
+            max_connections: 128,
+            idle_timeout: Some(Duration::from_secs(300)),
+            max_pending_output: 16 * 1024 * 1024,
+        }
+    }
+}
+
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig::new()
+    }
+}
+
+
+/// Replies written while [`capture_replies`] ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Captured {
+    /// Reply bytes, one `\n`-terminated line per reply.
+    pub bytes: Vec<u8>,
+
+    /// `true` if the replies outgrew the limit; `bytes` stops short then.
+    pub overflowed: bool,
+}
+
+
+thread_local! {
+    /// Buffer of the `capture_replies` call running on this thread, if any.
+    static CAPTURE: RefCell<Option<(Captured, usize)>> = const { RefCell::new(None) };
+}
+
+
+/// Run `f`, collecting the replies it writes instead of printing them.
+///
+/// # Arguments
+/// * `limit` - Most bytes to keep; later replies only mark the capture
+///   as overflowed.
+///
+/// # Example
+/// ```
+/// use kvstore::{capture_replies, execute_line, Session};
+/// let mut session = Session::ephemeral();
+/// let (_, captured) = capture_replies(1024, || execute_line(b"GET missing\n", &mut session));
+/// assert_eq!(captured.bytes, b"nil\n");
+/// assert!(!captured.overflowed);
+/// ```
+pub fn capture_replies<R>(limit: usize, f: impl FnOnce() -> R) -> (R, Captured) {
+    let outer = CAPTURE.with(|c| c.replace(Some((Captured::default(), limit))));
+    let result = f();
+    let (captured, _) = CAPTURE.with(|c| c.replace(outer)).unwrap_or_default();
+    (result, captured)
+}
+
+
+/// Write one reply line, behind the `reply!` macro.
+pub(crate) fn write_reply(args: fmt::Arguments) {
+    CAPTURE.with(|c| match &mut *c.borrow_mut() {
+        Some((captured, limit)) => {
+            let line = format!("{}\n", args);
+            if captured.overflowed || captured.bytes.len() + line.len() > *limit {
+                captured.overflowed = true;
+            } else {
+                captured.bytes.extend_from_slice(line.as_bytes());
+            }
+        }
+        None => println!("{}", args),
+    });
+}
+
+
+/// Per-client session state, kept apart from the shared session.
+#[derive(Default)]
+pub(crate) struct Connection {
+    transaction: Option<Transaction>,
+    user: Option<String>,
+}
+
+
+impl Connection {
+    /// Trade this client's state with whatever the session holds.
+    pub(crate) fn swap(&mut self, session: &mut Session) {
+        mem::swap(&mut self.transaction, &mut session.transaction);
+        mem::swap(&mut self.user, &mut session.user);
+    }
+
+
+    /// `true` while this client has a transaction open, so its reads must
+    /// see its own pending writes.
+    pub(crate) fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+}
+
+
+/// Apply a pending configuration reload, before a command runs or while
+/// the server is idle.
+pub(crate) fn apply_reload_request(session: &mut Session) {
+    if reload::take_reload_request() {
+        for problem in reload::reload(session) {
+            eprintln!("reload: {}", problem);
+        }
+    }
+}
+
+
+/// Counts a client against `max_connections` until dropped.
+pub(crate) struct Slot(pub(crate) Arc<AtomicUsize>);
+
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+
+/// Serve clients from `listener` until accepting fails for good.
+///
+/// Each client gets its own thread; the session is shared between them.
+///
+/// # Example
+/// ```no_run
+/// use kvstore::{serve, ServerConfig, Session};
+/// use std::net::TcpListener;
+///
+/// let listener = TcpListener::bind("127.0.0.1:6380").unwrap();
+/// serve(listener, Session::open("data").unwrap(), ServerConfig::new()).unwrap();
+/// ```
+pub fn serve(listener: TcpListener, session: Session, config: ServerConfig) -> io::Result<()> {
+    serve_shared(listener, Arc::new(SharedStore::new(session)), config)
+}
+
+
+/// [`serve`] a store that other front ends (such as the HTTP API) use too.
+pub fn serve_shared(listener: TcpListener, store: Arc<SharedStore>, config: ServerConfig) -> io::Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+    spawn_replication_poller(&store);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            // A client that vanished mid-handshake; keep accepting
+            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if active.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let _ = stream.write_all(b"ERR max number of clients reached\n");
+            continue;
+        }
+        let slot = Slot(active.clone());
+        let store = store.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = handle_client(stream, &store, config) {
+                eprintln!("client error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+
+/// Apply what a replica's primary streams (or a reader's writer logs),
+/// and any configuration reload, even while clients only read.
+///
+/// Each pass publishes a fresh snapshot for the readers. The thread ends
+/// once the last handle to the store is gone.
+pub(crate) fn spawn_replication_poller(store: &Arc<SharedStore>) {
+    let poller = Arc::downgrade(store);
+    thread::spawn(move || {
+        while let Some(store) = poller.upgrade() {
+            store.write(|session| {
+                apply_reload_request(session);
+                session.poll_replication();
+                session.poll_tail();
+            });
+            drop(store);
+            thread::sleep(REPLICATION_POLL);
+        }
+    });
+}
+
+
+/// Answer one client's commands until it exits, disconnects or breaks a quota.
+fn handle_client(stream: TcpStream, store: &SharedStore, config: ServerConfig) -> io::Result<()> {
+    stream.set_read_timeout(config.idle_timeout)?;
+    stream.set_write_timeout(config.idle_timeout)?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut conn = Connection::default();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                let _ = writer.write_all(b"ERR idle timeout, closing connection\n");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+
+        // A replica asking for the log: this connection becomes its stream
+        if let Some(from_seq) = replicate_request(&line) {
+            return stream_to_replica(&mut writer, store, &mut conn, from_seq);
+        }
+
+        let snapshot = store.snapshot();
+        let read = (!conn.in_transaction()).then(|| capture_replies(config.max_pending_output, || snapshot.execute_read(&line)));
+        let (result, captured) = match read {
+            Some((true, captured)) => (CommandResult::Continue, captured),
+            _ => store.write(|session| {
+                apply_reload_request(session);
+                conn.swap(session);
+                let (result, captured) = capture_replies(config.max_pending_output, || {
+                    let result = execute_line(&line, session);
+                    if let Err(e) = session.tick() {
+                        reply!("ERR background work failed: {}", e);
+                    }
+                    result
+                });
+                conn.swap(session);
+                (result, captured)
+            }),
+        };
+
+        if captured.overflowed {
+            let _ = writer.write_all(b"ERR reply exceeds max pending output, closing connection\n");
+            return Ok(());
+        }
+        writer.write_all(&captured.bytes)?;
+        if let CommandResult::Exit = result {
+            return Ok(());
+        }
+    }
+}
+
+
+/// The argument of a `REPLICATE <seq>` line, if that is what `line` is.
+fn replicate_request(line: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(line).ok()?;
+    let mut parts = text.split_whitespace();
+    let cmd = parts.next()?;
+    cmd.eq_ignore_ascii_case("REPLICATE").then(|| parts.collect::<Vec<_>>().join(" "))
+}
+
+
+/// Stream the log to a replica that sent `REPLICATE <replid> <seq>`.
+///
+/// Answers with a full sync first if the replica can't resume, then
+/// `STREAM <replid> <seq>` and one `<seq> <record>` line per record until
+/// the replica goes away or falls too far behind.
+fn stream_to_replica(writer: &mut TcpStream, store: &SharedStore, conn: &mut Connection, args: String) -> io::Result<()> {
+    let started = store.write(|session| {
+        conn.swap(session);
+        let result = session.check_access("REPLICATE", &[]).and_then(|()| {
+            let (replid, from_seq) = args
+                .split_once(' ')
+                .and_then(|(replid, seq)| Some((replid, seq.parse::<u64>().ok()?)))
+                .ok_or("REPLICATE requires <replid> <seq>")?;
+            start_sync(session, replid, from_seq)
+        });
+        conn.swap(session);
+        result
+    });
+
+    let sync = match started {
+        Ok(sync) => sync,
+        Err(e) => return writer.write_all(format!("ERR {}\n", e).as_bytes()),
+    };
+    let mut out = BufWriter::new(writer);
+    if let Some((seq, snapshot)) = &sync.snapshot {
+        writeln!(out, "FULLSYNC {} {} {}", sync.replid, seq, snapshot.len())?;
+        for record in snapshot {
+            writeln!(out, "{}", record)?;
+        }
+    }
+    writeln!(out, "STREAM {} {}", sync.replid, sync.from_seq)?;
+    out.flush()?;
+
+    // Ends once the primary drops this replica's channel
+    for (seq, record) in sync.records {
+        writeln!(out, "{} {}", seq, record)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+
+// =================================================================
+// server.rs Unit tests
+// =================================================================
+#[cfg(test)]
+mod server_tests {
+    use super::*;
+    use crate::MemFs;
+    use std::io::Read;
+    use std::net::SocketAddr;
+
+    /// Start a server on a free port with its log in memory.
+    fn start(config: ServerConfig) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        thread::spawn(move || serve(listener, session, config));
+        addr
+    }
+
+    /// Send `commands` and check the next reply lines are `expected`.
+    fn send(stream: &mut TcpStream, commands: &str, expected: &[&str]) {
+        stream.write_all(commands.as_bytes()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        for want in expected {
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            assert_eq!(reply.trim_end(), *want);
+        }
+    }
+
+    fn read_all(stream: &mut TcpStream) -> String {
+        let mut text = String::new();
+        stream.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_clients_share_data_but_not_transactions() {
+        let addr = start(ServerConfig::new());
+        let mut a = TcpStream::connect(addr).unwrap();
+        let mut b = TcpStream::connect(addr).unwrap();
+
+        send(&mut a, "SET k 1\nBEGIN\nSET k 2\n", &["OK", "OK"]);
+        send(&mut b, "GET k\n", &["1"]);
+        send(&mut a, "GET k\nCOMMIT\n", &["2", "OK"]);
+        send(&mut b, "GET k\n", &["2"]);
+    }
+
+    #[test]
+    fn test_clients_past_the_limit_are_refused() {
+        let addr = start(ServerConfig { max_connections: 1, ..ServerConfig::new() });
+        let mut first = TcpStream::connect(addr).unwrap();
+        send(&mut first, "SET a 1\n", &["OK"]);
+
+        let mut second = TcpStream::connect(addr).unwrap();
+        assert_eq!(read_all(&mut second), "ERR max number of clients reached\n");
+
+        // The slot frees up once the first client leaves
+        first.write_all(b"EXIT\n").unwrap();
+        assert_eq!(read_all(&mut first), "Exiting...\n");
+        let admitted = (0..50).any(|_| {
+            let mut next = TcpStream::connect(addr).unwrap();
+            next.write_all(b"GET a\nEXIT\n").unwrap();
+            // A refusal closes with our commands unread, which may reset the connection
+            let mut reply = String::new();
+            if next.read_to_string(&mut reply).is_ok() && reply == "1\nExiting...\n" {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+            false
+        });
+        assert!(admitted, "the slot was never released");
+    }
+
+    #[test]
+    fn test_idle_clients_are_closed() {
+        let addr = start(ServerConfig { idle_timeout: Some(Duration::from_millis(50)), ..ServerConfig::new() });
+        let mut idle = TcpStream::connect(addr).unwrap();
+        assert_eq!(read_all(&mut idle), "ERR idle timeout, closing connection\n");
+    }
+
+    #[test]
+    fn test_oversized_replies_close_the_client() {
+        let addr = start(ServerConfig { max_pending_output: 16, ..ServerConfig::new() });
+        let mut client = TcpStream::connect(addr).unwrap();
+        send(&mut client, "MSET key1 a key2 b key3 c key4 d\n", &["OK"]);
+        client.write_all(b"RANGE - +\n").unwrap();
+        assert_eq!(read_all(&mut client), "ERR reply exceeds max pending output, closing connection\n");
+    }
+
+    #[test]
+    fn test_capture_limit_marks_overflow() {
+        let (_, captured) = capture_replies(6, || {
+            write_reply(format_args!("one"));
+            write_reply(format_args!("two"));
+        });
+        assert_eq!(captured.bytes, b"one\n");
+        assert!(captured.overflowed);
+    }
+}
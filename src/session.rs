@@ -15,12 +15,38 @@
 // - Contain references to the BTreeIndex (key-value store).
 // - Manage TTL expiration logic through the TTLManager.
 // - Optionally track an in-progress transaction for atomic operations.
+// - Optionally limit how many values stay in memory (disk spillover).
+// - Optionally cache hot reads in front of the index (LRU).
+// - Optionally move large values to a value log, leaving pointers.
+// - Track live keys in a hash set so EXISTS and misses skip the tree.
+// - Run log compaction in small steps between commands.
+// - Reclaim a bounded number of expired keys per command.
+// - Optionally replay the log in the background while serving reads.
+// - Enforce key and value size limits on writes and replay.
+// - Report failed log appends and optionally turn read-only after
+//   repeated failures.
+// - Route every log append, cold read and compaction through one file
+//   system (real files by default, memory in tests), against a data
+//   file chosen per session.
+// - Number appended records for replicas, or apply the records a
+//   primary streams to us.
+// - Publish committed mutations to a change-data-capture sink and to
+//   webhooks.
 //
 // Each client session corresponds to a single REPL or Gradebot run,
 // ensuring isolated transaction and TTL states.
 // =====================================================================
-use crate::storage;
-use crate::{BTreeIndex, TTLManager, Transaction};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::storage::{self, LogLock, LogRecord, ReplayOp, Stamp, ValueRef};
+use crate::loader::{LoadedRecord, LOAD_BATCH};
+use crate::ttl::DEFAULT_EXPIRE_BUDGET;
+use crate::{Acl, AclUser, BackgroundLoad, BTreeIndex, Cdc, CompactionPolicy, DataDir, ClusterMap, Compactor, FollowerLink, Fs, KvRow, Limits, LoadReport, LogKey, LogTail, LruCache, NullFs, RealFs, Replica, ReplicaEvent, ReplicationLog, S3Config, SpillManager, StorageStats, TTLManager, Transaction, ValueLog, ValuePointer, Webhooks};
 
 /// Represents a single in-memory database session.
 /// Holds the live index, TTL manager, and optional transaction state.
@@ -28,92 +54,2225 @@ pub struct Session {
     /// The main persistent in-memory key-value index (B-tree).
     pub index: BTreeIndex,
 
-    /// Global TTL manager handling key expirations.
-    pub ttl: TTLManager,
+    /// Global TTL manager handling key expirations.
+    pub ttl: TTLManager,
+
+    /// Optional active transaction session (`None` if not in BEGIN/COMMIT mode).
+    pub transaction: Option<Transaction>,
+
+    /// Hot-key tracker for memory-limited mode (`None` keeps every value in memory).
+    pub spill: Option<SpillManager>,
+
+    /// Optional LRU read cache in front of the index (`None` disables caching).
+    pub cache: Option<LruCache>,
+
+    /// Values kept in the value log instead of the log and the index; no
+    /// new value is moved there unless a threshold is set.
+    pub value_log: ValueLog,
+
+    /// Every committed key, kept in step with the index by `set`, `delete`
+    /// and log replay. Answers membership without walking the tree.
+    pub live_keys: HashSet<String>,
+
+    /// Incremental log compactor, stepped once per command.
+    pub compactor: Compactor,
+
+    /// Maximum expired keys reclaimed per command tick.
+    pub expire_budget: usize,
+
+    /// Background log replay in progress (`None` once fully loaded).
+    pub loading: Option<BackgroundLoad>,
+
+    /// Key and value size limits checked by writes and replay.
+    pub limits: Limits,
+
+    /// Replayed records skipped for breaking `limits`.
+    pub rejected_records: u64,
+
+    /// Integrity summary of the last completed log replay.
+    pub load_report: Option<LoadReport>,
+
+    /// Print [`Session::load_report`] to stderr when a replay completes.
+    pub report_load: bool,
+
+    /// Config file re-read by [`crate::reload`] on SIGHUP (`None` for none).
+    pub config_path: Option<String>,
+
+    /// Users and permissions; `None` allows every command.
+    pub acl: Option<Acl>,
+
+    /// ACL file re-read by [`crate::reload`] on SIGHUP (`None` for none).
+    pub acl_path: Option<String>,
+
+    /// User switched to with `AUTH` (`None` runs as `default`).
+    pub user: Option<String>,
+
+    /// Acknowledge every command with `OK` or `ERR` on success or failure,
+    /// including `BEGIN` and `ABORT`, which are otherwise silent.
+    pub ack_mode: bool,
+
+    /// Lowercase every key named by a command, an API call or a replayed
+    /// record, so `GET Dog` and `GET dog` find the same entry.
+    pub lowercase_keys: bool,
+
+    /// Log appends that failed in a row; reset by the next successful one.
+    pub write_failures: u32,
+
+    /// Switch to read-only after this many failed appends in a row
+    /// (`0` never does).
+    pub read_only_after: u32,
+
+    /// Set once `read_only_after` appends failed in a row; writes and
+    /// deletes are refused from then on.
+    pub read_only: bool,
+
+    /// File system holding the log; [`RealFs`] unless a test swaps in
+    /// a [`crate::MemFs`].
+    pub fs: Arc<dyn Fs>,
+
+    /// Data file every append, cold read and compaction uses;
+    /// [`storage::get_data_file`] unless opened with [`Session::open`].
+    pub data_file: String,
+
+    /// Lock on the data file, held for as long as the session lives so no
+    /// other process appends to it (`None` unless opened with
+    /// [`Session::open`]; the binary takes its own before loading).
+    pub lock: Option<LogLock>,
+
+    /// Sequence number of the last record this session stamped (`None`
+    /// until the next append reads it from the log; see
+    /// [`storage::last_seq`]).
+    pub last_seq: Option<u64>,
+
+    /// Numbers appended records and streams them to replicas (`None`
+    /// when nobody can replicate from us).
+    pub replication: Option<ReplicationLog>,
+
+    /// The primary we follow (`None` unless this is a replica).
+    pub replica: Option<Replica>,
+
+    /// Cluster we are a shard of; keys owned by other shards are
+    /// answered with `MOVED` (`None` outside cluster mode).
+    pub cluster: Option<ClusterMap>,
+
+    /// Change stream every committed mutation is published to (`None`
+    /// without `KVSTORE_CDC`).
+    pub cdc: Option<Cdc>,
+
+    /// Hooks notified of key changes (`None` without `KVSTORE_WEBHOOKS`).
+    pub webhooks: Option<Webhooks>,
+
+    /// Webhook file re-read by [`crate::reload`] on SIGHUP.
+    pub webhooks_path: Option<String>,
+
+    /// Object store `BACKUP s3://...` writes to (`None` without
+    /// `KVSTORE_S3_ENDPOINT`).
+    pub s3: Option<S3Config>,
+
+    /// In-process read replicas fed every logged change.
+    pub followers: Vec<FollowerLink>,
+
+    /// Data file written by another process that this reader follows
+    /// (`None` unless `KVSTORE_READER=1`).
+    pub tail: Option<LogTail>,
+
+    /// Checkpoint the log continues from (`0` for none).
+    pub checkpoint_id: u64,
+
+    /// Records in the log after the checkpoint (or in all of it).
+    pub records_since_checkpoint: u64,
+
+    /// Take a checkpoint once this many records follow the last one
+    /// (`0` never does).
+    pub checkpoint_every: u64,
+
+    /// Rotate the log into a compressed cold segment once it grows past
+    /// this many bytes (`0` never does).
+    pub segment_bytes: u64,
+
+    /// When to start a compaction pass without a `COMPACT` (by default
+    /// never).
+    pub compaction_policy: CompactionPolicy,
+
+    /// Values set so far while [`crate::load_data`] replays the log, which
+    /// it bulk-loads into the index once the log is read (`None` otherwise).
+    pub(crate) replayed: Option<HashMap<String, Vec<u8>>>,
+}
+
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Session {
+    /// Creates a new, empty session with its own index and TTL manager.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::new();
+    /// assert!(session.transaction.is_none());
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            index: BTreeIndex::new(2),
+            ttl: TTLManager::new(),
+            transaction: None,
+            spill: None,
+            cache: None,
+            value_log: ValueLog::default(),
+            live_keys: HashSet::new(),
+            compactor: Compactor::default(),
+            expire_budget: DEFAULT_EXPIRE_BUDGET,
+            loading: None,
+            limits: Limits::default(),
+            rejected_records: 0,
+            load_report: None,
+            report_load: false,
+            config_path: None,
+            acl: None,
+            acl_path: None,
+            user: None,
+            ack_mode: false,
+            lowercase_keys: false,
+            write_failures: 0,
+            read_only_after: 0,
+            read_only: false,
+            fs: Arc::new(RealFs),
+            data_file: storage::get_data_file(),
+            lock: None,
+            last_seq: None,
+            replication: None,
+            replica: None,
+            cluster: None,
+            cdc: None,
+            webhooks: None,
+            webhooks_path: None,
+            s3: None,
+            followers: Vec::new(),
+            tail: None,
+            checkpoint_id: 0,
+            records_since_checkpoint: 0,
+            checkpoint_every: 0,
+            segment_bytes: 0,
+            compaction_policy: CompactionPolicy::default(),
+            replayed: None,
+        }
+    }
+
+    /// Opens the data directory at `dir` (see [`DataDir`]), creating it
+    /// if needed: locks its log segment (see [`LogLock`]), upgrades it
+    /// (see [`crate::migrate_log`]) and replays it into a new session that
+    /// appends there from then on.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` of kind `WouldBlock` if another session or process
+    /// has the directory open, of kind `InvalidData` if its `MANIFEST` is
+    /// malformed, from a newer format or names a missing segment, or any
+    /// error creating or upgrading it; also if the log holds encrypted
+    /// records it has no key for.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let dir = std::env::temp_dir().join("kvstore_open_doc");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let mut session = Session::open(&dir).unwrap();
+    /// session.set("a".into(), "1".into());
+    /// # kvstore::close_log(&session.data_file).unwrap();
+    /// assert!(Session::open(&dir).is_err()); // still locked
+    /// assert!(dir.join("MANIFEST").exists());
+    ///
+    /// drop(session);
+    /// let reopened = Session::open(&dir).unwrap();
+    /// assert_eq!(reopened.index.search("a"), Some("1"));
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn open(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::open_on(dir, Arc::new(RealFs))
+    }
+
+    /// Like [`Session::open`], with every record written from then on
+    /// encrypted with `key` (see [`crate::crypt`]).
+    ///
+    /// The key is kept in the session's file system (an
+    /// [`crate::EncryptedFs`]), so its compactions, checkpoints and
+    /// backups are encrypted too, and other sessions are not affected.
+    ///
+    /// # Example
+    /// ```
+    /// # #[cfg(feature = "encryption")] {
+    /// use kvstore::{LogKey, Session};
+    /// let dir = std::env::temp_dir().join("kvstore_open_encrypted_doc");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let mut session = Session::open_encrypted(&dir, LogKey::new(&[1; 32])).unwrap();
+    /// session.set("card".into(), "4111-1111".into());
+    /// # kvstore::close_log(&session.data_file).unwrap();
+    /// assert!(!std::fs::read(&session.data_file).unwrap().windows(4).any(|bytes| bytes == b"4111"));
+    /// drop(session);
+    ///
+    /// assert!(Session::open_encrypted(&dir, LogKey::new(&[2; 32])).is_err());
+    /// assert!(Session::open(&dir).is_err());
+    /// let mut reopened = Session::open_encrypted(&dir, LogKey::new(&[1; 32])).unwrap();
+    /// assert_eq!(reopened.get("card"), Some("4111-1111".to_string()));
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub fn open_encrypted(dir: impl AsRef<Path>, key: LogKey) -> std::io::Result<Self> {
+        Self::open_on(dir, Arc::new(crate::EncryptedFs::new(Arc::new(RealFs), key)))
+    }
+
+    fn open_on(dir: impl AsRef<Path>, fs: Arc<dyn Fs>) -> std::io::Result<Self> {
+        let mut data_dir = DataDir::open(dir)?;
+        let mut session = Self::new();
+        session.fs = fs;
+        session.data_file = data_dir.log_path();
+        session.lock = Some(LogLock::acquire(&session.data_file)?);
+        storage::migrate_log_with(&*session.fs, &session.data_file)?;
+        data_dir.record_format(storage::FORMAT_VERSION)?;
+        storage::check_log_key(&*session.fs, &session.data_file)?;
+        let file = session.data_file.clone();
+        crate::try_load_data(&mut session, &file)?;
+        Ok(session)
+    }
+
+    /// Creates a session that persists nothing: writes go to a [`NullFs`]
+    /// and the data file is never created, read or locked.
+    ///
+    /// Useful as a cache, or for a scratch REPL that must not leave a
+    /// `data.db` behind. Everything is lost when the session is dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Fs, Session};
+    /// let mut session = Session::ephemeral();
+    /// session.set("ephemeral_doc".into(), "1".into());
+    /// assert_eq!(session.get("ephemeral_doc"), Some("1".to_string()));
+    /// assert!(session.fs.read(&session.data_file).is_err());
+    /// ```
+    pub fn ephemeral() -> Self {
+        Self {
+            fs: Arc::new(NullFs),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a session that keeps at most `max_hot_keys` values in memory.
+    ///
+    /// Every key stays in the index, but values that fall out of the hot
+    /// set are dropped and read back from the log the next time they are
+    /// requested through [`Session::get`].
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let session = Session::with_memory_limit(1000);
+    /// assert_eq!(session.spill.as_ref().unwrap().capacity(), 1000);
+    /// ```
+    pub fn with_memory_limit(max_hot_keys: usize) -> Self {
+        Self {
+            spill: Some(SpillManager::new(max_hot_keys)),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a session whose index holds only keys (WiscKey style).
+    ///
+    /// Each key maps to the `(offset, len)` of its latest value in the
+    /// log, and every [`Session::get`] reads the value from disk. This
+    /// keeps the in-memory footprint proportional to the key set.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let session = Session::key_only();
+    /// assert!(session.spill.as_ref().unwrap().is_key_only());
+    /// ```
+    pub fn key_only() -> Self {
+        Self {
+            spill: Some(SpillManager::key_only()),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a session that keeps values of at least `threshold` bytes
+    /// in the value log (see [`crate::vlog`]).
+    ///
+    /// The index and the log hold only a pointer for such values, and
+    /// [`Session::get`] reads them from disk.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let session = Session::with_value_log(4096);
+    /// assert_eq!(session.value_log.threshold(), Some(4096));
+    /// ```
+    pub fn with_value_log(threshold: usize) -> Self {
+        Self {
+            value_log: ValueLog::new(Some(threshold)),
+            ..Self::new()
+        }
+    }
+
+    /// Returns `true` if a transaction is currently active.
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Starts a new transaction, overwriting any previous uncommitted session.
+    pub fn begin_transaction(&mut self) {
+        self.transaction = Some(Transaction::new());
+    }
+
+
+    /// Commits the active transaction, applying all pending key–value updates
+    /// to the main index and writing them to the persistent log.
+    ///
+    /// This method finalizes an in-progress transaction by:
+    /// - Iterating over each buffered key–value pair in the transaction’s
+    ///   `pending` map.
+    /// - Inserting those values into the live `index`.
+    /// - Appending every update to the write-ahead log as `SET` records,
+    ///   in a single write and sync.
+    /// - Applying the TTLs staged by `EXPIRE` during the transaction.
+    ///
+    /// Once all changes are applied, the transaction is cleared and removed
+    /// from the session.
+    ///
+    /// # Behavior
+    ///
+    /// If no transaction is active, this method does nothing.
+    /// If a transaction exists, all staged updates become durable and visible
+    /// to subsequent operations. If the writes can't be logged, the commit
+    /// replies `ERR persistence failure` and none of them is applied.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    ///
+    /// let mut session = Session::ephemeral();
+    /// session.begin_transaction();
+    ///
+    /// let tx = session.transaction.as_mut().unwrap();
+    /// tx.set("x".into(), "10".into());
+    /// tx.set("y".into(), "20".into());
+    ///
+    /// // Persist all staged writes
+    /// session.commit_transaction();
+    ///
+    /// assert_eq!(session.index.search("x"), Some("10"));
+    /// assert_eq!(session.index.search("y"), Some("20"));
+    /// ```
+    pub fn commit_transaction(&mut self) {
+        if let Some(tx) = self.transaction.take() {
+
+            // Apply all key/value mutations to the main index and persist
+            // them, with the staged TTL changes, in one append (Gradebot
+            // requires this!)
+            let cleared: Vec<String> = tx.cleared_ttls.iter().filter(|key| self.ttl.has_entry(key)).cloned().collect();
+            let pending = tx.pending.into_iter().map(|(key, value)| (key, value.into_bytes())).collect();
+            if let Err(e) = self.apply_batch(pending, cleared, tx.ttl_manager.deadlines_unix_ms()) {
+                reply!("ERR {}", e);
+                return;
+            }
+
+            // Keep the staged TTLs' own deadlines rather than the logged
+            // (millisecond) ones
+            self.ttl.merge(tx.ttl_manager);
+
+            // Transaction ends
+            reply!("OK");
+        } else {
+            reply!("ERR no active transaction");
+        }
+    }
+
+
+    /// Writes a key–value pair, staging it if a transaction is active.
+    ///
+    /// Outside a transaction the value goes straight into the index and
+    /// is appended to the log as a `SET` record. The write is not checked
+    /// against [`Session::limits`], and a failed append is only counted in
+    /// [`Session::write_failures`]; callers taking outside input should
+    /// use [`Session::try_set`].
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.begin_transaction();
+    /// session.set("a".into(), "1".into());
+    /// assert!(session.index.search("a").is_none()); // staged only
+    /// ```
+    pub fn set(&mut self, key: String, value: String) {
+        let _ = self.write(key, value);
+    }
+
+
+    /// Validates a write against the session's limits, then [`Session::set`]s it.
+    ///
+    /// # Returns
+    /// * `Ok(())` once the write is applied (or staged).
+    /// * `Err(message)` if the key is malformed, the write is too large or
+    ///   it could not be logged; nothing is written.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// assert!(session.try_set("try_set_doc".into(), "ok".into()).is_ok());
+    /// assert!(session.try_set("line\nbreak".into(), "x".into()).is_err());
+    /// assert!(!session.exists("line\nbreak"));
+    /// ```
+    pub fn try_set(&mut self, key: String, value: String) -> Result<(), String> {
+        self.limits.check_write(&key, value.as_bytes())?;
+        self.write(key, value)
+    }
+
+
+    /// [`Session::try_set`] for a value that need not be text.
+    ///
+    /// Transactions and background loads stage text only, so a value that
+    /// is not UTF-8 is only accepted once the session is idle.
+    ///
+    /// # Returns
+    /// * `Ok(())` once the write is applied (or staged).
+    /// * `Err(message)` if the write breaks the limits, is not text and
+    ///   can't be staged, or could not be logged; nothing is written.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.try_set_bytes("bytes_doc".into(), vec![0, 159, 146, 150]).unwrap();
+    /// assert_eq!(session.get_bytes("bytes_doc"), Some(vec![0, 159, 146, 150]));
+    /// assert_eq!(session.get("bytes_doc"), Some("\0\u{fffd}\u{fffd}\u{fffd}".to_string()));
+    /// ```
+    pub fn try_set_bytes(&mut self, key: String, value: Vec<u8>) -> Result<(), String> {
+        self.limits.check_write(&key, &value)?;
+        match String::from_utf8(value) {
+            Ok(text) => self.write(key, text),
+            Err(_) if self.transaction.is_some() || self.loading.is_some() => {
+                Err("values that are not UTF-8 can't be written inside a transaction or while loading".to_string())
+            }
+            Err(e) => self.apply_set(key, e.into_bytes()),
+        }
+    }
+
+
+    /// Validates several writes, then applies them as one batch.
+    ///
+    /// Outside a transaction (and once loading is done) every pair is
+    /// logged with a single append, so `MSET` of many pairs costs one
+    /// fsync rather than one per pair.
+    ///
+    /// # Returns
+    /// * `Ok(())` once every write is applied (or staged).
+    /// * `Err(message)` if any pair breaks the limits or the batch could
+    ///   not be logged; nothing is written.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// let pairs = vec![("mset_doc_a".to_string(), "1".to_string()), ("mset_doc_b".to_string(), "2".to_string())];
+    /// assert!(session.mset(pairs).is_ok());
+    /// assert_eq!(session.get("mset_doc_b"), Some("2".to_string()));
+    /// ```
+    pub fn mset(&mut self, pairs: Vec<(String, String)>) -> Result<(), String> {
+        for (key, value) in &pairs {
+            self.limits.check_write(key, value.as_bytes())?;
+        }
+        if self.transaction.is_none() && self.loading.is_none() {
+            return self.apply_sets(pairs);
+        }
+        for (key, value) in pairs {
+            self.write(key, value)?;
+        }
+        Ok(())
+    }
+
+
+    /// Validates a write, then sets the key with a TTL of `ms`
+    /// milliseconds, as `SET <key> <value> PX <ms>` does.
+    ///
+    /// The `SET` and its `EXPIREAT` deadline are logged with one append
+    /// and applied together, so no reader or restart sees the value
+    /// without its TTL. Inside a transaction both are staged until
+    /// `COMMIT`, which logs them the same way.
+    ///
+    /// # Returns
+    /// * `Ok(())` once the write and TTL are applied (or staged).
+    /// * `Err(message)` if `ms` is not positive, the write breaks the
+    ///   limits, the dataset is still loading, or the append failed;
+    ///   nothing is written.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.setex("setex_doc".into(), "v".into(), 60_000).unwrap();
+    /// assert_eq!(session.get("setex_doc"), Some("v".to_string()));
+    /// assert!(session.ttl_status("setex_doc") > 0);
+    /// assert!(session.setex("setex_doc".into(), "w".into(), 0).is_err());
+    /// ```
+    pub fn setex(&mut self, key: String, value: String, ms: i64) -> Result<(), String> {
+        if ms <= 0 {
+            return Err("invalid expire time".to_string());
+        }
+        self.limits.check_write(&key, value.as_bytes())?;
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        if self.transaction.is_some() {
+            self.write(key.clone(), value)?;
+            return self.expire(&key, ms).map(|_| ());
+        }
+        let at = crate::ttl::unix_now_ms() + ms as u64;
+        self.apply_batch(vec![(key.clone(), value.into_bytes())], Vec::new(), vec![(key, at)])
+    }
+
+
+    /// Applies one batch of `IMPORT JSON`/`IMPORT CSV` rows: the writes,
+    /// then the deadlines (Unix ms), logged with one append. Rows are
+    /// checked by the caller.
+    ///
+    /// # Returns
+    /// * `Err(message)` inside a transaction, while loading, or if the
+    ///   append failed; nothing is applied.
+    pub(crate) fn import_batch(&mut self, pairs: Vec<(String, String)>, expirations: Vec<(String, u64)>) -> Result<(), String> {
+        if self.transaction.is_some() || self.loading.is_some() {
+            return Err("imports are not allowed inside a transaction or while loading".to_string());
+        }
+        let pairs = pairs.into_iter().map(|(key, value)| (key, value.into_bytes())).collect();
+        self.apply_batch(pairs, Vec::new(), expirations)
+    }
+
+
+    /// Stages, queues or applies a write, reporting a failed log append.
+    fn write(&mut self, key: String, value: String) -> Result<(), String> {
+        let key = self.normalize_owned(key);
+        if let Some(tx) = &mut self.transaction {
+            tx.set(key, value);
+        } else if let Some(load) = &mut self.loading {
+            load.queue_set(key, value);
+        } else {
+            self.apply_set(key, value.into_bytes())?;
+        }
+        Ok(())
+    }
+
+
+    /// Returns `true` if the key is committed and not expired.
+    ///
+    /// Answered from the live-key set; the tree is never searched.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("exists_doc".into(), "1".into());
+    /// assert!(session.exists("exists_doc"));
+    /// assert!(!session.exists("missing_doc"));
+    /// ```
+    pub fn exists(&self, key: &str) -> bool {
+        self.ttl_status(key) != -2
+    }
+
+
+    /// Reports the TTL of a committed key, as the `TTL` command does.
+    ///
+    /// Unlike [`TTLManager::ttl_remaining`], which only knows about TTL
+    /// metadata, this also checks the live-key set, so a missing key is
+    /// reported as `-2` rather than `-1`.
+    ///
+    /// # Returns
+    /// * **Positive integer** — Milliseconds until the key expires.
+    /// * **-1** — The key exists and has no expiration.
+    /// * **-2** — The key is missing or has expired.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("ttl_status_doc".into(), "1".into());
+    /// assert_eq!(session.ttl_status("ttl_status_doc"), -1);
+    /// session.ttl.set_expiration("ttl_status_doc", 60_000);
+    /// assert!(session.ttl_status("ttl_status_doc") > 0);
+    /// assert_eq!(session.ttl_status("missing_doc"), -2);
+    /// ```
+    pub fn ttl_status(&self, key: &str) -> i64 {
+        let key = &*self.normalize_key(key);
+        if !self.live_keys.contains(key) {
+            return -2;
+        }
+        self.ttl.ttl_remaining(key)
+    }
+
+
+    /// Reads the committed value for a key, honoring TTLs and spillover.
+    ///
+    /// Expired and unknown keys are treated as missing; unknown keys are
+    /// rejected by the live-key set before the tree. When a read cache is enabled
+    /// it is consulted first and filled on a miss. In memory-limited mode
+    /// a cold value is reloaded from the log and becomes hot again, which
+    /// may evict other values.
+    ///
+    /// # Returns
+    /// `Some(value)` if the key is live, otherwise `None`. Bytes of a
+    /// value that are not UTF-8 read as `U+FFFD`; see [`Session::get_bytes`].
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        self.get_bytes(key).map(storage::lossy_text)
+    }
+
+
+    /// Reads the committed value for a key as it was written, as
+    /// [`Session::get`] does but without assuming it is text.
+    ///
+    /// Only text values are kept in the read cache.
+    ///
+    /// # Returns
+    /// `Some(value)` if the key is live, otherwise `None`.
+    pub fn get_bytes(&mut self, key: &str) -> Option<Vec<u8>> {
+        let key = &*self.normalize_key(key);
+        // Writes queued during a background load shadow the index
+        if let Some(queued) = self.loading.as_ref().and_then(|l| l.queued_value(key)) {
+            return queued.map(|v| v.as_bytes().to_vec());
+        }
+        if self.ttl_status(key) == -2 {
+            // An expired value is deleted on the way out
+            self.expire_key(key);
+            if let Some(cache) = &mut self.cache {
+                cache.invalidate(key);
+            }
+            return None;
+        }
+
+        if let Some(hit) = self.cache.as_mut().and_then(|c| c.get(key)) {
+            return Some(hit.into_bytes());
+        }
+
+        let value = self.read_index(key);
+        if let (Some(cache), Some(v)) = (&mut self.cache, &value)
+            && let Ok(text) = std::str::from_utf8(v)
+        {
+            cache.put(key, text);
+        }
+        value
+    }
+
+
+    /// Reads the committed values of several keys at once.
+    ///
+    /// The keys are sorted and resolved with a single batched index
+    /// traversal. Sessions with a read cache, spillover or separated
+    /// values fall back to [`Session::get`] per key so hotness and cache
+    /// stats stay correct and separated values are read.
+    ///
+    /// # Returns
+    /// One result per key, in the order the keys were given.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("get_many_b".into(), "2".into());
+    /// session.set("get_many_a".into(), "1".into());
+    /// assert_eq!(session.get_many(&["get_many_b", "x", "get_many_a"]),
+    ///            vec![Some("2".to_string()), None, Some("1".to_string())]);
+    /// ```
+    pub fn get_many(&mut self, keys: &[&str]) -> Vec<Option<String>> {
+        let keys: Vec<Cow<str>> = keys.iter().map(|k| self.normalize_key(k)).collect();
+        let keys: Vec<&str> = keys.iter().map(|k| k.as_ref()).collect();
+        if self.spill.is_some() || self.cache.is_some() || self.loading.is_some() || !self.value_log.is_empty() {
+            return keys.iter().map(|k| self.get(k)).collect();
+        }
+
+        // Sort positions of known keys so results can be put back in order
+        let mut order: Vec<usize> = (0..keys.len()).filter(|&i| self.live_keys.contains(keys[i])).collect();
+        let collation = self.index.collation;
+        order.sort_by(|&a, &b| collation.compare(keys[a], keys[b]));
+        let sorted: Vec<&str> = order.iter().map(|&i| keys[i]).collect();
+        let found = self.index.get_bytes_sorted(&sorted);
+
+        let mut out = vec![None; keys.len()];
+        let mut expired = Vec::new();
+        for (pos, value) in order.into_iter().zip(found) {
+            if self.ttl.get_expiration(keys[pos]) == -2 {
+                expired.push(keys[pos]);
+            } else {
+                out[pos] = value.map(|v| String::from_utf8_lossy(v).into_owned());
+            }
+        }
+        for key in expired {
+            self.expire_key(key);
+        }
+        out
+    }
+
+
+    /// Deletes a committed key along with its TTL, spill and cache entries.
+    ///
+    /// A `DEL` record is appended to the log so the key stays deleted
+    /// after a restart. A key whose delete can't be logged is kept; use
+    /// [`Session::try_delete`] to see why.
+    ///
+    /// # Returns
+    /// `true` if the key existed and was removed, otherwise `false`.
+    pub fn delete(&mut self, key: &str) -> bool {
+        self.try_delete(key).unwrap_or(false)
+    }
+
+
+    /// Like [`Session::delete`], but reports a delete that could not be logged.
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the key existed and was removed, `Ok(false)` if it
+    ///   did not exist.
+    /// * `Err(message)` if the `DEL` record could not be appended; the key
+    ///   is left in place.
+    pub fn try_delete(&mut self, key: &str) -> Result<bool, String> {
+        self.remove_key(key, false)
+    }
+
+
+    /// Deletes a key whose TTL ran out, as [`Session::delete`] does, but
+    /// reported to webhooks as an `expire` event.
+    ///
+    /// The `DEL` is logged before anything is dropped, and then the value
+    /// and TTL entry go together, so the value can't outlive its TTL and
+    /// come back after a restart. If the append fails the key is left as
+    /// it was: still expired, still hidden from reads, and retried by the
+    /// next read or sweep. Keys whose TTL has not run out are left alone.
+    ///
+    /// # Returns
+    /// `true` if the key had expired and is now gone.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("expire_key_doc".into(), "v".into());
+    /// assert!(!session.expire_key("expire_key_doc"));
+    ///
+    /// session.ttl.set_expiration("expire_key_doc", 1);
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    /// assert!(session.expire_key("expire_key_doc"));
+    /// assert!(session.index.search("expire_key_doc").is_none());
+    /// assert_eq!(session.ttl.active_count(), 0);
+    /// ```
+    pub fn expire_key(&mut self, key: &str) -> bool {
+        let key = &*self.normalize_key(key);
+        if !self.ttl.expired_at(key, Instant::now()) {
+            return false;
+        }
+        match self.remove_key(key, true) {
+            Ok(true) => true,
+            // No value behind the TTL, so there is nothing to log
+            Ok(false) => {
+                self.ttl.clear_expiration(key);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+
+    /// Gives a committed key a TTL of `ms` milliseconds, as `EXPIRE` does.
+    ///
+    /// Inside a transaction the TTL is staged until `COMMIT`. Otherwise
+    /// it is logged as an `EXPIREAT` record with the absolute deadline,
+    /// so it keeps counting down across a restart. A TTL of zero or less
+    /// removes any TTL the key had (logged as `PERSIST`).
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the TTL was set (or staged).
+    /// * `Ok(false)` if the key is missing or `ms` is not positive.
+    /// * `Err(message)` if the change could not be logged, or the dataset
+    ///   is still loading.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("expire_doc".into(), "1".into());
+    /// assert_eq!(session.expire("expire_doc", 60_000), Ok(true));
+    /// assert!(session.ttl_status("expire_doc") > 0);
+    /// assert_eq!(session.expire("missing_doc", 60_000), Ok(false));
+    /// ```
+    pub fn expire(&mut self, key: &str, ms: i64) -> Result<bool, String> {
+        let key = &*self.normalize_key(key);
+        if let Some(tx) = &mut self.transaction {
+            return Ok(tx.expire(key, ms));
+        }
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        // A key past its deadline is gone, even if no sweep has removed it yet
+        if self.expire_key(key) || !self.index.contains_key(key) {
+            return Ok(false);
+        }
+        if ms <= 0 {
+            self.persist(key)?;
+            return Ok(false);
+        }
+        let at = crate::ttl::unix_now_ms() + ms as u64;
+        self.append_record(LogRecord::ExpireAt { key: key.to_string(), ms: at })?;
+        self.ttl.set_expiration(key, ms);
+        Ok(true)
+    }
+
+
+    /// Gives a committed key an absolute deadline, `unix_ms` milliseconds
+    /// since the Unix epoch, as `PEXPIREAT` does.
+    ///
+    /// The deadline is logged as it was given, in an `EXPIREAT` record.
+    /// One that has already passed deletes the key (logged as `DEL` and
+    /// reported as an expiry). Inside a transaction the deadline is
+    /// staged until `COMMIT`; a past one then expires the key as soon as
+    /// it is committed.
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the deadline was set (or staged), or the key was
+    ///   deleted because it had passed.
+    /// * `Ok(false)` if the key is missing.
+    /// * `Err(message)` if the change could not be logged, or the dataset
+    ///   is still loading.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// use kvstore::ttl::unix_now_ms;
+    /// let mut session = Session::ephemeral();
+    /// session.set("expire_at_doc".into(), "1".into());
+    /// assert_eq!(session.expire_at("expire_at_doc", unix_now_ms() + 60_000), Ok(true));
+    /// assert!(session.ttl_status("expire_at_doc") > 0);
+    /// assert_eq!(session.expire_at("expire_at_doc", 1), Ok(true));
+    /// assert!(!session.exists("expire_at_doc"));
+    /// ```
+    pub fn expire_at(&mut self, key: &str, unix_ms: u64) -> Result<bool, String> {
+        let key = &*self.normalize_key(key);
+        let ms = unix_ms.saturating_sub(crate::ttl::unix_now_ms()).min(i64::MAX as u64) as i64;
+        if let Some(tx) = &mut self.transaction {
+            return Ok(tx.expire(key, ms.max(1)));
+        }
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        // A key past its deadline is gone, even if no sweep has removed it yet
+        if self.expire_key(key) || !self.index.contains_key(key) {
+            return Ok(false);
+        }
+        if ms == 0 {
+            return self.remove_key(key, true);
+        }
+        self.append_record(LogRecord::ExpireAt { key: key.to_string(), ms: unix_ms })?;
+        self.ttl.set_expires_at(key, unix_ms);
+        Ok(true)
+    }
+
+
+    /// Removes a committed key's TTL, as `PERSIST` does, logging it so
+    /// the TTL stays gone after a restart.
+    ///
+    /// Inside a transaction the clear is staged until `COMMIT`, like
+    /// [`Session::expire`], and dropped on `ABORT`.
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the key had a TTL (staged or committed).
+    /// * `Ok(false)` if it had none (nothing is logged).
+    /// * `Err(message)` if the change could not be logged, or the dataset
+    ///   is still loading.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("persist_doc".into(), "1".into());
+    /// session.expire("persist_doc", 60_000).unwrap();
+    ///
+    /// session.begin_transaction();
+    /// assert_eq!(session.persist("persist_doc"), Ok(true));
+    /// assert!(session.ttl_status("persist_doc") > 0); // staged only
+    /// session.commit_transaction();
+    /// assert_eq!(session.ttl_status("persist_doc"), -1);
+    /// ```
+    pub fn persist(&mut self, key: &str) -> Result<bool, String> {
+        let key = &*self.normalize_key(key);
+        if let Some(tx) = &mut self.transaction {
+            let committed = self.ttl.has_entry(key) && !tx.cleared_ttls.contains(key);
+            return Ok(tx.persist(key) || committed);
+        }
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        if self.expire_key(key) || !self.ttl.has_entry(key) {
+            return Ok(false);
+        }
+        self.append_record(LogRecord::Persist { key: key.to_string() })?;
+        self.apply_op(ReplayOp::Persist(key.to_string()));
+        Ok(true)
+    }
+
+
+    /// Logs and applies a delete; `expired` tells webhooks why.
+    fn remove_key(&mut self, key: &str, expired: bool) -> Result<bool, String> {
+        let key = &*self.normalize_key(key);
+        // While loading, deletes wait so older records can't resurrect the key
+        if let Some(load) = &mut self.loading {
+            let existed = match load.queued_value(key) {
+                Some(queued) => queued.is_some(),
+                None => self.live_keys.contains(key),
+            };
+            load.queue_delete(key);
+            return Ok(existed);
+        }
+
+        // The live-key set answers without a tree search; the delete itself is the only one
+        if !self.live_keys.contains(key) {
+            return Ok(false);
+        }
+        self.append_changes(&[LogRecord::Del { key: key.to_string() }], expired)?;
+        self.apply_op(ReplayOp::Del(key.to_string()));
+        // A TTL staged for the key by an open transaction goes with it
+        if let Some(tx) = &mut self.transaction {
+            tx.ttl_manager.clear_expiration(key);
+        }
+        Ok(true)
+    }
+
+
+    /// Index lookup that reads separated values from the value log and
+    /// reloads cold values in memory-limited mode.
+    fn read_index(&mut self, key: &str) -> Option<Vec<u8>> {
+        if let Some(value) = self.value_log.location(key) {
+            return self.read_separated(value).ok();
+        }
+        let (cold_ptr, key_only) = match &self.spill {
+            Some(spill) if spill.is_cold(key) => (spill.location(key), spill.is_key_only()),
+            _ => (None, false),
+        };
+
+        let Some(ptr) = cold_ptr else {
+            return self.index.get_bytes(key).map(<[u8]>::to_vec);
+        };
+
+        // Cold value - read it back and make it hot again
+        let value = storage::read_value_with(&*self.fs, &self.data_file, ptr).ok()?;
+        if key_only {
+            return Some(value);
+        }
+        self.index.insert_bytes(key.to_string(), value.clone());
+        let evicted = match &mut self.spill {
+            Some(spill) => spill.touch(key),
+            None => Vec::new(),
+        };
+        self.evict_values(&evicted);
+
+        Some(value)
+    }
+
+
+    /// Reads a value from the value log.
+    fn read_separated(&self, value: ValueRef) -> std::io::Result<Vec<u8>> {
+        storage::read_value_with(&*self.fs, &storage::value_log_path(&self.data_file, value.generation), value.ptr)
+    }
+
+
+    /// Applies a committed write: log append, then the same in-memory
+    /// update replay performs. A write that can't be logged is not applied.
+    fn apply_set(&mut self, key: String, value: Vec<u8>) -> Result<(), String> {
+        self.apply_batch(vec![(key, value)], Vec::new(), Vec::new())
+    }
+
+
+    /// [`Session::apply_set`] for a batch, logged with one append. If the
+    /// append fails none of the writes is applied.
+    fn apply_sets(&mut self, pairs: Vec<(String, String)>) -> Result<(), String> {
+        let pairs = pairs.into_iter().map(|(key, value)| (key, value.into_bytes())).collect();
+        self.apply_batch(pairs, Vec::new(), Vec::new())
+    }
+
+
+    /// Logs writes, then TTL removals and deadlines (Unix ms), with one
+    /// append and applies them in that order. If the append fails nothing
+    /// is applied.
+    fn apply_batch(
+        &mut self,
+        pairs: Vec<(String, Vec<u8>)>,
+        persisted: Vec<String>,
+        expirations: Vec<(String, u64)>,
+    ) -> Result<(), String> {
+        let mut records: Vec<LogRecord> = pairs
+            .into_iter()
+            .map(|(key, value)| LogRecord::Set { key: self.normalize_owned(key), value })
+            .collect();
+        records.extend(persisted.into_iter().map(|key| LogRecord::Persist { key: self.normalize_owned(key) }));
+        records.extend(expirations.into_iter().map(|(key, ms)| LogRecord::ExpireAt { key: self.normalize_owned(key), ms }));
+        // Applied as replay would, so values point at their bytes in the log
+        for op in self.append_changes(&records, false)?.into_iter().flatten() {
+            self.apply_op(op);
+        }
+        Ok(())
+    }
+
+
+    /// Appends one record to the data file unless the session is read-only,
+    /// then tells every subscriber about it (see [`Session::append_changes`]).
+    ///
+    /// # Returns
+    /// `Err(message)` if the session is read-only or the append failed.
+    fn append_record(&mut self, record: LogRecord) -> Result<(), String> {
+        self.append_changes(&[record], false).map(drop)
+    }
+
+
+    /// Encodes and appends a batch of records in one append, so the whole
+    /// batch costs one write and one sync, then tells every subscriber
+    /// (replicas, the change stream, webhooks, followers); `expired` marks
+    /// a TTL reclaim.
+    ///
+    /// # Returns
+    /// * `Ok(written)`: the changes each record makes, as replay decodes
+    ///   them, so values point at their bytes in the log.
+    /// * `Err(message)` if the session is read-only or the append failed;
+    ///   no subscriber hears of any of the records then.
+    fn append_changes(&mut self, records: &[LogRecord], expired: bool) -> Result<Vec<Vec<ReplayOp>>, String> {
+        if self.read_only {
+            return Err("read-only mode after repeated persistence failures".to_string());
+        }
+        let layout = self.fs.layout(&self.data_file).map_err(|e| format!("persistence failure: {}", e))?;
+        let published: Vec<String> = records.iter().map(LogRecord::encode).collect();
+        // Large values go to the value log first, so no VSET points past its end
+        let lines = match self.separate_values(records)? {
+            Some(separated) => separated.iter().map(LogRecord::encode).collect(),
+            None => published.clone(),
+        };
+        let stamps = self.stamps(lines.len())?;
+        let sealed: Vec<String> = lines.iter().zip(&stamps).map(|(line, stamp)| storage::seal_record_at(line, *stamp)).collect();
+        let result = self.fs.append_many(&self.data_file, &sealed);
+        let offsets = self.note_append(result)?;
+        self.records_since_checkpoint += lines.len() as u64;
+        // Subscribers get the values themselves; the value log is ours
+        for (record, stamp) in published.iter().zip(&stamps) {
+            if let Some(replication) = &mut self.replication {
+                replication.publish(stamp.seq, record);
+            }
+            if let Some(cdc) = &mut self.cdc {
+                cdc.publish(record);
+            }
+            if let Some(webhooks) = &self.webhooks {
+                webhooks.notify_record(record, expired);
+            }
+        }
+        self.followers.retain(|follower| follower.records(&published));
+        Ok(offsets.into_iter().zip(lines).map(|(offset, line)| layout.decode(offset, &line)).collect())
+    }
+
+
+    /// Sequence number of the last record stamped in the data file (`0`
+    /// if none is), read back from the log the first time it is needed.
+    ///
+    /// # Returns
+    /// `Err(message)` if the log can't be read.
+    pub fn log_seq(&mut self) -> Result<u64, String> {
+        match self.last_seq {
+            Some(seq) => Ok(seq),
+            None => {
+                let seq = storage::last_seq(&*self.fs, &self.data_file).map_err(|e| format!("persistence failure: {}", e))?;
+                self.last_seq = Some(seq);
+                Ok(seq)
+            }
+        }
+    }
+
+
+    /// The stamps of the next `count` records appended: the sequence
+    /// numbers after the last one logged, and the time now.
+    ///
+    /// # Returns
+    /// `Err(message)` if the last sequence number can't be read back from
+    /// the log.
+    fn stamps(&mut self, count: usize) -> Result<Vec<Stamp>, String> {
+        let last = self.log_seq()?;
+        // Taken even if the append fails, so a partly written batch is never numbered twice
+        self.last_seq = Some(last + count as u64);
+        let unix_ms = crate::ttl::unix_now_ms();
+        Ok((1..=count as u64).map(|i| Stamp { seq: last + i, unix_ms }).collect())
+    }
+
+
+    /// Appends the values [`Session::value_log`] separates to the value
+    /// log in one append.
+    ///
+    /// # Returns
+    /// * `Ok(Some(records))` with those `SET`s turned into `VSET`s.
+    /// * `Ok(None)` if no value is separated.
+    /// * `Err(message)` if the append failed.
+    fn separate_values(&mut self, records: &[LogRecord]) -> Result<Option<Vec<LogRecord>>, String> {
+        let separated: Vec<bool> = records.iter()
+            .map(|r| matches!(r, LogRecord::Set { value, .. } if self.value_log.separates(value)))
+            .collect();
+        let fields: Vec<String> = records.iter().zip(&separated).filter_map(|(record, &separate)| match record {
+            LogRecord::Set { value, .. } if separate => Some(storage::escape_bytes(value)),
+            _ => None,
+        }).collect();
+        if fields.is_empty() {
+            return Ok(None);
+        }
+
+        let generation = self.value_log.generation();
+        let result = self.fs.append_many(&storage::value_log_path(&self.data_file, generation), &fields);
+        let mut refs = self.note_append(result)?.into_iter().zip(&fields).map(|(offset, field)| {
+            ValueRef { generation, ptr: ValuePointer { offset, len: field.len() as u32, raw: false } }
+        });
+        let records = records.iter().zip(separated).map(|(record, separate)| {
+            match (record, separate.then(|| refs.next()).flatten()) {
+                (LogRecord::Set { key, .. }, Some(value)) => LogRecord::SetRef { key: key.clone(), value },
+                _ => record.clone(),
+            }
+        });
+        Ok(Some(records.collect()))
+    }
+
+
+    /// Tracks the outcome of a log append.
+    ///
+    /// A success resets [`Session::write_failures`]; a failure bumps it and
+    /// turns the session read-only once `read_only_after` is reached.
+    pub(crate) fn note_append<T>(&mut self, result: std::io::Result<T>) -> Result<T, String> {
+        match result {
+            Ok(written) => {
+                self.write_failures = 0;
+                Ok(written)
+            }
+            Err(e) => {
+                self.write_failures += 1;
+                if self.read_only_after > 0 && self.write_failures >= self.read_only_after {
+                    self.read_only = true;
+                }
+                Err(format!("persistence failure: {}", e))
+            }
+        }
+    }
+
+
+    /// Applies one logged change to the in-memory state.
+    ///
+    /// Shared by live writes (after they are appended) and log replay, so
+    /// both paths keep the index, live keys, TTLs, spill and cache in step.
+    pub(crate) fn apply_op(&mut self, op: ReplayOp) {
+        match op {
+            ReplayOp::Set(key, value, ptr) => {
+                if let Some(cache) = &mut self.cache {
+                    cache.invalidate(&key);
+                }
+                // Memory-limited: remember where the value lives
+                let evicted = match &mut self.spill {
+                    Some(spill) => spill.record_write(&key, ptr),
+                    None => Vec::new(),
+                };
+                self.value_log.forget(&key);
+                self.live_keys.insert(key.clone());
+                self.put_value(key, value);
+                self.evict_values(&evicted);
+            }
+            ReplayOp::SetRef(key, value) => {
+                if let Some(cache) = &mut self.cache {
+                    cache.invalidate(&key);
+                }
+                // The value is not in the log, so spillover has nothing to point at
+                if let Some(spill) = &mut self.spill {
+                    spill.forget(&key);
+                }
+                self.value_log.record(&key, value);
+                self.live_keys.insert(key.clone());
+                self.put_value(key, Vec::new());
+            }
+            ReplayOp::Del(key) => {
+                match &mut self.replayed {
+                    Some(replayed) => {
+                        replayed.remove(&key);
+                    }
+                    None => {
+                        self.index.delete(&key);
+                    }
+                }
+                self.live_keys.remove(&key);
+                self.ttl.clear_expiration(&key);
+                self.value_log.forget(&key);
+                if let Some(spill) = &mut self.spill {
+                    spill.forget(&key);
+                }
+                if let Some(cache) = &mut self.cache {
+                    cache.invalidate(&key);
+                }
+            }
+            ReplayOp::ExpireAt(key, at) => {
+                // A deadline that passed while the store was down
+                if self.live_keys.contains(&key) && !self.ttl.set_expires_at(&key, at) {
+                    self.apply_op(ReplayOp::Del(key));
+                }
+            }
+            ReplayOp::Persist(key) => {
+                self.ttl.clear_expiration(&key);
+            }
+        }
+    }
+
+
+    /// Drops the in-memory values of the given keys, leaving the keys indexed.
+    pub(crate) fn evict_values(&mut self, keys: &[String]) {
+        for key in keys {
+            let value = match &mut self.replayed {
+                Some(replayed) => replayed.get_mut(key),
+                None => self.index.get_bytes_mut(key),
+            };
+            if let Some(val) = value {
+                std::mem::take(val);
+            }
+        }
+    }
+
+
+    /// Sets a key's value in the index, or among the values being
+    /// replayed while [`crate::load_data`] runs.
+    pub(crate) fn put_value(&mut self, key: String, value: Vec<u8>) {
+        match &mut self.replayed {
+            Some(replayed) => {
+                replayed.insert(key, value);
+            }
+            None => {
+                self.index.insert_bytes(key, value);
+            }
+        }
+    }
+
+
+    /// The user commands currently run as.
+    ///
+    /// # Returns
+    /// The `AUTH`ed user, else the ACL's `default` user; `None` if neither
+    /// exists (or there is no ACL).
+    pub fn current_user(&self) -> Option<&AclUser> {
+        let acl = self.acl.as_ref()?;
+        acl.users.get(self.user.as_deref().unwrap_or("default"))
+    }
+
+
+    /// Checks the current user may run `cmd` on `keys`.
+    ///
+    /// Always succeeds without an ACL, and for commands outside every
+    /// category (`AUTH`, `EXIT`, ...).
+    ///
+    /// # Returns
+    /// `Err` with a `NOAUTH` or `NOPERM` message, without the `ERR` prefix.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Acl, Session};
+    /// let mut session = Session::ephemeral();
+    /// session.acl = Some(Acl::parse("user default pw read app:*").unwrap());
+    /// assert!(session.check_access("GET", &["app:1"]).is_ok());
+    /// assert!(session.check_access("GET", &["secret"]).is_err());
+    /// assert!(session.check_access("SET", &["app:1"]).is_err());
+    /// ```
+    pub fn check_access(&self, cmd: &str, keys: &[&str]) -> Result<(), String> {
+        let (Some(_), Some(category)) = (&self.acl, crate::Category::of(cmd)) else {
+            return Ok(());
+        };
+        let Some(user) = self.current_user() else {
+            return Err("NOAUTH authentication required".to_string());
+        };
+        if !user.allows(category) {
+            return Err(format!("NOPERM user '{}' may not run {} ({} commands)", user.name, cmd, category.name()));
+        }
+        match keys.iter().find(|k| !user.allows_key(k)) {
+            Some(key) => Err(format!("NOPERM user '{}' has no access to key '{}'", user.name, key)),
+            None => Ok(()),
+        }
+    }
+
+
+    /// Every live key the current user may see, with its value and
+    /// absolute expiration, in key order: what `EXPORT` writes.
+    pub fn export_rows(&mut self) -> Vec<KvRow> {
+        let now_ms = crate::ttl::unix_now_ms() as i64;
+        let mut keys = Vec::new();
+        self.index.collect_keys(&mut keys);
+        let mut rows = Vec::with_capacity(keys.len());
+        for key in keys {
+            if !self.key_visible(&key) {
+                continue;
+            }
+            let ttl = self.ttl_status(&key);
+            if ttl == -2 {
+                continue;
+            }
+            let Some(value) = self.get(&key) else {
+                continue;
+            };
+            rows.push(KvRow { key, value, expires_at: (ttl >= 0).then(|| now_ms + ttl) });
+        }
+        rows
+    }
+
+
+    /// Writes [`Session::export_rows`] to `out` as JSON lines (see
+    /// [`crate::dataset`]).
+    ///
+    /// # Returns
+    /// * `Ok(count)` of exported keys.
+    /// * `Err(io::Error)` from writing.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("a".into(), "1".into());
+    /// let mut out = Vec::new();
+    /// assert_eq!(session.export_json(&mut out).unwrap(), 1);
+    /// assert_eq!(out, b"{\"key\":\"a\",\"value\":\"1\"}\n");
+    /// ```
+    pub fn export_json(&mut self, out: impl std::io::Write) -> std::io::Result<usize> {
+        let rows = self.export_rows();
+        crate::dataset::write_json(&rows, out)?;
+        Ok(rows.len())
+    }
+
+
+    /// Writes [`Session::export_rows`] to `out` as CSV with a
+    /// `key,value,expires_at` header (see [`crate::dataset`]).
+    ///
+    /// # Returns
+    /// * `Ok(count)` of exported keys.
+    /// * `Err(io::Error)` from writing.
+    pub fn export_csv(&mut self, out: impl std::io::Write) -> std::io::Result<usize> {
+        let rows = self.export_rows();
+        crate::dataset::write_csv(&rows, out)?;
+        Ok(rows.len())
+    }
+
+
+    /// Live keys between two bounds, in index order, as `RANGE` lists
+    /// them.
+    ///
+    /// Keys stream from [`BTreeIndex::range`] and each is checked as it
+    /// comes against its TTL (with one clock reading for the whole scan)
+    /// and the current user's ACL, so nothing is collected and expired or
+    /// hidden keys are skipped. Values are left out, since memory-limited
+    /// and separated sessions don't keep them in the index; use
+    /// [`get`](Self::get) for those wanted.
+    ///
+    /// # Example
+    /// ```
+    /// use std::ops::Bound;
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// for key in ["range_doc_a", "range_doc_b", "range_doc_c"] {
+    ///     session.set(key.into(), "v".into());
+    /// }
+    /// session.ttl.set_expiration("range_doc_b", 1);
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    ///
+    /// let keys: Vec<&str> = session.range(Bound::Included("range_doc_"), Bound::Unbounded).collect();
+    /// assert_eq!(keys, vec!["range_doc_a", "range_doc_c"]);
+    /// ```
+    pub fn range<'a>(&'a self, start: Bound<&str>, end: Bound<&'a str>) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.live(self.index.range(start, end))
+    }
+
+
+    /// [`range`](Self::range), largest key first.
+    pub fn range_rev<'a>(&'a self, start: Bound<&'a str>, end: Bound<&str>) -> impl Iterator<Item = &'a str> + use<'a> {
+        self.live(self.index.range_rev(start, end))
+    }
+
+
+    /// Keys of `pairs` that are neither expired nor hidden from the user.
+    fn live<'a>(&'a self, pairs: impl Iterator<Item = (&'a str, &'a [u8])> + 'a) -> impl Iterator<Item = &'a str> + 'a {
+        let now = Instant::now();
+        pairs
+            .map(|(key, _)| key)
+            .filter(move |key| !self.ttl.expired_at(key, now) && self.key_visible(key))
+    }
+
+
+    /// `key` as the session stores it: lowercased when
+    /// [`lowercase_keys`](Self::lowercase_keys) is set, as given otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// assert_eq!(session.normalize_key("Dog"), "Dog");
+    /// session.lowercase_keys = true;
+    /// assert_eq!(session.normalize_key("Dog"), "dog");
+    /// ```
+    pub fn normalize_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        if self.lowercase_keys && key.chars().any(|c| c.to_lowercase().ne([c])) {
+            Cow::Owned(key.to_lowercase())
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+
+
+    /// [`normalize_key`](Self::normalize_key) for an owned key.
+    pub(crate) fn normalize_owned(&self, key: String) -> String {
+        match self.normalize_key(&key) {
+            Cow::Owned(lowered) => lowered,
+            Cow::Borrowed(_) => key,
+        }
+    }
+
+
+    /// `op` with its key normalized, for records replayed into a session
+    /// whose keys are lowercased.
+    fn normalize_op(&self, op: ReplayOp) -> ReplayOp {
+        if !self.lowercase_keys {
+            return op;
+        }
+        match op {
+            ReplayOp::Set(key, value, ptr) => ReplayOp::Set(self.normalize_owned(key), value, ptr),
+            ReplayOp::SetRef(key, value) => ReplayOp::SetRef(self.normalize_owned(key), value),
+            ReplayOp::Del(key) => ReplayOp::Del(self.normalize_owned(key)),
+            ReplayOp::ExpireAt(key, at) => ReplayOp::ExpireAt(self.normalize_owned(key), at),
+            ReplayOp::Persist(key) => ReplayOp::Persist(self.normalize_owned(key)),
+        }
+    }
+
+
+    /// Returns `true` if the current user may see `key` (always, without an ACL).
+    pub fn key_visible(&self, key: &str) -> bool {
+        self.acl.is_none() || self.current_user().is_some_and(|u| u.allows_key(key))
+    }
+
+
+    /// Changes how many values a memory-limited session keeps in memory.
+    ///
+    /// Lowering the limit drops the least recently used values at once;
+    /// they are read back from the log on their next `GET`.
+    ///
+    /// # Returns
+    /// `Err` unless the session is memory-limited (key-only sessions keep
+    /// no values, so they have no limit to change).
+    pub fn set_max_hot_keys(&mut self, max_hot_keys: usize) -> Result<(), String> {
+        let evicted = match &mut self.spill {
+            Some(spill) if !spill.is_key_only() => spill.set_capacity(max_hot_keys),
+            _ => return Err("max_hot_keys needs a memory-limited session".to_string()),
+        };
+        self.evict_values(&evicted);
+        Ok(())
+    }
+
+
+    /// Starts an incremental compaction pass over the data file.
+    ///
+    /// # Returns
+    /// `Err` if a pass is already running or the temp file can't be created.
+    pub fn start_compaction(&mut self) -> std::io::Result<()> {
+        let mut span = crate::telemetry::span("kvstore.compaction.start");
+        let started = self.compactor.start_with(self.fs.clone(), &self.data_file, &self.index);
+        if let Err(e) = &started {
+            span.error(&e.to_string());
+        }
+        started
+    }
+
+
+    /// Garbage-collects the value log (`COMPACT VALUES`).
+    ///
+    /// The values still live are copied to the next generation, which is
+    /// synced before a `VSET` per key points the log at it; once the log
+    /// is synced too, the older generations are removed. A crash at any
+    /// point leaves every value readable.
+    ///
+    /// # Returns
+    /// * `Ok(bytes)` by which the value log shrank.
+    /// * `Err(message)` while loading, in read-only mode, or if a file
+    ///   could not be read or written.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{MemFs, Session};
+    /// let mut session = Session::with_value_log(4);
+    /// session.fs = std::sync::Arc::new(MemFs::new());
+    /// session.set("a".into(), "first".into());
+    /// session.set("a".into(), "second".into());
+    /// assert_eq!(session.collect_value_log(), Ok(6));
+    /// assert_eq!(session.get("a"), Some("second".to_string()));
+    /// assert_eq!(session.value_log.generation(), 1);
+    /// ```
+    pub fn collect_value_log(&mut self) -> Result<u64, String> {
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        if self.read_only {
+            return Err("read-only mode after repeated persistence failures".to_string());
+        }
+
+        let mut span = crate::telemetry::span("kvstore.vlog.collect");
+        let old: BTreeSet<u64> = self.value_log.refs().map(|(_, value)| value.generation)
+            .chain([self.value_log.generation()])
+            .collect();
+        let mut live: Vec<(String, ValueRef)> = self.value_log.refs()
+            .filter(|(key, _)| self.ttl_status(key) != -2)
+            .map(|(key, value)| (key.clone(), *value))
+            .collect();
+        live.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let collected = self.copy_live_values(&live, self.value_log.generation() + 1, &old);
+        match &collected {
+            Ok(reclaimed) => span.attr("kvstore.vlog.reclaimed_bytes", *reclaimed),
+            Err(e) => span.error(e),
+        };
+        collected
+    }
+
+
+    /// Moves `live` to generation `next` for [`Session::collect_value_log`],
+    /// then removes the `old` generations.
+    fn copy_live_values(&mut self, live: &[(String, ValueRef)], next: u64, old: &BTreeSet<u64>) -> Result<u64, String> {
+        let mut fields = Vec::with_capacity(live.len());
+        for (_, value) in live {
+            let path = storage::value_log_path(&self.data_file, value.generation);
+            let field = self.fs.read_at(&path, value.ptr.offset, value.ptr.len as usize)
+                .and_then(|bytes| String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+                .map_err(|e| format!("cannot read the value log: {}", e))?;
+            fields.push(field);
+        }
+
+        // The new generation is durable before anything points at it
+        let path = storage::value_log_path(&self.data_file, next);
+        let written = self.fs.create(&path)
+            .and_then(|()| if fields.is_empty() { Ok(Vec::new()) } else { self.fs.append_many(&path, &fields) })
+            .and_then(|offsets| self.fs.sync(&path).map(|()| offsets));
+        let offsets = self.note_append(written)?;
+        let moved: Vec<(String, ValueRef)> = live.iter().zip(offsets).zip(&fields)
+            .map(|(((key, _), offset), field)| {
+                (key.clone(), ValueRef { generation: next, ptr: ValuePointer { offset, len: field.len() as u32, raw: false } })
+            })
+            .collect();
+
+        // Only a durable VSET for every live value frees the old files
+        let stamps = self.stamps(moved.len())?;
+        let records: Vec<String> = moved.iter().zip(stamps)
+            .map(|((key, value), stamp)| storage::seal_record_at(&LogRecord::SetRef { key: key.clone(), value: *value }.encode(), stamp))
+            .collect();
+        if !records.is_empty() {
+            let result = self.fs.append_many(&self.data_file, &records).and_then(|_| self.fs.sync(&self.data_file));
+            self.note_append(result)?;
+            self.records_since_checkpoint += records.len() as u64;
+        }
+        for (key, value) in moved {
+            self.value_log.record(&key, value);
+        }
+        self.value_log.start_generation(next);
+
+        let mut reclaimed = 0;
+        for &generation in old {
+            let path = storage::value_log_path(&self.data_file, generation);
+            reclaimed += self.fs.end(&path).unwrap_or(0);
+            let _ = self.fs.remove(&path);
+        }
+        let kept = self.fs.end(&path).unwrap_or(0);
+        Ok(reclaimed.saturating_sub(kept))
+    }
+
+
+    /// Returns `true` while the log is still being replayed in the background.
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+
+    /// Returns `true` if a read of `key` can't be answered yet because the
+    /// background replay hasn't reached it.
+    pub fn loading_miss(&self, key: &str) -> bool {
+        match &self.loading {
+            Some(load) => load.queued_value(key).is_none() && !self.live_keys.contains(key),
+            None => false,
+        }
+    }
+
+
+    /// Applies one change decoded from the log.
+    ///
+    /// Writes over the size limits are counted in `rejected_records` and
+    /// skipped, so an earlier value for the key (if any) stays. Separated
+    /// values are not read, so only their key is checked.
+    pub(crate) fn replay_op(&mut self, op: ReplayOp) {
+        let op = self.normalize_op(op);
+        let checked = match &op {
+            ReplayOp::Set(key, value, _) => self.limits.check_write(key, value),
+            ReplayOp::SetRef(key, _) => self.limits.check_key(key),
+            _ => Ok(()),
+        };
+        if checked.is_err() {
+            self.rejected_records += 1;
+            return;
+        }
+        self.apply_op(op);
+    }
+
 
-    /// Optional active transaction session (`None` if not in BEGIN/COMMIT mode).
-    pub transaction: Option<Transaction>,
-}
+    /// Applies up to `max` records from a background replay.
+    ///
+    /// Records arrive newest first, so a key already present is skipped.
+    /// When the replay is exhausted the queued writes are applied and the
+    /// session leaves loading mode.
+    pub fn poll_loading(&mut self, max: usize) -> std::io::Result<()> {
+        let Some(load) = &mut self.loading else {
+            return Ok(());
+        };
+        let records = load.next_records(max);
+        let drained = load.is_drained();
+
+        for record in records {
+            self.replay_loaded(record);
+        }
+        if drained {
+            self.complete_loading()?;
+        }
+        Ok(())
+    }
 
 
-impl Session {
-    /// Creates a new, empty session with its own index and TTL manager.
+    /// Blocks until the background replay is done, then applies queued writes.
     ///
     /// # Example
     /// ```
-    /// use kvstore::Session;
-    /// let mut session = Session::new();
-    /// assert!(session.transaction.is_none());
+    /// use kvstore::{load_data_background, log_text, Fs, MemFs, Session};
+    /// let fs = MemFs::new();
+    /// fs.append("log", &log_text(&["SET dog bark", "SET dog woof"])).unwrap();
+    ///
+    /// let mut session = Session::ephemeral();
+    /// session.fs = std::sync::Arc::new(fs);
+    /// load_data_background(&mut session, "log");
+    /// session.finish_loading().unwrap();
+    ///
+    /// assert!(!session.is_loading());
+    /// assert_eq!(session.get("dog"), Some("woof".to_string()));
     /// ```
-    pub fn new() -> Self {
-        Self {
-            index: BTreeIndex::new(2),
-            ttl: TTLManager::new(),
-            transaction: None,
+    pub fn finish_loading(&mut self) -> std::io::Result<()> {
+        while let Some(load) = &mut self.loading {
+            if load.is_drained() {
+                break;
+            }
+            for record in load.wait_records(LOAD_BATCH) {
+                self.replay_loaded(record);
+            }
         }
+        self.complete_loading()
     }
 
-    /// Returns `true` if a transaction is currently active.
-    pub fn in_transaction(&self) -> bool {
-        self.transaction.is_some()
+
+    /// Applies one key from a background replay, with its TTL, unless the
+    /// key is already present.
+    fn replay_loaded(&mut self, (op, expires_at): LoadedRecord) {
+        let op = self.normalize_op(op);
+        let (ReplayOp::Set(key, ..) | ReplayOp::SetRef(key, _)) = &op else {
+            return;
+        };
+        if self.live_keys.contains(key) {
+            return;
+        }
+        let key = key.clone();
+        self.replay_op(op);
+        if let Some(at) = expires_at {
+            self.replay_op(ReplayOp::ExpireAt(key, at));
+        }
     }
 
-    /// Starts a new transaction, overwriting any previous uncommitted session.
-    pub fn begin_transaction(&mut self) {
-        self.transaction = Some(Transaction::new());
+
+    /// Leaves loading mode and replays the writes queued meanwhile.
+    ///
+    /// Every queued write is attempted; the first one that can't be logged
+    /// is reported as an `Other` error.
+    fn complete_loading(&mut self) -> std::io::Result<()> {
+        let Some(load) = self.loading.take() else {
+            return Ok(());
+        };
+        let (queued, report) = load.finish_with_report()?;
+        self.record_load(report);
+        let mut failed = None;
+        for (key, value) in queued {
+            let result = match value {
+                Some(value) => self.apply_set(key, value.into_bytes()),
+                None => self.try_delete(&key).map(|_| ()),
+            };
+            if let Err(e) = result {
+                failed.get_or_insert(e);
+            }
+        }
+        match failed {
+            Some(e) => Err(std::io::Error::other(e)),
+            None => Ok(()),
+        }
     }
 
 
-    /// Commits the active transaction, applying all pending key–value updates
-    /// to the main index and writing them to the persistent log.
+    /// Completes a replay's [`LoadReport`] with the session's counts,
+    /// keeps it, and prints it to stderr if `report_load` is set.
+    pub(crate) fn record_load(&mut self, mut report: LoadReport) {
+        report.rejected = self.rejected_records;
+        report.live_keys = self.live_keys.len();
+        if self.report_load {
+            eprintln!("{}", report);
+        }
+        self.load_report = Some(report);
+    }
+
+
+    /// Applies what the writer process logged since the last call, when
+    /// this session is a reader.
+    pub fn poll_tail(&mut self) {
+        let Some(mut tail) = self.tail.take() else {
+            return;
+        };
+        if let Err(e) = tail.poll(self) {
+            eprintln!("reader: cannot follow {}: {}", tail.path, e);
+        }
+        self.tail = Some(tail);
+    }
+
+
+    /// Applies the records our primary streamed since the last call.
     ///
-    /// This method finalizes an in-progress transaction by:
-    /// - Iterating over each buffered key–value pair in the transaction’s
-    ///   `pending` map.
-    /// - Inserting those values into the live `index`.
-    /// - Appending each update to the write-ahead log as a `SET` operation.
+    /// Each record is appended to our own log and replayed into the
+    /// index, so a restart finds the same data. A record that can't be
+    /// logged stops replication rather than leave a gap.
+    pub fn poll_replication(&mut self) {
+        let events = match &mut self.replica {
+            Some(replica) => replica.next_events(),
+            None => return,
+        };
+        for event in events {
+            match event {
+                ReplicaEvent::Connected => {
+                    if let Some(replica) = &mut self.replica {
+                        replica.link_up = true;
+                    }
+                }
+                ReplicaEvent::Lost(e) => {
+                    if let Some(replica) = &mut self.replica {
+                        eprintln!("replication: lost {}: {}", replica.primary, e);
+                        replica.link_up = false;
+                    }
+                }
+                ReplicaEvent::Snapshot { seq, records } => {
+                    if let Err(e) = self.apply_snapshot(seq, &records) {
+                        eprintln!("replication: full sync failed: {}", e);
+                        self.replica = None;
+                        return;
+                    }
+                }
+                ReplicaEvent::Record(seq, record) => {
+                    if let Err(e) = self.apply_replicated(seq, &record) {
+                        eprintln!("replication: stopped at sequence {}: {}", seq, e);
+                        self.replica = None;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+
+    /// Replaces our data file and index with the primary's snapshot.
     ///
-    /// Once all changes are applied, the transaction is cleared and removed
-    /// from the session.
+    /// The records replace the data file atomically (see
+    /// [`storage::replace_file`]), then are replayed as at startup. A running
+    /// compaction is abandoned, replicas of ours start over with a full
+    /// sync of their own, and the change stream is reset and re-fed.
+    fn apply_snapshot(&mut self, seq: u64, records: &[String]) -> Result<(), String> {
+        let mut span = crate::telemetry::span("kvstore.replication.full_sync");
+        span.attr("kvstore.repl.seq", seq).attr("kvstore.repl.records", records.len());
+        self.compactor.cancel();
+        let file = self.data_file.clone();
+        // Our own sequence numbers carry on past the snapshot
+        let replaced = storage::last_seq(&*self.fs, &file)
+            .and_then(|seq| storage::replace_file(&*self.fs, &file, &storage::log_text_after(records, seq)));
+        if let Err(e) = replaced {
+            return Err(format!("persistence failure: {}", e));
+        }
+
+        crate::load_data(self, &file);
+        if let Some(cdc) = &mut self.cdc {
+            cdc.reset();
+            for record in records {
+                cdc.publish(record);
+            }
+        }
+        self.followers.retain(|follower| follower.reset(records));
+        if let Some(replica) = &mut self.replica {
+            replica.applied_seq = seq;
+        }
+        if let Some(replication) = &mut self.replication {
+            *replication = ReplicationLog::new(replication.capacity());
+        }
+        Ok(())
+    }
+
+
+    /// Logs and applies one record streamed by the primary.
+    fn apply_replicated(&mut self, seq: u64, record: &str) -> Result<(), String> {
+        let mut span = crate::telemetry::span("kvstore.replication.apply");
+        span.attr("kvstore.repl.seq", seq);
+        // A primary publishes values, never pointers into its value log
+        let Some(record) = LogRecord::decode(record).filter(|r| !matches!(r, LogRecord::SetRef { .. })) else {
+            let e = format!("unknown record from the primary: {}", record);
+            span.error(&e);
+            return Err(e);
+        };
+        let written = self.append_changes(&[record], false).inspect_err(|e| {
+            span.error(e);
+        })?;
+        for op in written.into_iter().flatten() {
+            self.replay_op(op);
+        }
+        if let Some(replica) = &mut self.replica {
+            replica.applied_seq = seq;
+        }
+        Ok(())
+    }
+
+
+    /// Runs the bounded background work due after a command.
     ///
-    /// # Behavior
+    /// Called by the REPL after every command: applies up to one batch of
+    /// background-replayed records, reclaims up to `expire_budget` expired
+    /// keys, takes one compaction step, then a checkpoint and a log
+    /// rotation if either is due.
+    pub fn tick(&mut self) -> std::io::Result<()> {
+        self.poll_loading(LOAD_BATCH)?;
+        self.expire_tick();
+        self.auto_compaction_tick()?;
+        self.compaction_tick()?;
+        self.checkpoint_tick()?;
+        self.rotate_tick().map(|_| ())
+    }
+
+
+    /// Deletes up to `expire_budget` keys whose TTL has elapsed, each
+    /// through [`Session::expire_key`].
     ///
-    /// If no transaction is active, this method does nothing.
-    /// If a transaction exists, all staged updates become durable and visible
-    /// to subsequent operations.
+    /// # Returns
+    /// The number of keys reclaimed.
     ///
     /// # Example
     /// ```
-    /// use kvstore::{Session, BTreeIndex};
+    /// use kvstore::Session;
+    /// let mut session = Session::ephemeral();
+    /// session.set("tick_doc".into(), "v".into());
+    /// session.ttl.set_expiration("tick_doc", 1);
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
     ///
-    /// let mut session = Session::new();
-    /// session.begin_transaction();
+    /// assert_eq!(session.expire_tick(), 1);
+    /// assert!(session.index.search("tick_doc").is_none());
+    /// ```
+    pub fn expire_tick(&mut self) -> usize {
+        // Peeked, not popped: a key whose delete can't be logged keeps its TTL
+        let due = self.ttl.due_keys(self.expire_budget);
+        due.iter().filter(|key| self.expire_key(key)).count()
+    }
+
+
+    /// Runs one bounded compaction step if a pass is in progress.
+    ///
+    /// # Returns
+    /// `Ok(true)` if this tick finished the pass.
+    pub fn compaction_tick(&mut self) -> std::io::Result<bool> {
+        let Some((done, total)) = self.compactor.progress() else {
+            return self.compactor.step(&self.index, &self.ttl, &self.value_log, self.spill.as_mut());
+        };
+        let mut span = crate::telemetry::span("kvstore.compaction.step");
+        span.attr("kvstore.compaction.done", done).attr("kvstore.compaction.total", total);
+        let stepped = self.compactor.step(&self.index, &self.ttl, &self.value_log, self.spill.as_mut());
+        match &stepped {
+            Ok(finished) => span.attr("kvstore.compaction.finished", *finished),
+            Err(e) => span.error(&e.to_string()),
+        };
+        // The compacted log holds every key, so it needs no checkpoint
+        if let Ok(true) = stepped {
+            self.checkpoint_id = 0;
+            self.records_since_checkpoint = self.live_keys.len() as u64;
+        }
+        stepped
+    }
+
+
+    /// Starts a compaction pass once [`Session::compaction_policy`] says
+    /// the log is due one, unless a pass or load is running or the session
+    /// is read-only. The log's records are counted since the last
+    /// checkpoint or compaction, so the check never reads the log.
     ///
-    /// session.set("x".into(), "10".into());
-    /// session.set("y".into(), "20".into());
+    /// # Returns
+    /// `Ok(true)` if this tick started a pass.
     ///
-    /// // Persist all staged writes
-    /// session.commit_transaction();
+    /// # Example
+    /// ```
+    /// use kvstore::{CompactionPolicy, MemFs, Session};
+    /// let mut session = Session::ephemeral();
+    /// session.fs = std::sync::Arc::new(MemFs::new());
+    /// session.compaction_policy = CompactionPolicy { min_dead_records: 3, ..CompactionPolicy::recommended() };
+    /// for i in 0..4 {
+    ///     session.set("auto_doc".into(), i.to_string());
+    /// }
+    /// assert!(session.auto_compaction_tick().unwrap());
+    /// while !session.compaction_tick().unwrap() {}
+    /// assert_eq!(session.storage_stats().unwrap().dead_records, 0);
+    /// assert!(!session.auto_compaction_tick().unwrap());
+    /// ```
+    pub fn auto_compaction_tick(&mut self) -> std::io::Result<bool> {
+        if !self.compaction_policy.is_enabled() || self.compactor.progress().is_some() || self.is_loading() || self.read_only {
+            return Ok(false);
+        }
+        let log_bytes = match self.compaction_policy.max_log_bytes {
+            Some(_) => self.fs.end(&self.data_file)?,
+            None => 0,
+        };
+        if !self.compaction_policy.is_due(self.records_since_checkpoint, self.live_keys.len() as u64, log_bytes) {
+            return Ok(false);
+        }
+        self.start_compaction()?;
+        Ok(true)
+    }
+
+
+    /// Saves every live key to the data file's snapshot and truncates the
+    /// log behind it (see [`crate::write_checkpoint`]). Values in the
+    /// value log are read into the snapshot.
     ///
-    /// assert_eq!(session.index.get("x"), Some("10".to_string()));
-    /// assert_eq!(session.index.get("y"), Some("20".to_string()));
+    /// # Returns
+    /// * `Ok(id)` of the new checkpoint.
+    /// * `Err(message)` if values live only in the log (memory-limited
+    ///   sessions), a compaction or load is running, the session is
+    ///   read-only, or a file could not be read or written.
+    ///
+    /// # Example
     /// ```
-    pub fn commit_transaction(&mut self) {
-        if let Some(tx) = self.transaction.take() {
+    /// use kvstore::Session;
+    /// let dir = std::env::temp_dir().join("kvstore_checkpoint_doc");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let mut session = Session::open(&dir).unwrap();
+    /// session.set("a".into(), "1".into());
+    /// assert_eq!(session.checkpoint(), Ok(1));
+    /// session.set("b".into(), "2".into());
+    /// # kvstore::close_log(&session.data_file).unwrap();
+    /// drop(session);
+    ///
+    /// let reopened = Session::open(&dir).unwrap();
+    /// assert_eq!(reopened.load_report.unwrap().checkpoint_keys, 1);
+    /// assert_eq!(reopened.index.search("b"), Some("2"));
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn checkpoint(&mut self) -> Result<u64, String> {
+        if self.spill.is_some() {
+            return Err("checkpoints need every value in memory".to_string());
+        }
+        if self.compactor.progress().is_some() {
+            return Err("compaction is running".to_string());
+        }
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        if self.read_only {
+            return Err("read-only mode after repeated persistence failures".to_string());
+        }
 
-            // Apply all key/value mutations to the main index.
-            for (key, val) in tx.pending.iter() {
-                self.index.insert(key.clone(), val.clone());
-                 // Persist this change to disk (Gradebot requires this!)
-                let line = format!("SET {} {}", key, val);
-                let _ = storage::append_write(&storage::get_data_file(), &line);
+        let mut span = crate::telemetry::span("kvstore.checkpoint");
+        let mut keys = Vec::new();
+        self.index.collect_keys(&mut keys);
+        let mut pairs: Vec<(String, Vec<u8>)> = Vec::with_capacity(keys.len());
+        for key in keys.into_iter().filter(|key| self.ttl_status(key) != -2) {
+            // Separated values are copied into the checkpoint, which replaces their VSETs
+            let value = match self.value_log.location(&key) {
+                Some(value) => self.read_separated(value).map_err(|e| format!("cannot read the value log: {}", e))?,
+                None => match self.index.get_bytes(&key) {
+                    Some(value) => value.to_vec(),
+                    None => continue,
+                },
+            };
+            pairs.push((key, value));
+        }
+        span.attr("kvstore.checkpoint.keys", pairs.len());
+        match crate::write_checkpoint(&*self.fs, &self.data_file, &pairs, &self.ttl.deadlines_unix_ms()) {
+            Ok(id) => {
+                self.checkpoint_id = id;
+                self.records_since_checkpoint = 0;
+                Ok(id)
             }
+            Err(e) => {
+                span.error(&e.to_string());
+                Err(format!("persistence failure: {}", e))
+            }
+        }
+    }
 
-            // Transaction ends
-            println!("OK");
-        } else {
-            println!("ERR no active transaction");
+
+    /// Takes a checkpoint once `checkpoint_every` records were logged
+    /// since the last one, unless a compaction or load is running or the
+    /// session is read-only.
+    ///
+    /// # Returns
+    /// `Ok(true)` if this tick took one.
+    pub fn checkpoint_tick(&mut self) -> std::io::Result<bool> {
+        if self.checkpoint_every == 0
+            || self.records_since_checkpoint < self.checkpoint_every
+            || self.compactor.progress().is_some()
+            || self.is_loading()
+            || self.read_only
+        {
+            return Ok(false);
+        }
+        self.checkpoint().map(|_| true).map_err(std::io::Error::other)
+    }
+
+
+    /// Moves the log into its next cold segment, gzip-compressed, and
+    /// starts it over (see [`crate::rotate_log`]). Replay reads the
+    /// segments back first, so the data loads as before.
+    ///
+    /// # Returns
+    /// * `Ok(id)` of the new segment.
+    /// * `Err(message)` if values live only in the log (memory-limited
+    ///   sessions), a compaction or load is running, the session is
+    ///   read-only, or a file could not be read or written.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{segment_path, Fs, MemFs, Session};
+    /// use std::sync::Arc;
+    /// let fs = Arc::new(MemFs::new());
+    /// let mut session = Session::ephemeral();
+    /// session.fs = fs.clone();
+    /// session.set("a".into(), "1".into());
+    /// assert_eq!(session.rotate_log(), Ok(1));
+    /// session.set("b".into(), "2".into());
+    /// assert!(fs.contents(&segment_path(&session.data_file, 1)).is_some());
+    ///
+    /// let mut reopened = Session::ephemeral();
+    /// reopened.fs = fs;
+    /// kvstore::load_data(&mut reopened, &session.data_file);
+    /// assert_eq!((reopened.index.search("a"), reopened.index.search("b")), (Some("1"), Some("2")));
+    /// ```
+    pub fn rotate_log(&mut self) -> Result<u64, String> {
+        if self.spill.is_some() {
+            return Err("cold segments need every value in memory".to_string());
+        }
+        if self.compactor.progress().is_some() {
+            return Err("compaction is running".to_string());
+        }
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+        if self.read_only {
+            return Err("read-only mode after repeated persistence failures".to_string());
+        }
+
+        let mut span = crate::telemetry::span("kvstore.rotate");
+        match crate::rotate_log(&*self.fs, &self.data_file) {
+            Ok(id) => {
+                span.attr("kvstore.segment.id", id);
+                Ok(id)
+            }
+            Err(e) => {
+                span.error(&e.to_string());
+                Err(format!("persistence failure: {}", e))
+            }
+        }
+    }
+
+
+    /// Rotates the log once it is `segment_bytes` long, unless a
+    /// compaction or load is running or the session is read-only.
+    ///
+    /// # Returns
+    /// `Ok(true)` if this tick rotated it.
+    pub fn rotate_tick(&mut self) -> std::io::Result<bool> {
+        if self.segment_bytes == 0 || self.compactor.progress().is_some() || self.is_loading() || self.read_only {
+            return Ok(false);
+        }
+        if self.fs.end(&self.data_file)? < self.segment_bytes {
+            return Ok(false);
+        }
+        self.rotate_log().map(|_| true).map_err(std::io::Error::other)
+    }
+
+
+    /// Size and liveness of the log right now (see [`StorageStats`]),
+    /// found by scanning it: how many records a compaction would drop.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` if the log cannot be read.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{MemFs, Session};
+    /// let mut session = Session::ephemeral();
+    /// session.fs = std::sync::Arc::new(MemFs::new());
+    /// session.set("a".into(), "1".into());
+    /// session.set("a".into(), "2".into());
+    /// session.set("b".into(), "3".into());
+    /// session.delete("b");
+    ///
+    /// let stats = session.storage_stats().unwrap();
+    /// assert_eq!((stats.records, stats.live_keys, stats.dead_records), (4, 1, 3));
+    /// assert_eq!(stats.last_compaction, None);
+    /// ```
+    pub fn storage_stats(&self) -> std::io::Result<StorageStats> {
+        let mut stats = StorageStats::scan(&*self.fs, &self.data_file, |key| self.live_keys.contains(key))?;
+        stats.live_keys = self.live_keys.len();
+        stats.last_compaction = self.compactor.last_completed();
+        Ok(stats)
+    }
+
+
+    /// Rolls the session back to how its log stood at `unix_ms` (Unix
+    /// ms) and reloads it: the records appended after then are moved
+    /// aside with [`crate::recover_to_with`].
+    ///
+    /// Only what the log still holds can be recovered; a compaction or
+    /// checkpoint folds away the history before it.
+    ///
+    /// # Returns
+    /// * `Ok(n)` with the number of records rolled back.
+    /// * `Err(message)` if a compaction or load is running, or the log
+    ///   could not be cut; the session is then unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Session;
+    /// let dir = std::env::temp_dir().join("kvstore_recover_to_doc");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let mut session = Session::open(&dir).unwrap();
+    /// session.set("a".into(), "1".into());
+    /// let before = kvstore::ttl::unix_now_ms();
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    /// session.set("a".into(), "2".into());
+    ///
+    /// assert_eq!(session.recover_to(before), Ok(1));
+    /// assert_eq!(session.get("a"), Some("1".to_string()));
+    /// # kvstore::close_log(&session.data_file).unwrap();
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn recover_to(&mut self, unix_ms: u64) -> Result<u64, String> {
+        if self.compactor.progress().is_some() {
+            return Err("compaction is running".to_string());
+        }
+        if self.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
         }
+        let undone = storage::recover_to_with(&*self.fs, &self.data_file, unix_ms)
+            .map_err(|e| format!("cannot recover {}: {}", self.data_file, e))?;
+        let file = self.data_file.clone();
+        crate::load_data(self, &file);
+        Ok(undone)
     }
 
 
@@ -134,11 +2293,12 @@ impl Session {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Collation, ValuePointer};
 
     // Basic Session Creation
     #[test]
     fn test_new_session_initial_state() {
-        let session = Session::new();
+        let session = Session::ephemeral();
 
         // Session should start empty and without a transaction
         assert!(session.index.search("nothing").is_none());
@@ -150,7 +2310,7 @@ mod tests {
     // Transaction Lifecycle
     #[test]
     fn test_begin_transaction_creates_new_tx() {
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.begin_transaction();
         assert!(session.transaction.is_some());
         assert!(session.in_transaction());
@@ -158,7 +2318,7 @@ mod tests {
 
     #[test]
     fn test_commit_transaction_writes_to_index() {
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.begin_transaction();
 
         // Add something to the pending transaction
@@ -174,7 +2334,7 @@ mod tests {
 
     #[test]
     fn test_abort_transaction_discards_changes() {
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.begin_transaction();
 
         // Add temporary data
@@ -193,7 +2353,7 @@ mod tests {
     // TTL Manager Integration
     #[test]
     fn test_set_and_clear_ttl_in_session() {
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.ttl.set_expiration("dog", 1000);
         assert_eq!(session.ttl.active_count(), 1);
 
@@ -206,7 +2366,7 @@ mod tests {
         use std::thread::sleep;
         use std::time::Duration;
 
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.ttl.set_expiration("temp", 50);
         assert_eq!(session.ttl.active_count(), 1);
 
@@ -219,7 +2379,7 @@ mod tests {
     // Nested Transactions and TTL interaction
     #[test]
     fn test_transaction_with_ttl_manager_present() {
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.begin_transaction();
 
         assert!(session.transaction.is_some());
@@ -232,10 +2392,285 @@ mod tests {
         assert!(session.transaction.is_none());
     }
 
+    // Memory-limited mode
+    #[test]
+    fn test_memory_limit_evicts_and_reloads_values() {
+        let mut session = Session::with_memory_limit(2);
+        session.fs = Arc::new(crate::MemFs::new());
+        session.set("spill_a".into(), "one".into());
+        session.set("spill_b".into(), "two".into());
+        session.set("spill_c".into(), "three".into());
+
+        // Oldest value dropped from memory, key still indexed
+        assert_eq!(session.index.search("spill_a"), Some(""));
+        assert_eq!(session.spill.as_ref().unwrap().hot_count(), 2);
+
+        // Reading it back pulls the value from the log
+        assert_eq!(session.get("spill_a"), Some("one".to_string()));
+        assert_eq!(session.index.search("spill_a"), Some("one"));
+        assert_eq!(session.index.search("spill_b"), Some(""));
+        assert_eq!(session.get("spill_b"), Some("two".to_string()));
+    }
+
+    #[test]
+    fn test_live_keys_follow_set_and_delete() {
+        let mut session = Session::ephemeral();
+        session.set("live_a".into(), "1".into());
+        assert!(session.exists("live_a"));
+
+        // Keys that were never set are rejected before the tree
+        session.index.insert("behind_back".into(), "x".into());
+        assert_eq!(session.get("behind_back"), None);
+
+        assert!(session.delete("live_a"));
+        assert!(!session.exists("live_a"));
+        assert!(!session.live_keys.contains("live_a"));
+    }
+
+    #[test]
+    fn test_ttl_status_checks_the_key() {
+        let mut session = Session::ephemeral();
+        session.set("status_a".into(), "1".into());
+        assert_eq!(session.ttl_status("status_a"), -1);
+
+        // A TTL left behind by a deleted key is not reported
+        session.ttl.set_expiration("status_a", 60_000);
+        assert!(session.ttl_status("status_a") > 0);
+        session.live_keys.remove("status_a");
+        assert_eq!(session.ttl_status("status_a"), -2);
+        assert_eq!(session.ttl_status("never_set"), -2);
+    }
+
+    #[test]
+    fn test_key_only_reads_values_from_log() {
+        let mut session = Session::key_only();
+        session.fs = Arc::new(crate::MemFs::new());
+        session.set("wisc_a".into(), "alpha".into());
+        session.set("wisc_b".into(), "beta".into());
+
+        // Only keys live in the index
+        assert_eq!(session.index.search("wisc_a"), Some(""));
+        assert_eq!(session.get("wisc_a"), Some("alpha".to_string()));
+        assert_eq!(session.get("wisc_b"), Some("beta".to_string()));
+        assert_eq!(session.index.search("wisc_a"), Some(""));
+        assert_eq!(session.spill.as_ref().unwrap().hot_count(), 0);
+    }
+
+    #[test]
+    fn test_delete_is_logged_and_replayed() {
+        let fs = Arc::new(crate::MemFs::new());
+        let mut session = Session::ephemeral();
+        session.fs = fs.clone();
+        session.set("logged_del".into(), "v".into());
+        assert!(session.delete("logged_del"));
+
+        let log = storage::replay_records(&*fs, &session.data_file, 0).unwrap();
+        assert!(log.iter().any(|(_, line)| line == "DEL logged_del"));
+
+        // Recovery goes through the same apply path
+        let mut replayed = Session::ephemeral();
+        replayed.replay_op(ReplayOp::Set("logged_del".into(), "v".into(), ValuePointer { offset: 0, len: 1, raw: false }));
+        replayed.replay_op(ReplayOp::Del("logged_del".into()));
+        assert!(!replayed.exists("logged_del"));
+        assert_eq!(replayed.index.search("logged_del"), None);
+    }
+
+    #[test]
+    fn test_ttls_are_logged_as_deadlines_and_replayed() {
+        let fs = Arc::new(crate::MemFs::new());
+        let on_fs = || {
+            let mut session = Session::new();
+            session.fs = fs.clone();
+            session.data_file = "ttl.db".to_string();
+            session
+        };
+        let mut session = on_fs();
+        session.set("ttl_keep".into(), "v".into());
+        session.set("ttl_gone".into(), "v".into());
+        session.set("ttl_cleared".into(), "v".into());
+        assert_eq!(session.expire("ttl_keep", 60_000), Ok(true));
+        assert_eq!(session.expire("ttl_cleared", 60_000), Ok(true));
+        assert_eq!(session.persist("ttl_cleared"), Ok(true));
+        assert_eq!(session.expire("ttl_missing", 60_000), Ok(false));
+
+        // A deadline that passed while the store was down drops the key
+        fs.append("ttl.db", &storage::seal_record(&storage::expire_at_record("ttl_gone", 1))).unwrap();
+
+        let mut restarted = on_fs();
+        crate::load_data(&mut restarted, "ttl.db");
+        let left = restarted.ttl.ttl_remaining("ttl_keep");
+        assert!(left > 0 && left <= 60_000, "{} ms left", left);
+        assert!(!restarted.exists("ttl_gone"));
+        assert_eq!(restarted.ttl.ttl_remaining("ttl_cleared"), -1);
+    }
+
+    #[test]
+    fn test_expired_keys_are_deleted_through_the_log() {
+        let fs = Arc::new(crate::MemFs::new());
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        session.data_file = "expire.db".to_string();
+        for key in ["exp_get", "exp_full", "exp_tick"] {
+            session.set(key.into(), "v".into());
+            session.ttl.set_expiration(key, 1);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        // A read drops the value with the TTL entry and logs the delete
+        assert_eq!(session.get("exp_get"), None);
+        assert!(session.index.search("exp_get").is_none() && !session.ttl.has_entry("exp_get"));
+        let log = String::from_utf8_lossy(&fs.read("expire.db").unwrap()).into_owned();
+        assert!(log.contains("DEL exp_get"), "{}", log);
+
+        // A delete that can't be logged leaves both, still hidden, to retry
+        fs.set_full(true);
+        assert_eq!(session.get("exp_full"), None);
+        assert!(session.index.search("exp_full").is_some() && session.ttl.has_entry("exp_full"));
+        assert_eq!(session.expire_tick(), 0);
+        fs.set_full(false);
+        assert_eq!(session.expire_tick(), 2);
+        assert_eq!((session.index.len(), session.ttl.active_count()), (0, 0));
+    }
+
+    #[test]
+    fn test_binary_values_round_trip_through_the_log() {
+        let fs = Arc::new(crate::MemFs::new());
+        let on_fs = || {
+            let mut session = Session::new();
+            session.fs = fs.clone();
+            session.data_file = "bytes.db".to_string();
+            session
+        };
+        let binary = vec![0xff, 0x00, b' ', 0xc3, b'\n', 0x80];
+        let mut session = on_fs();
+        session.try_set_bytes("bin".into(), binary.clone()).unwrap();
+        session.try_set_bytes("text".into(), "caf\u{e9}".into()).unwrap();
+        assert_eq!(session.get_bytes("bin"), Some(binary.clone()));
+        assert_eq!(session.get("text"), Some("caf\u{e9}".to_string()));
+
+        // Only the bytes that are not UTF-8 are hex-escaped
+        let log = String::from_utf8(fs.contents("bytes.db").unwrap()).unwrap();
+        assert!(log.contains("SET bin \\xff\\0\\s\\xc3\\n\\x80\t"), "{}", log);
+        assert!(log.contains("SET text caf\u{e9}\t"), "{}", log);
+
+        let mut restarted = on_fs();
+        crate::load_data(&mut restarted, "bytes.db");
+        assert_eq!(restarted.get_bytes("bin"), Some(binary));
+        assert_eq!(restarted.get_bytes("text"), Some("caf\u{e9}".into()));
+
+        // Transactions stage text only
+        restarted.begin_transaction();
+        assert!(restarted.try_set_bytes("staged".into(), vec![0xfe]).is_err());
+        assert!(restarted.try_set_bytes("staged".into(), b"ok".to_vec()).is_ok());
+    }
+
+    #[test]
+    fn test_range_streams_only_live_visible_keys() {
+        let mut session = Session::new();
+        session.fs = Arc::new(crate::MemFs::new());
+        for i in 0..30 {
+            session.set(format!("{}:{:02}", if i % 3 == 0 { "secret" } else { "app" }, i), "v".into());
+        }
+        for i in [1, 2, 28] {
+            session.ttl.set_expiration(&format!("app:{:02}", i), 1);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        let live: Vec<&str> = session.range(Bound::Included("app:"), Bound::Included("app:~")).collect();
+        assert_eq!(live.len(), 17);
+        assert!(!live.contains(&"app:01") && !live.contains(&"app:28"));
+        let mut reversed: Vec<&str> = session.range_rev(Bound::Included("app:"), Bound::Included("app:~")).collect();
+        reversed.reverse();
+        assert_eq!(reversed, live);
+
+        // Nothing was purged by reading, and a limited user sees only their keys
+        assert!(session.index.contains_key("app:01"));
+        session.acl = Some(crate::Acl::parse("user default pw read secret:*").unwrap());
+        assert_eq!(session.range(Bound::Unbounded, Bound::Unbounded).count(), 10);
+    }
+
+    #[test]
+    fn test_lowercase_keys_fold_every_way_in() {
+        let fs = Arc::new(crate::MemFs::new());
+        fs.write_file("case.db", b"SET Dog bark\nSET dog woof\nSET CAT meow\nEXPIREAT Cat 99999999999999\n");
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        session.data_file = "case.db".to_string();
+        session.lowercase_keys = true;
+
+        // Replayed records fold together, last write winning
+        crate::load_data(&mut session, "case.db");
+        assert_eq!(session.get("DOG"), Some("woof".to_string()));
+        assert!(session.ttl_status("cat") > 0);
+        assert_eq!(session.index.len(), 2);
+
+        session.try_set("Bird".into(), "tweet".into()).unwrap();
+        assert_eq!(session.index.search("bird"), Some("tweet"));
+        assert_eq!(session.get_many(&["BIRD", "Dog", "emu"]), vec![Some("tweet".to_string()), Some("woof".to_string()), None]);
+        assert_eq!(session.persist("Cat"), Ok(true));
+        assert!(session.delete("cAT"));
+        assert!(!session.exists("cat"));
+
+        // The log holds the folded names, so a restart finds them either way
+        let log = String::from_utf8(fs.contents("case.db").unwrap()).unwrap();
+        assert!(log.contains("SET bird tweet") && log.contains("DEL cat"), "{}", log);
+    }
+
+    #[test]
+    fn test_failed_appends_are_reported_and_turn_read_only() {
+        let mut session = Session::ephemeral();
+        session.read_only_after = 2;
+        let full = || Err::<u64, _>(std::io::Error::other("no space left on device"));
+
+        assert_eq!(session.note_append(full()).unwrap_err(), "persistence failure: no space left on device");
+        assert_eq!(session.note_append(Ok(7)), Ok(7));
+        assert_eq!(session.write_failures, 0);
+        assert!(!session.read_only);
+
+        // Two failures in a row stop all writes
+        let _ = session.note_append(full());
+        let _ = session.note_append(full());
+        assert!(session.read_only);
+        let err = session.try_set("ro_key".into(), "v".into()).unwrap_err();
+        assert!(err.contains("read-only"));
+        assert!(!session.exists("ro_key"));
+    }
+
+    #[test]
+    fn test_read_only_keeps_keys_it_cannot_delete() {
+        let mut session = Session::ephemeral();
+        session.set("ro_del".into(), "v".into());
+        session.read_only = true;
+
+        assert!(session.try_delete("ro_del").is_err());
+        assert!(!session.delete("ro_del"));
+        assert!(session.exists("ro_del"));
+    }
+
+    #[test]
+    fn test_get_many_follows_index_collation() {
+        let mut session = Session::ephemeral();
+        session.index = BTreeIndex::with_collation(2, Collation::CaseInsensitive);
+        for (k, v) in [("coll_B", "1"), ("coll_a", "2"), ("coll_C", "3"), ("coll_b", "4")] {
+            session.set(k.into(), v.into());
+        }
+
+        // Byte order would put every upper-case key first
+        assert_eq!(session.get_many(&["coll_b", "coll_C", "coll_a", "coll_x", "coll_B"]),
+                   vec![Some("4".into()), Some("3".into()), Some("2".into()), None, Some("1".into())]);
+    }
+
+    #[test]
+    fn test_set_outside_transaction_applies_immediately() {
+        let mut session = Session::ephemeral();
+        session.set("direct".into(), "yes".into());
+        assert_eq!(session.get("direct"), Some("yes".to_string()));
+    }
+
     // Multi-transaction overwrite behavior
     #[test]
     fn test_multiple_transactions_replace_previous() {
-        let mut session = Session::new();
+        let mut session = Session::ephemeral();
         session.begin_transaction();
 
         // Create a transaction and set data
@@ -250,4 +2685,34 @@ mod tests {
             assert!(tx.is_empty(), "New transaction should not carry over old data");
         }
     }
+
+    // Crash consistency: a session cut off mid-write reopens to a prefix
+    #[test]
+    fn test_sessions_reopen_to_a_prefix_after_a_crash() {
+        let dir = std::env::temp_dir().join("kvstore_session_crash");
+        let pairs: Vec<(String, String)> = (0..6).map(|i| (format!("k{}", i), format!("value {}", i))).collect();
+        let written: Vec<String> = pairs.iter().map(|(k, v)| storage::set_record(k, v)).collect();
+
+        for after in (0..400).step_by(13) {
+            let _ = std::fs::remove_dir_all(&dir);
+            let mut session = Session::open(&dir).unwrap();
+            let path = session.data_file.clone();
+            storage::inject_crash(&path, Some(after));
+            // Two single writes, then the rest as one batch
+            let mut acked = pairs[..2].iter().take_while(|(k, v)| session.try_set(k.clone(), v.clone()).is_ok()).count();
+            if acked == 2 && session.mset(pairs[2..].to_vec()).is_ok() {
+                acked = pairs.len();
+            }
+            drop(session);
+            storage::inject_crash(&path, None);
+
+            // Replay keeps every acknowledged write and nothing out of order
+            let reopened = Session::open(&dir).unwrap();
+            let kept = storage::verify_replay_prefix(&*reopened.fs, &path, &written, acked).unwrap();
+            for (n, (key, value)) in pairs.iter().enumerate() {
+                assert_eq!(reopened.index.search(key), (n < kept).then_some(value.as_str()), "crash after {} bytes", after);
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,268 @@
+// =====================================================================
+// File: shared/follower.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Implements `Follower`, an in-process read replica of a `Session`.
+//
+//   - `Follower::attach` copies the session's live keys and subscribes
+//     to its change stream: every record the session logs is sent to
+//     the follower as well.
+//   - A background thread applies the records to the follower's own
+//     index and publishes a `Snapshot` after each batch, so reads never
+//     touch the session or its lock, and always see whole records (a
+//     committed transaction appears all at once or not at all).
+//
+// Notes:
+//   * TTLs arrive as the session's `EXPIREAT` and `PERSIST` records;
+//     an expired key is hidden from reads until the session reclaims it
+//     and logs the delete.
+//   * Like `SharedStore`, publishing takes an O(1) snapshot of the index
+//     (`BTreeIndex::snapshot`); the next batch copies only the nodes it
+//     writes to, so a batch costs time proportional to its own records.
+// =====================================================================
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwap;
+
+use super::Snapshot;
+use crate::storage::{decode_record, ReplayOp};
+use crate::Session;
+
+/// Records a follower applies before publishing a snapshot.
+const BATCH: usize = 256;
+
+/// A change sent from a session to its followers.
+enum FollowEvent {
+    /// The records of one append, applied together so readers never see
+    /// half a batch.
+    Records(Vec<String>),
+
+    /// The dataset was replaced (full sync); these records are all of it.
+    Reset(Vec<String>),
+}
+
+
+/// The session's end of one follower's stream.
+pub struct FollowerLink {
+    /// Changes not yet applied are queued here.
+    events: Sender<FollowEvent>,
+
+    /// Events sent so far.
+    sent: Arc<AtomicU64>,
+}
+
+
+impl FollowerLink {
+    /// Send the records of one append; `false` once the follower is gone.
+    pub(crate) fn records(&self, records: &[String]) -> bool {
+        self.send(FollowEvent::Records(records.to_vec()))
+    }
+
+
+    /// Send the records that replaced the dataset; `false` once the
+    /// follower is gone.
+    pub(crate) fn reset(&self, records: &[String]) -> bool {
+        self.send(FollowEvent::Reset(records.to_vec()))
+    }
+
+
+    fn send(&self, event: FollowEvent) -> bool {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        self.events.send(event).is_ok()
+    }
+}
+
+
+/// State shared by a follower handle and its apply thread.
+struct Shared {
+    /// Latest published view.
+    snapshot: ArcSwap<Snapshot>,
+
+    /// Events applied and published so far.
+    applied: AtomicU64,
+
+    /// Events the session has sent.
+    sent: Arc<AtomicU64>,
+}
+
+
+/// Read-only view of a session, kept up to date on another thread.
+///
+/// Cloning is cheap and shares the same view.
+#[derive(Clone)]
+pub struct Follower {
+    shared: Arc<Shared>,
+}
+
+
+impl Follower {
+    /// Start following `session`.
+    ///
+    /// # Returns
+    /// `Err(message)` while the session is still loading its log.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{execute_line, Follower, MemFs, Session};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let mut session = Session::ephemeral();
+    /// session.fs = Arc::new(MemFs::new());
+    /// let follower = Follower::attach(&mut session).unwrap();
+    /// execute_line(b"SET dog bark", &mut session);
+    ///
+    /// let reader = follower.clone();
+    /// let value = std::thread::spawn(move || {
+    ///     reader.wait_caught_up(Duration::from_secs(5));
+    ///     reader.get("dog")
+    /// });
+    /// assert_eq!(value.join().unwrap(), Some("bark".to_string()));
+    /// ```
+    pub fn attach(session: &mut Session) -> Result<Follower, String> {
+        if session.is_loading() {
+            return Err("LOADING dataset is still being replayed".to_string());
+        }
+
+        // Full values, since a follower cannot read the session's cold ones
+        let mut index = session.index.clone();
+        index.clear();
+        let mut keys = Vec::new();
+        session.index.collect_keys(&mut keys);
+        for key in keys {
+            if let Some(value) = session.get_bytes(&key) {
+                index.insert_bytes(key, value);
+            }
+        }
+
+        let (events, queue) = channel();
+        let sent = Arc::new(AtomicU64::new(0));
+        let snapshot = Snapshot {
+            index,
+            ttl: session.ttl.expirations(),
+            spill: None,
+            data_file: session.data_file.clone(),
+            fs: session.fs.clone(),
+            lowercase_keys: session.lowercase_keys,
+            serves_reads: true,
+        };
+        let shared = Arc::new(Shared {
+            snapshot: ArcSwap::new(Arc::new(snapshot.clone())),
+            applied: AtomicU64::new(0),
+            sent: sent.clone(),
+        });
+        let weak = Arc::downgrade(&shared);
+        std::thread::spawn(move || apply_all(snapshot, &queue, &weak));
+        session.followers.push(FollowerLink { events, sent });
+        Ok(Follower { shared })
+    }
+
+
+    /// The latest published view.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.shared.snapshot.load_full()
+    }
+
+
+    /// GET against the latest view.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.snapshot().get(key)
+    }
+
+
+    /// MGET; every key is read from the same view.
+    pub fn mget(&self, keys: &[&str]) -> Vec<Option<String>> {
+        let snapshot = self.snapshot();
+        keys.iter().map(|k| snapshot.get(k)).collect()
+    }
+
+
+    /// RANGE against the latest view.
+    pub fn range(&self, start: &str, end: &str) -> Vec<String> {
+        self.snapshot().range(start, end)
+    }
+
+
+    /// Changes the session has logged that this view does not show yet.
+    pub fn lag(&self) -> u64 {
+        let applied = self.shared.applied.load(Ordering::SeqCst);
+        self.shared.sent.load(Ordering::SeqCst).saturating_sub(applied)
+    }
+
+
+    /// Wait up to `within` for the view to show every change logged so
+    /// far, e.g. to read one's own writes.
+    ///
+    /// # Returns
+    /// `true` if it caught up in time.
+    pub fn wait_caught_up(&self, within: Duration) -> bool {
+        let deadline = Instant::now() + within;
+        while self.lag() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+}
+
+
+/// Apply a follower's events until the session or the follower is gone.
+fn apply_all(mut view: Snapshot, queue: &Receiver<FollowEvent>, shared: &Weak<Shared>) {
+    while let Ok(first) = queue.recv() {
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let mut applied = 0;
+        for event in std::iter::once(first).chain(queue.try_iter().take(BATCH - 1)) {
+            match event {
+                FollowEvent::Records(records) => {
+                    for record in &records {
+                        apply(&mut view, record);
+                    }
+                }
+                FollowEvent::Reset(records) => {
+                    view.index.clear();
+                    for record in &records {
+                        apply(&mut view, record);
+                    }
+                }
+            }
+            applied += 1;
+        }
+        shared.snapshot.store(Arc::new(view.clone()));
+        shared.applied.fetch_add(applied, Ordering::SeqCst);
+    }
+}
+
+
+fn apply(view: &mut Snapshot, record: &str) {
+    for op in decode_record(0, record) {
+        match op {
+            ReplayOp::Set(key, value, _) => {
+                view.index.insert_bytes(key, value);
+            }
+            ReplayOp::Del(key) => {
+                view.index.delete(&key);
+            }
+            ReplayOp::ExpireAt(key, at) => {
+                if !view.ttl.set_expires_at(&key, at) {
+                    view.index.delete(&key);
+                }
+            }
+            ReplayOp::Persist(key) => {
+                view.ttl.clear_expiration(&key);
+            }
+            // Sessions publish the value itself, never a value log pointer
+            ReplayOp::SetRef(..) => {}
+        }
+    }
+}
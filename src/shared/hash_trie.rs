@@ -0,0 +1,290 @@
+// =====================================================================
+// File: shared/hash_trie.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   `HashTrie`, a hash map whose clones share their nodes: a hash array
+//   mapped trie, 32 children per branch, with `Arc` nodes copied on
+//   write as the B-tree index's are.
+//
+//   Cloning is O(1). Inserting or removing copies only the path to the
+//   key (at most 13 nodes for a 64-bit hash), so a snapshot keeps the
+//   old map alive while the session goes on changing its own.
+//
+// Notes:
+//   * Keys whose whole hash is equal share one leaf.
+//   * Branches emptied by removals are dropped; a branch left with a
+//     single leaf is not folded back into its parent.
+// =====================================================================
+
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::sync::Arc;
+
+/// Hash bits consumed per level.
+const BITS: u32 = 5;
+
+#[derive(Clone)]
+enum Node<K, V> {
+    /// Children for the hash chunks set in the bitmap, in chunk order.
+    Branch(u32, Vec<Arc<Node<K, V>>>),
+
+    /// Entries whose keys have this full hash.
+    Leaf(u64, Vec<(K, V)>),
+}
+
+
+impl<K, V> Node<K, V> {
+    fn is_empty(&self) -> bool {
+        match self {
+            Node::Branch(_, children) => children.is_empty(),
+            Node::Leaf(_, entries) => entries.is_empty(),
+        }
+    }
+}
+
+
+/// The bit for the chunk of `hash` read at `shift`.
+fn chunk_bit(hash: u64, shift: u32) -> u32 {
+    1 << (hash.checked_shr(shift).unwrap_or(0) & ((1 << BITS) - 1))
+}
+
+
+/// Position of `bit`'s child among the children `bitmap` marks.
+fn child_at(bitmap: u32, bit: u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+
+/// A hash map that is O(1) to clone; see the module notes.
+///
+/// # Example
+/// ```
+/// use kvstore::HashTrie;
+/// let mut live = HashTrie::new();
+/// live.insert("a".to_string(), 1);
+/// let snapshot = live.clone();
+/// live.insert("a".to_string(), 2);
+/// live.insert("b".to_string(), 3);
+/// assert_eq!((snapshot.get("a"), snapshot.get("b")), (Some(&1), None));
+/// assert_eq!((live.get("a"), live.len()), (Some(&2), 2));
+/// ```
+#[derive(Clone)]
+pub struct HashTrie<K, V> {
+    root: Arc<Node<K, V>>,
+    len: usize,
+    hasher: RandomState,
+}
+
+
+impl<K, V> Default for HashTrie<K, V> {
+    fn default() -> Self {
+        HashTrie { root: Arc::new(Node::Branch(0, Vec::new())), len: 0, hasher: RandomState::new() }
+    }
+}
+
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for HashTrie<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+
+impl<K, V> HashTrie<K, V> {
+    /// An empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+
+    /// Returns `true` if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+
+    /// Remove every entry. Clones taken before keep theirs.
+    pub fn clear(&mut self) {
+        *self = HashTrie { hasher: self.hasher.clone(), ..Self::default() };
+    }
+
+
+    /// Every entry, in no particular order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { stack: vec![std::slice::from_ref(&self.root).iter()], leaf: [].iter() }
+    }
+}
+
+
+impl<K: Hash + Eq + Clone, V: Clone> HashTrie<K, V> {
+    /// The value stored for `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        let mut node = &*self.root;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Branch(bitmap, children) => {
+                    let bit = chunk_bit(hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    node = &children[child_at(*bitmap, bit)];
+                    shift += BITS;
+                }
+                Node::Leaf(leaf_hash, entries) if *leaf_hash == hash => {
+                    return entries.iter().find(|(k, _)| k.borrow() == key).map(|(_, v)| v);
+                }
+                Node::Leaf(..) => return None,
+            }
+        }
+    }
+
+
+    /// Returns `true` if `key` has an entry.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+
+    /// Store `value` for `key`.
+    ///
+    /// # Returns
+    /// The value it replaces, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hasher.hash_one(&key);
+        let old = insert_at(Arc::make_mut(&mut self.root), hash, 0, key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+
+    /// Drop `key`'s entry. Nothing is copied if there is none.
+    ///
+    /// # Returns
+    /// Its value, if it had one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if !self.contains_key(key) {
+            return None;
+        }
+        let hash = self.hasher.hash_one(key);
+        let old = remove_at(Arc::make_mut(&mut self.root), hash, 0, key);
+        self.len -= 1;
+        old
+    }
+}
+
+
+fn insert_at<K: Eq + Clone, V: Clone>(node: &mut Node<K, V>, hash: u64, shift: u32, key: K, value: V) -> Option<V> {
+    match node {
+        Node::Branch(bitmap, children) => {
+            let bit = chunk_bit(hash, shift);
+            let at = child_at(*bitmap, bit);
+            if *bitmap & bit == 0 {
+                *bitmap |= bit;
+                children.insert(at, Arc::new(Node::Leaf(hash, vec![(key, value)])));
+                return None;
+            }
+            insert_at(Arc::make_mut(&mut children[at]), hash, shift + BITS, key, value)
+        }
+        Node::Leaf(leaf_hash, entries) if *leaf_hash == hash => match entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                entries.push((key, value));
+                None
+            }
+        },
+        Node::Leaf(leaf_hash, _) => {
+            // Two hashes down one path: push the leaf a level down beside the new entry
+            let bit = chunk_bit(*leaf_hash, shift);
+            let leaf = std::mem::replace(node, Node::Branch(bit, Vec::new()));
+            if let Node::Branch(_, children) = node {
+                children.push(Arc::new(leaf));
+            }
+            insert_at(node, hash, shift, key, value)
+        }
+    }
+}
+
+
+fn remove_at<K, V, Q>(node: &mut Node<K, V>, hash: u64, shift: u32, key: &Q) -> Option<V>
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    Q: Eq + ?Sized,
+{
+    match node {
+        Node::Branch(bitmap, children) => {
+            let bit = chunk_bit(hash, shift);
+            let at = child_at(*bitmap, bit);
+            let child = Arc::make_mut(&mut children[at]);
+            let old = remove_at(child, hash, shift + BITS, key);
+            if child.is_empty() {
+                children.remove(at);
+                *bitmap &= !bit;
+            }
+            old
+        }
+        Node::Leaf(_, entries) => {
+            let at = entries.iter().position(|(k, _)| k.borrow() == key)?;
+            Some(entries.swap_remove(at).1)
+        }
+    }
+}
+
+
+/// Iterator over the entries of a [`HashTrie`].
+pub struct Iter<'a, K, V> {
+    /// Children still to visit, one level per entry.
+    stack: Vec<std::slice::Iter<'a, Arc<Node<K, V>>>>,
+
+    /// Rest of the leaf being read.
+    leaf: std::slice::Iter<'a, (K, V)>,
+}
+
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.leaf.next() {
+                return Some((k, v));
+            }
+            match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(node) => match &**node {
+                    Node::Branch(_, children) => self.stack.push(children.iter()),
+                    Node::Leaf(_, entries) => self.leaf = entries.iter(),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// =====================================================================
+// File: shared/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `shared` module provides a thread-safe wrapper around a
+//! [`Session`](crate::Session) for embedders that read from many threads.
+//!
+//! Structure:
+//! - `store.rs`     : Defines [`SharedStore`] and its immutable
+//!   [`Snapshot`] used to answer GET / MGET / RANGE.
+//! - `follower.rs`  : Defines [`Follower`], an in-process read replica
+//!   fed by the session's change stream.
+//! - `hash_trie.rs` : Defines [`HashTrie`], a hash map whose clones
+//!   share nodes, so TTL and spill state snapshot in O(1).
+//! - `tests.rs`     : Unit and multi-threaded tests.
+//!
+//! Writers are serialized through a mutex around the session. After each
+//! write batch a fresh snapshot is published through an `ArcSwap` (from
+//! the `arc-swap` crate), so readers never wait on (or block) a writer.
+//! A [`Follower`] goes further and keeps its own copy of the data, so its
+//! readers never touch the session at all.
+// =====================================================================
+
+pub mod follower;
+pub mod hash_trie;
+pub mod store;
+
+pub use self::follower::{Follower, FollowerLink};
+pub use self::hash_trie::HashTrie;
+pub use self::store::{SharedStore, Snapshot};
+
+#[cfg(test)]
+pub mod tests;
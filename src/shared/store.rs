@@ -0,0 +1,284 @@
+// =====================================================================
+// File: shared/store.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Implements `SharedStore`, the concurrent front end for a `Session`.
+//
+//   - Writes run inside `SharedStore::write`, which locks the session,
+//     applies the whole batch, and then publishes a new `Snapshot`.
+//   - Reads (`get`, `mget`, `range`) are answered from the latest
+//     published `Snapshot` and never take the session lock.
+//
+// Notes:
+//   * Publishing is O(1): the index, the TTLs and the spill bookkeeping
+//     are shared with the session and copied on write, so the next batch
+//     copies only the nodes and entries it changes.
+//   * Expiration in a snapshot is evaluated against the current time, so
+//     a key whose TTL runs out is hidden even before the next publish.
+//   * The servers answer GET / MGET / RANGE with
+//     `Snapshot::execute_read` when the snapshot can stand in for the
+//     session (no ACL, cluster, background load or value log); anything
+//     else goes through `SharedStore::write`.
+// =====================================================================
+use std::borrow::Cow;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use arc_swap::ArcSwap;
+
+use crate::spill::SpillView;
+use crate::storage;
+use crate::ttl::Expirations;
+use crate::{parse_command, telemetry, BTreeIndex, Fs, Session};
+
+/// Immutable point-in-time view of a session, shared by readers.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Committed key–value pairs at publish time.
+    pub index: BTreeIndex,
+
+    /// TTL metadata at publish time.
+    pub ttl: Expirations,
+
+    /// Log locations of cold values when the session is memory-limited.
+    pub spill: Option<SpillView>,
+
+    /// Log the cold values are read from.
+    pub data_file: String,
+
+    /// File system the log lives on.
+    pub fs: Arc<dyn Fs>,
+
+    /// Keys are lowercased before lookup, as the session does.
+    pub lowercase_keys: bool,
+
+    /// `true` if [`execute_read`](Self::execute_read) answers as the
+    /// session would: no ACL, cluster map, background load or value log.
+    pub serves_reads: bool,
+}
+
+
+impl Snapshot {
+    /// Build a snapshot from the committed state of a session.
+    pub fn capture(session: &Session) -> Self {
+        Self {
+            index: session.index.snapshot(),
+            ttl: session.ttl.expirations(),
+            spill: session.spill.as_ref().map(|s| s.view()),
+            data_file: session.data_file.clone(),
+            fs: session.fs.clone(),
+            lowercase_keys: session.lowercase_keys,
+            serves_reads: session.acl.is_none()
+                && session.cluster.is_none()
+                && !session.is_loading()
+                && session.value_log.is_empty(),
+        }
+    }
+
+    /// Look up a live key. Cold values are read from the log without
+    /// changing the writer's hot set.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let key = &*self.normalize_key(key);
+        if self.ttl.get_expiration(key) == -2 {
+            return None;
+        }
+        if let Some(ptr) = self.spill.as_ref().and_then(|s| s.cold_location(key)) {
+            return storage::read_value_with(&*self.fs, &self.data_file, ptr).ok().map(storage::lossy_text);
+        }
+        self.index.get_bytes(key).map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    /// Live keys within `[start, end]` in index (collation) order.
+    ///
+    /// An empty bound is treated as open, matching the `RANGE` command.
+    pub fn range(&self, start: &str, end: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.scan(start, end, |key| {
+            keys.push(key.to_string());
+            true
+        });
+        keys
+    }
+
+    /// [`range`](Self::range) one key at a time, until `f` returns `false`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{SharedStore, Session};
+    /// let store = SharedStore::new(Session::ephemeral());
+    /// store.write(|s| {
+    ///     for key in ["a", "b", "c"] {
+    ///         s.set(key.into(), "1".into());
+    ///     }
+    /// });
+    ///
+    /// let mut seen = Vec::new();
+    /// store.snapshot().scan("", "", |key| {
+    ///     seen.push(key.to_string());
+    ///     seen.len() < 2
+    /// });
+    /// assert_eq!(seen, ["a", "b"]);
+    /// ```
+    pub fn scan(&self, start: &str, end: &str, f: impl FnMut(&str) -> bool) {
+        self.for_each_live(start, end, false, f);
+    }
+
+    /// Answer `line` if it is a well-formed GET, MGET or RANGE, writing
+    /// the same replies [`execute_line`](crate::execute_line) would.
+    ///
+    /// # Returns
+    /// `false` if the line is anything else (or the snapshot doesn't
+    /// [serve reads](Self::serves_reads)); nothing is written then and
+    /// the caller runs it against the session.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{capture_replies, SharedStore, Session};
+    /// let store = SharedStore::new(Session::ephemeral());
+    /// store.write(|s| s.set("dog".into(), "bark".into()));
+    ///
+    /// let snapshot = store.snapshot();
+    /// let (answered, replies) = capture_replies(1024, || snapshot.execute_read(b"MGET dog cat\r\n"));
+    /// assert!(answered);
+    /// assert_eq!(replies.bytes, b"bark\nnil\n");
+    /// assert!(!snapshot.execute_read(b"SET dog woof"));
+    /// ```
+    pub fn execute_read(&self, line: &[u8]) -> bool {
+        if !self.serves_reads {
+            return false;
+        }
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let Some((cmd, args)) = std::str::from_utf8(line).ok().and_then(|line| parse_command(line).ok()) else {
+            return false;
+        };
+        let descending = match (cmd.as_str(), args.len(), args.get(2)) {
+            ("GET", 1, _) | ("MGET", 1.., _) | ("RANGE", 2, _) => false,
+            ("RANGE", 3, Some(order)) if order.eq_ignore_ascii_case("ASC") => false,
+            ("RANGE", 3, Some(order)) if order.eq_ignore_ascii_case("DESC") => true,
+            _ => return false,
+        };
+
+        let mut span = telemetry::span("kvstore.command");
+        span.attr("db.system", "kvstore").attr("db.operation", cmd.as_str());
+        if cmd == "RANGE" {
+            self.for_each_live(&args[0], &args[1], descending, |key| {
+                reply!("{}", key);
+                true
+            });
+            reply!("END");
+            return true;
+        }
+        for key in &args {
+            match self.get(key) {
+                Some(value) => reply!("{}", value),
+                None => reply!("nil"),
+            }
+        }
+        true
+    }
+
+    /// Call `f` with each key between two `RANGE` bounds whose TTL
+    /// hasn't run out, checked against one clock reading, until it
+    /// returns `false`.
+    fn for_each_live(&self, start: &str, end: &str, descending: bool, mut f: impl FnMut(&str) -> bool) {
+        let bound = |arg: &str| match self.normalize_key(arg) {
+            arg if arg.is_empty() || arg == "\"\"" => None,
+            arg => Some(arg.into_owned()),
+        };
+        let (start, end) = (bound(start), bound(end));
+        let start = start.as_deref().map_or(Bound::Unbounded, Bound::Included);
+        let end = end.as_deref().map_or(Bound::Unbounded, Bound::Included);
+        let keys: Box<dyn Iterator<Item = (&str, &[u8])>> = if descending {
+            Box::new(self.index.range_rev(start, end))
+        } else {
+            Box::new(self.index.range(start, end))
+        };
+        let now = Instant::now();
+        for key in keys.map(|(key, _)| key).filter(|key| !self.ttl.expired_at(key, now)) {
+            if !f(key) {
+                break;
+            }
+        }
+    }
+
+    /// `key` as the session stores it (see [`Session::normalize_key`]).
+    fn normalize_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        if self.lowercase_keys && key.chars().any(|c| c.to_lowercase().ne([c])) {
+            Cow::Owned(key.to_lowercase())
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+}
+
+
+/// Thread-safe store: serialized writers, lock-free snapshot readers.
+pub struct SharedStore {
+    /// The writable session; only writers lock it.
+    session: Mutex<Session>,
+
+    /// Latest published snapshot read by `get` / `mget` / `range`.
+    snapshot: ArcSwap<Snapshot>,
+}
+
+
+impl SharedStore {
+    /// Wrap a session and publish its current state as the first snapshot.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Session, SharedStore};
+    /// use std::sync::Arc;
+    ///
+    /// let store = Arc::new(SharedStore::new(Session::ephemeral()));
+    /// store.write(|s| s.index.insert("dog".into(), "bark".into()));
+    ///
+    /// let reader = Arc::clone(&store);
+    /// let value = std::thread::spawn(move || reader.get("dog")).join().unwrap();
+    /// assert_eq!(value, Some("bark".to_string()));
+    /// ```
+    pub fn new(session: Session) -> Self {
+        let snapshot = ArcSwap::new(Arc::new(Snapshot::capture(&session)));
+        Self {
+            session: Mutex::new(session),
+            snapshot,
+        }
+    }
+
+    /// Run a write batch against the session, then publish a new snapshot.
+    ///
+    /// Readers keep seeing the previous snapshot until the batch is done.
+    pub fn write<R>(&self, batch: impl FnOnce(&mut Session) -> R) -> R {
+        let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        let result = batch(&mut session);
+        self.snapshot.store(Arc::new(Snapshot::capture(&session)));
+        result
+    }
+
+    /// The most recently published snapshot.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot.load_full()
+    }
+
+    /// Lock-free GET against the latest snapshot.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.snapshot().get(key)
+    }
+
+    /// Lock-free MGET; every key is read from the same snapshot.
+    pub fn mget(&self, keys: &[&str]) -> Vec<Option<String>> {
+        let snapshot = self.snapshot();
+        keys.iter().map(|k| snapshot.get(k)).collect()
+    }
+
+    /// Lock-free RANGE against the latest snapshot.
+    pub fn range(&self, start: &str, end: &str) -> Vec<String> {
+        self.snapshot().range(start, end)
+    }
+}
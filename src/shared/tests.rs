@@ -0,0 +1,279 @@
+// =====================================================================
+// File: shared/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for HashTrie and SharedStore, including
+//   concurrent readers running alongside a writer.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// HashTrie Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod hash_trie_tests {
+    use crate::HashTrie;
+    use std::collections::HashMap;
+
+    #[test]
+    fn matches_a_hash_map_through_inserts_and_removes() {
+        let mut trie = HashTrie::new();
+        let mut model = HashMap::new();
+        for i in 0..5000u64 {
+            let key = format!("k{}", i * 7919 % 1000);
+            if i % 3 == 0 {
+                assert_eq!(trie.remove(key.as_str()), model.remove(&key));
+            } else {
+                assert_eq!(trie.insert(key.clone(), i), model.insert(key, i));
+            }
+            assert_eq!(trie.len(), model.len());
+        }
+        for (key, value) in &model {
+            assert_eq!(trie.get(key.as_str()), Some(value));
+        }
+        let mut seen: Vec<_> = trie.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        let mut expected: Vec<_> = model.into_iter().collect();
+        seen.sort();
+        expected.sort();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn clones_keep_their_own_entries() {
+        let mut live: HashTrie<String, u32> = (0..100).fold(HashTrie::new(), |mut t, i| {
+            t.insert(i.to_string(), i);
+            t
+        });
+        let snapshot = live.clone();
+        live.remove("5");
+        live.insert("6".into(), 60);
+        live.clear();
+        live.insert("new".into(), 1);
+
+        assert_eq!((snapshot.len(), snapshot.get("5"), snapshot.get("6")), (100, Some(&5), Some(&6)));
+        assert_eq!((live.len(), live.get("5"), live.get("new")), (1, None, Some(&1)));
+    }
+}
+
+
+// =====================================================================
+// SharedStore Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod shared_store_tests {
+    use crate::{capture_replies, execute_line, Acl, MemFs, Session, SharedStore, SpillManager};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn memory_session() -> Session {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session
+    }
+
+    /// Replies to `line` from the store's snapshot, or `None` if it
+    /// left the line to the session.
+    fn read(store: &SharedStore, line: &str) -> Option<String> {
+        let snapshot = store.snapshot();
+        let (answered, captured) = capture_replies(1 << 20, || snapshot.execute_read(line.as_bytes()));
+        answered.then(|| String::from_utf8(captured.bytes).unwrap())
+    }
+
+    fn seeded_store() -> SharedStore {
+        let store = SharedStore::new(Session::ephemeral());
+        store.write(|s| {
+            s.index.insert("ant".into(), "1".into());
+            s.index.insert("bee".into(), "2".into());
+            s.index.insert("cat".into(), "3".into());
+        });
+        store
+    }
+
+    #[test]
+    fn reads_see_published_batch() {
+        let store = seeded_store();
+        assert_eq!(store.get("bee"), Some("2".to_string()));
+        assert_eq!(store.mget(&["ant", "zzz"]), vec![Some("1".to_string()), None]);
+        assert_eq!(store.range("b", ""), vec!["bee", "cat"]);
+    }
+
+    #[test]
+    fn held_snapshot_is_isolated_from_later_writes() {
+        let store = seeded_store();
+        let before = store.snapshot();
+        store.write(|s| s.index.delete("ant"));
+
+        assert_eq!(before.get("ant"), Some("1".to_string()));
+        assert_eq!(store.get("ant"), None);
+    }
+
+    #[test]
+    fn expired_keys_hidden_from_snapshot_reads() {
+        let store = seeded_store();
+        store.write(|s| s.ttl.set_expiration("cat", 30));
+        thread::sleep(std::time::Duration::from_millis(50));
+
+        assert_eq!(store.get("cat"), None);
+        assert_eq!(store.range("", ""), vec!["ant", "bee"]);
+    }
+
+    #[test]
+    fn readers_run_while_writer_publishes() {
+        let store = Arc::new(seeded_store());
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    for _ in 0..500 {
+                        assert_eq!(store.get("ant"), Some("1".to_string()));
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..100 {
+            store.write(|s| s.index.insert(format!("k{i}"), i.to_string()));
+        }
+        for r in readers {
+            r.join().unwrap();
+        }
+        assert_eq!(store.get("k99"), Some("99".to_string()));
+    }
+
+    #[test]
+    fn snapshot_reads_reply_as_the_session_does() {
+        let mut session = memory_session();
+        session.spill = Some(SpillManager::new(1));
+        execute_line(b"MSET ant 1 bee 2 cat 3 dog \"two\\nlines\"", &mut session);
+        execute_line(b"EXPIRE bee 1", &mut session);
+        let store = SharedStore::new(session);
+        thread::sleep(std::time::Duration::from_millis(5));
+        assert!(store.snapshot().spill.as_ref().unwrap().cold_location("ant").is_some());
+
+        let lines = ["GET ant", "GET dog\r\n", "MGET cat bee nope ant", "RANGE - +", "RANGE b \"\" DESC", "range a c asc"];
+        for line in lines {
+            let from_snapshot = read(&store, line).unwrap_or_else(|| panic!("{} not answered", line));
+            let from_session = store.write(|s| capture_replies(1 << 20, || execute_line(line.as_bytes(), s)).1.bytes);
+            assert_eq!(from_snapshot.as_bytes(), from_session, "{}", line);
+        }
+    }
+
+    #[test]
+    fn other_lines_are_left_to_the_session() {
+        let store = SharedStore::new(memory_session());
+        for line in ["SET a 1", "GET", "GET a b", "MGET", "RANGE a", "RANGE a b SIDEWAYS", "GET \"open", "INFO"] {
+            assert_eq!(read(&store, line), None, "{}", line);
+        }
+
+        // An ACL decides per user, so the session answers every read
+        store.write(|s| s.acl = Some(Acl::parse("user admin s3cret all").unwrap()));
+        assert_eq!(read(&store, "GET a"), None);
+    }
+
+    #[test]
+    fn held_snapshot_keeps_its_ttls() {
+        let store = SharedStore::new(memory_session());
+        store.write(|s| execute_line(b"SET a 1", s));
+        store.write(|s| execute_line(b"EXPIRE a 60000", s));
+        let before = store.snapshot();
+        store.write(|s| execute_line(b"PERSIST a", s));
+
+        assert!(before.ttl.get_expiration("a") > 0);
+        assert_eq!(store.snapshot().ttl.get_expiration("a"), -1);
+        assert_eq!(store.snapshot().ttl.len(), 0);
+    }
+}
+
+
+// =====================================================================
+// Follower Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod follower_tests {
+    use crate::{execute_line, Follower, MemFs, Session};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn memory_session() -> Session {
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session
+    }
+
+    #[test]
+    fn follower_starts_from_the_session_and_tracks_its_writes() {
+        let mut session = memory_session();
+        execute_line(b"MSET a 1 b 2 c 3", &mut session);
+        let follower = Follower::attach(&mut session).unwrap();
+        assert_eq!(follower.range("", ""), vec!["a", "b", "c"]);
+
+        execute_line(b"SET a 10", &mut session);
+        execute_line(b"DEL b", &mut session);
+        session.begin_transaction();
+        execute_line(b"SET d 4", &mut session);
+        // Staged writes stay invisible until the commit is logged
+        assert!(follower.wait_caught_up(Duration::from_secs(5)));
+        assert_eq!(follower.get("d"), None);
+        execute_line(b"COMMIT", &mut session);
+
+        assert!(follower.wait_caught_up(Duration::from_secs(5)));
+        assert_eq!(follower.lag(), 0);
+        assert_eq!(follower.mget(&["a", "b", "c", "d"]), vec![Some("10".into()), None, Some("3".into()), Some("4".into())]);
+    }
+
+    #[test]
+    fn readers_see_whole_records() {
+        let mut session = memory_session();
+        execute_line(b"MSET left 0 right 0", &mut session);
+        let follower = Follower::attach(&mut session).unwrap();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (follower, done) = (follower.clone(), done.clone());
+                thread::spawn(move || {
+                    while !done.load(Ordering::SeqCst) {
+                        let pair = follower.mget(&["left", "right"]);
+                        assert_eq!(pair[0], pair[1]);
+                    }
+                })
+            })
+            .collect();
+        for i in 1..=300 {
+            execute_line(format!("MSET left {} right {}", i, i).as_bytes(), &mut session);
+        }
+        assert!(follower.wait_caught_up(Duration::from_secs(5)));
+        done.store(true, Ordering::SeqCst);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(follower.get("right").as_deref(), Some("300"));
+    }
+
+    #[test]
+    fn dropped_followers_are_forgotten() {
+        let mut session = memory_session();
+        let kept = Follower::attach(&mut session).unwrap();
+        drop(Follower::attach(&mut session).unwrap());
+        assert_eq!(session.followers.len(), 2);
+
+        // The dropped one's thread exits on its next event; the send after that fails
+        execute_line(b"SET a 1", &mut session);
+        thread::sleep(Duration::from_millis(50));
+        execute_line(b"SET a 2", &mut session);
+        assert_eq!(session.followers.len(), 1);
+        assert!(kept.wait_caught_up(Duration::from_secs(5)));
+        assert_eq!(kept.get("a").as_deref(), Some("2"));
+    }
+}
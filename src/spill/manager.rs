@@ -0,0 +1,253 @@
+// =====================================================================
+// File: spill/manager.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`SpillManager`] decides which values are kept in memory when a
+//! session runs with a hot-key limit. It remembers where the latest
+//! `SET` for each key lives in the log, and evicts the least recently
+//! used values once more than `capacity` keys are hot.
+//!
+//! A key-only manager (capacity zero) keeps no values in memory at all:
+//! the index holds just the keys and every read goes to the log through
+//! the recorded [`ValuePointer`], WiscKey style.
+//!
+//! The manager only does the bookkeeping. The session is responsible for
+//! dropping evicted values from the index and reloading cold ones.
+//!
+//! Locations and hot ticks are kept in [`HashTrie`]s, so a snapshot takes
+//! them with [`view`](SpillManager::view) in O(1).
+// =====================================================================
+
+use std::collections::BTreeMap;
+
+use crate::shared::HashTrie;
+use crate::ValuePointer;
+
+/// Tracks log locations and value hotness for memory-limited sessions.
+#[derive(Debug, Clone)]
+pub struct SpillManager {
+    /// Maximum number of values held in memory at once.
+    capacity: usize,
+
+    /// Log position and length of the latest value for every known key.
+    locations: HashTrie<String, ValuePointer>,
+
+    /// Recency tick of each hot key.
+    hot: HashTrie<String, u64>,
+
+    /// Hot keys ordered by recency tick (oldest first).
+    order: BTreeMap<u64, String>,
+
+    /// Monotonic counter used to order accesses.
+    tick: u64,
+}
+
+
+impl SpillManager {
+    /// Create a manager that keeps at most `capacity` values in memory.
+    ///
+    /// A capacity of zero is bumped to one so the most recent value
+    /// is always available without a disk read.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            locations: HashTrie::new(),
+            hot: HashTrie::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+
+    /// Create a manager that keeps no values in memory (key-only index).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{SpillManager, ValuePointer};
+    /// let mut spill = SpillManager::key_only();
+    /// let ptr = ValuePointer { offset: 8, len: 4, raw: false };
+    /// assert_eq!(spill.record_write("a", ptr), vec!["a".to_string()]);
+    /// assert!(spill.is_cold("a"));
+    /// ```
+    pub fn key_only() -> Self {
+        Self {
+            capacity: 0,
+            ..Self::new(1)
+        }
+    }
+
+
+    /// Returns `true` if no values are ever kept in memory.
+    pub fn is_key_only(&self) -> bool {
+        self.capacity == 0
+    }
+
+
+    /// Record that the latest value of `key` now lives at `ptr`.
+    ///
+    /// The key becomes the most recently used hot value.
+    ///
+    /// # Returns
+    /// The keys whose values should now be dropped from memory.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{SpillManager, ValuePointer};
+    /// let mut spill = SpillManager::new(1);
+    /// let ptr = ValuePointer { offset: 0, len: 1, raw: false };
+    /// assert!(spill.record_write("a", ptr).is_empty());
+    /// assert_eq!(spill.record_write("b", ptr), vec!["a".to_string()]);
+    /// assert!(spill.is_cold("a"));
+    /// ```
+    pub fn record_write(&mut self, key: &str, ptr: ValuePointer) -> Vec<String> {
+        self.locations.insert(key.to_string(), ptr);
+        self.touch(key)
+    }
+
+
+    /// Mark `key` as the most recently used hot value.
+    ///
+    /// Called after a cold value has been loaded back into memory.
+    ///
+    /// # Returns
+    /// The keys whose values should now be dropped from memory.
+    pub fn touch(&mut self, key: &str) -> Vec<String> {
+        self.tick += 1;
+        if let Some(old_tick) = self.hot.insert(key.to_string(), self.tick) {
+            self.order.remove(&old_tick);
+        }
+        self.order.insert(self.tick, key.to_string());
+
+        let mut evicted = Vec::new();
+        while self.hot.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.hot.remove(&oldest);
+                evicted.push(oldest);
+            }
+        }
+        evicted
+    }
+
+
+    /// Returns `true` if the key is known but its value is not in memory.
+    pub fn is_cold(&self, key: &str) -> bool {
+        self.locations.contains_key(key) && !self.hot.contains_key(key)
+    }
+
+
+    /// Log position of the latest value for `key`, if known.
+    pub fn location(&self, key: &str) -> Option<ValuePointer> {
+        self.locations.get(key).copied()
+    }
+
+
+    /// Point a tracked key at a new log position without changing hotness.
+    ///
+    /// Used after compaction rewrites the log. Unknown keys are ignored.
+    pub fn relocate(&mut self, key: &str, ptr: ValuePointer) {
+        if self.locations.contains_key(key) {
+            self.locations.insert(key.to_string(), ptr);
+        }
+    }
+
+
+    /// Drop all tracking for a key (used when the key is deleted).
+    pub fn forget(&mut self, key: &str) {
+        self.locations.remove(key);
+        if let Some(tick) = self.hot.remove(key) {
+            self.order.remove(&tick);
+        }
+    }
+
+
+    /// Number of values currently held in memory.
+    pub fn hot_count(&self) -> usize {
+        self.hot.len()
+    }
+
+
+    /// Maximum number of values held in memory.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+
+    /// Change how many values may stay in memory.
+    ///
+    /// Like [`SpillManager::new`], zero is bumped to one. Lowering the
+    /// capacity evicts the least recently used values at once.
+    ///
+    /// # Returns
+    /// The keys whose values should now be dropped from memory.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{SpillManager, ValuePointer};
+    /// let mut spill = SpillManager::new(2);
+    /// let ptr = ValuePointer { offset: 0, len: 1, raw: false };
+    /// spill.record_write("a", ptr);
+    /// spill.record_write("b", ptr);
+    /// assert_eq!(spill.set_capacity(1), vec!["a".to_string()]);
+    /// ```
+    pub fn set_capacity(&mut self, capacity: usize) -> Vec<String> {
+        self.capacity = capacity.max(1);
+        let mut evicted = Vec::new();
+        while self.hot.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.hot.remove(&oldest);
+                evicted.push(oldest);
+            }
+        }
+        evicted
+    }
+
+
+    /// Remove all tracked keys (used before replaying the log).
+    pub fn clear(&mut self) {
+        self.locations.clear();
+        self.hot.clear();
+        self.order.clear();
+        self.tick = 0;
+    }
+
+
+    /// Which keys are cold and where their values are, as of now, for a
+    /// reader on another thread. O(1), like
+    /// [`TTLManager::expirations`](crate::TTLManager::expirations).
+    pub fn view(&self) -> SpillView {
+        SpillView { locations: self.locations.clone(), hot: self.hot.clone() }
+    }
+}
+
+
+/// Point-in-time cold-value locations taken with [`SpillManager::view`].
+#[derive(Debug, Clone)]
+pub struct SpillView {
+    locations: HashTrie<String, ValuePointer>,
+    hot: HashTrie<String, u64>,
+}
+
+
+impl SpillView {
+    /// Log position of `key`'s value if it was cold when the view was
+    /// taken, `None` if it was in memory or unknown.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{SpillManager, ValuePointer};
+    /// let mut spill = SpillManager::new(1);
+    /// let (a, b) = (ValuePointer { offset: 0, len: 1, raw: false }, ValuePointer { offset: 9, len: 1, raw: false });
+    /// spill.record_write("a", a);
+    /// spill.record_write("b", b);
+    /// let view = spill.view();
+    /// spill.touch("a");
+    /// assert_eq!(view.cold_location("a"), Some(a));
+    /// assert_eq!(view.cold_location("b"), None);
+    /// ```
+    pub fn cold_location(&self, key: &str) -> Option<ValuePointer> {
+        self.locations.get(key).filter(|_| !self.hot.contains_key(key)).copied()
+    }
+}
@@ -0,0 +1,26 @@
+// =====================================================================
+// File: spill/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `spill` module supports a memory-limited session mode where only
+//! the most recently used values stay in memory.
+//!
+//! Structure:
+//! - `manager.rs` : Defines the [`SpillManager`], which tracks the log
+//!   position of every value and the recency order of the hot set.
+//! - `tests.rs`   : Unit tests for eviction and reload bookkeeping.
+//!
+//! Keys always stay in the B-tree; only cold *values* are dropped and
+//! read back from the append-only log on demand. In key-only mode no
+//! values are hot, so the in-memory index shrinks to just the keys.
+// =====================================================================
+
+pub mod manager;
+
+pub use self::manager::{SpillManager, SpillView};
+
+#[cfg(test)]
+pub mod tests;
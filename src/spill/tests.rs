@@ -0,0 +1,93 @@
+// =====================================================================
+// File: spill/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for the SpillManager (hot set eviction and log offsets).
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Spill Manager Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod spill_manager_tests {
+    use crate::{SpillManager, ValuePointer};
+
+    fn at(offset: u64) -> ValuePointer {
+        ValuePointer { offset, len: 1, raw: false }
+    }
+
+    #[test]
+    fn writes_within_capacity_stay_hot() {
+        let mut spill = SpillManager::new(3);
+        assert!(spill.record_write("a", at(0)).is_empty());
+        assert!(spill.record_write("b", at(10)).is_empty());
+        assert!(spill.record_write("c", at(20)).is_empty());
+        assert_eq!(spill.hot_count(), 3);
+        assert!(!spill.is_cold("a"));
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let mut spill = SpillManager::new(2);
+        spill.record_write("a", at(0));
+        spill.record_write("b", at(10));
+
+        // Reading "a" makes "b" the oldest
+        spill.touch("a");
+        assert_eq!(spill.record_write("c", at(20)), vec!["b".to_string()]);
+        assert!(spill.is_cold("b"));
+        assert!(!spill.is_cold("a"));
+        assert_eq!(spill.hot_count(), 2);
+    }
+
+    #[test]
+    fn rewrite_updates_location() {
+        let mut spill = SpillManager::new(2);
+        spill.record_write("a", at(0));
+        spill.record_write("a", at(42));
+        assert_eq!(spill.location("a"), Some(at(42)));
+        assert_eq!(spill.hot_count(), 1);
+    }
+
+    #[test]
+    fn forget_removes_all_tracking() {
+        let mut spill = SpillManager::new(1);
+        spill.record_write("a", at(0));
+        spill.record_write("b", at(10));
+        spill.forget("a");
+        assert_eq!(spill.location("a"), None);
+        assert!(!spill.is_cold("a"));
+    }
+
+    #[test]
+    fn zero_capacity_keeps_one_value() {
+        let spill = SpillManager::new(0);
+        assert_eq!(spill.capacity(), 1);
+    }
+
+    #[test]
+    fn key_only_keeps_no_values() {
+        let mut spill = SpillManager::key_only();
+        assert!(spill.is_key_only());
+        assert_eq!(spill.record_write("a", at(0)), vec!["a".to_string()]);
+        assert_eq!(spill.touch("a"), vec!["a".to_string()]);
+        assert_eq!(spill.hot_count(), 0);
+        assert_eq!(spill.location("a"), Some(at(0)));
+    }
+
+    #[test]
+    fn unknown_key_is_not_cold() {
+        let spill = SpillManager::new(2);
+        assert!(!spill.is_cold("ghost"));
+        assert_eq!(spill.location("ghost"), None);
+    }
+}
@@ -0,0 +1,223 @@
+// =====================================================================
+// File: sqlite/format.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   The parts of the SQLite file format both directions share: varints,
+//   the record encoding, payload spill limits and the database header.
+//
+//   Reference: https://www.sqlite.org/fileformat2.html
+// =====================================================================
+
+/// Page size of the files we write.
+pub(crate) const PAGE_SIZE: usize = 4096;
+
+/// Schema of the exported table.
+pub const KV_TABLE_SQL: &str = "CREATE TABLE kv(key TEXT PRIMARY KEY, value TEXT, expires_at INTEGER)";
+
+/// One row of the `kv` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvRow {
+    /// The key.
+    pub key: String,
+
+    /// Its value.
+    pub value: String,
+
+    /// Absolute expiration in Unix milliseconds, or `None` without a TTL.
+    pub expires_at: Option<i64>,
+}
+
+
+/// A column value in a record.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SqlValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+
+/// Append a SQLite varint: 7 bits a byte, high bit first; a 9th byte
+/// carries 8 bits.
+pub(crate) fn put_varint(out: &mut Vec<u8>, value: u64) {
+    if value >> 56 != 0 {
+        let mut bytes = [0u8; 9];
+        bytes[8] = value as u8;
+        let mut rest = value >> 8;
+        for byte in bytes[..8].iter_mut().rev() {
+            *byte = (rest & 0x7F) as u8 | 0x80;
+            rest >>= 7;
+        }
+        out.extend_from_slice(&bytes);
+        return;
+    }
+    let mut groups = Vec::with_capacity(8);
+    let mut rest = value;
+    loop {
+        groups.push((rest & 0x7F) as u8);
+        rest >>= 7;
+        if rest == 0 {
+            break;
+        }
+    }
+    for (i, group) in groups.iter().enumerate().rev() {
+        out.push(if i == 0 { *group } else { group | 0x80 });
+    }
+}
+
+
+/// Bytes [`put_varint`] uses for `value`.
+pub(crate) fn varint_len(value: u64) -> usize {
+    let mut out = Vec::new();
+    put_varint(&mut out, value);
+    out.len()
+}
+
+
+/// Read a varint from the start of `bytes`.
+///
+/// # Returns
+/// The value and the bytes it took, or `None` if `bytes` ends first.
+pub(crate) fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().take(8).enumerate() {
+        value = value << 7 | u64::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    let last = *bytes.get(8)?;
+    Some((value << 8 | u64::from(last), 9))
+}
+
+
+/// Encode a record: a header of serial types, then the values.
+pub(crate) fn encode_record(values: &[SqlValue]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        let serial = match value {
+            SqlValue::Null => 0,
+            SqlValue::Integer(0) => 8,
+            SqlValue::Integer(1) => 9,
+            SqlValue::Integer(n) => {
+                let (serial, width) = match *n {
+                    -0x80..=0x7F => (1, 1),
+                    -0x8000..=0x7FFF => (2, 2),
+                    -0x80_0000..=0x7F_FFFF => (3, 3),
+                    -0x8000_0000..=0x7FFF_FFFF => (4, 4),
+                    -0x8000_0000_0000..=0x7FFF_FFFF_FFFF => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&n.to_be_bytes()[8 - width..]);
+                serial
+            }
+            SqlValue::Real(x) => {
+                body.extend_from_slice(&x.to_be_bytes());
+                7
+            }
+            SqlValue::Text(text) => {
+                body.extend_from_slice(text.as_bytes());
+                text.len() as u64 * 2 + 13
+            }
+            SqlValue::Blob(bytes) => {
+                body.extend_from_slice(bytes);
+                bytes.len() as u64 * 2 + 12
+            }
+        };
+        put_varint(&mut types, serial);
+    }
+
+    // The header length counts its own varint too
+    let mut header_len = types.len() + 1;
+    if header_len > 0x7F {
+        header_len += 1;
+    }
+    let mut record = Vec::with_capacity(header_len + body.len());
+    put_varint(&mut record, header_len as u64);
+    record.extend_from_slice(&types);
+    record.extend_from_slice(&body);
+    record
+}
+
+
+/// Decode a record written by [`encode_record`] (or by SQLite).
+pub(crate) fn decode_record(payload: &[u8]) -> Option<Vec<SqlValue>> {
+    let (header_len, mut pos) = read_varint(payload)?;
+    let header_len = usize::try_from(header_len).ok()?;
+    let mut body = header_len;
+    let mut values = Vec::new();
+    while pos < header_len {
+        let (serial, used) = read_varint(payload.get(pos..header_len)?)?;
+        pos += used;
+        let width = match serial {
+            0 | 8 | 9 => 0,
+            1..=4 => serial as usize,
+            5 => 6,
+            6 | 7 => 8,
+            10 | 11 => return None,
+            n => usize::try_from((n - 12) / 2).ok()?,
+        };
+        let bytes = payload.get(body..body.checked_add(width)?)?;
+        body += width;
+        values.push(match serial {
+            0 => SqlValue::Null,
+            8 => SqlValue::Integer(0),
+            9 => SqlValue::Integer(1),
+            1..=6 => {
+                // Sign-extend the big-endian integer
+                let mut full = if bytes[0] & 0x80 != 0 { [0xFF; 8] } else { [0; 8] };
+                full[8 - width..].copy_from_slice(bytes);
+                SqlValue::Integer(i64::from_be_bytes(full))
+            }
+            7 => SqlValue::Real(f64::from_be_bytes(bytes.try_into().ok()?)),
+            n if n % 2 == 0 => SqlValue::Blob(bytes.to_vec()),
+            _ => SqlValue::Text(String::from_utf8_lossy(bytes).into_owned()),
+        });
+    }
+    Some(values)
+}
+
+
+/// Bytes of a `payload_len` payload kept in the cell itself; the rest
+/// spills to overflow pages.
+///
+/// # Arguments
+/// * `usable` - Usable bytes per page.
+/// * `table_leaf` - `true` for table leaf cells, `false` for index cells.
+pub(crate) fn local_payload(payload_len: usize, usable: usize, table_leaf: bool) -> usize {
+    let max_local = if table_leaf { usable - 35 } else { (usable - 12) * 64 / 255 - 23 };
+    if payload_len <= max_local {
+        return payload_len;
+    }
+    let min_local = (usable - 12) * 32 / 255 - 23;
+    let spill = min_local + (payload_len - min_local) % (usable - 4);
+    if spill <= max_local { spill } else { min_local }
+}
+
+
+/// The 100-byte header at the start of page 1.
+pub(crate) fn database_header(page_count: u32) -> Vec<u8> {
+    let mut header = vec![0u8; 100];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    header[18] = 1; // file format write version (rollback journal)
+    header[19] = 1; // file format read version
+    header[21] = 64; // max embedded payload fraction
+    header[22] = 32; // min embedded payload fraction
+    header[23] = 32; // leaf payload fraction
+    header[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    header[28..32].copy_from_slice(&page_count.to_be_bytes());
+    header[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    header[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    header[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    header[92..96].copy_from_slice(&1u32.to_be_bytes()); // version-valid-for
+    header[96..100].copy_from_slice(&3_045_000u32.to_be_bytes());
+    header
+}
@@ -0,0 +1,36 @@
+// =====================================================================
+// File: sqlite/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `sqlite` module moves the keyspace in and out of SQLite database
+//! files, so data can be inspected with standard SQL tooling.
+//!
+//! Structure:
+//! - `format.rs` : Varints, records, payload spill limits and the file
+//!   header, shared by both directions, and the [`KvRow`] they carry.
+//! - `writer.rs` : [`export_sqlite`], which writes every live key as a
+//!   row of `kv(key TEXT PRIMARY KEY, value TEXT, expires_at INTEGER)`.
+//! - `reader.rs` : [`import_sqlite`], which reads that table back (from
+//!   our export or any SQLite-made file) and writes it through a session.
+//! - `tests.rs`  : Unit tests for the encodings and round trips, and
+//!   checks against SQLite itself (through `rusqlite`, a dev-dependency).
+//!
+//! `expires_at` is an absolute Unix time in milliseconds, `NULL` for
+//! keys without a TTL. The file format is produced directly, without
+//! linking SQLite; the tests open our exports with SQLite (integrity
+//! check, queries, overflow pages) and import files SQLite wrote.
+// =====================================================================
+
+mod format;
+pub mod reader;
+pub mod writer;
+
+pub use self::format::{KvRow, KV_TABLE_SQL};
+pub use self::reader::{import_sqlite, read_kv_rows, SqliteImportReport};
+pub use self::writer::{export_sqlite, write_kv_rows};
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,263 @@
+// =====================================================================
+// File: sqlite/reader.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Reads the `kv` table back from a SQLite database file
+//   (`IMPORT SQLITE`).
+//
+//   The file may come from `EXPORT SQLITE` or from SQLite itself: the
+//   schema on page 1 gives the table's root page and its column order,
+//   and the table b-tree is walked from there, following overflow
+//   chains. Only `key`, `value` and `expires_at` are read; other columns
+//   are ignored. A database in WAL mode must be checkpointed first, since
+//   only the main file is read.
+// =====================================================================
+
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::format::{decode_record, local_payload, read_varint, KvRow, SqlValue};
+use crate::Session;
+
+/// B-trees deeper than this are taken as corrupt.
+const MAX_DEPTH: usize = 32;
+
+/// Outcome of an import.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SqliteImportReport {
+    /// Keys written through the session.
+    pub imported: usize,
+
+    /// Rows already expired at import time.
+    pub expired_skipped: usize,
+
+    /// Rows refused by the session (size limits, another shard's key, ...)
+    /// or without a usable key or value.
+    pub rejected: usize,
+}
+
+
+/// Import the `kv` table of the SQLite file at `path` into `session`.
+///
+/// Each row is written as a regular `SET`; a future `expires_at` sets
+/// the key's TTL.
+///
+/// # Returns
+/// * `Ok(SqliteImportReport)` once every row is applied.
+/// * `Err(io::Error)` if the file can't be read or has no usable `kv` table.
+pub fn import_sqlite(session: &mut Session, path: &str) -> io::Result<SqliteImportReport> {
+    let bytes = std::fs::read(path)?;
+    let rows = read_kv_rows(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+
+    let mut report = SqliteImportReport::default();
+    for row in rows {
+        if row.expires_at.is_some_and(|at| at <= now_ms) {
+            report.expired_skipped += 1;
+            continue;
+        }
+        let misrouted = session.cluster.as_ref().is_some_and(|c| c.misrouted(&[&row.key]).is_some());
+        if misrouted || session.try_set(row.key.clone(), row.value).is_err() {
+            report.rejected += 1;
+            continue;
+        }
+        if let Some(at) = row.expires_at {
+            let _ = session.expire(&row.key, at - now_ms);
+        }
+        report.imported += 1;
+    }
+    Ok(report)
+}
+
+
+/// Read every row of the `kv` table in a SQLite database image.
+///
+/// Rows whose key is `NULL` or whose value is `NULL` are left out;
+/// numbers in `key` or `value` are read as their text.
+///
+/// # Returns
+/// `Err(message)` if the image is not a UTF-8 SQLite database with a
+/// `kv(key, value, ...)` table, or its pages don't add up.
+pub fn read_kv_rows(bytes: &[u8]) -> Result<Vec<KvRow>, String> {
+    let db = Database::open(bytes)?;
+
+    // sqlite_schema: type, name, tbl_name, rootpage, sql
+    let mut table = None;
+    db.walk(1, 0, &mut |record| {
+        if let [SqlValue::Text(kind), SqlValue::Text(name), _, SqlValue::Integer(root), SqlValue::Text(sql), ..] = record.as_slice()
+            && kind == "table"
+            && name.eq_ignore_ascii_case("kv")
+        {
+            table = Some((*root, sql.clone()));
+        }
+    })?;
+    let (root, sql) = table.ok_or("no kv table in the database")?;
+    let columns = column_names(&sql);
+    let position = |name: &str| columns.iter().position(|c| c == name);
+    let (Some(key_at), Some(value_at)) = (position("key"), position("value")) else {
+        return Err("table kv has no key and value columns".to_string());
+    };
+    let expires_at = position("expires_at");
+
+    let mut rows = Vec::new();
+    let root = u32::try_from(root).map_err(|_| "bad root page for kv")?;
+    db.walk(root, 0, &mut |record| {
+        let (Some(key), Some(value)) = (as_text(record.get(key_at)), as_text(record.get(value_at))) else {
+            return;
+        };
+        let expires_at = match expires_at.and_then(|i| record.get(i)) {
+            Some(SqlValue::Integer(n)) => Some(*n),
+            Some(SqlValue::Real(x)) => Some(*x as i64),
+            _ => None,
+        };
+        rows.push(KvRow { key, value, expires_at });
+    })?;
+    Ok(rows)
+}
+
+
+/// A column as text, for key and value columns.
+fn as_text(value: Option<&SqlValue>) -> Option<String> {
+    match value? {
+        SqlValue::Text(text) => Some(text.clone()),
+        SqlValue::Integer(n) => Some(n.to_string()),
+        SqlValue::Real(x) => Some(x.to_string()),
+        SqlValue::Blob(bytes) => String::from_utf8(bytes.clone()).ok(),
+        SqlValue::Null => None,
+    }
+}
+
+
+/// Lowercase column names of a `CREATE TABLE` statement, in order.
+fn column_names(sql: &str) -> Vec<String> {
+    let (Some(open), Some(close)) = (sql.find('('), sql.rfind(')')) else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    let mut depth = 0;
+    let mut start = open + 1;
+    for (i, c) in sql[..close].char_indices().skip_while(|(i, _)| *i <= open) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                names.extend(column_name(&sql[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    names.extend(column_name(&sql[start..close]));
+    names
+}
+
+
+/// Name of one column definition, or `None` for a table constraint.
+fn column_name(definition: &str) -> Option<String> {
+    let definition = definition.trim();
+    let quote = definition.chars().next()?;
+    let name = match quote {
+        '"' | '`' => definition[1..].split(quote).next()?,
+        '[' => definition[1..].split(']').next()?,
+        _ => {
+            let word = definition.split_whitespace().next()?;
+            let constraint = ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"];
+            if constraint.iter().any(|c| word.eq_ignore_ascii_case(c)) {
+                return None;
+            }
+            word
+        }
+    };
+    Some(name.to_ascii_lowercase())
+}
+
+
+/// A database image, split into pages.
+struct Database<'a> {
+    bytes: &'a [u8],
+    page_size: usize,
+    usable: usize,
+}
+
+
+impl<'a> Database<'a> {
+    fn open(bytes: &'a [u8]) -> Result<Self, String> {
+        if bytes.len() < 100 || !bytes.starts_with(b"SQLite format 3\0") {
+            return Err("not a SQLite database".to_string());
+        }
+        let page_size = match u16::from_be_bytes([bytes[16], bytes[17]]) {
+            1 => 65536,
+            n if n >= 512 && n.is_power_of_two() => usize::from(n),
+            _ => return Err("bad SQLite page size".to_string()),
+        };
+        if u32::from_be_bytes([bytes[56], bytes[57], bytes[58], bytes[59]]) > 1 {
+            return Err("only UTF-8 SQLite databases can be imported".to_string());
+        }
+        let usable = page_size - usize::from(bytes[20]);
+        Ok(Database { bytes, page_size, usable })
+    }
+
+
+    fn page(&self, number: u32) -> Result<&'a [u8], String> {
+        let start = (number as usize).checked_sub(1).ok_or("page 0 referenced")? * self.page_size;
+        self.bytes.get(start..start + self.page_size).ok_or_else(|| format!("page {} is past the end of the file", number))
+    }
+
+
+    /// Call `visit(record)` for every row of the table b-tree at `root`.
+    fn walk(&self, root: u32, depth: usize, visit: &mut dyn FnMut(Vec<SqlValue>)) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err("table b-tree is too deep".to_string());
+        }
+        let page = self.page(root)?;
+        let offset = if root == 1 { 100 } else { 0 };
+        let kind = page[offset];
+        let count = usize::from(u16::from_be_bytes([page[offset + 3], page[offset + 4]]));
+        let header = if kind == 0x05 { 12 } else { 8 };
+        let corrupt = || format!("page {} is corrupt", root);
+
+        for i in 0..count {
+            let ptr = offset + header + 2 * i;
+            let cell = usize::from(u16::from_be_bytes(page.get(ptr..ptr + 2).ok_or_else(corrupt)?.try_into().unwrap()));
+            let cell = page.get(cell..).ok_or_else(corrupt)?;
+            match kind {
+                0x05 => {
+                    let child = u32::from_be_bytes(cell.get(..4).ok_or_else(corrupt)?.try_into().unwrap());
+                    self.walk(child, depth + 1, visit)?;
+                }
+                0x0D => {
+                    let (len, a) = read_varint(cell).ok_or_else(corrupt)?;
+                    let (_, b) = read_varint(&cell[a..]).ok_or_else(corrupt)?;
+                    let payload = self.payload(&cell[a + b..], len as usize).ok_or_else(corrupt)?;
+                    visit(decode_record(&payload).ok_or_else(corrupt)?);
+                }
+                _ => return Err(format!("page {} is not a table b-tree page", root)),
+            }
+        }
+        if kind == 0x05 {
+            let right = u32::from_be_bytes(page[offset + 8..offset + 12].try_into().unwrap());
+            self.walk(right, depth + 1, visit)?;
+        }
+        Ok(())
+    }
+
+
+    /// A table leaf payload of `len` bytes: the local part, then the
+    /// overflow chain.
+    fn payload(&self, cell: &[u8], len: usize) -> Option<Vec<u8>> {
+        let local = local_payload(len, self.usable, true);
+        let mut payload = cell.get(..local)?.to_vec();
+        let mut next = if local < len { u32::from_be_bytes(cell.get(local..local + 4)?.try_into().ok()?) } else { 0 };
+        while payload.len() < len {
+            let page = self.page(next).ok()?;
+            let take = (len - payload.len()).min(self.usable - 4);
+            payload.extend_from_slice(page.get(4..4 + take)?);
+            next = u32::from_be_bytes(page[..4].try_into().ok()?);
+        }
+        Some(payload)
+    }
+}
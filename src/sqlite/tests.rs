@@ -0,0 +1,211 @@
+// =====================================================================
+// File: sqlite/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for the SQLite encodings, for exporting and importing
+//   the keyspace, and for both directions against SQLite itself.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Format Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod format_tests {
+    use crate::sqlite::format::*;
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 16_383, 16_384, 1 << 35, (1 << 56) - 1, 1 << 56, u64::MAX] {
+            let mut out = Vec::new();
+            put_varint(&mut out, value);
+            assert_eq!(out.len(), varint_len(value));
+            assert_eq!(read_varint(&out), Some((value, out.len())), "{}", value);
+        }
+        let mut out = Vec::new();
+        put_varint(&mut out, 300);
+        assert_eq!(out, vec![0x82, 0x2C]);
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let values = vec![
+            SqlValue::Null,
+            SqlValue::Integer(0),
+            SqlValue::Integer(1),
+            SqlValue::Integer(-2),
+            SqlValue::Integer(40_000),
+            SqlValue::Integer(-9_000_000),
+            SqlValue::Integer(1_700_000_000_000),
+            SqlValue::Integer(i64::MIN),
+            SqlValue::Real(2.5),
+            SqlValue::Text("héllo".into()),
+            SqlValue::Blob(vec![0, 255]),
+        ];
+        let record = encode_record(&values);
+        assert_eq!(decode_record(&record), Some(values));
+        assert_eq!(encode_record(&[SqlValue::Text("a".into())]), vec![2, 15, b'a']);
+    }
+
+    #[test]
+    fn large_payloads_spill() {
+        assert_eq!(local_payload(100, PAGE_SIZE, true), 100);
+        assert_eq!(local_payload(4061, PAGE_SIZE, true), 4061);
+        assert!(local_payload(5000, PAGE_SIZE, true) < 4061);
+        assert_eq!(local_payload(1002, PAGE_SIZE, false), 1002);
+        assert!(local_payload(1003, PAGE_SIZE, false) <= 1002);
+    }
+}
+
+
+// =====================================================================
+// Export and Import Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod round_trip_tests {
+    use std::sync::Arc;
+
+    use crate::{export_sqlite, import_sqlite, read_kv_rows, write_kv_rows, KvRow, MemFs, Session};
+
+    fn row(key: &str, value: &str, expires_at: Option<i64>) -> KvRow {
+        KvRow { key: key.into(), value: value.into(), expires_at }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("kvstore_sqlite_{}_{}.db", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn many_rows_and_long_values_round_trip() {
+        // Enough rows for interior pages in both trees, and values that overflow
+        let mut rows: Vec<KvRow> = (0..20_000)
+            .map(|i| row(&format!("key:{:06}", i), &format!("value {}", i), (i % 3 == 0).then_some(i)))
+            .collect();
+        rows.push(row("long", &"x".repeat(50_000), None));
+        rows.push(row(&"k".repeat(3_000), "long key", Some(7)));
+
+        let file = write_kv_rows(rows.clone());
+        assert_eq!(file.len() % 4096, 0);
+        let mut read = read_kv_rows(&file).unwrap();
+        rows.sort_by(|a, b| a.key.cmp(&b.key));
+        read.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(read, rows);
+    }
+
+    #[test]
+    fn later_duplicates_win_and_empty_tables_work() {
+        let file = write_kv_rows(vec![row("a", "1", None), row("a", "2", None)]);
+        assert_eq!(read_kv_rows(&file).unwrap(), vec![row("a", "2", None)]);
+        assert_eq!(read_kv_rows(&write_kv_rows(Vec::new())).unwrap(), Vec::new());
+        assert!(read_kv_rows(b"not a database").is_err());
+    }
+
+    #[test]
+    fn sessions_export_and_import_with_ttls() {
+        let mut source = Session::new();
+        source.fs = Arc::new(MemFs::new());
+        source.set("plain".into(), "value with spaces".into());
+        source.set("temp".into(), "soon gone".into());
+        source.set("dead".into(), "x".into());
+        source.ttl.set_expiration("temp", 60_000);
+        source.ttl.set_expiration("dead", 1);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        let path = temp_path("session");
+        assert_eq!(export_sqlite(&mut source, &path).unwrap(), 2);
+
+        let mut target = Session::new();
+        target.fs = Arc::new(MemFs::new());
+        target.limits.max_value_len = 10;
+        let report = import_sqlite(&mut target, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The long value breaks the target's limits
+        assert_eq!((report.imported, report.rejected, report.expired_skipped), (1, 1, 0));
+        assert_eq!(target.get("temp"), Some("soon gone".to_string()));
+        assert!((1..=60_000).contains(&target.ttl_status("temp")));
+        assert!(!target.exists("plain"));
+    }
+}
+
+
+// =====================================================================
+// SQLite Compatibility Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod sqlite_compat_tests {
+    use rusqlite::{params, Connection};
+
+    use crate::{read_kv_rows, write_kv_rows, KvRow, KV_TABLE_SQL};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("kvstore_sqlite_compat_{}_{}.db", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    /// Rows spanning interior pages, values long enough for overflow
+    /// chains several pages deep, and keys that overflow too.
+    fn sample_rows() -> Vec<KvRow> {
+        let mut rows: Vec<KvRow> = (0..5_000)
+            .map(|i| KvRow { key: format!("key:{:05}", i), value: format!("value {}", i), expires_at: (i % 4 == 0).then_some(i * 1_000) })
+            .collect();
+        rows.push(KvRow { key: "big".into(), value: "ab".repeat(60_000), expires_at: None });
+        rows.push(KvRow { key: "edge".into(), value: "e".repeat(4_062), expires_at: Some(-1) });
+        rows.push(KvRow { key: "k".repeat(5_000), value: "long key".into(), expires_at: Some(i64::MAX) });
+        rows.sort_by(|a, b| a.key.cmp(&b.key));
+        rows
+    }
+
+    #[test]
+    fn sqlite_reads_our_export() {
+        let rows = sample_rows();
+        let path = temp_path("export");
+        std::fs::write(&path, write_kv_rows(rows.clone())).unwrap();
+
+        let db = Connection::open(&path).unwrap();
+        let check: String = db.query_row("PRAGMA integrity_check", [], |r| r.get(0)).unwrap();
+        assert_eq!(check, "ok");
+        let read: Vec<KvRow> = db
+            .prepare("SELECT key, value, expires_at FROM kv ORDER BY key")
+            .unwrap()
+            .query_map([], |r| Ok(KvRow { key: r.get(0)?, value: r.get(1)?, expires_at: r.get(2)? }))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, rows);
+
+        // The primary key index answers lookups, long values included
+        let big: String = db.query_row("SELECT value FROM kv WHERE key = ?1", params!["big"], |r| r.get(0)).unwrap();
+        assert_eq!(big.len(), 120_000);
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn we_read_sqlite_made_files() {
+        let rows = sample_rows();
+        let path = temp_path("import");
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(KV_TABLE_SQL).unwrap();
+        db.execute_batch("CREATE TABLE other(x); INSERT INTO other VALUES (1);").unwrap();
+        for row in &rows {
+            db.execute("INSERT INTO kv VALUES (?1, ?2, ?3)", params![row.key, row.value, row.expires_at]).unwrap();
+        }
+        // Freed pages and a rebalanced tree, as a well-used file would have
+        db.execute("DELETE FROM kv WHERE key LIKE 'key:01%'", []).unwrap();
+        drop(db);
+
+        let mut read = read_kv_rows(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        read.sort_by(|a, b| a.key.cmp(&b.key));
+        let expected: Vec<KvRow> = rows.into_iter().filter(|row| !row.key.starts_with("key:01")).collect();
+        assert_eq!(read, expected);
+    }
+}
@@ -0,0 +1,321 @@
+// =====================================================================
+// File: sqlite/writer.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Writes the keyspace as a SQLite database file (`EXPORT SQLITE`).
+//
+//   The file is built bottom-up in memory: the `kv` table b-tree (rows
+//   in key order, so rowids follow the keys), the `sqlite_autoindex_kv_1`
+//   b-tree SQLite keeps for the `TEXT PRIMARY KEY`, and page 1 with the
+//   header and the schema pointing at both roots. Long values spill to
+//   overflow pages. The file is written next to the target and renamed
+//   over it, so readers never see half an export.
+// =====================================================================
+
+use std::io;
+
+use super::format::{database_header, encode_record, local_payload, put_varint, varint_len, KvRow, SqlValue, KV_TABLE_SQL, PAGE_SIZE};
+use crate::Session;
+
+/// Table interior cells are tiny; capping children per page keeps the
+/// split simple and every page well inside its 4 KiB.
+const TABLE_FANOUT: usize = 200;
+
+/// Index leaf and interior page types, table leaf and interior page types.
+const INDEX_INTERIOR: u8 = 0x02;
+const TABLE_INTERIOR: u8 = 0x05;
+const INDEX_LEAF: u8 = 0x0A;
+const TABLE_LEAF: u8 = 0x0D;
+
+/// Export every live key `session` lets the current user see to a
+/// SQLite file at `path`.
+///
+/// # Returns
+/// * `Ok(count)` of exported keys.
+/// * `Err(io::Error)` if the file can't be written.
+pub fn export_sqlite(session: &mut Session, path: &str) -> io::Result<usize> {
+    let rows = session.export_rows();
+    let count = rows.len();
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, write_kv_rows(rows))?;
+    std::fs::rename(&tmp, path)?;
+    Ok(count)
+}
+
+
+/// Build a SQLite database holding `rows` in a `kv` table.
+///
+/// Rows are sorted by key; of two with the same key the later wins.
+///
+/// # Example
+/// ```
+/// use kvstore::{read_kv_rows, write_kv_rows, KvRow};
+/// let rows = vec![KvRow { key: "a".into(), value: "1".into(), expires_at: None }];
+/// let file = write_kv_rows(rows.clone());
+/// assert!(file.starts_with(b"SQLite format 3\0"));
+/// assert_eq!(read_kv_rows(&file).unwrap(), rows);
+/// ```
+pub fn write_kv_rows(mut rows: Vec<KvRow>) -> Vec<u8> {
+    rows.reverse();
+    rows.sort_by(|a, b| a.key.as_bytes().cmp(b.key.as_bytes()));
+    rows.dedup_by(|later, earlier| later.key == earlier.key);
+
+    // Page 1 is filled in last, once both roots are known
+    let mut file = PageFile { pages: vec![Vec::new()] };
+    let table_root = build_table(&mut file, &rows);
+    let index_root = build_index(&mut file, &rows);
+
+    let schema = [
+        [SqlValue::Text("table".into()), SqlValue::Text("kv".into()), SqlValue::Text("kv".into()),
+         SqlValue::Integer(i64::from(table_root)), SqlValue::Text(KV_TABLE_SQL.into())],
+        [SqlValue::Text("index".into()), SqlValue::Text("sqlite_autoindex_kv_1".into()), SqlValue::Text("kv".into()),
+         SqlValue::Integer(i64::from(index_root)), SqlValue::Null],
+    ];
+    let cells: Vec<Vec<u8>> = schema
+        .iter()
+        .enumerate()
+        .map(|(i, row)| table_leaf_cell(&mut file, i as i64 + 1, &encode_record(row)))
+        .collect();
+    let mut first = page_bytes(TABLE_LEAF, &cells, None, 100);
+    first[..100].copy_from_slice(&database_header(file.pages.len() as u32));
+    file.pages[0] = first;
+    file.pages.concat()
+}
+
+
+/// Pages of the file being built; page `n` is `pages[n - 1]`.
+struct PageFile {
+    pages: Vec<Vec<u8>>,
+}
+
+
+impl PageFile {
+    /// Store `page` as the next page and return its number.
+    fn push(&mut self, page: Vec<u8>) -> u32 {
+        self.pages.push(page);
+        self.pages.len() as u32
+    }
+
+
+    /// Write `rest` of a payload to a chain of overflow pages.
+    ///
+    /// # Returns
+    /// The first page of the chain.
+    fn overflow(&mut self, rest: &[u8]) -> u32 {
+        let chunks: Vec<&[u8]> = rest.chunks(PAGE_SIZE - 4).collect();
+        let first = self.pages.len() as u32 + 1;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next = if i + 1 < chunks.len() { first + i as u32 + 1 } else { 0 };
+            let mut page = vec![0u8; PAGE_SIZE];
+            page[..4].copy_from_slice(&next.to_be_bytes());
+            page[4..4 + chunk.len()].copy_from_slice(chunk);
+            self.push(page);
+        }
+        first
+    }
+
+
+    /// A cell: `prefix`, the local part of `payload`, then the overflow
+    /// page number if it spills.
+    fn cell(&mut self, prefix: Vec<u8>, payload: &[u8], table_leaf: bool) -> Vec<u8> {
+        let local = local_payload(payload.len(), PAGE_SIZE, table_leaf);
+        let mut cell = prefix;
+        cell.extend_from_slice(&payload[..local]);
+        if local < payload.len() {
+            let first = self.overflow(&payload[local..]);
+            cell.extend_from_slice(&first.to_be_bytes());
+        }
+        cell
+    }
+}
+
+
+/// Bytes a cell takes on its page: `prefix_len` plus the local payload.
+fn cell_size(prefix_len: usize, payload_len: usize, table_leaf: bool) -> usize {
+    let local = local_payload(payload_len, PAGE_SIZE, table_leaf);
+    prefix_len + local + if local < payload_len { 4 } else { 0 }
+}
+
+
+/// Whether `count` cells of `bytes` in total fit on a page of `kind`.
+fn fits(kind: u8, count: usize, bytes: usize) -> bool {
+    let header = if matches!(kind, TABLE_LEAF | INDEX_LEAF) { 8 } else { 12 };
+    header + 2 * count + bytes <= PAGE_SIZE
+}
+
+
+/// Lay out a b-tree page: header, cell pointers, cells packed from the end.
+fn page_bytes(kind: u8, cells: &[Vec<u8>], right: Option<u32>, offset: usize) -> Vec<u8> {
+    let mut page = vec![0u8; PAGE_SIZE];
+    let header = if right.is_some() { 12 } else { 8 };
+    let mut content = PAGE_SIZE;
+    for (i, cell) in cells.iter().enumerate() {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        let ptr = offset + header + 2 * i;
+        page[ptr..ptr + 2].copy_from_slice(&(content as u16).to_be_bytes());
+    }
+    page[offset] = kind;
+    page[offset + 3..offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    page[offset + 5..offset + 7].copy_from_slice(&(content as u16).to_be_bytes());
+    if let Some(right) = right {
+        page[offset + 8..offset + 12].copy_from_slice(&right.to_be_bytes());
+    }
+    page
+}
+
+
+fn table_leaf_cell(file: &mut PageFile, rowid: i64, record: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::new();
+    put_varint(&mut prefix, record.len() as u64);
+    put_varint(&mut prefix, rowid as u64);
+    file.cell(prefix, record, true)
+}
+
+
+fn row_record(row: &KvRow) -> Vec<u8> {
+    let expires_at = row.expires_at.map_or(SqlValue::Null, SqlValue::Integer);
+    encode_record(&[SqlValue::Text(row.key.clone()), SqlValue::Text(row.value.clone()), expires_at])
+}
+
+
+/// Build the table b-tree, rowid `i + 1` for `rows[i]`.
+///
+/// # Returns
+/// The root page.
+fn build_table(file: &mut PageFile, rows: &[KvRow]) -> u32 {
+    // Leaves, each with the largest rowid on it
+    let mut level: Vec<(u32, i64)> = Vec::new();
+    let mut cells: Vec<Vec<u8>> = Vec::new();
+    let mut bytes = 0;
+    for (i, row) in rows.iter().enumerate() {
+        let rowid = i as i64 + 1;
+        let record = row_record(row);
+        let prefix_len = varint_len(record.len() as u64) + varint_len(rowid as u64);
+        if !fits(TABLE_LEAF, cells.len() + 1, bytes + cell_size(prefix_len, record.len(), true)) {
+            level.push((file.push(page_bytes(TABLE_LEAF, &cells, None, 0)), rowid - 1));
+            cells.clear();
+            bytes = 0;
+        }
+        let cell = table_leaf_cell(file, rowid, &record);
+        bytes += cell.len();
+        cells.push(cell);
+    }
+    level.push((file.push(page_bytes(TABLE_LEAF, &cells, None, 0)), rows.len() as i64));
+
+    // Interior levels: a cell per child but the last, which is the right pointer
+    while level.len() > 1 {
+        let pages = level.len().div_ceil(TABLE_FANOUT);
+        let per_page = level.len().div_ceil(pages);
+        level = level
+            .chunks(per_page)
+            .map(|children| {
+                let (last, max_rowid) = children[children.len() - 1];
+                let cells: Vec<Vec<u8>> = children[..children.len() - 1]
+                    .iter()
+                    .map(|(child, rowid)| {
+                        let mut cell = child.to_be_bytes().to_vec();
+                        put_varint(&mut cell, *rowid as u64);
+                        cell
+                    })
+                    .collect();
+                (file.push(page_bytes(TABLE_INTERIOR, &cells, Some(last), 0)), max_rowid)
+            })
+            .collect();
+    }
+    level[0].0
+}
+
+
+/// Build the primary key index: one `(key, rowid)` entry per row.
+///
+/// Unlike the table, an index keeps entries on interior pages too. When
+/// a page fills up, its last entry moves up as the separator between it
+/// and the next page, so no page is ever left empty.
+///
+/// # Returns
+/// The root page.
+fn build_index(file: &mut PageFile, rows: &[KvRow]) -> u32 {
+    let entry_size = |payload: &Vec<u8>, prefix: usize| cell_size(prefix + varint_len(payload.len() as u64), payload.len(), false);
+    let entries: Vec<Vec<u8>> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| encode_record(&[SqlValue::Text(row.key.clone()), SqlValue::Integer(i as i64 + 1)]))
+        .collect();
+
+    // Leaves, and the separators between them
+    let mut children: Vec<u32> = Vec::new();
+    let mut separators: Vec<Vec<u8>> = Vec::new();
+    let mut page: Vec<Vec<u8>> = Vec::new();
+    let mut bytes = 0;
+    for entry in entries {
+        let size = entry_size(&entry, 0);
+        if !fits(INDEX_LEAF, page.len() + 1, bytes + size) {
+            // At least four entries fit a page, so one stays behind
+            let separator = page.pop().expect("a full page holds entries");
+            children.push(write_index_page(file, INDEX_LEAF, &page, None));
+            separators.push(separator);
+            page.clear();
+            bytes = 0;
+        }
+        bytes += size;
+        page.push(entry);
+    }
+    children.push(write_index_page(file, INDEX_LEAF, &page, None));
+
+    // Interior levels: cells pair a child with the separator after it
+    while children.len() > 1 {
+        let mut next_children = Vec::new();
+        let mut next_separators = Vec::new();
+        let mut cells: Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut bytes = 0;
+        let mut pending = children[0];
+        for (separator, child) in separators.into_iter().zip(children.into_iter().skip(1)) {
+            let size = entry_size(&separator, 4);
+            if !fits(INDEX_INTERIOR, cells.len() + 1, bytes + size) {
+                let (right, promoted) = cells.pop().expect("a full page holds cells");
+                next_children.push(write_interior_index_page(file, &cells, right));
+                next_separators.push(promoted);
+                cells.clear();
+                bytes = 0;
+            }
+            bytes += size;
+            cells.push((pending, separator));
+            pending = child;
+        }
+        next_children.push(write_interior_index_page(file, &cells, pending));
+        children = next_children;
+        separators = next_separators;
+    }
+    children[0]
+}
+
+
+fn write_index_page(file: &mut PageFile, kind: u8, entries: &[Vec<u8>], right: Option<u32>) -> u32 {
+    let cells: Vec<Vec<u8>> = entries
+        .iter()
+        .map(|entry| {
+            let mut prefix = Vec::new();
+            put_varint(&mut prefix, entry.len() as u64);
+            file.cell(prefix, entry, false)
+        })
+        .collect();
+    file.push(page_bytes(kind, &cells, right, 0))
+}
+
+
+fn write_interior_index_page(file: &mut PageFile, cells: &[(u32, Vec<u8>)], right: u32) -> u32 {
+    let cells: Vec<Vec<u8>> = cells
+        .iter()
+        .map(|(child, entry)| {
+            let mut prefix = child.to_be_bytes().to_vec();
+            put_varint(&mut prefix, entry.len() as u64);
+            file.cell(prefix, entry, false)
+        })
+        .collect();
+    file.push(page_bytes(INDEX_INTERIOR, &cells, Some(right), 0))
+}
@@ -16,108 +16,3086 @@
 //    append-only writes to a file named data.db.
 // 2) Data must remain consistent after restarting the program.
 // 3) On startup, replay the log to rebuild the in-memory index.
+//
+// Records are `SET <key> <value>`, `MSET <k1> <v1> ...` and `DEL <key>`
+// lines, decoded by `decode_record`. Keys and values are escaped
+// (`\\`, `\s` for space, `\t`, `\n`, `\r`, `\0`, `\e` for empty) so
+// each is exactly one whitespace-free field and can always be split back
+// out on replay. Values are bytes: any byte that is not part of valid
+// UTF-8 is written `\xNN`, which no text value ever produces, so logs
+// of text values read the same as before.
+//
+// From format 5 the data file holds those records as length-prefixed
+// binary frames instead (see `Layout`): an op code byte, then each key,
+// value or number as a length and its bytes, so values are stored as
+// they are rather than escaped. Only the file changes: records are
+// still built, replicated and replayed as the text above, and framed
+// on the way to the file and back. `migrate_log` rewrites text logs.
+//
+// Logs written with `\r\n` line endings (for example copied through a
+// Windows editor or checkout) replay the same as `\n` logs; new records
+// always end in `\n`. Paths are built with `PathBuf`, so the data file
+// and its sidecar files (`.lock`, `.compact`, ...) sit correctly in a
+// directory given with either separator.
+//
+// New data files start with a `KVSTORE <version>` header line naming
+// the log format, which stays text in binary logs too; files written
+// before the header existed are version 0 and are upgraded in place by
+// `migrate_log`.
+//
+// From format 2 every record ends in a tab and its CRC-32 (`seal_record`);
+// a binary frame carries the CRC-32 of its bytes instead. Replay strips
+// the checksum and stops at the first record that fails it: a torn
+// write, which `recover_log` cuts off before anything is appended after
+// it. Records in memory (and on the replication stream) never carry the
+// checksum; it is added and checked only at the file.
+//
+// From format 4 an appended record is stamped `<seq>@<unix ms>` between
+// the record and its checksum (`seal_record_at`). Sequence numbers keep
+// counting up across restarts; a rewritten log (compaction, checkpoint,
+// repair) leaves its records unstamped and keeps the last number in its
+// header instead, `KVSTORE <version> <seq>`, so `last_seq` can carry on from it.
+//
+// With an encryption key installed (see `crypt`) a record is encrypted
+// to `ENC <base64>` before it is sealed (framed as the ciphertext itself
+// in a binary log), and decrypted after its checksum passes on replay.
+//
+// A log truncated by a checkpoint starts with a `CHECKPOINT <id>` record
+// and is replayed on top of the snapshot beside it (see `checkpoint`).
+//
+// Appends go through the process's `FileBackend` (see `backend`), which
+// caches a `LogWriter` per file, so the log is opened once and grown in
+// preallocated chunks. Unused preallocated space is
+// zero-filled and trimmed on close; replay ignores it after a crash.
 // ============================================================
 #![allow(dead_code)]
-use std::fs::{OpenOptions, File};
-use std::io::{self, Write, BufRead, BufReader};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, OpenOptions, File};
+use std::io::{self, Write, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::backend::FileBackend;
+use crate::crypt::{self, LogKey};
+use crate::pager::crc32;
+use crate::vfs::{Fs, RealFs};
+
+/// Log format written by this build, recorded in the header record.
+pub const FORMAT_VERSION: u32 = 5;
+
+/// First format whose records all carry a checksum.
+pub const CHECKSUMS_SINCE: u32 = 2;
+
+/// First format whose records may carry the time they were appended.
+pub const TIMESTAMPS_SINCE: u32 = 3;
+
+/// First format whose stamps also carry a sequence number.
+pub const SEQUENCES_SINCE: u32 = 4;
+
+/// First format whose records are binary frames rather than text lines.
+pub const BINARY_SINCE: u32 = 5;
+
+/// Bytes reserved ahead of the append position each time the log grows.
+pub const PREALLOC_CHUNK: u64 = 64 * 1024;
+
+
+/// How each append is made durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// `sync_all` after every append: data and all file metadata.
+    #[default]
+    All,
+
+    /// `sync_data` after every append: data plus only the metadata
+    /// needed to read it back (e.g. file size), skipping timestamps.
+    Data,
+
+    /// Open the log with `O_DSYNC` so every write is synchronous and no
+    /// separate sync call is needed. Falls back to [`SyncMode::Data`] on
+    /// platforms without `O_DSYNC`.
+    Dsync,
+}
+
+
+impl SyncMode {
+    /// Parse a mode name as used by `KVSTORE_SYNC` (`all`, `data`, `dsync`).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::SyncMode;
+    /// assert_eq!(SyncMode::from_name("DATA"), Some(SyncMode::Data));
+    /// assert_eq!(SyncMode::from_name("sometimes"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "all" => Some(SyncMode::All),
+            "data" => Some(SyncMode::Data),
+            "dsync" => Some(SyncMode::Dsync),
+            _ => None,
+        }
+    }
+
+
+    /// The name [`SyncMode::from_name`] accepts for this mode.
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncMode::All => "all",
+            SyncMode::Data => "data",
+            SyncMode::Dsync => "dsync",
+        }
+    }
+}
+
+
+/// Process-wide sync mode for newly opened logs (a `SyncMode` discriminant).
+static SYNC_MODE: AtomicU8 = AtomicU8::new(0);
+
+
+/// Set the sync mode used by logs opened from now on.
+///
+/// Logs that already have a cached handle keep their mode until closed,
+/// so call this before the first write.
+pub fn set_sync_mode(mode: SyncMode) {
+    SYNC_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+
+/// Sync mode used when a log is opened.
+pub fn sync_mode() -> SyncMode {
+    match SYNC_MODE.load(Ordering::Relaxed) {
+        1 => SyncMode::Data,
+        2 => SyncMode::Dsync,
+        _ => SyncMode::All,
+    }
+}
+
+
+/// When appends are made durable, traded against throughput.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Sync every append before it is acknowledged.
+    #[default]
+    Always,
+
+    /// Sync at most once per this many milliseconds; a background thread
+    /// syncs appends left pending once the interval has passed.
+    EveryMillis(u64),
+
+    /// Sync only when the log is closed (on shutdown, reload or before
+    /// the file is replaced).
+    OnExit,
+}
+
+
+impl Durability {
+    /// Parse a policy name as used by `KVSTORE_DURABILITY` (`always`,
+    /// `<n>ms`, `exit`).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Durability;
+    /// assert_eq!(Durability::from_name("250ms"), Some(Durability::EveryMillis(250)));
+    /// assert_eq!(Durability::from_name("EXIT"), Some(Durability::OnExit));
+    /// assert_eq!(Durability::from_name("sometimes"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "always" => Some(Durability::Always),
+            "exit" => Some(Durability::OnExit),
+            other => other.strip_suffix("ms")?.parse().ok().map(Durability::EveryMillis),
+        }
+    }
+
+
+    /// The name [`Durability::from_name`] accepts for this policy.
+    pub fn name(self) -> String {
+        match self {
+            Durability::Always => "always".to_string(),
+            Durability::EveryMillis(ms) => format!("{}ms", ms),
+            Durability::OnExit => "exit".to_string(),
+        }
+    }
+
+
+    /// Whether a sync last made at `last_sync` is due again.
+    fn sync_due(self, last_sync: Instant) -> bool {
+        match self {
+            Durability::Always => true,
+            Durability::EveryMillis(ms) => last_sync.elapsed() >= Duration::from_millis(ms),
+            Durability::OnExit => false,
+        }
+    }
+}
+
+
+/// Process-wide durability policy for newly opened logs.
+static DURABILITY: Mutex<Durability> = Mutex::new(Durability::Always);
+
+/// How often the background thread looks for pending appends to sync.
+const FLUSH_TICK: Duration = Duration::from_millis(10);
+
+
+/// Set the durability policy used by logs opened from now on.
+///
+/// Like [`set_sync_mode`], a log with a cached handle keeps its policy
+/// until it is closed.
+pub fn set_durability(policy: Durability) {
+    *DURABILITY.lock().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+
+/// Durability policy used when a log is opened.
+pub fn durability() -> Durability {
+    *DURABILITY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+
+/// `O_DSYNC` open flag, where the platform has one.
+#[cfg(target_os = "linux")]
+const O_DSYNC: Option<i32> = Some(0o10000);
+#[cfg(target_os = "macos")]
+const O_DSYNC: Option<i32> = Some(0x40_0000);
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const O_DSYNC: Option<i32> = None;
+
+
+/// Exact location of a value inside the log: `len` bytes at `offset`.
+///
+/// Key-only sessions keep one of these per key instead of the value,
+/// so a read is a single positioned read with no parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValuePointer {
+    /// Byte offset of the first value byte in the log.
+    pub offset: u64,
+
+    /// Length of the value in bytes.
+    pub len: u32,
+
+    /// `true` if the bytes are the value itself, as a binary log stores
+    /// it; `false` if they are its escaped text field.
+    pub raw: bool,
+}
+
+
+impl ValuePointer {
+    /// Locate the value field of a text `SET` record starting at
+    /// `record_offset`.
+    ///
+    /// The value is the last whitespace-separated field, stored escaped;
+    /// [`read_value`] decodes it.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{set_record, ValuePointer};
+    /// let line = set_record("k", "a b");
+    /// assert_eq!(ValuePointer::for_set_record(10, &line), ValuePointer { offset: 16, len: 4, raw: false });
+    /// ```
+    pub fn for_set_record(record_offset: u64, record: &str) -> Self {
+        let field = record.rsplit(char::is_whitespace).next().unwrap_or("");
+        Self::for_record(record_offset, record, field)
+    }
+
+    /// Locate `value` at the end of the text `record`, which starts at
+    /// `record_offset`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ValuePointer;
+    /// let ptr = ValuePointer::for_record(100, "SET dog bark", "bark");
+    /// assert_eq!(ptr, ValuePointer { offset: 108, len: 4, raw: false });
+    /// ```
+    pub fn for_record(record_offset: u64, record: &str, value: &str) -> Self {
+        Self {
+            offset: record_offset + (record.len() - value.len()) as u64,
+            len: value.len() as u32,
+            raw: false,
+        }
+    }
+}
+
+
+/// How a log's records are laid out in its file, which its header's
+/// format version decides.
+///
+/// Text logs (formats 0 to 4) hold one escaped record per line. Binary
+/// logs ([`BINARY_SINCE`] on) hold their text header line, then one
+/// frame per record:
+///
+/// ```text
+/// op (1 byte) | body length (u32) | body | CRC-32 (u32) | body length (u32) | \n
+/// ```
+///
+/// The op code names the record (`SET`, `DEL`, ...; `0x80` is added
+/// when the record is stamped). The body is the record's fields (key,
+/// value, numbers as 8 bytes), each a length (u32) and its bytes, then
+/// the stamp's sequence number and time (two u64s) if it has one.
+/// Integers are little-endian, and the CRC-32 covers the op code, body
+/// length and body. The length repeated at the end lets the log be read
+/// back from its end, and the closing newline keeps the last byte of
+/// every frame non-zero, so zero padding is still found by scanning back
+/// for the last non-zero byte. A record that does not decode to the
+/// same text (none written by this build) is framed as its text.
+///
+/// Appends stay text: [`Fs::append`] and [`append_write`] take sealed
+/// text records and frame them when the log's header says it is binary,
+/// and replay turns frames back into text records.
+///
+/// # Example
+/// ```
+/// use kvstore::{decode_record, Layout, ReplayOp, ValuePointer, BINARY_SINCE};
+/// assert_eq!(Layout::of_version(BINARY_SINCE), Layout::Binary);
+/// assert_eq!(Layout::Text.decode(0, "SET k v"), decode_record(0, "SET k v"));
+/// // op and length, the key's length and key, the value's length
+/// let ptr = ValuePointer { offset: 5 + 4 + 1 + 4, len: 1, raw: true };
+/// assert_eq!(Layout::Binary.decode(0, "SET k v"), vec![ReplayOp::Set("k".into(), b"v".to_vec(), ptr)]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// One text record per line.
+    #[default]
+    Text,
+
+    /// Length-prefixed binary frames after the header line.
+    Binary,
+}
+
+
+impl Layout {
+    /// The layout of a log of format `version`.
+    pub fn of_version(version: u32) -> Layout {
+        if version >= BINARY_SINCE { Layout::Binary } else { Layout::Text }
+    }
+
+
+    /// Decode the record (as replayed, without its checksum) starting at
+    /// `offset` in a log of this layout into the changes it makes, as
+    /// [`decode_record`] does for a text log: each value set points at
+    /// its bytes in the log.
+    pub fn decode(self, offset: u64, record: &str) -> Vec<ReplayOp> {
+        let (op, fields) = match self {
+            Layout::Text => return decode_record(offset, record),
+            Layout::Binary => record_fields(record),
+        };
+        if op == OP_TEXT {
+            // The record's text is the frame's only field
+            return decode_record(offset + FIELDS_AT + 4, record);
+        }
+
+        // Values are every other field from the second
+        let mut at = offset + FIELDS_AT;
+        let mut values = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            at += 4;
+            if i % 2 == 1 {
+                values.push(ValuePointer { offset: at, len: field.len() as u32, raw: true });
+            }
+            at += field.len() as u64;
+        }
+        decode_with(record, values.into_iter())
+    }
+}
+
+
+/// Op codes of binary records.
+const OP_SET: u8 = 1;
+const OP_MSET: u8 = 2;
+const OP_DEL: u8 = 3;
+const OP_EXPIREAT: u8 = 4;
+const OP_PERSIST: u8 = 5;
+const OP_CHECKPOINT: u8 = 6;
+const OP_VSET: u8 = 7;
+
+/// An encrypted record: the ciphertext, not base64 as in a text log.
+const OP_ENC: u8 = 8;
+
+/// Any other record, as its text.
+const OP_TEXT: u8 = 9;
+
+/// Added to the op code of a record that carries a stamp.
+const STAMPED: u8 = 0x80;
+
+/// Where a frame's fields start: after the op code and body length.
+const FIELDS_AT: u64 = 5;
+
+/// Bytes of a frame around its body.
+const FRAME_OVERHEAD: usize = 5 + 4 + 4 + 1;
+
+
+/// The op code and fields `record` is framed with.
+fn record_fields(record: &str) -> (u8, Vec<Vec<u8>>) {
+    if let Some(payload) = record.strip_prefix("ENC ")
+        && let Some(bytes) = crypt::base64_decode(payload).filter(|bytes| crypt::base64_encode(bytes) == payload)
+    {
+        return (OP_ENC, vec![bytes]);
+    }
+    let text = |text: String| text.into_bytes();
+    match LogRecord::decode(record) {
+        // Only a record that encodes back to the same text, so replay
+        // hands out exactly what was appended
+        Some(decoded) if decoded.encode() == record => match decoded {
+            LogRecord::Set { key, value } => (OP_SET, vec![text(key), value]),
+            LogRecord::MSet { pairs } => (OP_MSET, pairs.into_iter().flat_map(|(k, v)| [text(k), text(v)]).collect()),
+            LogRecord::Del { key } => (OP_DEL, vec![text(key)]),
+            LogRecord::ExpireAt { key, ms } => (OP_EXPIREAT, vec![text(key), ms.to_le_bytes().to_vec()]),
+            LogRecord::Persist { key } => (OP_PERSIST, vec![text(key)]),
+            LogRecord::Checkpoint { id } => (OP_CHECKPOINT, vec![id.to_le_bytes().to_vec()]),
+            // Framed as text, so older builds skip it as an unknown record instead of a torn one
+            LogRecord::Segment { .. } => (OP_TEXT, vec![record.as_bytes().to_vec()]),
+            LogRecord::SetRef { key, value } => (OP_VSET, vec![
+                text(key),
+                value.generation.to_le_bytes().to_vec(),
+                value.ptr.offset.to_le_bytes().to_vec(),
+                value.ptr.len.to_le_bytes().to_vec(),
+            ]),
+        },
+        _ => (OP_TEXT, vec![record.as_bytes().to_vec()]),
+    }
+}
+
+
+/// The text of the record framed with op code `op` and `fields`.
+///
+/// # Returns
+/// `None` for an unknown op code or fields that don't fit it.
+fn fields_record(op: u8, fields: &[&[u8]]) -> Option<String> {
+    let text = |field: &[u8]| String::from_utf8(field.to_vec()).ok();
+    let number = |field: &[u8]| Some(u64::from_le_bytes(field.try_into().ok()?));
+    let record = match (op, fields) {
+        (OP_SET, [key, value]) => LogRecord::Set { key: text(key)?, value: value.to_vec() },
+        (OP_MSET, pairs) if !pairs.is_empty() && pairs.len() % 2 == 0 => LogRecord::MSet {
+            pairs: pairs.chunks(2).map(|pair| Some((text(pair[0])?, text(pair[1])?))).collect::<Option<_>>()?,
+        },
+        (OP_DEL, [key]) => LogRecord::Del { key: text(key)? },
+        (OP_EXPIREAT, [key, ms]) => LogRecord::ExpireAt { key: text(key)?, ms: number(ms)? },
+        (OP_PERSIST, [key]) => LogRecord::Persist { key: text(key)? },
+        (OP_CHECKPOINT, [id]) => LogRecord::Checkpoint { id: number(id)? },
+        (OP_VSET, [key, generation, offset, len]) => LogRecord::SetRef {
+            key: text(key)?,
+            value: ValueRef {
+                generation: number(generation)?,
+                ptr: ValuePointer { offset: number(offset)?, len: u32::from_le_bytes((*len).try_into().ok()?), raw: false },
+            },
+        },
+        (OP_ENC, [ciphertext]) => return Some(format!("ENC {}", crypt::base64_encode(ciphertext))),
+        (OP_TEXT, [record]) => return text(record),
+        _ => return None,
+    };
+    Some(record.encode())
+}
+
+
+/// Append the frame of `record` (stamped with `stamp`, if any) to `out`.
+fn push_frame(out: &mut Vec<u8>, record: &str, stamp: Option<Stamp>) {
+    let (op, fields) = record_fields(record);
+    let start = out.len();
+    out.push(if stamp.is_some() { op | STAMPED } else { op });
+    out.extend_from_slice(&[0; 4]); // the body length, once it is known
+    for field in &fields {
+        out.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        out.extend_from_slice(field);
+    }
+    if let Some(stamp) = stamp {
+        out.extend_from_slice(&stamp.seq.to_le_bytes());
+        out.extend_from_slice(&stamp.unix_ms.to_le_bytes());
+    }
+    let len = ((out.len() - start) as u32 - FIELDS_AT as u32).to_le_bytes();
+    out[start + 1..start + 5].copy_from_slice(&len);
+    let sum = crc32(&out[start..]);
+    out.extend_from_slice(&sum.to_le_bytes());
+    out.extend_from_slice(&len);
+    out.push(b'\n');
+}
+
+
+/// What the bytes at some point of a binary log hold.
+pub(crate) enum Framed {
+    /// A whole frame this many bytes long, its record (as text, without
+    /// checksum) and its stamp.
+    Whole(usize, String, Option<Stamp>),
+
+    /// The start of a frame the bytes end inside.
+    Cut,
+
+    /// No frame: a bad op code, or a frame that fails its checksum or
+    /// does not decode.
+    Bad,
+}
+
+
+/// Returns `true` for the op code of a record, stamped or not.
+fn known_op(op: u8) -> bool {
+    (OP_SET..=OP_TEXT).contains(&(op & !STAMPED))
+}
+
+
+/// The frame at the start of `bytes`.
+pub(crate) fn frame_at(bytes: &[u8]) -> Framed {
+    let Some(&op) = bytes.first() else {
+        return Framed::Cut;
+    };
+    if !known_op(op) {
+        return Framed::Bad;
+    }
+    let Some(len) = bytes.get(1..5) else {
+        return Framed::Cut;
+    };
+    let len = u32::from_le_bytes(len.try_into().unwrap_or_default()) as usize;
+    let Some(frame) = bytes.get(..len + FRAME_OVERHEAD) else {
+        return Framed::Cut;
+    };
+    let (head, tail) = frame.split_at(FIELDS_AT as usize + len);
+    if tail[..4] != crc32(head).to_le_bytes() || tail[4..8] != head[1..5] || tail[8] != b'\n' {
+        return Framed::Bad;
+    }
+
+    let mut body = &head[FIELDS_AT as usize..];
+    let mut stamp = None;
+    if op & STAMPED != 0 {
+        let Some(at) = body.len().checked_sub(16) else {
+            return Framed::Bad;
+        };
+        let number = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap_or_default());
+        stamp = Some(Stamp { seq: number(&body[at..at + 8]), unix_ms: number(&body[at + 8..]) });
+        body = &body[..at];
+    }
+    let mut fields = Vec::new();
+    while !body.is_empty() {
+        let Some(len) = body.get(..4) else {
+            return Framed::Bad;
+        };
+        let len = u32::from_le_bytes(len.try_into().unwrap_or_default()) as usize;
+        let Some(field) = body.get(4..4 + len) else {
+            return Framed::Bad;
+        };
+        fields.push(field);
+        body = &body[4 + len..];
+    }
+    match fields_record(op & !STAMPED, &fields) {
+        Some(record) => Framed::Whole(frame.len(), record, stamp),
+        None => Framed::Bad,
+    }
+}
+
+
+/// Read the next frame of a binary log from `reader` into `frame`, or
+/// skip the zero padding there.
+///
+/// # Returns
+/// The bytes read: 0 at the end of the log. `frame` is left empty
+/// after padding; otherwise [`frame_at`] tells what it holds.
+fn read_frame(reader: &mut impl BufRead, frame: &mut Vec<u8>) -> io::Result<usize> {
+    frame.clear();
+    let mut padding = 0;
+    loop {
+        let buffered = reader.fill_buf()?;
+        let zeros = buffered.iter().take_while(|&&b| b == 0).count();
+        let more = zeros > 0 && zeros == buffered.len();
+        reader.consume(zeros);
+        padding += zeros;
+        if !more {
+            break;
+        }
+    }
+    if padding > 0 {
+        return Ok(padding);
+    }
+
+    reader.by_ref().take(FIELDS_AT).read_to_end(frame)?;
+    // A bad op code is damage; its length means nothing
+    if frame.len() == FIELDS_AT as usize && known_op(frame[0]) {
+        let len = u32::from_le_bytes(frame[1..5].try_into().unwrap_or_default()) as u64;
+        reader.by_ref().take(len + (FRAME_OVERHEAD as u64 - FIELDS_AT)).read_to_end(frame)?;
+    }
+    Ok(frame.len())
+}
+
+
+/// The layout of a log whose first bytes are `head`, from its first
+/// line that holds anything.
+///
+/// # Returns
+/// `None` if `head` holds nothing but blank lines and zero padding.
+pub(crate) fn head_layout(head: &[u8]) -> Option<Layout> {
+    let first = head.split(|&b| b == b'\n').find(|line| !line.iter().all(|&b| b == 0 || b.is_ascii_whitespace()))?;
+    let version = std::str::from_utf8(first).ok().and_then(|line| parse_header(trim_record(line)));
+    Some(Layout::of_version(version.unwrap_or(0)))
+}
+
+
+/// The layout of a log that `records` are the first things appended
+/// to: binary if they open with a binary header.
+pub(crate) fn first_layout<S: AsRef<str>>(records: &[S]) -> Option<Layout> {
+    records.iter().find_map(|record| head_layout(record.as_ref().as_bytes()))
+}
+
+
+/// The layout of the log at `path`, read through `fs` from its header;
+/// text for a missing or blank file.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidData` for a format newer than this
+/// build's, or any error from reading it.
+pub fn layout_with(fs: &dyn Fs, path: &str) -> io::Result<Layout> {
+    Ok(file_version(fs, path)?.map_or(Layout::Text, Layout::of_version))
+}
+
+
+/// The bytes that appending `records` (each one or more lines without
+/// the final newline) to a log laid out as `layout` writes, and the
+/// offset of each record within them.
+///
+/// A text log gets each record and a newline. In a binary log every
+/// sealed line is framed, a header line stays text and blank lines are
+/// dropped.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidInput` if a line for a binary log
+/// is neither a header nor a sealed record.
+pub(crate) fn encode_records<S: AsRef<str>>(layout: Layout, records: &[S]) -> io::Result<(Vec<u8>, Vec<u64>)> {
+    if layout == Layout::Text {
+        let mut bytes = records.iter().map(AsRef::as_ref).collect::<Vec<_>>().join("\n").into_bytes();
+        bytes.push(b'\n');
+        return Ok((bytes, record_offsets(0, records)));
+    }
+
+    let mut bytes = Vec::new();
+    let mut offsets = Vec::with_capacity(records.len());
+    for record in records {
+        offsets.push(bytes.len() as u64);
+        for line in record.as_ref().split('\n') {
+            let line = trim_record(line);
+            if line.is_empty() {
+                continue;
+            }
+            if parse_header(line).is_some() {
+                bytes.extend_from_slice(line.as_bytes());
+                bytes.push(b'\n');
+                continue;
+            }
+            let (record, stamp) = unseal_stamped(line).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("unsealed record for a binary log: {}", line))
+            })?;
+            push_frame(&mut bytes, record, stamp);
+        }
+    }
+    Ok((bytes, offsets))
+}
+
+
+/// The bytes of a data file holding the log `text` (a header, then
+/// sealed records, as [`log_text`] builds it): the text as it is, or
+/// under a binary header the header line and then each record framed.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidInput` if a binary log's record is
+/// not sealed.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_bytes, log_text, Fs, MemFs};
+/// let fs = MemFs::new();
+/// fs.append("log", &log_text(&["SET a 1"])).unwrap();
+/// assert_eq!(fs.contents("log").unwrap(), log_bytes(&log_text(&["SET a 1"])).unwrap());
+/// assert_eq!(log_bytes("SET a 1").unwrap(), b"SET a 1\n");
+/// ```
+pub fn log_bytes(text: &str) -> io::Result<Vec<u8>> {
+    encode_records(first_layout(&[text]).unwrap_or_default(), &[text]).map(|(bytes, _)| bytes)
+}
+
+
+/// Where a value moved out of the log lives: the generation of the value
+/// log holding it, and its escaped bytes within that file.
+///
+/// The log keeps a `VSET` record with one of these in place of the
+/// value (see [`crate::vlog`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueRef {
+    /// Value log file the bytes are in ([`value_log_path`]).
+    pub generation: u64,
+
+    /// The escaped value inside that file.
+    pub ptr: ValuePointer,
+}
+
+
+/// Path of generation `generation` of the value log beside the data file
+/// `path`: `<path>.vlog.<generation>`.
+///
+/// # Example
+/// ```
+/// use kvstore::value_log_path;
+/// assert_eq!(value_log_path("data.db", 3), "data.db.vlog.3");
+/// ```
+pub fn value_log_path(path: &str, generation: u64) -> String {
+    sidecar_path(path, &format!("vlog.{}", generation))
+}
 
 
 /// Uses consistent db file for persistence.
+///
+/// `KVSTORE_DATA_FILE` names the file outright; otherwise it is
+/// `data.db` inside `KVSTORE_DATA_DIR`, or in the working directory.
+/// (The binary opens `KVSTORE_DATA_DIR` as a [`DataDir`](crate::DataDir)
+/// instead, whose `MANIFEST` names the log.)
 pub fn get_data_file() -> String {
-    std::env::var("KVSTORE_DATA_FILE").unwrap_or_else(|_| "data.db".to_string())
+    resolve_data_file(std::env::var_os("KVSTORE_DATA_FILE"), std::env::var_os("KVSTORE_DATA_DIR"))
 }
 
-/// Append a single command to the persistent log file.
+
+/// [`get_data_file`] without the environment lookup.
+fn resolve_data_file(file: Option<OsString>, dir: Option<OsString>) -> String {
+    let path = match (file, dir) {
+        (Some(file), _) => PathBuf::from(file),
+        (None, Some(dir)) => PathBuf::from(dir).join("data.db"),
+        (None, None) => PathBuf::from("data.db"),
+    };
+    path.to_string_lossy().into_owned()
+}
+
+
+/// Path of a file kept next to `path`, named `<path>.<suffix>`.
 ///
-/// Each command is written on its own line with a trailing newline.
-/// The file is created if it does not exist, and opened in append mode.
-/// Data is flushed immediately to disk to reduce the chance of loss.
+/// # Example
+/// ```
+/// use kvstore::sidecar_path;
+/// let log = std::path::Path::new("store").join("data.db");
+/// let lock = sidecar_path(log.to_str().unwrap(), "lock");
+/// assert_eq!(std::path::Path::new(&lock), std::path::Path::new("store").join("data.db.lock"));
+/// ```
+pub fn sidecar_path(path: &str, suffix: &str) -> String {
+    let mut name = PathBuf::from(path).into_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name).to_string_lossy().into_owned()
+}
+
+
+/// Replace the file at `path` with `text` (plus a newline) so that a
+/// crash leaves either the old file or the new one, never a mix.
 ///
-/// # Arguments
-/// * `filename` - The path of the log file (e.g. `data.db`).
-/// * `input_data` - The raw command string (e.g. `"SET dog bark"`).
+/// The text goes to `<path>.tmp`, which is synced and installed with
+/// [`install_file`]. The temporary file is removed if any step fails.
+///
+/// # Returns
+/// `Err(io::Error)` from any step; `path` is then unchanged.
+///
+/// # Example
+/// ```
+/// use kvstore::{replace_file, Fs, MemFs};
+/// let fs = MemFs::new();
+/// fs.append("data.db", "old").unwrap();
+/// replace_file(&fs, "data.db", "new").unwrap();
+/// assert_eq!(fs.contents("data.db").unwrap(), b"new\n");
+/// assert!(fs.contents("data.db.tmp").is_none());
+/// ```
+pub fn replace_file(fs: &dyn Fs, path: &str, text: &str) -> io::Result<()> {
+    let tmp_path = sidecar_path(path, "tmp");
+    let written = fs.create(&tmp_path)
+        .and_then(|()| fs.append(&tmp_path, text))
+        .and_then(|_| install_file(fs, &tmp_path, path));
+    if written.is_err() {
+        let _ = fs.remove(&tmp_path);
+    }
+    written
+}
+
+
+/// Like [`replace_file`], with `bytes` written as they are: for a file
+/// of bytes that are already a log's (or any file's), such as a backup
+/// being restored.
+///
+/// # Example
+/// ```
+/// use kvstore::{replace_file_bytes, Fs, MemFs};
+/// let fs = MemFs::new();
+/// replace_file_bytes(&fs, "data.db", b"KVSTORE 5\n").unwrap();
+/// assert_eq!(fs.contents("data.db").unwrap(), b"KVSTORE 5\n");
+/// ```
+pub fn replace_file_bytes(fs: &dyn Fs, path: &str, bytes: &[u8]) -> io::Result<()> {
+    let tmp_path = sidecar_path(path, "tmp");
+    let written = fs.create(&tmp_path)
+        .and_then(|()| fs.append_raw(&tmp_path, bytes))
+        .and_then(|_| install_file(fs, &tmp_path, path));
+    if written.is_err() {
+        let _ = fs.remove(&tmp_path);
+    }
+    written
+}
+
+
+/// Make the fully written `tmp_path` durable and rename it over `path`,
+/// then sync the directory so the rename itself survives a crash.
+///
+/// For files written in several steps, such as a compaction's output;
+/// [`replace_file`] covers the single-write case.
+pub fn install_file(fs: &dyn Fs, tmp_path: &str, path: &str) -> io::Result<()> {
+    fs.sync(tmp_path)?;
+    fs.rename(tmp_path, path)?;
+    fs.sync_dir(path)
+}
+
+
+/// Bytes of text [`LineSink`] collects before appending them.
+const SINK_BATCH: usize = 1024 * 1024;
+
+
+/// Lines streamed into a file a batch at a time, so rewriting a log
+/// holds at most one batch in memory and costs one append (and sync)
+/// per batch rather than per line.
+struct LineSink<'a> {
+    fs: &'a dyn Fs,
+    path: &'a str,
+    batch: String,
+    lines: usize,
+}
+
+
+impl<'a> LineSink<'a> {
+    /// Start the file at `path` over, empty.
+    fn create(fs: &'a dyn Fs, path: &'a str) -> io::Result<Self> {
+        fs.create(path)?;
+        Ok(LineSink { fs, path, batch: String::new(), lines: 0 })
+    }
+
+
+    /// Add `line` (without its newline).
+    fn push(&mut self, line: &str) -> io::Result<()> {
+        if self.lines > 0 {
+            self.batch.push('\n');
+        }
+        self.batch.push_str(line);
+        self.lines += 1;
+        if self.batch.len() >= SINK_BATCH {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+
+    /// Append the lines still collected; each ends in a newline.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.lines > 0 {
+            self.fs.append(self.path, &self.batch)?;
+            self.batch.clear();
+            self.lines = 0;
+        }
+        Ok(())
+    }
+}
+
+
+/// The lines of a file read front to back, one at a time.
+struct RawLines {
+    reader: Box<dyn BufRead + Send>,
+
+    /// Offset of the next line.
+    offset: u64,
+    line: Vec<u8>,
+}
+
+
+impl RawLines {
+    /// Read the file at `path` from its start.
+    ///
+    /// # Returns
+    /// `Ok(None)` if it does not exist.
+    fn open(fs: &dyn Fs, path: &str) -> io::Result<Option<RawLines>> {
+        match fs.reader(path) {
+            Ok(reader) => Ok(Some(RawLines { reader, offset: 0, line: Vec::new() })),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+
+    /// The next line with its newline, if it has one, and its offset.
+    fn next_line(&mut self) -> io::Result<Option<(u64, &[u8])>> {
+        self.line.clear();
+        if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        self.offset += self.line.len() as u64;
+        Ok(Some((offset, &self.line)))
+    }
+}
+
+
+/// Format version of the log at `path`, read from its first line that
+/// holds anything; 0 without a header.
+///
+/// # Returns
+/// * `Ok(None)` if the file is missing or blank.
+/// * `Err(io::Error)` of kind `InvalidData` for a format newer than this
+///   build's, or any error from reading it.
+fn file_version(fs: &dyn Fs, path: &str) -> io::Result<Option<u32>> {
+    let Some(mut lines) = RawLines::open(fs, path)? else {
+        return Ok(None);
+    };
+    while let Some((_, line)) = lines.next_line()? {
+        if !std::str::from_utf8(line).is_ok_and(|l| trim_record(l).is_empty()) {
+            return line_version(line).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+
+/// Offset just past the last byte of the log at `path` that is not
+/// zero padding left by preallocation.
+fn data_end_with(fs: &dyn Fs, path: &str) -> io::Result<u64> {
+    const CHUNK: u64 = 64 * 1024;
+    let mut end = fs.end(path)?;
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK);
+        let chunk = fs.read_at(path, start, (end - start) as usize)?;
+        if let Some(pos) = chunk.iter().rposition(|&b| b != 0) {
+            return Ok(start + pos as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+
+/// Move everything in the log at `path` from byte `cut_at` (a line or
+/// frame start) on into `moved_path`, and cut the log off there.
+///
+/// Both files are streamed a batch of lines at a time, or for a binary
+/// log copied a batch of bytes at a time. The log is rewritten to
+/// `<path>.tmp` and installed with [`install_file`], so a crash leaves
+/// it whole or cut, never a mix.
+///
+/// # Returns
+/// The number of bytes moved.
+fn cut_log(fs: &dyn Fs, path: &str, cut_at: u64, moved_path: &str) -> io::Result<u64> {
+    let end = data_end_with(fs, path)?.max(cut_at);
+    let binary = layout_with(fs, path)? == Layout::Binary;
+    let copy = |start: u64, end: u64, to: &str| -> io::Result<()> {
+        if binary {
+            return copy_bytes(fs, path, start, end, to);
+        }
+        let mut sink = LineSink::create(fs, to)?;
+        copy_lines(fs, path, start, end, &mut sink)?;
+        sink.flush()
+    };
+    copy(cut_at, end, moved_path)?;
+    fs.sync(moved_path)?;
+
+    let tmp_path = sidecar_path(path, "tmp");
+    let kept = copy(0, cut_at, &tmp_path);
+    if let Err(e) = kept.and_then(|()| install_file(fs, &tmp_path, path)) {
+        let _ = fs.remove(&tmp_path);
+        return Err(e);
+    }
+    Ok(end - cut_at)
+}
+
+
+/// Copy bytes `start` to `end` of `path` into a new file at `to`, a
+/// batch at a time.
+fn copy_bytes(fs: &dyn Fs, path: &str, start: u64, end: u64, to: &str) -> io::Result<()> {
+    fs.create(to)?;
+    let mut at = start;
+    while at < end {
+        let len = (end - at).min(SINK_BATCH as u64);
+        fs.append_raw(to, &fs.read_at(path, at, len as usize)?)?;
+        at += len;
+    }
+    Ok(())
+}
+
+
+/// Stream the lines of `path` between bytes `start` and `end` into `sink`.
+fn copy_lines(fs: &dyn Fs, path: &str, start: u64, end: u64, sink: &mut LineSink) -> io::Result<()> {
+    let Some(mut lines) = RawLines::open(fs, path)? else {
+        return Ok(());
+    };
+    while let Some((offset, line)) = lines.next_line()? {
+        if offset >= end {
+            break;
+        }
+        if offset >= start {
+            let line = &line[..line.len().min((end - offset) as usize)];
+            sink.push(String::from_utf8_lossy(line).trim_end_matches('\n'))?;
+        }
+    }
+    Ok(())
+}
+
+/// Append-position tracking handle for one log file.
+///
+/// Keeps the file open between appends, remembers the logical end of the
+/// log (`offset`), and extends the physical file in [`PREALLOC_CHUNK`]
+/// steps so most appends neither reopen, seek, nor grow the file.
+#[derive(Debug)]
+pub struct LogWriter {
+    file: File,
+    /// The log's path, for finding its injected crash.
+    #[cfg(any(test, feature = "fault-injection"))]
+    path: String,
+    /// Next append position (end of real data).
+    offset: u64,
+    /// Physical file length, including zero-filled preallocation.
+    allocated: u64,
+    /// How appends are made durable.
+    sync: SyncMode,
+    /// When appends are made durable.
+    durability: Durability,
+    /// `true` while appends are written but not yet synced.
+    pending: bool,
+    /// When the log was last synced (or opened).
+    last_sync: Instant,
+    /// How records are written, from the log's header; `None` until the
+    /// log holds something.
+    layout: Option<Layout>,
+}
+
+
+impl LogWriter {
+    /// Open (or create) a log file positioned at the end of its real data.
+    ///
+    /// Trailing zero bytes left over from an earlier preallocation are
+    /// treated as free space and will be overwritten by the next append.
+    /// Uses the process-wide [`sync_mode`] and [`durability`].
+    pub fn open(filename: &str) -> io::Result<Self> {
+        Self::open_with(filename, sync_mode())
+    }
+
+    /// Open a log file with an explicit sync mode.
+    pub fn open_with(filename: &str, sync: SyncMode) -> io::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.create(true).truncate(false).read(true).write(true);
+
+        let sync = match (sync, O_DSYNC) {
+            (SyncMode::Dsync, None) => SyncMode::Data,
+            (mode, _) => mode,
+        };
+        #[cfg(unix)]
+        if let (SyncMode::Dsync, Some(flag)) = (sync, O_DSYNC) {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.custom_flags(flag);
+        }
+
+        let mut file = options.open(filename)?;
+        let allocated = file.metadata()?.len();
+        let offset = data_end(&mut file, allocated)?;
+        let layout = file_layout(&mut file, offset)?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        Ok(Self {
+            file,
+            #[cfg(any(test, feature = "fault-injection"))]
+            path: filename.to_string(),
+            offset,
+            allocated,
+            sync,
+            durability: Durability::Always,
+            pending: false,
+            last_sync: Instant::now(),
+            layout,
+        }
+        .with_durability(durability()))
+    }
+
+    /// Use `policy` for the appends made through this handle.
+    pub fn with_durability(mut self, policy: Durability) -> Self {
+        if let Durability::EveryMillis(_) = policy {
+            start_flusher();
+        }
+        self.durability = policy;
+        self
+    }
+
+    /// The sync mode this handle uses.
+    pub fn sync(&self) -> SyncMode {
+        self.sync
+    }
+
+    /// The durability policy this handle uses.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// `true` if appends were written but not yet synced.
+    pub fn has_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// How the log's records are written, once it holds anything.
+    pub fn layout(&self) -> Option<Layout> {
+        self.layout
+    }
+
+    /// Append one line, and flush it to disk when the durability policy
+    /// says a sync is due. In a binary log each sealed record in it is
+    /// written as its frame (see [`Layout`]).
+    ///
+    /// # Returns
+    /// The byte offset at which the new record starts.
+    pub fn append(&mut self, input_data: &str) -> io::Result<u64> {
+        self.append_many(&[input_data]).map(|offsets| offsets[0])
+    }
+
+    /// Append `bytes` as they are, such as a log's frames being copied.
+    ///
+    /// # Returns
+    /// The byte offset at which they start.
+    pub fn append_raw(&mut self, bytes: &[u8]) -> io::Result<u64> {
+        let start = self.write(bytes)?;
+        if self.layout.is_none() {
+            self.layout = head_layout(bytes);
+        }
+        Ok(start)
+    }
+
+    /// Write `record` at the end of the log, growing the file as needed.
+    fn write(&mut self, record: &[u8]) -> io::Result<u64> {
+        let start = self.offset;
+        let end = start + record.len() as u64;
+
+        // Grow in whole chunks so the file size changes rarely
+        let grew = end > self.allocated;
+        if grew {
+            let chunks = (end - self.allocated).div_ceil(PREALLOC_CHUNK);
+            self.allocated += chunks * PREALLOC_CHUNK;
+            self.file.set_len(self.allocated)?;
+        }
+
+        // A simulated crash keeps what was written and then fails
+        if let Some(cut) = self.crash_point(record.len()) {
+            self.file.write_all(&record[..cut])?;
+            self.offset = start + cut as u64;
+            return Err(io::Error::other("injected crash"));
+        }
+
+        // The cursor already sits at `offset`; no seek needed
+        self.file.write_all(record)?;
+        // O_DSYNC writes are durable, but a new length is not
+        self.pending |= grew || self.sync != SyncMode::Dsync;
+        if self.durability.sync_due(self.last_sync) {
+            self.sync_pending()?;
+        }
+
+        self.offset = end;
+        Ok(start)
+    }
+
+    /// Append several lines with one write and at most one sync, so a
+    /// batch costs the same fsync as a single record.
+    ///
+    /// # Returns
+    /// The byte offset at which each record starts; empty (and nothing
+    /// written) if `records` is.
+    pub fn append_many<S: AsRef<str>>(&mut self, records: &[S]) -> io::Result<Vec<u64>> {
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+        // The first records written to a log decide its layout
+        let layout = self.layout.or_else(|| first_layout(records));
+        let (bytes, offsets) = encode_records(layout.unwrap_or_default(), records)?;
+        let start = self.write(&bytes)?;
+        self.layout = layout;
+        Ok(offsets.into_iter().map(|offset| start + offset).collect())
+    }
+
+    /// Flush the appends not yet synced to disk.
+    pub fn sync_pending(&mut self) -> io::Result<()> {
+        self.last_sync = Instant::now();
+        if !self.pending {
+            return Ok(());
+        }
+        // Flushing will write data - reduces data loss
+        let mut span = crate::telemetry::span("kvstore.fsync");
+        span.attr("kvstore.sync_mode", self.sync.name()).attr("kvstore.durability", self.durability.name());
+        let synced = match self.sync {
+            SyncMode::All => self.file.sync_all(),
+            SyncMode::Data | SyncMode::Dsync => self.file.sync_data(),
+        };
+        if let Err(e) = &synced {
+            span.error(&e.to_string());
+        }
+        synced?;
+        self.pending = false;
+        Ok(())
+    }
+
+    /// Flush pending appends if this handle syncs on an interval and
+    /// the interval has passed.
+    pub fn sync_if_due(&mut self) -> io::Result<()> {
+        let due = matches!(self.durability, Durability::EveryMillis(_)) && self.durability.sync_due(self.last_sync);
+        if self.pending && due {
+            return self.sync_pending();
+        }
+        Ok(())
+    }
+
+    /// Logical length of the log (bytes of real data).
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Trim unused preallocated space and flush.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.allocated != self.offset {
+            self.file.set_len(self.offset)?;
+            self.allocated = self.offset;
+        }
+        self.pending = false;
+        self.file.sync_all()
+    }
+}
+
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+
+/// Bytes each log may still have written before its injected crash.
+#[cfg(any(test, feature = "fault-injection"))]
+fn crash_budgets() -> &'static Mutex<HashMap<String, u64>> {
+    static BUDGETS: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+    BUDGETS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+
+/// Simulate a crash of the log at `filename` once `after` more bytes have
+/// been appended to it (`None` disarms it). For durability tests only;
+/// built with `--features fault-injection`.
+///
+/// The append that crosses the limit writes only the bytes up to it and
+/// fails, as does every append after it until the crash is disarmed. The
+/// partial record stays in the file like a torn write, for
+/// [`recover_log`] and [`verify_replay_prefix`] to check.
+///
+/// # Example
+/// ```
+/// use kvstore::{append_write, close_log, inject_crash, replay_log};
+/// let path = std::env::temp_dir().join("kvstore_crash_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "").unwrap();
+/// inject_crash(file, Some(10));
+/// append_write(file, "SET a 1").unwrap();
+/// assert!(append_write(file, "SET b 2").is_err());
+/// inject_crash(file, None);
+/// close_log(file).unwrap();
+/// assert_eq!(std::fs::read_to_string(file).unwrap(), "SET a 1\nSE");
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+#[cfg(any(test, feature = "fault-injection"))]
+pub fn inject_crash(filename: &str, after: Option<u64>) {
+    let mut budgets = crash_budgets().lock().unwrap_or_else(|e| e.into_inner());
+    match after {
+        Some(bytes) => budgets.insert(filename.to_string(), bytes),
+        None => budgets.remove(filename),
+    };
+}
+
+
+impl LogWriter {
+    /// How much of a `len`-byte append lands before the injected crash,
+    /// or `None` if all of it does.
+    #[cfg(any(test, feature = "fault-injection"))]
+    fn crash_point(&self, len: usize) -> Option<usize> {
+        let mut budgets = crash_budgets().lock().unwrap_or_else(|e| e.into_inner());
+        let left = budgets.get_mut(&self.path)?;
+        if len as u64 <= *left {
+            *left -= len as u64;
+            return None;
+        }
+        let cut = *left as usize;
+        *left = 0;
+        Some(cut)
+    }
+
+    #[cfg(not(any(test, feature = "fault-injection")))]
+    #[inline]
+    fn crash_point(&self, _len: usize) -> Option<usize> {
+        None
+    }
+}
+
+
+/// Longest start of a log read to find its layout: room for blank lines
+/// ahead of the header.
+const HEAD_LEN: u64 = 4096;
+
+
+/// The layout of the log open as `file`, whose data ends at `end`.
+pub(crate) fn file_layout(file: &mut File, end: u64) -> io::Result<Option<Layout>> {
+    let mut head = vec![0; end.min(HEAD_LEN) as usize];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut head)?;
+    Ok(head_layout(&head))
+}
+
+
+/// Find the end of real data by skipping trailing zero padding.
+pub(crate) fn data_end(file: &mut File, len: u64) -> io::Result<u64> {
+    let mut end = len;
+    let mut buf = [0u8; 4096];
+
+    while end > 0 {
+        let start = end.saturating_sub(buf.len() as u64);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+
+        if let Some(pos) = chunk.iter().rposition(|b| *b != 0) {
+            return Ok(start + pos as u64 + 1);
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+
+/// Sync every cached log whose [`Durability::EveryMillis`] interval has
+/// passed with appends still pending.
+///
+/// # Returns
+/// The first sync error; the other logs are still synced.
+pub fn sync_due_logs() -> io::Result<()> {
+    FileBackend::process().sync_due()
+}
+
+
+/// Start the thread that syncs [`Durability::EveryMillis`] logs left
+/// pending by a quiet spell; only the first call starts it.
+fn start_flusher() {
+    static FLUSHER: OnceLock<()> = OnceLock::new();
+    FLUSHER.get_or_init(|| {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(FLUSH_TICK);
+            if let Err(e) = sync_due_logs() {
+                eprintln!("fsync failed: {}", e);
+            }
+        });
+    });
+}
+
+
+/// Close the cached handle for a log file, trimming its preallocation.
+///
+/// Must be called before the file is replaced or truncated by anything
+/// other than [`append_write`]; the next append reopens it.
+pub fn close_log(filename: &str) -> io::Result<()> {
+    FileBackend::process().close(filename)
+}
+
+
+/// Close every cached log handle (called on shutdown).
+pub fn close_all_logs() -> io::Result<()> {
+    FileBackend::process().close_all()
+}
+
+
+/// Exclusive advisory lock on a log, held for as long as this value lives.
+///
+/// The lock is taken on a `<log>.lock` file next to the log rather than
+/// the log itself, because compaction replaces the log file and a lock on
+/// the old file would silently stop protecting the new one.
+#[derive(Debug)]
+pub struct LogLock {
+    /// Open lock file; closing it releases the lock.
+    file: File,
+
+    /// Path of the lock file.
+    path: String,
+}
+
+
+impl LogLock {
+    /// Take the lock for `filename` without waiting.
+    ///
+    /// # Returns
+    /// * `Ok(lock)` once this process owns the log.
+    /// * `Err(io::Error)` of kind `WouldBlock` if another process holds
+    ///   it, or any error from opening the lock file.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::LogLock;
+    /// let path = std::env::temp_dir().join("doctest_lock.db");
+    /// let path = path.to_str().unwrap();
+    ///
+    /// let lock = LogLock::acquire(path).unwrap();
+    /// assert!(LogLock::acquire(path).is_err()); // already held
+    /// drop(lock);
+    /// assert!(LogLock::acquire(path).is_ok());
+    /// # std::fs::remove_file(format!("{}.lock", path)).unwrap();
+    /// ```
+    pub fn acquire(filename: &str) -> io::Result<Self> {
+        let path = sidecar_path(filename, "lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Self { file, path }),
+            Err(fs::TryLockError::WouldBlock) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("{} is in use by another kvstore process ({} is locked)", filename, path),
+            )),
+            Err(fs::TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+
+/// Append a single command to the persistent log file.
+///
+/// Each command is written on its own line with a trailing newline.
+/// The file is created if it does not exist. Its handle is kept open
+/// by the [`FileBackend`] and reused by later appends (see [`LogWriter`]).
+/// Data is flushed to disk as the [`durability`] policy says, by default
+/// immediately to reduce the chance of loss.
+///
+/// # Arguments
+/// * `filename` - The path of the log file (e.g. `data.db`).
+/// * `input_data` - The raw command string (e.g. `"SET dog bark"`).
+///
+/// # Returns
+/// * `Ok(())` if the line was successfully written and flushed.
+/// * `Err(io::Error)` if the file could not be opened or written to.
+///
+/// # Example
+/// ```
+/// use kvstore::append_write;
+/// let path = std::env::temp_dir().join("kvstore_append_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "").unwrap();
+/// append_write(file, "SET dog bark").unwrap();
+/// let contents = std::fs::read_to_string(file).unwrap();
+/// assert!(contents.contains("SET dog bark"));
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+pub fn append_write(filename: &str, input_data: &str) -> io::Result<()> {
+    append_write_at(filename, input_data).map(|_| ())
+}
+
+
+/// Append a single command to the log and report where it landed.
+///
+/// Behaves exactly like [`append_write`], but returns the byte offset at
+/// which the new record starts. Memory-limited sessions keep this offset
+/// so an evicted value can be read back later with [`read_value_at`].
+///
+/// # Example
+/// ```
+/// use kvstore::append_write_at;
+/// let path = std::env::temp_dir().join("kvstore_offsets_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "").unwrap();
+/// assert_eq!(append_write_at(file, "SET a 1").unwrap(), 0);
+/// assert_eq!(append_write_at(file, "SET b 2").unwrap(), 8);
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+pub fn append_write_at(filename: &str, input_data: &str) -> io::Result<u64> {
+    FileBackend::process().append_line(filename, input_data)
+}
+
+
+/// Append several commands to the log as one write and one sync.
+///
+/// Behaves like calling [`append_write_at`] for each record, except the
+/// batch shares a single `write` and (under [`Durability::Always`]) a
+/// single fsync. A crash can still keep only a prefix of the batch; load
+/// cuts the torn record off as usual.
+///
+/// # Returns
+/// The byte offset at which each record starts.
+///
+/// # Example
+/// ```
+/// use kvstore::append_many;
+/// let path = std::env::temp_dir().join("kvstore_many_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "").unwrap();
+/// assert_eq!(append_many(file, &["SET a 1", "SET bb 2"]).unwrap(), vec![0, 8]);
+/// assert_eq!(append_many(file, &["SET c 3"]).unwrap(), vec![17]);
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+pub fn append_many<S: AsRef<str>>(filename: &str, records: &[S]) -> io::Result<Vec<u64>> {
+    FileBackend::process().append_lines(filename, records)
+}
+
+
+/// Append `bytes` to the log as they are, such as frames copied from
+/// another binary log.
+///
+/// # Returns
+/// The byte offset at which they start.
+pub fn append_raw(filename: &str, bytes: &[u8]) -> io::Result<u64> {
+    FileBackend::process().append_bytes(filename, bytes)
+}
+
+
+/// Where each of `records` starts when they are appended as one
+/// newline-separated text beginning at `start`.
+pub(crate) fn record_offsets<S: AsRef<str>>(start: u64, records: &[S]) -> Vec<u64> {
+    let mut offset = start;
+    records
+        .iter()
+        .map(|record| {
+            let at = offset;
+            offset += record.as_ref().len() as u64 + 1;
+            at
+        })
+        .collect()
+}
+
+
+/// Byte offset just past the last record in the log.
+///
+/// Uses the cached writer when one is open, otherwise skips any zero
+/// padding left at the end of the file. A missing file has length zero.
+pub fn log_end(filename: &str) -> io::Result<u64> {
+    FileBackend::process().end(filename)
+}
+
+
+/// Replay the contents of a persistent log file into memory.
+///
+/// Reads the file line by line (`\n` or `\r\n`), collecting each
+/// command string into a vector, with its checksum stripped. This function
+/// is typically called on startup to rebuild the in-memory index from
+/// durable state.
+///
+/// # Arguments
+/// * `filename` - The path of the log file (e.g. `data.db`).
+///
+/// # Returns
+/// * `Ok(Vec<String>)` containing all non-empty lines, in order.
+/// * `Err(io::Error)` if the file could not be opened or read.
+///
+/// # Example
+/// ```
+/// use kvstore::{append_write, replay_log};
+///
+/// // Write some SET commands to a temporary file
+/// let path = std::env::temp_dir().join("kvstore_replay_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "").unwrap();
+/// append_write(file, "SET dog bark").unwrap();
+/// append_write(file, "SET cat meow").unwrap();
+///
+/// // Replay the log back into memory
+/// let records = replay_log(file).unwrap();
+/// assert_eq!(records, vec!["SET dog bark", "SET cat meow"]);
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+pub fn replay_log(filename: &str) -> io::Result<Vec<String>> {
+    let records = replay_records(&RealFs, filename, 0)?;
+    Ok(records.into_iter().map(|(_, record)| record).collect())
+}
+
+
+/// Replay the log, keeping the byte offset of every record.
+///
+/// Same filtering rules as [`replay_log`] (blank lines are skipped and
+/// records are trimmed), but each entry is paired with the offset where
+/// its line starts so callers can seek straight back to it.
+///
+/// # Returns
+/// * `Ok(Vec<(u64, String)>)` of `(offset, record)` pairs, in order.
+/// * `Ok(vec![])` if the file does not exist yet.
+pub fn replay_log_with_offsets(filename: &str) -> io::Result<Vec<(u64, String)>> {
+    replay_log_from(filename, 0)
+}
+
+
+/// Replay only the records that start at or after byte `start`.
+///
+/// Compaction uses this to pick up writes that landed in the log while
+/// it was copying live keys. `start` must be a record boundary.
+pub fn replay_log_from(filename: &str, start: u64) -> io::Result<Vec<(u64, String)>> {
+    replay_records(&RealFs, filename, start)
+}
+
+
+/// Replay the records at or after byte `start` of a log read through `fs`.
+///
+/// Same rules as [`replay_log_from`]: blank lines, zero padding and the
+/// format header are skipped, a `\r\n` line ending counts as `\n`, and
+/// each record comes with the offset of its first byte.
+///
+/// Records are returned without their checksums. From format 2 on, the
+/// replay stops at the first record that fails its checksum (or has
+/// none): that is where a torn write left off, and nothing after it is
+/// trusted. [`recover_log`] cuts such a tail off.
+///
+/// # Returns
+/// * `Ok(records)` in log order; empty if the file does not exist.
+/// * `Err(io::Error)` if the file can't be read, was written by a newer
+///   format, or (before format 2) a record is not UTF-8.
+///
+/// # Example
+/// ```
+/// use kvstore::{replay_records, Fs, MemFs};
+/// let fs = MemFs::new();
+/// fs.append("log", "SET a 1\n\nSET b 2").unwrap();
+/// assert_eq!(replay_records(&fs, "log", 0).unwrap(),
+///            vec![(0, "SET a 1".to_string()), (9, "SET b 2".to_string())]);
+/// assert!(replay_records(&fs, "missing", 0).unwrap().is_empty());
+/// ```
+pub fn replay_records(fs: &dyn Fs, filename: &str, start: u64) -> io::Result<Vec<(u64, String)>> {
+    ReplayIter::open(fs, filename, start)?.collect()
+}
+
+
+/// The records of a log, read one line at a time.
+///
+/// Yields what [`replay_records`] returns, in the same order and by the
+/// same rules, but holds only the current line in memory, so replaying a
+/// multi-gigabyte log takes no more memory than a small one. Files are
+/// read through [`Fs::reader`].
+///
+/// A record that fails its checksum (format 2 on) ends the iteration, and
+/// its offset is kept in [`ReplayIter::torn_at`]. An error (a format newer
+/// than this build's, a failed read, or before format 2 a record that is
+/// not UTF-8) is yielded once and ends it too.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_text, Fs, MemFs, ReplayIter};
+/// let fs = MemFs::new();
+/// fs.append("log", &log_text(&["SET a 1", "DEL a"])).unwrap();
+/// fs.append_raw("log", &[1, 9, 0]).unwrap(); // a frame cut short
+///
+/// let mut records = ReplayIter::open(&fs, "log", 0).unwrap();
+/// assert_eq!(records.next().unwrap().unwrap(), (10, "SET a 1".to_string()));
+/// assert_eq!(records.sealed_line(), log_text(&["SET a 1"]).lines().nth(1).unwrap());
+/// assert_eq!(records.next().unwrap().unwrap().1, "DEL a");
+/// assert!(records.next().is_none());
+/// assert_eq!(records.torn_at(), Some(53));
+/// ```
+pub struct ReplayIter<R = Box<dyn BufRead + Send>> {
+    reader: R,
+
+    /// Offset of the next line or frame.
+    offset: u64,
+
+    /// Records that start before this offset are skipped.
+    start: u64,
+
+    /// Format version of the log; known once the first non-blank line
+    /// (the header, if any) is read.
+    version: Option<u32>,
+
+    /// Past the header of a binary log, so reading frames.
+    framed: bool,
+
+    /// Key encrypted records are decrypted with, from the file system.
+    key: Option<Arc<LogKey>>,
+
+    torn_at: Option<u64>,
+    done: bool,
+
+    /// The last line or frame read.
+    line: Vec<u8>,
+}
+
+
+impl ReplayIter {
+    /// Replay the records at or after byte `start` (a record boundary) of
+    /// the log at `filename`. A missing file has no records.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` if the file exists but can't be opened.
+    pub fn open(fs: &dyn Fs, filename: &str, start: u64) -> io::Result<ReplayIter> {
+        let reader = match fs.reader(filename) {
+            Ok(reader) => reader,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Box::new(io::empty()),
+            Err(e) => return Err(e),
+        };
+        Ok(ReplayIter::from_reader(reader, 0, start, None, fs.log_key()))
+    }
+}
+
+
+impl<R: BufRead> ReplayIter<R> {
+    /// Replay the part of a log that `reader` holds, which starts at byte
+    /// `offset` of the log (a record boundary), skipping records before
+    /// `start`. Without `version`, the log's is read from its header, so
+    /// `offset` must then be 0.
+    pub(crate) fn from_reader(reader: R, offset: u64, start: u64, version: Option<u32>, key: Option<Arc<LogKey>>) -> ReplayIter<R> {
+        // Past its header, a binary log holds nothing but frames
+        let framed = offset > 0 && version.is_some_and(|version| version >= BINARY_SINCE);
+        ReplayIter { reader, offset, start, version, framed, key, torn_at: None, done: false, line: Vec::new() }
+    }
+
+
+    /// Offset of the torn record the replay stopped at, if it did.
+    pub fn torn_at(&self) -> Option<u64> {
+        self.torn_at
+    }
+
+
+    /// The last record yielded as it is in the log: still sealed, and
+    /// stamped and encrypted if it was written that way. A binary
+    /// record is given as the text line it was appended as.
+    pub fn sealed_line(&self) -> Cow<'_, str> {
+        if !self.framed {
+            return Cow::Borrowed(std::str::from_utf8(&self.line).map_or("", trim_record));
+        }
+        match frame_at(&self.line) {
+            Framed::Whole(_, record, stamp) => Cow::Owned(sealed_frame(&record, stamp)),
+            Framed::Cut | Framed::Bad => Cow::Borrowed(""),
+        }
+    }
+
+
+    /// The next line (or in a binary log, frame) and the offset it
+    /// starts at; `None` at the end of the log.
+    fn next_line(&mut self) -> io::Result<Option<(u64, Line<'_>)>> {
+        if self.framed {
+            return self.next_frame();
+        }
+        self.line.clear();
+        if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        self.offset += self.line.len() as u64;
+
+        let version = match self.version {
+            Some(version) => version,
+            None if self.line.iter().all(u8::is_ascii_whitespace) => return Ok(Some((offset, Line::Skip))),
+            None => *self.version.insert(line_version(&self.line)?),
+        };
+        self.framed = version >= BINARY_SINCE && !self.line.iter().all(u8::is_ascii_whitespace);
+        let line = parse_line(&self.line, offset, version >= CHECKSUMS_SINCE, self.key.as_deref())?;
+        Ok(Some((offset, line)))
+    }
+
+
+    /// [`ReplayIter::next_line`] past the header of a binary log.
+    fn next_frame(&mut self) -> io::Result<Option<(u64, Line<'_>)>> {
+        // Frames can't be told apart without reading them, so skip
+        // straight to `start`
+        if self.offset < self.start {
+            self.offset += io::copy(&mut self.reader.by_ref().take(self.start - self.offset), &mut io::sink())?;
+        }
+        let offset = self.offset;
+        let read = read_frame(&mut self.reader, &mut self.line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.offset += read as u64;
+        if self.line.is_empty() {
+            return Ok(Some((offset, Line::Skip)));
+        }
+        let line = match frame_at(&self.line) {
+            Framed::Whole(_, record, stamp) => {
+                let record = crypt::decrypt_record(self.key.as_deref(), &record, stamp)?.into_owned();
+                Line::Record(offset, Cow::Owned(record), stamp)
+            }
+            Framed::Cut | Framed::Bad => Line::Torn,
+        };
+        Ok(Some((offset, line)))
+    }
+
+
+    fn next_record(&mut self) -> io::Result<Option<(u64, String)>> {
+        let start = self.start;
+        let torn_at = loop {
+            match self.next_line()? {
+                None => return Ok(None),
+                Some((offset, _)) if offset < start => {}
+                Some((_, Line::Record(at, record, _))) => return Ok(Some((at, record.into_owned()))),
+                Some((_, Line::Skip)) => {}
+                Some((offset, Line::Torn)) => break offset,
+            }
+        };
+        self.torn_at = Some(torn_at);
+        Ok(None)
+    }
+}
+
+
+impl<R: BufRead> Iterator for ReplayIter<R> {
+    type Item = io::Result<(u64, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_record().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+
+/// Records parsed from a log, and the offset of the first damaged one if
+/// parsing stopped there.
+pub(crate) type ParsedRecords = (Vec<(u64, String)>, Option<u64>);
+
+
+/// Split log bytes that start at offset `start` (0, or a record boundary
+/// past the header) of a log of format `version` into records, as
+/// [`replay_records`] does for a whole file.
+///
+/// From format 2 every record must pass its checksum; before, a valid
+/// checksum is still stripped, and any record is taken. Encrypted
+/// records are decrypted with `key`.
+pub(crate) fn parse_records(tail: &[u8], start: u64, version: u32, key: Option<Arc<LogKey>>) -> io::Result<ParsedRecords> {
+    let mut records = ReplayIter::from_reader(tail, start, start, Some(version), key);
+    let out = records.by_ref().collect::<io::Result<_>>()?;
+    Ok((out, records.torn_at()))
+}
+
+
+/// How many of `bytes`, which start at offset `start` of a log laid out
+/// as `layout`, hold whole lines or frames: the rest is a record still
+/// being written.
+pub(crate) fn whole_records(bytes: &[u8], start: u64, layout: Layout) -> usize {
+    let whole_lines = |bytes: &[u8]| bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    if layout == Layout::Text {
+        return whole_lines(bytes);
+    }
+    let mut at = 0;
+    if start == 0 {
+        // The header line, once it is whole
+        at = match bytes.iter().position(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return 0,
+        };
+    }
+    while at < bytes.len() {
+        if bytes[at] == 0 {
+            at += 1;
+            continue;
+        }
+        match frame_at(&bytes[at..]) {
+            Framed::Whole(len, ..) => at += len,
+            // Damage is for the reader to find; all that matters is
+            // where to stop
+            Framed::Bad => return bytes.len(),
+            Framed::Cut => break,
+        }
+    }
+    at
+}
+
+
+/// What one line of a log holds.
+enum Line<'a> {
+    /// Nothing: a blank line, zero padding or the header.
+    Skip,
+
+    /// A record without its checksum (decrypted if it was written
+    /// encrypted), the offset of its first byte, and its stamp (see
+    /// [`seal_record_at`]).
+    Record(u64, Cow<'a, str>, Option<Stamp>),
+
+    /// A record that fails its checksum, where a torn write left off.
+    Torn,
+}
+
+
+/// Parse the raw line that starts at `offset`, with or without
+/// checksums and decrypting with `key` as for [`parse_records`].
+///
+/// An encrypted record that does not decrypt is an error, not a torn
+/// write: its checksum matched, so the key is missing or wrong.
+fn parse_line<'a>(raw: &'a [u8], offset: u64, checked: bool, key: Option<&LogKey>) -> io::Result<Line<'a>> {
+    let line = match std::str::from_utf8(raw) {
+        Ok(line) => line,
+        Err(_) if checked => return Ok(Line::Torn),
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    };
+    let trimmed = trim_record(line);
+    if trimmed.is_empty() || (offset == 0 && parse_header(trimmed).is_some()) {
+        return Ok(Line::Skip);
+    }
+    let (record, stamp) = match unseal_stamped(trimmed) {
+        Some(unsealed) => unsealed,
+        None if checked => return Ok(Line::Torn),
+        None => (trimmed, None),
+    };
+    // Point at the first byte of the record itself
+    let lead = line.len() - line.trim_start_matches(|c: char| c == '\0' || c.is_whitespace()).len();
+    Ok(Line::Record(offset + lead as u64, crypt::decrypt_record(key, record, stamp)?, stamp))
+}
+
+
+/// Add the checksum every record carries on disk: a tab, then the
+/// record's CRC-32 as eight hex digits.
+///
+/// Escaped fields never hold a raw tab, so the checksum can always be
+/// told apart from the record. A session with a key encrypts the record
+/// as it is appended (see [`crate::crypt::EncryptedFs`]) and seals it
+/// again, so the checksum covers the encrypted form.
+///
+/// # Example
+/// ```
+/// use kvstore::{seal_record, unseal_record};
+/// let sealed = seal_record("SET a 1");
+/// assert_eq!(sealed.len(), "SET a 1".len() + 9);
+/// assert_eq!(unseal_record(&sealed), Some("SET a 1"));
+/// assert_eq!(unseal_record(&sealed.replace("a 1", "a 2")), None);
+/// ```
+pub fn seal_record(record: &str) -> String {
+    format!("{}\t{:08x}", record, crc32(record.as_bytes()))
+}
+
+
+/// When a record was appended: its place in the log and the wall-clock
+/// time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    /// Sequence number, one higher than the record appended before it
+    /// (0 for records stamped before format 4).
+    pub seq: u64,
+
+    /// Unix time in milliseconds.
+    pub unix_ms: u64,
+}
+
+
+impl fmt::Display for Stamp {
+    /// `<seq>@<unix ms>`, as the stamp is written in the log.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.seq, self.unix_ms)
+    }
+}
+
+
+impl Stamp {
+    /// Parse a stamp as written in the log; a bare time is a format 3
+    /// stamp, with sequence number 0.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once('@') {
+            Some((seq, unix_ms)) => Some(Self { seq: parse_digits(seq)?, unix_ms: parse_digits(unix_ms)? }),
+            None => Some(Self { seq: 0, unix_ms: parse_digits(text)? }),
+        }
+    }
+}
+
+
+fn parse_digits(text: &str) -> Option<u64> {
+    text.bytes().all(|b| b.is_ascii_digit()).then(|| text.parse().ok()).flatten()
+}
+
+
+/// Like [`seal_record`], also stamping the record with its sequence
+/// number and the time it is appended: a tab and the stamp go between
+/// the record and the checksum, which covers both. [`recover_to`] cuts
+/// the log by these.
+///
+/// # Example
+/// ```
+/// use kvstore::{seal_record_at, unseal_record, unseal_stamped, Stamp};
+/// let stamp = Stamp { seq: 7, unix_ms: 1_700_000_000_000 };
+/// let sealed = seal_record_at("SET a 1", stamp);
+/// assert!(sealed.starts_with("SET a 1\t7@1700000000000\t"));
+/// assert_eq!(unseal_record(&sealed), Some("SET a 1"));
+/// assert_eq!(unseal_stamped(&sealed), Some(("SET a 1", Some(stamp))));
+/// assert_eq!(unseal_stamped(&sealed.replace("7@", "8@")), None);
+/// ```
+pub fn seal_record_at(record: &str, stamp: Stamp) -> String {
+    let body = format!("{}\t{}", record, stamp);
+    format!("{}\t{:08x}", body, crc32(body.as_bytes()))
+}
+
+
+/// The record inside a sealed line, without its timestamp if it has one.
+///
+/// # Returns
+/// `None` if the line has no checksum or it does not match.
+pub fn unseal_record(line: &str) -> Option<&str> {
+    unseal_stamped(line).map(|(record, _)| record)
+}
+
+
+/// The record inside a sealed line, and its stamp if [`seal_record_at`]
+/// sealed it.
+///
+/// # Returns
+/// `None` if the line has no checksum, it does not match, or the stamp
+/// is malformed.
+pub fn unseal_stamped(line: &str) -> Option<(&str, Option<Stamp>)> {
+    let (body, sum) = line.rsplit_once('\t')?;
+    let sum = u32::from_str_radix(sum, 16).ok().filter(|_| sum.len() == 8)?;
+    if crc32(body.as_bytes()) != sum {
+        return None;
+    }
+    match body.rsplit_once('\t') {
+        Some((record, stamp)) => Some((record, Some(Stamp::parse(stamp)?))),
+        None => Some((body, None)),
+    }
+}
+
+
+/// The plain record inside a sealed line: [`unseal_record`], then
+/// decrypted with `key` if it was written encrypted.
+///
+/// # Returns
+/// * `Ok(None)` if the line has no checksum or it does not match.
+/// * `Err(io::Error)` if the record does not decrypt (see
+///   [`crate::crypt::decrypt_record`]).
+///
+/// # Example
+/// ```
+/// use kvstore::{open_record, seal_record};
+/// assert_eq!(open_record(&seal_record("DEL a"), None).unwrap().as_deref(), Some("DEL a"));
+/// assert!(open_record("ENC AAAA\te22f9def", None).is_err());
+/// ```
+pub fn open_record<'a>(line: &'a str, key: Option<&LogKey>) -> io::Result<Option<Cow<'a, str>>> {
+    unseal_stamped(line).map(|(record, stamp)| crypt::decrypt_record(key, record, stamp)).transpose()
+}
+
+
+/// Check that the newest record of the log at `filename`, if it was
+/// written encrypted, decrypts with the key of `fs` (see
+/// [`Fs::log_key`]).
+///
+/// Only the end of the log is read, back to that record, so a session
+/// opened with a missing or wrong key fails at once rather than after
+/// replaying the log. An older record that does not decrypt fails the
+/// replay itself.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidData` if the key is missing or wrong.
+pub fn check_log_key(fs: &dyn Fs, filename: &str) -> io::Result<()> {
+    let newest = last_line_with(fs, filename, |line| unseal_stamped(line).map(|(record, stamp)| (record.to_string(), stamp)))?;
+    match newest {
+        Some((record, stamp)) => crypt::decrypt_record(fs.log_key().as_deref(), &record, stamp).map(drop),
+        None => Ok(()),
+    }
+}
+
+
+/// The text of a whole data file holding `records`: the header, then
+/// each record sealed, one per line.
+///
+/// There is no final newline; [`Fs::append`] adds it, and under the
+/// binary header frames the records (see [`log_bytes`]).
+///
+/// # Example
+/// ```
+/// use kvstore::{log_text, replay_records, Fs, MemFs};
+/// let fs = MemFs::new();
+/// fs.append("log", &log_text(&["SET a 1", "DEL a"])).unwrap();
+/// let records: Vec<String> = replay_records(&fs, "log", 0).unwrap().into_iter().map(|(_, r)| r).collect();
+/// assert_eq!(records, ["SET a 1", "DEL a"]);
+/// ```
+pub fn log_text<S: AsRef<str>>(records: &[S]) -> String {
+    log_text_after(records, 0)
+}
+
+
+/// Like [`log_text`], with the header of [`header_record_after`]: for
+/// rewriting a log whose records were stamped up to `last_seq`.
+pub fn log_text_after<S: AsRef<str>>(records: &[S], last_seq: u64) -> String {
+    let mut text = header_record_after(last_seq);
+    for record in records {
+        text.push('\n');
+        text.push_str(&seal_record(record.as_ref()));
+    }
+    text
+}
+
+
+/// Strip whitespace and zero padding left by preallocation.
+fn trim_record(line: &str) -> &str {
+    line.trim_matches(|c: char| c == '\0' || c.is_whitespace())
+}
+
+
+/// Build the header record that opens a data file of this format.
+///
+/// # Example
+/// ```
+/// use kvstore::{header_record, parse_header, FORMAT_VERSION};
+/// assert_eq!(parse_header(&header_record()), Some(FORMAT_VERSION));
+/// ```
+pub fn header_record() -> String {
+    format!("KVSTORE {}", FORMAT_VERSION)
+}
+
+
+/// The header of a log rewritten from one whose records were stamped up
+/// to sequence number `last_seq`, so appends to the new log carry on
+/// from there (see [`last_seq`]). Just [`header_record`] for 0.
+///
+/// # Example
+/// ```
+/// use kvstore::{header_record_after, parse_header, FORMAT_VERSION};
+/// assert_eq!(header_record_after(42), format!("KVSTORE {} 42", FORMAT_VERSION));
+/// assert_eq!(parse_header(&header_record_after(42)), Some(FORMAT_VERSION));
+/// ```
+pub fn header_record_after(last_seq: u64) -> String {
+    match last_seq {
+        0 => header_record(),
+        seq => format!("{} {}", header_record(), seq),
+    }
+}
+
+
+/// Read the format version out of a header record.
+///
+/// # Returns
+/// `Some(version)` for a `KVSTORE <version>` record (or one with the
+/// sequence number of [`header_record_after`]), otherwise `None`.
+pub fn parse_header(line: &str) -> Option<u32> {
+    parse_header_seq(line).map(|(version, _)| version)
+}
+
+
+/// The format version and sequence number a header record holds.
+fn parse_header_seq(line: &str) -> Option<(u32, u64)> {
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["KVSTORE", version] => Some((version.parse().ok()?, 0)),
+        ["KVSTORE", version, seq] => Some((version.parse().ok()?, parse_digits(seq)?)),
+        _ => None,
+    }
+}
+
+
+/// What [`migrate_log`] did to a data file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Migration {
+    /// The file was missing or empty and now holds just the header.
+    Created,
+
+    /// The file was rewritten from the given older format version.
+    Upgraded { from: u32 },
+
+    /// The file was already in the current format.
+    Current,
+}
+
+
+/// Bring the data file at `path` up to [`FORMAT_VERSION`].
+///
+/// Called once at startup, before the log is replayed. A new file gets
+/// the header record; an older file is rewritten with the current header
+/// and every record sealed with its checksum and framed (see
+/// [`Layout`]), through a temporary file that replaces the original only
+/// once it is durable. A torn tail can't be framed, so it is cut off
+/// first as [`recover_log`] does; before format 2, where only a last
+/// record with no newline may be a torn write, that record is moved to
+/// `<path>.torn`.
+///
+/// # Returns
+/// * `Ok(Migration)` describing what was done.
+/// * `Err(io::Error)` of kind `InvalidData` if the file was written by a
+///   newer format, or any error from reading or rewriting it.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_bytes, log_text, migrate_log, replay_log, Migration};
+/// let path = std::env::temp_dir().join("kvstore_migrate_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "SET a 1 \nSET b two\\swords\n").unwrap();
+/// assert_eq!(migrate_log(file).unwrap(), Migration::Upgraded { from: 0 });
+/// assert_eq!(std::fs::read(file).unwrap(), log_bytes(&log_text(&["SET a 1", "SET b two\\swords"])).unwrap());
+/// assert_eq!(migrate_log(file).unwrap(), Migration::Current);
+/// assert_eq!(replay_log(file).unwrap(), vec!["SET a 1", "SET b two\\swords"]);
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+pub fn migrate_log(path: &str) -> io::Result<Migration> {
+    migrate_log_with(&RealFs, path)
+}
+
+
+/// Like [`migrate_log`], with the file read and rewritten through `fs`.
+pub fn migrate_log_with(fs: &dyn Fs, path: &str) -> io::Result<Migration> {
+    let Some(version) = file_version(fs, path)? else {
+        fs.create(path)?;
+        fs.append(path, &header_record())?;
+        fs.sync(path)?;
+        return Ok(Migration::Created);
+    };
+    if version == FORMAT_VERSION {
+        return Ok(Migration::Current);
+    }
+
+    if version >= CHECKSUMS_SINCE {
+        recover_log_with(fs, path)?;
+    }
+
+    // Versions 0 and 1: the records gain checksums (and version 0 a
+    // header). Records already sealed, as all of version 2's are, are
+    // kept, and from version 5 every record is framed. The log is
+    // streamed through, so upgrading a big one takes no more memory
+    // than a small one
+    let tmp_path = sidecar_path(path, "tmp");
+    let mut torn = None;
+    let upgraded = LineSink::create(fs, &tmp_path).and_then(|mut upgraded| {
+        upgraded.push(&header_record_after(last_seq(fs, path)?))?;
+        let mut lines = RawLines::open(fs, path)?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))?;
+        while let Some((offset, line)) = lines.next_line()? {
+            let line = std::str::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let record = trim_record(line);
+            if record.is_empty() || (offset == 0 && parse_header(record).is_some()) {
+                continue;
+            }
+            match unseal_record(record) {
+                _ if !line.ends_with('\n') => torn = Some(record.to_string()),
+                Some(_) => upgraded.push(record)?,
+                None => upgraded.push(&seal_record(record))?,
+            }
+        }
+        upgraded.flush()
+    });
+    if let Err(e) = upgraded.and_then(|()| install_file(fs, &tmp_path, path)) {
+        let _ = fs.remove(&tmp_path);
+        return Err(e);
+    }
+    if let Some(torn) = torn {
+        replace_file(fs, &sidecar_path(path, "torn"), &torn)?;
+    }
+    Ok(Migration::Upgraded { from: version })
+}
+
+
+/// Format version of a log's bytes: its header's, or 0 without one.
+///
+/// # Returns
+/// `Err(io::Error)` of kind `InvalidData` for a format newer than this build's.
+pub(crate) fn log_version(body: &[u8]) -> io::Result<u32> {
+    let first = body.split(|&b| b == b'\n').find(|l| !l.iter().all(u8::is_ascii_whitespace)).unwrap_or_default();
+    line_version(first)
+}
+
+
+/// Format version given by the first non-blank line of a log.
+fn line_version(first: &[u8]) -> io::Result<u32> {
+    let version = std::str::from_utf8(first).ok().and_then(|l| parse_header(trim_record(l))).unwrap_or(0);
+    if version > FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("data file format {} is newer than supported format {}", version, FORMAT_VERSION),
+        ));
+    }
+    Ok(version)
+}
+
+
+/// Rewrite the log at `path` with only the latest value of each live key.
+///
+/// The offline counterpart of the `COMPACT` command, for a log no session
+/// has open (the caller holds its [`LogLock`]). Deleted keys are dropped,
+/// and the new file, written to a temporary file and synced first,
+/// replaces the old one in a single rename. A torn tail is cut off first,
+/// as [`recover_log`] does, and a checkpoint the log continues is folded
+/// into it (see [`crate::fold_checkpoint`]).
+///
+/// # Returns
+/// * `Ok(n)` with the number of keys kept.
+/// * `Err(io::Error)` of kind `InvalidData` if the file was written by a
+///   newer format, or any error from reading or rewriting it.
+///
+/// # Example
+/// ```
+/// use kvstore::{compact_log, log_bytes, log_text};
+/// let path = std::env::temp_dir().join("kvstore_compact_doc.db");
+/// let file = path.to_str().unwrap();
+/// std::fs::write(file, "KVSTORE 1\nSET a 1\nSET b 2\nSET a 3\nDEL b\n").unwrap();
+/// assert_eq!(compact_log(file).unwrap(), 1);
+/// assert_eq!(std::fs::read(file).unwrap(), log_bytes(&log_text(&["SET a 3"])).unwrap());
+/// # std::fs::remove_file(file).unwrap();
+/// ```
+pub fn compact_log(path: &str) -> io::Result<usize> {
+    compact_log_with(&RealFs, path)
+}
+
+
+/// Like [`compact_log`], with the file read and rewritten through `fs`.
+pub fn compact_log_with(fs: &dyn Fs, path: &str) -> io::Result<usize> {
+    recover_log_with(fs, path)?;
+    crate::checkpoint::fold_checkpoint(fs, path)?;
+    let bytes = fs.read(path)?;
+    let (records, _) = parse_records(&bytes, 0, log_version(&bytes)?, fs.log_key())?;
+
+    // Last write wins, as on replay
+    let mut live = Keyspace::default();
+    for (offset, line) in &records {
+        for op in decode_record(*offset, line) {
+            live.apply(op);
+        }
+    }
+
+    replace_file(fs, path, &log_text_after(&live.records(), last_seq(fs, path)?))?;
+    Ok(live.values.len())
+}
+
+
+/// The live keys a log leaves behind, folded without a session, for
+/// tools that rewrite the log offline.
+#[derive(Debug, Default)]
+pub(crate) struct Keyspace {
+    /// Every live key and its value, in key order.
+    pub values: BTreeMap<String, Vec<u8>>,
+
+    /// Deadlines (Unix ms) of the live keys that have a TTL.
+    pub expirations: HashMap<String, u64>,
+
+    /// Live keys whose value is in the value log; their entry in
+    /// `values` is empty.
+    pub refs: HashMap<String, ValueRef>,
+}
+
+
+impl Keyspace {
+    /// Apply one change the way a session replaying the log would.
+    pub fn apply(&mut self, op: ReplayOp) {
+        match op {
+            ReplayOp::Set(key, value, _) => {
+                self.refs.remove(&key);
+                self.values.insert(key, value);
+            }
+            ReplayOp::SetRef(key, value) => {
+                self.refs.insert(key.clone(), value);
+                self.values.insert(key, Vec::new());
+            }
+            ReplayOp::ExpireAt(key, at) if at <= crate::ttl::unix_now_ms() => {
+                self.values.remove(&key);
+                self.expirations.remove(&key);
+                self.refs.remove(&key);
+            }
+            ReplayOp::ExpireAt(key, at) => {
+                if self.values.contains_key(&key) {
+                    self.expirations.insert(key, at);
+                }
+            }
+            ReplayOp::Del(key) => {
+                self.values.remove(&key);
+                self.expirations.remove(&key);
+                self.refs.remove(&key);
+            }
+            ReplayOp::Persist(key) => {
+                self.expirations.remove(&key);
+            }
+        }
+    }
+
+    /// A `SET` record per key (`VSET` for a value in the value log, which
+    /// stays where it is), each followed by its `EXPIREAT` if any.
+    pub fn records(&self) -> Vec<String> {
+        let mut records = Vec::with_capacity(self.values.len() + self.expirations.len());
+        for (key, value) in &self.values {
+            records.push(match self.refs.get(key) {
+                Some(&value) => LogRecord::SetRef { key: key.clone(), value }.encode(),
+                None => set_record_bytes(key, value),
+            });
+            if let Some(at) = self.expirations.get(key) {
+                records.push(expire_at_record(key, *at));
+            }
+        }
+        records
+    }
+}
+
+
+/// Cut the log at `path` at its first damaged record.
+///
+/// A record that fails its checksum (format 2 on) is where a write was
+/// torn, so it and everything after it are moved to `<path>.torn` for
+/// inspection, and the intact records before it replace the log in a
+/// single rename. Writers run this before appending (loading a session
+/// does), so new records never land behind the damage. A whole last
+/// record missing only its newline gets one, so none land on its line.
+///
+/// # Returns
+/// * `Ok(bytes)` cut off; 0 if the log was intact or does not exist.
+/// * `Err(io::Error)` if it cannot be read or rewritten.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_bytes, log_text, recover_log, replay_log};
+/// let path = std::env::temp_dir().join("kvstore_recover_doc.db");
+/// let file = path.to_str().unwrap();
+/// let good = log_bytes(&log_text(&["SET a 1"])).unwrap();
+/// let torn = &log_bytes(&log_text(&["SET a 1", "SET b 2"])).unwrap()[good.len()..][..20];
+/// std::fs::write(file, [&good[..], torn].concat()).unwrap();
+/// assert_eq!(recover_log(file).unwrap(), 20);
+/// assert_eq!(replay_log(file).unwrap(), vec!["SET a 1"]);
+/// assert_eq!(std::fs::read(format!("{}.torn", file)).unwrap(), torn);
+/// # std::fs::remove_file(file).unwrap();
+/// # std::fs::remove_file(format!("{}.torn", file)).unwrap();
+/// ```
+pub fn recover_log(path: &str) -> io::Result<u64> {
+    recover_log_with(&RealFs, path)
+}
+
+
+/// Like [`recover_log`], with the file read and rewritten through `fs`.
+pub fn recover_log_with(fs: &dyn Fs, path: &str) -> io::Result<u64> {
+    if file_version(fs, path)?.is_none_or(|version| version < CHECKSUMS_SINCE) {
+        return Ok(0);
+    }
+    // Replay streams the log, holding one record at a time
+    let mut records = ReplayIter::open(fs, path, 0)?;
+    for record in records.by_ref() {
+        record?;
+    }
+    let Some(torn_at) = records.torn_at() else {
+        // A last record cut off just before its newline is whole, but the
+        // next append would run on from it and tear it
+        let data_end = data_end_with(fs, path)?;
+        if data_end > 0 && fs.read_at(path, data_end - 1, 1)? != b"\n" {
+            let tmp_path = sidecar_path(path, "tmp");
+            let mut whole = LineSink::create(fs, &tmp_path)?;
+            copy_lines(fs, path, 0, data_end, &mut whole)?;
+            whole.flush()?;
+            install_file(fs, &tmp_path, path)?;
+        }
+        return Ok(0);
+    };
+    cut_log(fs, path, torn_at, &sidecar_path(path, "torn"))
+}
+
+
+/// Check that the log at `path` replays as a prefix of `written`, the
+/// records appended to it (without their checksums) in order, holding at
+/// least the first `acked` of them: the ones whose appends succeeded.
+/// For durability tests after [`inject_crash`]; built with
+/// `--features fault-injection`.
+///
+/// A torn record at the end is not an error; replay stops before it as
+/// it does on load.
+///
+/// # Returns
+/// * `Ok(n)` with the number of records the log replays.
+/// * `Err(io::Error)` of kind `InvalidData` if a record differs from the
+///   one written at its place, the log holds records never written, or an
+///   acknowledged record is missing.
+///
+/// # Example
+/// ```
+/// use kvstore::{log_text, verify_replay_prefix, MemFs, Fs};
+/// let fs = MemFs::new();
+/// fs.append("log", &log_text(&["SET a 1", "SET b 2"])).unwrap();
+/// assert_eq!(verify_replay_prefix(&fs, "log", &["SET a 1", "SET b 2", "SET c 3"], 2).unwrap(), 2);
+/// assert!(verify_replay_prefix(&fs, "log", &["SET a 1", "SET b 2", "SET c 3"], 3).is_err());
+/// assert!(verify_replay_prefix(&fs, "log", &["SET a 1", "SET b 3"], 1).is_err());
+/// ```
+#[cfg(any(test, feature = "fault-injection"))]
+pub fn verify_replay_prefix<S: AsRef<str>>(fs: &dyn Fs, path: &str, written: &[S], acked: usize) -> io::Result<usize> {
+    let bytes = fs.read(path)?;
+    let (records, _) = parse_records(&bytes, 0, log_version(&bytes)?, fs.log_key())?;
+    let inconsistent = |message: String| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+
+    if records.len() > written.len() {
+        return inconsistent(format!("{} records replayed but only {} were written", records.len(), written.len()));
+    }
+    for (n, ((_, record), want)) in records.iter().zip(written).enumerate() {
+        if record != want.as_ref() {
+            return inconsistent(format!("record {} replayed as {:?}, but {:?} was written", n, record, want.as_ref()));
+        }
+    }
+    if records.len() < acked {
+        return inconsistent(format!("{} records replayed but {} were acknowledged", records.len(), acked));
+    }
+    Ok(records.len())
+}
+
+
+/// Roll the log at `path` back to how it stood at `unix_ms` (Unix ms):
+/// point-in-time recovery.
+///
+/// The first record stamped after `unix_ms` (see [`seal_record_at`]) and
+/// everything after it are moved to `<path>.after.<unix_ms>`, and the
+/// records before it replace the log in a single rename. Records written
+/// without a stamp (by a compaction, a checkpoint or a restore) stay
+/// with the stamped records around them, so history a compaction or
+/// checkpoint folded away is not recoverable. Run it on a log no session
+/// has open, then replay the log as usual.
+///
+/// # Returns
+/// * `Ok(n)` with the number of records rolled back; 0 if none were
+///   stamped after `unix_ms` or the log does not exist.
+/// * `Err(io::Error)` if it cannot be read or rewritten.
+///
+/// # Example
+/// ```
+/// use kvstore::{header_record, log_bytes, recover_to, replay_log, seal_record_at, Stamp};
+/// let path = std::env::temp_dir().join("kvstore_recover_to_doc.db");
+/// let file = path.to_str().unwrap();
+/// let at = |seq, unix_ms| Stamp { seq, unix_ms };
+/// let log = [seal_record_at("SET a 1", at(1, 1000)), seal_record_at("SET a 2", at(2, 2000)), seal_record_at("DEL a", at(3, 3000))];
+/// std::fs::write(file, log_bytes(&format!("{}\n{}", header_record(), log.join("\n"))).unwrap()).unwrap();
+/// assert_eq!(recover_to(file, 2500).unwrap(), 1);
+/// assert_eq!(replay_log(file).unwrap(), vec!["SET a 1", "SET a 2"]);
+/// assert_eq!(recover_to(file, 2500).unwrap(), 0);
+/// # std::fs::remove_file(file).unwrap();
+/// # std::fs::remove_file(format!("{}.after.2500", file)).unwrap();
+/// ```
+pub fn recover_to(path: &str, unix_ms: u64) -> io::Result<u64> {
+    recover_to_with(&RealFs, path, unix_ms)
+}
+
+
+/// Like [`recover_to`], with the file read and rewritten through `fs`.
+pub fn recover_to_with(fs: &dyn Fs, path: &str, unix_ms: u64) -> io::Result<u64> {
+    if file_version(fs, path)?.is_none_or(|version| version < TIMESTAMPS_SINCE) {
+        return Ok(0);
+    }
+
+    let mut lines = ReplayIter::open(fs, path, 0)?;
+    let mut cut_at = None;
+    let mut undone = 0;
+    while let Some((offset, line)) = lines.next_line()? {
+        match line {
+            Line::Record(_, _, Some(stamp)) if cut_at.is_none() && stamp.unix_ms > unix_ms => {
+                cut_at = Some(offset);
+                undone += 1;
+            }
+            Line::Record(..) if cut_at.is_some() => undone += 1,
+            Line::Torn => break,
+            _ => {}
+        }
+    }
+    let Some(cut_at) = cut_at else {
+        return Ok(0);
+    };
+    cut_log(fs, path, cut_at, &sidecar_path(path, &format!("after.{}", unix_ms)))?;
+    Ok(undone)
+}
+
+
+/// The highest sequence number stamped into the log at `path`: the next
+/// append is stamped one higher.
+///
+/// Appends are stamped in order, so this reads back from the end of the
+/// log to the last stamped record. A rewritten log with none left falls
+/// back on the number its header carries (see [`header_record_after`]).
+///
+/// # Returns
+/// * `Ok(seq)`; 0 if nothing was ever stamped or the log does not exist.
+/// * `Err(io::Error)` if it cannot be read.
+///
+/// # Example
+/// ```
+/// use kvstore::{header_record_after, last_seq, seal_record, seal_record_at, Fs, MemFs, Stamp};
+/// let fs = MemFs::new();
+/// fs.append("log", &header_record_after(9)).unwrap();
+/// assert_eq!(last_seq(&fs, "log").unwrap(), 9);
+/// fs.append("log", &seal_record_at("SET a 1", Stamp { seq: 10, unix_ms: 1 })).unwrap();
+/// fs.append("log", &seal_record("SET b 2")).unwrap();
+/// assert_eq!(last_seq(&fs, "log").unwrap(), 10);
+/// ```
+pub fn last_seq(fs: &dyn Fs, path: &str) -> io::Result<u64> {
+    let seq = last_line_with(fs, path, |line| match unseal_stamped(line) {
+        Some((_, Some(stamp))) => Some(stamp.seq),
+        _ => parse_header_seq(line).map(|(_, seq)| seq),
+    })?;
+    Ok(seq.unwrap_or(0))
+}
+
+
+/// The first of the lines of the log at `path`, newest first, that `find`
+/// picks something out of (given each line trimmed), and what it picked.
+///
+/// Reads back from the end a chunk at a time, so only as much of the
+/// log is read as lies after that line. A binary log's frames are given
+/// as the sealed lines they were appended as.
+fn last_line_with<T>(fs: &dyn Fs, path: &str, mut find: impl FnMut(&str) -> Option<T>) -> io::Result<Option<T>> {
+    const CHUNK: u64 = 64 * 1024;
+    if layout_with(fs, path)? == Layout::Binary {
+        return last_frame_with(fs, path, find);
+    }
+    let mut pos = fs.end(path)?;
+    // The start of a line the previous (later) chunk cut in two
+    let mut rest = Vec::new();
+    while pos > 0 {
+        let start = pos.saturating_sub(CHUNK);
+        let mut chunk = fs.read_at(path, start, (pos - start) as usize)?;
+        chunk.append(&mut rest);
+        // Unless this is the start of the file, the first line may be cut
+        let whole = match chunk.iter().position(|&b| b == b'\n') {
+            _ if start == 0 => 0,
+            Some(i) => i + 1,
+            None => chunk.len(),
+        };
+        for raw in chunk[whole..].rsplit(|&b| b == b'\n') {
+            let Ok(line) = std::str::from_utf8(raw) else { continue };
+            if let Some(found) = find(trim_record(line)) {
+                return Ok(Some(found));
+            }
+        }
+        chunk.truncate(whole);
+        rest = chunk;
+        pos = start;
+    }
+    Ok(None)
+}
+
+
+/// [`last_line_with`] for a binary log: walks back a frame at a time by
+/// the length each one ends with, and tries the header last. Damage in
+/// the way falls back on reading the frames front to back.
+fn last_frame_with<T>(fs: &dyn Fs, path: &str, mut find: impl FnMut(&str) -> Option<T>) -> io::Result<Option<T>> {
+    let mut lines = RawLines::open(fs, path)?.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))?;
+    let (header_end, header) = loop {
+        let Some((offset, line)) = lines.next_line()? else {
+            return Ok(None);
+        };
+        let text = String::from_utf8_lossy(line);
+        if !trim_record(&text).is_empty() {
+            break (offset + line.len() as u64, trim_record(&text).to_string());
+        }
+    };
+
+    // A frame ends in its checksum, its body length and a newline
+    const TRAILER: u64 = 9;
+    let mut pos = data_end_with(fs, path)?;
+    while pos > header_end {
+        let whole = pos.checked_sub(TRAILER).filter(|&at| at >= header_end).map(|at| fs.read_at(path, at, TRAILER as usize));
+        let Some(trailer) = whole.transpose()? else { break };
+        let len = u32::from_le_bytes(trailer[4..8].try_into().unwrap_or_default()) as u64 + FRAME_OVERHEAD as u64;
+        let Some(start) = pos.checked_sub(len).filter(|&start| start >= header_end) else { break };
+        let Framed::Whole(_, record, stamp) = frame_at(&fs.read_at(path, start, len as usize)?) else { break };
+        if let Some(found) = find(&sealed_frame(&record, stamp)) {
+            return Ok(Some(found));
+        }
+        pos = start;
+    }
+    if pos <= header_end {
+        return Ok(find(&header));
+    }
+
+    let mut reader = fs.reader(path)?;
+    io::copy(&mut reader.by_ref().take(header_end), &mut io::sink())?;
+    let mut found = find(&header);
+    let mut frame = Vec::new();
+    while read_frame(&mut reader, &mut frame)? > 0 {
+        match frame_at(&frame) {
+            _ if frame.is_empty() => {}
+            Framed::Whole(_, record, stamp) => found = find(&sealed_frame(&record, stamp)).or(found),
+            Framed::Cut | Framed::Bad => break,
+        }
+    }
+    Ok(found)
+}
+
+
+/// The sealed line a binary log's frame of `record` and `stamp` was
+/// appended as.
+fn sealed_frame(record: &str, stamp: Option<Stamp>) -> String {
+    match stamp {
+        Some(stamp) => seal_record_at(record, stamp),
+        None => seal_record(record),
+    }
+}
+
+
+/// Escape a key or value so it forms one whitespace-free log field.
+///
+/// The empty string is written as `\\e` so the field never disappears.
+///
+/// # Example
+/// ```
+/// use kvstore::{escape_field, unescape_field};
+/// let field = escape_field("hello world\n");
+/// assert_eq!(field, "hello\\sworld\\n");
+/// assert_eq!(unescape_field(&field), "hello world\n");
+/// ```
+pub fn escape_field(text: &str) -> String {
+    escape_bytes(text.as_bytes())
+}
+
+
+/// Escape a value of any bytes as one log field.
+///
+/// Text is escaped as [`escape_field`] does; each byte that is not part
+/// of valid UTF-8 is written `\\xNN`, so a text value's field is the same
+/// as ever and only binary values use the new escape.
+///
+/// # Example
+/// ```
+/// use kvstore::{escape_bytes, unescape_bytes};
+/// let field = escape_bytes(&[b'a', b' ', 0xff, 0]);
+/// assert_eq!(field, "a\\s\\xff\\0");
+/// assert_eq!(unescape_bytes(&field), vec![b'a', b' ', 0xff, 0]);
+/// ```
+pub fn escape_bytes(value: &[u8]) -> String {
+    if value.is_empty() {
+        return "\\e".to_string();
+    }
+    let mut out = String::with_capacity(value.len());
+    for chunk in value.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '\\' => out.push_str("\\\\"),
+                ' ' => out.push_str("\\s"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\0' => out.push_str("\\0"),
+                _ => out.push(ch),
+            }
+        }
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    out
+}
+
+
+/// Reverse [`escape_field`]. Unknown escapes are kept as written, and
+/// bytes that are not UTF-8 become `U+FFFD`; use [`unescape_bytes`] for
+/// values that may not be text.
+pub fn unescape_field(field: &str) -> String {
+    lossy_text(unescape_bytes(field))
+}
+
+
+/// A value as text, with bytes that are not UTF-8 shown as `U+FFFD`.
+/// Text values are moved, not copied.
+pub(crate) fn lossy_text(value: Vec<u8>) -> String {
+    match String::from_utf8(value) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+
+/// Reverse [`escape_bytes`]. Unknown escapes are kept as written.
+pub fn unescape_bytes(field: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+        let escaped = match rest.first() {
+            Some(b'\\') => Some(b'\\'),
+            Some(b's') => Some(b' '),
+            Some(b't') => Some(b'\t'),
+            Some(b'n') => Some(b'\n'),
+            Some(b'r') => Some(b'\r'),
+            Some(b'0') => Some(0),
+            Some(b'x') => rest.get(1..3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match (rest.first(), escaped) {
+            (Some(b'x'), Some(value)) => {
+                out.push(value);
+                rest = &rest[3..];
+            }
+            (_, Some(value)) => {
+                out.push(value);
+                rest = &rest[1..];
+            }
+            (Some(b'e'), None) => rest = &rest[1..],
+            // Unknown escape or a trailing backslash: kept as written
+            _ => out.push(b'\\'),
+        }
+    }
+    out
+}
+
+
+/// One record of the log, as written by [`LogRecord::encode`] and read
+/// back by [`LogRecord::decode`].
+///
+/// Every append and every replay goes through this type, so the text
+/// format lives in one place. On disk each record is one line of fields
+/// separated by spaces, keys escaped with [`escape_field`] and values
+/// with [`escape_bytes`], and is sealed with a checksum ([`seal_record`]).
+///
+/// # Example
+/// ```
+/// use kvstore::LogRecord;
+/// let record = LogRecord::Set { key: "a key".into(), value: b"v".to_vec() };
+/// assert_eq!(record.encode(), "SET a\\skey v");
+/// assert_eq!(LogRecord::decode(&record.encode()), Some(record));
+/// assert_eq!(LogRecord::decode("DEL"), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogRecord {
+    /// `SET <key> <value>`: the key now holds the value, any bytes.
+    Set { key: String, value: Vec<u8> },
+
+    /// `MSET <k1> <v1> ...`: several keys at once. Only older logs hold
+    /// these; batches are now logged as one `SET` per key.
+    MSet { pairs: Vec<(String, String)> },
+
+    /// `DEL <key>`: the key was deleted.
+    Del { key: String },
+
+    /// `EXPIREAT <key> <unix ms>`: the key expires at this absolute time.
+    ExpireAt { key: String, ms: u64 },
+
+    /// `PERSIST <key>`: the key's TTL was removed.
+    Persist { key: String },
+
+    /// `CHECKPOINT <id>`: the log continues checkpoint `id` (only ever
+    /// its first record; see [`crate::checkpoint`]).
+    Checkpoint { id: u64 },
+
+    /// `SEGMENT <id>`: the log continues cold segment `id` (only ever its
+    /// first record; see [`crate::compress`]).
+    Segment { id: u64 },
+
+    /// `VSET <key> <generation> <offset> <len>`: the key now holds the
+    /// value stored in the value log at `value`.
+    SetRef { key: String, value: ValueRef },
+}
+
+
+impl LogRecord {
+    /// The record's text, without checksum or newline.
+    pub fn encode(&self) -> String {
+        match self {
+            LogRecord::Set { key, value } => format!("SET {} {}", escape_field(key), escape_bytes(value)),
+            LogRecord::MSet { pairs } => {
+                let fields: Vec<String> = pairs.iter().map(|(k, v)| format!("{} {}", escape_field(k), escape_field(v))).collect();
+                format!("MSET {}", fields.join(" "))
+            }
+            LogRecord::Del { key } => format!("DEL {}", escape_field(key)),
+            LogRecord::ExpireAt { key, ms } => format!("EXPIREAT {} {}", escape_field(key), ms),
+            LogRecord::Persist { key } => format!("PERSIST {}", escape_field(key)),
+            LogRecord::Checkpoint { id } => format!("CHECKPOINT {}", id),
+            LogRecord::Segment { id } => format!("SEGMENT {}", id),
+            LogRecord::SetRef { key, value } => {
+                format!("VSET {} {} {} {}", escape_field(key), value.generation, value.ptr.offset, value.ptr.len)
+            }
+        }
+    }
+
+
+    /// Decode a record's text (checksum already removed).
+    ///
+    /// # Returns
+    /// `None` for a malformed or unknown record.
+    pub fn decode(record: &str) -> Option<LogRecord> {
+        let parts: Vec<&str> = record.split_whitespace().collect();
+        Some(match parts.as_slice() {
+            ["SET", key, value] => LogRecord::Set { key: unescape_field(key), value: unescape_bytes(value) },
+            ["MSET", pairs @ ..] if !pairs.is_empty() && pairs.len() % 2 == 0 => LogRecord::MSet {
+                pairs: pairs.chunks(2).map(|p| (unescape_field(p[0]), unescape_field(p[1]))).collect(),
+            },
+            ["DEL", key] => LogRecord::Del { key: unescape_field(key) },
+            ["EXPIREAT", key, ms] => LogRecord::ExpireAt { key: unescape_field(key), ms: ms.parse().ok()? },
+            ["PERSIST", key] => LogRecord::Persist { key: unescape_field(key) },
+            ["CHECKPOINT", id] => LogRecord::Checkpoint { id: id.parse().ok()? },
+            ["SEGMENT", id] => LogRecord::Segment { id: id.parse().ok()? },
+            ["VSET", key, generation, offset, len] => LogRecord::SetRef {
+                key: unescape_field(key),
+                value: ValueRef {
+                    generation: generation.parse().ok()?,
+                    ptr: ValuePointer { offset: offset.parse().ok()?, len: len.parse().ok()?, raw: false },
+                },
+            },
+            _ => return None,
+        })
+    }
+}
+
+
+/// Build the log record for `SET key value`, escaping both fields.
+pub fn set_record(key: &str, value: &str) -> String {
+    set_record_bytes(key, value.as_bytes())
+}
+
+
+/// Build the log record for `SET key value` with a value of any bytes.
+///
+/// # Example
+/// ```
+/// use kvstore::set_record_bytes;
+/// assert_eq!(set_record_bytes("img", &[0x89, b'P']), "SET img \\x89P");
+/// ```
+pub fn set_record_bytes(key: &str, value: &[u8]) -> String {
+    LogRecord::Set { key: key.to_string(), value: value.to_vec() }.encode()
+}
+
+
+/// Decode a `SET <key> <value>` record into its key and value bytes.
 ///
 /// # Returns
-/// * `Ok(())` if the line was successfully written and flushed.
-/// * `Err(io::Error)` if the file could not be opened or written to.
+/// `Some((key, value))`, or `None` for any other kind of record.
 ///
 /// # Example
 /// ```
-/// use kvstore::append_write;
-/// let file = "example.db";
-/// std::fs::write(file, "").unwrap();
-/// append_write(file, "SET dog bark").unwrap();
-/// let contents = std::fs::read_to_string(file).unwrap();
-/// assert!(contents.contains("SET dog bark"));
+/// use kvstore::{parse_set_record, set_record};
+/// let line = set_record("greeting", "hello world");
+/// assert_eq!(parse_set_record(&line), Some(("greeting".to_string(), b"hello world".to_vec())));
+/// assert_eq!(parse_set_record("DEL greeting"), None);
 /// ```
-pub fn append_write(filename: &str, input_data: &str) -> io::Result<()> {
-
-    // Access the data file, create if needed
-    let mut data_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(filename)?;
+pub fn parse_set_record(line: &str) -> Option<(String, Vec<u8>)> {
+    match LogRecord::decode(line)? {
+        LogRecord::Set { key, value } => Some((key, value)),
+        _ => None,
+    }
+}
 
-    // This will write the line and add a newline
-    writeln!(data_file, "{}", input_data)?;
-    // Flushing will write data - reduces data loss
-    data_file.sync_all()?;
 
-    Ok(())
+/// Build the log record for `DEL key`.
+pub fn del_record(key: &str) -> String {
+    LogRecord::Del { key: key.to_string() }.encode()
 }
 
 
-/// Replay the contents of a persistent log file into memory.
-///
-/// Reads the file line by line, collecting each command string
-/// into a vector. This function is typically called on startup
-/// to rebuild the in-memory index from durable state.
-///
-/// # Arguments
-/// * `filename` - The path of the log file (e.g. `data.db`).
+/// Build the log record for a TTL: `EXPIREAT key <unix ms>`.
 ///
-/// # Returns
-/// * `Ok(Vec<String>)` containing all non-empty lines, in order.
-/// * `Err(io::Error)` if the file could not be opened or read.
+/// The deadline is absolute so a restart knows how much time is left.
 ///
 /// # Example
 /// ```
-/// use kvstore::{append_write, replay_log};
+/// use kvstore::{decode_record, expire_at_record, ReplayOp};
+/// let line = expire_at_record("a key", 1_700_000_000_000);
+/// assert_eq!(line, "EXPIREAT a\\skey 1700000000000");
+/// assert_eq!(decode_record(0, &line), vec![ReplayOp::ExpireAt("a key".into(), 1_700_000_000_000)]);
+/// ```
+pub fn expire_at_record(key: &str, unix_ms: u64) -> String {
+    LogRecord::ExpireAt { key: key.to_string(), ms: unix_ms }.encode()
+}
+
+
+/// Build the log record for `PERSIST key` (the key's TTL was removed).
+pub fn persist_record(key: &str) -> String {
+    LogRecord::Persist { key: key.to_string() }.encode()
+}
+
+
+/// One change to the store decoded from a log record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayOp {
+    /// The key now holds the value, whose escaped bytes sit at the pointer.
+    Set(String, Vec<u8>, ValuePointer),
+
+    /// The key now holds the value kept in the value log at the reference.
+    SetRef(String, ValueRef),
+
+    /// The key was deleted.
+    Del(String),
+
+    /// The key expires at this Unix time in milliseconds. A deadline that
+    /// has passed by the time it is replayed means the key is gone.
+    ExpireAt(String, u64),
+
+    /// The key's TTL was removed.
+    Persist(String),
+}
+
+
+/// Decode the record starting at `offset` into the changes it makes.
 ///
-/// // Write some SET commands to a temporary file
-/// let file = "example_replay.db";
-/// std::fs::write(file, "").unwrap();
-/// append_write(file, "SET dog bark").unwrap();
-/// append_write(file, "SET cat meow").unwrap();
+/// Decodes it as a [`LogRecord`] and points each value set at its bytes
+/// in the log. Malformed or unknown records, and `CHECKPOINT`, decode to
+/// no changes.
 ///
-/// // Replay the log back into memory
-/// let records = replay_log(file).unwrap();
-/// assert_eq!(records, vec!["SET dog bark", "SET cat meow"]);
+/// # Example
+/// ```
+/// use kvstore::{decode_record, ReplayOp, ValuePointer};
+/// let ops = decode_record(0, "MSET a 1 b 22");
+/// assert_eq!(ops, vec![
+///     ReplayOp::Set("a".into(), "1".into(), ValuePointer { offset: 7, len: 1, raw: false }),
+///     ReplayOp::Set("b".into(), "22".into(), ValuePointer { offset: 11, len: 2, raw: false }),
+/// ]);
+/// assert_eq!(decode_record(0, "DEL a"), vec![ReplayOp::Del("a".into())]);
+/// assert!(decode_record(0, "NOPE a").is_empty());
 /// ```
-pub fn replay_log(_filename: &str) -> io::Result<Vec<String>> {
-    let filename = get_data_file();
-    let file = File::open(&filename);
+pub fn decode_record(offset: u64, line: &str) -> Vec<ReplayOp> {
+    // Values are found again by position: every other field from the third
+    let values = line.split_whitespace().skip(2).step_by(2).map(|field| {
+        let at = field.as_ptr() as usize - line.as_ptr() as usize;
+        ValuePointer { offset: offset + at as u64, len: field.len() as u32, raw: false }
+    });
+    decode_with(line, values)
+}
 
-    if let Err(e) = &file {
-        if e.kind() == io::ErrorKind::NotFound {
-            return Ok(vec![]);
-        }
-        return Err(std::io::Error::new(e.kind(), e.to_string()));
+
+/// Decode `line` into its changes, the values set pointing at `values`
+/// in order.
+fn decode_with(line: &str, mut values: impl Iterator<Item = ValuePointer>) -> Vec<ReplayOp> {
+    match LogRecord::decode(line) {
+        Some(LogRecord::Set { key, value }) => values.next().map(|ptr| ReplayOp::Set(key, value, ptr)).into_iter().collect(),
+        Some(LogRecord::MSet { pairs }) => pairs.into_iter().zip(values).map(|((k, v), ptr)| ReplayOp::Set(k, v.into_bytes(), ptr)).collect(),
+        Some(LogRecord::Del { key }) => vec![ReplayOp::Del(key)],
+        Some(LogRecord::ExpireAt { key, ms }) => vec![ReplayOp::ExpireAt(key, ms)],
+        Some(LogRecord::Persist { key }) => vec![ReplayOp::Persist(key)],
+        Some(LogRecord::SetRef { key, value }) => vec![ReplayOp::SetRef(key, value)],
+        Some(LogRecord::Checkpoint { .. } | LogRecord::Segment { .. }) | None => Vec::new(),
     }
+}
 
-    let reader = BufReader::new(file.unwrap());
-    let mut out = Vec::new();
 
-    for line in reader.lines() {
-        if let Ok(l) = line {
-            let trimmed = l.trim();
-            if !trimmed.is_empty() {
-                out.push(trimmed.to_string());
-            }
-        }
+/// Read back the value of the `SET` record starting at `offset`.
+///
+/// Used by memory-limited sessions to reload values that were evicted
+/// from memory. Only the single line (or frame) at `offset` is read.
+///
+/// # Returns
+/// * `Ok(Some(value))` if a well-formed `SET <key> <value>` record is there.
+/// * `Ok(None)` if the record at that position is not a `SET`.
+/// * `Err(io::Error)` if the file could not be opened or read.
+pub fn read_value_at(filename: &str, offset: u64) -> io::Result<Option<Vec<u8>>> {
+    let layout = FileBackend::process().layout(filename)?;
+    let mut file = File::open(filename)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+
+    if layout == Layout::Binary {
+        let mut frame = Vec::new();
+        read_frame(&mut reader, &mut frame)?;
+        return Ok(match frame_at(&frame) {
+            Framed::Whole(_, record, _) => parse_set_record(&record).map(|(_, value)| value),
+            Framed::Cut | Framed::Bad => None,
+        });
     }
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let line = trim_record(&line);
+    Ok(parse_set_record(unseal_record(line).unwrap_or(line)).map(|(_, value)| value))
+}
+
+
+
+/// Read exactly the value bytes described by `ptr`.
+///
+/// # Returns
+/// * `Ok(value)` with the value escaped in the `ptr.len` bytes at `ptr.offset`.
+/// * `Err(io::Error)` if the file is short or unreadable, or the field is
+///   not an escaped value.
+pub fn read_value(filename: &str, ptr: ValuePointer) -> io::Result<Vec<u8>> {
+    read_value_with(&RealFs, filename, ptr)
+}
+
 
-    Ok(out)
+/// Read the value described by `ptr` from a log read through `fs`.
+///
+/// # Returns
+/// * `Ok(value)`, unescaped unless `ptr` is raw.
+/// * `Err(io::Error)` if the file is short or unreadable, or the field is
+///   not an escaped value (which is always ASCII or UTF-8 text).
+pub fn read_value_with(fs: &dyn Fs, filename: &str, ptr: ValuePointer) -> io::Result<Vec<u8>> {
+    let buf = fs.read_at(filename, ptr.offset, ptr.len as usize)?;
+    if ptr.raw {
+        return Ok(buf);
+    }
+    let field = String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(unescape_bytes(&field))
 }
 
 
@@ -143,6 +3121,17 @@ mod storage_tests {
         let _ = fs::remove_file(path);
     }
 
+    // A format 4 log, the last whose records are text lines
+    fn text_log<S: AsRef<str>>(records: &[S]) -> String {
+        log_text(records).replacen(&header_record(), "KVSTORE 4", 1)
+    }
+
+    // The frame a binary log holds for the sealed record `sealed`
+    fn frame(sealed: &str) -> Vec<u8> {
+        let header = format!("{}\n", header_record());
+        log_bytes(&format!("{}{}", header, sealed)).unwrap().split_off(header.len())
+    }
+
     #[test]
     fn test_append_and_replay_single_entry() {
         let file = test_file("append_single");
@@ -216,4 +3205,599 @@ mod storage_tests {
 
         clean(&file);
     }
+
+    #[test]
+    fn test_replay_with_offsets_tracks_line_starts() {
+        let file = test_file("offsets");
+        clean(&file);
+
+        append_write(&file, "SET a 1").unwrap();
+        let second = append_write_at(&file, "SET bee 22").unwrap();
+        let records = replay_log_with_offsets(&file).unwrap();
+        assert_eq!(records, vec![(0, "SET a 1".to_string()), (second, "SET bee 22".to_string())]);
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_append_many_writes_a_batch_with_one_sync() {
+        let file = test_file("append_many");
+        clean(&file);
+
+        let mut writer = LogWriter::open_with(&file, SyncMode::All).unwrap().with_durability(Durability::OnExit);
+        assert!(writer.append_many::<&str>(&[]).unwrap().is_empty());
+        assert!(!writer.has_pending());
+        let offsets = writer.append_many(&["SET a 1", "SET bee 22", "DEL a"]).unwrap();
+        assert_eq!(offsets, vec![0, 8, 19]);
+        drop(writer);
+
+        // The cached path lines up with what replay sees
+        let more = append_many(&file, &["SET c 3", "SET d 4"]).unwrap();
+        let records = replay_log_with_offsets(&file).unwrap();
+        assert_eq!(records.iter().map(|(offset, _)| *offset).collect::<Vec<_>>(), [offsets, more].concat());
+        assert_eq!(read_value_at(&file, records[4].0).unwrap(), Some(b"4".to_vec()));
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_read_value_at_offset() {
+        let file = test_file("read_at");
+        clean(&file);
+
+        append_write(&file, "SET dog bark").unwrap();
+        let offset = append_write_at(&file, "SET cat meow").unwrap();
+        assert_eq!(read_value_at(&file, offset).unwrap(), Some(b"meow".to_vec()));
+        assert_eq!(read_value_at(&file, 0).unwrap(), Some(b"bark".to_vec()));
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_read_value_by_pointer() {
+        let file = test_file("read_ptr");
+        clean(&file);
+
+        append_write(&file, "SET dog bark").unwrap();
+        let record = "SET cat meow";
+        let offset = append_write_at(&file, record).unwrap();
+        let ptr = ValuePointer::for_record(offset, record, "meow");
+        assert_eq!(read_value(&file, ptr).unwrap(), b"meow");
+
+        // Pointers taken from replay line up with what was written
+        let (replayed_at, line) = replay_log_with_offsets(&file).unwrap()[1].clone();
+        assert_eq!(ValuePointer::for_record(replayed_at, &line, "meow"), ptr);
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_decoded_pointers_read_back_values() {
+        let file = test_file("decode");
+        clean(&file);
+
+        append_write(&file, &set_record("x", "first")).unwrap();
+        let mset = "MSET a one b two\\stwo";
+        let offset = append_write_at(&file, mset).unwrap();
+        append_write(&file, &del_record("a b")).unwrap();
+
+        let ops = decode_record(offset, mset);
+        assert_eq!(ops.len(), 2);
+        for (op, want) in ops.iter().zip(["one", "two two"]) {
+            let ReplayOp::Set(_, value, ptr) = op else { panic!("expected a set") };
+            assert_eq!(value, want.as_bytes());
+            assert_eq!(read_value(&file, *ptr).unwrap(), want.as_bytes());
+        }
+
+        let (del_at, del_line) = replay_log_with_offsets(&file).unwrap()[2].clone();
+        assert_eq!(decode_record(del_at, &del_line), vec![ReplayOp::Del("a b".to_string())]);
+        assert!(decode_record(0, "MSET a").is_empty());
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_log_lock_is_exclusive_and_survives_log_replacement() {
+        let file = test_file("locked");
+        clean(&file);
+
+        let lock = LogLock::acquire(&file).unwrap();
+        assert_eq!(lock.path(), format!("{}.lock", file));
+        let err = LogLock::acquire(&file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        // Replacing the log (as compaction does) keeps it locked
+        append_write(&file, "SET a 1").unwrap();
+        close_log(&file).unwrap();
+        fs::write(format!("{}.new", file), "SET a 2\n").unwrap();
+        fs::rename(format!("{}.new", file), &file).unwrap();
+        assert!(LogLock::acquire(&file).is_err());
+
+        drop(lock);
+        assert!(LogLock::acquire(&file).is_ok());
+        clean(&file);
+        let _ = fs::remove_file(format!("{}.lock", file));
+    }
+
+    #[test]
+    fn test_escaped_fields_round_trip() {
+        let file = test_file("escaped");
+        clean(&file);
+
+        let value = "hello world\tline\nback\\slash";
+        let record = set_record("my key", value);
+        assert_eq!(record.split_whitespace().count(), 3);
+
+        let offset = append_write_at(&file, &record).unwrap();
+        let ptr = ValuePointer::for_set_record(offset, &record);
+        assert_eq!(read_value(&file, ptr).unwrap(), value.as_bytes());
+        assert_eq!(read_value_at(&file, offset).unwrap(), Some(value.as_bytes().to_vec()));
+
+        let replayed = replay_log(&file).unwrap();
+        assert_eq!(parse_set_record(&replayed[0]), Some(("my key".to_string(), value.as_bytes().to_vec())));
+
+        // Empty fields survive as `\e`
+        assert_eq!(parse_set_record(&set_record("k", "")), Some(("k".to_string(), Vec::new())));
+
+        // Unknown escapes are left alone
+        assert_eq!(unescape_field("a\\qb\\"), "a\\qb\\");
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_log_grows_in_preallocated_chunks() {
+        let file = test_file("prealloc");
+        clean(&file);
+
+        append_write(&file, "SET a 1").unwrap();
+        assert_eq!(fs::metadata(&file).unwrap().len(), PREALLOC_CHUNK);
+
+        // Padding is invisible to replay
+        assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1"]);
+
+        // Closing trims the file back to its real data
+        close_log(&file).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "SET a 1\n");
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_every_sync_mode_appends_durably() {
+        for mode in [SyncMode::All, SyncMode::Data, SyncMode::Dsync] {
+            let file = test_file(&format!("sync_{:?}", mode));
+            clean(&file);
+
+            let mut writer = LogWriter::open_with(&file, mode).unwrap();
+            writer.append("SET a 1").unwrap();
+            writer.append("SET b 2").unwrap();
+            drop(writer);
+
+            assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "SET b 2"]);
+            clean(&file);
+        }
+    }
+
+    #[test]
+    fn test_deferred_durability_syncs_pending_appends() {
+        for policy in [Durability::EveryMillis(60_000), Durability::OnExit] {
+            let file = test_file(&format!("durability_{}", policy.name()));
+            clean(&file);
+
+            let mut writer = LogWriter::open_with(&file, SyncMode::All).unwrap().with_durability(policy);
+            writer.append("SET a 1").unwrap();
+            writer.append("SET b 2").unwrap();
+            assert!(writer.has_pending());
+            assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "SET b 2"]);
+
+            writer.sync_pending().unwrap();
+            assert!(!writer.has_pending());
+            writer.append("SET c 3").unwrap();
+            drop(writer);
+            assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "SET b 2", "SET c 3"]);
+            clean(&file);
+        }
+
+        // An interval that has passed syncs on the next append
+        let file = test_file("durability_elapsed");
+        clean(&file);
+        let mut writer = LogWriter::open(&file).unwrap().with_durability(Durability::EveryMillis(0));
+        writer.append("SET a 1").unwrap();
+        assert!(!writer.has_pending());
+        drop(writer);
+        clean(&file);
+    }
+
+    #[test]
+    fn test_reopen_resumes_after_padding() {
+        let file = test_file("reopen_padding");
+        clean(&file);
+
+        // Simulate a crash that left zero padding behind
+        let mut padded = b"SET a 1\n".to_vec();
+        padded.resize(100, 0);
+        fs::write(&file, &padded).unwrap();
+
+        let mut writer = LogWriter::open(&file).unwrap();
+        assert_eq!(writer.offset(), 8);
+        assert_eq!(writer.append("SET b 2").unwrap(), 8);
+        drop(writer);
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "SET a 1\nSET b 2\n");
+        assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "SET b 2"]);
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_migrate_adds_header_to_old_logs() {
+        let file = test_file("migrate_old");
+        clean(&file);
+
+        // Missing file: created with just the header
+        assert_eq!(migrate_log(&file).unwrap(), Migration::Created);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "KVSTORE 5\n");
+
+        // Headerless log with preallocation padding left behind
+        let mut padded = b"SET a 1\nDEL a\nSET b 2\n".to_vec();
+        padded.resize(64, 0);
+        fs::write(&file, &padded).unwrap();
+        assert_eq!(migrate_log(&file).unwrap(), Migration::Upgraded { from: 0 });
+        assert_eq!(fs::read(&file).unwrap(), log_bytes(&log_text(&["SET a 1", "DEL a", "SET b 2"])).unwrap());
+        assert!(fs::metadata(format!("{}.tmp", file)).is_err());
+
+        // Already current: left alone, and the header is not replayed
+        assert_eq!(migrate_log(&file).unwrap(), Migration::Current);
+        let records = replay_log_with_offsets(&file).unwrap();
+        assert_eq!(records[0], (10, "SET a 1".to_string()));
+        assert_eq!(records[1].0, 10 + frame(&seal_record("SET a 1")).len() as u64);
+        assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "DEL a", "SET b 2"]);
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_binary_frames_hold_values_as_they_are() {
+        let fs = crate::MemFs::new();
+        let value = "two words\nand\\a tab\t";
+        let records = [set_record("a key", value), "MSET b 1 c 22".to_string(), "PUT a 1".to_string()];
+        fs.append("log", &log_text(&records)).unwrap();
+        assert_eq!(fs.layout("log").unwrap(), Layout::Binary);
+        assert!(fs.contents("log").unwrap().windows(value.len()).any(|bytes| bytes == value.as_bytes()));
+
+        // Records replay as they were appended, and pointers land on the unescaped bytes
+        let replayed = replay_records(&fs, "log", 0).unwrap();
+        assert_eq!(replayed.iter().map(|(_, r)| r.as_str()).collect::<Vec<_>>(), records);
+        let mut values = Vec::new();
+        for (offset, record) in &replayed {
+            for op in Layout::Binary.decode(*offset, record) {
+                let ReplayOp::Set(_, want, ptr) = op else { unreachable!() };
+                assert!(ptr.raw);
+                assert_eq!(read_value_with(&fs, "log", ptr).unwrap(), want);
+                values.push(want);
+            }
+        }
+        assert_eq!(values, [value.as_bytes(), b"1", b"22"]);
+
+        // A text log stays text until it is migrated
+        fs.write_file("old", format!("{}\n", text_log(&["SET a 1"])).as_bytes());
+        fs.append("old", &seal_record("SET b 2")).unwrap();
+        assert_eq!(fs.layout("old").unwrap(), Layout::Text);
+        assert!(fs.contents("old").unwrap().ends_with(format!("{}\n", seal_record("SET b 2")).as_bytes()));
+        assert_eq!(migrate_log_with(&fs, "old").unwrap(), Migration::Upgraded { from: 4 });
+        assert_eq!(fs.contents("old").unwrap(), log_bytes(&log_text(&["SET a 1", "SET b 2"])).unwrap());
+
+        // Only sealed records go into a binary log
+        assert_eq!(fs.append("log", "SET c 3").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_formats() {
+        let fs = crate::MemFs::new();
+        fs.write_file("log", b"KVSTORE 99\nSET a 1\n");
+        let err = migrate_log_with(&fs, "log").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs.contents("log").unwrap(), b"KVSTORE 99\nSET a 1\n");
+
+        // A header later in the file is just an unknown record
+        fs.write_file("log", b"SET a 1\nKVSTORE 1\n");
+        assert_eq!(migrate_log_with(&fs, "log").unwrap(), Migration::Upgraded { from: 0 });
+        assert_eq!(replay_records(&fs, "log", 0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_torn_tail_is_cut_before_new_appends() {
+        let fs = crate::MemFs::new();
+        let good = text_log(&["SET a 1", "SET b 2"]);
+        let torn = format!("{}\n", seal_record("SET c 3"));
+        fs.write_file("log", format!("{}\n{}", good, &torn[..torn.len() - 4]).as_bytes());
+
+        // Replay stops at the damage, even with good records behind it
+        fs.append("log", &seal_record("SET d 4")).unwrap();
+        let records: Vec<String> = replay_records(&fs, "log", 0).unwrap().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(records, ["SET a 1", "SET b 2"]);
+
+        let cut = recover_log_with(&fs, "log").unwrap();
+        assert_eq!(cut as usize, torn.len() - 4 + seal_record("SET d 4").len() + 1);
+        assert_eq!(fs.contents("log").unwrap(), format!("{}\n", good).as_bytes());
+        assert!(fs.contents(&sidecar_path("log", "torn")).unwrap().starts_with(b"SET c 3\t"));
+        assert_eq!(recover_log_with(&fs, "log").unwrap(), 0);
+
+        // A cut frame swallows the length of the one after it, so it fails its checksum
+        let good = log_bytes(&log_text(&["SET a 1", "SET b 2"])).unwrap();
+        let torn = frame(&seal_record("SET c 3"));
+        fs.write_file("log", &[&good[..], &torn[..torn.len() - 4]].concat());
+        fs.append("log", &seal_record("SET d 4")).unwrap();
+        assert_eq!(replay_records(&fs, "log", 0).unwrap().len(), 2);
+        let cut = recover_log_with(&fs, "log").unwrap();
+        assert_eq!(cut as usize, torn.len() - 4 + frame(&seal_record("SET d 4")).len());
+        assert_eq!(fs.contents("log").unwrap(), good);
+        assert!(fs.contents(&sidecar_path("log", "torn")).unwrap().starts_with(&torn[..torn.len() - 4]));
+        assert_eq!(recover_log_with(&fs, "log").unwrap(), 0);
+
+        // Older formats carry no checksums, so nothing counts as torn
+        fs.write_file("log", b"KVSTORE 1\nSET a 1\nSET b");
+        assert_eq!(recover_log_with(&fs, "log").unwrap(), 0);
+        assert_eq!(replay_records(&fs, "log", 0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_migration_and_recovery_stream_logs_bigger_than_a_batch() {
+        let fs = crate::MemFs::new();
+        let records: Vec<String> = (0..40_000).map(|i| format!("SET key{:05} {}", i, "v".repeat(24))).collect();
+        fs.write_file("log", format!("{}\n", records.join("\n")).as_bytes());
+        assert!(records.len() * records[0].len() > SINK_BATCH);
+
+        assert_eq!(migrate_log_with(&fs, "log").unwrap(), Migration::Upgraded { from: 0 });
+        let migrated = log_bytes(&log_text(&records)).unwrap();
+        assert_eq!(fs.contents("log").unwrap(), migrated);
+
+        let torn = frame(&seal_record("SET late 1"));
+        // Cut after the value: a cut ending in zero bytes would lose them as padding
+        fs.append_raw("log", &torn[..torn.len() - 9]).unwrap();
+        assert_eq!(recover_log_with(&fs, "log").unwrap(), torn.len() as u64 - 9);
+        assert_eq!(fs.contents("log").unwrap(), migrated);
+    }
+
+    fn at(seq: u64, unix_ms: u64) -> Stamp {
+        Stamp { seq, unix_ms }
+    }
+
+    #[test]
+    fn test_recover_to_cuts_at_the_first_later_stamp() {
+        let fs = crate::MemFs::new();
+        let records = [
+            seal_record_at("SET a 1", at(1, 1000)),
+            seal_record("SET b 2"),
+            seal_record_at("SET a 2", at(2, 2000)),
+            seal_record("SET c 3"),
+            seal_record_at("DEL a", at(3, 3000)),
+        ];
+        let header = format!("{}\n", header_record());
+        let frames: Vec<u8> = records.iter().flat_map(|r| frame(r)).collect();
+        let mut log = [header.as_bytes(), &frames].concat();
+        log.resize(log.len() + 32, 0);
+        fs.write_file("log", &log);
+
+        // Unstamped records go with the stamped ones before them
+        assert_eq!(recover_to_with(&fs, "log", 999).unwrap(), 5);
+        assert_eq!(fs.contents("log").unwrap(), header.as_bytes());
+        assert_eq!(fs.contents(&sidecar_path("log", "after.999")).unwrap(), frames);
+
+        fs.write_file("log", &log);
+        assert_eq!(recover_to_with(&fs, "log", 2000).unwrap(), 1);
+        let kept: Vec<String> = replay_records(&fs, "log", 0).unwrap().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(kept, ["SET a 1", "SET b 2", "SET a 2", "SET c 3"]);
+        assert_eq!(recover_to_with(&fs, "log", 2000).unwrap(), 0);
+
+        // A torn record ends the search; older formats have no stamps
+        let torn = frame(&seal_record_at("SET d 4", at(4, 5000)));
+        fs.write_file("log", &[header.as_bytes(), &frame(&records[0]), &torn[..torn.len() - 1]].concat());
+        assert_eq!(recover_to_with(&fs, "log", 1000).unwrap(), 0);
+        fs.write_file("log", b"KVSTORE 2\nSET a 1\t302af431\n");
+        assert_eq!(recover_to_with(&fs, "log", 0).unwrap(), 0);
+        assert_eq!(recover_to_with(&fs, "missing", 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_stamped_records_survive_migration() {
+        let fs = crate::MemFs::new();
+        // Format 3 stamped records with the time alone
+        let stamped = format!("SET a 1\t1000\t{:08x}", crc32(b"SET a 1\t1000"));
+        assert_eq!(unseal_stamped(&stamped), Some(("SET a 1", Some(at(0, 1000)))));
+        fs.write_file("log", format!("KVSTORE 3\n{}\n{}\n", seal_record("SET b 2"), stamped).as_bytes());
+        assert_eq!(migrate_log_with(&fs, "log").unwrap(), Migration::Upgraded { from: 3 });
+        let migrated = log_bytes(&format!("{}\n{}\n{}", header_record(), seal_record("SET b 2"), stamped)).unwrap();
+        assert_eq!(fs.contents("log").unwrap(), migrated);
+        let records = replay_records(&fs, "log", 0).unwrap();
+        assert_eq!(records[1], (header_record().len() as u64 + 1 + frame(&seal_record("SET b 2")).len() as u64, "SET a 1".to_string()));
+        assert_eq!(recover_to_with(&fs, "log", 999).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_last_seq_reads_back_from_the_end() {
+        let fs = crate::MemFs::new();
+        assert_eq!(last_seq(&fs, "log").unwrap(), 0);
+        fs.append("log", &header_record()).unwrap();
+        assert_eq!(last_seq(&fs, "log").unwrap(), 0);
+
+        // Enough records that the last stamp is chunks away from the header
+        let filler = format!("SET filler {}", "x".repeat(1000));
+        fs.append("log", &seal_record_at("SET a 1", at(41, 1))).unwrap();
+        for _ in 0..200 {
+            fs.append("log", &seal_record(&filler)).unwrap();
+        }
+        assert_eq!(last_seq(&fs, "log").unwrap(), 41);
+        fs.append("log", &seal_record_at(&filler, at(42, 2))).unwrap();
+        let torn = frame(&seal_record_at("SET b 2", at(43, 3)));
+        fs.append_raw("log", &torn[..torn.len() - 2]).unwrap();
+        assert_eq!(last_seq(&fs, "log").unwrap(), 42);
+
+        // Rewrites carry the number in the header
+        assert_eq!(compact_log_with(&fs, "log").unwrap(), 2);
+        assert!(fs.contents("log").unwrap().starts_with(format!("{} 42\n", header_record()).as_bytes()));
+        assert_eq!(last_seq(&fs, "log").unwrap(), 42);
+    }
+
+    #[test]
+    fn test_a_crash_at_any_byte_replays_a_prefix() {
+        let file = test_file("crash_any_byte");
+        let written: Vec<String> = (0..5).map(|i| set_record(&format!("key{}", i), &"v".repeat(i))).collect();
+        let total: usize = written.iter().map(|r| seal_record(r).len() + 1).sum();
+
+        for after in 0..=total as u64 {
+            clean(&file);
+            fs::write(&file, format!("{}\n", header_record())).unwrap();
+            inject_crash(&file, Some(after));
+            let acked = written.iter().take_while(|r| append_write(&file, &seal_record(r)).is_ok()).count();
+            inject_crash(&file, None);
+            close_log(&file).unwrap();
+
+            // A record cut off just before its newline is whole, though unacknowledged
+            recover_log(&file).unwrap();
+            let kept = verify_replay_prefix(&RealFs, &file, &written, acked).unwrap();
+            assert!(kept == acked || kept == acked + 1, "crash after {} bytes", after);
+
+            // The log takes appends again once recovered
+            append_write(&file, &seal_record("DEL key0")).unwrap();
+            close_log(&file).unwrap();
+            assert_eq!(replay_log(&file).unwrap().last().map(String::as_str), Some("DEL key0"));
+        }
+        clean(&file);
+        let _ = fs::remove_file(sidecar_path(&file, "torn"));
+
+        // A log that strays from what was written is caught
+        let fs = crate::MemFs::new();
+        fs.append("log", &log_text(&["SET a 1", "SET b 2"])).unwrap();
+        assert_eq!(verify_replay_prefix(&fs, "log", &["SET a 1", "SET b 2", "SET c 3"], 2).unwrap(), 2);
+        assert!(verify_replay_prefix(&fs, "log", &["SET a 1", "SET b 2", "SET c 3"], 3).is_err());
+        assert!(verify_replay_prefix(&fs, "log", &["SET a 1", "SET b 3"], 1).is_err());
+        assert!(verify_replay_prefix(&fs, "log", &["SET a 1"], 1).is_err());
+    }
+
+    #[test]
+    fn test_log_records_round_trip() {
+        let records = [
+            LogRecord::Set { key: "a key".into(), value: "".into() },
+            LogRecord::MSet { pairs: vec![("a".into(), "1".into()), ("b\n".into(), "2 2".into())] },
+            LogRecord::Del { key: "x\\y".into() },
+            LogRecord::ExpireAt { key: "t".into(), ms: 1_700_000_000_000 },
+            LogRecord::Persist { key: "t".into() },
+            LogRecord::Checkpoint { id: 7 },
+            LogRecord::Segment { id: 3 },
+        ];
+        for record in records {
+            assert_eq!(LogRecord::decode(&record.encode()), Some(record));
+        }
+        for bad in ["", "SET a", "MSET a 1 b", "EXPIREAT t soon", "CHECKPOINT -1", "SEGMENT", "PUT a 1"] {
+            assert_eq!(LogRecord::decode(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_replay_iter_streams_what_replay_records_returns() {
+        let fs = crate::MemFs::new();
+        let mut padded = format!("{}\r\n\n{}\n", text_log(&["SET a 1", "MSET b 2 c 3"]), seal_record("DEL a")).into_bytes();
+        padded.resize(padded.len() + 16, 0);
+        let mut framed = log_bytes(&log_text(&["SET a 1", "MSET b 2 c 3", "DEL a"])).unwrap();
+        framed.resize(framed.len() + 16, 0);
+        for log in [padded, framed] {
+            fs.write_file("log", &log);
+            let all = replay_records(&fs, "log", 0).unwrap();
+            assert_eq!(all.len(), 3);
+            let streamed: Vec<_> = ReplayIter::open(&fs, "log", 0).unwrap().map(Result::unwrap).collect();
+            assert_eq!(streamed, all);
+            let from = ReplayIter::open(&fs, "log", all[1].0).unwrap().map(Result::unwrap).collect::<Vec<_>>();
+            assert_eq!(from, all[1..]);
+        }
+
+        // A torn record ends the stream; an error is yielded once
+        fs.write_file("log", format!("{}\nSET b 2\t0bad\n{}", text_log(&["SET a 1"]), seal_record("SET c 3")).as_bytes());
+        let mut records = ReplayIter::open(&fs, "log", 0).unwrap();
+        assert_eq!(records.by_ref().count(), 1);
+        assert_eq!(records.torn_at(), Some(text_log(&["SET a 1"]).len() as u64 + 1));
+        let good = log_bytes(&log_text(&["SET a 1"])).unwrap();
+        let mut bad = frame(&seal_record("SET b 2"));
+        bad[6] ^= 1;
+        fs.write_file("log", &[&good[..], &bad, &frame(&seal_record("SET c 3"))].concat());
+        let mut records = ReplayIter::open(&fs, "log", 0).unwrap();
+        assert_eq!(records.by_ref().count(), 1);
+        assert_eq!(records.torn_at(), Some(good.len() as u64));
+        fs.write_file("log", b"KVSTORE 99\nSET a 1\n");
+        let mut records = ReplayIter::open(&fs, "log", 0).unwrap();
+        assert_eq!(records.next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(records.next().is_none());
+        assert_eq!(ReplayIter::open(&fs, "missing", 0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_compact_keeps_only_live_keys() {
+        let fs = crate::MemFs::new();
+        let log = format!("KVSTORE 1\nSET a 1\nMSET b 2 c 3\n{}\nDEL b\nDEL missing\n", set_record("a", "two words"));
+        fs.write_file("log", log.as_bytes());
+        assert_eq!(compact_log_with(&fs, "log").unwrap(), 2);
+        let compacted = log_bytes(&log_text(&[set_record("a", "two words"), "SET c 3".to_string()])).unwrap();
+        assert_eq!(fs.contents("log").unwrap(), compacted);
+        assert!(fs.contents(&sidecar_path("log", "tmp")).is_none());
+
+        // Nothing to keep still leaves a valid, current log
+        fs.write_file("log", b"SET a 1\nDEL a\n");
+        assert_eq!(compact_log_with(&fs, "log").unwrap(), 0);
+        assert_eq!(fs.contents("log").unwrap(), b"KVSTORE 5\n");
+
+        fs.write_file("log", b"KVSTORE 99\nSET a 1\n");
+        assert_eq!(compact_log_with(&fs, "log").unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs.contents("log").unwrap(), b"KVSTORE 99\nSET a 1\n");
+    }
+
+    #[test]
+    fn test_crlf_logs_replay_like_lf_logs() {
+        let file = test_file("crlf");
+        clean(&file);
+        fs::write(&file, "KVSTORE 1\r\nSET a 1\r\nMSET b 2 c 33\r\n\r\nDEL a\r\n").unwrap();
+
+        assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "MSET b 2 c 33", "DEL a"]);
+        let records = replay_log_with_offsets(&file).unwrap();
+        assert_eq!(records.iter().map(|(at, _)| *at).collect::<Vec<_>>(), vec![11, 20, 37]);
+
+        // Value pointers still land on the value bytes
+        let (at, line) = &records[1];
+        for op in decode_record(*at, line) {
+            let ReplayOp::Set(_, value, ptr) = op else { unreachable!() };
+            assert_eq!(read_value(&file, ptr).unwrap(), value);
+        }
+
+        // New records end in `\n` and replay after the old ones
+        append_write(&file, "SET d 4").unwrap();
+        assert_eq!(replay_log(&file).unwrap().last().unwrap(), "SET d 4");
+        close_log(&file).unwrap();
+        assert_eq!(migrate_log(&file).unwrap(), Migration::Upgraded { from: 1 });
+        assert_eq!(replay_log(&file).unwrap(), vec!["SET a 1", "MSET b 2 c 33", "DEL a", "SET d 4"]);
+
+        clean(&file);
+    }
+
+    #[test]
+    fn test_paths_are_built_per_platform() {
+        use std::path::Path;
+
+        assert_eq!(resolve_data_file(None, None), "data.db");
+        let in_dir = resolve_data_file(None, Some("store dir".into()));
+        assert_eq!(Path::new(&in_dir), Path::new("store dir").join("data.db"));
+        assert_eq!(resolve_data_file(Some("a.db".into()), Some("ignored".into())), "a.db");
+
+        let log = std::env::temp_dir().join("kvstore dir").join("data.db");
+        let lock = sidecar_path(log.to_str().unwrap(), "lock");
+        assert_eq!(Path::new(&lock), log.with_extension("db.lock"));
+
+        // A log in a directory with spaces works end to end
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        let log = log.to_string_lossy().into_owned();
+        clean(&log);
+        append_write(&log, "SET a 1").unwrap();
+        assert_eq!(replay_log(&log).unwrap(), vec!["SET a 1"]);
+        close_log(&log).unwrap();
+        clean(&log);
+    }
 }
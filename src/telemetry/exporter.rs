@@ -0,0 +1,132 @@
+// =====================================================================
+// File: telemetry/exporter.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   OTLP/HTTP export of finished spans, for the kvstore binary and any
+//   application that wants kvstore to set tracing up.
+//
+//   [`init`] builds an `opentelemetry_sdk` tracer provider with a batch
+//   processor feeding an `opentelemetry-otlp` exporter (JSON encoding),
+//   installs it as the global provider and the W3C trace context as the
+//   global propagator. Applications with a provider of their own skip
+//   [`init`]: kvstore's spans go through whatever provider and propagator
+//   they installed. Only plain `http://` endpoints are supported; put a
+//   local collector or agent in front of anything that needs TLS. Without
+//   the `otel` feature, [`init`] reports that tracing is not built in.
+// =====================================================================
+
+use std::time::Duration;
+
+#[cfg(feature = "otel")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "otel")]
+use opentelemetry::global;
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::trace::{BatchConfigBuilder, BatchSpanProcessor, SdkTracerProvider};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::Resource;
+
+/// Where and how spans are exported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtlpConfig {
+    /// Collector URL, e.g. `http://localhost:4318/v1/traces`.
+    pub endpoint: String,
+
+    /// `service.name` resource attribute.
+    pub service_name: String,
+
+    /// Spans that trigger an export before `interval` is up.
+    pub batch_size: usize,
+
+    /// Longest a finished span waits for export.
+    pub interval: Duration,
+
+    /// Spans kept while the collector is slow or down; more are dropped.
+    pub max_queued: usize,
+}
+
+
+impl OtlpConfig {
+    /// Defaults for `endpoint`: service `kvstore`, batches of 512, every
+    /// second, at most 8192 queued.
+    pub fn new(endpoint: &str) -> Self {
+        OtlpConfig {
+            endpoint: endpoint.to_string(),
+            service_name: "kvstore".to_string(),
+            batch_size: 512,
+            interval: Duration::from_secs(1),
+            max_queued: 8192,
+        }
+    }
+}
+
+
+/// The provider [`init`] installed, kept for [`shutdown`].
+#[cfg(feature = "otel")]
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+
+/// Start recording spans and exporting them to `config.endpoint`.
+///
+/// # Returns
+/// `Err(message)` if the endpoint is not an `http://` URL, tracing was
+/// already started, or kvstore was built without the `otel` feature.
+#[cfg(feature = "otel")]
+pub fn init(config: OtlpConfig) -> Result<(), String> {
+    crate::http::split_url(&config.endpoint)?;
+    if PROVIDER.get().is_some() {
+        return Err("tracing is already started".to_string());
+    }
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(config.endpoint)
+        .with_timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let batch = BatchConfigBuilder::default()
+        .with_max_export_batch_size(config.batch_size.max(1))
+        .with_scheduled_delay(config.interval)
+        .with_max_queue_size(config.max_queued.max(1))
+        .build();
+    let provider = SdkTracerProvider::builder()
+        .with_span_processor(BatchSpanProcessor::builder(exporter).with_batch_config(batch).build())
+        .with_resource(Resource::builder_empty().with_service_name(config.service_name).build())
+        .build();
+    PROVIDER.set(provider.clone()).map_err(|_| "tracing is already started")?;
+
+    global::set_tracer_provider(provider);
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    Ok(())
+}
+
+
+/// Start recording spans (unavailable: built without the `otel` feature).
+#[cfg(not(feature = "otel"))]
+pub fn init(config: OtlpConfig) -> Result<(), String> {
+    let _ = config;
+    Err("kvstore was built without the `otel` feature".to_string())
+}
+
+
+/// Export the spans still queued, e.g. before the process exits.
+///
+/// Does nothing if [`init`] was never called; applications that installed
+/// their own provider shut it down themselves.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("otel: final export failed: {}", e);
+    }
+}
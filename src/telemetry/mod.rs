@@ -0,0 +1,39 @@
+// =====================================================================
+// File: telemetry/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `telemetry` module traces commands and storage work as
+//! OpenTelemetry spans, through the `opentelemetry` crate's global tracer
+//! provider and propagator.
+//!
+//! Structure:
+//! - `span.rs`     : [`span`] and the [`Span`] guard it returns, the
+//!   W3C [`SpanContext`], and [`with_parent`] and [`with_remote_parent`],
+//!   which make spans opened in a closure children of a caller's trace.
+//! - `exporter.rs` : [`OtlpConfig`], [`init`], which installs an OTLP
+//!   exporting provider, and [`shutdown`], which flushes it.
+//! - `tests.rs`    : Unit tests for context propagation and the export.
+//!
+//! Spans are recorded only when kvstore is built with the `otel` feature
+//! and a tracer provider is installed, by the embedding application or
+//! by [`init`]; otherwise [`span`] returns an inert guard. They nest under
+//! the application's current `opentelemetry::Context`. Instrumented:
+//!
+//! - `kvstore.command` for every command line (`db.operation`)
+//! - `kvstore.http` for every REST request, parented by its `traceparent`
+//! - `kvstore.fsync` for every log flush (`kvstore.sync_mode`, `kvstore.durability`)
+//! - `kvstore.compaction.start` / `kvstore.compaction.step`
+//! - `kvstore.replication.publish`, `.sync`, `.apply` and `.full_sync`
+// =====================================================================
+
+pub mod exporter;
+pub mod span;
+
+pub use self::exporter::{init, shutdown, OtlpConfig};
+pub use self::span::{current_context, propagation_fields, span, with_parent, with_remote_parent, AttrValue, Span, SpanContext};
+
+#[cfg(test)]
+pub mod tests;
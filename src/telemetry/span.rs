@@ -0,0 +1,353 @@
+// =====================================================================
+// File: telemetry/span.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Spans and their context.
+//
+//   A [`Span`] is a guard: it starts when [`span`] returns and ends when
+//   it is dropped. With the `otel` feature it is an OpenTelemetry span
+//   from the global tracer provider (see [`opentelemetry::global`]),
+//   opened under the current [`opentelemetry::Context`] and attached as
+//   current while it is open, so spans opened meanwhile (by kvstore or
+//   the embedding application) become its children. Without the feature
+//   spans are inert, and only the parents given to [`with_parent`] are
+//   tracked, for [`current_context`].
+// =====================================================================
+
+#[cfg(not(feature = "otel"))]
+use std::cell::RefCell;
+
+#[cfg(feature = "otel")]
+use opentelemetry::propagation::Extractor;
+#[cfg(feature = "otel")]
+use opentelemetry::trace::{Span as _, Status, TraceContextExt, TraceFlags, TraceState, Tracer as _};
+#[cfg(feature = "otel")]
+use opentelemetry::{global, Context, ContextGuard, KeyValue, Value};
+
+#[cfg(not(feature = "otel"))]
+thread_local! {
+    /// Parents given to [`with_parent`] on this thread, innermost last.
+    static CURRENT: RefCell<Vec<SpanContext>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Identifies a span within a trace, as a W3C `traceparent` header does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanContext {
+    /// 16-byte trace id shared by every span of the trace.
+    pub trace_id: u128,
+
+    /// 8-byte id of this span.
+    pub span_id: u64,
+
+    /// Whether the trace is being recorded upstream.
+    pub sampled: bool,
+}
+
+
+impl SpanContext {
+    /// Parse a `traceparent` header (`00-<trace id>-<span id>-<flags>`).
+    ///
+    /// # Returns
+    /// `None` for a malformed header or an all-zero id.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::SpanContext;
+    /// let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    /// let context = SpanContext::parse_traceparent(header).unwrap();
+    /// assert_eq!(context.span_id, 0x00f0_67aa_0ba9_02b7);
+    /// assert!(context.sampled);
+    /// assert_eq!(context.traceparent(), header);
+    /// assert!(SpanContext::parse_traceparent("00-0-0-01").is_none());
+    /// ```
+    pub fn parse_traceparent(header: &str) -> Option<SpanContext> {
+        let mut parts = header.trim().split('-');
+        let (version, trace, span, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        let hex = |text: &str, len: usize| (text.len() == len && text.bytes().all(|b| b.is_ascii_hexdigit())).then_some(());
+        hex(version, 2)?;
+        hex(trace, 32)?;
+        hex(span, 16)?;
+        hex(flags, 2)?;
+        if version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        let context = SpanContext {
+            trace_id: u128::from_str_radix(trace, 16).ok()?,
+            span_id: u64::from_str_radix(span, 16).ok()?,
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+        };
+        (context.trace_id != 0 && context.span_id != 0).then_some(context)
+    }
+
+
+    /// This context as a `traceparent` header, for calls made from it.
+    pub fn traceparent(&self) -> String {
+        format!("00-{:032x}-{:016x}-{:02x}", self.trace_id, self.span_id, u8::from(self.sampled))
+    }
+}
+
+
+#[cfg(feature = "otel")]
+impl From<SpanContext> for opentelemetry::trace::SpanContext {
+    fn from(context: SpanContext) -> Self {
+        let flags = if context.sampled { TraceFlags::SAMPLED } else { TraceFlags::default() };
+        opentelemetry::trace::SpanContext::new(context.trace_id.into(), context.span_id.into(), flags, true, TraceState::default())
+    }
+}
+
+
+/// An attribute value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+
+impl From<&str> for AttrValue {
+    fn from(value: &str) -> Self {
+        AttrValue::Str(value.to_string())
+    }
+}
+
+
+impl From<String> for AttrValue {
+    fn from(value: String) -> Self {
+        AttrValue::Str(value)
+    }
+}
+
+
+impl From<i64> for AttrValue {
+    fn from(value: i64) -> Self {
+        AttrValue::Int(value)
+    }
+}
+
+
+impl From<u64> for AttrValue {
+    fn from(value: u64) -> Self {
+        AttrValue::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+
+impl From<usize> for AttrValue {
+    fn from(value: usize) -> Self {
+        AttrValue::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+
+impl From<bool> for AttrValue {
+    fn from(value: bool) -> Self {
+        AttrValue::Bool(value)
+    }
+}
+
+
+#[cfg(feature = "otel")]
+impl From<AttrValue> for Value {
+    fn from(value: AttrValue) -> Self {
+        match value {
+            AttrValue::Str(text) => Value::from(text),
+            AttrValue::Int(n) => Value::I64(n),
+            AttrValue::Bool(b) => Value::Bool(b),
+        }
+    }
+}
+
+
+/// Context of the innermost span open on this thread (or of the parent
+/// given to [`with_parent`]).
+pub fn current_context() -> Option<SpanContext> {
+    #[cfg(feature = "otel")]
+    {
+        let current = Context::current();
+        let context = current.span().span_context().clone();
+        context.is_valid().then(|| SpanContext {
+            trace_id: u128::from_be_bytes(context.trace_id().to_bytes()),
+            span_id: u64::from_be_bytes(context.span_id().to_bytes()),
+            sampled: context.is_sampled(),
+        })
+    }
+    #[cfg(not(feature = "otel"))]
+    CURRENT.with(|current| current.borrow().last().copied())
+}
+
+
+/// Run `f` with `parent` as the current span, so the spans it opens join
+/// the caller's trace.
+///
+/// # Example
+/// ```
+/// use kvstore::telemetry::{current_context, with_parent};
+/// use kvstore::SpanContext;
+/// let parent = SpanContext::parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+/// assert_eq!(with_parent(parent, current_context), Some(parent));
+/// assert_eq!(current_context(), None);
+/// ```
+pub fn with_parent<R>(parent: SpanContext, f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "otel")]
+    {
+        let _attached = Context::current().with_remote_span_context(parent.into()).attach();
+        f()
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        /// Pops the parent even if `f` panics.
+        struct Pop;
+        impl Drop for Pop {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.borrow_mut().pop());
+            }
+        }
+
+        CURRENT.with(|current| current.borrow_mut().push(parent));
+        let _pop = Pop;
+        f()
+    }
+}
+
+
+/// Names of the headers that carry trace context into a request, as
+/// the global propagator reads them (just `traceparent` without the
+/// `otel` feature).
+pub fn propagation_fields() -> Vec<String> {
+    #[cfg(feature = "otel")]
+    return global::get_text_map_propagator(|propagator| propagator.fields().map(str::to_string).collect());
+    #[cfg(not(feature = "otel"))]
+    vec!["traceparent".to_string()]
+}
+
+
+/// Run `f` inside the trace that a request's `headers` carry (see
+/// [`propagation_fields`]), extracted by the global propagator, so its
+/// spans join the caller's trace. Header names match in any case.
+///
+/// # Example
+/// ```
+/// use kvstore::telemetry::with_remote_parent;
+/// let headers = vec![("Traceparent".to_string(), "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string())];
+/// assert_eq!(with_remote_parent(&headers, || 7), 7);
+/// ```
+pub fn with_remote_parent<R>(headers: &[(String, String)], f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "otel")]
+    {
+        /// Headers as the propagator reads them.
+        struct Carrier<'a>(&'a [(String, String)]);
+        impl Extractor for Carrier<'_> {
+            fn get(&self, key: &str) -> Option<&str> {
+                self.0.iter().find(|(name, _)| name.eq_ignore_ascii_case(key)).map(|(_, value)| value.as_str())
+            }
+
+            fn keys(&self) -> Vec<&str> {
+                self.0.iter().map(|(name, _)| name.as_str()).collect()
+            }
+        }
+
+        if headers.is_empty() {
+            return f();
+        }
+        let _attached = global::get_text_map_propagator(|propagator| propagator.extract(&Carrier(headers))).attach();
+        f()
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        let traceparent = headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("traceparent"));
+        match traceparent.and_then(|(_, value)| SpanContext::parse_traceparent(value)) {
+            Some(parent) => with_parent(parent, f),
+            None => f(),
+        }
+    }
+}
+
+
+/// An open span; it ends when dropped.
+#[must_use = "a span ends as soon as it is dropped"]
+pub struct Span {
+    /// The span's context, attached as current until the span ends.
+    #[cfg(feature = "otel")]
+    open: Option<(Context, ContextGuard)>,
+}
+
+
+/// Open a span as a child of the current one (or as a new trace's root).
+///
+/// Inert unless tracing is built in and a tracer provider is installed
+/// (by the embedding application, or by [`super::init`]), or when the
+/// provider's sampler drops it.
+///
+/// # Example
+/// ```
+/// let mut span = kvstore::telemetry::span("kvstore.example");
+/// span.attr("kvstore.keys", 2usize);
+/// drop(span);
+/// ```
+pub fn span(name: &'static str) -> Span {
+    #[cfg(feature = "otel")]
+    {
+        let span = global::tracer("kvstore").start(name);
+        if !span.is_recording() {
+            return Span { open: None };
+        }
+        let context = Context::current_with_span(span);
+        Span { open: Some((context.clone(), context.attach())) }
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = name;
+        Span {}
+    }
+}
+
+
+impl Span {
+    /// Whether this span will be exported.
+    pub fn is_recording(&self) -> bool {
+        #[cfg(feature = "otel")]
+        return self.open.is_some();
+        #[cfg(not(feature = "otel"))]
+        false
+    }
+
+
+    /// Set an attribute; values are only converted for recording spans.
+    pub fn attr(&mut self, key: &'static str, value: impl Into<AttrValue>) -> &mut Self {
+        #[cfg(feature = "otel")]
+        if let Some((context, _)) = &self.open {
+            context.span().set_attribute(KeyValue::new(key, Value::from(value.into())));
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = (key, value);
+        self
+    }
+
+
+    /// Mark the span failed.
+    pub fn error(&mut self, message: &str) -> &mut Self {
+        #[cfg(feature = "otel")]
+        if let Some((context, _)) = &self.open {
+            context.span().set_status(Status::error(message.to_string()));
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = message;
+        self
+    }
+}
+
+
+#[cfg(feature = "otel")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        // Detaching the context (the guard) restores the parent
+        if let Some((context, _attached)) = self.open.take() {
+            context.span().end();
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// =====================================================================
+// File: telemetry/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for span context propagation and the OTLP export.
+//
+// Notes:
+//   * Only compiled when running `cargo test`; the export tests also
+//     need `--features otel`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Span Context Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod context_tests {
+    use crate::telemetry::*;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn traceparent_headers_are_validated() {
+        let context = SpanContext::parse_traceparent(HEADER).unwrap();
+        assert_eq!(context.trace_id, 0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736);
+        assert!(!SpanContext::parse_traceparent(&HEADER.replace("-01", "-00")).unwrap().sampled);
+
+        for bad in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473g-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(SpanContext::parse_traceparent(bad), None, "{}", bad);
+        }
+        // Later versions may add fields
+        assert!(SpanContext::parse_traceparent(&format!("01{}-extra", &HEADER[2..])).is_some());
+    }
+
+    #[test]
+    fn parents_nest_and_unwind() {
+        let outer = SpanContext::parse_traceparent(HEADER).unwrap();
+        let inner = SpanContext { span_id: 7, ..outer };
+        with_parent(outer, || {
+            with_parent(inner, || assert_eq!(current_context(), Some(inner)));
+            assert_eq!(current_context(), Some(outer));
+        });
+        assert_eq!(current_context(), None);
+    }
+}
+
+
+// =====================================================================
+// OTLP Export Unit Tests
+// =====================================================================
+#[cfg(all(test, feature = "otel"))]
+mod export_tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::telemetry::*;
+    use crate::{execute_line, MemFs, Session};
+
+    /// A collector that answers `200` and hands over each request body.
+    fn fake_collector() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                tx.send(String::from_utf8(body).unwrap()).unwrap();
+            }
+        });
+        (format!("http://{}/v1/traces", addr), rx)
+    }
+
+    #[test]
+    fn commands_are_exported_inside_the_callers_trace() {
+        let (endpoint, bodies) = fake_collector();
+        let mut config = OtlpConfig::new(&endpoint);
+        config.interval = Duration::from_millis(20);
+        init(config).unwrap();
+        assert!(init(OtlpConfig::new(&endpoint)).is_err());
+
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        let header = |flags: &str| vec![("traceparent".to_string(), format!("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-{}", flags))];
+        with_remote_parent(&header("01"), || execute_line(b"SET traced 1", &mut session));
+        with_remote_parent(&header("00"), || execute_line(b"SET untraced 1", &mut session));
+        shutdown();
+
+        // Tests running alongside may export spans of their own
+        let trace = r#""traceId":"4bf92f3577b34da6a3ce929d0e0e4736""#;
+        let mut exported = String::new();
+        while !exported.contains(trace)
+            && let Ok(body) = bodies.recv_timeout(Duration::from_secs(2))
+        {
+            // The exporter pretty-prints; ids and names have no spaces
+            exported.extend(body.split_whitespace());
+        }
+        assert_eq!(exported.matches(trace).count(), 1, "{}", exported);
+        assert!(exported.contains(r#""parentSpanId":"00f067aa0ba902b7""#), "{}", exported);
+        assert!(exported.contains(r#""kvstore.command""#), "{}", exported);
+        assert!(exported.contains(r#""service.name""#), "{}", exported);
+    }
+}
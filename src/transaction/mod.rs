@@ -12,6 +12,7 @@
 //   database’s runtime state (index, TTL, and active transaction).
 //
 // =====================================================================
+#[allow(clippy::module_inception)]
 pub mod transaction;
 
 pub use self::transaction::Transaction;
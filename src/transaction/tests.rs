@@ -21,7 +21,14 @@
 mod transaction_tests {
 
     use super::super::transaction::Transaction;
-    use crate::BTreeIndex;
+    use crate::{close_log, replay_log, BTreeIndex};
+
+    // Unique log per test, so commits stay out of the working directory
+    fn test_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kvstore_tx_{}.db", name));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
 
     // -------------------------------------------------------------
     // Basic construction and initial state
@@ -69,11 +76,15 @@ mod transaction_tests {
         tx.set("dog".into(), "bark".into());
         tx.set("cat".into(), "meow".into());
 
+        let file = test_file("commit_all");
         let mut index = BTreeIndex::new(2);
-        tx.commit(&mut index);
+        tx.commit(&mut index, &file).unwrap();
 
         assert_eq!(index.search("dog"), Some("bark"));
         assert_eq!(index.search("cat"), Some("meow"));
+        assert_eq!(replay_log(&file).unwrap(), vec!["SET dog bark", "SET cat meow"]);
+        close_log(&file).unwrap();
+        let _ = std::fs::remove_file(&file);
         assert_eq!(tx.pending_count(), 0, "Pending list should clear after commit");
         assert!(tx.is_empty());
     }
@@ -85,7 +96,10 @@ mod transaction_tests {
 
         let mut tx = Transaction::new();
         tx.set("color".into(), "blue".into());
-        tx.commit(&mut index);
+        let file = test_file("commit_overwrite");
+        tx.commit(&mut index, &file).unwrap();
+        close_log(&file).unwrap();
+        let _ = std::fs::remove_file(&file);
 
         assert_eq!(index.search("color"), Some("blue"));
         assert!(tx.is_empty());
@@ -129,4 +143,35 @@ mod transaction_tests {
         let tx = Transaction::new();
         assert_eq!(tx.ttl_manager.active_count(), 0);
     }
+
+    #[test]
+    fn test_expire_stages_and_clear_discards_ttls() {
+        let mut tx = Transaction::new();
+        assert!(tx.expire("a", 5000));
+        assert!(!tx.expire("b", 0));
+        assert!(tx.ttl_manager.has_entry("a"));
+        assert!(tx.cleared_ttls.contains("b"));
+
+        // A later positive EXPIRE replaces a staged clear
+        assert!(tx.expire("b", 5000));
+        assert!(!tx.cleared_ttls.contains("b"));
+
+        tx.clear();
+        assert_eq!(tx.ttl_manager.active_count(), 0);
+        assert!(tx.cleared_ttls.is_empty());
+    }
+
+    #[test]
+    fn test_persist_stages_a_clear() {
+        let mut tx = Transaction::new();
+        assert!(!tx.persist("a"));
+        assert!(tx.cleared_ttls.contains("a"));
+
+        // It drops a TTL staged before it, and a later EXPIRE wins again
+        assert!(tx.expire("b", 5000));
+        assert!(tx.persist("b"));
+        assert!(!tx.ttl_manager.has_entry("b") && tx.cleared_ttls.contains("b"));
+        assert!(tx.expire("b", 5000));
+        assert!(!tx.cleared_ttls.contains("b"));
+    }
 }
@@ -17,6 +17,8 @@
 // =====================================================================
 use crate::{BTreeIndex, TTLManager};
 use crate::storage;
+use std::collections::HashSet;
+use std::io;
 
 /// Represents a single active transaction session.
 /// Holds all pending writes and their temporary TTL metadata.
@@ -25,7 +27,18 @@ pub struct Transaction {
     pub pending: Vec<(String, String)>,
 
     /// Per-transaction TTL manager (for temporary expirations).
+    /// Holds the TTLs staged by `EXPIRE`, applied only on commit.
     pub ttl_manager: TTLManager,
+
+    /// Keys whose TTL an `EXPIRE <key> 0` (or negative) will clear on commit.
+    pub cleared_ttls: HashSet<String>,
+}
+
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 
@@ -35,6 +48,7 @@ impl Transaction {
         Self {
             pending: Vec::new(),
             ttl_manager: TTLManager::new(),
+            cleared_ttls: HashSet::new(),
         }
     }
 
@@ -52,24 +66,84 @@ impl Transaction {
     }
 
 
+    /// Stages an `EXPIRE` so it takes effect only if the transaction commits.
+    ///
+    /// The deadline counts from now, as it would outside a transaction.
+    /// A zero or negative duration stages clearing the key's TTL.
+    ///
+    /// # Returns
+    /// `true` if a TTL was staged, `false` for a clear.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Transaction;
+    /// let mut tx = Transaction::new();
+    /// assert!(tx.expire("user1", 5000));
+    /// assert!(tx.ttl_manager.has_entry("user1"));
+    /// assert!(!tx.expire("user1", 0));
+    /// assert!(tx.cleared_ttls.contains("user1"));
+    /// ```
+    pub fn expire(&mut self, key: &str, ms: i64) -> bool {
+        if self.ttl_manager.set_expiration(key, ms) {
+            self.cleared_ttls.remove(key);
+            true
+        } else {
+            self.cleared_ttls.insert(key.to_string());
+            false
+        }
+    }
+
+
+    /// Stages a `PERSIST` so the key's TTL is cleared only if the
+    /// transaction commits. A TTL staged for the key earlier in the
+    /// transaction is dropped now.
+    ///
+    /// # Returns
+    /// `true` if a TTL was staged for the key.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::Transaction;
+    /// let mut tx = Transaction::new();
+    /// tx.expire("user1", 5000);
+    /// assert!(tx.persist("user1"));
+    /// assert!(!tx.ttl_manager.has_entry("user1"));
+    /// assert!(tx.cleared_ttls.contains("user1"));
+    /// ```
+    pub fn persist(&mut self, key: &str) -> bool {
+        self.cleared_ttls.insert(key.to_string());
+        self.ttl_manager.clear_expiration(key)
+    }
+
+
     /// Commits all pending writes into the main BTree index.
     ///
-    /// Writes are applied in insertion order, and also appended to
-    /// the persistent log as plain SET commands so they survive
-    /// process restarts.
-    pub fn commit(&mut self, index: &mut BTreeIndex) {
+    /// Writes are appended to the persistent log as plain SET commands
+    /// (one append and sync for the lot) so they survive process
+    /// restarts, then applied in insertion order. Staged TTLs need the session's TTL manager and
+    /// are applied by [`crate::Session::commit_transaction`]; here they
+    /// are discarded.
+    ///
+    /// # Arguments
+    /// * `index` - The index the writes are applied to.
+    /// * `data_file` - The log the `SET` records are appended to.
+    ///
+    /// # Returns
+    /// `Err(io::Error)` if the append fails; nothing is applied and the
+    /// buffers are left as they were.
+    pub fn commit(&mut self, index: &mut BTreeIndex, data_file: &str) -> io::Result<()> {
+        // Log every write as a SET command in one append and sync
+        let lines: Vec<String> = self.pending.iter().map(|(k, v)| storage::seal_record(&storage::set_record(k, v))).collect();
+        storage::append_many(data_file, &lines)?;
+
+        // Then apply to the in-memory index
         for (k, v) in &self.pending {
-            // Apply to in-memory index
             index.insert(k.clone(), v.clone());
-
-            // Also append to disk log as a SET command
-            let line = format!("SET {} {}", k, v);
-            let _ = storage::append_write(&storage::get_data_file(), &line);
         }
 
         // Clear transaction buffers
-        self.pending.clear();
-        self.ttl_manager.clear();
+        self.clear();
+        Ok(())
     }
 
 
@@ -80,6 +154,7 @@ impl Transaction {
     pub fn clear(&mut self) {
         self.pending.clear();
         self.ttl_manager.clear();
+        self.cleared_ttls.clear();
     }
 
     /// Returns the number of pending writes in the buffer.
@@ -10,19 +10,37 @@
 //! and calculates remaining lifespan on demand.
 //!
 //! Expiration is handled lazily — keys are only considered expired
-//! at read time.
+//! at read time. Deadlines are also kept in time order so a bounded
+//! number of expired keys can be reclaimed per command
+//! ([`expire_due`](TTLManager::expire_due)) without scanning every TTL.
+//!
+//! The key-to-deadline map is a [`HashTrie`], so
+//! [`expirations`](TTLManager::expirations) hands a snapshot the TTLs in
+//! O(1) and later writes copy only the entries they touch.
 // =====================================================================
 
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::collections::{BTreeSet, HashSet};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::shared::HashTrie;
+
+/// Milliseconds since the Unix epoch, the clock the log's `EXPIREAT`
+/// records use.
+pub fn unix_now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
 
 /// Manages TTL metadata for keys in the key–value store.
 ///
 /// This structure stores expiration timestamps for each key.
 /// Expired entries are removed lazily when accessed.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct TTLManager {
-    expirations: HashMap<String, Instant>,
+    expirations: HashTrie<String, Instant>,
+
+    /// The same entries ordered by deadline (soonest first).
+    deadlines: BTreeSet<(Instant, String)>,
 }
 
 
@@ -30,7 +48,8 @@ impl TTLManager {
     /// Create a new, empty TTL manager.
     pub fn new() -> Self {
         Self {
-            expirations: HashMap::new(),
+            expirations: HashTrie::new(),
+            deadlines: BTreeSet::new(),
         }
     }
 
@@ -68,7 +87,7 @@ impl TTLManager {
 
         // Reject negative durations and remove existing expirations
         if time_ms <= 0 {
-            self.expirations.remove(key);
+            self.remove_entry(key);
             return false;
         }
 
@@ -76,7 +95,10 @@ impl TTLManager {
         let expiration_time = Instant::now() + Duration::from_millis(time_ms as u64);
 
         // Record/update the expiration entry
-        self.expirations.insert(key.to_string(), expiration_time);
+        if let Some(old) = self.expirations.insert(key.to_string(), expiration_time) {
+            self.deadlines.remove(&(old, key.to_string()));
+        }
+        self.deadlines.insert((expiration_time, key.to_string()));
 
         // Indicate success
         true
@@ -161,7 +183,7 @@ impl TTLManager {
         // Remove the key’s expiration entry from the map
         // println!("[TTL-DEBUG] clear_expiration key='{}'", key);
 
-        self.expirations.remove(key).is_some()
+        self.remove_entry(key)
     }
 
 
@@ -208,7 +230,7 @@ impl TTLManager {
 
             if now >= exp_at {
                 // println!("[TTL-DEBUG] is_expired: EXPIRED key='{}' -> removing", key);
-                self.expirations.remove(key);
+                self.remove_entry(key);
                 return true;
             }
 
@@ -221,6 +243,28 @@ impl TTLManager {
     }
 
 
+    /// `true` if `key` has a TTL that ran out at or before `now`.
+    ///
+    /// Unlike [`is_expired`](Self::is_expired) this leaves the entry in
+    /// place, so a reader can check key after key against one clock
+    /// reading without needing `&mut`.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::TTLManager;
+    /// use std::time::{Duration, Instant};
+    /// let mut ttl = TTLManager::new();
+    /// ttl.set_expiration("soon", 50);
+    /// let later = Instant::now() + Duration::from_millis(100);
+    /// assert!(!ttl.expired_at("soon", Instant::now()));
+    /// assert!(ttl.expired_at("soon", later));
+    /// assert!(!ttl.expired_at("no_ttl", later));
+    /// ```
+    pub fn expired_at(&self, key: &str, now: Instant) -> bool {
+        self.expirations.get(key).is_some_and(|&exp_at| now >= exp_at)
+    }
+
+
     /// Retrieve the remaining time-to-live (TTL) for a key, in milliseconds.
     ///
     /// Calculates how much time remains before a key’s expiration.
@@ -236,10 +280,12 @@ impl TTLManager {
     /// * **-2** — The key is missing or its expiration has already passed.
     ///
     /// # Notes
-    /// - Expired entries are removed from the internal map when encountered.
+    /// - Expired entries are reported as `-2` but left in place; removal
+    ///   happens lazily through [`is_expired`](Self::is_expired).
     /// - For reliability, monotonic time is used.
     /// - This function does not verify whether the key exists in the
-    ///   main index; it only reports TTL metadata.
+    ///   main index; it only reports TTL metadata. The `TTL` command uses
+    ///   [`Session::ttl_status`](crate::Session::ttl_status), which does.
     ///
     /// # Example
     /// ```
@@ -255,7 +301,7 @@ impl TTLManager {
     /// assert_eq!(ttl.ttl_remaining("temp"), -2);
     /// assert!(ttl.is_expired("temp"));
     /// ```
-    pub fn ttl_remaining(&mut self, key: &str) -> i64 {
+    pub fn ttl_remaining(&self, key: &str) -> i64 {
         //println!("[TTL-DEBUG] ttl_remaining key='{}'", key);
 
         // Is there a TTL entry?
@@ -266,8 +312,6 @@ impl TTLManager {
 
             // Expired?
             if now >= exp_at {
-                //println!("[TTL-DEBUG] ttl_remaining EXPIRED key='{}' -> removing", key);
-                self.expirations.remove(key);
                 return -2;
             }
 
@@ -317,6 +361,7 @@ impl TTLManager {
     /// ```
     pub fn clear(&mut self) {
         self.expirations.clear();
+        self.deadlines.clear();
     }
 
 
@@ -335,14 +380,15 @@ impl TTLManager {
     /// assert_eq!(ttl.get_expiration("k"), -1);
     /// ```
     pub fn persist(&mut self, key: &str) -> bool {
-        self.expirations.remove(key).is_some()
+        self.remove_entry(key)
     }
 
 
     /// Remove all expired keys from the TTL map.
     ///
-    /// This performs lazy cleanup of any entries whose expiration time
-    /// has already passed. Safe to call periodically.
+    /// Entries are popped from the deadline-ordered set until the first
+    /// one still in the future, so only expired entries are visited,
+    /// not every tracked TTL. Safe to call periodically.
     ///
     /// # Example
     /// ```
@@ -357,8 +403,186 @@ impl TTLManager {
     /// assert_eq!(ttl.active_count(), 0);
     /// ```
     pub fn cleanup_expired(&mut self) {
+        self.expire_due(usize::MAX);
+    }
+
+
+    /// Remove up to `budget` expired entries, soonest deadline first.
+    ///
+    /// Only expired entries are visited, so the cost is bounded by
+    /// `budget` no matter how many TTLs are tracked.
+    ///
+    /// # Returns
+    /// The keys whose TTL elapsed; the caller drops them from the index.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::manager::TTLManager;
+    /// use std::thread::sleep;
+    /// use std::time::Duration;
+    ///
+    /// let mut ttl = TTLManager::new();
+    /// for key in ["a", "b", "c"] {
+    ///     ttl.set_expiration(key, 10);
+    /// }
+    /// ttl.set_expiration("later", 60_000);
+    /// sleep(Duration::from_millis(20));
+    ///
+    /// assert_eq!(ttl.expire_due(2).len(), 2);
+    /// assert_eq!(ttl.expire_due(2).len(), 1);
+    /// assert_eq!(ttl.active_count(), 1);
+    /// ```
+    pub fn expire_due(&mut self, budget: usize) -> Vec<String> {
         let now = Instant::now();
-        self.expirations.retain(|_, &mut exp| exp > now);
+        let mut expired = Vec::new();
+
+        while expired.len() < budget {
+            match self.deadlines.first() {
+                Some((deadline, _)) if *deadline <= now => {}
+                _ => break,
+            }
+            if let Some((_, key)) = self.deadlines.pop_first() {
+                self.expirations.remove(&key);
+                expired.push(key);
+            }
+        }
+        expired
+    }
+
+
+    /// Up to `budget` keys whose TTL has elapsed, soonest deadline first,
+    /// left in place.
+    ///
+    /// For a caller that must log each removal before dropping the entry,
+    /// so a removal that can't be logged leaves the TTL to retry.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::manager::TTLManager;
+    /// let mut ttl = TTLManager::new();
+    /// ttl.set_expiration("first", 1);
+    /// ttl.set_expiration("second", 2);
+    /// ttl.set_expiration("later", 60_000);
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    ///
+    /// assert_eq!(ttl.due_keys(1), ["first"]);
+    /// assert_eq!(ttl.due_keys(5), ["first", "second"]);
+    /// assert_eq!(ttl.active_count(), 3);
+    /// ```
+    pub fn due_keys(&self, budget: usize) -> Vec<String> {
+        let now = Instant::now();
+        self.deadlines
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .take(budget)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+
+    /// Keys whose TTL has elapsed, evaluated once against a single `now`.
+    ///
+    /// Unlike [`is_expired`](Self::is_expired) this does not remove
+    /// anything, so a caller can filter a whole scan against one
+    /// consistent view and purge the keys afterwards.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::manager::TTLManager;
+    /// let mut ttl = TTLManager::new();
+    /// ttl.set_expiration("soon", 1);
+    /// ttl.set_expiration("later", 60_000);
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    ///
+    /// let expired = ttl.expired_keys();
+    /// assert!(expired.contains("soon") && !expired.contains("later"));
+    /// assert_eq!(ttl.active_count(), 2);
+    /// ```
+    pub fn expired_keys(&self) -> HashSet<&str> {
+        let now = Instant::now();
+        self.deadlines
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .map(|(_, key)| key.as_str())
+            .collect()
+    }
+
+
+    /// Move every entry of `other` into this manager, replacing any TTL
+    /// already set for the same key. Deadlines are kept as they are.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::manager::TTLManager;
+    /// let mut ttl = TTLManager::new();
+    /// ttl.set_expiration("a", 60_000);
+    /// let mut staged = TTLManager::new();
+    /// staged.set_expiration("a", 100);
+    /// staged.set_expiration("b", 100);
+    ///
+    /// ttl.merge(staged);
+    /// assert_eq!(ttl.active_count(), 2);
+    /// assert!(ttl.get_expiration("a") <= 100);
+    /// ```
+    pub fn merge(&mut self, other: TTLManager) {
+        for (key, &deadline) in other.expirations.iter() {
+            let key = key.clone();
+            if let Some(old) = self.expirations.insert(key.clone(), deadline) {
+                self.deadlines.remove(&(old, key.clone()));
+            }
+            self.deadlines.insert((deadline, key));
+        }
+    }
+
+
+    /// Drop a key's entry from both maps.
+    fn remove_entry(&mut self, key: &str) -> bool {
+        match self.expirations.remove(key) {
+            Some(deadline) => self.deadlines.remove(&(deadline, key.to_string())),
+            None => false,
+        }
+    }
+
+
+    /// Set a key's expiration to a wall-clock deadline (Unix milliseconds).
+    ///
+    /// The deadline is turned into the time left now, so a TTL read back
+    /// from the log keeps counting down across a restart.
+    ///
+    /// # Returns
+    /// `false` if the deadline has already passed (any TTL is removed),
+    /// as [`set_expiration`](Self::set_expiration) does for a zero TTL.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::{unix_now_ms, TTLManager};
+    /// let mut ttl = TTLManager::new();
+    /// assert!(ttl.set_expires_at("dog", unix_now_ms() + 60_000));
+    /// assert!(ttl.get_expiration("dog") > 59_000);
+    /// assert!(!ttl.set_expires_at("cat", unix_now_ms() - 1));
+    /// ```
+    pub fn set_expires_at(&mut self, key: &str, unix_ms: u64) -> bool {
+        self.set_expiration(key, unix_ms.saturating_sub(unix_now_ms()) as i64)
+    }
+
+
+    /// A key's deadline in Unix milliseconds, if it has a TTL that has
+    /// not elapsed.
+    pub fn expires_at(&self, key: &str) -> Option<u64> {
+        let remaining = self.ttl_remaining(key);
+        (remaining >= 0).then(|| unix_now_ms() + remaining as u64)
+    }
+
+
+    /// Every TTL that has not elapsed, as `(key, Unix ms deadline)`,
+    /// soonest first.
+    pub fn deadlines_unix_ms(&self) -> Vec<(String, u64)> {
+        let (now, unix_now) = (Instant::now(), unix_now_ms());
+        self.deadlines
+            .iter()
+            .filter(|(deadline, _)| *deadline > now)
+            .map(|(deadline, key)| (key.clone(), unix_now + deadline.duration_since(now).as_millis() as u64))
+            .collect()
     }
 
 
@@ -369,4 +593,92 @@ impl TTLManager {
     pub fn has_entry(&self, key: &str) -> bool {
         self.expirations.contains_key(key)
     }
+
+
+    /// The key-to-deadline map as it is now, for a reader on another
+    /// thread. O(1): the map's nodes are shared until this manager
+    /// changes them.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::ttl::TTLManager;
+    /// let mut ttl = TTLManager::new();
+    /// ttl.set_expiration("dog", 60_000);
+    /// let view = ttl.expirations();
+    /// ttl.clear_expiration("dog");
+    /// assert!(view.get_expiration("dog") > 0);
+    /// assert_eq!(ttl.get_expiration("dog"), -1);
+    /// ```
+    pub fn expirations(&self) -> Expirations {
+        Expirations(self.expirations.clone())
+    }
+}
+
+
+/// Point-in-time TTLs taken with [`TTLManager::expirations`].
+///
+/// Without the deadline order a manager keeps, so nothing can be reclaimed
+/// through it; a read replica that applies `EXPIREAT` and `PERSIST`
+/// records can still set and clear entries.
+#[derive(Debug, Default, Clone)]
+pub struct Expirations(HashTrie<String, Instant>);
+
+
+impl Expirations {
+    /// Remaining milliseconds for `key`, `-1` without a TTL, `-2` once
+    /// expired, as [`TTLManager::get_expiration`].
+    pub fn get_expiration(&self, key: &str) -> i64 {
+        match self.0.get(key) {
+            Some(&exp_at) => {
+                let now = Instant::now();
+                if now >= exp_at { -2 } else { exp_at.duration_since(now).as_millis() as i64 }
+            }
+            None => -1,
+        }
+    }
+
+
+    /// `true` if `key` has a TTL that ran out at or before `now`.
+    pub fn expired_at(&self, key: &str, now: Instant) -> bool {
+        self.0.get(key).is_some_and(|&exp_at| now >= exp_at)
+    }
+
+
+    /// Set a key's expiration to a wall-clock deadline (Unix milliseconds).
+    ///
+    /// # Returns
+    /// `false` if the deadline has already passed (any TTL is removed).
+    pub fn set_expires_at(&mut self, key: &str, unix_ms: u64) -> bool {
+        let remaining = unix_ms.saturating_sub(unix_now_ms());
+        if remaining == 0 {
+            self.0.remove(key);
+            return false;
+        }
+        self.0.insert(key.to_string(), Instant::now() + Duration::from_millis(remaining));
+        true
+    }
+
+
+    /// Remove the TTL for `key`; `true` if it had one.
+    pub fn clear_expiration(&mut self, key: &str) -> bool {
+        self.0.remove(key).is_some()
+    }
+
+
+    /// Remove every TTL.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+
+    /// Number of TTL entries, elapsed or not.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+
+    /// Returns `true` if no key has a TTL.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
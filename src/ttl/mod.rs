@@ -10,17 +10,23 @@
 //!
 //! Structure:
 //! - `manager.rs` : Defines the [`TTLManager`] structure and its methods
-//!                  (`set_expiry`, `is_expired`, `ttl_remaining`, `clear_expiry`).
+//!   (`set_expiry`, `is_expired`, `ttl_remaining`, `clear_expiry`,
+//!   `expire_due` for budgeted cleanup, and wall-clock deadlines for the
+//!   `EXPIREAT` records the log keeps), plus [`Expirations`], the
+//!   point-in-time TTLs a snapshot reads.
 //! - `tests.rs`   : Unit tests for TTL behavior and command interactions.
 //!
 //! This organization separates TTL logic from the core index and persistence
-//! layers to maintain modularity and simplify future extensions (e.g. persistence
-//! of TTLs or background cleanup threads).
+//! layers to maintain modularity and simplify future extensions (e.g.
+//! background cleanup threads).
 // =====================================================================
 
 pub mod manager;
 
-pub use self::manager::TTLManager;
+pub use self::manager::{unix_now_ms, Expirations, TTLManager};
+
+/// Expired keys reclaimed per command unless configured otherwise.
+pub const DEFAULT_EXPIRE_BUDGET: usize = 20;
 
 #[cfg(test)]
 pub mod tests;
@@ -121,6 +121,23 @@ mod ttl_manager_tests {
         assert_eq!(ttl.active_count(), 0);
     }
 
+    #[test]
+    fn expire_due_respects_budget_and_renewals() {
+        let mut ttl = make_manager();
+        for i in 0..5 {
+            ttl.set_expiration(&format!("short{i}"), 10);
+        }
+        // Renewed before expiring - must survive
+        ttl.set_expiration("short0", 60_000);
+        sleep(Duration::from_millis(30));
+
+        assert_eq!(ttl.expire_due(3).len(), 3);
+        assert_eq!(ttl.expire_due(3).len(), 1);
+        assert!(ttl.expire_due(3).is_empty());
+        assert!(ttl.get_expiration("short0") > 0);
+        assert_eq!(ttl.active_count(), 1);
+    }
+
     #[test]
     fn get_expiration_returns_minus_one_for_no_ttl() {
         let ttl = make_manager();
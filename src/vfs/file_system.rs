@@ -0,0 +1,121 @@
+// =====================================================================
+// File: vfs/file_system.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The [`Fs`] trait: the file operations the log needs, addressed by path.
+//!
+//! Appends take text records and add the newline; a log whose header
+//! is binary (format 5 on) gets each sealed record as its frame instead
+//! (see [`storage::Layout`]).
+//! Implementations must be shareable across threads because background
+//! loading replays the log on a worker.
+// =====================================================================
+
+use std::fmt::Debug;
+use std::io::{self, BufRead};
+use std::sync::Arc;
+
+use crate::crypt::LogKey;
+use crate::storage::{self, Layout};
+
+/// File operations used by the log, compaction and recovery.
+pub trait Fs: Debug + Send + Sync {
+    /// Create the file if it is missing, leaving existing contents alone.
+    fn open(&self, path: &str) -> io::Result<()>;
+
+
+    /// Create the file, or empty it if it exists.
+    fn create(&self, path: &str) -> io::Result<()>;
+
+
+    /// Append `text` plus a newline, creating the file if needed, or
+    /// its records' frames in a binary log.
+    ///
+    /// `text` may hold several records separated by newlines.
+    ///
+    /// # Returns
+    /// The byte offset at which `text` starts.
+    fn append(&self, path: &str, text: &str) -> io::Result<u64>;
+
+
+    /// Append each of `records` plus a newline as a single append, so
+    /// the batch is written (and synced) once.
+    ///
+    /// # Returns
+    /// The byte offset at which each record starts.
+    fn append_many(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+        let start = self.append(path, &records.join("\n"))?;
+        Ok(storage::record_offsets(start, records))
+    }
+
+
+    /// Append `bytes` as they are, creating the file if needed.
+    ///
+    /// # Returns
+    /// The byte offset at which they start.
+    fn append_raw(&self, path: &str, bytes: &[u8]) -> io::Result<u64>;
+
+
+    /// How the log's records are written, from its header; text for a
+    /// missing or blank file.
+    fn layout(&self, path: &str) -> io::Result<Layout>;
+
+
+    /// The whole file. May end in zero padding a reader must skip.
+    ///
+    /// # Returns
+    /// `Err` of kind `NotFound` if the file does not exist.
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+
+    /// A buffered reader over the whole file, for reading it front to
+    /// back without holding it in memory. The default reads it all.
+    ///
+    /// # Returns
+    /// `Err` of kind `NotFound` if the file does not exist.
+    fn reader(&self, path: &str) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(io::Cursor::new(self.read(path)?)))
+    }
+
+
+    /// Exactly `len` bytes starting at `offset`.
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+
+    /// Offset the next append lands at; zero for a missing file.
+    fn end(&self, path: &str) -> io::Result<u64>;
+
+
+    /// Atomically replace `to` with `from`.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+
+
+    /// Make everything written to `path` durable.
+    fn sync(&self, path: &str) -> io::Result<()>;
+
+
+    /// Make the latest rename onto `path` durable by syncing the
+    /// directory that holds it. Nothing to do where renames are durable
+    /// as soon as they return, as in memory.
+    fn sync_dir(&self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+
+    /// Delete the file.
+    fn remove(&self, path: &str) -> io::Result<()>;
+
+
+    /// Key the logs read through this file system are decrypted with
+    /// (see [`crate::crypt::EncryptedFs`]); `None` reads them as
+    /// written.
+    fn log_key(&self) -> Option<Arc<LogKey>> {
+        None
+    }
+}
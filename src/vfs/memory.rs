@@ -0,0 +1,246 @@
+// =====================================================================
+// File: vfs/memory.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! [`MemFs`] keeps files in memory for hermetic tests.
+//!
+//! Each file remembers how much of it is durable. [`MemFs::new`] makes
+//! every append durable at once, like the default `all` sync mode;
+//! [`MemFs::unsynced`] keeps appends volatile until [`Fs::sync`].
+//! [`MemFs::crash`] then throws away everything not yet durable, and
+//! [`MemFs::set_full`] makes writes fail as on a full disk.
+// =====================================================================
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::storage::{self, Layout};
+use crate::vfs::Fs;
+
+/// One in-memory file.
+#[derive(Debug, Clone, Default)]
+struct MemFile {
+    /// Current contents.
+    data: Vec<u8>,
+
+    /// Length of the prefix that survives a crash.
+    synced: usize,
+}
+
+
+/// Files and fault settings behind the lock.
+#[derive(Debug, Default)]
+struct State {
+    files: HashMap<String, MemFile>,
+
+    /// Appends become durable immediately.
+    sync_appends: bool,
+
+    /// Writes fail with `StorageFull`.
+    full: bool,
+}
+
+
+/// An in-memory file system.
+#[derive(Debug, Default)]
+pub struct MemFs {
+    state: Mutex<State>,
+}
+
+
+impl MemFs {
+    /// An empty file system whose appends are durable at once.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Fs, MemFs};
+    /// let fs = MemFs::new();
+    /// fs.append("log", "SET a 1").unwrap();
+    /// fs.crash();
+    /// assert_eq!(fs.contents("log").unwrap(), b"SET a 1\n");
+    /// ```
+    pub fn new() -> Self {
+        let fs = Self::unsynced();
+        fs.lock().sync_appends = true;
+        fs
+    }
+
+
+    /// An empty file system whose appends are lost on a crash unless synced.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{Fs, MemFs};
+    /// let fs = MemFs::unsynced();
+    /// fs.append("log", "SET a 1").unwrap();
+    /// fs.sync("log").unwrap();
+    /// fs.append("log", "SET b 2").unwrap();
+    /// fs.crash();
+    /// assert_eq!(fs.contents("log").unwrap(), b"SET a 1\n");
+    /// ```
+    pub fn unsynced() -> Self {
+        Self::default()
+    }
+
+
+    /// Simulate power loss: every file shrinks to its durable prefix.
+    pub fn crash(&self) {
+        for file in self.lock().files.values_mut() {
+            file.data.truncate(file.synced);
+        }
+    }
+
+
+    /// Make appends and creates fail (`true`) or succeed again (`false`).
+    pub fn set_full(&self, full: bool) {
+        self.lock().full = full;
+    }
+
+
+    /// Replace a file with `bytes`, durably. Handy for seeding damaged logs.
+    pub fn write_file(&self, path: &str, bytes: &[u8]) {
+        let file = MemFile { data: bytes.to_vec(), synced: bytes.len() };
+        self.lock().files.insert(path.to_string(), file);
+    }
+
+
+    /// Current contents of a file, or `None` if it does not exist.
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.lock().files.get(path).map(|f| f.data.clone())
+    }
+
+
+    /// Append the bytes `encode` makes from the file's current contents,
+    /// creating it if needed.
+    ///
+    /// # Returns
+    /// Where they start, and what `encode` returned beside them.
+    fn append_with<T>(&self, path: &str, encode: impl FnOnce(&[u8]) -> io::Result<(Vec<u8>, T)>) -> io::Result<(u64, T)> {
+        let mut state = self.lock();
+        if state.full {
+            return Err(storage_full());
+        }
+        let sync = state.sync_appends;
+        let file = state.files.entry(path.to_string()).or_default();
+        let (bytes, extra) = encode(&file.data)?;
+        let start = file.data.len() as u64;
+        file.data.extend_from_slice(&bytes);
+        if sync {
+            file.synced = file.data.len();
+        }
+        Ok((start, extra))
+    }
+
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+
+/// Error for operations on a missing file.
+fn not_found(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} does not exist", path))
+}
+
+
+/// The layout `records` are appended in after `data`: the header's, or
+/// for a blank file the one they open with.
+fn layout_of<S: AsRef<str>>(data: &[u8], records: &[S]) -> Layout {
+    storage::head_layout(data).or_else(|| storage::first_layout(records)).unwrap_or_default()
+}
+
+
+/// Error for writes while the disk is full.
+fn storage_full() -> io::Error {
+    io::Error::new(io::ErrorKind::StorageFull, "no space left on device")
+}
+
+
+impl Fs for MemFs {
+    fn open(&self, path: &str) -> io::Result<()> {
+        self.lock().files.entry(path.to_string()).or_default();
+        Ok(())
+    }
+
+
+    fn create(&self, path: &str) -> io::Result<()> {
+        let mut state = self.lock();
+        if state.full {
+            return Err(storage_full());
+        }
+        state.files.insert(path.to_string(), MemFile::default());
+        Ok(())
+    }
+
+
+    fn append(&self, path: &str, text: &str) -> io::Result<u64> {
+        self.append_with(path, |data| storage::encode_records(layout_of(data, &[text]), &[text]))
+            .map(|(start, _)| start)
+    }
+
+
+    fn append_many(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (start, offsets) = self.append_with(path, |data| storage::encode_records(layout_of(data, records), records))?;
+        Ok(offsets.into_iter().map(|offset| start + offset).collect())
+    }
+
+
+    fn append_raw(&self, path: &str, bytes: &[u8]) -> io::Result<u64> {
+        self.append_with(path, |_| Ok((bytes.to_vec(), ()))).map(|(start, ())| start)
+    }
+
+
+    fn layout(&self, path: &str) -> io::Result<Layout> {
+        let state = self.lock();
+        Ok(state.files.get(path).map_or(Layout::Text, |f| storage::head_layout(&f.data).unwrap_or_default()))
+    }
+
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.contents(path).ok_or_else(|| not_found(path))
+    }
+
+
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let state = self.lock();
+        let data = &state.files.get(path).ok_or_else(|| not_found(path))?.data;
+        let start = offset as usize;
+        data.get(start..start + len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "read past end of file"))
+    }
+
+
+    fn end(&self, path: &str) -> io::Result<u64> {
+        Ok(self.lock().files.get(path).map_or(0, |f| f.data.len() as u64))
+    }
+
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut state = self.lock();
+        let file = state.files.remove(from).ok_or_else(|| not_found(from))?;
+        state.files.insert(to.to_string(), file);
+        Ok(())
+    }
+
+
+    fn sync(&self, path: &str) -> io::Result<()> {
+        let mut state = self.lock();
+        let file = state.files.get_mut(path).ok_or_else(|| not_found(path))?;
+        file.synced = file.data.len();
+        Ok(())
+    }
+
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        self.lock().files.remove(path).map(|_| ()).ok_or_else(|| not_found(path))
+    }
+}
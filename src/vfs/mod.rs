@@ -0,0 +1,41 @@
+// =====================================================================
+// File: vfs/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `vfs` module puts the log's file operations behind the [`Fs`]
+//! trait so storage, compaction and recovery can run against memory.
+//!
+//! Structure:
+//! - `file_system.rs` : Defines the [`Fs`] trait (open, append, read,
+//!   rename, sync, ...), one method per file operation the store needs.
+//! - `real.rs`        : [`RealFs`], the default, backed by real files and
+//!   the cached append handles in `storage`.
+//! - `memory.rs`      : [`MemFs`], an in-memory file system that can
+//!   simulate a crash (dropping unsynced bytes) and a full disk.
+//! - `null.rs`        : [`NullFs`], which accepts every write and keeps
+//!   none, for stores that need no persistence at all.
+//! - `tests.rs`       : Unit tests for the implementations and for
+//!   recovery and compaction running on [`MemFs`].
+//!
+//! The session keeps its file system in [`Session::fs`](crate::Session::fs);
+//! every append, cold read, replay and compaction goes through it. An
+//! implementation of these file operations gets the log format,
+//! checksums, checkpoints and compaction unchanged; the log-level
+//! [`StorageBackend`](crate::StorageBackend) trait is built on top.
+// =====================================================================
+
+pub mod file_system;
+pub mod memory;
+pub mod null;
+pub mod real;
+
+pub use self::file_system::Fs;
+pub use self::memory::MemFs;
+pub use self::null::NullFs;
+pub use self::real::RealFs;
+
+#[cfg(test)]
+pub mod tests;
@@ -0,0 +1,86 @@
+// =====================================================================
+// File: vfs/null.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! [`NullFs`] persists nothing: every write succeeds and is dropped.
+//!
+//! Reads find no file, so a session on it always loads empty, and
+//! compaction, checkpoints and recovery run through without touching
+//! a disk. Offsets are always zero, so it can't back a memory-limited
+//! session, whose evicted values must be read back from the log.
+// =====================================================================
+
+use std::io;
+
+use crate::storage::Layout;
+use crate::vfs::Fs;
+
+/// A file system that keeps nothing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullFs;
+
+
+/// Error for every read: nothing was ever written.
+fn not_found(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} is not persisted", path))
+}
+
+
+impl Fs for NullFs {
+    fn open(&self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+
+    fn create(&self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+
+    fn append(&self, _path: &str, _text: &str) -> io::Result<u64> {
+        Ok(0)
+    }
+
+
+    fn append_raw(&self, _path: &str, _bytes: &[u8]) -> io::Result<u64> {
+        Ok(0)
+    }
+
+
+    fn layout(&self, _path: &str) -> io::Result<Layout> {
+        Ok(Layout::Text)
+    }
+
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        Err(not_found(path))
+    }
+
+
+    fn read_at(&self, path: &str, _offset: u64, _len: usize) -> io::Result<Vec<u8>> {
+        Err(not_found(path))
+    }
+
+
+    fn end(&self, _path: &str) -> io::Result<u64> {
+        Ok(0)
+    }
+
+
+    fn rename(&self, _from: &str, _to: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+
+    fn sync(&self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+
+    fn remove(&self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
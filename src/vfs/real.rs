@@ -0,0 +1,109 @@
+// =====================================================================
+// File: vfs/real.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! [`RealFs`] runs the [`Fs`] operations on real files.
+//!
+//! Appends go through the cached, preallocating handles in `storage`,
+//! so they honor the process-wide sync mode. Operations that replace or
+//! truncate a file close its cached handle first.
+// =====================================================================
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::backend::FileBackend;
+use crate::storage::{self, Layout};
+use crate::vfs::Fs;
+
+/// The real file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+
+impl Fs for RealFs {
+    fn open(&self, path: &str) -> io::Result<()> {
+        OpenOptions::new().create(true).truncate(false).write(true).open(path).map(|_| ())
+    }
+
+
+    fn create(&self, path: &str) -> io::Result<()> {
+        storage::close_log(path)?;
+        File::create(path).map(|_| ())
+    }
+
+
+    fn append(&self, path: &str, text: &str) -> io::Result<u64> {
+        storage::append_write_at(path, text)
+    }
+
+
+    fn append_many(&self, path: &str, records: &[String]) -> io::Result<Vec<u64>> {
+        storage::append_many(path, records)
+    }
+
+
+    fn append_raw(&self, path: &str, bytes: &[u8]) -> io::Result<u64> {
+        storage::append_raw(path, bytes)
+    }
+
+
+    fn layout(&self, path: &str) -> io::Result<Layout> {
+        FileBackend::process().layout(path)
+    }
+
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+
+    fn reader(&self, path: &str) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+
+    fn read_at(&self, path: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0u8; len];
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+
+    fn end(&self, path: &str) -> io::Result<u64> {
+        storage::log_end(path)
+    }
+
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        // Both cached handles would point at the wrong file afterwards
+        storage::close_log(from)?;
+        storage::close_log(to)?;
+        fs::rename(from, to)
+    }
+
+
+    fn sync(&self, path: &str) -> io::Result<()> {
+        storage::close_log(path)?;
+        File::open(path)?.sync_all()
+    }
+
+
+    #[cfg(unix)]
+    fn sync_dir(&self, path: &str) -> io::Result<()> {
+        let dir = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        File::open(dir)?.sync_all()
+    }
+
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        storage::close_log(path)?;
+        fs::remove_file(path)
+    }
+}
@@ -0,0 +1,298 @@
+// =====================================================================
+// File: vfs/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for the file system implementations, and for recovery,
+//   compaction and write failures running entirely in memory.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// MemFs Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod mem_fs_tests {
+    use crate::{Fs, MemFs};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn crash_keeps_only_synced_bytes() {
+        let fs = MemFs::unsynced();
+        assert_eq!(fs.append("log", "SET a 1").unwrap(), 0);
+        fs.sync("log").unwrap();
+        assert_eq!(fs.append("log", "SET b 2").unwrap(), 8);
+        assert_eq!(fs.end("log").unwrap(), 16);
+
+        fs.crash();
+        assert_eq!(fs.read("log").unwrap(), b"SET a 1\n");
+        assert_eq!(fs.end("log").unwrap(), 8);
+    }
+
+    #[test]
+    fn read_at_rename_and_remove() {
+        let fs = MemFs::new();
+        fs.append("tmp", "SET a 1").unwrap();
+        assert_eq!(fs.read_at("tmp", 4, 1).unwrap(), b"a");
+        assert_eq!(fs.read_at("tmp", 6, 9).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        fs.write_file("log", b"old\n");
+        fs.rename("tmp", "log").unwrap();
+        assert_eq!(fs.contents("log").unwrap(), b"SET a 1\n");
+        assert!(fs.contents("tmp").is_none());
+
+        fs.remove("log").unwrap();
+        assert_eq!(fs.read("log").unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(fs.end("log").unwrap(), 0);
+    }
+
+    #[test]
+    fn full_disk_rejects_writes() {
+        let fs = MemFs::new();
+        fs.set_full(true);
+        assert_eq!(fs.append("log", "SET a 1").unwrap_err().kind(), ErrorKind::StorageFull);
+        assert!(fs.create("log").is_err());
+
+        fs.set_full(false);
+        assert!(fs.append("log", "SET a 1").is_ok());
+    }
+
+    #[test]
+    fn replace_file_is_all_or_nothing() {
+        let fs = MemFs::unsynced();
+        fs.write_file("log", b"old\n");
+        crate::replace_file(&fs, "log", "new").unwrap();
+        fs.crash();
+        assert_eq!(fs.contents("log").unwrap(), b"new\n");
+        assert!(fs.contents("log.tmp").is_none());
+
+        fs.set_full(true);
+        assert!(crate::replace_file(&fs, "log", "newer").is_err());
+        assert_eq!(fs.contents("log").unwrap(), b"new\n");
+        assert!(fs.contents("log.tmp").is_none());
+    }
+}
+
+
+// =====================================================================
+// RealFs Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod real_fs_tests {
+    use crate::{Fs, RealFs};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("kvstore_vfs_{}.db", name)).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn appends_and_renames_real_files() {
+        let (log, tmp) = (temp_path("real_log"), temp_path("real_tmp"));
+        let fs = RealFs;
+        fs.create(&log).unwrap();
+        fs.append(&log, "SET a 1").unwrap();
+        assert_eq!(fs.append(&log, "SET b 2").unwrap(), 8);
+        assert_eq!(fs.end(&log).unwrap(), 16);
+        assert_eq!(fs.read_at(&log, 12, 1).unwrap(), b"b");
+
+        fs.create(&tmp).unwrap();
+        fs.append(&tmp, "SET c 3").unwrap();
+        fs.sync(&tmp).unwrap();
+        fs.rename(&tmp, &log).unwrap();
+
+        // The cached handle was closed, so the next append lands in the new file
+        fs.append(&log, "SET d 4").unwrap();
+        fs.sync(&log).unwrap(); // trims the preallocated tail
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "SET c 3\nSET d 4\n");
+
+        crate::replace_file(&fs, &log, "SET e 5").unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "SET e 5\n");
+        assert!(!std::path::Path::new(&format!("{}.tmp", log)).exists());
+        fs.remove(&log).unwrap();
+    }
+}
+
+
+// =====================================================================
+// Session-on-MemFs Tests
+// =====================================================================
+#[cfg(test)]
+mod mem_session_tests {
+    use std::sync::Arc;
+
+    use crate::storage::{get_data_file, log_bytes, log_text_after};
+    use crate::{load_data, load_data_background, MemFs, ReplayIter, Session};
+
+    fn session_on(fs: &Arc<MemFs>) -> Session {
+        let mut session = Session::new();
+        session.fs = fs.clone();
+        session
+    }
+
+    #[test]
+    fn recovery_skips_a_torn_last_record() {
+        let fs = Arc::new(MemFs::new());
+        let log = get_data_file();
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.set("b".into(), "2".into());
+        assert!(session.delete("a"));
+
+        // Power loss in the middle of the next append
+        let mut bytes = fs.contents(&log).unwrap();
+        bytes.extend_from_slice(b"SET c");
+        fs.write_file(&log, &bytes);
+        fs.crash();
+
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, &log);
+        assert_eq!(restarted.get("a"), None);
+        assert_eq!(restarted.get("b"), Some("2".to_string()));
+        assert!(!restarted.exists("c"));
+    }
+
+    #[test]
+    fn compaction_runs_in_memory() {
+        let fs = Arc::new(MemFs::new());
+        let log = get_data_file();
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.set("a".into(), "2".into());
+        session.set("b".into(), "gone".into());
+        session.delete("b");
+
+        session.start_compaction().unwrap();
+        while !session.compaction_tick().unwrap() {}
+        assert_eq!(fs.contents(&log).unwrap(), log_bytes(&log_text_after(&["SET a 2"], 4)).unwrap());
+        assert!(fs.contents(&format!("{}.compact", log)).is_none());
+
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, &log);
+        assert_eq!(restarted.get("a"), Some("2".to_string()));
+    }
+
+    #[test]
+    fn key_only_reads_values_back_from_memory() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = Session::key_only();
+        session.fs = fs.clone();
+        session.set("wisc".into(), "value one".into());
+        assert_eq!(session.get("wisc"), Some("value one".to_string()));
+    }
+
+    #[test]
+    fn appends_are_numbered_across_restarts_and_compactions() {
+        let fs = Arc::new(MemFs::new());
+        let log = get_data_file();
+        let seqs = |fs: &MemFs| -> Vec<u64> {
+            let mut records = ReplayIter::open(fs, &log, 0).unwrap();
+            let mut seqs = Vec::new();
+            while let Some(record) = records.next() {
+                record.unwrap();
+                seqs.extend(crate::unseal_stamped(&records.sealed_line()).and_then(|(_, stamp)| stamp).map(|stamp| stamp.seq));
+            }
+            seqs
+        };
+        let mut session = session_on(&fs);
+        session.set("a".into(), "1".into());
+        session.mset(vec![("b".into(), "2".into()), ("c".into(), "3".into())]).unwrap();
+        assert_eq!(seqs(&fs), [1, 2, 3]);
+
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, &log);
+        restarted.set("a".into(), "4".into());
+        assert_eq!(seqs(&fs), [1, 2, 3, 4]);
+
+        // The compacted log keeps no stamps, but its header keeps the count
+        restarted.start_compaction().unwrap();
+        while !restarted.compaction_tick().unwrap() {}
+        assert!(seqs(&fs).is_empty());
+        let mut restarted = session_on(&fs);
+        load_data(&mut restarted, &log);
+        restarted.delete("b");
+        assert_eq!(seqs(&fs), [5]);
+    }
+
+    #[test]
+    fn full_disk_fails_writes_without_applying_them() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session_on(&fs);
+        session.read_only_after = 2;
+        session.set("kept".into(), "1".into());
+        fs.set_full(true);
+
+        let err = session.try_set("lost".into(), "1".into()).unwrap_err();
+        assert_eq!(err, "persistence failure: no space left on device");
+        assert!(!session.exists("lost"));
+        assert!(session.try_delete("kept").is_err());
+        assert!(session.exists("kept"));
+        assert!(session.read_only);
+
+        // Read-only stays on even after space frees up
+        fs.set_full(false);
+        assert!(session.try_set("lost".into(), "1".into()).is_err());
+    }
+
+    #[test]
+    fn background_load_reads_through_the_session_fs() {
+        let fs = Arc::new(MemFs::new());
+        let log = get_data_file();
+        fs.write_file(&log, b"SET a 1\nMSET b 2 c 3\nDEL b\n");
+
+        let mut session = session_on(&fs);
+        load_data_background(&mut session, &log);
+        session.finish_loading().unwrap();
+        assert_eq!(session.get("a"), Some("1".to_string()));
+        assert_eq!(session.get("b"), None);
+        assert_eq!(session.get("c"), Some("3".to_string()));
+    }
+}
+
+
+// =====================================================================
+// NullFs Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod null_fs_tests {
+    use std::sync::Arc;
+
+    use crate::{load_data, Fs, NullFs, Session};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn writes_succeed_and_nothing_is_kept() {
+        let fs = NullFs;
+        assert_eq!(fs.append("log", "SET a 1").unwrap(), 0);
+        fs.sync("log").unwrap();
+        fs.rename("log", "other").unwrap();
+        assert_eq!(fs.read("log").unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(fs.end("log").unwrap(), 0);
+    }
+
+    #[test]
+    fn session_runs_and_reloads_empty() {
+        let mut session = Session::new();
+        session.fs = Arc::new(NullFs);
+        session.set("a".into(), "1".into());
+        session.set("b".into(), "2".into());
+        assert!(session.delete("a"));
+        assert_eq!(session.checkpoint(), Ok(1));
+        session.start_compaction().unwrap();
+        while !session.compaction_tick().unwrap() {}
+        assert_eq!(session.get("b"), Some("2".to_string()));
+
+        let mut restarted = Session::new();
+        restarted.fs = Arc::new(NullFs);
+        let file = restarted.data_file.clone();
+        load_data(&mut restarted, &file);
+        assert!(!restarted.exists("b"));
+        assert!(!restarted.read_only);
+    }
+}
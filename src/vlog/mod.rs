@@ -0,0 +1,31 @@
+// =====================================================================
+// File: vlog/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+//! The `vlog` module keeps large values out of the log and the index
+//! (WiscKey-style key-value separation).
+//!
+//! Structure:
+//! - `value_log.rs` : Defines the [`ValueLog`], which decides which values
+//!   are separated and tracks where each separated value lives.
+//! - `tests.rs`     : Unit tests for the bookkeeping, separated writes,
+//!   restarts, compaction and garbage collection.
+//!
+//! A value at least the threshold long is appended, escaped, to the value
+//! log `<data file>.vlog.<generation>`, and the log gets a `VSET` record
+//! pointing at it instead of a `SET`. The index holds an empty value for
+//! the key, and reads go to the value log. Overwritten and deleted values
+//! are dead bytes until `COMPACT VALUES`
+//! ([`Session::collect_value_log`](crate::Session::collect_value_log))
+//! copies the live ones to the next generation and removes the old file.
+// =====================================================================
+
+pub mod value_log;
+
+pub use self::value_log::ValueLog;
+
+#[cfg(test)]
+pub mod tests;
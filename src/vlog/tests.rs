@@ -0,0 +1,159 @@
+// =====================================================================
+// File: vlog/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Unit tests for value-log bookkeeping, and for sessions that keep
+//   large values in the value log across writes, restarts, compaction
+//   and garbage collection.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Value Log Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod value_log_tests {
+    use std::sync::Arc;
+
+    use crate::{execute_line, load_data, replay_records, value_log_path, Fs, MemFs, Session, ValueLog, ValuePointer, ValueRef};
+
+    const BIG: &str = "a value long enough to be separated";
+
+    fn session(fs: &Arc<MemFs>) -> Session {
+        let mut session = Session::with_value_log(16);
+        session.fs = fs.clone();
+        session.data_file = "vlog.db".to_string();
+        session
+    }
+
+    fn reopen(fs: &Arc<MemFs>) -> Session {
+        let mut session = session(fs);
+        load_data(&mut session, "vlog.db");
+        session
+    }
+
+    fn text(fs: &MemFs, path: &str) -> String {
+        String::from_utf8(fs.read(path).unwrap()).unwrap()
+    }
+
+    /// The records the log at `path` replays, a line each.
+    fn records(fs: &MemFs, path: &str) -> String {
+        replay_records(fs, path, 0).unwrap().into_iter().map(|(_, record)| record).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn bookkeeping_counts_live_and_dead_bytes() {
+        let at = |generation, len| ValueRef { generation, ptr: ValuePointer { offset: 0, len, raw: false } };
+        let mut vlog = ValueLog::new(Some(8));
+        vlog.record("a", at(0, 10));
+        vlog.record("b", at(1, 20));
+        vlog.record("a", at(1, 5));
+        vlog.forget("b");
+        vlog.forget("missing");
+        assert_eq!((vlog.len(), vlog.live_bytes(), vlog.dead_bytes(), vlog.generation()), (1, 5, 30, 1));
+
+        vlog.start_generation(2);
+        assert_eq!((vlog.dead_bytes(), vlog.generation()), (0, 2));
+        vlog.clear();
+        assert!(vlog.is_empty());
+        assert_eq!((vlog.threshold(), vlog.generation()), (Some(8), 0));
+    }
+
+    #[test]
+    fn large_values_live_outside_the_log_and_the_index() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session(&fs);
+        session.mset(vec![("big".into(), BIG.into()), ("small".into(), "tiny".into())]).unwrap();
+
+        let log = records(&fs, "vlog.db");
+        assert!(log.contains("SET small tiny") && log.contains("VSET big 0 0 "), "{}", log);
+        assert!(!log.contains(BIG));
+        assert_eq!(text(&fs, &value_log_path("vlog.db", 0)), format!("{}\n", BIG.replace(' ', "\\s")));
+        assert_eq!(session.index.search("big"), Some(""));
+        assert_eq!(session.get_many(&["big", "small"]), vec![Some(BIG.to_string()), Some("tiny".to_string())]);
+
+        // An inline write replaces the separated value, and a delete forgets it
+        session.set("big".into(), "short".into());
+        assert_eq!(session.get("big"), Some("short".to_string()));
+        session.set("gone".into(), BIG.into());
+        assert!(session.delete("gone"));
+        assert!(session.value_log.is_empty());
+        assert_eq!(session.value_log.dead_bytes(), 2 * BIG.replace(' ', "\\s").len() as u64);
+    }
+
+    #[test]
+    fn separated_values_survive_restart_and_compaction() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session(&fs);
+        session.set("big".into(), BIG.into());
+        assert_eq!(session.expire("big", 60_000), Ok(true));
+        session.set("small".into(), "tiny".into());
+
+        // Readable even with separation switched off
+        let mut reopened = reopen(&fs);
+        reopened.value_log.set_threshold(None);
+        assert_eq!(reopened.get("big"), Some(BIG.to_string()));
+        assert!(reopened.ttl_status("big") > 0);
+
+        reopened.start_compaction().unwrap();
+        while !reopened.compaction_tick().unwrap() {}
+        assert!(records(&fs, "vlog.db").contains("VSET big 0 0 "));
+        assert_eq!(reopen(&fs).get("big"), Some(BIG.to_string()));
+    }
+
+    #[test]
+    fn collection_moves_live_values_and_removes_old_files() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session(&fs);
+        session.set("a".into(), format!("{} 1", BIG));
+        session.set("a".into(), format!("{} 2", BIG));
+        session.set("b".into(), format!("{} 3", BIG));
+        session.set("c".into(), format!("{} 4", BIG));
+        assert!(session.delete("c"));
+        let before = fs.end(&value_log_path("vlog.db", 0)).unwrap();
+
+        let reclaimed = session.collect_value_log().unwrap();
+        let after = fs.end(&value_log_path("vlog.db", 1)).unwrap();
+        assert_eq!(reclaimed, before - after);
+        assert!(fs.read(&value_log_path("vlog.db", 0)).is_err());
+        assert_eq!(session.value_log.dead_bytes(), 0);
+        assert_eq!(session.get("a"), Some(format!("{} 2", BIG)));
+
+        // New values land in the new generation, and a restart finds them all
+        session.set("d".into(), format!("{} 5", BIG));
+        assert_eq!(session.value_log.location("d").unwrap().generation, 1);
+        let mut reopened = reopen(&fs);
+        assert_eq!(reopened.get_many(&["a", "b", "c", "d"]), vec![
+            Some(format!("{} 2", BIG)),
+            Some(format!("{} 3", BIG)),
+            None,
+            Some(format!("{} 5", BIG)),
+        ]);
+        assert_eq!(reopened.value_log.generation(), 1);
+    }
+
+    #[test]
+    fn commands_collect_and_report_the_value_log() {
+        let fs = Arc::new(MemFs::new());
+        let mut session = session(&fs);
+        session.set("a".into(), BIG.into());
+        session.set("a".into(), BIG.into());
+        let (_, captured) = crate::capture_replies(4096, || {
+            execute_line(b"COMPACT VALUES", &mut session);
+            execute_line(b"COMPACT NOW", &mut session);
+            execute_line(b"INFO", &mut session);
+        });
+        let replies = String::from_utf8(captured.bytes).unwrap();
+        let field = BIG.replace(' ', "\\s").len() + 1;
+        assert!(replies.starts_with(&format!("{}\nERR COMPACT takes no arguments or VALUES\n", field)), "{}", replies);
+        assert!(replies.contains("value_log_generation:1\nvalue_log_keys:1\n"), "{}", replies);
+    }
+}
@@ -0,0 +1,145 @@
+// =====================================================================
+// File: webhook/config.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Webhook definitions, read from the file named by `KVSTORE_WEBHOOKS`
+//   in the style of the ACL file, one per line:
+//
+//     hook <url> <key pattern> [<events>]
+//
+//   `<url>` is an `http://` URL. The key pattern uses `*` and `?` like
+//   ACL patterns. `<events>` is a comma-separated list of `set`, `del` and
+//   `expire` (or `all`, the default). Blank lines and `#` comments are
+//   ignored.
+// =====================================================================
+
+use std::io;
+
+use crate::{glob_match, Fs};
+
+/// A kind of key change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// `SET`, `MSET` or a committed transaction wrote the key.
+    Set,
+
+    /// `DEL` removed the key.
+    Del,
+
+    /// The key's TTL ran out and it was reclaimed.
+    Expire,
+}
+
+
+impl EventKind {
+    /// Every kind, for hooks that want `all`.
+    pub const ALL: [EventKind; 3] = [EventKind::Set, EventKind::Del, EventKind::Expire];
+
+
+    /// Parse an event name (`set`, `del`, `expire`).
+    pub fn from_name(name: &str) -> Option<EventKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "set" => Some(EventKind::Set),
+            "del" => Some(EventKind::Del),
+            "expire" => Some(EventKind::Expire),
+            _ => None,
+        }
+    }
+
+
+    /// Name used in the file and in the JSON payload.
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Set => "set",
+            EventKind::Del => "del",
+            EventKind::Expire => "expire",
+        }
+    }
+}
+
+
+/// One webhook: where to POST, and for which keys and events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    /// `http://` URL notifications are POSTed to.
+    pub url: String,
+
+    /// Key pattern (`*` and `?` wildcards).
+    pub pattern: String,
+
+    /// Events the hook wants.
+    pub events: Vec<EventKind>,
+}
+
+
+impl Webhook {
+    /// Whether a `kind` change of `key` goes to this hook.
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::{parse_webhooks, EventKind};
+    /// let hooks = parse_webhooks("hook http://hooks.local/kv user:* set,expire").unwrap();
+    /// assert!(hooks[0].wants(EventKind::Set, "user:1"));
+    /// assert!(!hooks[0].wants(EventKind::Del, "user:1"));
+    /// assert!(!hooks[0].wants(EventKind::Set, "order:1"));
+    /// ```
+    pub fn wants(&self, kind: EventKind, key: &str) -> bool {
+        self.events.contains(&kind) && glob_match(&self.pattern, key)
+    }
+}
+
+
+/// Parse a webhook file.
+///
+/// # Returns
+/// `Err(message)` naming the first bad line.
+pub fn parse_webhooks(text: &str) -> Result<Vec<Webhook>, String> {
+    let mut hooks = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        hooks.push(parse_hook(line).map_err(|e| format!("line {}: {}", i + 1, e))?);
+    }
+    Ok(hooks)
+}
+
+
+/// Read and parse the webhook file at `path` through `fs`.
+///
+/// # Returns
+/// `Err` of kind `InvalidData` if the file doesn't parse.
+pub fn load_webhooks(fs: &dyn Fs, path: &str) -> io::Result<Vec<Webhook>> {
+    let bytes = fs.read(path)?;
+    parse_webhooks(&String::from_utf8_lossy(&bytes)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+
+/// Parse one `hook <url> <key pattern> [<events>]` line.
+fn parse_hook(line: &str) -> Result<Webhook, String> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let (url, pattern, events) = match parts.as_slice() {
+        ["hook", url, pattern] => (*url, *pattern, "all"),
+        ["hook", url, pattern, events] => (*url, *pattern, *events),
+        _ => return Err("expected hook <url> <key pattern> [<events>]".to_string()),
+    };
+    crate::http::split_url(url)?;
+
+    let mut wanted = Vec::new();
+    for name in events.split(',') {
+        if name.eq_ignore_ascii_case("all") {
+            wanted = EventKind::ALL.to_vec();
+            continue;
+        }
+        let kind = EventKind::from_name(name).ok_or_else(|| format!("unknown event '{}'", name))?;
+        if !wanted.contains(&kind) {
+            wanted.push(kind);
+        }
+    }
+    Ok(Webhook { url: url.to_string(), pattern: pattern.to_string(), events: wanted })
+}
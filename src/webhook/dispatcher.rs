@@ -0,0 +1,227 @@
+// =====================================================================
+// File: webhook/dispatcher.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Background delivery of webhook notifications.
+//
+//   Each hook has its own queue and delivery thread, so a slow or failing
+//   endpoint holds up only its own notifications, which stay in order.
+//   A delivery that fails to connect, times out, or gets a `5xx`, `408`
+//   or `429` is retried after a delay that doubles each time (up to
+//   `max_delay`), `max_attempts` times in all; any other status is final.
+//   A notification that finds its hook's queue full is dropped. All three
+//   outcomes are counted in [`WebhookStats`].
+// =====================================================================
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::config::{EventKind, Webhook};
+use crate::json_string;
+use crate::storage::{decode_record, ReplayOp};
+
+/// Notifications a hook may have waiting before new ones are dropped.
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// How deliveries are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries per notification, the first included.
+    pub max_attempts: u32,
+
+    /// Wait before the first retry; doubled for each one after.
+    pub base_delay: Duration,
+
+    /// Longest wait between tries.
+    pub max_delay: Duration,
+
+    /// Connect, send and response timeout of each try.
+    pub timeout: Duration,
+}
+
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+
+impl RetryPolicy {
+    /// Wait before try number `attempt` (2 for the first retry).
+    ///
+    /// # Example
+    /// ```
+    /// use kvstore::RetryPolicy;
+    /// use std::time::Duration;
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(policy.delay_before(2), Duration::from_millis(500));
+    /// assert_eq!(policy.delay_before(4), Duration::from_secs(2));
+    /// assert_eq!(policy.delay_before(20), Duration::from_secs(30));
+    /// ```
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(2).min(31);
+        self.base_delay.saturating_mul(1 << doublings).min(self.max_delay)
+    }
+}
+
+
+/// Delivery outcomes, shared with the delivery threads.
+#[derive(Debug, Default)]
+pub struct WebhookStats {
+    /// Notifications a hook accepted (`2xx`).
+    pub delivered: AtomicU64,
+
+    /// Notifications given up on after their last try.
+    pub failed: AtomicU64,
+
+    /// Notifications dropped because their hook's queue was full.
+    pub dropped: AtomicU64,
+}
+
+
+/// The configured hooks and their delivery threads.
+///
+/// Dropping it closes the queues; each thread finishes what is already
+/// queued and exits.
+pub struct Webhooks {
+    /// Hooks, in file order.
+    pub hooks: Vec<Webhook>,
+
+    /// Retry behavior of every hook.
+    pub policy: RetryPolicy,
+
+    /// Counts across all hooks (kept by [`Webhooks::restart`]).
+    pub stats: Arc<WebhookStats>,
+
+    queues: Vec<SyncSender<String>>,
+
+    threads: Vec<JoinHandle<()>>,
+}
+
+
+impl Webhooks {
+    /// Start one delivery thread per hook.
+    pub fn start(hooks: Vec<Webhook>, policy: RetryPolicy) -> Webhooks {
+        Webhooks::start_with(hooks, policy, Arc::default())
+    }
+
+
+    /// Replace the hooks (e.g. on reload), keeping the policy and counts.
+    /// Notifications already queued for the old hooks are still sent.
+    pub fn restart(&self, hooks: Vec<Webhook>) -> Webhooks {
+        Webhooks::start_with(hooks, self.policy, self.stats.clone())
+    }
+
+
+    fn start_with(hooks: Vec<Webhook>, policy: RetryPolicy, stats: Arc<WebhookStats>) -> Webhooks {
+        let (queues, threads) = hooks
+            .iter()
+            .map(|hook| {
+                let (tx, rx) = sync_channel(QUEUE_CAPACITY);
+                let (url, stats) = (hook.url.clone(), stats.clone());
+                (tx, std::thread::spawn(move || deliver_all(&url, &rx, policy, &stats)))
+            })
+            .unzip();
+        Webhooks { hooks, policy, stats, queues, threads }
+    }
+
+
+    /// Close the queues and wait up to `within` for what is queued to be
+    /// sent, e.g. before the process exits.
+    ///
+    /// # Returns
+    /// `true` if every delivery thread finished in time.
+    pub fn finish(self, within: Duration) -> bool {
+        let deadline = Instant::now() + within;
+        drop(self.queues);
+        while self.threads.iter().any(|t| !t.is_finished()) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+
+    /// Queue a notification for every hook that wants it.
+    pub fn notify(&self, kind: EventKind, key: &str, value: Option<&str>) {
+        let mut payload = None;
+        for (hook, queue) in self.hooks.iter().zip(&self.queues) {
+            if !hook.wants(kind, key) {
+                continue;
+            }
+            let body = payload.get_or_insert_with(|| encode_event(kind, key, value)).clone();
+            if queue.try_send(body).is_err() {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+
+    /// Queue the notifications for a log record just appended; a `DEL`
+    /// counts as `expire` when `expired` is set.
+    pub(crate) fn notify_record(&self, record: &str, expired: bool) {
+        for op in decode_record(0, record) {
+            match op {
+                ReplayOp::Set(key, value, _) => self.notify(EventKind::Set, &key, Some(&String::from_utf8_lossy(&value))),
+                ReplayOp::Del(key) if expired => self.notify(EventKind::Expire, &key, None),
+                ReplayOp::Del(key) => self.notify(EventKind::Del, &key, None),
+                // Sessions publish the value itself, never a value log pointer
+                ReplayOp::SetRef(..) | ReplayOp::ExpireAt(..) | ReplayOp::Persist(_) => {}
+            }
+        }
+    }
+}
+
+
+/// The JSON body of one notification.
+fn encode_event(kind: EventKind, key: &str, value: Option<&str>) -> String {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    let value = value.map_or(String::new(), |v| format!(r#","value":{}"#, json_string(v)));
+    format!(r#"{{"event":"{}","key":{}{},"ts":{}}}"#, kind.name(), json_string(key), value, ts)
+}
+
+
+/// Deliver a hook's notifications in order until its queue closes.
+fn deliver_all(url: &str, queue: &Receiver<String>, policy: RetryPolicy, stats: &WebhookStats) {
+    let headers = [("Content-Type", "application/json"), ("User-Agent", "kvstore-webhooks")];
+    for body in queue {
+        let mut attempt = 1;
+        loop {
+            let outcome = crate::http::post(url, &headers, body.as_bytes(), policy.timeout);
+            let retry = match &outcome {
+                Ok(status) if (200..300).contains(status) => {
+                    stats.delivered.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                Ok(status) => *status >= 500 || *status == 408 || *status == 429,
+                Err(_) => true,
+            };
+            if !retry || attempt >= policy.max_attempts {
+                let reason = match outcome {
+                    Ok(status) => format!("status {}", status),
+                    Err(e) => e.to_string(),
+                };
+                eprintln!("webhook: gave up on {} after {} tries: {}", url, attempt, reason);
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+            attempt += 1;
+            std::thread::sleep(policy.delay_before(attempt));
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// =====================================================================
+// File: webhook/mod.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+//! The `webhook` module POSTs a JSON notification to configured URLs
+//! when matching keys are set, deleted or expire.
+//!
+//! Structure:
+//! - `config.rs`     : [`Webhook`] (URL, key pattern, events) and the
+//!   file they are read from (`KVSTORE_WEBHOOKS`).
+//! - `dispatcher.rs` : [`Webhooks`], which queues notifications for one
+//!   delivery thread per hook and retries failures with backoff.
+//! - `tests.rs`      : Unit tests for parsing, matching and delivery.
+//!
+//! A notification is sent once the change is in the log, as
+//!
+//!   {"event":"set","key":"user:1","value":"ann","ts":1760450000123}
+//!
+//! (`del` and `expire` events carry no `value`). Each hook gets its
+//! notifications in commit order. A committed transaction or `MSET`
+//! sends one per key.
+// =====================================================================
+
+pub mod config;
+pub mod dispatcher;
+
+pub use self::config::{load_webhooks, parse_webhooks, EventKind, Webhook};
+pub use self::dispatcher::{RetryPolicy, WebhookStats, Webhooks};
+
+#[cfg(test)]
+pub mod tests;
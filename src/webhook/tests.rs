@@ -0,0 +1,164 @@
+// =====================================================================
+// File: webhook/tests.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Unit tests for webhook files, matching, and delivery with retries.
+//
+// Notes:
+//   * Only compiled when running `cargo test`.
+//   * Does not affect release builds.
+// =====================================================================
+
+
+// =====================================================================
+// Config Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod config_tests {
+    use crate::webhook::*;
+
+    #[test]
+    fn webhook_files_parse() {
+        let hooks = parse_webhooks(concat!(
+            "# audit everything\n",
+            "hook http://audit.local:9000/events *\n",
+            "\n",
+            "hook http://cache.local/purge session:* del,expire,DEL\n",
+        ))
+        .unwrap();
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks[0].events, EventKind::ALL.to_vec());
+        assert_eq!(hooks[1], Webhook {
+            url: "http://cache.local/purge".into(),
+            pattern: "session:*".into(),
+            events: vec![EventKind::Del, EventKind::Expire],
+        });
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        for (text, error) in [
+            ("hook http://a/ *\nhook http://b/", "line 2: expected hook"),
+            ("hook https://a/ *", "line 1: https://a/ is not an http:// URL"),
+            ("hook http://a/ * set,touch", "line 1: unknown event 'touch'"),
+            ("url http://a/ *", "line 1: expected hook"),
+        ] {
+            let got = parse_webhooks(text).unwrap_err();
+            assert!(got.starts_with(error), "{}: {}", text, got);
+        }
+    }
+}
+
+
+// =====================================================================
+// Delivery Unit Tests
+// =====================================================================
+#[cfg(test)]
+mod delivery_tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::webhook::*;
+    use crate::{execute_line, MemFs, Session};
+
+    /// An endpoint answering with `statuses` in turn (then `200`), and
+    /// handing over each request body it gets.
+    fn endpoint(statuses: Vec<u16>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut statuses = statuses.into_iter();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let status = statuses.next().unwrap_or(200);
+                write!(stream, "HTTP/1.1 {} X\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+                tx.send(String::from_utf8(body).unwrap()).unwrap();
+            }
+        });
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    fn quick() -> RetryPolicy {
+        RetryPolicy { max_attempts: 3, base_delay: Duration::from_millis(5), max_delay: Duration::from_millis(20), ..RetryPolicy::default() }
+    }
+
+    /// Body with its `ts` cut off, for comparing.
+    fn strip_ts(body: String) -> String {
+        body[..body.find(r#","ts":"#).unwrap()].to_string()
+    }
+
+    #[test]
+    fn matching_changes_are_posted_in_order() {
+        let (url, bodies) = endpoint(Vec::new());
+        let hooks = parse_webhooks(&format!("hook {} user:* set,expire", url)).unwrap();
+        let mut session = Session::new();
+        session.fs = Arc::new(MemFs::new());
+        session.webhooks = Some(Webhooks::start(hooks, quick()));
+
+        execute_line(b"MSET user:1 \"ann\\nlee\" order:1 x", &mut session);
+        execute_line(b"DEL user:1", &mut session);
+        execute_line(b"SET user:2 bo", &mut session);
+        execute_line(b"EXPIRE user:2 1", &mut session);
+        std::thread::sleep(Duration::from_millis(5));
+        session.expire_tick();
+
+        let got: Vec<String> = (0..3).map(|_| strip_ts(bodies.recv_timeout(Duration::from_secs(5)).unwrap())).collect();
+        assert_eq!(got, vec![
+            r#"{"event":"set","key":"user:1","value":"ann\nlee""#,
+            r#"{"event":"set","key":"user:2","value":"bo""#,
+            r#"{"event":"expire","key":"user:2""#,
+        ]);
+        assert!(session.webhooks.take().unwrap().finish(Duration::from_secs(5)));
+        assert!(bodies.try_recv().is_err());
+    }
+
+    #[test]
+    fn failures_are_retried_then_given_up() {
+        // 503 twice, then 200; then 500 three times in a row
+        let (url, bodies) = endpoint(vec![503, 503, 200, 500, 500, 500]);
+        let hooks = vec![Webhook { url, pattern: "*".into(), events: EventKind::ALL.to_vec() }];
+        let webhooks = Webhooks::start(hooks, quick());
+        webhooks.notify(EventKind::Set, "a", Some("1"));
+        webhooks.notify(EventKind::Del, "a", None);
+        let stats = webhooks.stats.clone();
+        assert!(webhooks.finish(Duration::from_secs(5)));
+
+        assert_eq!(bodies.try_iter().count(), 6);
+        assert_eq!(stats.delivered.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.failed.load(Ordering::Relaxed), 1);
+
+        // A 4xx is final, and a dead endpoint fails after every try
+        let (url, bodies) = endpoint(vec![404]);
+        let dead = format!("http://{}/", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let hooks = parse_webhooks(&format!("hook {} *\nhook {} *", url, dead)).unwrap();
+        let webhooks = Webhooks::start(hooks, quick());
+        webhooks.notify(EventKind::Set, "b", Some("2"));
+        let stats = webhooks.stats.clone();
+        assert!(webhooks.finish(Duration::from_secs(5)));
+        assert_eq!(bodies.try_iter().count(), 1);
+        assert_eq!(stats.failed.load(Ordering::Relaxed), 2);
+    }
+}
@@ -24,10 +24,19 @@
 #[allow(unused_imports)]
 use kvstore::{BTreeIndex, append_write, replay_log};
 
+/// Helper - path of a test log in the temp directory, so runs leave
+/// nothing in the working tree.
+fn test_path(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("kvstore_integration_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+
 /// Helper - create a fresh in-memory tree and clean log file
 fn setup() -> BTreeIndex {
     // Clean test log file
-    let test_file = "integration_test.db";
+    let test_file = test_path("integration_test.db");
     std::fs::write(test_file, "").unwrap();
     BTreeIndex::new(2)
 }
@@ -42,7 +51,7 @@ fn setup_file(file: &str) {
 #[test]
 fn test_set_and_get_persisted() {
     let mut tree = setup();
-    let file = "integration_test.db";
+    let file = &test_path("integration_test.db");
 
     // SET dog bark
     append_write(file, "SET dog bark").unwrap();
@@ -72,7 +81,7 @@ fn test_set_and_get_persisted() {
 
 #[test]
 fn test_overwrite_persists() {
-    let file = "integration_overwrite.db";
+    let file = &test_path("integration_overwrite.db");
     setup_file(file);
 
     append_write(file, "SET dog bark").unwrap();
@@ -93,7 +102,7 @@ fn test_overwrite_persists() {
 
 #[test]
 fn test_nonexistent_get() {
-    let file = "integration_missing.db";
+    let file = &test_path("integration_missing.db");
     setup_file(file);
 
     append_write(file, "SET cat meow").unwrap();
@@ -113,7 +122,7 @@ fn test_nonexistent_get() {
 
 #[test]
 fn test_case_insensitive_commands() {
-    let file = "integration_case.db";
+    let file = &test_path("integration_case.db");
     setup_file(file);
 
     // Mixed casing in commands, but we'll normalize to uppercase
@@ -146,7 +155,7 @@ fn test_case_insensitive_commands() {
 
 #[test]
 fn test_delete_persists() {
-    let file = "integration_delete.db";
+    let file = &test_path("integration_delete.db");
     setup_file(file);
 
     // Write a SET and a DEL to the log
@@ -162,7 +171,7 @@ fn test_delete_persists() {
             continue;
         }
         match parts[0] {
-            "SET" if parts.len() == 3 => tree.insert(parts[1].into(), parts[2].into()),
+            "SET" if parts.len() == 3 => { tree.insert(parts[1].into(), parts[2].into()); },
             "DEL" if parts.len() == 2 => { tree.delete(parts[1]); },
             _ => {}
         }
@@ -174,7 +183,7 @@ fn test_delete_persists() {
 
 #[test]
 fn test_ttl_does_not_persist_across_restart() {
-    let file = "integration_ttl_persist.db";
+    let file = &test_path("integration_ttl_persist.db");
     setup_file(file);
 
     append_write(file, "SET temp 123").unwrap();
@@ -196,7 +205,7 @@ fn test_ttl_does_not_persist_across_restart() {
 
 #[test]
 fn test_transaction_commit_persists() {
-    let file = "integration_commit.db";
+    let file = &test_path("integration_commit.db");
     setup_file(file);
 
     // Simulate a user session that begins, sets, commits
@@ -210,7 +219,7 @@ fn test_transaction_commit_persists() {
     for line in records {
         let parts: Vec<&str> = line.split_whitespace().collect();
         match parts.as_slice() {
-            ["SET", key, val] => tree.insert((*key).into(), (*val).into()),
+            ["SET", key, val] => { tree.insert((*key).into(), (*val).into()); },
             ["DEL", key] => { tree.delete(key); },
             _ => {} // BEGIN/COMMIT lines safely ignored
         }
@@ -221,7 +230,7 @@ fn test_transaction_commit_persists() {
 
 #[test]
 fn test_mset_replay_correctly_restores_last_values() {
-    let file = "integration_mset.db";
+    let file = &test_path("integration_mset.db");
     // Force delete any stale file
     std::fs::remove_file(file).ok();
     setup_file(file);
@@ -251,8 +260,6 @@ fn test_mset_replay_correctly_restores_last_values() {
             }
         }
     }
-    // Used for debugging dups in inserts
-    //tree.deduplicate();
     println!("=== BTree structure after replay ===");
     tree.debug_dump();
 
@@ -263,7 +270,7 @@ fn test_mset_replay_correctly_restores_last_values() {
 
 #[test]
 fn test_range_persists_ordered_keys() {
-    let file = "integration_range.db";
+    let file = &test_path("integration_range.db");
     setup_file(file);
 
     append_write(file, "SET cat meow").unwrap();
@@ -286,7 +293,7 @@ fn test_range_persists_ordered_keys() {
 
 #[test]
 fn test_delete_then_set_sequence_persists_final_value() {
-    let file = "integration_delset.db";
+    let file = &test_path("integration_delset.db");
     setup_file(file);
 
     append_write(file, "SET frog ribbit").unwrap();
@@ -298,7 +305,7 @@ fn test_delete_then_set_sequence_persists_final_value() {
     for line in records {
         let parts: Vec<&str> = line.split_whitespace().collect();
         match parts.as_slice() {
-            ["SET", key, val] => tree.insert((*key).into(), (*val).into()),
+            ["SET", key, val] => { tree.insert((*key).into(), (*val).into()); },
             ["DEL", key] => { tree.delete(key); },
             _ => {}
         }
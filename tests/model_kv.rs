@@ -0,0 +1,343 @@
+// =====================================================================
+// File: model_kv.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 14, 2026
+//
+// Description:
+//   Property-based model tests. Random command sequences are run
+//   through the REPL command path (`execute_line`) and, in parallel,
+//   through a reference model built from a `BTreeMap` of values, a set
+//   of keys with a TTL and an optional transaction buffer. Every
+//   command's reply must be the one the model predicts, so the store is
+//   only judged by what a client sees: GET, EXISTS, TTL, RANGE and the
+//   acknowledgements of the writes.
+//
+//   Sequences include simulated restarts: the session is dropped
+//   (cleanly, or with a power loss that throws away whatever was not
+//   synced) and rebuilt by replaying the log, which the model mirrors by
+//   forgetting staged writes. TTLs are logged as deadlines far in the
+//   future, so they survive it. The log lives on a `MemFs`, so runs
+//   touch no files.
+//
+//   Cases come from `proptest`, which shrinks a failing sequence to a
+//   short one and records it in `model_kv.proptest-regressions` next to
+//   this file, so it is replayed first on later runs.
+// =====================================================================
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use proptest::prelude::*;
+
+use kvstore::{capture_replies, execute_line, load_data, LruCache, MemFs, Session};
+
+/// Cases generated per run.
+const CASES: u32 = 96;
+
+/// Most commands per case.
+const STEPS: usize = 80;
+
+/// Keys commands pick from; one needs quoting.
+const KEYS: [&str; 5] = ["a", "b", "k1", "zeta", "sp ace"];
+
+/// Values commands pick from; `""` is the empty value.
+const VALUES: [&str; 5] = ["1", "22", "two words", "x\\\\y", ""];
+
+/// Stands for any positive `TTL` reply, since the milliseconds left
+/// depend on the clock.
+const SOME_TTL: &str = "<ttl>";
+
+
+/// One generated command.
+#[derive(Debug, Clone)]
+enum Op {
+    Set(usize, usize),
+    MSet(Vec<(usize, usize)>),
+    Del(usize),
+    Expire(usize, i64),
+    Persist(usize),
+    Range(usize, usize),
+    Get(usize),
+    Exists(usize),
+    Ttl(usize),
+    Begin,
+    Commit,
+    Abort,
+    Compact,
+    Restart { crash: bool },
+}
+
+
+/// Commands in roughly the mix a client sends: mostly writes and reads,
+/// with transactions, compactions and restarts among them.
+fn op_strategy() -> impl Strategy<Value = Op> {
+    let key = || 0..KEYS.len();
+    let value = || 0..VALUES.len();
+    // An index past the last key is the empty (open) bound
+    let bound = || 0..=KEYS.len();
+    prop_oneof![
+        6 => (key(), value()).prop_map(|(k, v)| Op::Set(k, v)),
+        2 => prop::collection::vec((key(), value()), 1..4).prop_map(Op::MSet),
+        2 => key().prop_map(Op::Del),
+        1 => (key(), prop::sample::select(vec![1_000_000, 0, -5])).prop_map(|(k, ms)| Op::Expire(k, ms)),
+        1 => key().prop_map(Op::Persist),
+        2 => (bound(), bound()).prop_map(|(s, e)| Op::Range(s, e)),
+        3 => key().prop_map(Op::Get),
+        1 => key().prop_map(Op::Exists),
+        1 => key().prop_map(Op::Ttl),
+        1 => Just(Op::Begin),
+        1 => Just(Op::Commit),
+        1 => Just(Op::Abort),
+        1 => Just(Op::Compact),
+        2 => any::<bool>().prop_map(|crash| Op::Restart { crash }),
+    ]
+}
+
+
+impl Op {
+    /// REPL line for this command (`None` for a restart).
+    fn line(&self) -> Option<String> {
+        let q = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\"));
+        let bound = |i: usize| KEYS.get(i).map_or("\"\"".to_string(), |k| q(k));
+        Some(match self {
+            Op::Set(k, v) => format!("SET {} {}", q(KEYS[*k]), q(VALUES[*v])),
+            Op::MSet(pairs) => {
+                let args: Vec<String> = pairs.iter().map(|(k, v)| format!("{} {}", q(KEYS[*k]), q(VALUES[*v]))).collect();
+                format!("MSET {}", args.join(" "))
+            }
+            Op::Del(k) => format!("DEL {}", q(KEYS[*k])),
+            Op::Expire(k, ms) => format!("EXPIRE {} {}", q(KEYS[*k]), ms),
+            Op::Persist(k) => format!("PERSIST {}", q(KEYS[*k])),
+            Op::Range(s, e) => format!("RANGE {} {}", bound(*s), bound(*e)),
+            Op::Get(k) => format!("GET {}", q(KEYS[*k])),
+            Op::Exists(k) => format!("EXISTS {}", q(KEYS[*k])),
+            Op::Ttl(k) => format!("TTL {}", q(KEYS[*k])),
+            Op::Begin => "BEGIN".to_string(),
+            Op::Commit => "COMMIT".to_string(),
+            Op::Abort => "ABORT".to_string(),
+            Op::Compact => "COMPACT".to_string(),
+            Op::Restart { .. } => return None,
+        })
+    }
+}
+
+
+/// Reference behavior the session must match.
+#[derive(Default)]
+struct Model {
+    values: BTreeMap<String, String>,
+    ttl: BTreeSet<String>,
+    pending: Option<Vec<(String, String)>>,
+    /// TTL changes staged by `EXPIRE` and `PERSIST` inside the transaction.
+    staged_ttl: BTreeMap<String, bool>,
+}
+
+
+impl Model {
+    /// The value a read sees: the transaction's latest staged write, else
+    /// the committed one.
+    fn visible(&self, key: &str) -> Option<&str> {
+        let staged = self.pending.iter().flatten().rev().find(|(k, _)| k == key);
+        staged.map(|(_, v)| v.as_str()).or_else(|| self.values.get(key).map(String::as_str))
+    }
+
+    /// Reply lines the store must answer `op` with, before the model
+    /// applies it. `compacting` is whether an earlier pass still runs.
+    fn reply(&self, op: &Op, compacting: bool) -> Vec<String> {
+        let flag = |b: bool| if b { "1" } else { "0" }.to_string();
+        let line = |text: &str| vec![text.to_string()];
+        match op {
+            Op::Set(..) | Op::MSet(_) => line("OK"),
+            // DEL only sees committed keys
+            Op::Del(k) => vec![flag(self.values.contains_key(KEYS[*k]))],
+            // A deadline that is not in the future only clears the TTL
+            Op::Expire(k, ms) => vec![flag(self.visible(KEYS[*k]).is_some() && *ms > 0)],
+            Op::Persist(k) => {
+                let key = KEYS[*k];
+                let had_ttl = match self.pending {
+                    // A TTL staged here, or a committed one not cleared here yet
+                    Some(_) => self.staged_ttl.get(key) == Some(&true) || (self.ttl.contains(key) && self.staged_ttl.get(key) != Some(&false)),
+                    None => self.ttl.contains(key),
+                };
+                vec![flag(self.visible(key).is_some() && had_ttl)]
+            }
+            Op::Range(s, e) => {
+                let mut keys = self.range(KEYS.get(*s).copied(), KEYS.get(*e).copied());
+                keys.push("END".to_string());
+                keys
+            }
+            Op::Get(k) => line(self.visible(KEYS[*k]).unwrap_or("nil")),
+            Op::Exists(k) => vec![flag(self.visible(KEYS[*k]).is_some())],
+            // Only committed keys have a TTL; staged ones have none yet
+            Op::Ttl(k) => match (self.values.contains_key(KEYS[*k]), self.visible(KEYS[*k]).is_some()) {
+                (true, _) if self.ttl.contains(KEYS[*k]) => line(SOME_TTL),
+                (_, true) => line("-1"),
+                _ => line("-2"),
+            },
+            // BEGIN and ABORT succeed silently
+            Op::Begin if self.pending.is_some() => line("ERR transaction already active"),
+            Op::Begin => Vec::new(),
+            Op::Commit | Op::Abort if self.pending.is_none() => line("ERR no active transaction"),
+            Op::Commit => line("OK"),
+            Op::Abort => Vec::new(),
+            Op::Compact if compacting => line("ERR compaction already running"),
+            Op::Compact => line("OK"),
+            Op::Restart { .. } => Vec::new(),
+        }
+    }
+
+    fn write(&mut self, key: &str, value: &str) {
+        match &mut self.pending {
+            Some(pending) => pending.push((key.to_string(), value.to_string())),
+            None => {
+                // SET keeps an existing TTL, like the store does
+                self.values.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match op {
+            Op::Set(k, v) => self.write(KEYS[*k], VALUES[*v]),
+            Op::MSet(pairs) => pairs.iter().for_each(|(k, v)| self.write(KEYS[*k], VALUES[*v])),
+            // DEL is not staged by transactions
+            Op::Del(k) => {
+                if self.values.remove(KEYS[*k]).is_some() && self.staged_ttl.get(KEYS[*k]) == Some(&true) {
+                    self.staged_ttl.remove(KEYS[*k]);
+                }
+                self.ttl.remove(KEYS[*k]);
+            }
+            // Inside a transaction EXPIRE also sees staged keys, and waits for COMMIT
+            Op::Expire(k, ms) if self.pending.is_some() => {
+                let staged = self.pending.iter().flatten().any(|(key, _)| key == KEYS[*k]);
+                if staged || self.values.contains_key(KEYS[*k]) {
+                    self.staged_ttl.insert(KEYS[*k].to_string(), *ms > 0);
+                }
+            }
+            Op::Expire(k, ms) if self.values.contains_key(KEYS[*k]) => {
+                if *ms > 0 {
+                    self.ttl.insert(KEYS[*k].to_string());
+                } else {
+                    self.ttl.remove(KEYS[*k]);
+                }
+            }
+            // Inside a transaction PERSIST is staged like `EXPIRE <key> 0`
+            Op::Persist(k) if self.pending.is_some() => {
+                let staged = self.pending.iter().flatten().any(|(key, _)| key == KEYS[*k]);
+                if staged || self.values.contains_key(KEYS[*k]) {
+                    self.staged_ttl.insert(KEYS[*k].to_string(), false);
+                }
+            }
+            Op::Persist(k) => {
+                self.ttl.remove(KEYS[*k]);
+            }
+            Op::Begin if self.pending.is_none() => self.pending = Some(Vec::new()),
+            Op::Commit => {
+                for (k, v) in self.pending.take().unwrap_or_default() {
+                    self.values.insert(k, v);
+                }
+                for (k, set) in std::mem::take(&mut self.staged_ttl) {
+                    if set {
+                        self.ttl.insert(k);
+                    } else {
+                        self.ttl.remove(&k);
+                    }
+                }
+            }
+            Op::Abort => {
+                self.pending = None;
+                self.staged_ttl.clear();
+            }
+            // TTLs are logged as deadlines; staged writes are not persisted
+            Op::Restart { .. } => {
+                self.pending = None;
+                self.staged_ttl.clear();
+            }
+            _ => {}
+        }
+    }
+
+    fn range(&self, start: Option<&str>, end: Option<&str>) -> Vec<String> {
+        self.values
+            .keys()
+            .filter(|k| start.is_none_or(|s| k.as_str() >= s) && end.is_none_or(|e| k.as_str() <= e))
+            .cloned()
+            .collect()
+    }
+}
+
+
+/// Whether the store's reply lines are the ones the model expects.
+fn same_reply(got: &[&str], want: &[String]) -> bool {
+    got.len() == want.len()
+        && got.iter().zip(want).all(|(got, want)| match want.as_str() {
+            SOME_TTL => got.parse::<i64>().is_ok_and(|ms| ms > 0),
+            want => *got == want,
+        })
+}
+
+
+/// Session flavors every case may run under, each replaying the log
+/// kept on `fs`.
+fn open_session(kind: usize, fs: &Arc<MemFs>) -> Session {
+    let mut session = match kind {
+        0 => Session::ephemeral(),
+        1 => Session::with_memory_limit(2),
+        2 => Session::key_only(),
+        _ => {
+            let mut session = Session::ephemeral();
+            session.cache = Some(LruCache::new(2));
+            session
+        }
+    };
+    session.fs = fs.clone();
+    session.data_file = "model.db".to_string();
+    load_data(&mut session, "model.db");
+    session
+}
+
+
+/// Run one generated case against a fresh log.
+fn run_case(kind: usize, ops: &[Op]) -> Result<(), String> {
+    // Appends are durable at once, as in the default `all` sync mode
+    let fs = Arc::new(MemFs::new());
+    let mut session = open_session(kind, &fs);
+    let mut model = Model::default();
+
+    for (step, op) in ops.iter().enumerate() {
+        let failed = |what: String| format!("step {} ({:?}, session kind {}): {}", step, op, kind, what);
+        match op.line() {
+            Some(line) => {
+                let want = model.reply(op, session.compactor.is_running());
+                let (_, captured) = capture_replies(usize::MAX, || execute_line(line.as_bytes(), &mut session));
+                let replies = String::from_utf8_lossy(&captured.bytes).into_owned();
+                let got: Vec<&str> = replies.lines().collect();
+                if !same_reply(&got, &want) {
+                    return Err(failed(format!("{} replied {:?}, model expects {:?}", line, got, want)));
+                }
+                session.tick().map_err(|e| failed(e.to_string()))?;
+            }
+            None => {
+                let Op::Restart { crash } = op else { unreachable!() };
+                drop(session);
+                if *crash {
+                    fs.crash();
+                }
+                session = open_session(kind, &fs);
+            }
+        }
+        model.apply(op);
+    }
+    Ok(())
+}
+
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn test_random_commands_match_reference_model(kind in 0..4usize, ops in prop::collection::vec(op_strategy(), 1..=STEPS)) {
+        run_case(kind, &ops).map_err(TestCaseError::fail)?;
+    }
+}
@@ -0,0 +1,56 @@
+// =====================================================================
+// File: otel_embedder.rs
+// Author: Bob Jack
+// Course: CSCE 5350: Fundamentals of Database Systems
+// Final Project Part 2
+// Date: Oct. 15, 2026
+//
+// Description:
+//   Tracing as an application embedding kvstore sees it: the application
+//   installs its own tracer provider and propagator (never calling
+//   `telemetry::init`), and kvstore's spans must land in that provider,
+//   nested under the application's current span or the remote parent
+//   its propagator extracts.
+//
+// Notes:
+//   * Needs `--features otel`. A test binary of its own, since the
+//     global provider is process-wide.
+// =====================================================================
+#![cfg(feature = "otel")]
+
+use opentelemetry::trace::{SpanId, TraceId, Tracer};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+use kvstore::telemetry::{current_context, with_remote_parent};
+use kvstore::{execute_line, Session};
+
+#[test]
+fn spans_join_the_applications_provider_and_context() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+    global::set_tracer_provider(provider.clone());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let mut session = Session::ephemeral();
+    global::tracer("app").in_span("app.request", |_| {
+        assert!(current_context().is_some());
+        execute_line(b"SET a 1", &mut session);
+    });
+    let headers = vec![("traceparent".to_string(), "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string())];
+    with_remote_parent(&headers, || execute_line(b"GET a", &mut session));
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let request = spans.iter().find(|span| span.name == "app.request").unwrap();
+    let commands: Vec<_> = spans.iter().filter(|span| span.name == "kvstore.command").collect();
+    assert_eq!(commands.len(), 2);
+
+    assert_eq!(commands[0].span_context.trace_id(), request.span_context.trace_id());
+    assert_eq!(commands[0].parent_span_id, request.span_context.span_id());
+    assert!(commands[0].attributes.contains(&KeyValue::new("db.operation", "SET")));
+
+    assert_eq!(commands[1].span_context.trace_id(), TraceId::from(0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736));
+    assert_eq!(commands[1].parent_span_id, SpanId::from(0x00f0_67aa_0ba9_02b7));
+}